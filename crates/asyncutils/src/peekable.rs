@@ -67,6 +67,7 @@ impl<R: AsyncRead + Unpin> PeekableReader<R> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use futures::io::Cursor;
     use rstest::rstest;
 
     fn test_data() -> Vec<u8> {
@@ -74,4 +75,42 @@ mod tests {
           It needs to be long enough to test multiple peek() calls."
             .to_vec()
     }
+
+    #[rstest]
+    #[case::nothing(0)]
+    #[case::some(13)]
+    #[case::everything(1024)]
+    #[tokio::test]
+    async fn test_peek_then_read(#[case] limit: usize) {
+        let data = test_data();
+        let mut reader = PeekableReader::new(Cursor::new(data.clone()));
+        let peeked = reader.peek(limit).await.unwrap().to_vec();
+        assert_eq!(&data[..limit.min(data.len())], peeked);
+        assert_eq!(peeked, reader.head());
+        assert_eq!(data, reader.into_bytes().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_successive_peeks() {
+        let data = test_data();
+        let mut reader = PeekableReader::new(Cursor::new(data.clone()));
+        assert_eq!(b"Hello", reader.peek(5).await.unwrap());
+        // Shorter peeks come from what's already buffered.
+        assert_eq!(b"He", reader.peek(2).await.unwrap());
+        assert_eq!(b"Hello, world!", reader.peek(13).await.unwrap());
+
+        let mut output = Vec::new();
+        reader.into_reader().read_to_end(&mut output).await.unwrap();
+        assert_eq!(data, output);
+    }
+
+    #[tokio::test]
+    async fn test_copy_into() {
+        let data = test_data();
+        let mut reader = PeekableReader::new(Cursor::new(data.clone()));
+        reader.peek(20).await.unwrap();
+        let mut output = Cursor::new(Vec::new());
+        assert_eq!(data.len() as u64, reader.copy_into(&mut output).await.unwrap());
+        assert_eq!(data, output.into_inner());
+    }
 }
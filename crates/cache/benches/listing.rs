@@ -0,0 +1,116 @@
+//! Benchmarks of listing a large target's versions, grouped with their files.
+//!
+//! The library's biggest targets hold around 100,000 files. Listing their
+//! versions groups the rows of a `versions`/`files` join as they're fetched
+//! (see `group.rs`), converting each version once however many files it has;
+//! the `hash_map` benchmark groups the same target the way it used to be, by
+//! converting every row into a file and a version and keying them by
+//! (cloned) content hash. Before measuring time, each benchmark prints how
+//! many allocations one listing makes:
+//!
+//! ```sh
+//! cargo bench -p rawr-cache --bench listing
+//! ```
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use rawr_cache::{Database, Repository};
+use rawr_compress::Compression;
+use rawr_extract::models::Version;
+use rawr_extract::testing::Generator;
+use rawr_storage::file::{FileInfo, FileMeta, Processed};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use time::UtcDateTime;
+use tokio::runtime::Runtime;
+
+const TARGET: &str = "library";
+const VERSIONS: u64 = 25_000;
+const FILES_PER_VERSION: u64 = 4;
+
+/// Counts allocations, to report how many a listing makes.
+struct Counting;
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// A target of `VERSIONS` versions, each in `FILES_PER_VERSION` files.
+fn populate(runtime: &Runtime) -> (Database, Repository) {
+    let html = Generator::new(0xCAC4E).generate().html;
+    let template = rawr_extract::extract(&html).expect("generated works extract");
+    runtime.block_on(async {
+        let db = Database::connect_in_memory().await.expect("in-memory database");
+        let cache = Repository::from(&db).with_write_coalescing(1_000, Duration::from_secs(60));
+        for n in 0..VERSIONS {
+            let mut version = template.clone();
+            version.hash = format!("{n:064x}");
+            version.metadata.work_id = n / 2 + 1;
+            for copy in 0..FILES_PER_VERSION {
+                let path = format!("works/{n}/{copy}.html.gz");
+                let file: FileInfo<Processed> =
+                    FileMeta::new(TARGET, path, Compression::Gzip, 1_000, UtcDateTime::now())
+                        .with_file_hash(format!("{n}-{copy}"))
+                        .with_content_hash(&version.hash);
+                cache.upsert(&file, &version).await.expect("upsert");
+            }
+        }
+        cache.flush().await.expect("flush");
+        (db, cache)
+    })
+}
+
+/// Groups a target's files by version the way listing versions used to.
+async fn group_by_hash_map(cache: &Repository) -> Vec<(Version, Vec<FileInfo<Processed>>)> {
+    let mut map: HashMap<String, (Version, Vec<FileInfo<Processed>>)> = HashMap::new();
+    for (file, version) in cache.list_files_for_target(TARGET).await.expect("list files") {
+        map.entry(version.hash.clone()).or_insert_with(|| (version, Vec::new())).1.push(file);
+    }
+    map.into_values().collect()
+}
+
+/// Prints how many allocations (and bytes) one run of `f` makes.
+fn report_allocations<T>(name: &str, f: impl FnOnce() -> T) {
+    let (count, bytes) = (ALLOCATIONS.load(Ordering::Relaxed), ALLOCATED_BYTES.load(Ordering::Relaxed));
+    black_box(f());
+    let count = ALLOCATIONS.load(Ordering::Relaxed) - count;
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes;
+    let rows = VERSIONS * FILES_PER_VERSION;
+    println!(
+        "{name}: {count} allocations ({:.1} per row), {} MiB allocated",
+        count as f64 / rows as f64,
+        bytes >> 20
+    );
+}
+
+fn bench_group_by_version(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("runtime");
+    let (_db, cache) = populate(&runtime);
+    let streaming = || runtime.block_on(cache.list_versions_for_target(TARGET)).expect("list versions");
+    let hash_map = || runtime.block_on(group_by_hash_map(&cache));
+    assert_eq!(VERSIONS as usize, streaming().len());
+    report_allocations("group_by_version/streaming", streaming);
+    report_allocations("group_by_version/hash_map", hash_map);
+
+    let mut group = c.benchmark_group("group_by_version");
+    group.sample_size(10);
+    group.bench_function("streaming", |b| b.iter(streaming));
+    group.bench_function("hash_map", |b| b.iter(hash_map));
+    group.finish();
+}
+
+criterion_group!(benches, bench_group_by_version);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+-- The Postgres cache store holds the same file and version records as the
+-- SQLite cache (see `migrations/`), for the operations in `CacheStore`.
+
+-- Versions table: stores extracted metadata for each unique HTML content
+CREATE TABLE IF NOT EXISTS versions (
+    content_hash TEXT PRIMARY KEY NOT NULL,
+    content_crc32 BIGINT NOT NULL,
+    work_id BIGINT NOT NULL,
+    content_size BIGINT NOT NULL,
+    title TEXT NOT NULL,
+    authors TEXT NOT NULL,
+    fandoms TEXT NOT NULL,
+    series TEXT NOT NULL,
+    chapters_written BIGINT NOT NULL,
+    chapters_total BIGINT,
+    complete BOOLEAN NOT NULL DEFAULT FALSE,
+    words BIGINT NOT NULL,
+    summary TEXT,
+    rating TEXT,
+    warnings TEXT NOT NULL,
+    lang TEXT NOT NULL,
+    published_on BIGINT NOT NULL,
+    last_modified BIGINT NOT NULL,
+    tags TEXT NOT NULL,
+    extracted_at BIGINT NOT NULL,
+    encoding TEXT NOT NULL DEFAULT 'utf-8',
+    detected_lang TEXT,
+    detected_lang_iso TEXT,
+    detected_lang_confidence BIGINT
+);
+CREATE INDEX IF NOT EXISTS idx_versions_work_id ON versions(work_id);
+CREATE INDEX IF NOT EXISTS idx_versions_complete ON versions(complete);
+
+-- Files table: tracks physical files across all storage backends
+CREATE TABLE IF NOT EXISTS files (
+    target TEXT NOT NULL,
+    path TEXT NOT NULL,
+    compression TEXT NOT NULL,
+    file_size BIGINT NOT NULL,
+    file_hash TEXT NOT NULL,
+    content_hash TEXT NOT NULL REFERENCES versions(content_hash) ON DELETE CASCADE,
+    discovered_at BIGINT NOT NULL,
+    stale BOOLEAN NOT NULL DEFAULT FALSE,
+    verified_at BIGINT,
+    PRIMARY KEY (target, path)
+);
+CREATE INDEX IF NOT EXISTS idx_files_content_hash ON files(content_hash);
+CREATE INDEX IF NOT EXISTS idx_files_file_hash ON files(file_hash);
+CREATE INDEX IF NOT EXISTS idx_files_target ON files(target);
+
+-- Aliases table: maps the work IDs of deleted/re-uploaded works to the work ID
+-- they now live under.
+CREATE TABLE IF NOT EXISTS aliases (
+    work_id BIGINT PRIMARY KEY NOT NULL,
+    canonical_work_id BIGINT NOT NULL,
+    CHECK (work_id != canonical_work_id)
+);
+CREATE INDEX IF NOT EXISTS idx_aliases_canonical_work_id ON aliases(canonical_work_id);
@@ -0,0 +1,2 @@
+-- See `migrations/0014_versions_provenance_column.sql`.
+ALTER TABLE versions ADD COLUMN IF NOT EXISTS provenance TEXT NOT NULL DEFAULT '{}';
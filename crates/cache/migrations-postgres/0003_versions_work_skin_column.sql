@@ -0,0 +1,2 @@
+-- See `migrations/0021_versions_work_skin_column.sql`.
+ALTER TABLE versions ADD COLUMN IF NOT EXISTS work_skin TEXT;
@@ -0,0 +1,10 @@
+-- Replace the virtual "complete" column with a real, indexed one so that
+-- filtering on completeness doesn't have to evaluate every row. It's written
+-- on insert from `Chapters::is_complete()`, keeping a single definition of
+-- what "complete" means (posted chapters reached the planned total).
+ALTER TABLE versions DROP COLUMN complete;
+ALTER TABLE versions ADD COLUMN complete INT NOT NULL DEFAULT 0;
+UPDATE versions SET complete = (chapters_total IS NOT NULL AND chapters_written >= chapters_total);
+
+-- Index for organizing/filtering works-in-progress separately from completed works
+CREATE INDEX IF NOT EXISTS idx_versions_complete ON versions(complete);
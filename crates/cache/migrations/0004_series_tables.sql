@@ -0,0 +1,26 @@
+-- Series table: one row per AO3 series, regardless of how many works are in the library
+CREATE TABLE IF NOT EXISTS series (
+    series_id INT PRIMARY KEY NOT NULL, -- AO3 series ID
+    name TEXT NOT NULL                  -- Most recently seen series name
+);
+
+-- Series membership: which versions claim which position within a series
+CREATE TABLE IF NOT EXISTS series_versions (
+    series_id INT NOT NULL,     -- FK to series.series_id
+    content_hash TEXT NOT NULL, -- FK to versions.content_hash
+    position INT NOT NULL,      -- Position within the series (1-indexed)
+    PRIMARY KEY (series_id, content_hash),
+    FOREIGN KEY (series_id) REFERENCES series(series_id) ON DELETE CASCADE,
+    FOREIGN KEY (content_hash) REFERENCES versions(content_hash) ON DELETE CASCADE
+);
+
+-- Index for finding all series a version belongs to (cascading deletes)
+CREATE INDEX IF NOT EXISTS idx_series_versions_content_hash ON series_versions(content_hash);
+
+-- Populate from versions scanned before this migration existed
+INSERT OR IGNORE INTO series (series_id, name)
+SELECT json_extract(s.value, '$.id'), json_extract(s.value, '$.name')
+FROM versions v, json_each(v.series) s;
+INSERT OR IGNORE INTO series_versions (series_id, content_hash, position)
+SELECT json_extract(s.value, '$.id'), v.content_hash, json_extract(s.value, '$.pos')
+FROM versions v, json_each(v.series) s;
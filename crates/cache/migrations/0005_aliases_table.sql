@@ -0,0 +1,11 @@
+-- Aliases table: maps the work IDs of deleted/re-uploaded works to the work ID
+-- they now live under. Chains are flattened on insert: a canonical work ID is
+-- never itself an alias.
+CREATE TABLE IF NOT EXISTS aliases (
+    work_id INT PRIMARY KEY NOT NULL,   -- Old (aliased) AO3 work ID
+    canonical_work_id INT NOT NULL,     -- AO3 work ID the old one now refers to
+    CHECK (work_id != canonical_work_id)
+);
+
+-- Index for finding every alias of a canonical work ID
+CREATE INDEX IF NOT EXISTS idx_aliases_canonical_work_id ON aliases(canonical_work_id);
@@ -0,0 +1,15 @@
+-- Smart collections: saved filters whose membership is computed from the
+-- versions table on demand, rather than curated by hand
+CREATE TABLE IF NOT EXISTS smart_collections (
+    name TEXT PRIMARY KEY NOT NULL,
+    filter TEXT NOT NULL,   -- JSON-serialized Filter
+    refreshed_at INT        -- Unix timestamp; NULL if membership has never been computed
+);
+
+-- Membership as of the collection's last refresh
+CREATE TABLE IF NOT EXISTS smart_collection_works (
+    name TEXT NOT NULL,     -- FK to smart_collections.name
+    work_id INT NOT NULL,   -- AO3 work ID (not an FK; versions may come and go)
+    PRIMARY KEY (name, work_id),
+    FOREIGN KEY (name) REFERENCES smart_collections(name) ON DELETE CASCADE ON UPDATE CASCADE
+);
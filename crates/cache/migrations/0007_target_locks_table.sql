@@ -0,0 +1,10 @@
+-- Advisory locks preventing conflicting operations (e.g. a scan and an
+-- organize) from running against the same target concurrently
+CREATE TABLE IF NOT EXISTS target_locks (
+    target TEXT PRIMARY KEY NOT NULL,
+    operation TEXT NOT NULL,    -- Name of the operation holding the lock (e.g. "scan")
+    holder TEXT NOT NULL,       -- Human-readable description of the holding process
+    token TEXT NOT NULL,        -- Unique to each acquisition; only the holder knows it
+    acquired_at INT NOT NULL,   -- Unix timestamp
+    heartbeat_at INT NOT NULL   -- Unix timestamp; locks not refreshed for a while are stale
+);
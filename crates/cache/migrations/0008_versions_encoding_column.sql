@@ -0,0 +1,4 @@
+-- The encoding each version's HTML was detected as (see `rawr_extract::Encoding`),
+-- so that files which aren't UTF-8 can be found and re-encoded. Versions
+-- extracted before detection existed are assumed to be UTF-8.
+ALTER TABLE versions ADD COLUMN encoding TEXT NOT NULL DEFAULT 'utf-8';
@@ -0,0 +1,4 @@
+-- Files whose size or modification time in storage no longer match their
+-- record (as found by a metadata-only scan, which never reads contents), to
+-- be re-read by the next full scan. Replacing the record clears the flag.
+ALTER TABLE files ADD COLUMN stale INTEGER NOT NULL DEFAULT 0;
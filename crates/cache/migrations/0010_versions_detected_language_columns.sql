@@ -0,0 +1,6 @@
+-- The language each version's chapter text was detected to be written in
+-- (see `rawr_extract::detect_language`), alongside the declared `lang`. NULL
+-- when detection wasn't enabled, or couldn't tell.
+ALTER TABLE versions ADD COLUMN detected_lang TEXT;
+ALTER TABLE versions ADD COLUMN detected_lang_iso TEXT;
+ALTER TABLE versions ADD COLUMN detected_lang_confidence INTEGER;
@@ -0,0 +1,9 @@
+-- Advisory lease naming the one process expected to write to the cache (such
+-- as a daemon scanning in the background), so that others open it read-only
+CREATE TABLE IF NOT EXISTS writer_lease (
+    id INT PRIMARY KEY NOT NULL CHECK (id = 1), -- There's only ever one lease
+    holder TEXT NOT NULL,       -- Human-readable description of the holding process
+    token TEXT NOT NULL,        -- Unique to each acquisition; only the holder knows it
+    acquired_at INT NOT NULL,   -- Unix timestamp
+    heartbeat_at INT NOT NULL   -- Unix timestamp; leases not refreshed for a while are stale
+);
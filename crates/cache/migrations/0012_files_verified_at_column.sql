@@ -0,0 +1,5 @@
+-- When each file was last verified intact (re-read, and its hash found to
+-- match), so that periodic integrity checks can skip files verified recently.
+-- NULL for files never verified (or not since they last changed).
+ALTER TABLE files ADD COLUMN verified_at INT;
+CREATE INDEX IF NOT EXISTS idx_files_target_verified_at ON files(target, verified_at);
@@ -0,0 +1,9 @@
+-- Reading status table: works the user has marked as read, and what the best
+-- version of the work was at the time (to tell when a newer one arrives).
+-- Unlike everything else in the cache, this can't be rebuilt by scanning.
+CREATE TABLE IF NOT EXISTS reading_status (
+    work_id INT PRIMARY KEY NOT NULL,   -- Canonical AO3 work ID
+    read_at INT NOT NULL,               -- Unix timestamp
+    chapters_written INT NOT NULL,      -- Chapters posted in the version read
+    last_modified INT NOT NULL          -- Unix timestamp (midnight) of the version read
+);
@@ -0,0 +1,4 @@
+-- Where each version's metadata fields came from when they weren't extracted
+-- as-is (see `rawr_extract::Provenances`), as a JSON object of field names to
+-- provenance. Empty for versions extracted in full.
+ALTER TABLE versions ADD COLUMN provenance TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1,11 @@
+-- Overrides table: user corrections to the metadata of a (canonical) work,
+-- applied over every version extracted from it. Each column is NULL unless
+-- that field is overridden; lists are stored as JSON, like in `versions`.
+-- Like reading status, this can't be rebuilt by scanning.
+CREATE TABLE IF NOT EXISTS overrides (
+    work_id INT PRIMARY KEY NOT NULL,   -- Canonical AO3 work ID
+    title TEXT,
+    authors TEXT,                       -- JSON array of authors
+    fandoms TEXT,                       -- JSON array of fandoms
+    tags TEXT                           -- JSON array of tags
+);
@@ -0,0 +1,12 @@
+-- Scan runs table: scans that haven't completed, and how far each got, so that
+-- a scan interrupted (by a restart, say) can be resumed rather than started
+-- over. A run's row is deleted once it completes.
+CREATE TABLE IF NOT EXISTS scan_runs (
+    run_id TEXT PRIMARY KEY NOT NULL,
+    target TEXT NOT NULL,
+    prefix TEXT,                    -- Subdirectory of the target being scanned, if any
+    checkpoint TEXT,                -- Path of the last file listed that, along with every file listed before it, was scanned
+    scanned INT NOT NULL DEFAULT 0, -- Files scanned up to the checkpoint
+    started_at INT NOT NULL,        -- Unix timestamp
+    updated_at INT NOT NULL         -- Unix timestamp of the last checkpoint
+);
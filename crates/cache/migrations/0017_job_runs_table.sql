@@ -0,0 +1,9 @@
+-- Job runs table: when each scheduled maintenance job last ran, so that a
+-- scheduler that's restarted knows which jobs came due while it was down.
+-- There's one row per job, replaced each time the job starts.
+CREATE TABLE IF NOT EXISTS job_runs (
+    name TEXT PRIMARY KEY NOT NULL,
+    started_at INT NOT NULL, -- Unix timestamp
+    finished_at INT,         -- Unix timestamp; NULL while running, or if interrupted
+    error TEXT               -- Why the run failed, if it did
+);
@@ -0,0 +1,8 @@
+-- Tombstones table: (canonical) works the user deliberately deleted, so that
+-- scanning a target still holding a copy (a backup, say) doesn't add them
+-- back. Like overrides, this can't be rebuilt by scanning.
+CREATE TABLE IF NOT EXISTS tombstones (
+    work_id INT PRIMARY KEY NOT NULL, -- Canonical AO3 work ID
+    reason TEXT,                      -- Why the work was deleted, if the user said
+    deleted_at INT NOT NULL           -- Unix timestamp
+);
@@ -0,0 +1,20 @@
+-- Attributes tables: key/value data that applications embedding the cache
+-- keep about files and versions (sync state, IDs in other systems...),
+-- without needing columns of their own. They're deleted along with their
+-- file or version, and follow a file when it's renamed.
+CREATE TABLE IF NOT EXISTS file_attributes (
+    target TEXT NOT NULL,
+    path TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (target, path, key),
+    FOREIGN KEY (target, path) REFERENCES files(target, path) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS version_attributes (
+    content_hash TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (content_hash, key),
+    FOREIGN KEY (content_hash) REFERENCES versions(content_hash) ON DELETE CASCADE
+);
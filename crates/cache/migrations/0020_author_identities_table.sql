@@ -0,0 +1,13 @@
+-- Author identities table: maps pseudonyms (or whole accounts) of the same
+-- person to the author they're merged under. Authors are stored as display
+-- strings ("pseud (user)"), as in `versions.authors`, which is left as it was
+-- extracted. Chains are flattened on insert: a canonical author is never
+-- itself merged. Like aliases, this can't be rebuilt by scanning.
+CREATE TABLE IF NOT EXISTS author_identities (
+    author TEXT PRIMARY KEY NOT NULL,   -- Merged author
+    canonical_author TEXT NOT NULL,     -- Author it's merged under
+    CHECK (author != canonical_author)
+);
+
+-- Index for finding every author merged under a canonical author
+CREATE INDEX IF NOT EXISTS idx_author_identities_canonical_author ON author_identities(canonical_author);
@@ -0,0 +1,3 @@
+-- The work skin (CSS) embedded in each version's download, if it has one
+-- (see `rawr_extract::Metadata::work_skin`).
+ALTER TABLE versions ADD COLUMN work_skin TEXT;
@@ -0,0 +1,5 @@
+-- Words per posted chapter, for filtering and sorting works by how long their
+-- chapters are. Generated from `words` and `chapters_written`, so it's never
+-- out of date, and indexed so range filters don't evaluate every row.
+ALTER TABLE versions ADD COLUMN words_per_chapter INT GENERATED ALWAYS AS (words / max(chapters_written, 1)) VIRTUAL;
+CREATE INDEX IF NOT EXISTS idx_versions_words_per_chapter ON versions(words_per_chapter);
@@ -0,0 +1,15 @@
+-- Chunks table: the content-defined chunks each file stored on a chunked
+-- target (see `rawr_storage::backend::ChunkedBackend`) is made of, so that
+-- writing a file only has to upload the chunks the target doesn't have yet.
+-- Only a hint: a chunk is uploaded again if it's not here.
+CREATE TABLE IF NOT EXISTS chunks (
+    target TEXT NOT NULL,
+    path TEXT NOT NULL,
+    seq INT NOT NULL,     -- Position of the chunk in the file, from 0
+    hash TEXT NOT NULL,   -- BLAKE3 hash of the chunk
+    length INT NOT NULL,
+    PRIMARY KEY (target, path, seq)
+);
+
+-- Index for finding whether a target has a chunk
+CREATE INDEX IF NOT EXISTS idx_chunks_target_hash ON chunks(target, hash);
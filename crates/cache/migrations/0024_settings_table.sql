@@ -0,0 +1,9 @@
+-- Settings table: small preferences of applications using the cache (the
+-- last template used, the default target...), grouped by namespace so that
+-- applications don't step on each other's keys. Values are JSON.
+CREATE TABLE IF NOT EXISTS settings (
+    namespace TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (namespace, key)
+);
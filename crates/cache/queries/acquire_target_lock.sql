@@ -0,0 +1,10 @@
+INSERT INTO target_locks (target, operation, holder, token, acquired_at, heartbeat_at)
+VALUES (?, ?, ?, ?, ?, ?)
+ON CONFLICT (target) DO UPDATE SET
+    operation = excluded.operation,
+    holder = excluded.holder,
+    token = excluded.token,
+    acquired_at = excluded.acquired_at,
+    heartbeat_at = excluded.heartbeat_at
+WHERE target_locks.heartbeat_at < ?
+RETURNING token
@@ -0,0 +1,9 @@
+INSERT INTO writer_lease (id, holder, token, acquired_at, heartbeat_at)
+VALUES (1, ?, ?, ?, ?)
+ON CONFLICT (id) DO UPDATE SET
+    holder = excluded.holder,
+    token = excluded.token,
+    acquired_at = excluded.acquired_at,
+    heartbeat_at = excluded.heartbeat_at
+WHERE writer_lease.heartbeat_at < ?
+RETURNING token
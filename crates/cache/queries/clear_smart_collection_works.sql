@@ -0,0 +1,2 @@
+DELETE FROM smart_collection_works
+WHERE name = ?
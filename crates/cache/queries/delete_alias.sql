@@ -0,0 +1,2 @@
+DELETE FROM aliases
+WHERE work_id = ?
@@ -0,0 +1,2 @@
+DELETE FROM author_identities
+WHERE author = ?
@@ -0,0 +1,2 @@
+DELETE FROM chunks
+WHERE target = ? AND path = ?
@@ -0,0 +1,2 @@
+DELETE FROM file_attributes
+WHERE target = ? AND path = ? AND key = ?
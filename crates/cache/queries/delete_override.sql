@@ -0,0 +1 @@
+DELETE FROM overrides WHERE work_id = ?
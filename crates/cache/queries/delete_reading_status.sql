@@ -0,0 +1,2 @@
+DELETE FROM reading_status
+WHERE work_id = ?
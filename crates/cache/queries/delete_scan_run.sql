@@ -0,0 +1 @@
+DELETE FROM scan_runs WHERE run_id = ?
@@ -0,0 +1,2 @@
+DELETE FROM settings
+WHERE namespace = ? AND key = ?
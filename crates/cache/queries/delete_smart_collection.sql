@@ -0,0 +1,2 @@
+DELETE FROM smart_collections
+WHERE name = ?
@@ -0,0 +1,2 @@
+DELETE FROM target_locks
+WHERE target = ?
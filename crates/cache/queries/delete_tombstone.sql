@@ -0,0 +1,2 @@
+DELETE FROM tombstones
+WHERE work_id = ?
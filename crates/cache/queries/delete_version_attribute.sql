@@ -0,0 +1,2 @@
+DELETE FROM version_attributes
+WHERE content_hash = ? AND key = ?
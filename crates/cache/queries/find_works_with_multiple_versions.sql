@@ -1,5 +1,6 @@
-SELECT v.work_id, COUNT(*) as count
+SELECT COALESCE(a.canonical_work_id, v.work_id) AS id, COUNT(*) as count
 FROM versions v
-GROUP BY v.work_id
+LEFT JOIN aliases a ON a.work_id = v.work_id
+GROUP BY id
 HAVING count > 1
 ORDER BY count DESC
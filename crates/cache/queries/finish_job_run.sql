@@ -0,0 +1,3 @@
+UPDATE job_runs
+SET finished_at = ?, error = ?
+WHERE name = ? AND finished_at IS NULL
@@ -0,0 +1,3 @@
+SELECT work_id, title, authors, fandoms, tags
+FROM overrides
+WHERE work_id = ?
@@ -0,0 +1,3 @@
+SELECT work_id, read_at, chapters_written, last_modified
+FROM reading_status
+WHERE work_id = ?
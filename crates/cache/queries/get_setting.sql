@@ -0,0 +1,2 @@
+SELECT value FROM settings
+WHERE namespace = ? AND key = ?
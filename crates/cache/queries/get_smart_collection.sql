@@ -0,0 +1,3 @@
+SELECT name, filter, refreshed_at
+FROM smart_collections
+WHERE name = ?
@@ -0,0 +1,3 @@
+SELECT target, operation, holder, acquired_at, heartbeat_at
+FROM target_locks
+WHERE target = ?
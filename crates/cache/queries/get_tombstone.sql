@@ -0,0 +1,3 @@
+SELECT work_id, reason, deleted_at
+FROM tombstones
+WHERE work_id = ?
@@ -0,0 +1,3 @@
+SELECT holder, acquired_at, heartbeat_at
+FROM writer_lease
+WHERE id = 1
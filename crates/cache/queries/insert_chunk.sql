@@ -0,0 +1,2 @@
+INSERT INTO chunks (target, path, seq, hash, length)
+VALUES (?, ?, ?, ?, ?)
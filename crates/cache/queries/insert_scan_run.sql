@@ -0,0 +1,2 @@
+INSERT INTO scan_runs (run_id, target, prefix, checkpoint, scanned, started_at, updated_at)
+VALUES (?, ?, ?, NULL, 0, ?, ?)
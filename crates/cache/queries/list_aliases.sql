@@ -0,0 +1,4 @@
+SELECT work_id
+FROM aliases
+WHERE canonical_work_id = ?
+ORDER BY work_id
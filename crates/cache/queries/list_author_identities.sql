@@ -0,0 +1,3 @@
+SELECT author, canonical_author
+FROM author_identities
+ORDER BY canonical_author, author
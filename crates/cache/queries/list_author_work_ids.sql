@@ -0,0 +1,3 @@
+SELECT DISTINCT a.value AS author, v.work_id
+FROM versions v, json_each(v.authors) a
+ORDER BY a.value, v.work_id
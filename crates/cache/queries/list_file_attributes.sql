@@ -0,0 +1,4 @@
+SELECT key, value
+FROM file_attributes
+WHERE target = ? AND path = ?
+ORDER BY key
@@ -0,0 +1,9 @@
+SELECT
+    f.*,
+    v.*
+FROM files f
+JOIN versions v ON f.content_hash = v.content_hash
+WHERE f.target = ?
+  AND f.path > ?
+ORDER BY f.path
+LIMIT ?
@@ -0,0 +1,3 @@
+SELECT name, started_at, finished_at, error
+FROM job_runs
+ORDER BY name
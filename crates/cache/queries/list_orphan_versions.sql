@@ -0,0 +1,6 @@
+SELECT v.*
+FROM versions v
+WHERE v.content_hash NOT IN (
+    SELECT f.content_hash
+    FROM files f
+)
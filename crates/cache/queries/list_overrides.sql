@@ -0,0 +1,7 @@
+-- Every override, once for its canonical work ID and once for each alias of it.
+SELECT work_id, title, authors, fandoms, tags
+FROM overrides
+UNION ALL
+SELECT a.work_id, o.title, o.authors, o.fandoms, o.tags
+FROM aliases a
+JOIN overrides o ON o.work_id = a.canonical_work_id
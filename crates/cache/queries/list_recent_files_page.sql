@@ -0,0 +1,9 @@
+SELECT
+    f.*,
+    v.*
+FROM files f
+JOIN versions v ON f.content_hash = v.content_hash
+WHERE f.discovered_at < ?1
+   OR (f.discovered_at = ?1 AND (f.target, f.path) > (?2, ?3))
+ORDER BY f.discovered_at DESC, f.target, f.path
+LIMIT ?4
@@ -0,0 +1,3 @@
+SELECT run_id, target, prefix, checkpoint, scanned, started_at, updated_at
+FROM scan_runs
+ORDER BY started_at, run_id
@@ -0,0 +1,10 @@
+SELECT
+    s.series_id,
+    s.name,
+    COUNT(DISTINCT v.work_id) AS works,
+    MAX(sv.position) AS last_position
+FROM series s
+JOIN series_versions sv ON sv.series_id = s.series_id
+JOIN versions v ON v.content_hash = sv.content_hash
+GROUP BY s.series_id
+ORDER BY s.name, s.series_id
@@ -0,0 +1,4 @@
+SELECT DISTINCT sv.position
+FROM series_versions sv
+WHERE sv.series_id = ?
+ORDER BY sv.position
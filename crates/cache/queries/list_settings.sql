@@ -0,0 +1,3 @@
+SELECT key, value FROM settings
+WHERE namespace = ?
+ORDER BY key
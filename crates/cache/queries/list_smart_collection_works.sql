@@ -0,0 +1,4 @@
+SELECT work_id
+FROM smart_collection_works
+WHERE name = ?
+ORDER BY work_id
@@ -0,0 +1,5 @@
+SELECT c.name, c.filter, c.refreshed_at, COUNT(w.work_id) AS works
+FROM smart_collections c
+LEFT JOIN smart_collection_works w ON w.name = c.name
+GROUP BY c.name
+ORDER BY c.name
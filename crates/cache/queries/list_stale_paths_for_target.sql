@@ -0,0 +1,4 @@
+SELECT f.path
+FROM files f
+WHERE f.target = ? AND f.stale
+ORDER BY f.path
@@ -0,0 +1,4 @@
+SELECT DISTINCT c.hash
+FROM json_each(?) h
+JOIN chunks c ON c.hash = h.value
+WHERE c.target = ?
@@ -0,0 +1,4 @@
+SELECT key, value
+FROM version_attributes
+WHERE content_hash = ?
+ORDER BY key
@@ -0,0 +1,9 @@
+-- Coarse filter only; authors are stored as display strings ("pseud (user)")
+-- so the exact username/pseudonym match happens after parsing.
+SELECT
+    f.*,
+    v.*
+FROM versions v
+LEFT JOIN files f ON f.content_hash = v.content_hash
+WHERE instr(lower(v.authors), lower(?)) > 0
+ORDER BY v.content_hash
@@ -0,0 +1,9 @@
+SELECT
+    sv.position,
+    f.*,
+    v.*
+FROM series_versions sv
+JOIN versions v ON v.content_hash = sv.content_hash
+LEFT JOIN files f ON f.content_hash = v.content_hash
+WHERE sv.series_id = ?
+ORDER BY sv.position, v.content_hash
@@ -0,0 +1,3 @@
+UPDATE smart_collections
+SET refreshed_at = ?
+WHERE name = ?
@@ -0,0 +1,3 @@
+UPDATE files
+SET stale = 1
+WHERE files.target = ? AND files.path = ?
@@ -0,0 +1,3 @@
+UPDATE files
+SET verified_at = ?
+WHERE target = ? AND path = ?
@@ -0,0 +1,3 @@
+DELETE
+FROM files
+WHERE files.target = $1 AND files.path = $2
@@ -0,0 +1,8 @@
+SELECT
+    f.*,
+    v.*
+FROM files f
+JOIN versions v ON f.content_hash = v.content_hash
+WHERE f.target = $1
+  AND f.path = $2
+LIMIT 1
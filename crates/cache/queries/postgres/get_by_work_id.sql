@@ -0,0 +1,14 @@
+WITH canonical (work_id) AS (
+    SELECT COALESCE((SELECT canonical_work_id FROM aliases WHERE work_id = $1), $1)
+)
+SELECT
+    f.*,
+    v.*
+FROM versions v
+LEFT JOIN files f ON f.content_hash = v.content_hash
+WHERE v.work_id IN (
+    SELECT work_id FROM canonical
+    UNION
+    SELECT a.work_id FROM aliases a JOIN canonical c ON a.canonical_work_id = c.work_id
+)
+ORDER BY v.content_hash
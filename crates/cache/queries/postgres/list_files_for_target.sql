@@ -0,0 +1,7 @@
+SELECT
+    f.*,
+    v.*
+FROM files f
+JOIN versions v ON f.content_hash = v.content_hash
+WHERE f.target = $1
+ORDER BY f.path
@@ -0,0 +1,3 @@
+UPDATE files
+SET path = $1
+WHERE files.target = $2 AND files.path = $3
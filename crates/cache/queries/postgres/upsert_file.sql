@@ -0,0 +1,12 @@
+INSERT INTO files (target, path, compression, file_size, file_hash, content_hash, discovered_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7)
+ON CONFLICT (target, path) DO UPDATE SET
+    compression = excluded.compression,
+    file_size = excluded.file_size,
+    file_hash = excluded.file_hash,
+    content_hash = excluded.content_hash,
+    discovered_at = excluded.discovered_at,
+    stale = FALSE,
+    -- A verification of the previous file says nothing about its replacement.
+    verified_at = CASE WHEN files.file_hash = excluded.file_hash THEN files.verified_at END
+WHERE files.file_hash != excluded.file_hash OR files.stale;
@@ -0,0 +1,13 @@
+INSERT INTO versions (
+    content_hash,       content_crc32,  work_id,        content_size,
+    title,              authors,        fandoms,        series,
+    chapters_written,   chapters_total, complete,       words,
+    summary,            rating,         warnings,       lang,
+    published_on,       last_modified,  tags,           extracted_at,
+    encoding,           detected_lang,  detected_lang_iso,
+    detected_lang_confidence,   provenance,     work_skin
+) VALUES (
+    $1,  $2,  $3,  $4,  $5,  $6,  $7,  $8,  $9,  $10, $11, $12,
+    $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26
+)
+ON CONFLICT (content_hash) DO NOTHING;
@@ -0,0 +1,3 @@
+UPDATE target_locks
+SET heartbeat_at = ?
+WHERE target = ? AND token = ?
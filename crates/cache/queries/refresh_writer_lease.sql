@@ -0,0 +1,3 @@
+UPDATE writer_lease
+SET heartbeat_at = ?
+WHERE id = 1 AND token = ?
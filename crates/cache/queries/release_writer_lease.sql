@@ -0,0 +1,2 @@
+DELETE FROM writer_lease
+WHERE id = 1 AND token = ?
@@ -0,0 +1,3 @@
+UPDATE aliases
+SET canonical_work_id = ?
+WHERE canonical_work_id = ?;
@@ -0,0 +1,3 @@
+UPDATE author_identities
+SET canonical_author = ?
+WHERE canonical_author = ?;
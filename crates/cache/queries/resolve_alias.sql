@@ -0,0 +1,3 @@
+SELECT canonical_work_id
+FROM aliases
+WHERE work_id = ?
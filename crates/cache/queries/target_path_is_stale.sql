@@ -0,0 +1,3 @@
+SELECT COUNT(*)
+FROM files f
+WHERE f.target = ? AND f.path = ? AND f.stale
@@ -0,0 +1,4 @@
+SELECT COALESCE(a.canonical_work_id, v.work_id) AS work_id, f.file_size, f.discovered_at
+FROM files f
+JOIN versions v ON v.content_hash = f.content_hash
+LEFT JOIN aliases a ON a.work_id = v.work_id
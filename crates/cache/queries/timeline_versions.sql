@@ -0,0 +1,4 @@
+SELECT COALESCE(a.canonical_work_id, v.work_id) AS work_id, v.words, v.fandoms, v.extracted_at
+FROM versions v
+LEFT JOIN aliases a ON a.work_id = v.work_id
+ORDER BY v.extracted_at, v.content_hash
@@ -0,0 +1,3 @@
+UPDATE scan_runs
+SET checkpoint = ?, scanned = ?, updated_at = ?
+WHERE run_id = ?
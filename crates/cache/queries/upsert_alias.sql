@@ -0,0 +1,4 @@
+INSERT INTO aliases (work_id, canonical_work_id)
+VALUES (?, ?)
+ON CONFLICT (work_id) DO UPDATE SET
+    canonical_work_id = excluded.canonical_work_id;
@@ -0,0 +1,4 @@
+INSERT INTO author_identities (author, canonical_author)
+VALUES (?, ?)
+ON CONFLICT (author) DO UPDATE SET
+    canonical_author = excluded.canonical_author;
@@ -5,5 +5,8 @@ ON CONFLICT (target, path) DO UPDATE SET
     file_size = excluded.file_size,
     file_hash = excluded.file_hash,
     content_hash = excluded.content_hash,
-    discovered_at = excluded.discovered_at
-WHERE file_hash != excluded.file_hash;
+    discovered_at = excluded.discovered_at,
+    stale = 0,
+    -- A verification of the previous file says nothing about its replacement.
+    verified_at = CASE WHEN file_hash = excluded.file_hash THEN verified_at END
+WHERE file_hash != excluded.file_hash OR stale;
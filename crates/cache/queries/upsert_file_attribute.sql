@@ -0,0 +1,5 @@
+INSERT INTO file_attributes (target, path, key, value)
+SELECT ?1, ?2, ?3, ?4
+WHERE EXISTS (SELECT 1 FROM files WHERE target = ?1 AND path = ?2)
+ON CONFLICT (target, path, key) DO UPDATE SET
+    value = excluded.value;
@@ -0,0 +1,6 @@
+INSERT INTO job_runs (name, started_at, finished_at, error)
+VALUES (?, ?, NULL, NULL)
+ON CONFLICT (name) DO UPDATE SET
+    started_at = excluded.started_at,
+    finished_at = NULL,
+    error = NULL;
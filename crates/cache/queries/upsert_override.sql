@@ -0,0 +1,7 @@
+INSERT INTO overrides (work_id, title, authors, fandoms, tags)
+VALUES (?, ?, ?, ?, ?)
+ON CONFLICT (work_id) DO UPDATE SET
+    title = excluded.title,
+    authors = excluded.authors,
+    fandoms = excluded.fandoms,
+    tags = excluded.tags;
@@ -0,0 +1,6 @@
+INSERT INTO reading_status (work_id, read_at, chapters_written, last_modified)
+VALUES (?, ?, ?, ?)
+ON CONFLICT (work_id) DO UPDATE SET
+    read_at = excluded.read_at,
+    chapters_written = excluded.chapters_written,
+    last_modified = excluded.last_modified;
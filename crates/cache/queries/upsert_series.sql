@@ -0,0 +1,5 @@
+INSERT INTO series (series_id, name)
+VALUES (?, ?)
+ON CONFLICT (series_id) DO UPDATE SET
+    name = excluded.name
+WHERE name != excluded.name;
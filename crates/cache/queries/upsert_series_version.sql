@@ -0,0 +1,3 @@
+INSERT INTO series_versions (series_id, content_hash, position)
+VALUES (?, ?, ?)
+ON CONFLICT (series_id, content_hash) DO NOTHING;
@@ -0,0 +1,4 @@
+INSERT INTO settings (namespace, key, value)
+VALUES (?, ?, ?)
+ON CONFLICT (namespace, key) DO UPDATE SET
+    value = excluded.value;
@@ -0,0 +1,5 @@
+INSERT INTO smart_collections (name, filter, refreshed_at)
+VALUES (?, ?, NULL)
+ON CONFLICT (name) DO UPDATE SET
+    filter = excluded.filter,
+    refreshed_at = NULL;
@@ -0,0 +1,5 @@
+INSERT INTO tombstones (work_id, reason, deleted_at)
+VALUES (?, ?, ?)
+ON CONFLICT (work_id) DO UPDATE SET
+    reason = excluded.reason,
+    deleted_at = excluded.deleted_at;
@@ -1,8 +1,10 @@
 INSERT INTO versions (
     content_hash,       content_crc32,  work_id,        content_size,
     title,              authors,        fandoms,        series,
-    chapters_written,   chapters_total, words,          summary,
-    rating,             warnings,       lang,           published_on,
-    last_modified,      tags,           extracted_at
-) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+    chapters_written,   chapters_total, complete,       words,
+    summary,            rating,         warnings,       lang,
+    published_on,       last_modified,  tags,           extracted_at,
+    encoding,           detected_lang,  detected_lang_iso,
+    detected_lang_confidence,   provenance,     work_skin
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
 ON CONFLICT (content_hash) DO NOTHING;
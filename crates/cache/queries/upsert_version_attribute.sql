@@ -0,0 +1,5 @@
+INSERT INTO version_attributes (content_hash, key, value)
+SELECT ?1, ?2, ?3
+WHERE EXISTS (SELECT 1 FROM versions WHERE content_hash = ?1)
+ON CONFLICT (content_hash, key) DO UPDATE SET
+    value = excluded.value;
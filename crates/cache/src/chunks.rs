@@ -0,0 +1,29 @@
+//! The cache as a [`ChunkIndex`], remembering which chunks are stored on
+//! chunked targets (see [`ChunkedBackend`](rawr_storage::backend::ChunkedBackend)).
+
+use crate::Repository;
+use async_trait::async_trait;
+use exn::ResultExt;
+use rawr_storage::chunk::{Chunk, ChunkIndex};
+use rawr_storage::error::{ErrorKind as StorageErrorKind, Result as StorageResult};
+use std::collections::HashSet;
+use std::path::Path;
+
+#[async_trait]
+impl ChunkIndex for Repository {
+    async fn stored_chunks(&self, target: &str, hashes: &[&str]) -> StorageResult<HashSet<String>> {
+        Repository::stored_chunks(self, target, hashes).await.or_raise(chunk_index_error)
+    }
+
+    async fn record_chunks(&self, target: &str, path: &Path, chunks: &[Chunk]) -> StorageResult<()> {
+        Repository::record_chunks(self, target, path, chunks).await.or_raise(chunk_index_error)
+    }
+
+    async fn forget_chunks(&self, target: &str, path: &Path) -> StorageResult<()> {
+        Repository::forget_chunks(self, target, path).await.map(drop).or_raise(chunk_index_error)
+    }
+}
+
+fn chunk_index_error() -> StorageErrorKind {
+    StorageErrorKind::BackendError("chunk index in the cache failed".to_string())
+}
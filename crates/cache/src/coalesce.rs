@@ -0,0 +1,54 @@
+//! Coalescing upserts into batched transactions.
+//!
+//! A scan upserts a record for every file it reads, each in a transaction of
+//! its own: for a large library, hundreds at a time contending for SQLite's
+//! write lock, and syncing to disk once apiece. A repository [coalescing
+//! writes](crate::Repository::with_write_coalescing) queues upserts instead,
+//! and writes each batch in a single transaction: once the queue holds a
+//! batch's worth (which the upsert filling it waits for, holding back the
+//! writers that outpace the database), or once the oldest upsert has waited
+//! for the interval (in the background).
+//!
+//! Queued upserts can't be read back until they're written; callers that
+//! need them to be (and need to know they were) [flush](crate::Repository::flush)
+//! the queue.
+
+use crate::error::Error;
+use crate::{File, Version};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Upserts waiting to be written, shared by every clone of a repository.
+#[derive(Debug)]
+pub(crate) struct WriteQueue {
+    pub(crate) max_batch: usize,
+    pub(crate) interval: Duration,
+    pending: Mutex<Pending>,
+    /// Held while writing a batch, so batches are written in order.
+    pub(crate) flushing: tokio::sync::Mutex<()>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Pending {
+    pub(crate) writes: Vec<(File, Version)>,
+    /// Whether a background flush is waiting for the interval to pass.
+    pub(crate) timer: bool,
+    /// The first error writing a batch in the background, for the next
+    /// flush to return.
+    pub(crate) error: Option<Error>,
+}
+
+impl WriteQueue {
+    pub(crate) fn new(max_batch: usize, interval: Duration) -> Self {
+        Self {
+            max_batch: max_batch.max(1),
+            interval,
+            pending: Mutex::default(),
+            flushing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub(crate) fn pending(&self) -> MutexGuard<'_, Pending> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
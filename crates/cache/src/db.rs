@@ -1,10 +1,10 @@
 //! Database connection and pool management.
 
-use exn::ResultExt;
+use exn::{OptionExt, ResultExt};
 use sqlx::SqliteConnection;
 use sqlx::pool::PoolConnectionMetadata;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteSynchronous};
-use std::path::Path;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous};
+use std::path::{Path, PathBuf};
 use tracing::instrument;
 
 use crate::error::{ErrorKind, Result};
@@ -18,13 +18,28 @@ const MAX_CONNECTIONS: u32 = 5;
 ///
 /// This is the main entry point for interacting with the cache database.
 /// It manages the SQLite connection pool and provides access to repositories.
+///
+/// # Multiple processes
+/// Any number of processes can open the same cache file at once (such as a
+/// daemon scanning in the background while a CLI queries it), provided that:
+/// - The database is in WAL journal mode, so that readers never block the
+///   writer (or vice versa). [`connect()`](Self::connect) switches it to WAL
+///   mode, and every connection refuses a database that isn't (which is what
+///   happens on network filesystems without shared memory support). Don't
+///   put the cache on a network filesystem.
+/// - Only one process writes at a time. SQLite serializes writers, and a
+///   writer waiting for longer than the busy timeout (1.5 seconds) gives up
+///   with an error rather than waiting indefinitely. Processes that only
+///   read should [connect read-only](Self::connect_read_only), and a
+///   long-running writer should hold the [writer lease](crate::WriterLease).
 #[derive(Debug, Clone)]
 pub struct Database {
     pool: SqlitePool,
+    read_only: bool,
 }
 
 impl Database {
-    async fn new(options: SqliteConnectOptions, max: Option<u32>) -> Result<Self> {
+    async fn new(options: SqliteConnectOptions, max: Option<u32>, read_only: bool) -> Result<Self> {
         let pool = SqlitePoolOptions::new()
             // This is IMPORTANT to apply the query-based PRAGMAs to EVERY
             // connection (set by max connections) instead of only the
@@ -36,18 +51,78 @@ impl Database {
             .connect_with(options)
             .await
             .or_raise(|| ErrorKind::Database)?;
-        let db = Self { pool };
-        db.migrate().await?;
-        Ok(db)
+        Ok(Self { pool, read_only })
+    }
+
+    /// Where the cache database is kept unless configured otherwise, without
+    /// creating anything (see [`default_location()`](Self::default_location)):
+    /// the path in the [`RAWR_CACHE`](rawr_dirs::CACHE_PATH_ENV_VAR)
+    /// environment variable, or `cache.db` in the platform's data directory
+    /// (see [`rawr_dirs::cache_path()`]).
+    ///
+    /// Returns `None` if there's no override and no home directory.
+    pub fn default_path() -> Option<PathBuf> {
+        rawr_dirs::cache_path()
+    }
+
+    /// Resolves the [default path](Self::default_path) of the cache database,
+    /// creating its directory if it doesn't exist, ready to [`connect()`](Self::connect)
+    /// to. Every consumer of the cache should find it here, unless told
+    /// otherwise.
+    ///
+    /// Returns [`ErrorKind::Location`] if there's no default path, or its
+    /// directory can't be created.
+    pub fn default_location() -> Result<PathBuf> {
+        Self::prepare_location(Self::default_path())
+    }
+
+    /// Creates the directory of the cache database at `path` (if there is
+    /// one) if it doesn't exist.
+    fn prepare_location(path: Option<PathBuf>) -> Result<PathBuf> {
+        let path = path.ok_or_raise(|| ErrorKind::Location)?;
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).or_raise(|| ErrorKind::Location)?;
+        }
+        Ok(path)
     }
 
     /// Connect to the cache database at the given path.
     ///
-    /// Creates the database file if it doesn't exist and runs migrations.
+    /// Creates the database file if it doesn't exist, switches it to WAL
+    /// journal mode and runs migrations. Returns [`ErrorKind::JournalMode`]
+    /// if the database can't use WAL mode.
     pub async fn connect(path: impl AsRef<Path>) -> Result<Self> {
         let path = path.as_ref();
-        let options = Self::base_options().filename(path).create_if_missing(true);
-        Self::new(options, None).await
+        let options = Self::base_options()
+            .filename(path)
+            .create_if_missing(true)
+            // Enable WAL mode for concurrent readers alongside the writer
+            .journal_mode(SqliteJournalMode::Wal);
+        let db = Self::new(options, None, false).await?;
+        db.require_wal().await?;
+        db.migrate().await?;
+        Ok(db)
+    }
+
+    /// Connect to an existing cache database at the given path, read-only.
+    ///
+    /// For processes that only query the cache while another process writes
+    /// to it. Read-only connections never take the write lock (so they never
+    /// wait for the writer, nor it for them), and [repositories](crate::Repository)
+    /// created from them refuse to write with [`ErrorKind::ReadOnly`].
+    ///
+    /// The database must already exist, be in WAL journal mode (see
+    /// [`ErrorKind::JournalMode`]) and be migrated by a writable
+    /// [`connect()`](Self::connect) to at least this version's schema
+    /// (otherwise [`ErrorKind::Migration`] is returned).
+    pub async fn connect_read_only(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        // Don't set the journal mode: changing it is a write.
+        let options = Self::base_options().filename(path).read_only(true);
+        let db = Self::new(options, None, true).await?;
+        db.require_wal().await?;
+        db.require_migrated().await?;
+        Ok(db)
     }
 
     /// Connect to an in-memory database (useful for testing).
@@ -60,14 +135,19 @@ impl Database {
         // In-memory database must either use the same cache `.shared_cache(true)`,
         // or be limited to one connection. Otherwise parallel connections will
         // see different databases that contain different data.
-        Self::new(options, Some(1)).await
+        let db = Self::new(options, Some(1), false).await?;
+        db.migrate().await?;
+        Ok(db)
+    }
+
+    /// Whether the database was opened [read-only](Self::connect_read_only).
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
     }
 
     /// Base connection options shared between file and in-memory databases.
     fn base_options() -> SqliteConnectOptions {
         SqliteConnectOptions::new()
-            // Enable WAL mode for better concurrent read performance
-            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
             // Foreign key enforcement
             .foreign_keys(true)
             // PRAGMA synchronous = NORMAL (balance between safety and speed)
@@ -94,6 +174,30 @@ impl Database {
         Ok(())
     }
 
+    /// Fail unless the database file is in WAL journal mode.
+    async fn require_wal(&self) -> Result<()> {
+        let mode: String =
+            sqlx::query_scalar("PRAGMA journal_mode").fetch_one(&self.pool).await.or_raise(|| ErrorKind::Database)?;
+        if !mode.eq_ignore_ascii_case("wal") {
+            exn::bail!(ErrorKind::JournalMode(mode));
+        }
+        Ok(())
+    }
+
+    /// Fail unless every migration has been applied (which read-only
+    /// connections can't do themselves).
+    async fn require_migrated(&self) -> Result<()> {
+        let applied: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+            .fetch_one(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Migration)?;
+        let latest = MIGRATOR.iter().map(|migration| migration.version).max();
+        if applied < latest {
+            exn::bail!(ErrorKind::Migration);
+        }
+        Ok(())
+    }
+
     /// Run database migrations.
     ///
     /// This is called automatically by `connect` and `connect_in_memory`,
@@ -125,6 +229,9 @@ impl Database {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Repository;
+    use sqlx::Connection;
+    use std::ops::Deref;
 
     #[tokio::test]
     async fn test_connect_in_memory() {
@@ -141,6 +248,65 @@ mod tests {
         db.close().await;
     }
 
+    #[tokio::test]
+    async fn test_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.db");
+        assert!(Database::connect_read_only(&path).await.is_err(), "read-only connections can't create");
+        let writer = Database::connect(&path).await.unwrap();
+        let reader = Database::connect_read_only(&path).await.unwrap();
+        assert!(reader.is_read_only() && !writer.is_read_only());
+
+        let cache = Repository::from(&reader);
+        assert!(cache.is_read_only());
+        assert!(matches!(cache.lock_target("library", "scan").await.unwrap_err().deref(), ErrorKind::ReadOnly));
+        assert!(matches!(cache.delete_orphaned_versions().await.unwrap_err().deref(), ErrorKind::ReadOnly));
+        assert!(matches!(reader.maintain().await.unwrap_err().deref(), ErrorKind::ReadOnly));
+        // Reading isn't blocked by the writer's open transaction.
+        let mut tx = writer.pool().begin().await.unwrap();
+        sqlx::query("DELETE FROM versions").execute(&mut *tx).await.unwrap();
+        assert_eq!(0, cache.count_versions().await.unwrap());
+        tx.commit().await.unwrap();
+        // Even at the connection level, as a last resort.
+        assert!(sqlx::query("DELETE FROM versions").execute(reader.pool()).await.is_err());
+        reader.close().await;
+        writer.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_default_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("cache.db");
+        assert_eq!(path, Database::prepare_location(Some(path.clone())).unwrap());
+        assert!(path.parent().unwrap().is_dir());
+        assert!(!path.exists(), "only the directory is created");
+        Database::connect(&path).await.unwrap().close().await;
+        assert!(path.exists());
+        // Preparing it again is fine.
+        assert_eq!(path, Database::prepare_location(Some(path.clone())).unwrap());
+
+        let error = Database::prepare_location(None).unwrap_err();
+        assert!(matches!(error.deref(), ErrorKind::Location));
+    }
+
+    #[tokio::test]
+    async fn test_journal_mode_is_required() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.db");
+        // A database created by something else, in the default rollback
+        // journal mode.
+        let options = SqliteConnectOptions::new().filename(&path).create_if_missing(true);
+        let mut conn = SqliteConnection::connect_with(&options).await.unwrap();
+        sqlx::query("PRAGMA user_version = 1").execute(&mut conn).await.unwrap();
+        conn.close().await.unwrap();
+
+        let error = Database::connect_read_only(&path).await.unwrap_err();
+        assert!(matches!(error.deref(), ErrorKind::JournalMode(mode) if mode == "delete"));
+        // Connecting to write switches it back.
+        Database::connect(&path).await.unwrap().close().await;
+        Database::connect_read_only(&path).await.unwrap().close().await;
+    }
+
     #[tokio::test]
     async fn test_pragmas_are_applied() {
         let db = Database::connect_in_memory().await.unwrap();
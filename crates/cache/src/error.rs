@@ -7,7 +7,10 @@
 //!       more crates. Designing errors in Rust is **hard** and I don't want
 //!       to resort to anyhow+thiserror just because I don't want to deal with it.
 
+use crate::lease::LeaseInfo;
+use crate::lock::LockInfo;
 use derive_more::{Display, Error};
+pub use rawr_error::{Code, Domain, ErrorCode};
 use std::path::PathBuf;
 
 /// A cache error with automatic location tracking.
@@ -24,13 +27,47 @@ pub enum ErrorKind {
     Database,
     #[display("database migration error")]
     Migration,
+    /// The default location of the database couldn't be determined (there's
+    /// no home directory), or its directory couldn't be created.
+    #[display("cannot resolve the default cache database location")]
+    Location,
     #[display("file not found: ({_0}, {})", _1.display())]
     FileNotFound(#[error(not(source))] String, PathBuf),
     #[display("version not found: ({_0})")]
     VersionNotFound(#[error(not(source))] String),
+    #[display("smart collection not found: ({_0})")]
+    CollectionNotFound(#[error(not(source))] String),
     /// Serialization/deserialization error.
     #[display("invalid cache data in field {_0}")]
     InvalidData(#[error(not(source))] &'static str),
+    /// The target is locked by another operation.
+    #[display("{_0}")]
+    Locked(#[error(not(source))] LockInfo),
+    /// A held target lock expired and was taken over (or was broken).
+    #[display("lock on target {_0} was lost")]
+    LockLost(#[error(not(source))] String),
+    /// Another process holds the writer lease.
+    #[display("{_0}")]
+    Leased(#[error(not(source))] LeaseInfo),
+    /// A held writer lease expired and was taken over (or was broken).
+    #[display("writer lease was lost")]
+    LeaseLost,
+    /// A write was attempted through a read-only connection.
+    #[display("cache was opened read-only")]
+    ReadOnly,
+    /// The database file isn't in WAL journal mode (and couldn't be switched
+    /// to it), so readers and writers in different processes would block
+    /// each other.
+    #[display("cache database must use WAL journal mode, but uses {_0}")]
+    JournalMode(#[error(not(source))] String),
+    /// A pagination cursor was malformed, or came from a different kind of
+    /// listing.
+    #[display("invalid pagination cursor")]
+    InvalidCursor,
+    /// A filter expression couldn't be parsed; holds the (character)
+    /// position of the problem and what it was.
+    #[display("invalid filter at position {_0}: {_1}")]
+    InvalidFilter(#[error(not(source))] usize, String),
     /// When the cache is asked to handle file/version pair that
     /// don't relate to each other
     #[display("relationship constraint")]
@@ -43,3 +80,27 @@ impl ErrorKind {
         false
     }
 }
+
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::InvalidCursor => 400,
+            Self::InvalidFilter(..) => 406,
+            Self::InvalidData(_) => 422,
+            Self::FileNotFound(..) => 404,
+            Self::VersionNotFound(_) => 410,
+            Self::CollectionNotFound(_) => 411,
+            Self::Constraint => 409,
+            Self::Locked(_) => 423,
+            Self::LockLost(_) => 424,
+            Self::Leased(_) => 425,
+            Self::LeaseLost => 426,
+            Self::ReadOnly => 405,
+            Self::JournalMode(_) => 501,
+            Self::Database => 500,
+            Self::Migration => 503,
+            Self::Location => 502,
+        };
+        Code::new(Domain::Cache, number)
+    }
+}
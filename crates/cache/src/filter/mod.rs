@@ -0,0 +1,287 @@
+//! Composable filters over cached versions.
+//!
+//! A [`Filter`] is built from simple conditions on a version's metadata,
+//! combined with [`and()`](Filter::and), [`or()`](Filter::or) and `!`
+//! (negation), then compiled to an SQL `WHERE` clause. Filters serialize to
+//! JSON so they can be saved in the cache (see [smart collections](crate::SmartCollection)).
+//!
+//! Filters can also be [parsed](parse) from a small expression language, such
+//! as `fandom:"MCU" rating<=T -tag:"Major Character Death"`, for users to type
+//! into command lines and search boxes.
+
+mod parse;
+mod sort;
+
+pub use self::sort::Sort;
+
+use rawr_extract::models::Rating;
+use serde::{Deserialize, Serialize};
+use sqlx::Sqlite;
+use sqlx::query::QueryAs;
+use sqlx::sqlite::SqliteArguments;
+use std::ops::Not;
+
+/// A condition on a version's metadata.
+///
+/// Text conditions are case-insensitive; all but the language conditions
+/// match on *contains* rather than equality (AO3 tags are long, and
+/// wrangled inconsistently).
+///
+/// # Example
+///
+/// ```
+/// use rawr_cache::Filter;
+/// use rawr_extract::models::Rating;
+///
+/// let filter = Filter::rating(Rating::Explicit).and(Filter::tag("slow burn")).and(!Filter::complete(false));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    /// Every filter matches (matches everything when empty).
+    All(Vec<Filter>),
+    /// At least one filter matches (matches nothing when empty).
+    Any(Vec<Filter>),
+    /// The filter does not match.
+    Not(Box<Filter>),
+    #[serde(with = "rating")]
+    Rating(Rating),
+    /// Any tag (relationship, character or freeform) contains the text.
+    Tag(String),
+    /// Any fandom contains the text.
+    Fandom(String),
+    /// Any author (pseudonym or username) contains the text.
+    Author(String),
+    /// The language name (e.g. `"English"`) is exactly the text.
+    Language(String),
+    /// The name of the language the text was detected to be written in is
+    /// exactly the text.
+    DetectedLanguage(String),
+    /// The text was detected (with at least this confidence, from 0 to 100)
+    /// to be written in a language other than the declared one.
+    MixedLanguage(u8),
+    /// All planned chapters have been posted (or not).
+    Complete(bool),
+    /// The work has at least this many words.
+    MinWords(u64),
+    /// The work has at most this many words.
+    MaxWords(u64),
+    /// The work has at least this many words per posted chapter.
+    MinWordsPerChapter(u64),
+    /// The work has at most this many words per posted chapter.
+    MaxWordsPerChapter(u64),
+}
+impl Filter {
+    pub fn rating(rating: Rating) -> Self {
+        Self::Rating(rating)
+    }
+
+    pub fn tag(text: impl Into<String>) -> Self {
+        Self::Tag(text.into())
+    }
+
+    pub fn fandom(text: impl Into<String>) -> Self {
+        Self::Fandom(text.into())
+    }
+
+    pub fn author(text: impl Into<String>) -> Self {
+        Self::Author(text.into())
+    }
+
+    pub fn language(name: impl Into<String>) -> Self {
+        Self::Language(name.into())
+    }
+
+    pub fn detected_language(name: impl Into<String>) -> Self {
+        Self::DetectedLanguage(name.into())
+    }
+
+    pub fn mixed_language(min_confidence: u8) -> Self {
+        Self::MixedLanguage(min_confidence)
+    }
+
+    pub fn complete(complete: bool) -> Self {
+        Self::Complete(complete)
+    }
+
+    pub fn min_words(words: u64) -> Self {
+        Self::MinWords(words)
+    }
+
+    pub fn max_words(words: u64) -> Self {
+        Self::MaxWords(words)
+    }
+
+    pub fn min_words_per_chapter(words: u64) -> Self {
+        Self::MinWordsPerChapter(words)
+    }
+
+    pub fn max_words_per_chapter(words: u64) -> Self {
+        Self::MaxWordsPerChapter(words)
+    }
+
+    /// Matches when both this filter and `other` match.
+    pub fn and(self, other: Filter) -> Self {
+        match self {
+            Self::All(mut filters) => {
+                filters.push(other);
+                Self::All(filters)
+            },
+            filter => Self::All(vec![filter, other]),
+        }
+    }
+
+    /// Matches when either this filter or `other` matches.
+    pub fn or(self, other: Filter) -> Self {
+        match self {
+            Self::Any(mut filters) => {
+                filters.push(other);
+                Self::Any(filters)
+            },
+            filter => Self::Any(vec![filter, other]),
+        }
+    }
+
+    /// Compiles the filter to an SQL expression over the `versions` table
+    /// (aliased as `v`), with `?` placeholders for each of the returned values.
+    pub(crate) fn to_sql(&self) -> (String, Vec<Value>) {
+        let mut sql = String::new();
+        let mut values = Vec::new();
+        self.write_sql(&mut sql, &mut values);
+        (sql, values)
+    }
+
+    fn write_sql(&self, sql: &mut String, values: &mut Vec<Value>) {
+        let mut group = |filters: &[Filter], operator: &str, empty: &str| {
+            if filters.is_empty() {
+                sql.push_str(empty);
+                return;
+            }
+            sql.push('(');
+            for (i, filter) in filters.iter().enumerate() {
+                if i > 0 {
+                    sql.push_str(operator);
+                }
+                filter.write_sql(sql, values);
+            }
+            sql.push(')');
+        };
+        match self {
+            Self::All(filters) => return group(filters, " AND ", "1"),
+            Self::Any(filters) => return group(filters, " OR ", "0"),
+            Self::Not(filter) => {
+                sql.push_str("NOT ");
+                filter.write_sql(sql, values);
+                return;
+            },
+            _ => (),
+        }
+        let (condition, value) = match self {
+            Self::Rating(rating) => ("v.rating = ?", Value::Text(rating.as_short_str().to_string())),
+            Self::Tag(text) => (
+                "EXISTS (SELECT 1 FROM json_each(v.tags) j WHERE instr(lower(json_extract(j.value, '$.name')), lower(?)) > 0)",
+                Value::Text(text.clone()),
+            ),
+            Self::Fandom(text) => (
+                "EXISTS (SELECT 1 FROM json_each(v.fandoms) j WHERE instr(lower(j.value), lower(?)) > 0)",
+                Value::Text(text.clone()),
+            ),
+            // Authors are stored as display strings ("pseud (user)"), so this
+            // matches either.
+            Self::Author(text) => ("instr(lower(v.authors), lower(?)) > 0", Value::Text(text.clone())),
+            Self::Language(name) => ("lower(v.lang) = lower(?)", Value::Text(name.clone())),
+            Self::DetectedLanguage(name) => ("lower(v.detected_lang) = lower(?)", Value::Text(name.clone())),
+            Self::MixedLanguage(confidence) => (
+                "(lower(v.detected_lang) != lower(v.lang) AND v.detected_lang_confidence >= ?)",
+                Value::Int(i64::from(*confidence)),
+            ),
+            Self::Complete(complete) => ("v.complete = ?", Value::Int(i64::from(*complete))),
+            Self::MinWords(words) => ("v.words >= ?", Value::Int(i64::try_from(*words).unwrap_or(i64::MAX))),
+            Self::MaxWords(words) => ("v.words <= ?", Value::Int(i64::try_from(*words).unwrap_or(i64::MAX))),
+            Self::MinWordsPerChapter(words) => {
+                ("v.words_per_chapter >= ?", Value::Int(i64::try_from(*words).unwrap_or(i64::MAX)))
+            },
+            Self::MaxWordsPerChapter(words) => {
+                ("v.words_per_chapter <= ?", Value::Int(i64::try_from(*words).unwrap_or(i64::MAX)))
+            },
+            Self::All(_) | Self::Any(_) | Self::Not(_) => unreachable!("handled above"),
+        };
+        sql.push_str(condition);
+        values.push(value);
+    }
+}
+impl Not for Filter {
+    type Output = Filter;
+    fn not(self) -> Self::Output {
+        match self {
+            Self::Not(filter) => *filter,
+            filter => Self::Not(Box::new(filter)),
+        }
+    }
+}
+
+/// A value bound to a placeholder of a compiled [`Filter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Value {
+    Text(String),
+    Int(i64),
+}
+impl Value {
+    pub(crate) fn bind<'q, O>(
+        self,
+        query: QueryAs<'q, Sqlite, O, SqliteArguments<'q>>,
+    ) -> QueryAs<'q, Sqlite, O, SqliteArguments<'q>> {
+        match self {
+            Self::Text(text) => query.bind(text),
+            Self::Int(int) => query.bind(int),
+        }
+    }
+}
+
+/// Serializes ratings by their short form (e.g. `"E"`), same as the cache.
+mod rating {
+    use rawr_extract::models::Rating;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(rating: &Rating, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(rating.as_short_str())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Rating, D::Error> {
+        let s = String::deserialize(d)?;
+        s.parse().map_err(|_| D::Error::custom(format!("unknown rating: {s}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_sql() {
+        let filter = Filter::rating(Rating::Explicit).and(Filter::tag("Slow Burn")).and(!Filter::min_words(1000));
+        let (sql, values) = filter.to_sql();
+        assert_eq!(3, sql.matches('?').count());
+        assert!(sql.starts_with("(v.rating = ? AND EXISTS"));
+        assert!(sql.ends_with(" AND NOT v.words >= ?)"));
+        assert_eq!(
+            vec![
+                Value::Text("E".into()),
+                Value::Text("Slow Burn".into()),
+                Value::Int(1000)
+            ],
+            values
+        );
+        assert_eq!(("0".to_string(), vec![]), Filter::Any(vec![]).to_sql());
+        assert_eq!(Filter::tag("a"), !!Filter::tag("a"));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let filter = Filter::rating(Rating::Mature).or(Filter::fandom("Star Wars").and(Filter::complete(true)));
+        let json = serde_json::to_string(&filter).unwrap();
+        assert!(json.contains(r#"{"rating":"M"}"#), "{json}");
+        assert_eq!(filter, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -0,0 +1,385 @@
+//! Parsing [`Filter`]s from text, as typed by users.
+//!
+//! Conditions are written `field:value` (or `field=value`), with values
+//! quoted if they contain whitespace or parentheses. Conditions separated by
+//! whitespace must all match; `OR` between them makes either enough, and
+//! binds more loosely (`a b OR c` is `(a b) OR c`). A leading `-` negates a
+//! condition, and parentheses group them:
+//!
+//! ```text
+//! fandom:"MCU" rating<=T words>50000 -tag:"Major Character Death"
+//! (author:someone OR author:"someone else") complete:yes
+//! ```
+//!
+//! | Field                    | Operators                 | Value                                   |
+//! |--------------------------|---------------------------|-----------------------------------------|
+//! | `tag`                    | `:`                       | Text any tag contains                   |
+//! | `fandom`                 | `:`                       | Text any fandom contains                |
+//! | `author`                 | `:`                       | Text any author contains                |
+//! | `language`, `lang`       | `:`                       | Language name                           |
+//! | `detected`               | `:`                       | Detected language name                  |
+//! | `mixed`                  | `:`, `>=`                 | Minimum confidence (0 to 100)           |
+//! | `complete`               | `:`                       | `yes`/`no` (or `true`/`false`)          |
+//! | `rating`                 | `:`, `<`, `<=`, `>`, `>=` | `G`, `T`, `M`, `E` (or full names)      |
+//! | `words`                  | `:`, `<`, `<=`, `>`, `>=` | Number, optionally in thousands (`50k`) |
+//! | `chapter_words`          | `:`, `<`, `<=`, `>`, `>=` | Words per posted chapter, as `words`    |
+//!
+//! Ratings compare in order of explicitness (`G` < `T` < `M` < `E`); works
+//! that aren't rated never match a comparison. Field names are
+//! case-insensitive. Negations and parentheses can nest at most 64 levels
+//! deep.
+
+use crate::Filter;
+use crate::error::{Error, ErrorKind};
+use rawr_extract::models::Rating;
+use std::str::FromStr;
+
+/// Ratings in order of explicitness.
+const RATINGS: [Rating; 4] = [
+    Rating::GeneralAudiences,
+    Rating::TeenAndUp,
+    Rating::Mature,
+    Rating::Explicit,
+];
+
+/// How deeply negations and parentheses can nest, so that a pathological
+/// expression is rejected rather than overflowing the stack.
+const MAX_DEPTH: usize = 64;
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    /// Parses a filter expression (see the [module documentation](self)).
+    /// An empty expression matches everything.
+    ///
+    /// Returns [`ErrorKind::InvalidFilter`] if it can't be parsed.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            at: 0,
+            depth: 0,
+        };
+        let filter = parser.expression()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(filter),
+            Some(')') => Err(parser.error("unmatched closing parenthesis")),
+            Some(_) => Err(parser.error("unexpected input")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+    depth: usize,
+}
+impl Parser {
+    fn error(&self, message: impl Into<String>) -> Error {
+        self.error_at(self.at, message)
+    }
+
+    fn error_at(&self, at: usize, message: impl Into<String>) -> Error {
+        Error::new(ErrorKind::InvalidFilter(at, message.into()))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.at += 1;
+        }
+    }
+
+    /// Whether the next word is `OR` (on its own), consuming it if so.
+    fn or(&mut self) -> bool {
+        let is_or = self.chars.get(self.at..self.at + 2) == Some(&['O', 'R'])
+            && self.chars.get(self.at + 2).is_none_or(|c| c.is_whitespace() || *c == '(');
+        if is_or {
+            self.at += 2;
+        }
+        is_or
+    }
+
+    /// Conditions separated by `OR`.
+    fn expression(&mut self) -> Result<Filter, Error> {
+        let mut any = vec![self.all()?];
+        loop {
+            self.skip_whitespace();
+            if !self.or() {
+                break;
+            }
+            any.push(self.all()?);
+        }
+        Ok(match any.len() {
+            1 => any.remove(0),
+            _ => Filter::Any(any),
+        })
+    }
+
+    /// Conditions that must all match, up to an `OR`, a closing parenthesis
+    /// or the end.
+    fn all(&mut self) -> Result<Filter, Error> {
+        let mut all = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let start = self.at;
+            if matches!(self.peek(), None | Some(')')) || self.or() {
+                self.at = start;
+                break;
+            }
+            all.push(self.unary()?);
+        }
+        Ok(match all.len() {
+            1 => all.remove(0),
+            _ => Filter::All(all),
+        })
+    }
+
+    /// A (negated) condition, or a parenthesised expression.
+    fn unary(&mut self) -> Result<Filter, Error> {
+        if matches!(self.peek(), Some('-' | '(')) {
+            if self.depth == MAX_DEPTH {
+                return Err(self.error(format!("nested more than {MAX_DEPTH} levels deep")));
+            }
+            self.depth += 1;
+            let filter = self.nested();
+            self.depth -= 1;
+            return filter;
+        }
+        self.condition()
+    }
+
+    /// The negation or parenthesised expression at the cursor.
+    fn nested(&mut self) -> Result<Filter, Error> {
+        match self.peek() {
+            Some('-') => {
+                self.at += 1;
+                Ok(!self.unary()?)
+            },
+            Some('(') => {
+                let open = self.at;
+                self.at += 1;
+                let filter = self.expression()?;
+                self.skip_whitespace();
+                if self.peek() != Some(')') {
+                    return Err(self.error_at(open, "unclosed parenthesis"));
+                }
+                self.at += 1;
+                Ok(filter)
+            },
+            _ => unreachable!("only called at `-` or `(`"),
+        }
+    }
+
+    /// `field`, operator and value.
+    fn condition(&mut self) -> Result<Filter, Error> {
+        let start = self.at;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.at += 1;
+        }
+        let field: String = self.chars[start..self.at].iter().collect::<String>().to_lowercase();
+        if field.is_empty() {
+            return Err(self.error("expected a field name"));
+        }
+        let (operator, length) = match (self.peek(), self.chars.get(self.at + 1)) {
+            (Some(':' | '='), _) => (Operator::Equal, 1),
+            (Some('<'), Some('=')) => (Operator::LessOrEqual, 2),
+            (Some('>'), Some('=')) => (Operator::GreaterOrEqual, 2),
+            (Some('<'), _) => (Operator::Less, 1),
+            (Some('>'), _) => (Operator::Greater, 1),
+            _ => return Err(self.error(format!("expected an operator after `{field}` (such as `{field}:value`)"))),
+        };
+        let operator_at = self.at;
+        self.at += length;
+        let value_at = self.at;
+        let value = self.value()?;
+
+        let text_only = |filter: fn(String) -> Filter| match operator {
+            Operator::Equal => Ok(filter(value.clone())),
+            _ => Err(self.error_at(operator_at, format!("`{field}` can only be compared with `:`"))),
+        };
+        match field.as_str() {
+            "tag" => text_only(Filter::Tag),
+            "fandom" => text_only(Filter::Fandom),
+            "author" => text_only(Filter::Author),
+            "language" | "lang" => text_only(Filter::Language),
+            "detected" => text_only(Filter::DetectedLanguage),
+            "complete" => match (operator, value.to_ascii_lowercase().as_str()) {
+                (Operator::Equal, "yes" | "true") => Ok(Filter::Complete(true)),
+                (Operator::Equal, "no" | "false") => Ok(Filter::Complete(false)),
+                (Operator::Equal, _) => Err(self.error_at(value_at, "expected `yes` or `no`")),
+                _ => Err(self.error_at(operator_at, "`complete` can only be compared with `:`")),
+            },
+            "mixed" => match (operator, value.parse::<u8>()) {
+                (Operator::Equal | Operator::GreaterOrEqual, Ok(confidence)) if confidence <= 100 => {
+                    Ok(Filter::MixedLanguage(confidence))
+                },
+                (Operator::Equal | Operator::GreaterOrEqual, _) => {
+                    Err(self.error_at(value_at, "expected a confidence from 0 to 100"))
+                },
+                _ => Err(self.error_at(operator_at, "`mixed` can only be compared with `:` or `>=`")),
+            },
+            "rating" => {
+                let rating: Rating = value.parse().map_err(|_| self.error_at(value_at, "unknown rating"))?;
+                let Some(rank) = RATINGS.iter().position(|r| *r == rating) else {
+                    return match operator {
+                        Operator::Equal => Ok(Filter::Rating(rating)),
+                        _ => Err(self.error_at(value_at, "unrated works can't be compared")),
+                    };
+                };
+                let ratings = match operator {
+                    Operator::Equal => return Ok(Filter::Rating(rating)),
+                    Operator::Less => &RATINGS[..rank],
+                    Operator::LessOrEqual => &RATINGS[..=rank],
+                    Operator::Greater => &RATINGS[rank + 1..],
+                    Operator::GreaterOrEqual => &RATINGS[rank..],
+                };
+                Ok(match ratings {
+                    [rating] => Filter::Rating(*rating),
+                    ratings => Filter::Any(ratings.iter().copied().map(Filter::Rating).collect()),
+                })
+            },
+            "words" | "chapter_words" => {
+                let words = parse_words(&value).ok_or_else(|| self.error_at(value_at, "expected a number of words"))?;
+                let per_chapter = field == "chapter_words";
+                let min = |words| match per_chapter {
+                    true => Filter::MinWordsPerChapter(words),
+                    false => Filter::MinWords(words),
+                };
+                let max = |words| match per_chapter {
+                    true => Filter::MaxWordsPerChapter(words),
+                    false => Filter::MaxWords(words),
+                };
+                Ok(match operator {
+                    Operator::Equal => Filter::All(vec![min(words), max(words)]),
+                    Operator::Less => match words.checked_sub(1) {
+                        Some(words) => max(words),
+                        None => Filter::Any(vec![]),
+                    },
+                    Operator::LessOrEqual => max(words),
+                    Operator::Greater => min(words.saturating_add(1)),
+                    Operator::GreaterOrEqual => min(words),
+                })
+            },
+            _ => Err(self.error_at(start, format!("unknown field `{field}`"))),
+        }
+    }
+
+    /// A quoted (with `\` escaping quotes and backslashes) or bare value.
+    fn value(&mut self) -> Result<String, Error> {
+        let start = self.at;
+        let mut value = String::new();
+        if self.peek() != Some('"') {
+            while let Some(c) = self.peek().filter(|c| !c.is_whitespace() && !matches!(c, '(' | ')')) {
+                value.push(c);
+                self.at += 1;
+            }
+            if value.is_empty() {
+                return Err(self.error("expected a value"));
+            }
+            return Ok(value);
+        }
+        self.at += 1;
+        loop {
+            match self.peek() {
+                None => return Err(self.error_at(start, "unclosed quote")),
+                Some('"') => {
+                    self.at += 1;
+                    return Ok(value);
+                },
+                Some('\\') if matches!(self.chars.get(self.at + 1), Some('"' | '\\')) => {
+                    value.push(self.chars[self.at + 1]);
+                    self.at += 2;
+                },
+                Some(c) => {
+                    value.push(c);
+                    self.at += 1;
+                },
+            }
+        }
+    }
+}
+
+/// A number of words, such as `50000`, `50,000` or `50k`.
+fn parse_words(value: &str) -> Option<u64> {
+    let value: String = value.chars().filter(|c| !matches!(c, ',' | '_')).collect();
+    match value.strip_suffix(['k', 'K']) {
+        Some(thousands) => thousands.parse::<u64>().ok()?.checked_mul(1000),
+        None => value.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let filter: Filter = r#"fandom:"MCU" rating<=T words>50k -tag:"Major Character Death""#.parse().unwrap();
+        let expected = Filter::All(vec![
+            Filter::fandom("MCU"),
+            Filter::Any(vec![
+                Filter::rating(Rating::GeneralAudiences),
+                Filter::rating(Rating::TeenAndUp),
+            ]),
+            Filter::min_words(50_001),
+            !Filter::tag("Major Character Death"),
+        ]);
+        assert_eq!(expected, filter);
+
+        let filter: Filter = r#"(Author:a OR author:"b \"c\"") complete:no OR rating>M"#.parse().unwrap();
+        let expected = Filter::Any(vec![
+            Filter::All(vec![
+                Filter::Any(vec![Filter::author("a"), Filter::author("b \"c\"")]),
+                Filter::complete(false),
+            ]),
+            Filter::rating(Rating::Explicit),
+        ]);
+        assert_eq!(expected, filter);
+
+        assert_eq!(Filter::All(vec![]), "  ".parse().unwrap());
+        assert_eq!(Filter::language("English"), "lang=English".parse().unwrap());
+        assert_eq!(Filter::Any(vec![]), "words<0".parse().unwrap());
+        assert_eq!(Filter::tag("ORigin"), "tag:ORigin".parse().unwrap());
+        assert_eq!(Filter::min_words_per_chapter(3001), "chapter_words>3k".parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let position = |input: &str| match &*input.parse::<Filter>().unwrap_err() {
+            ErrorKind::InvalidFilter(at, _) => *at,
+            kind => panic!("unexpected error: {kind}"),
+        };
+        assert_eq!(0, position("title:Foo"));
+        assert_eq!(3, position("tag \"x\""));
+        assert_eq!(3, position("tag<x"));
+        assert_eq!(7, position("rating:Z"));
+        assert_eq!(6, position("words>lots"));
+        assert_eq!(0, position("(tag:a"));
+        assert_eq!(5, position("tag:a)"));
+        assert_eq!(4, position("tag:\"open"));
+        assert_eq!(4, position("tag:"));
+        assert_eq!(MAX_DEPTH, position(&"(".repeat(100_000)));
+        assert_eq!(MAX_DEPTH, position(&"-".repeat(100_000)));
+    }
+
+    #[test]
+    fn test_parse_nesting() {
+        let nested = format!("{}tag:a{}", "(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+        assert_eq!(Filter::tag("a"), nested.parse().unwrap());
+        let negated = format!("{}tag:a", "-".repeat(MAX_DEPTH));
+        assert_eq!(Filter::tag("a"), negated.parse().unwrap());
+    }
+}
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+/// What to rank works by, highest first, when listing the
+/// [top works](crate::Repository::top_works) matching a [`Filter`](crate::Filter).
+///
+/// A work ranks by the highest value of any of its versions.
+///
+/// There's no ranking by kudos (or kudos per hit): a downloaded work's stats
+/// only give its dates, words and chapters. Kudos, hits, bookmarks and
+/// comments are only shown on the Archive's work pages, so they're never
+/// extracted, and there's nothing to derive such a ranking from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    /// Longest works first.
+    Words,
+    /// Works with the longest chapters first (words per posted chapter).
+    ChapterWords,
+    /// Most recently updated works first.
+    Updated,
+    /// Most recently published works first.
+    Published,
+}
+impl Sort {
+    /// The column of the `versions` table (aliased as `v`) ranked by.
+    pub(crate) fn column(&self) -> &'static str {
+        match self {
+            Self::Words => "v.words",
+            Self::ChapterWords => "v.words_per_chapter",
+            Self::Updated => "v.last_modified",
+            Self::Published => "v.published_on",
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Grouping joined file/version rows into versions and their files.
+//!
+//! Queries joining `versions` to `files` return one row per file, each with
+//! a full copy of its version's columns. Rather than key a map by content
+//! hash (cloning every hash, and converting every copy of the version), the
+//! rows are ordered so that each version's rows are adjacent, and grouped as
+//! they arrive: a version is only converted once, from the first of its
+//! rows, and the copies in the rest are dropped unread.
+//!
+//! Any `ORDER BY` works, provided it sorts by columns of the version (ending
+//! with `v.content_hash`) before any of the file; otherwise a version's rows
+//! come out in separate groups.
+
+use crate::error::{ErrorKind, Result};
+use crate::models::{FileRow, VersionRow};
+use crate::{File, Version};
+use exn::ResultExt;
+use futures::{Stream, TryStreamExt};
+use std::pin::pin;
+
+/// Versions grouped so far, and the rows of the one being grouped.
+#[derive(Default)]
+struct Groups {
+    grouped: Vec<(Version, Vec<File>)>,
+    current: Option<(VersionRow, Vec<File>)>,
+}
+impl Groups {
+    fn push(&mut self, (file, version): (Option<FileRow>, VersionRow)) -> Result<()> {
+        let file = file.map(File::try_from).transpose()?;
+        match &mut self.current {
+            Some((current, files)) if current.content_hash == version.content_hash => files.extend(file),
+            current => {
+                if let Some((version, files)) = current.replace((version, Vec::from_iter(file))) {
+                    self.grouped.push((Version::try_from(version)?, files));
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<(Version, Vec<File>)>> {
+        if let Some((version, files)) = self.current.take() {
+            self.grouped.push((Version::try_from(version)?, files));
+        }
+        Ok(self.grouped)
+    }
+}
+
+/// Groups rows, in which each version's rows are adjacent, into versions and
+/// their files (none, for a version without files in a `LEFT JOIN`).
+pub(crate) fn group_by_version<R: Into<(Option<FileRow>, VersionRow)>>(
+    rows: impl IntoIterator<Item = R>,
+) -> Result<Vec<(Version, Vec<File>)>> {
+    let mut groups = Groups::default();
+    for row in rows {
+        groups.push(row.into())?;
+    }
+    groups.finish()
+}
+
+/// Like [`group_by_version`], but grouping rows as they're fetched, rather
+/// than holding every row of the result in memory at once.
+pub(crate) async fn fetch_grouped<R: Into<(Option<FileRow>, VersionRow)>>(
+    rows: impl Stream<Item = sqlx::Result<R>>,
+) -> Result<Vec<(Version, Vec<File>)>> {
+    let mut rows = pin!(rows);
+    let mut groups = Groups::default();
+    while let Some(row) = rows.try_next().await.or_raise(|| ErrorKind::Database)? {
+        groups.push(row.into())?;
+    }
+    groups.finish()
+}
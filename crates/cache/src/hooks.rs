@@ -0,0 +1,97 @@
+//! Notifications of changes to the cache.
+//!
+//! Embedding applications that mirror the cache elsewhere (a TUI listing the
+//! library, a search index) can [subscribe](crate::Repository::subscribe) to
+//! be told what changed instead of polling for it. Hooks are shared by every
+//! clone of the [`Repository`](crate::Repository) they were registered on.
+//!
+//! Hooks run after the change is committed, one at a time and in the order
+//! they were registered, and are awaited before the write method returns:
+//! hooks with slow work to do should hand events off (for example, over a
+//! channel) rather than holding up the writer. Dry-run repositories change
+//! nothing, so they notify nothing.
+
+use crate::{File, Version};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// A change to the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepositoryEvent {
+    /// A file record was inserted (or replaced), along with its version.
+    Upserted { file: File, version: Version },
+    /// A file record was moved to the path it now has.
+    PathUpdated {
+        file: File,
+        version: Version,
+        old_path: PathBuf,
+    },
+    /// A file record was deleted. Its version wasn't, unless followed by
+    /// [`VersionDeleted`](Self::VersionDeleted).
+    FileDeleted { file: File, version: Version },
+    /// A version was deleted. Every file record referencing it was deleted
+    /// with it, and reported first.
+    VersionDeleted(Version),
+}
+
+/// Identifies a registered hook, to [unsubscribe](crate::Repository::unsubscribe) it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Subscription(u64);
+
+type Hook = Arc<dyn Fn(RepositoryEvent) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// Hooks registered on a repository (and its clones).
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    hooks: Arc<RwLock<Vec<(Subscription, Hook)>>>,
+    next: Arc<AtomicU64>,
+}
+impl Hooks {
+    pub(crate) fn subscribe<F, Fut>(&self, hook: F) -> Subscription
+    where
+        F: Fn(RepositoryEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let subscription = Subscription(self.next.fetch_add(1, Ordering::Relaxed));
+        let hook: Hook = Arc::new(move |event| Box::pin(hook(event)));
+        self.hooks.write().unwrap_or_else(|e| e.into_inner()).push((subscription, hook));
+        subscription
+    }
+
+    pub(crate) fn unsubscribe(&self, subscription: Subscription) -> bool {
+        let mut hooks = self.hooks.write().unwrap_or_else(|e| e.into_inner());
+        let before = hooks.len();
+        hooks.retain(|(s, _)| *s != subscription);
+        hooks.len() < before
+    }
+
+    /// Whether anyone is listening; affected records are only fetched (for
+    /// deletes) when they'd be reported.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.hooks.read().unwrap_or_else(|e| e.into_inner()).is_empty()
+    }
+
+    pub(crate) async fn notify(&self, events: impl IntoIterator<Item = RepositoryEvent>) {
+        // Don't hold the lock across awaits: hooks may (un)subscribe.
+        let hooks: Vec<Hook> =
+            self.hooks.read().unwrap_or_else(|e| e.into_inner()).iter().map(|(_, h)| h.clone()).collect();
+        if hooks.is_empty() {
+            return;
+        }
+        for event in events {
+            for hook in &hooks {
+                hook(event.clone()).await;
+            }
+        }
+    }
+}
+impl Debug for Hooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let count = self.hooks.read().map(|h| h.len()).unwrap_or_default();
+        f.debug_struct("Hooks").field("count", &count).finish()
+    }
+}
@@ -0,0 +1,198 @@
+//! The advisory writer lease.
+//!
+//! SQLite allows any number of readers alongside a single writer (in WAL
+//! mode, which [`Database`](crate::Database) requires), but writers from
+//! different processes queue up behind each other's transactions, and a
+//! scan's steady stream of writes keeps another process's writes waiting
+//! until they time out. A process that intends to write for a while (such as
+//! a daemon scanning in the background) takes the lease via
+//! [`Repository::acquire_writer_lease()`](crate::Repository::acquire_writer_lease),
+//! and other processes check for it, opening the cache
+//! [read-only](crate::Database::connect_read_only) while it's held.
+//!
+//! Like [target locks](crate::TargetLock), the lease is advisory, and expires
+//! after [`LOCK_TIMEOUT`](crate::LOCK_TIMEOUT) unless [refreshed](WriterLease::refresh).
+
+use crate::error::{ErrorKind, Result};
+use crate::lock::{REFRESH_INTERVAL, new_token, stale_before};
+use exn::ResultExt;
+use sqlx::SqlitePool;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::time::Instant;
+use time::UtcDateTime;
+
+/// Who holds the writer lease.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LeaseInfo {
+    /// Description of the process holding the lease, including the role it
+    /// was acquired for (e.g. `"daemon (process 1234)"`).
+    pub holder: String,
+    pub acquired_at: UtcDateTime,
+    /// When the holder last refreshed the lease.
+    pub heartbeat_at: UtcDateTime,
+}
+impl Display for LeaseInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "cache is leased for writing by {} since {} (last heartbeat at {})",
+            self.holder, self.acquired_at, self.heartbeat_at
+        )
+    }
+}
+
+/// The held writer lease.
+///
+/// Just like a [`TargetLock`](crate::TargetLock), holders must call
+/// [`refresh()`](Self::refresh) regularly and [`release()`](Self::release)
+/// when done.
+#[derive(Debug)]
+pub struct WriterLease {
+    pool: SqlitePool,
+    info: LeaseInfo,
+    token: String,
+    refreshed: Instant,
+    /// Dry-run leases are checked for, but never written.
+    dry_run: bool,
+}
+impl WriterLease {
+    pub(crate) async fn acquire(pool: &SqlitePool, dry_run: bool, role: &str) -> Result<Self> {
+        let now = UtcDateTime::now();
+        let info = LeaseInfo {
+            holder: format!("{role} (process {})", std::process::id()),
+            acquired_at: now,
+            heartbeat_at: now,
+        };
+        let lease = Self {
+            pool: pool.clone(),
+            info,
+            token: new_token(now),
+            refreshed: Instant::now(),
+            dry_run,
+        };
+        if dry_run {
+            return match current(pool).await? {
+                Some(held) if !is_stale(&held) => exn::bail!(ErrorKind::Leased(held)),
+                _ => Ok(lease),
+            };
+        }
+        let acquired: Option<String> = sqlx::query_scalar(include_str!("../queries/acquire_writer_lease.sql"))
+            .bind(&lease.info.holder)
+            .bind(&lease.token)
+            .bind(now.unix_timestamp())
+            .bind(now.unix_timestamp())
+            .bind(stale_before(now))
+            .fetch_optional(pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        if acquired.as_ref() == Some(&lease.token) {
+            tracing::debug!(holder = lease.info.holder, "Acquired writer lease");
+            return Ok(lease);
+        }
+        match current(pool).await? {
+            Some(held) => exn::bail!(ErrorKind::Leased(held)),
+            // Released in the meantime; the caller can try again.
+            None => exn::bail!(ErrorKind::LeaseLost),
+        }
+    }
+
+    /// Who holds the lease.
+    pub fn info(&self) -> &LeaseInfo {
+        &self.info
+    }
+
+    /// Keeps the lease from expiring.
+    ///
+    /// Only writes to the database if the lease hasn't been refreshed
+    /// recently. Returns [`ErrorKind::LeaseLost`] if the lease expired and
+    /// was taken over (or was broken).
+    pub async fn refresh(&mut self) -> Result<()> {
+        if self.dry_run || self.refreshed.elapsed() < REFRESH_INTERVAL {
+            return Ok(());
+        }
+        let now = UtcDateTime::now();
+        let result = sqlx::query(include_str!("../queries/refresh_writer_lease.sql"))
+            .bind(now.unix_timestamp())
+            .bind(&self.token)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        if result.rows_affected() == 0 {
+            exn::bail!(ErrorKind::LeaseLost);
+        }
+        self.refreshed = Instant::now();
+        self.info.heartbeat_at = now;
+        Ok(())
+    }
+
+    /// Releases the lease. Releasing a lease that has since been taken over
+    /// leaves the new holder's lease in place.
+    pub async fn release(self) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        sqlx::query(include_str!("../queries/release_writer_lease.sql"))
+            .bind(&self.token)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        tracing::debug!(holder = self.info.holder, "Released writer lease");
+        Ok(())
+    }
+}
+
+/// The current writer lease, whether or not it's stale.
+pub(crate) async fn current(pool: &SqlitePool) -> Result<Option<LeaseInfo>> {
+    let row: Option<(String, i64, i64)> = sqlx::query_as(include_str!("../queries/get_writer_lease.sql"))
+        .fetch_optional(pool)
+        .await
+        .or_raise(|| ErrorKind::Database)?;
+    let Some((holder, acquired_at, heartbeat_at)) = row else {
+        return Ok(None);
+    };
+    Ok(Some(LeaseInfo {
+        holder,
+        acquired_at: UtcDateTime::from_unix_timestamp(acquired_at)
+            .or_raise(|| ErrorKind::InvalidData("acquired at"))?,
+        heartbeat_at: UtcDateTime::from_unix_timestamp(heartbeat_at)
+            .or_raise(|| ErrorKind::InvalidData("heartbeat at"))?,
+    }))
+}
+
+pub(crate) fn is_stale(info: &LeaseInfo) -> bool {
+    info.heartbeat_at.unix_timestamp() < stale_before(UtcDateTime::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Database, LOCK_TIMEOUT, Repository};
+    use std::ops::Deref;
+
+    #[tokio::test]
+    async fn test_writer_lease() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let mut lease = cache.acquire_writer_lease("daemon").await.unwrap();
+        assert!(lease.info().holder.starts_with("daemon (process "));
+        let error = cache.acquire_writer_lease("cli").await.unwrap_err();
+        assert!(matches!(error.deref(), ErrorKind::Leased(info) if info.holder.starts_with("daemon")));
+        assert!(error.to_string().starts_with("cache is leased for writing by daemon"));
+        assert!(cache.writer_lease().await.unwrap().is_some_and(|info| !is_stale(&info)));
+
+        // Abandoned leases are taken over.
+        sqlx::query("UPDATE writer_lease SET heartbeat_at = heartbeat_at - ?")
+            .bind(LOCK_TIMEOUT.as_secs() as i64 + 1)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let cli = cache.acquire_writer_lease("cli").await.unwrap();
+        lease.refreshed -= REFRESH_INTERVAL;
+        assert!(matches!(lease.refresh().await.unwrap_err().deref(), ErrorKind::LeaseLost));
+        lease.release().await.unwrap();
+        assert!(cache.writer_lease().await.unwrap().unwrap().holder.starts_with("cli"));
+        cli.release().await.unwrap();
+        assert!(cache.writer_lease().await.unwrap().is_none());
+        assert!(!cache.break_writer_lease().await.unwrap());
+    }
+}
@@ -13,14 +13,48 @@
 //! - **FileRecords**: Physical files tracked across targets, linking paths
 //!   to their content hashes. Multiple files may reference the same Version
 //!   if they have identical content.
+//!
+//! Several processes can share one cache; see [`Database`] for what that
+//! requires. Several machines can share one through [`CacheStore`], with the
+//! `postgres` feature's `PostgresStore`.
 
+mod chunks;
+mod coalesce;
 mod db;
 pub mod error;
+mod filter;
+mod group;
+mod hooks;
+mod lease;
+mod lock;
+mod maintenance;
 mod models;
+mod overrides;
+mod page;
+#[cfg(feature = "postgres")]
+mod postgres;
 mod repo;
+mod store;
+mod timeline;
 
 pub use crate::db::Database;
-pub use crate::repo::{ExistenceResult, Repository};
+pub use crate::filter::{Filter, Sort};
+pub use crate::hooks::{RepositoryEvent, Subscription};
+pub use crate::lease::{LeaseInfo, WriterLease};
+pub use crate::lock::{LOCK_TIMEOUT, LockInfo, TargetLock};
+pub use crate::maintenance::MaintenanceReport;
+pub use crate::models::VersionSummary;
+pub use crate::overrides::Overrides;
+pub use crate::page::{Cursor, Page};
+#[cfg(feature = "postgres")]
+pub use crate::postgres::PostgresStore;
+pub use crate::repo::{
+    Attributes, ExistenceResult, JobRun, ReadingStatus, Repository, ScanRun, Series, SmartCollection, Tombstone,
+    UpdatedWork,
+};
+pub use crate::store::CacheStore;
+pub use crate::timeline::{Timeline, TimelineMonth};
+pub use rawr_dirs::CACHE_PATH_ENV_VAR;
 use rawr_extract::models as extract;
 use rawr_storage::file as storage;
 
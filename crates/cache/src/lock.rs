@@ -0,0 +1,247 @@
+//! Advisory locks on targets.
+//!
+//! Operations that modify a target (such as scanning and organizing) corrupt
+//! each other's view of it when run concurrently, so each first takes the
+//! target's lock via [`Repository::lock_target()`](crate::Repository::lock_target).
+//! Locks are rows in the cache, which every process working on a library
+//! shares regardless of the target's storage backend.
+//!
+//! Locks are advisory: nothing stops a process that doesn't ask for the lock
+//! from modifying the target. A holder that crashes can't release its lock,
+//! so locks expire unless [refreshed](TargetLock::refresh) regularly.
+
+use crate::error::{ErrorKind, Result};
+use exn::ResultExt;
+use sqlx::SqlitePool;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use time::UtcDateTime;
+
+/// Locks not refreshed for this long are considered abandoned, and can be
+/// taken over.
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Minimum time between refreshes actually written to the database.
+pub(crate) const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Distinguishes locks acquired by the same process.
+static ACQUISITIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Who holds a target's lock, and for what.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LockInfo {
+    pub target: String,
+    /// Name of the operation holding the lock (e.g. `"scan"`).
+    pub operation: String,
+    /// Description of the process holding the lock.
+    pub holder: String,
+    pub acquired_at: UtcDateTime,
+    /// When the holder last refreshed the lock.
+    pub heartbeat_at: UtcDateTime,
+}
+impl Display for LockInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "target \"{}\" is locked by {} ({}) since {} (last heartbeat at {})",
+            self.target, self.operation, self.holder, self.acquired_at, self.heartbeat_at
+        )
+    }
+}
+
+/// A held target lock.
+///
+/// Holders of long-running operations must call [`refresh()`](Self::refresh)
+/// regularly (calling it often is cheap), and [`release()`](Self::release)
+/// when done. A lock that is dropped without being released stays in place
+/// until it expires after [`LOCK_TIMEOUT`].
+#[derive(Debug)]
+pub struct TargetLock {
+    pool: SqlitePool,
+    info: LockInfo,
+    token: String,
+    refreshed: Instant,
+    /// Dry-run locks are checked for, but never written.
+    dry_run: bool,
+}
+impl TargetLock {
+    pub(crate) async fn acquire(pool: &SqlitePool, dry_run: bool, target: &str, operation: &str) -> Result<Self> {
+        let now = UtcDateTime::now();
+        let holder = format!("process {}", std::process::id());
+        let token = new_token(now);
+        let info = LockInfo {
+            target: target.to_string(),
+            operation: operation.to_string(),
+            holder,
+            acquired_at: now,
+            heartbeat_at: now,
+        };
+        let lock = Self {
+            pool: pool.clone(),
+            info,
+            token,
+            refreshed: Instant::now(),
+            dry_run,
+        };
+        if dry_run {
+            return match current(pool, target).await? {
+                Some(held) if !is_stale(&held) => exn::bail!(ErrorKind::Locked(held)),
+                _ => Ok(lock),
+            };
+        }
+        let acquired: Option<String> = sqlx::query_scalar(include_str!("../queries/acquire_target_lock.sql"))
+            .bind(target)
+            .bind(operation)
+            .bind(&lock.info.holder)
+            .bind(&lock.token)
+            .bind(now.unix_timestamp())
+            .bind(now.unix_timestamp())
+            .bind(stale_before(now))
+            .fetch_optional(pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        if acquired.as_ref() == Some(&lock.token) {
+            tracing::debug!(target, operation, "Acquired target lock");
+            return Ok(lock);
+        }
+        match current(pool, target).await? {
+            Some(held) => exn::bail!(ErrorKind::Locked(held)),
+            // Released in the meantime; the caller can try again.
+            None => exn::bail!(ErrorKind::LockLost(target.to_string())),
+        }
+    }
+
+    /// Who holds the lock, and for what.
+    pub fn info(&self) -> &LockInfo {
+        &self.info
+    }
+
+    /// Keeps the lock from expiring.
+    ///
+    /// Only writes to the database if the lock hasn't been refreshed
+    /// recently, so it can be called after every unit of work. Returns
+    /// [`ErrorKind::LockLost`] if the lock expired and was taken over (or
+    /// was broken): the holder must stop modifying the target.
+    pub async fn refresh(&mut self) -> Result<()> {
+        if self.dry_run || self.refreshed.elapsed() < REFRESH_INTERVAL {
+            return Ok(());
+        }
+        let now = UtcDateTime::now();
+        let result = sqlx::query(include_str!("../queries/refresh_target_lock.sql"))
+            .bind(now.unix_timestamp())
+            .bind(&self.info.target)
+            .bind(&self.token)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        if result.rows_affected() == 0 {
+            exn::bail!(ErrorKind::LockLost(self.info.target.clone()));
+        }
+        self.refreshed = Instant::now();
+        self.info.heartbeat_at = now;
+        Ok(())
+    }
+
+    /// Releases the lock. Releasing a lock that has since been taken over
+    /// leaves the new holder's lock in place.
+    pub async fn release(self) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+        sqlx::query(include_str!("../queries/release_target_lock.sql"))
+            .bind(&self.info.target)
+            .bind(&self.token)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        tracing::debug!(target = self.info.target, operation = self.info.operation, "Released target lock");
+        Ok(())
+    }
+}
+
+/// The current lock on a target, whether or not it's stale.
+pub(crate) async fn current(pool: &SqlitePool, target: &str) -> Result<Option<LockInfo>> {
+    let row: Option<(String, String, String, i64, i64)> =
+        sqlx::query_as(include_str!("../queries/get_target_lock.sql"))
+            .bind(target)
+            .fetch_optional(pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+    let Some((target, operation, holder, acquired_at, heartbeat_at)) = row else {
+        return Ok(None);
+    };
+    Ok(Some(LockInfo {
+        target,
+        operation,
+        holder,
+        acquired_at: UtcDateTime::from_unix_timestamp(acquired_at)
+            .or_raise(|| ErrorKind::InvalidData("acquired at"))?,
+        heartbeat_at: UtcDateTime::from_unix_timestamp(heartbeat_at)
+            .or_raise(|| ErrorKind::InvalidData("heartbeat at"))?,
+    }))
+}
+
+/// A token unique to an acquisition, which only its holder knows.
+pub(crate) fn new_token(now: UtcDateTime) -> String {
+    format!(
+        "{}-{}-{}",
+        std::process::id(),
+        now.unix_timestamp_nanos(),
+        ACQUISITIONS.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+pub(crate) fn stale_before(now: UtcDateTime) -> i64 {
+    now.unix_timestamp() - LOCK_TIMEOUT.as_secs() as i64
+}
+
+fn is_stale(info: &LockInfo) -> bool {
+    info.heartbeat_at.unix_timestamp() < stale_before(UtcDateTime::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Database, Repository};
+    use std::ops::Deref;
+
+    #[tokio::test]
+    async fn test_locking() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let lock = cache.lock_target("library", "scan").await.unwrap();
+        let error = cache.lock_target("library", "organize").await.unwrap_err();
+        match error.deref() {
+            ErrorKind::Locked(info) => assert_eq!(("library", "scan"), (info.target.as_str(), info.operation.as_str())),
+            other => panic!("expected lock to be held, got {other:?}"),
+        }
+        assert!(error.to_string().starts_with("target \"library\" is locked by scan"));
+        let other = cache.lock_target("trash", "organize").await.unwrap();
+        lock.release().await.unwrap();
+        assert!(cache.get_target_lock("library").await.unwrap().is_none());
+        cache.lock_target("library", "organize").await.unwrap();
+        other.release().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stale_and_broken_locks() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let mut abandoned = cache.lock_target("library", "scan").await.unwrap();
+        sqlx::query("UPDATE target_locks SET heartbeat_at = heartbeat_at - ?")
+            .bind(LOCK_TIMEOUT.as_secs() as i64 + 1)
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let mut lock = cache.lock_target("library", "organize").await.unwrap();
+        abandoned.refreshed -= REFRESH_INTERVAL;
+        assert!(matches!(abandoned.refresh().await.unwrap_err().deref(), ErrorKind::LockLost(_)));
+        // Releasing the lost lock mustn't release the new holder's lock.
+        abandoned.release().await.unwrap();
+        assert_eq!("organize", cache.get_target_lock("library").await.unwrap().unwrap().operation);
+
+        assert!(cache.break_target_lock("library").await.unwrap());
+        lock.refreshed -= REFRESH_INTERVAL;
+        assert!(matches!(lock.refresh().await.unwrap_err().deref(), ErrorKind::LockLost(_)));
+    }
+}
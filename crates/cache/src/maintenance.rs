@@ -0,0 +1,190 @@
+//! Database maintenance: statistics, checkpoints, vacuuming and reindexing.
+//!
+//! The cache sees bursts of writes (scans, organizes) followed by long idle
+//! periods. Long-running processes can check [`Database::needs_maintenance()`]
+//! when idle, and run [`Database::maintain()`] if it returns `true`.
+
+use crate::Database;
+use crate::error::{ErrorKind, Result};
+use exn::ResultExt;
+use rawr_storage::ByteSize;
+use sqlx::SqliteConnection;
+use std::path::PathBuf;
+use tracing::instrument;
+
+/// Free pages make up at least 1/`FREELIST_RATIO` of the database...
+const FREELIST_RATIO: i64 = 5;
+/// ... and there are at least this many of them (tiny databases aren't worth vacuuming).
+const FREELIST_MIN_PAGES: i64 = 256;
+/// Size at which the write-ahead log is worth truncating.
+const WAL_THRESHOLD: u64 = 16 * 1024 * 1024;
+/// Number of pages at which the query planner benefits from statistics.
+const ANALYZE_MIN_PAGES: i64 = 64;
+
+/// What [`Database::maintain()`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaintenanceReport {
+    /// Size of the database (and its write-ahead log) before maintenance, in bytes.
+    pub size_before: u64,
+    /// Size of the database (and its write-ahead log) after maintenance, in bytes.
+    pub size_after: u64,
+    /// The write-ahead log was checkpointed and truncated.
+    pub checkpointed: bool,
+    /// Free pages were released by a full `VACUUM`.
+    pub vacuumed: bool,
+    /// Indexes were rebuilt, because an integrity check found problems with them.
+    pub reindexed: bool,
+}
+impl MaintenanceReport {
+    /// Bytes of disk space reclaimed by maintenance.
+    pub fn reclaimed(&self) -> u64 {
+        self.size_before.saturating_sub(self.size_after)
+    }
+}
+
+struct Stats {
+    page_size: i64,
+    page_count: i64,
+    freelist_count: i64,
+    wal: Option<PathBuf>,
+}
+impl Stats {
+    async fn read(conn: &mut SqliteConnection) -> Result<Self> {
+        let pragma = async |conn: &mut SqliteConnection, name: &str| -> Result<i64> {
+            sqlx::query_scalar(&format!("PRAGMA {name}")).fetch_one(conn).await.or_raise(|| ErrorKind::Database)
+        };
+        let files: Vec<(i64, String, String)> =
+            sqlx::query_as("PRAGMA database_list").fetch_all(&mut *conn).await.or_raise(|| ErrorKind::Database)?;
+        Ok(Self {
+            page_size: pragma(conn, "page_size").await?,
+            page_count: pragma(conn, "page_count").await?,
+            freelist_count: pragma(conn, "freelist_count").await?,
+            // In-memory databases have an empty filename (and no WAL).
+            wal: files
+                .into_iter()
+                .find(|(_, name, file)| name == "main" && !file.is_empty())
+                .map(|(_, _, file)| PathBuf::from(format!("{file}-wal"))),
+        })
+    }
+
+    fn wal_size(&self) -> u64 {
+        self.wal.as_ref().and_then(|wal| wal.metadata().ok()).map_or(0, |m| m.len())
+    }
+
+    fn size(&self) -> u64 {
+        u64::try_from(self.page_size.saturating_mul(self.page_count)).unwrap_or(0) + self.wal_size()
+    }
+
+    fn fragmented(&self) -> bool {
+        self.freelist_count >= FREELIST_MIN_PAGES
+            && self.freelist_count.saturating_mul(FREELIST_RATIO) >= self.page_count
+    }
+}
+
+impl Database {
+    /// Cheaply estimates whether [`maintain()`](Self::maintain) is worth
+    /// running: the database has a lot of free pages, the write-ahead log has
+    /// grown large, or the query planner has no statistics yet.
+    ///
+    /// Only reads a handful of PRAGMAs and the size of the write-ahead log.
+    pub async fn needs_maintenance(&self) -> Result<bool> {
+        let mut conn = self.pool().acquire().await.or_raise(|| ErrorKind::Database)?;
+        let stats = Stats::read(&mut conn).await?;
+        if stats.fragmented() || stats.wal_size() >= WAL_THRESHOLD {
+            return Ok(true);
+        }
+        let analyzed: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'sqlite_stat1')")
+                .fetch_one(&mut *conn)
+                .await
+                .or_raise(|| ErrorKind::Database)?;
+        Ok(!analyzed && stats.page_count >= ANALYZE_MIN_PAGES)
+    }
+
+    /// Runs routine maintenance, returning what was done and how much space
+    /// was reclaimed.
+    ///
+    /// 1. Updates query planner statistics (`ANALYZE`).
+    /// 2. Checkpoints and truncates the write-ahead log.
+    /// 3. Releases free pages with a full `VACUUM` (the cache doesn't use
+    ///    auto-vacuum), but only when enough of the database is free pages to
+    ///    be worth rewriting it.
+    /// 4. Rebuilds indexes (`REINDEX`), but only if an integrity check reports
+    ///    problems.
+    ///
+    /// A full `VACUUM` rewrites the entire database and blocks writers while
+    /// it runs; prefer calling this while idle. Returns [`ErrorKind::ReadOnly`]
+    /// for [read-only](Self::connect_read_only) databases.
+    #[instrument(skip_all)]
+    pub async fn maintain(&self) -> Result<MaintenanceReport> {
+        if self.is_read_only() {
+            exn::bail!(ErrorKind::ReadOnly);
+        }
+        let mut conn = self.pool().acquire().await.or_raise(|| ErrorKind::Database)?;
+        let before = Stats::read(&mut conn).await?;
+        let mut report = MaintenanceReport {
+            size_before: before.size(),
+            ..MaintenanceReport::default()
+        };
+
+        sqlx::query("ANALYZE").execute(&mut *conn).await.or_raise(|| ErrorKind::Database)?;
+
+        let (busy, log, _checkpointed): (i64, i64, i64) = sqlx::query_as("PRAGMA wal_checkpoint(TRUNCATE)")
+            .fetch_one(&mut *conn)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        // A log of -1 means the database isn't in WAL mode (in-memory databases).
+        report.checkpointed = busy == 0 && log >= 0;
+
+        if before.fragmented() {
+            sqlx::query("VACUUM").execute(&mut *conn).await.or_raise(|| ErrorKind::Database)?;
+            report.vacuumed = true;
+        }
+
+        // Unlike `quick_check`, `integrity_check` also verifies that indexes
+        // match their tables: exactly the problems `REINDEX` can fix.
+        let problems: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+            .fetch_all(&mut *conn)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        if problems.iter().any(|p| p != "ok") {
+            tracing::warn!(problems = problems.len(), "Cache database integrity check failed; rebuilding indexes");
+            sqlx::query("REINDEX").execute(&mut *conn).await.or_raise(|| ErrorKind::Database)?;
+            report.reindexed = true;
+        }
+
+        report.size_after = Stats::read(&mut conn).await?.size();
+        tracing::info!(
+            reclaimed = %ByteSize(report.reclaimed()),
+            vacuumed = report.vacuumed,
+            reindexed = report.reindexed,
+            "Cache database maintenance complete",
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_maintenance() {
+        let db = Database::connect_in_memory().await.unwrap();
+        assert!(!db.needs_maintenance().await.unwrap());
+
+        // Fill, then empty, a table to leave plenty of free pages behind.
+        sqlx::query("CREATE TABLE filler (data BLOB)").execute(db.pool()).await.unwrap();
+        for _ in 0..200 {
+            sqlx::query("INSERT INTO filler VALUES (zeroblob(8192))").execute(db.pool()).await.unwrap();
+        }
+        sqlx::query("DELETE FROM filler").execute(db.pool()).await.unwrap();
+        assert!(db.needs_maintenance().await.unwrap());
+
+        let report = db.maintain().await.unwrap();
+        assert!(report.vacuumed);
+        assert!(!report.reindexed);
+        assert!(report.reclaimed() > 200 * 8192);
+        assert!(!db.needs_maintenance().await.unwrap());
+    }
+}
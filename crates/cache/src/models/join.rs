@@ -14,6 +14,11 @@ impl From<FullJoinRow> for (FileRow, VersionRow) {
         (join.file, join.version)
     }
 }
+impl From<FullJoinRow> for (Option<FileRow>, VersionRow) {
+    fn from(join: FullJoinRow) -> Self {
+        (Some(join.file), join.version)
+    }
+}
 impl TryFrom<(FileRow, VersionRow)> for FullJoinRow {
     type Error = Error;
     fn try_from(pair: (FileRow, VersionRow)) -> Result<Self, Self::Error> {
@@ -59,6 +64,7 @@ where
     &'r str: sqlx::ColumnIndex<R>,
     String: sqlx::Type<R::Database> + sqlx::Decode<'r, R::Database>,
     i64: sqlx::Type<R::Database> + sqlx::Decode<'r, R::Database>,
+    bool: sqlx::Type<R::Database> + sqlx::Decode<'r, R::Database>,
     Option<String>: sqlx::Type<R::Database> + sqlx::Decode<'r, R::Database>,
     Option<i64>: sqlx::Type<R::Database> + sqlx::Decode<'r, R::Database>,
 {
@@ -96,6 +102,11 @@ where
         Ok(LeftJoinRow { file, version })
     }
 }
+impl From<LeftJoinRow> for (Option<FileRow>, VersionRow) {
+    fn from(join: LeftJoinRow) -> Self {
+        (join.file, join.version)
+    }
+}
 impl TryFrom<LeftJoinRow> for (Option<File>, Version) {
     type Error = Error;
     fn try_from(join: LeftJoinRow) -> Result<Self, Self::Error> {
@@ -116,3 +127,11 @@ impl TryFrom<(Option<&File>, &Version)> for LeftJoinRow {
         Ok(LeftJoinRow { file, version })
     }
 }
+
+/// A [`LeftJoinRow`] along with the version's position in a series.
+#[derive(sqlx::FromRow)]
+pub(crate) struct SeriesJoinRow {
+    pub(crate) position: i64,
+    #[sqlx(flatten)]
+    pub(crate) join: LeftJoinRow,
+}
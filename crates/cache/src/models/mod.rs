@@ -1,8 +1,12 @@
 mod file;
 mod join;
+mod summary;
 mod version;
 
 pub(crate) use self::file::FileRow;
 pub(crate) use self::join::FullJoinRow;
 pub(crate) use self::join::LeftJoinRow;
+pub(crate) use self::join::SeriesJoinRow;
+pub(crate) use self::summary::SummaryRow;
+pub use self::summary::VersionSummary;
 pub(crate) use self::version::VersionRow;
@@ -0,0 +1,62 @@
+use crate::error::{Error, ErrorKind};
+use crate::overrides::Overrides;
+use exn::ResultExt;
+use rawr_extract::models::{Author, Rating};
+use serde_json::from_str as from_json;
+use time::{Date, UtcDateTime};
+
+/// What listings of works show of a version, without the cost of decoding
+/// the rest of it (see [`Repository::list_work_summaries`](crate::Repository::list_work_summaries)).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionSummary {
+    /// Content hash of the version, to fetch the rest of it with.
+    pub hash: String,
+    pub work_id: u64,
+    pub title: String,
+    pub authors: Vec<Author>,
+    pub words: u64,
+    pub rating: Option<Rating>,
+    /// When the version was last updated on AO3.
+    pub updated: Date,
+}
+impl VersionSummary {
+    /// Replaces the fields `overrides` overrides (those a summary has).
+    pub(crate) fn apply(&mut self, overrides: &Overrides) {
+        if let Some(title) = &overrides.title {
+            self.title.clone_from(title);
+        }
+        if let Some(authors) = &overrides.authors {
+            self.authors.clone_from(authors);
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct SummaryRow {
+    pub(crate) content_hash: String,
+    pub(crate) work_id: i64,
+    pub(crate) title: String,
+    pub(crate) authors: String,
+    pub(crate) words: i64,
+    pub(crate) rating: Option<String>,
+    pub(crate) last_modified: i64,
+}
+impl TryFrom<SummaryRow> for VersionSummary {
+    type Error = Error;
+    fn try_from(row: SummaryRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            hash: row.content_hash,
+            work_id: u64::try_from(row.work_id).or_raise(|| ErrorKind::InvalidData("work id"))?,
+            title: row.title,
+            authors: from_json(&row.authors).or_raise(|| ErrorKind::InvalidData("authors"))?,
+            words: u64::try_from(row.words).or_raise(|| ErrorKind::InvalidData("words"))?,
+            rating: row
+                .rating
+                .map(|r| r.parse::<Rating>().or_raise(|| ErrorKind::InvalidData("rating")))
+                .transpose()?,
+            updated: UtcDateTime::from_unix_timestamp(row.last_modified)
+                .or_raise(|| ErrorKind::InvalidData("last modified date"))?
+                .date(),
+        })
+    }
+}
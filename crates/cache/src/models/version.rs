@@ -18,6 +18,9 @@ pub(crate) struct VersionRow {
     pub(crate) chapters_written: i64,
     #[sqlx(default)]
     pub(crate) chapters_total: Option<i64>,
+    /// Derived from the chapter counts; only ever written, never read back.
+    #[sqlx(default)]
+    pub(crate) complete: bool,
     pub(crate) words: i64,
     pub(crate) summary: Option<String>,
     pub(crate) rating: Option<String>,
@@ -27,6 +30,17 @@ pub(crate) struct VersionRow {
     pub(crate) last_modified: i64,
     pub(crate) tags: String,
     pub(crate) extracted_at: i64,
+    pub(crate) encoding: String,
+    #[sqlx(default)]
+    pub(crate) detected_lang: Option<String>,
+    #[sqlx(default)]
+    pub(crate) detected_lang_iso: Option<String>,
+    #[sqlx(default)]
+    pub(crate) detected_lang_confidence: Option<i64>,
+    #[sqlx(default)]
+    pub(crate) provenance: Option<String>,
+    #[sqlx(default)]
+    pub(crate) work_skin: Option<String>,
 }
 impl TryFrom<&Version> for VersionRow {
     type Error = Error;
@@ -42,6 +56,7 @@ impl TryFrom<&Version> for VersionRow {
             series: to_json(&version.metadata.series).or_raise(|| ErrorKind::InvalidData("series"))?,
             chapters_written: i64::from(version.metadata.chapters.written),
             chapters_total: version.metadata.chapters.total.map(i64::from),
+            complete: version.metadata.is_complete(),
             words: i64::try_from(version.metadata.words).or_raise(|| ErrorKind::InvalidData("words"))?,
             summary: version.metadata.summary.as_ref().map(|s| s.to_string()),
             rating: version.metadata.rating.map(|r| r.as_short_str().to_string()),
@@ -51,6 +66,12 @@ impl TryFrom<&Version> for VersionRow {
             last_modified: version.metadata.last_modified.midnight().as_utc().unix_timestamp(),
             tags: to_json(&version.metadata.tags).or_raise(|| ErrorKind::InvalidData("tags"))?,
             extracted_at: version.extracted_at.unix_timestamp(),
+            encoding: version.encoding.as_str().to_string(),
+            detected_lang: version.detected_language.as_ref().map(|d| d.language.name.clone()),
+            detected_lang_iso: version.detected_language.as_ref().and_then(|d| d.language.iso_code.clone()),
+            detected_lang_confidence: version.detected_language.as_ref().map(|d| i64::from(d.confidence)),
+            provenance: Some(to_json(&version.metadata.provenance).or_raise(|| ErrorKind::InvalidData("provenance"))?),
+            work_skin: version.metadata.work_skin.clone(),
         })
     }
 }
@@ -61,6 +82,14 @@ impl TryFrom<VersionRow> for Version {
             hash: row.content_hash,
             crc32: u32::try_from(row.content_crc32).or_raise(|| ErrorKind::InvalidData("crc32"))?,
             length: u64::try_from(row.content_size).or_raise(|| ErrorKind::InvalidData("content length"))?,
+            encoding: row.encoding.parse::<extract::Encoding>().or_raise(|| ErrorKind::InvalidData("encoding"))?,
+            detected_language: match (row.detected_lang, row.detected_lang_confidence) {
+                (Some(name), Some(confidence)) => Some(extract::DetectedLanguage {
+                    language: extract::Language { name, iso_code: row.detected_lang_iso },
+                    confidence: u8::try_from(confidence).or_raise(|| ErrorKind::InvalidData("language confidence"))?,
+                }),
+                _ => None,
+            },
             metadata: extract::Metadata {
                 work_id: u64::try_from(row.work_id).or_raise(|| ErrorKind::InvalidData("work id"))?,
                 title: row.title,
@@ -89,6 +118,12 @@ impl TryFrom<VersionRow> for Version {
                 last_modified: UtcDateTime::from_unix_timestamp(row.last_modified)
                     .or_raise(|| ErrorKind::InvalidData("last modified date"))?
                     .date(),
+                provenance: row
+                    .provenance
+                    .map(|p| from_json(&p).or_raise(|| ErrorKind::InvalidData("provenance")))
+                    .transpose()?
+                    .unwrap_or_default(),
+                work_skin: row.work_skin,
             },
             extracted_at: UtcDateTime::from_unix_timestamp(row.extracted_at)
                 .or_raise(|| ErrorKind::InvalidData("extraction date"))?,
@@ -115,6 +150,7 @@ mod tests {
             series: "[]".to_string(),
             chapters_written: 6,
             chapters_total: Some(6),
+            complete: true,
             words: 19375,
             summary: None,
             rating: Some("G".to_string()),
@@ -124,8 +160,21 @@ mod tests {
             last_modified: 820450800,
             tags: r#"[{"name":"Piglet (Winnie-the-Pooh)","kind":"Character"}]"#.to_string(),
             extracted_at: 1771177811,
+            encoding: "windows-1252".to_string(),
+            detected_lang: Some("Español".to_string()),
+            detected_lang_iso: Some("es".to_string()),
+            detected_lang_confidence: Some(92),
+            provenance: Some(r#"{"series":"inferred","title":"user_edited"}"#.to_string()),
+            work_skin: Some("#workskin .texting { font-family: monospace; }".to_string()),
         };
         let model = Version::try_from(row).unwrap();
+        assert_eq!(extract::Encoding::Windows1252, model.encoding);
+        let detected = model.detected_language.as_ref().unwrap();
+        assert_eq!((Some("es"), 92), (detected.language.iso_code.as_deref(), detected.confidence));
+        assert_eq!(extract::Provenance::UserEdited, model.metadata.provenance.get(extract::Field::Title));
+        assert_eq!(extract::Provenance::Inferred, model.metadata.provenance.get(extract::Field::Series));
+        assert_eq!(extract::Provenance::Extracted, model.metadata.provenance.get(extract::Field::Words));
+        assert!(model.metadata.work_skin.as_deref().is_some_and(|css| css.starts_with("#workskin")));
         assert!(matches!(
             model.metadata.tags.first(),
             Some(extract::Tag {
@@ -142,6 +191,8 @@ mod tests {
             hash: "692ed948ccd76c2230efe90175a519a3092b1862ab049704b7221738e56028ca".to_string(),
             crc32: 123,
             length: 1024,
+            encoding: extract::Encoding::Utf8,
+            detected_language: None,
             metadata: Metadata {
                 work_id: 12345,
                 title: "Winnie the Pooh's Teatime Cookbook".to_string(),
@@ -165,10 +216,14 @@ mod tests {
                     name: "Piglet (Winnie-the-Pooh)".to_string(),
                     kind: extract::TagKind::Character,
                 }],
+                provenance: extract::Provenances::default(),
+                work_skin: None,
             },
             extracted_at: UtcDateTime::now(),
         };
         let row = VersionRow::try_from(&model).unwrap();
         assert_eq!(row.published_on, published_on.midnight().as_utc().unix_timestamp());
+        assert!(row.complete);
+        assert_eq!(Some("{}"), row.provenance.as_deref());
     }
 }
@@ -0,0 +1,99 @@
+//! User corrections to the metadata of works.
+//!
+//! AO3 metadata is sometimes wrong, or goes stale: a work is orphaned, or a
+//! title is fixed after it was downloaded. Rather than editing the files (the
+//! source of truth), users record [`Overrides`] in the cache, which the
+//! [`Repository`](crate::Repository) applies over what was extracted whenever
+//! it returns a version of the work.
+
+use crate::error::{Error, ErrorKind, Result};
+use exn::ResultExt;
+use rawr_extract::models::{Author, Fandom, Field, Metadata, Tag};
+use serde_json::{from_str as from_json, to_string as to_json};
+
+/// Fields of a work's metadata replaced by the user, for every version of
+/// the work. `None` leaves the extracted value as-is.
+///
+/// Applied fields are marked as [user edited](rawr_extract::models::Provenance::UserEdited).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Overrides {
+    pub title: Option<String>,
+    pub authors: Option<Vec<Author>>,
+    pub fandoms: Option<Vec<Fandom>>,
+    pub tags: Option<Vec<Tag>>,
+}
+impl Overrides {
+    /// Whether no field is overridden.
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.authors.is_none() && self.fandoms.is_none() && self.tags.is_none()
+    }
+
+    /// Replaces the overridden fields of `metadata`.
+    pub fn apply(&self, metadata: &mut Metadata) {
+        if let Some(title) = &self.title {
+            metadata.edit(Field::Title, |m| m.title.clone_from(title));
+        }
+        if let Some(authors) = &self.authors {
+            metadata.edit(Field::Authors, |m| m.authors.clone_from(authors));
+        }
+        if let Some(fandoms) = &self.fandoms {
+            metadata.edit(Field::Fandoms, |m| m.fandoms.clone_from(fandoms));
+        }
+        if let Some(tags) = &self.tags {
+            metadata.edit(Field::Tags, |m| m.tags.clone_from(tags));
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub(crate) struct OverrideRow {
+    pub(crate) work_id: i64,
+    pub(crate) title: Option<String>,
+    pub(crate) authors: Option<String>,
+    pub(crate) fandoms: Option<String>,
+    pub(crate) tags: Option<String>,
+}
+impl OverrideRow {
+    pub(crate) fn new(work_id: u64, overrides: &Overrides) -> Result<Self> {
+        Ok(Self {
+            work_id: i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?,
+            title: overrides.title.clone(),
+            authors: overrides
+                .authors
+                .as_ref()
+                .map(to_json)
+                .transpose()
+                .or_raise(|| ErrorKind::InvalidData("authors"))?,
+            fandoms: overrides
+                .fandoms
+                .as_ref()
+                .map(to_json)
+                .transpose()
+                .or_raise(|| ErrorKind::InvalidData("fandoms"))?,
+            tags: overrides.tags.as_ref().map(to_json).transpose().or_raise(|| ErrorKind::InvalidData("tags"))?,
+        })
+    }
+}
+impl TryFrom<OverrideRow> for (u64, Overrides) {
+    type Error = Error;
+    fn try_from(row: OverrideRow) -> Result<Self> {
+        let work_id = u64::try_from(row.work_id).or_raise(|| ErrorKind::InvalidData("work id"))?;
+        Ok((
+            work_id,
+            Overrides {
+                title: row.title,
+                authors: row
+                    .authors
+                    .map(|a| from_json(&a))
+                    .transpose()
+                    .or_raise(|| ErrorKind::InvalidData("authors"))?,
+                fandoms: row
+                    .fandoms
+                    .map(|f| from_json(&f))
+                    .transpose()
+                    .or_raise(|| ErrorKind::InvalidData("fandoms"))?,
+                tags: row.tags.map(|t| from_json(&t)).transpose().or_raise(|| ErrorKind::InvalidData("tags"))?,
+            },
+        ))
+    }
+}
@@ -0,0 +1,124 @@
+//! Keyset pagination of large listings.
+//!
+//! Paging with `LIMIT`/`OFFSET` skips or repeats rows when others are
+//! inserted or deleted between pages (which scanning, in the background of a
+//! TUI, does constantly). Paginated listings instead continue *after* the
+//! last row of the previous page, identified by its sort key: rows inserted
+//! before that point are missed rather than shifting everything along, and
+//! rows after it are picked up as the iteration reaches them.
+//!
+//! The sort key is handed to the caller as an opaque [`Cursor`], which can
+//! be stored (as text) and used later; it's only valid for the kind of
+//! listing that produced it.
+
+use crate::error::{Error, ErrorKind, Result};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64;
+use exn::ResultExt;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+/// A page of results, and where the next one starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Continues the listing after this page; `None` when this page is the
+    /// last.
+    pub next: Option<Cursor>,
+}
+impl<T> Page<T> {
+    /// Builds a page from up to `limit + 1` items fetched in order: the extra
+    /// item (if any) is dropped, and only signals that there's a next page.
+    pub(crate) fn from_overfetched(mut items: Vec<T>, limit: usize, key: impl Fn(&T) -> Key) -> Self {
+        let next = match items.len() > limit {
+            true => {
+                items.truncate(limit);
+                items.last().map(|item| Cursor::new(&key(item)))
+            },
+            false => None,
+        };
+        Self { items, next }
+    }
+}
+
+/// An opaque position within a paginated listing.
+///
+/// Cursors survive being converted to text (with [`Display`]) and back (with
+/// [`FromStr`]), for example to be kept in a URL or a TUI's saved state.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Cursor(String);
+impl Cursor {
+    pub(crate) fn new(key: &Key) -> Self {
+        // Keys only hold strings and integers; serializing can't fail.
+        let json = serde_json::to_vec(key).unwrap_or_default();
+        Self(BASE64.encode(json))
+    }
+
+    pub(crate) fn key(&self) -> Result<Key> {
+        let json = BASE64.decode(&self.0).or_raise(|| ErrorKind::InvalidCursor)?;
+        serde_json::from_slice(&json).or_raise(|| ErrorKind::InvalidCursor)
+    }
+
+    /// The cursor as text; the same as its [`Display`] form.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+impl FromStr for Cursor {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let cursor = Self(s.to_string());
+        cursor.key()?;
+        Ok(cursor)
+    }
+}
+impl Display for Cursor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(&self.0)
+    }
+}
+
+/// The sort key of the last row of a page, for each kind of listing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Key {
+    /// Files of a target, by path.
+    Path(String),
+    /// Files of every target, most recently discovered first.
+    Recent {
+        discovered_at: i64,
+        target: String,
+        path: String,
+    },
+    /// Versions (grouped by work), by work ID.
+    Work(i64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let key = Key::Recent {
+            discovered_at: 1_700_000_000,
+            target: "local".to_string(),
+            path: "a/b.html".to_string(),
+        };
+        let cursor = Cursor::new(&key);
+        let parsed: Cursor = cursor.to_string().parse().unwrap();
+        assert_eq!(key, parsed.key().unwrap());
+        assert!("not a cursor".parse::<Cursor>().is_err());
+        assert!(BASE64.encode("{}").parse::<Cursor>().is_err());
+    }
+
+    #[test]
+    fn test_from_overfetched() {
+        let page = Page::from_overfetched(vec![1, 2, 3], 2, |i| Key::Work(*i));
+        assert_eq!(vec![1, 2], page.items);
+        assert_eq!(Key::Work(2), page.next.unwrap().key().unwrap());
+        let page = Page::from_overfetched(vec![1, 2], 2, |i| Key::Work(*i));
+        assert!(page.next.is_none());
+    }
+}
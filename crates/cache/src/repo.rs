@@ -4,18 +4,37 @@
 //! no point keeping a version if there's no physical file to extract it from
 //! (unless for historical record keeping).
 
-use crate::error::{ErrorKind, Result};
-use crate::models::{FileRow, FullJoinRow, LeftJoinRow, VersionRow};
+use crate::coalesce::WriteQueue;
+use crate::error::{Error, ErrorKind, Result};
+use crate::filter::{Filter, Sort};
+use crate::group::{fetch_grouped, group_by_version};
+use crate::hooks::{Hooks, RepositoryEvent, Subscription};
+use crate::lease::{self, LeaseInfo, WriterLease};
+use crate::lock::{self, LockInfo, TargetLock, new_token};
+use crate::models::{FileRow, FullJoinRow, LeftJoinRow, SeriesJoinRow, SummaryRow, VersionRow, VersionSummary};
+use crate::overrides::{OverrideRow, Overrides};
+use crate::page::{Cursor, Key, Page};
+use crate::timeline::{self, Timeline};
 use crate::{Database, File, Version};
-use exn::ResultExt;
-use rawr_storage::ValidatedPath;
-use sqlx::SqlitePool;
+use exn::{OptionExt, ResultExt};
+use rawr_extract::models::Author;
+use rawr_storage::chunk::Chunk;
+use rawr_storage::{Mode, ValidatedPath};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use sqlx::{SqliteConnection, SqlitePool};
 use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use time::{Date, UtcDateTime};
 use tracing::instrument;
 
 type FileResult = (File, Version);
+/// Key/value data an application keeps about a file or version, by key.
+pub type Attributes = BTreeMap<String, String>;
 type VersionResult = (Version, Vec<File>);
 
 /// Result of checking whether a file exists in the cache.
@@ -37,18 +56,190 @@ pub enum ExistenceResult {
     LocatedElsewhere(File, Version),
 }
 
-fn group_by_version<F: Into<Option<File>>>(
-    rows: impl IntoIterator<Item = Result<(F, Version)>>,
-) -> Result<Vec<VersionResult>> {
-    let mut map: HashMap<String, VersionResult> = HashMap::new();
-    for row in rows {
-        let (file, version) = row?;
-        let entry = map.entry(version.hash.clone()).or_insert_with(|| (version, Vec::new()));
-        if let Some(file) = file.into() {
-            entry.1.push(file);
-        }
+/// A series with at least one of its works in the library.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Series {
+    /// AO3 series ID.
+    pub id: u64,
+    /// Series name, as recorded by the most recently scanned work.
+    pub name: String,
+    /// Number of distinct works from this series in the library.
+    pub works: u64,
+    /// Highest position of any work in the library. The series may well be
+    /// longer; AO3 doesn't record the total number of parts on each work.
+    pub last_position: u32,
+}
+
+/// A named [`Filter`] saved in the cache, whose matching works are
+/// recomputed on demand.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SmartCollection {
+    /// Unique name of the collection.
+    pub name: String,
+    /// Saved filter that decides which works belong to the collection.
+    pub filter: Filter,
+    /// Number of distinct works in the collection as of its last refresh.
+    pub works: u64,
+    /// When membership was last computed; `None` if it never has been (or
+    /// the filter has changed since).
+    pub refreshed_at: Option<UtcDateTime>,
+}
+
+/// What the user last read of a work, recorded when it was
+/// [marked as read](Repository::mark_read).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ReadingStatus {
+    /// Canonical AO3 work ID.
+    pub work_id: u64,
+    pub read_at: UtcDateTime,
+    /// Chapters posted in the version that was read.
+    pub chapters_written: u32,
+    /// When the version that was read was last updated on AO3.
+    pub last_modified: Date,
+}
+impl TryFrom<(i64, i64, i64, i64)> for ReadingStatus {
+    type Error = Error;
+
+    fn try_from((work_id, read_at, chapters_written, last_modified): (i64, i64, i64, i64)) -> Result<Self> {
+        Ok(Self {
+            work_id: u64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?,
+            read_at: UtcDateTime::from_unix_timestamp(read_at).or_raise(|| ErrorKind::InvalidData("read at"))?,
+            chapters_written: u32::try_from(chapters_written).or_raise(|| ErrorKind::InvalidData("chapters"))?,
+            last_modified: UtcDateTime::from_unix_timestamp(last_modified)
+                .or_raise(|| ErrorKind::InvalidData("last modified"))?
+                .date(),
+        })
+    }
+}
+
+/// A scan of a target that hasn't completed: it's still running, or was
+/// interrupted (and can be resumed from its checkpoint).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ScanRun {
+    pub run_id: String,
+    pub target: String,
+    /// Subdirectory of the target being scanned, if not all of it.
+    pub prefix: Option<PathBuf>,
+    /// Path of the last file listed that, along with every file listed before
+    /// it, has been scanned; `None` until the first is.
+    pub checkpoint: Option<PathBuf>,
+    /// Number of files scanned up to the checkpoint.
+    pub scanned: u64,
+    pub started_at: UtcDateTime,
+    /// When the checkpoint was last saved.
+    pub updated_at: UtcDateTime,
+}
+type ScanRunRow = (String, String, Option<String>, Option<String>, i64, i64, i64);
+impl TryFrom<ScanRunRow> for ScanRun {
+    type Error = Error;
+
+    fn try_from((run_id, target, prefix, checkpoint, scanned, started_at, updated_at): ScanRunRow) -> Result<Self> {
+        Ok(Self {
+            run_id,
+            target,
+            prefix: prefix.map(PathBuf::from),
+            checkpoint: checkpoint.map(PathBuf::from),
+            scanned: u64::try_from(scanned).or_raise(|| ErrorKind::InvalidData("files scanned"))?,
+            started_at: UtcDateTime::from_unix_timestamp(started_at)
+                .or_raise(|| ErrorKind::InvalidData("started at"))?,
+            updated_at: UtcDateTime::from_unix_timestamp(updated_at)
+                .or_raise(|| ErrorKind::InvalidData("updated at"))?,
+        })
+    }
+}
+
+/// A work the user deliberately deleted, recorded so it isn't added back.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Tombstone {
+    /// Canonical AO3 work ID.
+    pub work_id: u64,
+    /// Why the work was deleted, if the user said.
+    pub reason: Option<String>,
+    pub deleted_at: UtcDateTime,
+}
+type TombstoneRow = (i64, Option<String>, i64);
+impl TryFrom<TombstoneRow> for Tombstone {
+    type Error = Error;
+
+    fn try_from((work_id, reason, deleted_at): TombstoneRow) -> Result<Self> {
+        Ok(Self {
+            work_id: u64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?,
+            reason,
+            deleted_at: UtcDateTime::from_unix_timestamp(deleted_at)
+                .or_raise(|| ErrorKind::InvalidData("deleted at"))?,
+        })
+    }
+}
+
+/// The latest run of a scheduled job.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct JobRun {
+    pub name: String,
+    pub started_at: UtcDateTime,
+    /// When the run finished; `None` while it's running, or if it was
+    /// interrupted.
+    pub finished_at: Option<UtcDateTime>,
+    /// Why the run failed, if it did.
+    pub error: Option<String>,
+}
+impl JobRun {
+    /// Whether the run finished without error.
+    pub fn succeeded(&self) -> bool {
+        self.finished_at.is_some() && self.error.is_none()
+    }
+}
+type JobRunRow = (String, i64, Option<i64>, Option<String>);
+impl TryFrom<JobRunRow> for JobRun {
+    type Error = Error;
+
+    fn try_from((name, started_at, finished_at, error): JobRunRow) -> Result<Self> {
+        Ok(Self {
+            name,
+            started_at: UtcDateTime::from_unix_timestamp(started_at)
+                .or_raise(|| ErrorKind::InvalidData("started at"))?,
+            finished_at: finished_at
+                .map(UtcDateTime::from_unix_timestamp)
+                .transpose()
+                .or_raise(|| ErrorKind::InvalidData("finished at"))?,
+            error,
+        })
+    }
+}
+
+/// A work with a newer version in the library than the one the user read.
+#[derive(Debug, Clone)]
+pub struct UpdatedWork {
+    /// What was read.
+    pub status: ReadingStatus,
+    /// The best version now in the library, and the files containing it.
+    pub version: Version,
+    pub files: Vec<File>,
+}
+impl UpdatedWork {
+    /// Chapters posted since the work was read (zero if the work was only
+    /// edited, or chapters were removed).
+    pub fn new_chapters(&self) -> u32 {
+        self.version.metadata.chapters.written.saturating_sub(self.status.chapters_written)
+    }
+}
+
+/// The `LIMIT` of a paginated query: one more than the page holds, to tell
+/// whether there's another page after it.
+fn overfetch(limit: usize) -> Result<i64> {
+    if limit == 0 {
+        exn::bail!(ErrorKind::InvalidData("limit"));
     }
-    Ok(map.into_values().collect())
+    i64::try_from(limit.saturating_add(1)).or_raise(|| ErrorKind::InvalidData("limit"))
+}
+
+/// Events reporting the deletion of versions (and the files referencing them).
+fn versions_deleted(results: Vec<VersionResult>) -> Vec<RepositoryEvent> {
+    let mut events = Vec::new();
+    for (version, files) in results {
+        events.extend(files.into_iter().map(|file| RepositoryEvent::FileDeleted { file, version: version.clone() }));
+        events.push(RepositoryEvent::VersionDeleted(version));
+    }
+    events
 }
 
 /// Repository for managing File and Version entries in the cache database.
@@ -57,9 +248,14 @@ fn group_by_version<F: Into<Option<File>>>(
 /// locations in storage targets by path, while versions track unique content
 /// (identified by BLAKE3 hash of decompressed HTML) and its extracted metadata.
 ///
-/// When `dry_run` is enabled, write operations (inserts, updates, deletes)
+/// In a [dry run](Mode::DryRun), write operations (inserts, updates, deletes)
 /// still validate their inputs but skip the actual database mutation,
-/// returning the same values they would on success.
+/// returning the same values they would on success. [Read-only](Self::read_only)
+/// repositories return [`ErrorKind::ReadOnly`] from write operations instead
+/// (unless in dry-run mode).
+///
+/// Applications can [subscribe](Self::subscribe) to be notified of changes
+/// made through the repository (or any of its clones).
 ///
 /// # Relationships
 /// - Many files can reference the same version (duplicate content at different paths)
@@ -70,23 +266,113 @@ fn group_by_version<F: Into<Option<File>>>(
 #[derive(Debug, Clone)]
 pub struct Repository {
     pool: SqlitePool,
-    dry_run: bool,
+    mode: Mode,
+    read_only: bool,
+    hooks: Hooks,
+    queue: Option<Arc<WriteQueue>>,
 }
 impl From<&Database> for Repository {
+    /// Creates a repository for the database, which is read-only if the
+    /// database was [opened read-only](Database::connect_read_only).
     fn from(db: &Database) -> Self {
-        Self { pool: db.pool().clone(), dry_run: false }
+        match db.is_read_only() {
+            true => Self::read_only(db.pool().clone()),
+            false => Self::new(db.pool().clone(), false),
+        }
     }
 }
 impl Repository {
-    /// Create a new repository with the given connection pool.
-    pub fn new(pool: SqlitePool, dry_run: bool) -> Self {
-        Self { pool, dry_run }
+    /// Create a new repository with the given connection pool, making
+    /// changes or only validating them depending on `mode` (`true` for a
+    /// [dry run](Mode::DryRun)).
+    pub fn new(pool: SqlitePool, mode: impl Into<Mode>) -> Self {
+        Self {
+            pool,
+            mode: mode.into(),
+            read_only: false,
+            hooks: Hooks::default(),
+            queue: None,
+        }
+    }
+
+    /// Create a repository that only reads, for processes querying a cache
+    /// that another process writes to.
+    ///
+    /// Write operations return [`ErrorKind::ReadOnly`] without touching the
+    /// database, rather than waiting for the write lock (or failing on a
+    /// [read-only connection](Database::connect_read_only)).
+    pub fn read_only(pool: SqlitePool) -> Self {
+        Self {
+            read_only: true,
+            ..Self::new(pool, false)
+        }
+    }
+
+    /// Whether write operations are refused.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Whether write operations are made, or only validated.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The same repository (sharing its pool and hooks), making changes or
+    /// only validating them depending on `mode`.
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Whether a write should be skipped (in dry-run mode) rather than made.
+    /// Fails for read-only repositories.
+    fn skip_write(&self) -> Result<bool> {
+        if self.read_only && !self.mode.is_dry_run() {
+            exn::bail!(ErrorKind::ReadOnly);
+        }
+        Ok(self.mode.is_dry_run())
+    }
+
+    /// Like [`skip_write`](Self::skip_write), for writes other than upserts,
+    /// which first write any [queued](Self::with_write_coalescing) upserts:
+    /// otherwise, those would be written after (and could undo) the write.
+    async fn begin_write(&self) -> Result<bool> {
+        let skip = self.skip_write()?;
+        if !skip && let Some(queue) = &self.queue {
+            self.flush_queue(queue).await?;
+        }
+        Ok(skip)
     }
 
     fn sqlx_hates_paths(path: impl AsRef<Path>) -> Result<String> {
         Ok(ValidatedPath::new(path).or_raise(|| ErrorKind::InvalidData("path"))?.into())
     }
 
+    /* ===== *\
+    |  Hooks  |
+    \* ===== */
+
+    /// Registers a hook to be called with each change made through this
+    /// repository, or any of its clones.
+    ///
+    /// Hooks run after a change is committed, and the method making it
+    /// waits for them to finish. See [`RepositoryEvent`] for what's reported.
+    pub fn subscribe<F, Fut>(&self, hook: F) -> Subscription
+    where
+        F: Fn(RepositoryEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks.subscribe(hook)
+    }
+
+    /// Removes a hook registered with [`subscribe()`](Self::subscribe).
+    ///
+    /// Returns `false` if it had already been removed.
+    pub fn unsubscribe(&self, subscription: Subscription) -> bool {
+        self.hooks.unsubscribe(subscription)
+    }
+
     /* ============== *\
     |  Upsert Methods  |
     \* ============== */
@@ -99,6 +385,12 @@ impl Repository {
     ///
     /// Returns [`ErrorKind::Constraint`] if the file's content hash does not
     /// match the version's content hash.
+    ///
+    /// If the repository [coalesces writes](Self::with_write_coalescing),
+    /// the records are validated and queued rather than written (so they
+    /// can't be read back until they are), and hooks are notified once
+    /// they're written. The upsert that fills the queue writes it, waiting
+    /// for any batch already being written first.
     #[instrument(skip_all, fields(
         target = file.target,
         path = %file.path.display(),
@@ -110,12 +402,34 @@ impl Repository {
         if file.content_hash != version.hash {
             exn::bail!(ErrorKind::Constraint);
         }
-        if self.dry_run {
+        if self.skip_write()? {
             return Ok(());
         }
         let version_row = VersionRow::try_from(version)?;
         let file_row = FileRow::try_from(file)?;
+        if let Some(queue) = &self.queue {
+            return self.enqueue(queue, file, version).await;
+        }
         let mut tx = self.pool.begin().await.or_raise(|| ErrorKind::Database)?;
+        Self::write_upsert(&mut tx, version, version_row, file_row).await?;
+        tx.commit().await.or_raise(|| ErrorKind::Database)?;
+        self.hooks
+            .notify([RepositoryEvent::Upserted {
+                file: file.clone(),
+                version: version.clone(),
+            }])
+            .await;
+        Ok(())
+    }
+
+    /// Writes a file and its version, as [`upsert`](Self::upsert) does, on
+    /// a connection (in a transaction).
+    async fn write_upsert(
+        conn: &mut SqliteConnection,
+        version: &Version,
+        version_row: VersionRow,
+        file_row: FileRow,
+    ) -> Result<()> {
         sqlx::query(include_str!("../queries/upsert_version.sql"))
             .bind(version_row.content_hash)
             .bind(version_row.content_crc32)
@@ -127,6 +441,7 @@ impl Repository {
             .bind(version_row.series)
             .bind(version_row.chapters_written)
             .bind(version_row.chapters_total)
+            .bind(version_row.complete)
             .bind(version_row.words)
             .bind(version_row.summary)
             .bind(version_row.rating)
@@ -136,9 +451,31 @@ impl Repository {
             .bind(version_row.last_modified)
             .bind(version_row.tags)
             .bind(version_row.extracted_at)
-            .execute(&mut *tx)
+            .bind(version_row.encoding)
+            .bind(version_row.detected_lang)
+            .bind(version_row.detected_lang_iso)
+            .bind(version_row.detected_lang_confidence)
+            .bind(version_row.provenance)
+            .bind(version_row.work_skin)
+            .execute(&mut *conn)
             .await
             .or_raise(|| ErrorKind::Database)?;
+        for series in &version.metadata.series {
+            let id = i64::try_from(series.id).or_raise(|| ErrorKind::InvalidData("series id"))?;
+            sqlx::query(include_str!("../queries/upsert_series.sql"))
+                .bind(id)
+                .bind(&series.name)
+                .execute(&mut *conn)
+                .await
+                .or_raise(|| ErrorKind::Database)?;
+            sqlx::query(include_str!("../queries/upsert_series_version.sql"))
+                .bind(id)
+                .bind(&version.hash)
+                .bind(i64::from(series.position))
+                .execute(&mut *conn)
+                .await
+                .or_raise(|| ErrorKind::Database)?;
+        }
         sqlx::query(include_str!("../queries/upsert_file.sql"))
             .bind(file_row.target)
             .bind(file_row.path)
@@ -147,13 +484,111 @@ impl Repository {
             .bind(file_row.file_hash)
             .bind(file_row.content_hash)
             .bind(file_row.discovered_at)
-            .execute(&mut *tx)
+            .execute(&mut *conn)
             .await
             .or_raise(|| ErrorKind::Database)?;
-        tx.commit().await.or_raise(|| ErrorKind::Database)?;
         Ok(())
     }
 
+    /* ================ *\
+    |  Write Coalescing  |
+    \* ================ */
+
+    /// Queues upserts, writing them in batches of up to `max_batch` (each in
+    /// one transaction) rather than one at a time. See [`upsert`](Self::upsert)
+    /// and [`flush`](Self::flush).
+    ///
+    /// Shared by the clones made of the repository afterwards. Other writes
+    /// aren't queued: they write the queue first, so that they're made after
+    /// the upserts queued before them.
+    pub fn with_write_coalescing(mut self, max_batch: usize, interval: Duration) -> Self {
+        self.queue = Some(Arc::new(WriteQueue::new(max_batch, interval)));
+        self
+    }
+
+    /// Writes any upserts [queued](Self::with_write_coalescing) (and not yet
+    /// written), waiting for them to be. Does nothing if writes aren't
+    /// coalesced.
+    ///
+    /// Also returns the error of any batch that failed to be written in the
+    /// background since the last flush. A batch that fails is retried one
+    /// upsert at a time, so only the upserts that fail on their own are lost.
+    pub async fn flush(&self) -> Result<()> {
+        let Some(queue) = &self.queue else {
+            return Ok(());
+        };
+        let flushed = self.flush_queue(queue).await;
+        let background = queue.pending().error.take();
+        flushed?;
+        background.map_or(Ok(()), Err)
+    }
+
+    async fn enqueue(&self, queue: &Arc<WriteQueue>, file: &File, version: &Version) -> Result<()> {
+        let (full, timer) = {
+            let mut pending = queue.pending();
+            pending.writes.push((file.clone(), version.clone()));
+            let timer = !std::mem::replace(&mut pending.timer, true);
+            (pending.writes.len() >= queue.max_batch, timer)
+        };
+        if timer {
+            let (repository, queue) = (self.clone(), Arc::clone(queue));
+            tokio::spawn(async move {
+                tokio::time::sleep(queue.interval).await;
+                queue.pending().timer = false;
+                if let Err(e) = repository.flush_queue(&queue).await {
+                    tracing::warn!(error = ?e, "Could not write queued upserts");
+                    queue.pending().error.get_or_insert(e);
+                }
+            });
+        }
+        match full {
+            true => self.flush_queue(queue).await,
+            false => Ok(()),
+        }
+    }
+
+    /// Writes the queued upserts in one transaction, or (if that fails) one
+    /// at a time, returning the first error.
+    async fn flush_queue(&self, queue: &WriteQueue) -> Result<()> {
+        let _flushing = queue.flushing.lock().await;
+        let writes = std::mem::take(&mut queue.pending().writes);
+        if writes.is_empty() {
+            return Ok(());
+        }
+        let batch = async {
+            let mut tx = self.pool.begin().await.or_raise(|| ErrorKind::Database)?;
+            for (file, version) in &writes {
+                Self::write_upsert(&mut tx, version, version.try_into()?, file.try_into()?).await?;
+            }
+            tx.commit().await.or_raise(|| ErrorKind::Database)
+        };
+        let mut first_error = None;
+        let written = match batch.await {
+            Ok(()) => writes,
+            Err(e) => {
+                tracing::debug!(error = ?e, writes = writes.len(), "Batch of upserts failed; writing one at a time");
+                let mut written = Vec::with_capacity(writes.len());
+                for (file, version) in writes {
+                    let single = async {
+                        let mut tx = self.pool.begin().await.or_raise(|| ErrorKind::Database)?;
+                        Self::write_upsert(&mut tx, &version, (&version).try_into()?, (&file).try_into()?).await?;
+                        tx.commit().await.or_raise(|| ErrorKind::Database)
+                    };
+                    match single.await {
+                        Ok(()) => written.push((file, version)),
+                        Err(e) => _ = first_error.get_or_insert(e),
+                    }
+                }
+                written
+            },
+        };
+        tracing::debug!(writes = written.len(), "Queued upserts written");
+        self.hooks
+            .notify(written.into_iter().map(|(file, version)| RepositoryEvent::Upserted { file, version }))
+            .await;
+        first_error.map_or(Ok(()), Err)
+    }
+
     /* ================ *\
     |  Fetching Methods  |
     \* ================ */
@@ -173,7 +608,9 @@ impl Repository {
             .fetch_optional(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
-        row.map(|r| r.try_into()).transpose()
+        let mut result: Option<FileResult> = row.map(|r| r.try_into()).transpose()?;
+        self.apply_overrides(result.iter_mut().map(|(_, v)| v)).await?;
+        Ok(result)
     }
 
     /// Look up all file records matching a relative path, regardless of target.
@@ -183,7 +620,9 @@ impl Repository {
             .fetch_all(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
-        row.into_iter().map(|r| r.try_into()).collect::<Result<Vec<_>>>()
+        let mut files = row.into_iter().map(|r| r.try_into()).collect::<Result<Vec<FileResult>>>()?;
+        self.apply_overrides(files.iter_mut().map(|(_, v)| v)).await?;
+        Ok(files)
     }
 
     /// Get all files and their versions matching a file hash within a storage target.
@@ -204,7 +643,9 @@ impl Repository {
             .fetch_all(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
-        rows.into_iter().map(|r| r.try_into()).collect()
+        let mut files = rows.into_iter().map(|r| r.try_into()).collect::<Result<Vec<FileResult>>>()?;
+        self.apply_overrides(files.iter_mut().map(|(_, v)| v)).await?;
+        Ok(files)
     }
 
     /// Get all files and their versions matching a file hash across all storage targets.
@@ -220,7 +661,9 @@ impl Repository {
             .fetch_all(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
-        rows.into_iter().map(|r| r.try_into()).collect()
+        let mut files = rows.into_iter().map(|r| r.try_into()).collect::<Result<Vec<FileResult>>>()?;
+        self.apply_overrides(files.iter_mut().map(|(_, v)| v)).await?;
+        Ok(files)
     }
 
     /// Get a version and all files that reference it by content hash.
@@ -230,16 +673,12 @@ impl Repository {
     /// exists at different paths, with different compression formats, or in different targets.
     pub async fn get_by_content_hash(&self, content_hash: impl AsRef<str>) -> Result<Option<VersionResult>> {
         let content_hash = content_hash.as_ref();
-        let rows: Vec<LeftJoinRow> = sqlx::query_as(include_str!("../queries/get_by_content_hash.sql"))
+        let rows = sqlx::query_as::<_, LeftJoinRow>(include_str!("../queries/get_by_content_hash.sql"))
             .bind(content_hash)
-            .fetch_all(&self.pool)
-            .await
-            .or_raise(|| ErrorKind::Database)?;
-        if rows.is_empty() {
-            return Ok(None);
-        }
-        let pairs = rows.into_iter().map(|r| r.try_into());
-        Ok(group_by_version(pairs)?.into_iter().next())
+            .fetch(&self.pool);
+        let mut result = fetch_grouped(rows).await?.into_iter().next();
+        self.apply_overrides(result.iter_mut().map(|(v, _)| v)).await?;
+        Ok(result)
     }
 
     /// Get all versions and their files for a given AO3 work ID.
@@ -248,16 +687,17 @@ impl Repository {
     /// times (e.g., before and after the author added chapters). Each version
     /// may have multiple files if duplicates exist (possibly across targets).
     ///
+    /// Versions recorded under any [alias](Self::register_alias) of the work
+    /// are included, whether `work_id` is the canonical ID or one of its aliases.
+    ///
     /// Results are sorted by the version comparison algorithm (best/newest first).
     pub async fn get_by_work_id(&self, work_id: u64) -> Result<Vec<VersionResult>> {
         let work_id = i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?;
-        let rows: Vec<LeftJoinRow> = sqlx::query_as(include_str!("../queries/get_by_work_id.sql"))
+        let rows = sqlx::query_as::<_, LeftJoinRow>(include_str!("../queries/get_by_work_id.sql"))
             .bind(work_id)
-            .fetch_all(&self.pool)
-            .await
-            .or_raise(|| ErrorKind::Database)?;
-        let pairs = rows.into_iter().map(|r| r.try_into());
-        let mut map = group_by_version(pairs)?;
+            .fetch(&self.pool);
+        let mut map = fetch_grouped(rows).await?;
+        self.apply_overrides(map.iter_mut().map(|(v, _)| v)).await?;
         // TODO: If Ordering::Less the correct value? Or Greater? I should write some tests for that... eventually...
         map.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Less));
         Ok(map)
@@ -295,13 +735,12 @@ impl Repository {
     /// Returns a list of (version, files) tuples. Each version appears once
     /// with all files that reference it within the target.
     pub async fn list_versions_for_target(&self, target: impl AsRef<str>) -> Result<Vec<VersionResult>> {
-        let rows: Vec<FullJoinRow> = sqlx::query_as(include_str!("../queries/list_versions_for_target.sql"))
+        let rows = sqlx::query_as::<_, FullJoinRow>(include_str!("../queries/list_versions_for_target.sql"))
             .bind(target.as_ref())
-            .fetch_all(&self.pool)
-            .await
-            .or_raise(|| ErrorKind::Database)?;
-        let pairs = rows.into_iter().map(|r| r.try_into());
-        group_by_version(pairs)
+            .fetch(&self.pool);
+        let mut versions = fetch_grouped(rows).await?;
+        self.apply_overrides(versions.iter_mut().map(|(v, _)| v)).await?;
+        Ok(versions)
     }
 
     /// List all files for a specific target.
@@ -313,7 +752,30 @@ impl Repository {
             .fetch_all(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
-        rows.into_iter().map(|r| r.try_into()).collect()
+        let mut files = rows.into_iter().map(|r| r.try_into()).collect::<Result<Vec<FileResult>>>()?;
+        self.apply_overrides(files.iter_mut().map(|(_, v)| v)).await?;
+        Ok(files)
+    }
+
+    /// List all files for a specific target whose work is (or isn't) complete.
+    ///
+    /// A work is complete once its posted chapters reach the planned total;
+    /// works with an unknown total (`?`) are always works-in-progress.
+    pub async fn list_files_for_target_by_completeness(
+        &self,
+        target: impl AsRef<str>,
+        complete: bool,
+    ) -> Result<Vec<FileResult>> {
+        let rows: Vec<FullJoinRow> =
+            sqlx::query_as(include_str!("../queries/list_files_for_target_by_completeness.sql"))
+                .bind(target.as_ref())
+                .bind(complete)
+                .fetch_all(&self.pool)
+                .await
+                .or_raise(|| ErrorKind::Database)?;
+        let mut files = rows.into_iter().map(|r| r.try_into()).collect::<Result<Vec<FileResult>>>()?;
+        self.apply_overrides(files.iter_mut().map(|(_, v)| v)).await?;
+        Ok(files)
     }
 
     /// List all file paths for a specific target.
@@ -329,6 +791,32 @@ impl Repository {
         Ok(paths)
     }
 
+    /// List the paths of a target's [stale](Self::mark_stale) files: those
+    /// waiting to be re-read by the next full scan.
+    pub async fn list_stale_paths_for_target(&self, target: impl AsRef<str>) -> Result<Vec<String>> {
+        let paths: Vec<String> = sqlx::query_scalar(include_str!("../queries/list_stale_paths_for_target.sql"))
+            .bind(target.as_ref())
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(paths)
+    }
+
+    /// List the files of a target whose verification is stale: those not
+    /// [verified](Self::mark_verified) intact since `older_than` (including
+    /// files never verified at all), sorted by path.
+    pub async fn list_stale(&self, target: impl AsRef<str>, older_than: UtcDateTime) -> Result<Vec<FileResult>> {
+        let rows: Vec<FullJoinRow> = sqlx::query_as(include_str!("../queries/list_unverified_files_for_target.sql"))
+            .bind(target.as_ref())
+            .bind(older_than.unix_timestamp())
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        let mut files = rows.into_iter().map(|r| r.try_into()).collect::<Result<Vec<FileResult>>>()?;
+        self.apply_overrides(files.iter_mut().map(|(_, v)| v)).await?;
+        Ok(files)
+    }
+
     /// List recently extracted files with their versions, ordered by extraction time.
     ///
     /// Useful for showing a picker of recent works.
@@ -340,36 +828,1587 @@ impl Repository {
             .fetch_all(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
-        rows.into_iter().map(|r| r.try_into()).collect::<Result<Vec<_>>>()
+        let mut files = rows.into_iter().map(|r| r.try_into()).collect::<Result<Vec<FileResult>>>()?;
+        self.apply_overrides(files.iter_mut().map(|(_, v)| v)).await?;
+        Ok(files)
+    }
+
+    /// List a page of up to `limit` files (with their versions) for a target,
+    /// ordered by path, continuing `after` a previous page if given.
+    ///
+    /// Unlike offset pagination, files added or removed while paging don't
+    /// cause others to be skipped or repeated; see [`Cursor`].
+    ///
+    /// # Errors
+    /// Returns [`ErrorKind::InvalidCursor`] for a cursor from another kind of
+    /// listing.
+    pub async fn list_files_for_target_page(
+        &self,
+        target: impl AsRef<str>,
+        after: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<FileResult>> {
+        let after = match after.map(Cursor::key).transpose()? {
+            Some(Key::Path(path)) => path,
+            Some(_) => exn::bail!(ErrorKind::InvalidCursor),
+            None => String::new(),
+        };
+        let rows: Vec<FullJoinRow> = sqlx::query_as(include_str!("../queries/list_files_for_target_page.sql"))
+            .bind(target.as_ref())
+            .bind(after)
+            .bind(overfetch(limit)?)
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        let mut files = rows.into_iter().map(|r| r.try_into()).collect::<Result<Vec<FileResult>>>()?;
+        self.apply_overrides(files.iter_mut().map(|(_, v)| v)).await?;
+        Ok(Page::from_overfetched(files, limit, |(file, _)| {
+            Key::Path(file.path.to_string_lossy().into_owned())
+        }))
+    }
+
+    /// List a page of up to `limit` files (with their versions) across all
+    /// targets, most recently discovered first, continuing `after` a previous
+    /// page if given.
+    ///
+    /// # Errors
+    /// Returns [`ErrorKind::InvalidCursor`] for a cursor from another kind of
+    /// listing.
+    pub async fn list_recent_files_page(&self, after: Option<&Cursor>, limit: usize) -> Result<Page<FileResult>> {
+        let (discovered_at, target, path) = match after.map(Cursor::key).transpose()? {
+            Some(Key::Recent { discovered_at, target, path }) => (discovered_at, target, path),
+            Some(_) => exn::bail!(ErrorKind::InvalidCursor),
+            None => (i64::MAX, String::new(), String::new()),
+        };
+        let rows: Vec<FullJoinRow> = sqlx::query_as(include_str!("../queries/list_recent_files_page.sql"))
+            .bind(discovered_at)
+            .bind(target)
+            .bind(path)
+            .bind(overfetch(limit)?)
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        let mut files = rows.into_iter().map(|r| r.try_into()).collect::<Result<Vec<FileResult>>>()?;
+        self.apply_overrides(files.iter_mut().map(|(_, v)| v)).await?;
+        Ok(Page::from_overfetched(files, limit, |(file, _)| Key::Recent {
+            discovered_at: file.discovered_at.unix_timestamp(),
+            target: file.target.clone(),
+            path: file.path.to_string_lossy().into_owned(),
+        }))
     }
 
     /// List all distinct work IDs in the database.
     ///
-    /// Useful for iterating over all works in the library.
-    pub async fn list_all_work_ids(&self) -> Result<Vec<u64>> {
-        let ids: Vec<i64> = sqlx::query_scalar(include_str!("../queries/list_all_work_ids.sql"))
+    /// Useful for iterating over all works in the library.
+    pub async fn list_all_work_ids(&self) -> Result<Vec<u64>> {
+        let ids: Vec<i64> = sqlx::query_scalar(include_str!("../queries/list_all_work_ids.sql"))
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        let ids = ids
+            .into_iter()
+            .map(|id| u64::try_from(id).or_raise(|| ErrorKind::InvalidData("work id")))
+            .collect::<Result<Vec<u64>>>()?;
+        Ok(ids)
+    }
+
+    /// List all distinct work IDs that have files in the given target.
+    pub async fn list_all_work_ids_for_target(&self, target: impl AsRef<str>) -> Result<Vec<u64>> {
+        let ids: Vec<i64> = sqlx::query_scalar(include_str!("../queries/list_all_work_ids_for_target.sql"))
+            .bind(target.as_ref())
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        let ids = ids
+            .into_iter()
+            .map(|id| u64::try_from(id).or_raise(|| ErrorKind::InvalidData("work id")))
+            .collect::<Result<Vec<u64>>>()?;
+        Ok(ids)
+    }
+
+    /// List every author with the number of distinct works they've written.
+    ///
+    /// Authors are normalized on parsing (see [`Author::new`]) and compared
+    /// ignoring case (see [`Author::key`]), so the same pseudonym recorded
+    /// with different spacing or casing is counted once. A
+    /// user writing under several pseudonyms appears once per pseudonym,
+    /// unless they've been [merged](Self::merge_author): merged authors are
+    /// counted as (and listed as) their canonical author, each work once.
+    /// Sorted by work count descending, then by author.
+    pub async fn list_authors_with_counts(&self) -> Result<Vec<(Author, u64)>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(include_str!("../queries/list_author_work_ids.sql"))
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        let identities: HashMap<_, _> =
+            self.author_identities().await?.into_iter().map(|(author, canonical)| (author.key(), canonical)).collect();
+        // Grouped by key, so an author is counted once however their name is
+        // cased; the first spelling seen stands for the rest.
+        let mut works: HashMap<_, (Author, HashSet<i64>)> = HashMap::new();
+        for (author, work_id) in rows {
+            let author = author.parse::<Author>().or_raise(|| ErrorKind::InvalidData("authors"))?;
+            let author = identities.get(&author.key()).cloned().unwrap_or(author);
+            works.entry(author.key()).or_insert_with(|| (author, HashSet::new())).1.insert(work_id);
+        }
+        let mut counts = works
+            .into_values()
+            .map(|(author, ids)| u64::try_from(ids.len()).map(|c| (author, c)).or_raise(|| ErrorKind::Database))
+            .collect::<Result<Vec<_>>>()?;
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.key().cmp(&b.key())));
+        Ok(counts)
+    }
+
+    /// List all versions (and their files) of works written by the AO3 user
+    /// `username`, optionally restricted to a single `pseudonym`.
+    ///
+    /// Usernames and pseudonyms are matched case-insensitively. Works by
+    /// every author [merged](Self::merge_author) with the one matched are
+    /// included too. Results are sorted by work ID, then by the version
+    /// comparison algorithm (best/newest first) within each work.
+    pub async fn list_works_by_author(
+        &self,
+        username: impl AsRef<str>,
+        pseudonym: Option<&str>,
+    ) -> Result<Vec<VersionResult>> {
+        let username = username.as_ref().trim();
+        if username.is_empty() {
+            return Ok(Vec::new());
+        }
+        // The canonical authors of every merged author matched, and the
+        // usernames of everyone merged with them.
+        let identities = self.author_identities().await?;
+        let canonical: HashSet<_> = identities
+            .iter()
+            .filter(|(author, canonical)| author.is(username, pseudonym) || canonical.is(username, pseudonym))
+            .map(|(_, canonical)| canonical.key())
+            .collect();
+        let mut usernames = vec![username.to_lowercase()];
+        for (author, c) in &identities {
+            if canonical.contains(&c.key()) {
+                usernames.extend([author.username.to_lowercase(), c.username.to_lowercase()]);
+            }
+        }
+        let resolved: HashMap<_, _> = identities.iter().map(|(author, c)| (author.key(), c.key())).collect();
+        usernames.sort();
+        usernames.dedup();
+
+        let mut versions = Vec::new();
+        let mut seen = HashSet::new();
+        for username in &usernames {
+            let rows = sqlx::query_as::<_, LeftJoinRow>(include_str!("../queries/list_works_by_author.sql"))
+                .bind(username)
+                .fetch(&self.pool);
+            for (version, files) in fetch_grouped(rows).await? {
+                if seen.insert(version.hash.clone()) {
+                    versions.push((version, files));
+                }
+            }
+        }
+        self.apply_overrides(versions.iter_mut().map(|(v, _)| v)).await?;
+        versions.retain(|(v, _)| {
+            v.metadata.authors.iter().any(|a| {
+                a.is(username, pseudonym) || {
+                    let key = a.key();
+                    canonical.contains(resolved.get(&key).unwrap_or(&key))
+                }
+            })
+        });
+        versions.sort_by(|(a, _), (b, _)| {
+            a.metadata.work_id.cmp(&b.metadata.work_id).then_with(|| b.partial_cmp(a).unwrap_or(Ordering::Less))
+        });
+        Ok(versions)
+    }
+
+    /* ====== *\
+    |  Series  |
+    \* ====== */
+
+    /// List every series with at least one work in the library, sorted by name.
+    pub async fn list_series(&self) -> Result<Vec<Series>> {
+        let rows: Vec<(i64, String, i64, i64)> = sqlx::query_as(include_str!("../queries/list_series.sql"))
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        rows.into_iter()
+            .map(|(id, name, works, last_position)| {
+                Ok(Series {
+                    id: u64::try_from(id).or_raise(|| ErrorKind::InvalidData("series id"))?,
+                    name,
+                    works: u64::try_from(works).or_raise(|| ErrorKind::Database)?,
+                    last_position: u32::try_from(last_position)
+                        .or_raise(|| ErrorKind::InvalidData("series position"))?,
+                })
+            })
+            .collect()
+    }
+
+    /// List all versions (and their files) of works in a series, as
+    /// `(position, version, files)`.
+    ///
+    /// Sorted by position in the series, then by the version comparison
+    /// algorithm (best/newest first) for multiple versions of the same work.
+    pub async fn list_works_in_series(&self, series_id: u64) -> Result<Vec<(u32, Version, Vec<File>)>> {
+        let series_id = i64::try_from(series_id).or_raise(|| ErrorKind::InvalidData("series id"))?;
+        let rows: Vec<SeriesJoinRow> = sqlx::query_as(include_str!("../queries/list_works_in_series.sql"))
+            .bind(series_id)
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        // Each version's rows are adjacent, and grouped in order: its position
+        // is that of the first of them.
+        let positions = rows
+            .iter()
+            .enumerate()
+            .filter(|(i, row)| *i == 0 || rows[i - 1].join.version.content_hash != row.join.version.content_hash)
+            .map(|(_, row)| u32::try_from(row.position).or_raise(|| ErrorKind::InvalidData("series position")))
+            .collect::<Result<Vec<u32>>>()?;
+        let mut versions = group_by_version(rows.into_iter().map(|r| r.join))?;
+        self.apply_overrides(versions.iter_mut().map(|(v, _)| v)).await?;
+        let mut works = positions
+            .into_iter()
+            .zip(versions)
+            .map(|(position, (version, files))| (position, version, files))
+            .collect::<Vec<_>>();
+        works.sort_by(|(a_pos, a, _), (b_pos, b, _)| {
+            a_pos.cmp(b_pos).then_with(|| b.partial_cmp(a).unwrap_or(Ordering::Less))
+        });
+        Ok(works)
+    }
+
+    /// Find the positions of a series missing from the library.
+    ///
+    /// Only gaps *before* the last position in the library can be detected,
+    /// since AO3 doesn't record the total number of parts on each work.
+    /// Returns an empty list for unknown series.
+    pub async fn find_series_gaps(&self, series_id: u64) -> Result<Vec<u32>> {
+        let series_id = i64::try_from(series_id).or_raise(|| ErrorKind::InvalidData("series id"))?;
+        let positions: Vec<i64> = sqlx::query_scalar(include_str!("../queries/list_series_positions.sql"))
+            .bind(series_id)
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        let positions = positions
+            .into_iter()
+            .map(|p| u32::try_from(p).or_raise(|| ErrorKind::InvalidData("series position")))
+            .collect::<Result<HashSet<u32>>>()?;
+        let last = positions.iter().max().copied().unwrap_or(0);
+        Ok((1..last).filter(|p| !positions.contains(p)).collect())
+    }
+
+    /* ======= *\
+    |  Aliases  |
+    \* ======= */
+
+    /// Record that `work_id` now refers to the work `canonical_work_id`.
+    ///
+    /// Authors sometimes delete a work and re-upload it under a new ID. Once
+    /// an alias is registered, versions recorded under the old ID are treated
+    /// as versions of the canonical work: they're included in
+    /// [`get_by_work_id`](Self::get_by_work_id) (whichever of the two IDs is
+    /// requested) and counted together when looking for
+    /// [works with multiple versions](Self::find_works_with_multiple_versions).
+    ///
+    /// Chains are flattened: if `canonical_work_id` is itself an alias, the
+    /// alias points to what it resolves to instead, and any existing aliases
+    /// of `work_id` are re-pointed along with it. Re-registering an alias
+    /// replaces its previous canonical work ID.
+    ///
+    /// Returns [`ErrorKind::Constraint`] if the alias would (eventually) refer
+    /// back to itself.
+    #[instrument(skip_all, fields(work_id = work_id, canonical_work_id = canonical_work_id))]
+    pub async fn register_alias(&self, work_id: u64, canonical_work_id: u64) -> Result<()> {
+        let canonical_work_id = self.resolve_work_id(canonical_work_id).await?;
+        if canonical_work_id == work_id {
+            exn::bail!(ErrorKind::Constraint);
+        }
+        if self.begin_write().await? {
+            return Ok(());
+        }
+        let work_id = i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?;
+        let canonical_work_id = i64::try_from(canonical_work_id).or_raise(|| ErrorKind::InvalidData("work id"))?;
+        let mut tx = self.pool.begin().await.or_raise(|| ErrorKind::Database)?;
+        sqlx::query(include_str!("../queries/repoint_aliases.sql"))
+            .bind(canonical_work_id)
+            .bind(work_id)
+            .execute(&mut *tx)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        sqlx::query(include_str!("../queries/upsert_alias.sql"))
+            .bind(work_id)
+            .bind(canonical_work_id)
+            .execute(&mut *tx)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        tx.commit().await.or_raise(|| ErrorKind::Database)?;
+        Ok(())
+    }
+
+    /// Resolve a work ID to its canonical work ID.
+    ///
+    /// Returns the work ID unchanged if it isn't an alias.
+    pub async fn resolve_work_id(&self, work_id: u64) -> Result<u64> {
+        let id = i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?;
+        let canonical: Option<i64> = sqlx::query_scalar(include_str!("../queries/resolve_alias.sql"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        match canonical {
+            Some(canonical) => u64::try_from(canonical).or_raise(|| ErrorKind::InvalidData("work id")),
+            None => Ok(work_id),
+        }
+    }
+
+    /// List every work ID that is an alias of the given canonical work ID,
+    /// sorted ascending.
+    pub async fn list_aliases(&self, canonical_work_id: u64) -> Result<Vec<u64>> {
+        let id = i64::try_from(canonical_work_id).or_raise(|| ErrorKind::InvalidData("work id"))?;
+        let ids: Vec<i64> = sqlx::query_scalar(include_str!("../queries/list_aliases.sql"))
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        ids.into_iter().map(|id| u64::try_from(id).or_raise(|| ErrorKind::InvalidData("work id"))).collect()
+    }
+
+    /// Remove an alias, so the work ID refers to itself again.
+    ///
+    /// Returns `true` if the work ID was an alias.
+    #[instrument(skip_all, fields(work_id = work_id))]
+    pub async fn remove_alias(&self, work_id: u64) -> Result<bool> {
+        if self.begin_write().await? {
+            return Ok(self.resolve_work_id(work_id).await? != work_id);
+        }
+        let result = sqlx::query(include_str!("../queries/delete_alias.sql"))
+            .bind(i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /* ================= *\
+    |  Author Identities  |
+    \* ================= */
+
+    /// Record that `author` is the same person as `canonical`, such as
+    /// another pseudonym (or account) of theirs.
+    ///
+    /// Once merged, `author` is counted as `canonical` in
+    /// [`list_authors_with_counts`](Self::list_authors_with_counts), and works
+    /// by either are listed by [`list_works_by_author`](Self::list_works_by_author)
+    /// for both. Versions keep the byline they were extracted with; merging
+    /// only changes how it's grouped, and can be undone with
+    /// [`unmerge_author`](Self::unmerge_author).
+    ///
+    /// Authors are identified ignoring case (see [`Author::key`]): merging
+    /// `author` covers every spelling of their name, replacing any merge
+    /// recorded under another spelling.
+    ///
+    /// Chains are flattened as with [aliases](Self::register_alias): if
+    /// `canonical` is itself merged, `author` is merged under what it
+    /// resolves to, along with any authors already merged under `author`.
+    ///
+    /// Returns [`ErrorKind::Constraint`] if the author would (eventually) be
+    /// merged under itself.
+    #[instrument(skip_all, fields(author = %author, canonical = %canonical))]
+    pub async fn merge_author(&self, author: &Author, canonical: &Author) -> Result<()> {
+        let canonical = self.resolve_author(canonical).await?;
+        if canonical.key() == author.key() {
+            exn::bail!(ErrorKind::Constraint);
+        }
+        if self.begin_write().await? {
+            return Ok(());
+        }
+        // Identities are stored as they were spelled: find every spelling of
+        // `author`, whether merged or merged under.
+        let identities = self.author_identities().await?;
+        let mut merged: Vec<String> = vec![];
+        let mut merged_under: Vec<String> = vec![];
+        for (a, c) in &identities {
+            if a.key() == author.key() {
+                merged.push(a.to_string());
+            }
+            if c.key() == author.key() {
+                merged_under.push(c.to_string());
+            }
+        }
+        merged_under.sort();
+        merged_under.dedup();
+        let (author, canonical) = (author.to_string(), canonical.to_string());
+        let mut tx = self.pool.begin().await.or_raise(|| ErrorKind::Database)?;
+        for spelling in &merged {
+            sqlx::query(include_str!("../queries/delete_author_identity.sql"))
+                .bind(spelling)
+                .execute(&mut *tx)
+                .await
+                .or_raise(|| ErrorKind::Database)?;
+        }
+        for spelling in &merged_under {
+            sqlx::query(include_str!("../queries/repoint_author_identities.sql"))
+                .bind(&canonical)
+                .bind(spelling)
+                .execute(&mut *tx)
+                .await
+                .or_raise(|| ErrorKind::Database)?;
+        }
+        sqlx::query(include_str!("../queries/upsert_author_identity.sql"))
+            .bind(&author)
+            .bind(&canonical)
+            .execute(&mut *tx)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        tx.commit().await.or_raise(|| ErrorKind::Database)?;
+        Ok(())
+    }
+
+    /// Resolve an author (ignoring case) to the canonical author they're
+    /// merged under.
+    ///
+    /// Returns the author unchanged if they aren't merged.
+    pub async fn resolve_author(&self, author: &Author) -> Result<Author> {
+        let key = author.key();
+        let canonical = self.author_identities().await?.into_iter().find(|(a, _)| a.key() == key);
+        Ok(canonical.map_or_else(|| author.clone(), |(_, canonical)| canonical))
+    }
+
+    /// List every author merged under the given canonical author (ignoring
+    /// case), sorted.
+    pub async fn list_merged_authors(&self, canonical: &Author) -> Result<Vec<Author>> {
+        let key = canonical.key();
+        let mut authors: Vec<_> =
+            self.author_identities().await?.into_iter().filter(|(_, c)| c.key() == key).map(|(a, _)| a).collect();
+        authors.sort();
+        Ok(authors)
+    }
+
+    /// Undo merging an author (however their name is cased), so they're
+    /// counted as themselves again.
+    ///
+    /// Returns `true` if the author was merged.
+    #[instrument(skip_all, fields(author = %author))]
+    pub async fn unmerge_author(&self, author: &Author) -> Result<bool> {
+        let key = author.key();
+        let merged: Vec<_> =
+            self.author_identities().await?.into_iter().filter(|(a, _)| a.key() == key).map(|(a, _)| a).collect();
+        if self.begin_write().await? {
+            return Ok(!merged.is_empty());
+        }
+        let mut unmerged = false;
+        for spelling in merged {
+            let result = sqlx::query(include_str!("../queries/delete_author_identity.sql"))
+                .bind(spelling.to_string())
+                .execute(&self.pool)
+                .await
+                .or_raise(|| ErrorKind::Database)?;
+            unmerged |= result.rows_affected() > 0;
+        }
+        Ok(unmerged)
+    }
+
+    /// Every merged author, with the canonical author they're merged under,
+    /// as they were spelled when merged.
+    async fn author_identities(&self) -> Result<Vec<(Author, Author)>> {
+        let rows: Vec<(String, String)> = sqlx::query_as(include_str!("../queries/list_author_identities.sql"))
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        rows.into_iter()
+            .map(|(author, canonical)| {
+                let parse = |s: String| s.parse::<Author>().or_raise(|| ErrorKind::InvalidData("author identity"));
+                Ok((parse(author)?, parse(canonical)?))
+            })
+            .collect()
+    }
+
+    /* ============== *\
+    |  Reading Status  |
+    \* ============== */
+
+    /// Record that the user has read a work, as of its current
+    /// [best version](Self::get_best_for_work_id).
+    ///
+    /// The status is recorded against the canonical work ID (see
+    /// [`register_alias`](Self::register_alias)), and replaces any earlier
+    /// one. Returns `false` (recording nothing) if there is no version of the
+    /// work in the library.
+    ///
+    /// Unlike the rest of the cache, reading status can't be recovered by
+    /// scanning the library again.
+    #[instrument(skip_all, fields(work_id = work_id))]
+    pub async fn mark_read(&self, work_id: u64, read_at: UtcDateTime) -> Result<bool> {
+        let work_id = self.resolve_work_id(work_id).await?;
+        let Some((version, _)) = self.get_best_for_work_id(work_id).await? else {
+            return Ok(false);
+        };
+        if self.begin_write().await? {
+            return Ok(true);
+        }
+        let last_modified = version.metadata.last_modified.midnight().as_utc().unix_timestamp();
+        sqlx::query(include_str!("../queries/upsert_reading_status.sql"))
+            .bind(i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?)
+            .bind(read_at.unix_timestamp())
+            .bind(i64::from(version.metadata.chapters.written))
+            .bind(last_modified)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(true)
+    }
+
+    /// Forget that the user has read a work.
+    ///
+    /// Returns `true` if the work had been marked as read.
+    #[instrument(skip_all, fields(work_id = work_id))]
+    pub async fn mark_unread(&self, work_id: u64) -> Result<bool> {
+        let work_id = self.resolve_work_id(work_id).await?;
+        if self.begin_write().await? {
+            return Ok(self.get_reading_status(work_id).await?.is_some());
+        }
+        let result = sqlx::query(include_str!("../queries/delete_reading_status.sql"))
+            .bind(i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// What the user last read of a work, if they've marked it as read.
+    pub async fn get_reading_status(&self, work_id: u64) -> Result<Option<ReadingStatus>> {
+        let work_id = self.resolve_work_id(work_id).await?;
+        let row: Option<(i64, i64, i64, i64)> = sqlx::query_as(include_str!("../queries/get_reading_status.sql"))
+            .bind(i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?)
+            .fetch_optional(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        row.map(ReadingStatus::try_from).transpose()
+    }
+
+    /// List the works that have been updated since the user read them: those
+    /// whose best version has more chapters, or was updated on AO3 later,
+    /// than the version that was [marked as read](Self::mark_read).
+    ///
+    /// Works read but since removed from the library are left out. Results
+    /// are sorted by work ID.
+    pub async fn updated_since_read(&self) -> Result<Vec<UpdatedWork>> {
+        let rows: Vec<(i64, i64, i64, i64)> = sqlx::query_as(include_str!("../queries/list_reading_status.sql"))
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        let mut updated = Vec::new();
+        for row in rows {
+            let status = ReadingStatus::try_from(row)?;
+            let Some((version, files)) = self.get_best_for_work_id(status.work_id).await? else {
+                continue;
+            };
+            let metadata = &version.metadata;
+            if metadata.chapters.written > status.chapters_written || metadata.last_modified > status.last_modified {
+                updated.push(UpdatedWork { status, version, files });
+            }
+        }
+        Ok(updated)
+    }
+
+    /* ========= *\
+    |  Scan Runs  |
+    \* ========= */
+
+    /// Record the start of a scan of `target` (or just its `prefix`), so that
+    /// if it's interrupted it can be resumed from its latest
+    /// [checkpoint](Self::checkpoint_scan_run) rather than started over.
+    ///
+    /// The run is recorded until it's [finished](Self::finish_scan_run).
+    #[instrument(skip_all, fields(target = target))]
+    pub async fn start_scan_run(&self, target: &str, prefix: Option<&Path>) -> Result<ScanRun> {
+        let now = UtcDateTime::now();
+        let run_id = new_token(now);
+        // Times are recorded to the second.
+        let now = UtcDateTime::from_unix_timestamp(now.unix_timestamp()).or_raise(|| ErrorKind::InvalidData("now"))?;
+        let run = ScanRun {
+            run_id,
+            target: target.to_string(),
+            prefix: prefix.map(Path::to_path_buf),
+            checkpoint: None,
+            scanned: 0,
+            started_at: now,
+            updated_at: now,
+        };
+        let prefix = prefix.map(Self::sqlx_hates_paths).transpose()?;
+        if self.begin_write().await? {
+            return Ok(run);
+        }
+        sqlx::query(include_str!("../queries/insert_scan_run.sql"))
+            .bind(&run.run_id)
+            .bind(&run.target)
+            .bind(prefix)
+            .bind(now.unix_timestamp())
+            .bind(now.unix_timestamp())
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(run)
+    }
+
+    /// Record how far a scan run has got: `checkpoint` is the path of the
+    /// last file listed that, along with every file listed before it, has
+    /// been scanned, and `scanned` the number of files that is.
+    ///
+    /// Returns `false` if there is no such run.
+    pub async fn checkpoint_scan_run(&self, run_id: &str, checkpoint: &Path, scanned: u64) -> Result<bool> {
+        let checkpoint = Self::sqlx_hates_paths(checkpoint)?;
+        let scanned = i64::try_from(scanned).or_raise(|| ErrorKind::InvalidData("files scanned"))?;
+        if self.begin_write().await? {
+            return Ok(self.get_scan_run(run_id).await?.is_some());
+        }
+        let result = sqlx::query(include_str!("../queries/update_scan_run_checkpoint.sql"))
+            .bind(checkpoint)
+            .bind(scanned)
+            .bind(UtcDateTime::now().unix_timestamp())
+            .bind(run_id)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Look up a scan run that hasn't finished.
+    pub async fn get_scan_run(&self, run_id: &str) -> Result<Option<ScanRun>> {
+        let row: Option<ScanRunRow> = sqlx::query_as(include_str!("../queries/get_scan_run.sql"))
+            .bind(run_id)
+            .fetch_optional(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        row.map(ScanRun::try_from).transpose()
+    }
+
+    /// List the scan runs that haven't finished (whether they're still
+    /// running, or were interrupted), oldest first.
+    pub async fn list_scan_runs(&self) -> Result<Vec<ScanRun>> {
+        let rows: Vec<ScanRunRow> = sqlx::query_as(include_str!("../queries/list_scan_runs.sql"))
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        rows.into_iter().map(ScanRun::try_from).collect()
+    }
+
+    /// Forget a scan run, once it has completed (or is to be started over).
+    ///
+    /// Returns `true` if the run was recorded.
+    #[instrument(skip(self))]
+    pub async fn finish_scan_run(&self, run_id: &str) -> Result<bool> {
+        if self.begin_write().await? {
+            return Ok(self.get_scan_run(run_id).await?.is_some());
+        }
+        let result = sqlx::query(include_str!("../queries/delete_scan_run.sql"))
+            .bind(run_id)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /* ======== *\
+    |  Job Runs  |
+    \* ======== */
+
+    /// Record that the scheduled job `name` has started, replacing the
+    /// record of its previous run.
+    ///
+    /// Until it's [finished](Self::finish_job_run), the run counts as
+    /// running (or, if the process died, interrupted).
+    #[instrument(skip(self))]
+    pub async fn start_job_run(&self, name: &str) -> Result<JobRun> {
+        // Times are recorded to the second.
+        let now = UtcDateTime::from_unix_timestamp(UtcDateTime::now().unix_timestamp())
+            .or_raise(|| ErrorKind::InvalidData("now"))?;
+        let run = JobRun {
+            name: name.to_string(),
+            started_at: now,
+            finished_at: None,
+            error: None,
+        };
+        if self.begin_write().await? {
+            return Ok(run);
+        }
+        sqlx::query(include_str!("../queries/upsert_job_run.sql"))
+            .bind(name)
+            .bind(now.unix_timestamp())
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(run)
+    }
+
+    /// Record that the running job `name` has finished, having failed with
+    /// `error` if given.
+    ///
+    /// Returns `false` if the job isn't running.
+    #[instrument(skip(self))]
+    pub async fn finish_job_run(&self, name: &str, error: Option<&str>) -> Result<bool> {
+        if self.begin_write().await? {
+            return Ok(self.get_job_run(name).await?.is_some_and(|run| run.finished_at.is_none()));
+        }
+        let result = sqlx::query(include_str!("../queries/finish_job_run.sql"))
+            .bind(UtcDateTime::now().unix_timestamp())
+            .bind(error)
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Look up the latest run of the scheduled job `name`.
+    pub async fn get_job_run(&self, name: &str) -> Result<Option<JobRun>> {
+        let row: Option<JobRunRow> = sqlx::query_as(include_str!("../queries/get_job_run.sql"))
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        row.map(JobRun::try_from).transpose()
+    }
+
+    /// List the latest run of every scheduled job that has run, by name.
+    pub async fn list_job_runs(&self) -> Result<Vec<JobRun>> {
+        let rows: Vec<JobRunRow> = sqlx::query_as(include_str!("../queries/list_job_runs.sql"))
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        rows.into_iter().map(JobRun::try_from).collect()
+    }
+
+    /* ========= *\
+    |  Overrides  |
+    \* ========= */
+
+    /// Replace fields of a work's metadata with the user's own values.
+    ///
+    /// Overrides are recorded against the canonical work ID (see
+    /// [`register_alias`](Self::register_alias)), and replace any earlier
+    /// ones; empty overrides [clear](Self::clear_overrides) them. From then on,
+    /// every version of the work the repository returns has the overridden
+    /// fields replaced (and marked as user edited), so organizing the library
+    /// files works by their corrected metadata.
+    ///
+    /// Unlike the rest of the cache, overrides can't be recovered by scanning
+    /// the library again.
+    #[instrument(skip_all, fields(work_id = work_id))]
+    pub async fn set_overrides(&self, work_id: u64, overrides: &Overrides) -> Result<()> {
+        if overrides.is_empty() {
+            return self.clear_overrides(work_id).await.map(|_| ());
+        }
+        let row = OverrideRow::new(self.resolve_work_id(work_id).await?, overrides)?;
+        if self.begin_write().await? {
+            return Ok(());
+        }
+        sqlx::query(include_str!("../queries/upsert_override.sql"))
+            .bind(row.work_id)
+            .bind(row.title)
+            .bind(row.authors)
+            .bind(row.fandoms)
+            .bind(row.tags)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(())
+    }
+
+    /// The user's overrides of a work's metadata, if there are any.
+    pub async fn get_overrides(&self, work_id: u64) -> Result<Option<Overrides>> {
+        let work_id = self.resolve_work_id(work_id).await?;
+        let row: Option<OverrideRow> = sqlx::query_as(include_str!("../queries/get_override.sql"))
+            .bind(i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?)
+            .fetch_optional(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(row.map(<(u64, Overrides)>::try_from).transpose()?.map(|(_, overrides)| overrides))
+    }
+
+    /// Remove the user's overrides of a work's metadata, so its extracted
+    /// values are used again.
+    ///
+    /// Returns `true` if the work had overrides.
+    #[instrument(skip_all, fields(work_id = work_id))]
+    pub async fn clear_overrides(&self, work_id: u64) -> Result<bool> {
+        let work_id = self.resolve_work_id(work_id).await?;
+        if self.begin_write().await? {
+            return Ok(self.get_overrides(work_id).await?.is_some());
+        }
+        let result = sqlx::query(include_str!("../queries/delete_override.sql"))
+            .bind(i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Apply the user's [overrides](Self::set_overrides) to versions fetched
+    /// from the database.
+    async fn apply_overrides<'a>(&self, versions: impl IntoIterator<Item = &'a mut Version>) -> Result<()> {
+        let overrides = self.all_overrides().await?;
+        if overrides.is_empty() {
+            return Ok(());
+        }
+        for version in versions {
+            if let Some(overrides) = overrides.get(&version.metadata.work_id) {
+                overrides.apply(&mut version.metadata);
+            }
+        }
+        Ok(())
+    }
+
+    /// Every work's overrides, by work ID.
+    async fn all_overrides(&self) -> Result<HashMap<u64, Overrides>> {
+        let rows: Vec<OverrideRow> = sqlx::query_as(include_str!("../queries/list_overrides.sql"))
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        rows.into_iter().map(<(u64, Overrides)>::try_from).collect()
+    }
+
+    /* ========== *\
+    |  Attributes  |
+    \* ========== */
+
+    /// Set an attribute of a file, replacing any value it had.
+    ///
+    /// Attributes are for applications embedding the cache to keep their own
+    /// data about files (such as their sync state). They follow the file
+    /// when it's [renamed](Self::update_target_path), and are deleted along
+    /// with it.
+    ///
+    /// # Errors
+    /// Returns [`ErrorKind::FileNotFound`] if the file isn't cached.
+    #[instrument(skip(self, value), fields(path = %path.as_ref().display()))]
+    pub async fn set_file_attribute(&self, target: &str, path: impl AsRef<Path>, key: &str, value: &str) -> Result<()> {
+        let sqlx_path = Self::sqlx_hates_paths(&path)?;
+        if self.begin_write().await? {
+            return Ok(());
+        }
+        let result = sqlx::query(include_str!("../queries/upsert_file_attribute.sql"))
+            .bind(target)
+            .bind(sqlx_path)
+            .bind(key)
+            .bind(value)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        if result.rows_affected() == 0 {
+            exn::bail!(ErrorKind::FileNotFound(target.to_string(), path.as_ref().to_path_buf()));
+        }
+        Ok(())
+    }
+
+    /// Every attribute of a file (none, if it isn't cached).
+    pub async fn get_file_attributes(&self, target: &str, path: impl AsRef<Path>) -> Result<Attributes> {
+        let rows: Vec<(String, String)> = sqlx::query_as(include_str!("../queries/list_file_attributes.sql"))
+            .bind(target)
+            .bind(Self::sqlx_hates_paths(path)?)
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Remove an attribute of a file.
+    ///
+    /// Returns `true` if the file had the attribute.
+    #[instrument(skip(self), fields(path = %path.as_ref().display()))]
+    pub async fn remove_file_attribute(&self, target: &str, path: impl AsRef<Path>, key: &str) -> Result<bool> {
+        if self.begin_write().await? {
+            return Ok(self.get_file_attributes(target, path).await?.contains_key(key));
+        }
+        let result = sqlx::query(include_str!("../queries/delete_file_attribute.sql"))
+            .bind(target)
+            .bind(Self::sqlx_hates_paths(path)?)
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Set an attribute of a version, replacing any value it had.
+    ///
+    /// Like [file attributes](Self::set_file_attribute), but about the
+    /// version's content wherever it's stored (such as its ID in another
+    /// system). They're deleted along with the version, and travel with it
+    /// in export bundles.
+    ///
+    /// # Errors
+    /// Returns [`ErrorKind::VersionNotFound`] if the version isn't cached.
+    #[instrument(skip(self, value))]
+    pub async fn set_version_attribute(&self, content_hash: &str, key: &str, value: &str) -> Result<()> {
+        if self.begin_write().await? {
+            return Ok(());
+        }
+        let result = sqlx::query(include_str!("../queries/upsert_version_attribute.sql"))
+            .bind(content_hash)
+            .bind(key)
+            .bind(value)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        if result.rows_affected() == 0 {
+            exn::bail!(ErrorKind::VersionNotFound(content_hash.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Every attribute of a version (none, if it isn't cached).
+    pub async fn get_version_attributes(&self, content_hash: &str) -> Result<Attributes> {
+        let rows: Vec<(String, String)> = sqlx::query_as(include_str!("../queries/list_version_attributes.sql"))
+            .bind(content_hash)
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Remove an attribute of a version.
+    ///
+    /// Returns `true` if the version had the attribute.
+    #[instrument(skip(self))]
+    pub async fn remove_version_attribute(&self, content_hash: &str, key: &str) -> Result<bool> {
+        if self.begin_write().await? {
+            return Ok(self.get_version_attributes(content_hash).await?.contains_key(key));
+        }
+        let result = sqlx::query(include_str!("../queries/delete_version_attribute.sql"))
+            .bind(content_hash)
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /* ========== *\
+    |  Tombstones  |
+    \* ========== */
+
+    /// Record that the user deliberately deleted a work, for `reason` if
+    /// given, and forget every version of it (and of its
+    /// [aliases](Self::register_alias)) in the cache.
+    ///
+    /// From then on, scans skip files of the work rather than adding it
+    /// back, until the tombstone is [removed](Self::remove_tombstone). The
+    /// work's files are left alone; deleting them is up to the caller.
+    /// Replaces any earlier tombstone of the work.
+    #[instrument(skip(self))]
+    pub async fn add_tombstone(&self, work_id: u64, reason: Option<&str>) -> Result<Tombstone> {
+        let work_id = self.resolve_work_id(work_id).await?;
+        // Times are recorded to the second.
+        let now = UtcDateTime::from_unix_timestamp(UtcDateTime::now().unix_timestamp())
+            .or_raise(|| ErrorKind::InvalidData("now"))?;
+        let tombstone = Tombstone {
+            work_id,
+            reason: reason.map(str::to_string),
+            deleted_at: now,
+        };
+        if self.begin_write().await? {
+            return Ok(tombstone);
+        }
+        sqlx::query(include_str!("../queries/upsert_tombstone.sql"))
+            .bind(i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?)
+            .bind(reason)
+            .bind(now.unix_timestamp())
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        for id in std::iter::once(work_id).chain(self.list_aliases(work_id).await?) {
+            self.delete_by_work_id(id).await?;
+        }
+        Ok(tombstone)
+    }
+
+    /// The tombstone of a work, if the user deliberately deleted it.
+    pub async fn get_tombstone(&self, work_id: u64) -> Result<Option<Tombstone>> {
+        let work_id = self.resolve_work_id(work_id).await?;
+        let row: Option<TombstoneRow> = sqlx::query_as(include_str!("../queries/get_tombstone.sql"))
+            .bind(i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?)
+            .fetch_optional(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        row.map(Tombstone::try_from).transpose()
+    }
+
+    /// List the tombstones of every work the user deliberately deleted,
+    /// oldest first.
+    pub async fn list_tombstones(&self) -> Result<Vec<Tombstone>> {
+        let rows: Vec<TombstoneRow> = sqlx::query_as(include_str!("../queries/list_tombstones.sql"))
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        rows.into_iter().map(Tombstone::try_from).collect()
+    }
+
+    /// Remove the tombstone of a work, so scans add it back again.
+    ///
+    /// Returns `true` if the work had a tombstone.
+    #[instrument(skip(self))]
+    pub async fn remove_tombstone(&self, work_id: u64) -> Result<bool> {
+        let work_id = self.resolve_work_id(work_id).await?;
+        if self.begin_write().await? {
+            return Ok(self.get_tombstone(work_id).await?.is_some());
+        }
+        let result = sqlx::query(include_str!("../queries/delete_tombstone.sql"))
+            .bind(i64::try_from(work_id).or_raise(|| ErrorKind::InvalidData("work id"))?)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /* ====== *\
+    |  Chunks  |
+    \* ====== */
+
+    /// Which of the chunks (by hash) are known to be stored on `target`, as
+    /// part of any file (see [`record_chunks`](Self::record_chunks)).
+    pub async fn stored_chunks(&self, target: &str, hashes: &[&str]) -> Result<HashSet<String>> {
+        let hashes = serde_json::to_string(hashes).or_raise(|| ErrorKind::InvalidData("chunk hashes"))?;
+        let rows: Vec<(String,)> = sqlx::query_as(include_str!("../queries/list_stored_chunks.sql"))
+            .bind(hashes)
+            .bind(target)
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(rows.into_iter().map(|(hash,)| hash).collect())
+    }
+
+    /// Record that the file at `path` on a chunked `target` is made of
+    /// `chunks` (in order), replacing whatever it was made of before.
+    #[instrument(skip(self, chunks), fields(chunks = chunks.len()))]
+    pub async fn record_chunks(&self, target: &str, path: &Path, chunks: &[Chunk]) -> Result<()> {
+        let path = Self::sqlx_hates_paths(path)?;
+        if self.begin_write().await? {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await.or_raise(|| ErrorKind::Database)?;
+        sqlx::query(include_str!("../queries/delete_chunks.sql"))
+            .bind(target)
+            .bind(&path)
+            .execute(&mut *tx)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        for (seq, chunk) in chunks.iter().enumerate() {
+            sqlx::query(include_str!("../queries/insert_chunk.sql"))
+                .bind(target)
+                .bind(&path)
+                .bind(i64::try_from(seq).or_raise(|| ErrorKind::InvalidData("chunk"))?)
+                .bind(&chunk.hash)
+                .bind(i64::try_from(chunk.length).or_raise(|| ErrorKind::InvalidData("chunk length"))?)
+                .execute(&mut *tx)
+                .await
+                .or_raise(|| ErrorKind::Database)?;
+        }
+        tx.commit().await.or_raise(|| ErrorKind::Database)
+    }
+
+    /// Forget the chunks of the file at `path` on a chunked `target`.
+    ///
+    /// Returns `true` if any were recorded.
+    #[instrument(skip(self))]
+    pub async fn forget_chunks(&self, target: &str, path: &Path) -> Result<bool> {
+        let path = Self::sqlx_hates_paths(path)?;
+        if self.begin_write().await? {
+            return Ok(false);
+        }
+        let result = sqlx::query(include_str!("../queries/delete_chunks.sql"))
+            .bind(target)
+            .bind(path)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /* ======== *\
+    |  Settings  |
+    \* ======== */
+
+    /// A setting of an application, or `None` if it was never set.
+    ///
+    /// Settings are small preferences (such as the last template used, or
+    /// the default target) that applications using the cache keep between
+    /// runs, each under their own `namespace`. Any serializable value can be
+    /// stored: strings, numbers, booleans or whole structs.
+    ///
+    /// # Errors
+    /// Returns [`ErrorKind::InvalidData`] if the setting isn't a `T`.
+    pub async fn get_setting<T: DeserializeOwned>(&self, namespace: &str, key: &str) -> Result<Option<T>> {
+        let value: Option<String> = sqlx::query_scalar(include_str!("../queries/get_setting.sql"))
+            .bind(namespace)
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        value.map(|value| serde_json::from_str(&value).or_raise(|| ErrorKind::InvalidData("setting"))).transpose()
+    }
+
+    /// Set a setting of an application, replacing any value it had.
+    #[instrument(skip(self, value))]
+    pub async fn set_setting<T: Serialize + ?Sized>(&self, namespace: &str, key: &str, value: &T) -> Result<()> {
+        let value = serde_json::to_string(value).or_raise(|| ErrorKind::InvalidData("setting"))?;
+        if self.begin_write().await? {
+            return Ok(());
+        }
+        sqlx::query(include_str!("../queries/upsert_setting.sql"))
+            .bind(namespace)
+            .bind(key)
+            .bind(value)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(())
+    }
+
+    /// Every setting of an application, as JSON.
+    pub async fn list_settings(&self, namespace: &str) -> Result<BTreeMap<String, serde_json::Value>> {
+        let rows: Vec<(String, String)> = sqlx::query_as(include_str!("../queries/list_settings.sql"))
+            .bind(namespace)
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        rows.into_iter()
+            .map(|(key, value)| Ok((key, serde_json::from_str(&value).or_raise(|| ErrorKind::InvalidData("setting"))?)))
+            .collect()
+    }
+
+    /// Remove a setting of an application.
+    ///
+    /// Returns `true` if it was set.
+    #[instrument(skip(self))]
+    pub async fn remove_setting(&self, namespace: &str, key: &str) -> Result<bool> {
+        if self.begin_write().await? {
+            return Ok(self.get_setting::<serde_json::Value>(namespace, key).await?.is_some());
+        }
+        let result = sqlx::query(include_str!("../queries/delete_setting.sql"))
+            .bind(namespace)
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /* ========= *\
+    |  Analytics  |
+    \* ========= */
+
+    /// How the library grew, month by month: works, files and bytes added,
+    /// version churn, and words available to read per fandom.
+    ///
+    /// Works and bytes are counted by when their files were discovered, and
+    /// versions (and words) by when they were extracted. Works are counted
+    /// once across all of their [aliases](Self::register_alias) and targets.
+    pub async fn timeline(&self) -> Result<Timeline> {
+        let files: Vec<timeline::FileRow> = sqlx::query_as(include_str!("../queries/timeline_files.sql"))
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        let versions: Vec<timeline::VersionRow> = sqlx::query_as(include_str!("../queries/timeline_versions.sql"))
+            .fetch_all(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        timeline::build(files, versions)
+    }
+
+    /* ============ *\
+    |  Target Locks  |
+    \* ============ */
+
+    /// Take the advisory lock on a target for an `operation` (e.g. `"scan"`).
+    ///
+    /// Locks held by another operation are taken over once they have expired
+    /// (see [`LOCK_TIMEOUT`](crate::LOCK_TIMEOUT)). In dry-run mode, the lock
+    /// is checked for but not taken.
+    ///
+    /// Returns [`ErrorKind::Locked`] (describing the holder) if the target is
+    /// already locked.
+    #[instrument(skip(self))]
+    pub async fn lock_target(&self, target: &str, operation: &str) -> Result<TargetLock> {
+        TargetLock::acquire(&self.pool, self.skip_write()?, target, operation).await
+    }
+
+    /// Get the lock currently held on a target, if any (including expired
+    /// locks that haven't been taken over yet).
+    pub async fn get_target_lock(&self, target: impl AsRef<str>) -> Result<Option<LockInfo>> {
+        lock::current(&self.pool, target.as_ref()).await
+    }
+
+    /// Forcibly remove the lock on a target, such as one left behind by a
+    /// crashed process. Returns `true` if there was one.
+    ///
+    /// The operation holding the lock (if it's still running) finds out the
+    /// next time it refreshes the lock.
+    #[instrument(skip(self))]
+    pub async fn break_target_lock(&self, target: &str) -> Result<bool> {
+        if self.begin_write().await? {
+            return Ok(self.get_target_lock(target).await?.is_some());
+        }
+        let result = sqlx::query(include_str!("../queries/delete_target_lock.sql"))
+            .bind(target)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /* ============ *\
+    |  Writer Lease  |
+    \* ============ */
+
+    /// Take the advisory [writer lease](crate::WriterLease), for a process
+    /// that will be writing to the cache for a while, in a `role` (e.g.
+    /// `"daemon"`) describing it to other processes.
+    ///
+    /// Leases held by another process are taken over once they have expired
+    /// (see [`LOCK_TIMEOUT`](crate::LOCK_TIMEOUT)). In dry-run mode, the
+    /// lease is checked for but not taken.
+    ///
+    /// Returns [`ErrorKind::Leased`] (describing the holder) if another
+    /// process holds the lease.
+    #[instrument(skip(self))]
+    pub async fn acquire_writer_lease(&self, role: &str) -> Result<WriterLease> {
+        WriterLease::acquire(&self.pool, self.skip_write()?, role).await
+    }
+
+    /// Get the writer lease currently held, if any (including an expired
+    /// lease that hasn't been taken over yet). Processes that aren't holding
+    /// the lease should only read while another holds it.
+    pub async fn writer_lease(&self) -> Result<Option<LeaseInfo>> {
+        lease::current(&self.pool).await
+    }
+
+    /// Forcibly remove the writer lease, such as one left behind by a crashed
+    /// process. Returns `true` if there was one.
+    #[instrument(skip(self))]
+    pub async fn break_writer_lease(&self) -> Result<bool> {
+        if self.begin_write().await? {
+            return Ok(self.writer_lease().await?.is_some());
+        }
+        let result = sqlx::query(include_str!("../queries/delete_writer_lease.sql"))
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /* ================= *\
+    |  Smart Collections  |
+    \* ================= */
+
+    /// Get all versions (and their files) matching a [`Filter`].
+    ///
+    /// Results are sorted by work ID, then by the version comparison algorithm
+    /// (best/newest first).
+    pub async fn filter_versions(&self, filter: &Filter) -> Result<Vec<VersionResult>> {
+        let (condition, values) = filter.to_sql();
+        let sql = format!(
+            "SELECT f.*, v.* FROM versions v LEFT JOIN files f ON f.content_hash = v.content_hash WHERE {condition} \
+             ORDER BY v.content_hash"
+        );
+        let query = values.into_iter().fold(sqlx::query_as::<_, LeftJoinRow>(&sql), |query, value| value.bind(query));
+        let mut versions = fetch_grouped(query.fetch(&self.pool)).await?;
+        self.apply_overrides(versions.iter_mut().map(|(v, _)| v)).await?;
+        versions.sort_by(|(a, _), (b, _)| {
+            a.metadata.work_id.cmp(&b.metadata.work_id).then_with(|| b.partial_cmp(a).unwrap_or(Ordering::Less))
+        });
+        Ok(versions)
+    }
+
+    /// Get a page of the versions (and their files) matching a [`Filter`],
+    /// covering up to `limit` works, continuing `after` a previous page if
+    /// given.
+    ///
+    /// Works are never split across pages: each page holds every matching
+    /// version of its works, sorted as by [`filter_versions`](Self::filter_versions).
+    ///
+    /// # Errors
+    /// Returns [`ErrorKind::InvalidCursor`] for a cursor from another kind of
+    /// listing.
+    pub async fn filter_versions_page(
+        &self,
+        filter: &Filter,
+        after: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<VersionResult>> {
+        let after = match after.map(Cursor::key).transpose()? {
+            Some(Key::Work(work_id)) => work_id,
+            Some(_) => exn::bail!(ErrorKind::InvalidCursor),
+            None => i64::MIN,
+        };
+        let (condition, values) = filter.to_sql();
+        let sql = format!(
+            "SELECT f.*, v.* FROM versions v LEFT JOIN files f ON f.content_hash = v.content_hash \
+             WHERE {condition} AND v.work_id IN ( \
+                SELECT DISTINCT v.work_id FROM versions v WHERE {condition} AND v.work_id > ? \
+                ORDER BY v.work_id LIMIT ? \
+             ) \
+             ORDER BY v.content_hash"
+        );
+        let query = values
+            .iter()
+            .chain(&values)
+            .cloned()
+            .fold(sqlx::query_as::<_, LeftJoinRow>(&sql), |query, value| value.bind(query));
+        let query = query.bind(after).bind(overfetch(limit)?);
+        let mut versions = fetch_grouped(query.fetch(&self.pool)).await?;
+        self.apply_overrides(versions.iter_mut().map(|(v, _)| v)).await?;
+        versions.sort_by(|(a, _), (b, _)| {
+            a.metadata.work_id.cmp(&b.metadata.work_id).then_with(|| b.partial_cmp(a).unwrap_or(Ordering::Less))
+        });
+
+        // Pages hold whole works, so overfetching is by work rather than by version.
+        let mut works: Vec<u64> = versions.iter().map(|(v, _)| v.metadata.work_id).collect();
+        works.dedup();
+        let next = match works.len() > limit {
+            true => {
+                versions.retain(|(v, _)| v.metadata.work_id != works[limit]);
+                let work_id = i64::try_from(works[limit - 1]).or_raise(|| ErrorKind::InvalidData("work id"))?;
+                Some(Cursor::new(&Key::Work(work_id)))
+            },
+            false => None,
+        };
+        Ok(Page { items: versions, next })
+    }
+
+    /// Get the `limit` works ranking highest by `sort` among those with a
+    /// version matching a [`Filter`] (and every matching version of them,
+    /// with their files), such as the longest complete works of a fandom.
+    ///
+    /// Works are in rank order (ties broken by work ID), and each work's
+    /// versions are sorted by the version comparison algorithm (best/newest
+    /// first).
+    pub async fn top_works(&self, filter: &Filter, sort: Sort, limit: usize) -> Result<Vec<VersionResult>> {
+        if limit == 0 {
+            exn::bail!(ErrorKind::InvalidData("limit"));
+        }
+        let (condition, values) = filter.to_sql();
+        let column = sort.column();
+        let sql = format!(
+            "WITH ranked AS ( \
+                SELECT v.work_id, max({column}) AS rank FROM versions v WHERE {condition} \
+                GROUP BY v.work_id ORDER BY rank DESC, v.work_id LIMIT ? \
+             ) \
+             SELECT f.*, v.* FROM versions v JOIN ranked r ON r.work_id = v.work_id \
+             LEFT JOIN files f ON f.content_hash = v.content_hash WHERE {condition} \
+             ORDER BY r.rank DESC, v.work_id, v.content_hash"
+        );
+        let query =
+            values.iter().cloned().fold(sqlx::query_as::<_, LeftJoinRow>(&sql), |query, value| value.bind(query));
+        let query = values
+            .into_iter()
+            .fold(query.bind(i64::try_from(limit).unwrap_or(i64::MAX)), |query, value| value.bind(query));
+        let mut versions = fetch_grouped(query.fetch(&self.pool)).await?;
+        self.apply_overrides(versions.iter_mut().map(|(v, _)| v)).await?;
+        let mut ranks = HashMap::new();
+        for (version, _) in &versions {
+            let rank = ranks.len();
+            ranks.entry(version.metadata.work_id).or_insert(rank);
+        }
+        versions.sort_by(|(a, _), (b, _)| {
+            ranks[&a.metadata.work_id]
+                .cmp(&ranks[&b.metadata.work_id])
+                .then_with(|| b.partial_cmp(a).unwrap_or(Ordering::Less))
+        });
+        Ok(versions)
+    }
+
+    /// Get a page of summaries of the works with a version matching a
+    /// [`Filter`], by work ID, continuing `after` a previous page if given.
+    ///
+    /// For list screens of large libraries: only the columns a summary needs
+    /// are read, and nothing but the authors is decoded. Each work is
+    /// summarized by its most recently updated matching version (with the
+    /// most chapters, on the same day), which is its best version as far as
+    /// that goes; [`filter_versions`](Self::filter_versions) has the rest.
+    ///
+    /// # Errors
+    /// Returns [`ErrorKind::InvalidCursor`] for a cursor from another kind of
+    /// listing.
+    pub async fn list_work_summaries(
+        &self,
+        filter: &Filter,
+        after: Option<&Cursor>,
+        limit: usize,
+    ) -> Result<Page<VersionSummary>> {
+        let after = match after.map(Cursor::key).transpose()? {
+            Some(Key::Work(work_id)) => work_id,
+            Some(_) => exn::bail!(ErrorKind::InvalidCursor),
+            None => i64::MIN,
+        };
+        let (condition, values) = filter.to_sql();
+        let sql = format!(
+            "SELECT content_hash, work_id, title, authors, words, rating, last_modified FROM ( \
+                SELECT v.content_hash, v.work_id, v.title, v.authors, v.words, v.rating, v.last_modified, \
+                    row_number() OVER ( \
+                        PARTITION BY v.work_id \
+                        ORDER BY v.last_modified DESC, v.chapters_written DESC, v.extracted_at DESC \
+                    ) AS n \
+                FROM versions v WHERE {condition} AND v.work_id > ? \
+             ) WHERE n = 1 \
+             ORDER BY work_id LIMIT ?"
+        );
+        let query = values.into_iter().fold(sqlx::query_as::<_, SummaryRow>(&sql), |query, value| value.bind(query));
+        let rows =
+            query.bind(after).bind(overfetch(limit)?).fetch_all(&self.pool).await.or_raise(|| ErrorKind::Database)?;
+        let mut summaries = rows.into_iter().map(VersionSummary::try_from).collect::<Result<Vec<_>>>()?;
+        let overrides = self.all_overrides().await?;
+        for summary in &mut summaries {
+            if let Some(overrides) = overrides.get(&summary.work_id) {
+                summary.apply(overrides);
+            }
+        }
+        Ok(Page::from_overfetched(summaries, limit, |summary| {
+            Key::Work(i64::try_from(summary.work_id).unwrap_or(i64::MAX))
+        }))
+    }
+
+    /// Save a smart collection: a named [`Filter`] whose matching works are
+    /// computed when the collection is first listed, and on every
+    /// [refresh](Self::refresh_collection) after that.
+    ///
+    /// Saving over an existing collection replaces its filter and discards
+    /// its membership.
+    #[instrument(skip(self, filter))]
+    pub async fn save_collection(&self, name: impl AsRef<str> + std::fmt::Debug, filter: &Filter) -> Result<()> {
+        let filter = serde_json::to_string(filter).or_raise(|| ErrorKind::InvalidData("filter"))?;
+        if self.begin_write().await? {
+            return Ok(());
+        }
+        let mut tx = self.pool.begin().await.or_raise(|| ErrorKind::Database)?;
+        sqlx::query(include_str!("../queries/upsert_smart_collection.sql"))
+            .bind(name.as_ref())
+            .bind(filter)
+            .execute(&mut *tx)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        sqlx::query(include_str!("../queries/clear_smart_collection_works.sql"))
+            .bind(name.as_ref())
+            .execute(&mut *tx)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        tx.commit().await.or_raise(|| ErrorKind::Database)?;
+        Ok(())
+    }
+
+    /// List all smart collections, sorted by name.
+    pub async fn list_collections(&self) -> Result<Vec<SmartCollection>> {
+        let rows: Vec<(String, String, Option<i64>, i64)> =
+            sqlx::query_as(include_str!("../queries/list_smart_collections.sql"))
+                .fetch_all(&self.pool)
+                .await
+                .or_raise(|| ErrorKind::Database)?;
+        rows.into_iter()
+            .map(|(name, filter, refreshed_at, works)| {
+                Ok(SmartCollection {
+                    name,
+                    filter: serde_json::from_str(&filter).or_raise(|| ErrorKind::InvalidData("filter"))?,
+                    refreshed_at: refreshed_at
+                        .map(|t| {
+                            UtcDateTime::from_unix_timestamp(t).or_raise(|| ErrorKind::InvalidData("refreshed at"))
+                        })
+                        .transpose()?,
+                    works: u64::try_from(works).or_raise(|| ErrorKind::Database)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Recompute which works belong to a smart collection, returning how many do.
+    ///
+    /// Returns [`ErrorKind::CollectionNotFound`] for unknown collections.
+    #[instrument(skip(self))]
+    pub async fn refresh_collection(&self, name: impl AsRef<str> + std::fmt::Debug) -> Result<u64> {
+        Ok(self.compute_collection(name.as_ref()).await?.len() as u64)
+    }
+
+    /// List the IDs of works belonging to a smart collection, sorted ascending.
+    ///
+    /// Membership is computed the first time a collection is listed; after
+    /// that, it's only updated by [`refresh_collection`](Self::refresh_collection).
+    /// Returns [`ErrorKind::CollectionNotFound`] for unknown collections.
+    pub async fn list_collection_work_ids(&self, name: impl AsRef<str>) -> Result<Vec<u64>> {
+        let name = name.as_ref();
+        let (_, refreshed_at) = self.fetch_collection(name).await?;
+        if refreshed_at.is_none() {
+            return self.compute_collection(name).await;
+        }
+        let ids: Vec<i64> = sqlx::query_scalar(include_str!("../queries/list_smart_collection_works.sql"))
+            .bind(name)
             .fetch_all(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
-        let ids = ids
-            .into_iter()
-            .map(|id| u64::try_from(id).or_raise(|| ErrorKind::InvalidData("work id")))
-            .collect::<Result<Vec<u64>>>()?;
-        Ok(ids)
+        ids.into_iter().map(|id| u64::try_from(id).or_raise(|| ErrorKind::InvalidData("work id"))).collect()
     }
 
-    /// List all distinct work IDs that have files in the given target.
-    pub async fn list_all_work_ids_for_target(&self, target: impl AsRef<str>) -> Result<Vec<u64>> {
-        let ids: Vec<i64> = sqlx::query_scalar(include_str!("../queries/list_all_work_ids_for_target.sql"))
-            .bind(target.as_ref())
-            .fetch_all(&self.pool)
+    /// Delete a smart collection. Returns `true` if it existed.
+    #[instrument(skip(self))]
+    pub async fn delete_collection(&self, name: impl AsRef<str> + std::fmt::Debug) -> Result<bool> {
+        if self.begin_write().await? {
+            let exists: Option<(String, String, Option<i64>)> =
+                sqlx::query_as(include_str!("../queries/get_smart_collection.sql"))
+                    .bind(name.as_ref())
+                    .fetch_optional(&self.pool)
+                    .await
+                    .or_raise(|| ErrorKind::Database)?;
+            return Ok(exists.is_some());
+        }
+        let result = sqlx::query(include_str!("../queries/delete_smart_collection.sql"))
+            .bind(name.as_ref())
+            .execute(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
-        let ids = ids
-            .into_iter()
-            .map(|id| u64::try_from(id).or_raise(|| ErrorKind::InvalidData("work id")))
-            .collect::<Result<Vec<u64>>>()?;
-        Ok(ids)
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn fetch_collection(&self, name: &str) -> Result<(Filter, Option<i64>)> {
+        let (_, filter, refreshed_at): (String, String, Option<i64>) =
+            sqlx::query_as(include_str!("../queries/get_smart_collection.sql"))
+                .bind(name)
+                .fetch_optional(&self.pool)
+                .await
+                .or_raise(|| ErrorKind::Database)?
+                .ok_or_raise(|| ErrorKind::CollectionNotFound(name.to_string()))?;
+        let filter = serde_json::from_str(&filter).or_raise(|| ErrorKind::InvalidData("filter"))?;
+        Ok((filter, refreshed_at))
+    }
+
+    /// Evaluate a collection's filter, storing the resulting membership
+    /// (unless in dry-run mode, or read-only) and returning it.
+    async fn compute_collection(&self, name: &str) -> Result<Vec<u64>> {
+        let (filter, _) = self.fetch_collection(name).await?;
+        let (condition, values) = filter.to_sql();
+        // Read-only repositories compute membership without storing it.
+        let ids: Vec<(i64,)> = if self.mode.is_dry_run() || self.read_only {
+            let sql = format!("SELECT DISTINCT v.work_id FROM versions v WHERE {condition} ORDER BY v.work_id");
+            let query = values.into_iter().fold(sqlx::query_as(&sql), |query, value| value.bind(query));
+            query.fetch_all(&self.pool).await.or_raise(|| ErrorKind::Database)?
+        } else {
+            let mut tx = self.pool.begin().await.or_raise(|| ErrorKind::Database)?;
+            sqlx::query(include_str!("../queries/clear_smart_collection_works.sql"))
+                .bind(name)
+                .execute(&mut *tx)
+                .await
+                .or_raise(|| ErrorKind::Database)?;
+            let sql = format!(
+                "INSERT INTO smart_collection_works (name, work_id) SELECT DISTINCT ?, v.work_id FROM versions v WHERE {condition} RETURNING work_id"
+            );
+            let query = values.into_iter().fold(sqlx::query_as(&sql).bind(name), |query, value| value.bind(query));
+            let mut ids: Vec<(i64,)> = query.fetch_all(&mut *tx).await.or_raise(|| ErrorKind::Database)?;
+            ids.sort_unstable();
+            sqlx::query(include_str!("../queries/mark_smart_collection_refreshed.sql"))
+                .bind(UtcDateTime::now().unix_timestamp())
+                .bind(name)
+                .execute(&mut *tx)
+                .await
+                .or_raise(|| ErrorKind::Database)?;
+            tx.commit().await.or_raise(|| ErrorKind::Database)?;
+            ids
+        };
+        ids.into_iter().map(|(id,)| u64::try_from(id).or_raise(|| ErrorKind::InvalidData("work id"))).collect()
     }
 
     /* ============== *\
@@ -388,13 +2427,70 @@ impl Repository {
         old_path: impl AsRef<Path>,
         new_path: impl AsRef<Path>,
     ) -> Result<bool> {
-        if self.dry_run {
+        if self.begin_write().await? {
             return Ok(true);
         }
         let result = sqlx::query(include_str!("../queries/update_target_path.sql"))
-            .bind(Self::sqlx_hates_paths(new_path)?)
+            .bind(Self::sqlx_hates_paths(&new_path)?)
             .bind(target.as_ref())
-            .bind(Self::sqlx_hates_paths(old_path)?)
+            .bind(Self::sqlx_hates_paths(&old_path)?)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        let updated = result.rows_affected() > 0;
+        if updated
+            && !self.hooks.is_empty()
+            && let Some((file, version)) = self.get_by_target_path(target, new_path).await?
+        {
+            let old_path = old_path.as_ref().to_path_buf();
+            self.hooks.notify([RepositoryEvent::PathUpdated { file, version, old_path }]).await;
+        }
+        Ok(updated)
+    }
+
+    /// Flag a file as stale: changed in storage (going by its size or
+    /// modification time) since it was last read, so that the next scan
+    /// reads it again instead of trusting its record.
+    ///
+    /// The record is otherwise left as it is until it's replaced, which
+    /// clears the flag.
+    ///
+    /// Returns `true` if a record was flagged, `false` if `path` was not found.
+    pub async fn mark_stale(&self, target: impl AsRef<str>, path: impl AsRef<Path>) -> Result<bool> {
+        if self.begin_write().await? {
+            return Ok(true);
+        }
+        let result = sqlx::query(include_str!("../queries/mark_target_path_stale.sql"))
+            .bind(target.as_ref())
+            .bind(Self::sqlx_hates_paths(path)?)
+            .execute(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record when a file was last verified intact (its contents re-read,
+    /// and found to match its file hash), or forget that it ever was with
+    /// `None` (such as when it's found damaged), so that it's
+    /// [listed as stale](Self::list_stale) again.
+    ///
+    /// Replacing the file's record with a different file hash forgets its
+    /// verification too.
+    ///
+    /// Returns `true` if a record was updated, `false` if `path` was not found.
+    pub async fn mark_verified(
+        &self,
+        target: impl AsRef<str>,
+        path: impl AsRef<Path>,
+        verified_at: Option<UtcDateTime>,
+    ) -> Result<bool> {
+        if self.begin_write().await? {
+            return Ok(true);
+        }
+        let result = sqlx::query(include_str!("../queries/mark_target_path_verified.sql"))
+            .bind(verified_at.map(|at| at.unix_timestamp()))
+            .bind(target.as_ref())
+            .bind(Self::sqlx_hates_paths(path)?)
             .execute(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
@@ -446,6 +2542,18 @@ impl Repository {
         Ok(row.0 > 0)
     }
 
+    /// Check if the file record at the given target and path has been
+    /// [flagged as stale](Self::mark_stale).
+    pub async fn is_stale(&self, target: impl AsRef<str>, path: impl AsRef<Path>) -> Result<bool> {
+        let row: (i64,) = sqlx::query_as(include_str!("../queries/target_path_is_stale.sql"))
+            .bind(target.as_ref())
+            .bind(Self::sqlx_hates_paths(path)?)
+            .fetch_one(&self.pool)
+            .await
+            .or_raise(|| ErrorKind::Database)?;
+        Ok(row.0 > 0)
+    }
+
     /// Check if a file with the given compressed file hash exists in any target.
     ///
     /// Useful for detecting if an identical compressed file exists elsewhere
@@ -544,6 +2652,8 @@ impl Repository {
     ///
     /// These may be intentional (tracking history) or candidates for cleanup.
     /// Returns a list of (work_id, version_count) tuples, sorted by count descending.
+    /// Versions recorded under an alias are counted towards (and reported as)
+    /// the canonical work ID.
     ///
     /// Use [`get_by_work_id`](Self::get_by_work_id) to retrieve the versions
     /// and determine which to keep.
@@ -573,15 +2683,20 @@ impl Repository {
     /// Returns `true` if a record was deleted, `false` if the path was not found.
     #[instrument(skip_all, fields(target = target.as_ref(), path = %path.as_ref().display()))]
     pub async fn delete_by_target_path(&self, target: impl AsRef<str>, path: impl AsRef<Path>) -> Result<bool> {
-        if self.dry_run {
+        if self.begin_write().await? {
             return Ok(true);
         }
+        let deleted = match self.hooks.is_empty() {
+            true => None,
+            false => self.get_by_target_path(&target, &path).await?,
+        };
         let result = sqlx::query(include_str!("../queries/delete_by_target_path.sql"))
             .bind(target.as_ref())
             .bind(Self::sqlx_hates_paths(path)?)
             .execute(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
+        self.files_deleted(deleted).await;
         // Calling self.delete_orphaned_versions() is the responsibility of
         // the callee (orchestrator in app binary).
         Ok(result.rows_affected() > 0)
@@ -599,15 +2714,20 @@ impl Repository {
         target: impl AsRef<str>,
         file_hash: impl AsRef<str>,
     ) -> Result<bool> {
-        if self.dry_run {
+        if self.begin_write().await? {
             return Ok(true);
         }
+        let deleted = match self.hooks.is_empty() {
+            true => Vec::new(),
+            false => self.get_by_target_file_hash(&target, &file_hash).await?,
+        };
         let result = sqlx::query(include_str!("../queries/delete_by_target_file_hash.sql"))
             .bind(target.as_ref())
             .bind(file_hash.as_ref())
             .execute(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
+        self.files_deleted(deleted).await;
         Ok(result.rows_affected() > 0)
     }
 
@@ -619,14 +2739,19 @@ impl Repository {
     /// Returns `true` if any records were deleted.
     #[instrument(skip_all, fields(file_hash = file_hash.as_ref()))]
     pub async fn delete_by_file_hash_across_targets(&self, file_hash: impl AsRef<str>) -> Result<bool> {
-        if self.dry_run {
+        if self.begin_write().await? {
             return Ok(true);
         }
+        let deleted = match self.hooks.is_empty() {
+            true => Vec::new(),
+            false => self.get_by_file_hash_across_targets(&file_hash).await?,
+        };
         let result = sqlx::query(include_str!("../queries/delete_by_file_hash_across_targets.sql"))
             .bind(file_hash.as_ref())
             .execute(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
+        self.files_deleted(deleted).await;
         Ok(result.rows_affected() > 0)
     }
 
@@ -638,14 +2763,19 @@ impl Repository {
     /// Returns `true` if the version was deleted, `false` if it was not found.
     #[instrument(skip_all, fields(content_hash = content_hash.as_ref()))]
     pub async fn delete_by_content_hash(&self, content_hash: impl AsRef<str>) -> Result<bool> {
-        if self.dry_run {
+        if self.begin_write().await? {
             return Ok(true);
         }
+        let deleted = match self.hooks.is_empty() {
+            true => None,
+            false => self.get_by_content_hash(&content_hash).await?,
+        };
         let result = sqlx::query(include_str!("../queries/delete_by_content_hash.sql"))
             .bind(content_hash.as_ref())
             .execute(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
+        self.hooks.notify(versions_deleted(deleted.into_iter().collect())).await;
         Ok(result.rows_affected() > 0)
     }
 
@@ -657,14 +2787,21 @@ impl Repository {
     /// Returns `true` if any versions were deleted.
     #[instrument(skip_all, fields(work_id = work_id))]
     pub async fn delete_by_work_id(&self, work_id: u64) -> Result<bool> {
-        if self.dry_run {
+        if self.begin_write().await? {
             return Ok(true);
         }
+        let mut deleted = match self.hooks.is_empty() {
+            true => Vec::new(),
+            false => self.get_by_work_id(work_id).await?,
+        };
+        // Versions recorded under aliases of the work are left alone.
+        deleted.retain(|(version, _)| version.metadata.work_id == work_id);
         let result = sqlx::query(include_str!("../queries/delete_by_work_id.sql"))
             .bind(i64::try_from(work_id).or_raise(|| ErrorKind::Database)?)
             .execute(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
+        self.hooks.notify(versions_deleted(deleted)).await;
         Ok(result.rows_affected() > 0)
     }
 
@@ -680,7 +2817,7 @@ impl Repository {
     /// Returns the number of orphaned versions deleted.
     #[instrument(skip_all)]
     pub async fn delete_orphaned_versions(&self) -> Result<u64> {
-        if self.dry_run {
+        if self.begin_write().await? {
             let row: (i64,) = sqlx::query_as(include_str!("../queries/count_orphan_versions.sql"))
                 .fetch_one(&self.pool)
                 .await
@@ -691,12 +2828,26 @@ impl Repository {
             // Return zero even if it's wrong.
             return Ok(u64::try_from(row.0).unwrap_or(0));
         }
+        let deleted: Vec<VersionRow> = match self.hooks.is_empty() {
+            true => Vec::new(),
+            false => sqlx::query_as(include_str!("../queries/list_orphan_versions.sql"))
+                .fetch_all(&self.pool)
+                .await
+                .or_raise(|| ErrorKind::Database)?,
+        };
+        let deleted = deleted.into_iter().map(|row| Ok((row.try_into()?, Vec::new()))).collect::<Result<_>>()?;
         let result = sqlx::query(include_str!("../queries/delete_orphan_versions.sql"))
             .execute(&self.pool)
             .await
             .or_raise(|| ErrorKind::Database)?;
+        self.hooks.notify(versions_deleted(deleted)).await;
         Ok(result.rows_affected())
     }
+
+    async fn files_deleted(&self, deleted: impl IntoIterator<Item = FileResult>) {
+        let events = deleted.into_iter().map(|(file, version)| RepositoryEvent::FileDeleted { file, version });
+        self.hooks.notify(events).await;
+    }
 }
 
 #[cfg(test)]
@@ -704,7 +2855,9 @@ mod tests {
     use super::*;
     use crate::{Database, File, Version};
     use rawr_compress::Compression;
-    use rawr_extract::models::{Chapters, Language, Metadata, Rating};
+    use rawr_extract::models::{
+        Chapters, DetectedLanguage, Encoding, Field, Language, Metadata, Provenance, Rating, SeriesPosition,
+    };
     use rawr_storage::file::FileMeta;
     use time::{Date, UtcDateTime};
 
@@ -715,6 +2868,8 @@ mod tests {
             hash: content_hash.to_string(),
             length: 1000,
             crc32: 12_345_678,
+            encoding: Encoding::Utf8,
+            detected_language: None,
             metadata: Metadata {
                 work_id,
                 title: "Test Work".to_string(),
@@ -732,6 +2887,8 @@ mod tests {
                 words: 1000,
                 published: Date::from_calendar_date(2024, time::Month::January, 1).unwrap(),
                 last_modified: Date::from_calendar_date(2024, time::Month::January, 1).unwrap(),
+                provenance: Default::default(),
+                work_skin: None,
                 series: vec![],
             },
             extracted_at: UtcDateTime::now(),
@@ -763,6 +2920,56 @@ mod tests {
         assert_eq!(file.content_hash, "content_abc");
     }
 
+    #[tokio::test]
+    async fn test_dry_run() {
+        let repo = make_repository().await;
+        let dry_run = repo.clone().with_mode(Mode::DryRun);
+        assert_eq!((Mode::Real, Mode::DryRun), (repo.mode(), dry_run.mode()));
+        dry_run
+            .upsert(&make_test_file("work.html.bz2", "content_abc"), &make_test_version(1, "content_abc"))
+            .await
+            .unwrap();
+        assert!(repo.get_by_target_path(DEFAULT_TARGET, "work.html.bz2").await.unwrap().is_none());
+        let lock = dry_run.lock_target(DEFAULT_TARGET, "organize").await.unwrap();
+        assert!(repo.get_target_lock(DEFAULT_TARGET).await.unwrap().is_none());
+        lock.release().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_versions_for_target() {
+        let repo = make_repository().await;
+        let files = [
+            ("a/1.html", 1, "hash_a"),
+            ("b/2.html", 2, "hash_b"),
+            ("a/2.html", 1, "hash_a"),
+            ("a/3.html", 1, "hash_a"),
+            ("c/3.html", 2, "hash_c"),
+        ];
+        for (path, work_id, hash) in files {
+            repo.upsert(&make_test_file(path, hash), &make_test_version(work_id, hash)).await.unwrap();
+        }
+        let versions = repo.list_versions_for_target(DEFAULT_TARGET).await.unwrap();
+        let mut grouped: Vec<_> = versions
+            .iter()
+            .map(|(version, files)| {
+                let mut paths: Vec<_> = files.iter().map(|f| f.path.to_str().unwrap()).collect();
+                paths.sort();
+                (version.metadata.work_id, version.hash.as_str(), paths)
+            })
+            .collect();
+        // Newest work first; versions of the same work were extracted together.
+        assert_eq!(vec![2, 2, 1], grouped.iter().map(|(work_id, _, _)| *work_id).collect::<Vec<_>>());
+        grouped.sort();
+        assert_eq!(
+            vec![
+                (1, "hash_a", vec!["a/1.html", "a/2.html", "a/3.html"]),
+                (2, "hash_b", vec!["b/2.html"]),
+                (2, "hash_c", vec!["c/3.html"]),
+            ],
+            grouped
+        );
+    }
+
     #[tokio::test]
     async fn test_upsert() {
         let repo = make_repository().await;
@@ -776,6 +2983,63 @@ mod tests {
         assert_eq!(2, repo.count_scanned_files().await.unwrap());
     }
 
+    #[tokio::test]
+    async fn test_write_coalescing() {
+        use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+        let repo = make_repository().await.with_write_coalescing(3, Duration::from_millis(50));
+        let notified = Arc::new(AtomicUsize::new(0));
+        repo.subscribe({
+            let notified = notified.clone();
+            move |_| {
+                notified.fetch_add(1, AtomicOrdering::Relaxed);
+                async {}
+            }
+        });
+        let version = make_test_version(12345, "content_abc");
+        let upsert = async |n: usize| repo.upsert(&make_test_file(&format!("{n}.html"), "content_abc"), &version).await;
+        upsert(1).await.unwrap();
+        upsert(2).await.unwrap();
+        // Queued, and not written yet.
+        assert_eq!(0, repo.count_scanned_files().await.unwrap());
+        // Filling the queue writes it.
+        upsert(3).await.unwrap();
+        assert_eq!(3, repo.count_scanned_files().await.unwrap());
+        assert_eq!(3, notified.load(AtomicOrdering::Relaxed));
+        // So does waiting for the interval.
+        upsert(4).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(4, repo.count_scanned_files().await.unwrap());
+
+        // A bad upsert doesn't take the rest of its batch down with it.
+        let mut bad = make_test_version(12345, "content_bad");
+        bad.metadata.series = vec![SeriesPosition {
+            id: u64::MAX,
+            name: "Too Long".to_string(),
+            position: 1,
+        }];
+        upsert(5).await.unwrap();
+        repo.upsert(&make_test_file("bad.html", "content_bad"), &bad).await.unwrap();
+        assert!(repo.flush().await.is_err());
+        assert_eq!(5, repo.count_scanned_files().await.unwrap());
+        assert_eq!(5, notified.load(AtomicOrdering::Relaxed));
+        repo.flush().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_coalescing_order() {
+        let repo = make_repository().await.with_write_coalescing(100, Duration::from_secs(60));
+        let version = make_test_version(12345, "content_abc");
+        repo.upsert(&make_test_file("a.html", "content_abc"), &version).await.unwrap();
+        repo.upsert(&make_test_file("b.html", "content_abc"), &version).await.unwrap();
+        // Other writes see the upserts queued before them...
+        assert!(repo.delete_by_target_path(DEFAULT_TARGET, "a.html").await.unwrap());
+        assert!(repo.update_target_path(DEFAULT_TARGET, Path::new("b.html"), Path::new("c.html")).await.unwrap());
+        // ...which flushing doesn't bring back.
+        repo.flush().await.unwrap();
+        let files = repo.list_files_for_target(DEFAULT_TARGET).await.unwrap();
+        assert_eq!(vec![Path::new("c.html")], files.iter().map(|(f, _)| f.path.as_path()).collect::<Vec<_>>());
+    }
+
     #[tokio::test]
     async fn test_left_join_orphaned_version() {
         let path = "fandoms/work.html.bz2";
@@ -825,6 +3089,55 @@ mod tests {
         assert!(repo.get_by_target_path(DEFAULT_TARGET, "new/path.html.bz2").await.unwrap().is_some());
     }
 
+    #[tokio::test]
+    async fn test_stale() {
+        let repo = make_repository().await;
+        let version = make_test_version(12345, "content_abc");
+        let file = make_test_file("work.html", "content_abc");
+        repo.upsert(&file, &version).await.unwrap();
+        assert!(!repo.is_stale(DEFAULT_TARGET, "work.html").await.unwrap());
+        assert!(repo.mark_stale(DEFAULT_TARGET, "work.html").await.unwrap());
+        assert!(!repo.mark_stale(DEFAULT_TARGET, "missing.html").await.unwrap());
+        assert!(repo.is_stale(DEFAULT_TARGET, "work.html").await.unwrap());
+        assert_eq!(vec!["work.html"], repo.list_stale_paths_for_target(DEFAULT_TARGET).await.unwrap());
+        // Re-reading the file (even to find it unchanged) clears the flag.
+        repo.upsert(&file, &version).await.unwrap();
+        assert!(!repo.is_stale(DEFAULT_TARGET, "work.html").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_verification_staleness() {
+        let repo = make_repository().await;
+        let version = make_test_version(12345, "content_abc");
+        let file = make_test_file("work.html", "content_abc");
+        repo.upsert(&file, &version).await.unwrap();
+        repo.upsert(&make_test_file("other.html", "content_abc"), &version).await.unwrap();
+        let paths = |files: Vec<FileResult>| files.into_iter().map(|(f, _)| f.path.clone()).collect::<Vec<_>>();
+        let now = UtcDateTime::now();
+        let week_ago = now - time::Duration::weeks(1);
+        // Never verified.
+        assert_eq!(2, repo.list_stale(DEFAULT_TARGET, week_ago).await.unwrap().len());
+
+        assert!(repo.mark_verified(DEFAULT_TARGET, "work.html", Some(now)).await.unwrap());
+        assert!(!repo.mark_verified(DEFAULT_TARGET, "missing.html", Some(now)).await.unwrap());
+        assert_eq!(vec![Path::new("other.html")], paths(repo.list_stale(DEFAULT_TARGET, week_ago).await.unwrap()));
+        // Verified, but longer ago than asked for.
+        let stale = repo.list_stale(DEFAULT_TARGET, now + time::Duration::hours(1)).await.unwrap();
+        assert_eq!(2, stale.len());
+
+        // Re-scanning the same file keeps its verification; replacing it doesn't.
+        repo.upsert(&file, &version).await.unwrap();
+        assert_eq!(1, repo.list_stale(DEFAULT_TARGET, week_ago).await.unwrap().len());
+        let mut replaced = file.clone();
+        replaced.file_hash = "file_hash_456".to_string();
+        repo.upsert(&replaced, &version).await.unwrap();
+        assert_eq!(2, repo.list_stale(DEFAULT_TARGET, week_ago).await.unwrap().len());
+
+        repo.mark_verified(DEFAULT_TARGET, "work.html", Some(now)).await.unwrap();
+        repo.mark_verified(DEFAULT_TARGET, "work.html", None).await.unwrap();
+        assert_eq!(2, repo.list_stale(DEFAULT_TARGET, week_ago).await.unwrap().len());
+    }
+
     #[tokio::test]
     async fn test_cascade_delete() {
         let repo = make_repository().await;
@@ -837,6 +3150,158 @@ mod tests {
         assert!(retrieved.is_none());
     }
 
+    #[tokio::test]
+    async fn test_completeness_filter() {
+        let repo = make_repository().await;
+        let complete = make_test_version(111, "hash1");
+        let mut wip = make_test_version(222, "hash2");
+        wip.metadata.chapters = Chapters { written: 1, total: None };
+        repo.upsert(&make_test_file("complete.html.bz2", "hash1"), &complete).await.unwrap();
+        repo.upsert(&make_test_file("wip.html.bz2", "hash2"), &wip).await.unwrap();
+        let files = repo.list_files_for_target_by_completeness(DEFAULT_TARGET, true).await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].1.metadata.work_id, 111);
+        let files = repo.list_files_for_target_by_completeness(DEFAULT_TARGET, false).await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].1.metadata.work_id, 222);
+    }
+
+    #[tokio::test]
+    async fn test_series() {
+        let repo = make_repository().await;
+        let part = |work_id: u64, hash: &str, position: u32| {
+            let mut version = make_test_version(work_id, hash);
+            version.metadata.series = vec![SeriesPosition::new(7, "Saga", position)];
+            version
+        };
+        repo.upsert(&make_test_file("one.html.bz2", "hash1"), &part(111, "hash1", 1)).await.unwrap();
+        repo.upsert(&make_test_file("four.html.bz2", "hash4"), &part(444, "hash4", 4)).await.unwrap();
+        repo.upsert(&make_test_file("two.html.bz2", "hash2"), &part(222, "hash2", 2)).await.unwrap();
+
+        let series = repo.list_series().await.unwrap();
+        assert_eq!(
+            series,
+            vec![Series {
+                id: 7,
+                name: "Saga".to_string(),
+                works: 3,
+                last_position: 4
+            }]
+        );
+        let works = repo.list_works_in_series(7).await.unwrap();
+        let order: Vec<_> = works.iter().map(|(pos, v, _)| (*pos, v.metadata.work_id)).collect();
+        assert_eq!(order, vec![(1, 111), (2, 222), (4, 444)]);
+        assert_eq!(repo.find_series_gaps(7).await.unwrap(), vec![3]);
+        assert!(repo.find_series_gaps(8).await.unwrap().is_empty());
+
+        // Deleting a version removes it from the series.
+        repo.delete_by_content_hash("hash4").await.unwrap();
+        assert!(repo.find_series_gaps(7).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_authors() {
+        let repo = make_repository().await;
+        let mut version1 = make_test_version(111, "hash1");
+        version1.metadata.authors = vec![Author::new("user", None::<&str>), Author::new("other", Some("pseud"))];
+        let mut version2 = make_test_version(111, "hash2");
+        version2.metadata.authors = vec![Author::new("User", Some("user"))];
+        let mut version3 = make_test_version(222, "hash3");
+        version3.metadata.authors = vec![Author::new("user_name", Some("pseud"))];
+        let mut version4 = make_test_version(333, "hash4");
+        version4.metadata.authors = vec![Author::new("USER", None::<&str>), Author::new("Other", Some("PSEUD"))];
+        repo.upsert(&make_test_file("path1.html.bz2", "hash1"), &version1).await.unwrap();
+        repo.upsert(&make_test_file("path2.html.bz2", "hash2"), &version2).await.unwrap();
+        repo.upsert(&make_test_file("path3.html.bz2", "hash3"), &version3).await.unwrap();
+        repo.upsert(&make_test_file("path4.html.bz2", "hash4"), &version4).await.unwrap();
+
+        // However they're cased, "user" and "pseud (other)" are each one author.
+        let authors = repo.list_authors_with_counts().await.unwrap();
+        let counts: Vec<_> = authors.iter().map(|(author, count)| (author.key(), *count)).collect();
+        assert_eq!(
+            counts,
+            [
+                (("other".to_string(), Some("pseud".to_string())), 2),
+                (("user".to_string(), None), 2),
+                (("user_name".to_string(), Some("pseud".to_string())), 1),
+            ]
+        );
+
+        // Both versions of work 111 and work 333, but not "user_name"'s work.
+        let works = repo.list_works_by_author("USER", None).await.unwrap();
+        let ids: Vec<_> = works.iter().map(|(v, _)| v.metadata.work_id).collect();
+        assert_eq!(ids, [111, 111, 333]);
+        let works = repo.list_works_by_author("other", Some("Pseud")).await.unwrap();
+        assert_eq!(works.len(), 2);
+        assert!(repo.list_works_by_author("other", None).await.unwrap().len() == 2);
+        assert!(repo.list_works_by_author("other", Some("other")).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_author_identities() {
+        let repo = make_repository().await;
+        let (user, pseud, alt) = (
+            Author::new("user", None::<&str>),
+            Author::new("user", Some("pseud")),
+            Author::new("alt_account", None::<&str>),
+        );
+        let mut version1 = make_test_version(111, "hash1");
+        version1.metadata.authors = vec![user.clone()];
+        let mut version2 = make_test_version(222, "hash2");
+        version2.metadata.authors = vec![pseud.clone()];
+        let mut version3 = make_test_version(333, "hash3");
+        version3.metadata.authors = vec![alt.clone(), user.clone()];
+        for (i, version) in [version1, version2, version3].iter().enumerate() {
+            let hash = &version.hash;
+            repo.upsert(&make_test_file(&format!("path{i}.html"), hash), version).await.unwrap();
+        }
+        assert_eq!(3, repo.list_authors_with_counts().await.unwrap().len());
+
+        repo.merge_author(&pseud, &user).await.unwrap();
+        repo.merge_author(&alt, &pseud).await.unwrap();
+        assert_eq!(user, repo.resolve_author(&alt).await.unwrap());
+        assert_eq!(vec![alt.clone(), pseud.clone()], repo.list_merged_authors(&user).await.unwrap());
+        assert!(repo.merge_author(&user, &alt).await.is_err());
+        // Each work is counted once, under the canonical author.
+        assert_eq!(vec![(user.clone(), 3)], repo.list_authors_with_counts().await.unwrap());
+        assert_eq!(3, repo.list_works_by_author("alt_account", None).await.unwrap().len());
+        assert_eq!(3, repo.list_works_by_author("user", Some("pseud")).await.unwrap().len());
+        // The original bylines are untouched.
+        let (version, _) = repo.get_by_content_hash("hash2").await.unwrap().unwrap();
+        assert_eq!(vec![pseud.clone()], version.metadata.authors);
+
+        assert!(repo.unmerge_author(&alt).await.unwrap());
+        assert!(!repo.unmerge_author(&alt).await.unwrap());
+        assert_eq!(vec![(user.clone(), 3), (alt.clone(), 1)], repo.list_authors_with_counts().await.unwrap());
+        assert_eq!(1, repo.list_works_by_author("alt_account", None).await.unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_author_identities_ignore_case() {
+        let repo = make_repository().await;
+        let (foo, lower, bar) =
+            (Author::new("Foo", None::<&str>), Author::new("foo", None::<&str>), Author::new("bar", None::<&str>));
+        for (i, author) in [&foo, &lower, &bar].into_iter().enumerate() {
+            let mut version = make_test_version(111 * (i as u64 + 1), &format!("hash{i}"));
+            version.metadata.authors = vec![author.clone()];
+            repo.upsert(&make_test_file(&format!("path{i}.html"), &version.hash), &version).await.unwrap();
+        }
+
+        // "Foo" and "foo" are one author, so merging either spelling covers both.
+        repo.merge_author(&foo, &bar).await.unwrap();
+        assert_eq!(bar, repo.resolve_author(&lower).await.unwrap());
+        assert_eq!(vec![(bar.clone(), 3)], repo.list_authors_with_counts().await.unwrap());
+        assert_eq!(3, repo.list_works_by_author("bar", None).await.unwrap().len());
+        assert!(repo.merge_author(&bar, &lower).await.is_err());
+        // Merging another spelling replaces the merge, rather than adding to it.
+        repo.merge_author(&lower, &Author::new("BAR", None::<&str>)).await.unwrap();
+        assert_eq!(vec![lower.clone()], repo.list_merged_authors(&bar).await.unwrap());
+
+        assert!(repo.unmerge_author(&Author::new("FOO", None::<&str>)).await.unwrap());
+        assert_eq!(foo, repo.resolve_author(&foo).await.unwrap());
+        assert_eq!(2, repo.list_authors_with_counts().await.unwrap().len());
+    }
+
     #[tokio::test]
     async fn test_find_duplicate_content() {
         let repo = make_repository().await;
@@ -852,4 +3317,514 @@ mod tests {
         assert_eq!(dups.len(), 1);
         assert_eq!(dups[0], ("hash1".to_string(), 2));
     }
+
+    #[tokio::test]
+    async fn test_aliases() {
+        let repo = make_repository().await;
+        repo.upsert(&make_test_file("old.html.bz2", "hash_old"), &make_test_version(111, "hash_old")).await.unwrap();
+        repo.upsert(&make_test_file("new.html.bz2", "hash_new"), &make_test_version(222, "hash_new")).await.unwrap();
+        assert!(repo.find_works_with_multiple_versions().await.unwrap().is_empty());
+
+        repo.register_alias(111, 222).await.unwrap();
+        assert_eq!(222, repo.resolve_work_id(111).await.unwrap());
+        assert_eq!(222, repo.resolve_work_id(222).await.unwrap());
+        assert_eq!(2, repo.get_by_work_id(111).await.unwrap().len());
+        assert_eq!(2, repo.get_by_work_id(222).await.unwrap().len());
+        assert_eq!(vec![(222, 2)], repo.find_works_with_multiple_versions().await.unwrap());
+
+        // Chains are flattened, and cycles are refused.
+        repo.register_alias(222, 333).await.unwrap();
+        assert_eq!(vec![111, 222], repo.list_aliases(333).await.unwrap());
+        assert!(repo.register_alias(333, 111).await.is_err());
+        assert!(repo.register_alias(333, 333).await.is_err());
+
+        assert!(repo.remove_alias(111).await.unwrap());
+        assert!(!repo.remove_alias(111).await.unwrap());
+        assert_eq!(1, repo.get_by_work_id(111).await.unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_updated_since_read() {
+        let repo = make_repository().await;
+        assert!(!repo.mark_read(111, UtcDateTime::now()).await.unwrap());
+        repo.upsert(&make_test_file("111.html.bz2", "hash_111"), &make_test_version(111, "hash_111")).await.unwrap();
+        repo.upsert(&make_test_file("222.html.bz2", "hash_222"), &make_test_version(222, "hash_222")).await.unwrap();
+        assert!(repo.mark_read(111, UtcDateTime::now()).await.unwrap());
+        assert!(repo.mark_read(222, UtcDateTime::now()).await.unwrap());
+        assert_eq!(1, repo.get_reading_status(111).await.unwrap().unwrap().chapters_written);
+        assert!(repo.updated_since_read().await.unwrap().is_empty());
+
+        // A new chapter for one, a later edit for the other.
+        let mut chapter = make_test_version(111, "hash_111_2");
+        chapter.metadata.chapters = Chapters { written: 3, total: None };
+        repo.upsert(&make_test_file("111-2.html.bz2", "hash_111_2"), &chapter).await.unwrap();
+        let mut edited = make_test_version(222, "hash_222_2");
+        edited.metadata.last_modified = Date::from_calendar_date(2024, time::Month::March, 1).unwrap();
+        repo.upsert(&make_test_file("222-2.html.bz2", "hash_222_2"), &edited).await.unwrap();
+        let updated = repo.updated_since_read().await.unwrap();
+        assert_eq!(
+            vec![(111, 2), (222, 0)],
+            updated.iter().map(|u| (u.status.work_id, u.new_chapters())).collect::<Vec<_>>()
+        );
+        assert_eq!("hash_111_2", updated[0].version.hash);
+
+        assert!(repo.mark_read(111, UtcDateTime::now()).await.unwrap());
+        assert!(repo.mark_unread(222).await.unwrap());
+        assert!(!repo.mark_unread(222).await.unwrap());
+        assert!(repo.updated_since_read().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scan_runs() {
+        let repo = make_repository().await;
+        let run = repo.start_scan_run(DEFAULT_TARGET, Some(Path::new("Fandom"))).await.unwrap();
+        assert_eq!(vec![run.clone()], repo.list_scan_runs().await.unwrap());
+
+        assert!(repo.checkpoint_scan_run(&run.run_id, Path::new("Fandom/123.html.bz2"), 42).await.unwrap());
+        let saved = repo.get_scan_run(&run.run_id).await.unwrap().unwrap();
+        assert_eq!(Some(Path::new("Fandom/123.html.bz2")), saved.checkpoint.as_deref());
+        assert_eq!((42, Some(Path::new("Fandom"))), (saved.scanned, saved.prefix.as_deref()));
+
+        assert!(repo.finish_scan_run(&run.run_id).await.unwrap());
+        assert!(!repo.finish_scan_run(&run.run_id).await.unwrap());
+        assert!(!repo.checkpoint_scan_run(&run.run_id, Path::new("Fandom/456.html.bz2"), 43).await.unwrap());
+        assert!(repo.list_scan_runs().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_attributes() {
+        let repo = make_repository().await;
+        repo.upsert(&make_test_file("111.html.bz2", "hash_111"), &make_test_version(111, "hash_111")).await.unwrap();
+        repo.set_file_attribute(DEFAULT_TARGET, "111.html.bz2", "synced", "no").await.unwrap();
+        repo.set_file_attribute(DEFAULT_TARGET, "111.html.bz2", "synced", "yes").await.unwrap();
+        repo.set_version_attribute("hash_111", "external_id", "abc").await.unwrap();
+        let error = repo.set_file_attribute(DEFAULT_TARGET, "222.html.bz2", "synced", "yes").await.unwrap_err();
+        assert!(matches!(&*error, ErrorKind::FileNotFound(..)));
+        let error = repo.set_version_attribute("hash_222", "external_id", "def").await.unwrap_err();
+        assert!(matches!(&*error, ErrorKind::VersionNotFound(_)));
+
+        let attributes = |pairs: &[(&str, &str)]| -> Attributes {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        assert_eq!(
+            attributes(&[("synced", "yes")]),
+            repo.get_file_attributes(DEFAULT_TARGET, "111.html.bz2").await.unwrap()
+        );
+        assert_eq!(attributes(&[("external_id", "abc")]), repo.get_version_attributes("hash_111").await.unwrap());
+
+        // File attributes follow renames...
+        repo.update_target_path(DEFAULT_TARGET, "111.html.bz2", "moved.html.bz2").await.unwrap();
+        assert!(repo.get_file_attributes(DEFAULT_TARGET, "111.html.bz2").await.unwrap().is_empty());
+        assert!(repo.remove_file_attribute(DEFAULT_TARGET, "moved.html.bz2", "synced").await.unwrap());
+        assert!(!repo.remove_file_attribute(DEFAULT_TARGET, "moved.html.bz2", "synced").await.unwrap());
+
+        // ...and are deleted with their file or version.
+        repo.set_file_attribute(DEFAULT_TARGET, "moved.html.bz2", "synced", "yes").await.unwrap();
+        repo.delete_by_content_hash("hash_111").await.unwrap();
+        assert!(repo.get_file_attributes(DEFAULT_TARGET, "moved.html.bz2").await.unwrap().is_empty());
+        assert!(repo.get_version_attributes("hash_111").await.unwrap().is_empty());
+        assert!(!repo.remove_version_attribute("hash_111", "external_id").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_chunks() {
+        use rawr_storage::BackendHandle;
+        use rawr_storage::backend::{ChunkedBackend, MockBackend, StorageBackend};
+        use rawr_storage::chunk::ChunkIndex;
+
+        let repo = make_repository().await;
+        let chunk = |hash: &str| Chunk {
+            offset: 0,
+            length: 10,
+            hash: hash.to_string(),
+        };
+        repo.record_chunks("backup", Path::new("a.html"), &[chunk("aa"), chunk("bb")]).await.unwrap();
+        repo.record_chunks("backup", Path::new("b.html"), &[chunk("bb")]).await.unwrap();
+        let stored = repo.stored_chunks("backup", &["aa", "bb", "cc"]).await.unwrap();
+        assert_eq!(HashSet::from(["aa".to_string(), "bb".to_string()]), stored);
+        assert!(repo.stored_chunks("elsewhere", &["aa"]).await.unwrap().is_empty());
+        // Re-recording a file replaces its chunks; others' are kept.
+        repo.record_chunks("backup", Path::new("a.html"), &[chunk("cc")]).await.unwrap();
+        assert_eq!(2, repo.stored_chunks("backup", &["aa", "bb", "cc"]).await.unwrap().len());
+        assert!(repo.forget_chunks("backup", Path::new("b.html")).await.unwrap());
+        assert!(!repo.forget_chunks("backup", Path::new("b.html")).await.unwrap());
+
+        // A chunked backend keeps its index in the cache.
+        let inner: BackendHandle = Arc::new(MockBackend::default().with_name("backup"));
+        let index: Arc<dyn ChunkIndex> = Arc::new(repo.clone());
+        let backend = ChunkedBackend::new(inner).with_index(index);
+        backend.write(Path::new("work.html"), b"<html>Hello</html>").await.unwrap();
+        let hash = rawr_storage::chunk::chunk(b"<html>Hello</html>", &Default::default()).remove(0).hash;
+        assert_eq!(1, repo.stored_chunks("backup", &[&hash]).await.unwrap().len());
+        backend.rename(Path::new("work.html"), Path::new("moved.html")).await.unwrap();
+        assert_eq!(1, repo.stored_chunks("backup", &[&hash]).await.unwrap().len());
+        backend.delete(Path::new("moved.html")).await.unwrap();
+        assert!(repo.stored_chunks("backup", &[&hash]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_settings() {
+        let repo = make_repository().await;
+        assert_eq!(None, repo.get_setting::<String>("tui", "template").await.unwrap());
+        repo.set_setting("tui", "template", "compact").await.unwrap();
+        repo.set_setting("tui", "page_size", &50).await.unwrap();
+        repo.set_setting("tui", "dark_mode", &true).await.unwrap();
+        repo.set_setting("cli", "template", &["a", "b"]).await.unwrap();
+        assert_eq!(Some("compact".to_string()), repo.get_setting("tui", "template").await.unwrap());
+        assert_eq!(Some(50i64), repo.get_setting("tui", "page_size").await.unwrap());
+        assert_eq!(Some(true), repo.get_setting("tui", "dark_mode").await.unwrap());
+        assert_eq!(Some(vec!["a".to_string(), "b".to_string()]), repo.get_setting("cli", "template").await.unwrap());
+        // Asking for the wrong type is an error, not a missing setting.
+        assert!(repo.get_setting::<bool>("tui", "template").await.is_err());
+
+        repo.set_setting("tui", "page_size", &100).await.unwrap();
+        let settings = repo.list_settings("tui").await.unwrap();
+        assert_eq!(vec!["dark_mode", "page_size", "template"], settings.keys().collect::<Vec<_>>());
+        assert_eq!(serde_json::json!(100), settings["page_size"]);
+
+        assert!(repo.remove_setting("tui", "template").await.unwrap());
+        assert!(!repo.remove_setting("tui", "template").await.unwrap());
+        assert!(repo.get_setting::<Vec<String>>("cli", "template").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_tombstones() {
+        let repo = make_repository().await;
+        repo.upsert(&make_test_file("111.html.bz2", "hash_111"), &make_test_version(111, "hash_111")).await.unwrap();
+        repo.upsert(&make_test_file("222.html.bz2", "hash_222"), &make_test_version(222, "hash_222")).await.unwrap();
+        repo.upsert(&make_test_file("333.html.bz2", "hash_333"), &make_test_version(333, "hash_333")).await.unwrap();
+        repo.register_alias(222, 111).await.unwrap();
+        assert_eq!(None, repo.get_tombstone(111).await.unwrap());
+
+        let tombstone = repo.add_tombstone(222, Some("not for me")).await.unwrap();
+        assert_eq!((111, Some("not for me")), (tombstone.work_id, tombstone.reason.as_deref()));
+        assert_eq!(Some(&tombstone), repo.get_tombstone(111).await.unwrap().as_ref());
+        assert_eq!(vec![tombstone], repo.list_tombstones().await.unwrap());
+        // Every version of the work is forgotten, and no others.
+        assert!(repo.get_by_work_id(111).await.unwrap().is_empty());
+        assert!(repo.get_by_target_path(DEFAULT_TARGET, "222.html.bz2").await.unwrap().is_none());
+        assert!(repo.get_by_target_path(DEFAULT_TARGET, "333.html.bz2").await.unwrap().is_some());
+
+        assert!(repo.remove_tombstone(111).await.unwrap());
+        assert!(!repo.remove_tombstone(222).await.unwrap());
+        assert!(repo.list_tombstones().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_job_runs() {
+        let repo = make_repository().await;
+        assert_eq!(None, repo.get_job_run("purge").await.unwrap());
+        assert!(!repo.finish_job_run("purge", None).await.unwrap());
+
+        let run = repo.start_job_run("purge").await.unwrap();
+        assert_eq!(Some(&run), repo.get_job_run("purge").await.unwrap().as_ref());
+        assert!(!run.succeeded());
+        assert!(repo.finish_job_run("purge", Some("trash unavailable")).await.unwrap());
+        assert!(!repo.finish_job_run("purge", None).await.unwrap());
+        let failed = repo.get_job_run("purge").await.unwrap().unwrap();
+        assert_eq!((Some("trash unavailable"), false), (failed.error.as_deref(), failed.succeeded()));
+
+        repo.start_job_run("maintenance").await.unwrap();
+        repo.start_job_run("purge").await.unwrap();
+        assert!(repo.finish_job_run("purge", None).await.unwrap());
+        let runs = repo.list_job_runs().await.unwrap();
+        assert_eq!(vec!["maintenance", "purge"], runs.iter().map(|r| r.name.as_str()).collect::<Vec<_>>());
+        assert_eq!((None, true), (runs[1].error.as_deref(), runs[1].succeeded()));
+    }
+
+    #[tokio::test]
+    async fn test_overrides() {
+        let repo = make_repository().await;
+        repo.upsert(&make_test_file("111.html.bz2", "hash_111"), &make_test_version(111, "hash_111")).await.unwrap();
+        repo.upsert(&make_test_file("222.html.bz2", "hash_222"), &make_test_version(222, "hash_222")).await.unwrap();
+        repo.register_alias(222, 111).await.unwrap();
+        assert_eq!(None, repo.get_overrides(111).await.unwrap());
+
+        let overrides = Overrides {
+            title: Some("Corrected Title".to_string()),
+            authors: Some(vec!["orphan_account".parse().unwrap()]),
+            ..Overrides::default()
+        };
+        repo.set_overrides(222, &overrides).await.unwrap();
+        assert_eq!(Some(&overrides), repo.get_overrides(111).await.unwrap().as_ref());
+        for (version, _) in repo.get_by_work_id(111).await.unwrap() {
+            assert_eq!("Corrected Title", version.metadata.title);
+            assert_eq!(Provenance::UserEdited, version.metadata.provenance.get(Field::Authors));
+            assert_eq!(Provenance::Extracted, version.metadata.provenance.get(Field::Fandoms));
+        }
+        let (_, version) = repo.get_by_target_path(DEFAULT_TARGET, "222.html.bz2").await.unwrap().unwrap();
+        assert_eq!("Corrected Title", version.metadata.title);
+
+        assert!(repo.clear_overrides(111).await.unwrap());
+        assert!(!repo.clear_overrides(111).await.unwrap());
+        let (_, version) = repo.get_by_target_path(DEFAULT_TARGET, "111.html.bz2").await.unwrap().unwrap();
+        assert!(version.metadata.provenance.is_empty());
+        assert_ne!("Corrected Title", version.metadata.title);
+    }
+
+    #[tokio::test]
+    async fn test_timeline() {
+        let repo = make_repository().await;
+        assert!(repo.timeline().await.unwrap().months.is_empty());
+        let mut version = make_test_version(12345, "content_abc");
+        version.metadata.fandoms = vec!["Fandom".to_string().into()];
+        repo.upsert(&make_test_file("a.html.bz2", "content_abc"), &version).await.unwrap();
+        repo.upsert(&make_test_file("b.html.bz2", "content_abc"), &version).await.unwrap();
+        let timeline = repo.timeline().await.unwrap();
+        assert_eq!(1, timeline.months.len());
+        assert_eq!((1, 2, 246, 1), {
+            let m = &timeline.months[0];
+            (m.works_added, m.files_added, m.bytes_added, m.versions_added)
+        });
+        assert_eq!(vec![1000], timeline.fandom_words["Fandom"]);
+    }
+
+    #[tokio::test]
+    async fn test_smart_collections() {
+        let repo = make_repository().await;
+        let mut explicit = make_test_version(111, "content_111");
+        explicit.metadata.rating = Some(Rating::Explicit);
+        explicit.metadata.words = 50_000;
+        repo.upsert(&make_test_file("111.html.bz2", "content_111"), &explicit).await.unwrap();
+        repo.upsert(&make_test_file("222.html.bz2", "content_222"), &make_test_version(222, "content_222"))
+            .await
+            .unwrap();
+
+        let long = Filter::min_words(10_000);
+        assert_eq!(1, repo.filter_versions(&long).await.unwrap().len());
+        assert_eq!(2, repo.filter_versions(&long.clone().or(Filter::language("english"))).await.unwrap().len());
+
+        repo.save_collection("long", &long).await.unwrap();
+        repo.save_collection("not explicit", &!Filter::rating(Rating::Explicit)).await.unwrap();
+        let collections = repo.list_collections().await.unwrap();
+        assert_eq!(vec!["long", "not explicit"], collections.iter().map(|c| c.name.as_str()).collect::<Vec<_>>());
+        assert!(collections.iter().all(|c| c.refreshed_at.is_none()));
+
+        // Membership is computed lazily, then only on refresh.
+        assert_eq!(vec![111], repo.list_collection_work_ids("long").await.unwrap());
+        assert_eq!(vec![222], repo.list_collection_work_ids("not explicit").await.unwrap());
+        repo.upsert(&make_test_file("333.html.bz2", "content_333"), &{
+            let mut version = make_test_version(333, "content_333");
+            version.metadata.words = 20_000;
+            version
+        })
+        .await
+        .unwrap();
+        assert_eq!(vec![111], repo.list_collection_work_ids("long").await.unwrap());
+        assert_eq!(2, repo.refresh_collection("long").await.unwrap());
+        assert_eq!(vec![111, 333], repo.list_collection_work_ids("long").await.unwrap());
+        let collections = repo.list_collections().await.unwrap();
+        assert_eq!(2, collections[0].works);
+        assert!(collections[0].refreshed_at.is_some());
+
+        assert!(repo.delete_collection("long").await.unwrap());
+        assert!(!repo.delete_collection("long").await.unwrap());
+        assert!(repo.list_collection_work_ids("long").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_top_works() {
+        let repo = make_repository().await;
+        // (work, version, words, chapters written)
+        for (work_id, hash, words, chapters) in [
+            (1, "content_1", 90_000, 30),
+            (2, "content_2", 20_000, 2),
+            (2, "content_2b", 24_000, 3),
+            (3, "content_3", 5_000, 1),
+        ] {
+            let mut version = make_test_version(work_id, hash);
+            version.metadata.words = words;
+            version.metadata.chapters.written = chapters;
+            repo.upsert(&make_test_file(&format!("{hash}.html"), hash), &version).await.unwrap();
+        }
+        let work_ids = |results: &[VersionResult]| results.iter().map(|(v, _)| v.metadata.work_id).collect::<Vec<_>>();
+
+        let all = Filter::All(vec![]);
+        assert_eq!(vec![1, 2, 2], work_ids(&repo.top_works(&all, Sort::Words, 2).await.unwrap()));
+        let top = repo.top_works(&all, Sort::ChapterWords, 10).await.unwrap();
+        assert_eq!(vec![2, 2, 3, 1], work_ids(&top));
+        // Each work's versions are still best first.
+        assert_eq!("content_2b", top[0].0.hash);
+
+        let long_chapters = Filter::min_words_per_chapter(5_000);
+        assert_eq!(vec![2, 2, 3], work_ids(&repo.top_works(&long_chapters, Sort::Words, 10).await.unwrap()));
+        assert_eq!(
+            vec![1, 2, 3],
+            work_ids(&repo.filter_versions(&Filter::max_words_per_chapter(9_000)).await.unwrap())
+        );
+        assert!(repo.top_works(&all, Sort::Updated, 0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_work_summaries() {
+        let repo = make_repository().await;
+        for (work_id, hash, day, words) in [
+            (1, "content_1", 1, 1_000),
+            (2, "content_2", 1, 2_000),
+            (2, "content_2b", 5, 2_500),
+            (3, "content_3", 1, 3_000),
+        ] {
+            let mut version = make_test_version(work_id, hash);
+            version.metadata.last_modified = Date::from_calendar_date(2024, time::Month::March, day).unwrap();
+            version.metadata.words = words;
+            repo.upsert(&make_test_file(&format!("{hash}.html"), hash), &version).await.unwrap();
+        }
+        repo.set_overrides(
+            3,
+            &Overrides {
+                title: Some("Renamed".to_string()),
+                ..Overrides::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let all = Filter::All(vec![]);
+        let page = repo.list_work_summaries(&all, None, 2).await.unwrap();
+        // One summary per work, of its most recently updated version.
+        assert_eq!(
+            vec![(1, "content_1"), (2, "content_2b")],
+            page.items.iter().map(|s| (s.work_id, s.hash.as_str())).collect::<Vec<_>>()
+        );
+        assert_eq!((2_500, Some(Rating::GeneralAudiences)), (page.items[1].words, page.items[1].rating));
+        assert_eq!(Date::from_calendar_date(2024, time::Month::March, 5).unwrap(), page.items[1].updated);
+        let last = repo.list_work_summaries(&all, page.next.as_ref(), 2).await.unwrap();
+        assert_eq!(("Renamed", None), (last.items[0].title.as_str(), last.next));
+
+        let long = repo.list_work_summaries(&Filter::min_words(2_200), None, 10).await.unwrap();
+        assert_eq!(vec![2, 3], long.items.iter().map(|s| s.work_id).collect::<Vec<_>>());
+        let cursor = repo.list_files_for_target_page(DEFAULT_TARGET, None, 1).await.unwrap().next.unwrap();
+        assert!(repo.list_work_summaries(&all, Some(&cursor), 2).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_detected_language_filters() {
+        let repo = make_repository().await;
+        let detected = |name: &str, confidence| DetectedLanguage {
+            language: Language::new(name),
+            confidence,
+        };
+        for (work_id, detected_language) in [
+            (1, None),
+            (2, Some(detected("English", 99))),
+            (3, Some(detected("Español", 95))),
+            (4, Some(detected("Français", 30))),
+        ] {
+            let hash = format!("content_{work_id}");
+            let version = Version {
+                detected_language,
+                ..make_test_version(work_id, &hash)
+            };
+            repo.upsert(&make_test_file(&format!("{work_id}.html"), &hash), &version).await.unwrap();
+        }
+        let work_ids = |results: Vec<VersionResult>| {
+            let mut ids: Vec<_> = results.into_iter().map(|(v, _)| v.metadata.work_id).collect();
+            ids.sort_unstable();
+            ids
+        };
+        assert_eq!(vec![3], work_ids(repo.filter_versions(&Filter::detected_language("español")).await.unwrap()));
+        assert_eq!(vec![3, 4], work_ids(repo.filter_versions(&Filter::mixed_language(0)).await.unwrap()));
+        assert_eq!(vec![3], work_ids(repo.filter_versions(&Filter::mixed_language(50)).await.unwrap()));
+        let (version, _) = repo.get_by_content_hash("content_3").await.unwrap().unwrap();
+        assert_eq!(Some(detected("Español", 95)), version.detected_language);
+    }
+
+    #[tokio::test]
+    async fn test_pagination() {
+        let repo = make_repository().await;
+        for (work_id, path) in [(1, "a"), (2, "b"), (3, "c"), (4, "d"), (5, "e")] {
+            let hash = format!("content_{work_id}");
+            repo.upsert(&make_test_file(path, &hash), &make_test_version(work_id, &hash)).await.unwrap();
+        }
+        let paths = |page: &Page<FileResult>| page.items.iter().map(|(f, _)| f.path.clone()).collect::<Vec<_>>();
+
+        let first = repo.list_files_for_target_page(DEFAULT_TARGET, None, 2).await.unwrap();
+        assert_eq!(vec![Path::new("a"), Path::new("b")], paths(&first));
+        // Rows inserted before the cursor don't shift the next page along.
+        repo.upsert(&make_test_file("aa", "content_1"), &make_test_version(1, "content_1")).await.unwrap();
+        let cursor: Cursor = first.next.unwrap().to_string().parse().unwrap();
+        let second = repo.list_files_for_target_page(DEFAULT_TARGET, Some(&cursor), 2).await.unwrap();
+        assert_eq!(vec![Path::new("c"), Path::new("d")], paths(&second));
+        let third = repo.list_files_for_target_page(DEFAULT_TARGET, second.next.as_ref(), 2).await.unwrap();
+        assert_eq!(vec![Path::new("e")], paths(&third));
+        assert!(third.next.is_none());
+
+        let mut recent = Vec::new();
+        let mut after = None;
+        loop {
+            let page = repo.list_recent_files_page(after.as_ref(), 4).await.unwrap();
+            recent.extend(paths(&page));
+            match page.next {
+                Some(next) => after = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(6, recent.len());
+        assert_eq!(6, recent.iter().collect::<HashSet<_>>().len());
+
+        repo.upsert(&make_test_file("b2", "content_2b"), &make_test_version(2, "content_2b")).await.unwrap();
+        let filter = Filter::All(vec![]);
+        let page = repo.filter_versions_page(&filter, None, 2).await.unwrap();
+        let works: Vec<_> = page.items.iter().map(|(v, _)| v.metadata.work_id).collect();
+        assert_eq!(vec![1, 2, 2], works);
+        let page = repo.filter_versions_page(&filter, page.next.as_ref(), 2).await.unwrap();
+        let works: Vec<_> = page.items.iter().map(|(v, _)| v.metadata.work_id).collect();
+        assert_eq!(vec![3, 4], works);
+        let last = repo.filter_versions_page(&filter, page.next.as_ref(), 2).await.unwrap();
+        assert_eq!(1, last.items.len());
+        assert!(last.next.is_none());
+
+        // Cursors only continue the kind of listing they came from.
+        assert!(repo.list_files_for_target_page(DEFAULT_TARGET, page.next.as_ref(), 2).await.is_err());
+        assert!(repo.list_files_for_target_page(DEFAULT_TARGET, None, 0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hooks() {
+        use std::sync::{Arc, Mutex};
+        let repo = make_repository().await;
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let subscription = repo.subscribe({
+            let events = events.clone();
+            move |event| {
+                let events = events.clone();
+                async move {
+                    let event = match event {
+                        RepositoryEvent::Upserted { file, .. } => format!("upserted {}", file.path.display()),
+                        RepositoryEvent::PathUpdated { file, old_path, .. } => {
+                            format!("moved {} to {}", old_path.display(), file.path.display())
+                        },
+                        RepositoryEvent::FileDeleted { file, .. } => format!("deleted {}", file.path.display()),
+                        RepositoryEvent::VersionDeleted(version) => format!("deleted {}", version.hash),
+                    };
+                    events.lock().unwrap().push(event);
+                }
+            }
+        });
+        // Clones share hooks, but dry runs change nothing.
+        let clone = repo.clone();
+        clone.upsert(&make_test_file("a.html.bz2", "content_a"), &make_test_version(1, "content_a")).await.unwrap();
+        repo.upsert(&make_test_file("b.html.bz2", "content_b"), &make_test_version(2, "content_b")).await.unwrap();
+        Repository::new(repo.pool.clone(), true).delete_by_work_id(1).await.unwrap();
+        repo.update_target_path(DEFAULT_TARGET, "a.html.bz2", "c.html.bz2").await.unwrap();
+        repo.delete_by_target_path(DEFAULT_TARGET, "b.html.bz2").await.unwrap();
+        repo.delete_orphaned_versions().await.unwrap();
+        repo.delete_by_work_id(1).await.unwrap();
+        assert_eq!(
+            vec![
+                "upserted a.html.bz2",
+                "upserted b.html.bz2",
+                "moved a.html.bz2 to c.html.bz2",
+                "deleted b.html.bz2",
+                "deleted content_b",
+                "deleted c.html.bz2",
+                "deleted content_a",
+            ],
+            *events.lock().unwrap()
+        );
+        assert!(repo.unsubscribe(subscription));
+        assert!(!clone.unsubscribe(subscription));
+        repo.upsert(&make_test_file("a.html.bz2", "content_a"), &make_test_version(1, "content_a")).await.unwrap();
+        assert_eq!(7, events.lock().unwrap().len());
+    }
 }
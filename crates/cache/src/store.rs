@@ -0,0 +1,161 @@
+//! The storage-agnostic interface to the cache.
+//!
+//! [`Repository`] is the cache: a SQLite database, which works well for a
+//! library on one machine but not when several machines share a cache (SQLite
+//! on a network filesystem can't use WAL mode, and corrupts under concurrent
+//! writes). [`CacheStore`] is the subset of its operations that scanning and
+//! organizing a library need, so that the same can be done against a cache
+//! shared over the network (see `PostgresStore`, with the `postgres` feature).
+
+use crate::error::Result;
+use crate::{ExistenceResult, File, Repository, Version};
+use async_trait::async_trait;
+use std::path::Path;
+
+/// The file and version records needed to scan and organize a library,
+/// whichever database holds them.
+///
+/// Implementations must behave like the [`Repository`] methods of the same
+/// names: versions are keyed by content hash, files by target and path, work
+/// IDs are resolved through [aliases](Repository::register_alias), and
+/// dry-run stores validate writes without making them. Metadata
+/// [overrides](Repository::set_overrides) are the exception: they're only
+/// kept (and applied) by the [`Repository`].
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    /// See [`Repository::upsert`].
+    async fn upsert(&self, file: &File, version: &Version) -> Result<()>;
+
+    /// See [`Repository::get_by_target_path`].
+    async fn get_by_target_path(&self, target: &str, path: &Path) -> Result<Option<(File, Version)>>;
+
+    /// See [`Repository::get_by_content_hash`].
+    async fn get_by_content_hash(&self, content_hash: &str) -> Result<Option<(Version, Vec<File>)>>;
+
+    /// See [`Repository::get_by_work_id`].
+    async fn get_by_work_id(&self, work_id: u64) -> Result<Vec<(Version, Vec<File>)>>;
+
+    /// See [`Repository::list_files_for_target`].
+    async fn list_files_for_target(&self, target: &str) -> Result<Vec<(File, Version)>>;
+
+    /// See [`Repository::exists`].
+    async fn exists(&self, target: &str, path: &Path, file_hash: &str) -> Result<ExistenceResult>;
+
+    /// See [`Repository::update_target_path`].
+    async fn update_target_path(&self, target: &str, old_path: &Path, new_path: &Path) -> Result<bool>;
+
+    /// See [`Repository::delete_by_target_path`].
+    async fn delete_by_target_path(&self, target: &str, path: &Path) -> Result<bool>;
+}
+
+#[async_trait]
+impl CacheStore for Repository {
+    async fn upsert(&self, file: &File, version: &Version) -> Result<()> {
+        Repository::upsert(self, file, version).await
+    }
+
+    async fn get_by_target_path(&self, target: &str, path: &Path) -> Result<Option<(File, Version)>> {
+        Repository::get_by_target_path(self, target, path).await
+    }
+
+    async fn get_by_content_hash(&self, content_hash: &str) -> Result<Option<(Version, Vec<File>)>> {
+        Repository::get_by_content_hash(self, content_hash).await
+    }
+
+    async fn get_by_work_id(&self, work_id: u64) -> Result<Vec<(Version, Vec<File>)>> {
+        Repository::get_by_work_id(self, work_id).await
+    }
+
+    async fn list_files_for_target(&self, target: &str) -> Result<Vec<(File, Version)>> {
+        Repository::list_files_for_target(self, target).await
+    }
+
+    async fn exists(&self, target: &str, path: &Path, file_hash: &str) -> Result<ExistenceResult> {
+        Repository::exists(self, target, path, file_hash).await
+    }
+
+    async fn update_target_path(&self, target: &str, old_path: &Path, new_path: &Path) -> Result<bool> {
+        Repository::update_target_path(self, target, old_path, new_path).await
+    }
+
+    async fn delete_by_target_path(&self, target: &str, path: &Path) -> Result<bool> {
+        Repository::delete_by_target_path(self, target, path).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::Database;
+    use rawr_compress::Compression;
+    use rawr_extract::models::{Chapters, Encoding, Language, Metadata};
+    use rawr_storage::file::FileMeta;
+    use time::{Date, Month, UtcDateTime};
+
+    fn make_test_version(work_id: u64, content_hash: &str, chapters: u32) -> Version {
+        Version {
+            hash: content_hash.to_string(),
+            length: 1000,
+            crc32: 12_345_678,
+            encoding: Encoding::Utf8,
+            detected_language: None,
+            metadata: Metadata {
+                work_id,
+                title: "Test Work".to_string(),
+                authors: vec![],
+                fandoms: vec![],
+                rating: None,
+                warnings: vec![],
+                tags: vec![],
+                summary: None,
+                language: Language {
+                    name: "English".to_string(),
+                    iso_code: Some("en".to_string()),
+                },
+                chapters: Chapters { written: chapters, total: None },
+                words: 1000,
+                published: Date::from_calendar_date(2024, Month::January, 1).unwrap(),
+                last_modified: Date::from_calendar_date(2024, Month::January, 1).unwrap(),
+                provenance: Default::default(),
+                work_skin: None,
+                series: vec![],
+            },
+            extracted_at: UtcDateTime::now(),
+        }
+    }
+
+    /// Exercises every operation of an empty `store`, which any [`CacheStore`]
+    /// must pass in the same way.
+    pub(crate) async fn exercise(store: &dyn CacheStore) {
+        let file = |path: &str, hash: &str| {
+            FileMeta::new("local", path, Compression::Gzip, 100, UtcDateTime::now())
+                .with_file_hash(format!("file_{hash}"))
+                .with_content_hash(hash)
+        };
+        store.upsert(&file("a.html.gz", "one"), &make_test_version(1, "one", 1)).await.unwrap();
+        store.upsert(&file("b.html.gz", "two"), &make_test_version(1, "two", 2)).await.unwrap();
+
+        let (found, version) = store.get_by_target_path("local", Path::new("a.html.gz")).await.unwrap().unwrap();
+        assert_eq!("file_one", found.file_hash);
+        assert_eq!(1, version.metadata.chapters.written);
+        let versions = store.get_by_work_id(1).await.unwrap();
+        assert_eq!(vec!["two", "one"], versions.iter().map(|(v, _)| v.hash.as_str()).collect::<Vec<_>>());
+        assert!(matches!(
+            store.exists("local", Path::new("c.html.gz"), "file_two").await.unwrap(),
+            ExistenceResult::LocatedElsewhere(..)
+        ));
+
+        assert!(store.update_target_path("local", Path::new("a.html.gz"), Path::new("c.html.gz")).await.unwrap());
+        assert!(store.delete_by_target_path("local", Path::new("b.html.gz")).await.unwrap());
+        let files = store.list_files_for_target("local").await.unwrap();
+        assert_eq!(vec![Path::new("c.html.gz")], files.iter().map(|(f, _)| f.path.as_path()).collect::<Vec<_>>());
+        let (_, orphaned) = store.get_by_content_hash("two").await.unwrap().unwrap();
+        assert!(orphaned.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_repository_store() {
+        let db = Database::connect_in_memory().await.unwrap();
+        exercise(&Repository::from(&db)).await;
+    }
+}
@@ -0,0 +1,200 @@
+//! How the library grew over time, month by month.
+//!
+//! Built from the cache alone (see [`Repository::timeline()`](crate::Repository::timeline)),
+//! so it describes the library as it is now: files that were deleted, and
+//! versions that were replaced or cleaned up, no longer count towards the
+//! months they were added in.
+
+use crate::error::{ErrorKind, Result};
+use exn::ResultExt;
+use std::collections::{BTreeMap, HashMap};
+use time::{Date, Month, UtcDateTime};
+
+/// Activity within a single calendar month (UTC).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimelineMonth {
+    /// First day of the month.
+    pub month: Date,
+    /// Works whose first file was discovered this month.
+    pub works_added: u64,
+    /// Files discovered this month.
+    pub files_added: u64,
+    /// Total size (as stored) of files discovered this month.
+    pub bytes_added: u64,
+    /// Versions extracted this month.
+    pub versions_added: u64,
+    /// Versions extracted this month of works that already had an earlier
+    /// version: updates (new chapters, edits) rather than new works.
+    pub versions_updated: u64,
+}
+
+/// Month-by-month growth of the library, suitable for charting.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Timeline {
+    /// Every month from the earliest to the latest with any activity, in
+    /// order; months without activity are included (with zero counts) so
+    /// the series can be plotted as-is.
+    pub months: Vec<TimelineMonth>,
+    /// Words available to read in each fandom at the end of each month in
+    /// [`months`](Self::months) (same order and length).
+    ///
+    /// Only the latest version of each work counts, so an update adds the
+    /// difference in words. Crossovers count towards every one of their
+    /// fandoms.
+    pub fandom_words: BTreeMap<String, Vec<u64>>,
+}
+impl Timeline {
+    /// Total number of works in the library at the end of each month.
+    pub fn cumulative_works(&self) -> Vec<u64> {
+        cumulative(self.months.iter().map(|m| m.works_added))
+    }
+
+    /// Total size of the library at the end of each month.
+    pub fn cumulative_bytes(&self) -> Vec<u64> {
+        cumulative(self.months.iter().map(|m| m.bytes_added))
+    }
+}
+
+fn cumulative(values: impl Iterator<Item = u64>) -> Vec<u64> {
+    values
+        .scan(0u64, |total, value| {
+            *total += value;
+            Some(*total)
+        })
+        .collect()
+}
+
+/// A file row: (work ID, file size, discovered at).
+pub(crate) type FileRow = (i64, i64, i64);
+/// A version row, in order of extraction: (work ID, words, fandoms JSON, extracted at).
+pub(crate) type VersionRow = (i64, i64, String, i64);
+
+/// First day of the month of a Unix timestamp.
+fn month_of(timestamp: i64) -> Result<Date> {
+    let date = UtcDateTime::from_unix_timestamp(timestamp).or_raise(|| ErrorKind::InvalidData("timestamp"))?.date();
+    Ok(date.replace_day(1).expect("every month has a first day"))
+}
+
+fn next_month(month: Date) -> Date {
+    match month.month() {
+        Month::December => Date::from_calendar_date(month.year() + 1, Month::January, 1),
+        m => Date::from_calendar_date(month.year(), m.next(), 1),
+    }
+    .expect("first day of a month is a valid date")
+}
+
+pub(crate) fn build(files: Vec<FileRow>, versions: Vec<VersionRow>) -> Result<Timeline> {
+    let mut months: BTreeMap<Date, TimelineMonth> = BTreeMap::new();
+    let mut first_seen: HashMap<i64, i64> = HashMap::new();
+    for (work_id, size, discovered_at) in files {
+        let month = month_of(discovered_at)?;
+        let m = month_entry(&mut months, month);
+        m.files_added += 1;
+        m.bytes_added += u64::try_from(size).unwrap_or(0);
+        first_seen.entry(work_id).and_modify(|t| *t = (*t).min(discovered_at)).or_insert(discovered_at);
+    }
+    for discovered_at in first_seen.into_values() {
+        month_entry(&mut months, month_of(discovered_at)?).works_added += 1;
+    }
+
+    // Words per fandom change whenever a work gains (or replaces) a version.
+    let mut changes: BTreeMap<Date, HashMap<String, i64>> = BTreeMap::new();
+    let mut latest: HashMap<i64, (i64, Vec<String>)> = HashMap::new();
+    for (work_id, words, fandoms, extracted_at) in versions {
+        let month = month_of(extracted_at)?;
+        let fandoms: Vec<String> = serde_json::from_str(&fandoms).or_raise(|| ErrorKind::InvalidData("fandoms"))?;
+        let m = month_entry(&mut months, month);
+        m.versions_added += 1;
+        let change = changes.entry(month).or_default();
+        if let Some((previous, fandoms)) = latest.remove(&work_id) {
+            m.versions_updated += 1;
+            for fandom in fandoms {
+                *change.entry(fandom).or_default() -= previous;
+            }
+        }
+        for fandom in &fandoms {
+            *change.entry(fandom.clone()).or_default() += words;
+        }
+        latest.insert(work_id, (words, fandoms));
+    }
+
+    // Fill in months without any activity.
+    if let (Some(&first), Some(&last)) = (months.keys().next(), months.keys().next_back()) {
+        let mut month = first;
+        while month < last {
+            month_entry(&mut months, month);
+            month = next_month(month);
+        }
+    }
+
+    let mut fandom_words: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    let mut totals: HashMap<String, i64> = HashMap::new();
+    for (i, month) in months.keys().enumerate() {
+        for (fandom, change) in changes.remove(month).unwrap_or_default() {
+            *totals.entry(fandom.clone()).or_default() += change;
+            fandom_words.entry(fandom).or_insert_with(|| vec![0; i]);
+        }
+        for (fandom, series) in &mut fandom_words {
+            series.push(u64::try_from(totals[fandom]).unwrap_or(0));
+        }
+    }
+    Ok(Timeline {
+        months: months.into_values().collect(),
+        fandom_words,
+    })
+}
+
+fn month_entry(months: &mut BTreeMap<Date, TimelineMonth>, month: Date) -> &mut TimelineMonth {
+    months.entry(month).or_insert_with(|| TimelineMonth {
+        month,
+        works_added: 0,
+        files_added: 0,
+        bytes_added: 0,
+        versions_added: 0,
+        versions_updated: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: Month) -> i64 {
+        Date::from_calendar_date(year, month, 15).unwrap().midnight().as_utc().unix_timestamp()
+    }
+
+    #[test]
+    fn test_build() {
+        let files = vec![
+            (1, 100, at(2024, Month::January)),
+            (1, 50, at(2024, Month::March)),
+            (2, 200, at(2024, Month::March)),
+        ];
+        let versions = vec![
+            (1, 1000, r#"["A"]"#.to_string(), at(2024, Month::January)),
+            (2, 500, r#"["A","B"]"#.to_string(), at(2024, Month::March)),
+            (1, 1500, r#"["A"]"#.to_string(), at(2024, Month::March)),
+        ];
+        let timeline = build(files, versions).unwrap();
+        let months: Vec<_> = timeline
+            .months
+            .iter()
+            .map(|m| {
+                (m.month.month(), m.works_added, m.files_added, m.bytes_added, m.versions_added, m.versions_updated)
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (Month::January, 1, 1, 100, 1, 0),
+                (Month::February, 0, 0, 0, 0, 0),
+                (Month::March, 1, 2, 250, 2, 1),
+            ],
+            months
+        );
+        assert_eq!(vec![1000, 1000, 2000], timeline.fandom_words["A"]);
+        assert_eq!(vec![0, 0, 500], timeline.fandom_words["B"]);
+        assert_eq!(vec![1, 1, 2], timeline.cumulative_works());
+        assert_eq!(vec![100, 100, 350], timeline.cumulative_bytes());
+        assert_eq!(Timeline::default(), build(vec![], vec![]).unwrap());
+    }
+}
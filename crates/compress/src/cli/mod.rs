@@ -2,8 +2,12 @@
 //!
 //! Maps the three-state CLI pattern (`--compress`, `--compress=gz`,
 //! or omitted) into a [`Preference`] that can be resolved against
-//! a configured default and an original file's format.
+//! a configured default and an original file's format, and
+//! [transcodes](transcode_dir) whole directories between formats.
 
+mod transcode;
+
+pub use self::transcode::{TranscodeSummary, transcode_dir};
 use crate::Compression;
 use crate::error::Error;
 use std::str::FromStr;
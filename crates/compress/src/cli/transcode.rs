@@ -0,0 +1,212 @@
+//! Converting every compressed file in a directory tree to another format.
+
+use crate::Compression;
+use crate::error::{Error, ErrorKind, Result};
+use exn::ResultExt;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::{self, File, FileTimes};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+/// What [`transcode_dir`] did.
+#[derive(Debug, Default)]
+pub struct TranscodeSummary {
+    /// The format files were converted to.
+    pub target: Compression,
+    /// Files converted to the target format.
+    pub converted: u64,
+    /// Files already in the target format, left as they were.
+    pub skipped: u64,
+    /// Files that couldn't be converted (and were left as they were), with why.
+    pub failed: Vec<(PathBuf, Error)>,
+    /// Total size of the converted files before conversion.
+    pub bytes_before: u64,
+    /// Total size of the converted files after conversion.
+    pub bytes_after: u64,
+}
+impl Display for TranscodeSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "Converted {} file(s) to {} ({} -> {} bytes), {} already {}, {} failed",
+            self.converted,
+            self.target,
+            self.bytes_before,
+            self.bytes_after,
+            self.skipped,
+            self.target,
+            self.failed.len(),
+        )?;
+        for (path, error) in &self.failed {
+            write!(f, "\n  {}: {error}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Convert every compressed file under `root` (recognized by its extension)
+/// to `target`, with up to `workers` threads (one per CPU if zero),
+/// returning a summary of what was done (for the caller to display).
+///
+/// Each file is written alongside the original under the new format's
+/// extension (`work.html.gz` becomes `work.html.bz2`), keeping its
+/// modification time, and the original is only removed once the new file has
+/// been read back in full. Files that can't be converted are left as they
+/// were and reported in the summary, rather than stopping the others; an
+/// existing file is never overwritten. Uncompressed files are ignored, so
+/// transcoding to [`Compression::None`] decompresses everything.
+///
+/// # Errors
+/// Returns [`ErrorKind::Io`] if the directory tree can't be read.
+pub fn transcode_dir(root: impl AsRef<Path>, target: Compression, workers: usize) -> Result<TranscodeSummary> {
+    let mut files = Vec::new();
+    walk(root.as_ref(), &mut files)?;
+    files.sort();
+    let workers = match workers {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let workers = workers.min(files.len()).max(1);
+    let queue = Mutex::new(files.into_iter());
+    let summary = Mutex::new(TranscodeSummary { target, ..TranscodeSummary::default() });
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while let Some(path) = queue.lock().unwrap_or_else(PoisonError::into_inner).next() {
+                    let source = Compression::from_path(&path);
+                    let result = (source != target).then(|| transcode_file(&path, source, target));
+                    let mut summary = summary.lock().unwrap_or_else(PoisonError::into_inner);
+                    match result {
+                        None => summary.skipped += 1,
+                        Some(Ok((before, after))) => {
+                            summary.converted += 1;
+                            summary.bytes_before += before;
+                            summary.bytes_after += after;
+                        },
+                        Some(Err(e)) => {
+                            tracing::warn!(path = %path.display(), error = ?e, "Could not transcode file");
+                            summary.failed.push((path, e));
+                        },
+                    }
+                }
+            });
+        }
+    });
+    let mut summary = summary.into_inner().unwrap_or_else(PoisonError::into_inner);
+    summary.failed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    tracing::info!(
+        converted = summary.converted,
+        skipped = summary.skipped,
+        failed = summary.failed.len(),
+        "Transcoded directory to {target}",
+    );
+    Ok(summary)
+}
+
+/// Collect the compressed files under `dir`, without following symlinks.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir).or_raise(|| ErrorKind::Io)? {
+        let entry = entry.or_raise(|| ErrorKind::Io)?;
+        let file_type = entry.file_type().or_raise(|| ErrorKind::Io)?;
+        if file_type.is_dir() {
+            walk(&entry.path(), files)?;
+        } else if file_type.is_file() && Compression::from_path(entry.path()) != Compression::None {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Convert a single file, returning its size before and after.
+fn transcode_file(path: &Path, source: Compression, target: Compression) -> Result<(u64, u64)> {
+    let mut destination = path.with_extension("").into_os_string();
+    destination.push(target.extension());
+    let destination = PathBuf::from(destination);
+    let input = File::open(path).or_raise(|| ErrorKind::Io)?;
+    let metadata = input.metadata().or_raise(|| ErrorKind::Io)?;
+    let modified = metadata.modified().or_raise(|| ErrorKind::Io)?;
+    let output = File::create_new(&destination).or_raise(|| ErrorKind::Io)?;
+    let written = (|| {
+        let mut reader = source.wrap_reader(BufReader::new(input))?;
+        let mut writer = target.wrap_writer(&output)?;
+        let length = io::copy(&mut reader, &mut writer).or_raise(|| ErrorKind::Io)?;
+        writer.flush().or_raise(|| ErrorKind::Io)?;
+        // Encoders only write the end of their stream when dropped.
+        drop(writer);
+        output.set_times(FileTimes::new().set_modified(modified)).or_raise(|| ErrorKind::Io)?;
+        output.sync_all().or_raise(|| ErrorKind::Io)?;
+        // Check the new file holds everything before the original is removed.
+        let written = File::open(&destination).or_raise(|| ErrorKind::Io)?;
+        let mut reader = target.wrap_reader(BufReader::new(written))?;
+        if io::copy(&mut reader, &mut io::sink()).or_raise(|| ErrorKind::InvalidData)? != length {
+            exn::bail!(ErrorKind::Truncated);
+        }
+        output.metadata().map(|m| m.len()).or_raise(|| ErrorKind::Io)
+    })();
+    match written {
+        Ok(after) => {
+            fs::remove_file(path).or_raise(|| ErrorKind::Io)?;
+            Ok((metadata.len(), after))
+        },
+        Err(e) => {
+            _ = fs::remove_file(&destination);
+            Err(e)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HTML: &[u8] = b"<html><body><p>Once upon a time.</p></body></html>";
+
+    #[test]
+    fn test_transcode_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        fs::write(dir.path().join("a.html.gz"), Compression::Gzip.compress(HTML).unwrap()).unwrap();
+        fs::write(dir.path().join("nested/b.html.gz"), Compression::Gzip.compress(HTML).unwrap()).unwrap();
+        fs::write(dir.path().join("c.html.bz2"), Compression::Bzip2.compress(HTML).unwrap()).unwrap();
+        fs::write(dir.path().join("d.html"), HTML).unwrap();
+        fs::write(dir.path().join("corrupt.html.gz"), b"not gzip at all").unwrap();
+        let modified = File::open(dir.path().join("a.html.gz")).unwrap().metadata().unwrap().modified().unwrap()
+            - std::time::Duration::from_secs(86_400);
+        File::options()
+            .write(true)
+            .open(dir.path().join("a.html.gz"))
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(modified))
+            .unwrap();
+
+        let summary = transcode_dir(dir.path(), Compression::Bzip2, 2).unwrap();
+        assert_eq!((2, 1), (summary.converted, summary.skipped));
+        assert_eq!(
+            vec![dir.path().join("corrupt.html.gz")],
+            summary.failed.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>()
+        );
+        for path in ["a.html.bz2", "nested/b.html.bz2", "c.html.bz2"] {
+            let compressed = fs::read(dir.path().join(path)).unwrap();
+            assert_eq!(HTML, Compression::Bzip2.decompress(&compressed).unwrap());
+        }
+        assert!(!dir.path().join("a.html.gz").exists());
+        assert!(dir.path().join("corrupt.html.gz").exists());
+        assert!(!dir.path().join("corrupt.html.bz2").exists());
+        assert!(dir.path().join("d.html").exists());
+        let transcoded = File::open(dir.path().join("a.html.bz2")).unwrap().metadata().unwrap().modified().unwrap();
+        assert_eq!(modified, transcoded);
+    }
+
+    #[test]
+    fn test_transcode_never_overwrites() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.html.gz"), Compression::Gzip.compress(HTML).unwrap()).unwrap();
+        fs::write(dir.path().join("a.html"), b"edited").unwrap();
+        let summary = transcode_dir(dir.path(), Compression::None, 0).unwrap();
+        assert_eq!(1, summary.failed.len());
+        assert_eq!(b"edited".as_slice(), fs::read(dir.path().join("a.html")).unwrap());
+        assert!(dir.path().join("a.html.gz").exists());
+    }
+}
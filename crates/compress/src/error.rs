@@ -8,6 +8,7 @@
 //!       to resort to anyhow+thiserror just because I don't want to deal with it.
 
 use derive_more::{Display, Error};
+pub use rawr_error::{Code, Domain, ErrorCode};
 
 /// A compression error with automatic location tracking.
 pub type Error = exn::Exn<ErrorKind>;
@@ -25,6 +26,10 @@ pub enum ErrorKind {
     /// Data is corrupt or malformed. Don't retry with the same input. Used for reading/decoding.
     #[display("invalid or corrupted data")]
     InvalidData,
+    /// Data ends part-way through a compressed stream (the file was cut short,
+    /// by an interrupted download or copy). Used for reading/decoding.
+    #[display("data ends unexpectedly")]
+    Truncated,
     /// The requested format is not supported.
     #[display("unsupported format: {_0}")]
     UnsupportedFormat(#[error(not(source))] String),
@@ -43,6 +48,20 @@ impl ErrorKind {
     }
 }
 
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::InvalidData => 422,
+            Self::Truncated => 400,
+            Self::UnsupportedFormat(_) => 415,
+            Self::Io => 500,
+            Self::DisabledFormat(_) => 501,
+            Self::Encoder => 503,
+        };
+        Code::new(Domain::Compress, number)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -62,6 +81,13 @@ mod tests {
         assert!(ErrorKind::Io.is_retryable());
     }
 
+    #[test]
+    fn error_kind_code() {
+        assert_eq!(ErrorKind::InvalidData.code().to_string(), "RAWR-COMPRESS-422");
+        assert!(ErrorKind::UnsupportedFormat("lz4".to_string()).code().is_client_error());
+        assert!(ErrorKind::Io.code().is_system_error());
+    }
+
     #[test]
     fn error_from_result() {
         let result: std::result::Result<(), std::io::Error> =
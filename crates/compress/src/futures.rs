@@ -34,7 +34,8 @@ impl Compression {
     /// Async counterpart of [`Compression::wrap_reader`]. Does not throw an
     /// [`Encoder`](crate::error::ErrorKind::Encoder) error like it's sync
     /// counterpart because the underlying crate defers them until the
-    /// first read attempt.
+    /// first read attempt. Concatenated streams are decompressed in full, as
+    /// they are by the sync counterpart.
     pub fn async_wrap_reader<'a, R: AsyncRead + Unpin + 'a>(&self, reader: R) -> Box<dyn AsyncRead + Unpin + 'a> {
         // `async-compression` requires AsyncBufRead, but AsyncBufRead/AsyncWrite
         // doesn't mirror the sync API of Read/Write. Wrap the incoming AsyncRead
@@ -44,12 +45,28 @@ impl Compression {
             Compression::None => Box::new(reader),
             #[cfg(feature = "brotli")]
             Compression::Brotli => Box::new(BrotliDecoder::new(reader)),
-            Compression::Bzip2 => Box::new(BzDecoder::new(reader)),
-            Compression::Gzip => Box::new(GzipDecoder::new(reader)),
+            Compression::Bzip2 => {
+                let mut decoder = BzDecoder::new(reader);
+                decoder.multiple_members(true);
+                Box::new(decoder)
+            },
+            Compression::Gzip => {
+                let mut decoder = GzipDecoder::new(reader);
+                decoder.multiple_members(true);
+                Box::new(decoder)
+            },
             #[cfg(feature = "xz")]
-            Compression::Xz => Box::new(XzDecoder::new(reader)),
+            Compression::Xz => {
+                let mut decoder = XzDecoder::new(reader);
+                decoder.multiple_members(true);
+                Box::new(decoder)
+            },
             #[cfg(feature = "zstd")]
-            Compression::Zstd => Box::new(ZstdDecoder::new(reader)),
+            Compression::Zstd => {
+                let mut decoder = ZstdDecoder::new(reader);
+                decoder.multiple_members(true);
+                Box::new(decoder)
+            },
         }
     }
 
@@ -163,6 +180,21 @@ mod tests {
         assert_eq!(decompressed, original);
     }
 
+    #[tokio::test]
+    #[rstest]
+    #[case(Compression::Bzip2)]
+    #[case(Compression::Gzip)]
+    #[cfg_attr(feature = "xz", case(Compression::Xz))]
+    #[cfg_attr(feature = "zstd", case(Compression::Zstd))]
+    async fn test_async_concatenated_streams(#[case] format: Compression) {
+        let mut compressed = format.compress(b"First half, ").unwrap();
+        compressed.extend(format.compress(b"second half.").unwrap());
+        let mut reader = format.async_wrap_reader(Cursor::new(compressed));
+        let mut decompressed = Vec::new();
+        reader.read_to_end(&mut decompressed).await.unwrap();
+        assert_eq!(b"First half, second half.", &*decompressed);
+    }
+
     #[rstest]
     #[case(Compression::None)]
     #[case(Compression::Gzip)]
@@ -0,0 +1,141 @@
+//! Hashing while writing, so written data never has to be read back.
+//!
+//! Wraps any [`Write`]r and tees the bytes it accepts into BLAKE3 and CRC32
+//! hashers: the same digests used to identify files and versions elsewhere.
+
+use std::io::{Result, Write};
+
+/// Digests of (and number of bytes in) everything written through a
+/// [`HashingWriter`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Digests {
+    /// Hex-encoded BLAKE3 hash.
+    pub blake3: String,
+    pub crc32: u32,
+    pub bytes: u64,
+}
+
+/// A [`Write`]r that hashes everything written to the inner writer.
+///
+/// Only bytes the inner writer accepts are hashed, so the digests always
+/// match what was actually written (even after partial writes). Wrap the
+/// *innermost* writer to hash compressed output, or the outermost to hash
+/// uncompressed input.
+///
+/// # Example
+///
+/// ```
+/// use rawr_compress::{Compression, HashingWriter};
+/// use std::io::Write;
+///
+/// let mut writer = HashingWriter::new(Vec::new());
+/// let mut compressor = Compression::Gzip.wrap_writer(&mut writer).unwrap();
+/// compressor.write_all(b"<html></html>").unwrap();
+/// drop(compressor);
+/// let (compressed, digests) = writer.finish().unwrap();
+/// assert_eq!(blake3::hash(&compressed).to_string(), digests.blake3);
+/// ```
+pub struct HashingWriter<W> {
+    inner: W,
+    blake3: blake3::Hasher,
+    crc32: crc32fast::Hasher,
+    bytes: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    /// Wrap any writer for hashing.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            blake3: blake3::Hasher::new(),
+            crc32: crc32fast::Hasher::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Number of bytes written so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Access the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Digests of everything written so far, without finishing.
+    pub fn digests(&self) -> Digests {
+        Digests {
+            blake3: self.blake3.finalize().to_string(),
+            crc32: self.crc32.clone().finalize(),
+            bytes: self.bytes,
+        }
+    }
+
+    /// Flush, and return the inner writer along with the digests of
+    /// everything written to it.
+    pub fn finish(mut self) -> Result<(W, Digests)> {
+        self.inner.flush()?;
+        let digests = self.digests();
+        Ok((self.inner, digests))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.blake3.update(&buf[..written]);
+        self.crc32.update(&buf[..written]);
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Compression;
+
+    /// Accepts at most three bytes per write.
+    struct Trickle(Vec<u8>);
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let n = buf.len().min(3);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_partial_writes() {
+        let data = b"<html><body>partial writes</body></html>";
+        let mut writer = HashingWriter::new(Trickle(Vec::new()));
+        assert_eq!(3, writer.write(data).unwrap());
+        writer.write_all(&data[3..]).unwrap();
+        let (inner, digests) = writer.finish().unwrap();
+        assert_eq!(data.as_slice(), inner.0.as_slice());
+        assert_eq!(blake3::hash(data).to_string(), digests.blake3);
+        assert_eq!(crc32fast::hash(data), digests.crc32);
+        assert_eq!(data.len() as u64, digests.bytes);
+    }
+
+    #[test]
+    fn test_compressed_output() {
+        let mut writer = HashingWriter::new(Vec::new());
+        let mut compressor = Compression::Bzip2.wrap_writer(&mut writer).unwrap();
+        compressor.write_all("rawr ".repeat(1000).as_bytes()).unwrap();
+        drop(compressor);
+        let (compressed, digests) = writer.finish().unwrap();
+        assert_eq!(blake3::hash(&compressed).to_string(), digests.blake3);
+        assert_eq!(compressed.len() as u64, digests.bytes);
+        assert_eq!(Some(Compression::Bzip2), Compression::from_magic_bytes(&compressed));
+    }
+}
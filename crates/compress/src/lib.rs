@@ -11,6 +11,8 @@
 //!   [`Compression::wrap_writer`])
 //! - **Peek-decide-stream** workflows via [`PeekableReader`] — decompress just
 //!   enough to inspect content, then stream the rest or discard
+//! - **Hashing while writing** via [`HashingWriter`] — BLAKE3 and CRC32
+//!   digests of written (e.g. compressed) data, without reading it back
 //!
 //! Bzip2 and Gzip are always available. Optional formats (Brotli, XZ, Zstd)
 //! are behind feature flags. Async counterparts require the `async` feature
@@ -25,10 +27,12 @@ mod construct;
 pub mod error;
 #[cfg(feature = "async")]
 mod futures;
+mod hashing;
 mod ops;
 mod peekable;
 mod util;
 
+pub use crate::hashing::{Digests, HashingWriter};
 pub use crate::peekable::PeekableReader;
 
 /// A supported compression format.
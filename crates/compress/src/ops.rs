@@ -4,10 +4,10 @@ use crate::Compression;
 use crate::error::{ErrorKind, Result};
 #[cfg(feature = "brotli")]
 use brotli::{CompressorWriter as BrotliEncoder, Decompressor as BrotliDecoder};
-use bzip2::{Compression as BzCompression, read::BzDecoder, write::BzEncoder};
+use bzip2::{Compression as BzCompression, read::MultiBzDecoder, write::BzEncoder};
 use exn::ResultExt;
-use flate2::{Compression as GzCompression, read::GzDecoder, write::GzEncoder};
-use std::io::{Read, Write};
+use flate2::{Compression as GzCompression, read::MultiGzDecoder, write::GzEncoder};
+use std::io::{self, Read, Write};
 use tracing::instrument;
 #[cfg(feature = "xz")]
 use xz2::{read::XzDecoder, write::XzEncoder};
@@ -30,6 +30,16 @@ const BROTLI_BUFFER_SIZE: usize = 4096;
 #[cfg(feature = "brotli")]
 const BROTLI_LG_WINDOW_SIZE: u32 = 22;
 
+/// Reads everything from a decoder, telling streams that end too soon from
+/// otherwise invalid data.
+fn read_to_end(mut decoder: impl Read, output: &mut Vec<u8>) -> Result<usize> {
+    match decoder.read_to_end(output) {
+        Ok(size) => Ok(size),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(e).or_raise(|| ErrorKind::Truncated),
+        Err(e) => Err(e).or_raise(|| ErrorKind::InvalidData),
+    }
+}
+
 impl Compression {
     /// Compress a byte slice in memory.
     ///
@@ -131,7 +141,11 @@ impl Compression {
     /// Decompress `input` into the provided `output` buffer, returning bytes written.
     ///
     /// Returns [`ErrorKind::InvalidData`] if the input is corrupt or not in the
-    /// expected format.
+    /// expected format, or [`ErrorKind::Truncated`] if it ends too soon.
+    ///
+    /// Input made of several compressed streams one after the other (such as
+    /// the multi-member gzip files some tools write) is decompressed in full,
+    /// rather than stopping silently after the first.
     #[instrument(skip(input, output), fields(
         format = %self,
         input_size = input.len(),
@@ -149,28 +163,13 @@ impl Compression {
                 input.len()
             },
             #[cfg(feature = "brotli")]
-            Compression::Brotli => {
-                let mut decoder = BrotliDecoder::new(input, BROTLI_BUFFER_SIZE);
-                decoder.read_to_end(output).or_raise(|| ErrorKind::InvalidData)?
-            },
-            Compression::Bzip2 => {
-                let mut decoder = BzDecoder::new(input);
-                decoder.read_to_end(output).or_raise(|| ErrorKind::InvalidData)?
-            },
-            Compression::Gzip => {
-                let mut decoder = GzDecoder::new(input);
-                decoder.read_to_end(output).or_raise(|| ErrorKind::InvalidData)?
-            },
+            Compression::Brotli => read_to_end(BrotliDecoder::new(input, BROTLI_BUFFER_SIZE), output)?,
+            Compression::Bzip2 => read_to_end(MultiBzDecoder::new(input), output)?,
+            Compression::Gzip => read_to_end(MultiGzDecoder::new(input), output)?,
             #[cfg(feature = "xz")]
-            Compression::Xz => {
-                let mut decoder = XzDecoder::new(input);
-                decoder.read_to_end(output).or_raise(|| ErrorKind::InvalidData)?
-            },
+            Compression::Xz => read_to_end(XzDecoder::new_multi_decoder(input), output)?,
             #[cfg(feature = "zstd")]
-            Compression::Zstd => {
-                let mut decoder = ZstdDecoder::new(input).or_raise(|| ErrorKind::Encoder)?;
-                decoder.read_to_end(output).or_raise(|| ErrorKind::InvalidData)?
-            },
+            Compression::Zstd => read_to_end(ZstdDecoder::new(input).or_raise(|| ErrorKind::Encoder)?, output)?,
         };
         tracing::Span::current().record("output_size", size);
         Ok(size)
@@ -178,7 +177,8 @@ impl Compression {
 
     /// Wrap a reader with the appropriate decompression layer.
     ///
-    /// Returns a boxed reader that automatically decompresses data.
+    /// Returns a boxed reader that automatically decompresses data, carrying
+    /// on through concatenated streams like [`decompress_into`](Self::decompress_into).
     ///
     /// # Examples
     ///
@@ -199,10 +199,10 @@ impl Compression {
             Compression::None => Box::new(reader),
             #[cfg(feature = "brotli")]
             Compression::Brotli => Box::new(BrotliDecoder::new(reader, BROTLI_BUFFER_SIZE)),
-            Compression::Bzip2 => Box::new(BzDecoder::new(reader)),
-            Compression::Gzip => Box::new(GzDecoder::new(reader)),
+            Compression::Bzip2 => Box::new(MultiBzDecoder::new(reader)),
+            Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
             #[cfg(feature = "xz")]
-            Compression::Xz => Box::new(XzDecoder::new(reader)),
+            Compression::Xz => Box::new(XzDecoder::new_multi_decoder(reader)),
             #[cfg(feature = "zstd")]
             Compression::Zstd => Box::new(ZstdDecoder::new(reader).or_raise(|| ErrorKind::Encoder)?),
         })
@@ -295,6 +295,7 @@ impl Compression {
 #[cfg(test)]
 mod tests {
     use crate::Compression;
+    use crate::error::ErrorKind;
     use rstest::rstest;
     use std::io::{Read, Write};
 
@@ -324,6 +325,36 @@ mod tests {
         assert!(format.decompress(invalid_data).is_err());
     }
 
+    #[rstest]
+    #[case(Compression::Bzip2)]
+    #[case(Compression::Gzip)]
+    #[cfg_attr(feature = "xz", case(Compression::Xz))]
+    #[cfg_attr(feature = "zstd", case(Compression::Zstd))]
+    fn test_truncated_compressed_data(#[case] format: Compression) {
+        let original = b"Hello, world! This is a test of some compression.".repeat(100);
+        let compressed = format.compress(&original).unwrap();
+        let error = format.decompress(&compressed[..compressed.len() / 2]).unwrap_err();
+        assert_eq!(ErrorKind::Truncated, *error);
+    }
+
+    #[rstest]
+    #[case(Compression::Bzip2)]
+    #[case(Compression::Gzip)]
+    #[cfg_attr(feature = "xz", case(Compression::Xz))]
+    #[cfg_attr(feature = "zstd", case(Compression::Zstd))]
+    fn test_concatenated_streams(#[case] format: Compression) {
+        let mut compressed = format.compress(b"<html>First half, ").unwrap();
+        compressed.extend(format.compress(b"second half.</html>").unwrap());
+        assert_eq!(b"<html>First half, second half.</html>", &*format.decompress(&compressed).unwrap());
+        let mut decompressed = Vec::new();
+        format.wrap_reader(&compressed[..]).unwrap().read_to_end(&mut decompressed).unwrap();
+        assert_eq!(b"<html>First half, second half.</html>", &*decompressed);
+
+        // A later stream ending too soon is still noticed.
+        let error = format.decompress(&compressed[..compressed.len() - 4]).unwrap_err();
+        assert_eq!(ErrorKind::Truncated, *error);
+    }
+
     #[rstest]
     #[case(Compression::None)]
     #[case(Compression::Bzip2)]
@@ -48,6 +48,30 @@ impl Compression {
         }
     }
 
+    /// Size (in bytes) of the smallest complete stream in this format: that
+    /// of compressing nothing at all.
+    ///
+    /// Anything shorter can't be valid (so is either empty, or truncated).
+    #[inline]
+    #[must_use]
+    pub fn minimum_size(&self) -> usize {
+        match self {
+            Compression::None => 0,
+            #[cfg(feature = "brotli")]
+            Compression::Brotli => 1,
+            // Header, end-of-stream marker and checksum.
+            Compression::Bzip2 => 14,
+            // Header, an empty deflate block, checksum and size.
+            Compression::Gzip => 20,
+            // Stream header, index and stream footer.
+            #[cfg(feature = "xz")]
+            Compression::Xz => 32,
+            // Magic, frame header and an empty last block.
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => 9,
+        }
+    }
+
     /// Verify that `bytes` start with the expected magic bytes for this format.
     ///
     /// Useful for cross-checking a format detected from a file extension against
@@ -83,4 +107,17 @@ mod tests {
     fn test_extension_default(#[case] format: Compression, #[case] expected: &str) {
         assert_eq!(format.extension(), expected);
     }
+
+    #[rstest]
+    #[case(Compression::None)]
+    #[case(Compression::Bzip2)]
+    #[case(Compression::Gzip)]
+    #[cfg_attr(feature = "brotli", case(Compression::Brotli))]
+    #[cfg_attr(feature = "xz", case(Compression::Xz))]
+    #[cfg_attr(feature = "zstd", case(Compression::Zstd))]
+    fn test_minimum_size(#[case] format: Compression) {
+        let empty = format.compress(b"").unwrap();
+        assert!(empty.len() >= format.minimum_size());
+        assert_eq!(b"", &*format.decompress(&empty).unwrap());
+    }
 }
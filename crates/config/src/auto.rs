@@ -13,10 +13,8 @@
 //! 2. **Export target** — if `library.targets.export` is unset, it mirrors the
 //!    resolved import target (whether user-specified or auto-configured above).
 //! 3. **Cache database** — if `library.cache` is unset, it defaults to the
-//!    platform-specific data directory (via [`directories::ProjectDirs`]).
+//!    cache's [default path](rawr_dirs::cache_path).
 
-use crate::APP_NAME;
-use directories::ProjectDirs;
 use figment::value::{Dict, Map, Tag, Value};
 use figment::{Error, Figment, Metadata, Profile, Provider};
 use std::collections::BTreeSet;
@@ -78,7 +76,7 @@ impl Configurator {
         }
         // Safety: target_names is guaranteed to contain a value.
         let singular_target_name = target_names.pop_last().unwrap();
-        let default_profile = data.entry(Profile::Default).or_insert_with(Dict::new);
+        let default_profile = data.entry(Profile::Default).or_default();
         if let Some((_, library)) = get_or_insert_dict(default_profile, "library")
             && let Some((targets_tag, targets)) = get_or_insert_dict(library, "targets")
         {
@@ -110,7 +108,7 @@ impl Configurator {
                     })
             })
             .cloned();
-        let default_profile = data.entry(Profile::Default).or_insert_with(Dict::new);
+        let default_profile = data.entry(Profile::Default).or_default();
         if let Some(import_value) = import_value
             && let Some((_, library)) = get_or_insert_dict(default_profile, "library")
             && let Some((targets_tag, targets)) = get_or_insert_dict(library, "targets")
@@ -120,9 +118,10 @@ impl Configurator {
         }
     }
 
-    /// Sets `library.cache` to the platform-specific data directory
-    /// (e.g. `~/.local/share/rawr/cache.db` on Linux) when no cache path is
-    /// configured. Uses [`directories::ProjectDirs`] for platform resolution.
+    /// Sets `library.cache` to the cache's [default path](rawr_dirs::cache_path)
+    /// (e.g. `~/.local/share/rawr/cache.db` on Linux, unless overridden by the
+    /// [`RAWR_CACHE`](rawr_dirs::CACHE_PATH_ENV_VAR) environment variable)
+    /// when no cache path is configured.
     fn autoconfigure_cache_database(data: &mut ProviderData) {
         // Do any of the profiles contain a cache database path?
         let is_database_specified = data.values_mut().any(|dict| {
@@ -131,11 +130,11 @@ impl Configurator {
         if is_database_specified {
             return;
         }
-        let default_profile = data.entry(Profile::Default).or_insert_with(Dict::new);
+        let default_profile = data.entry(Profile::Default).or_default();
         if let Some((library_tag, library)) = get_or_insert_dict(default_profile, "library")
-            && let Some(dirs) = ProjectDirs::from("", "", APP_NAME)
+            && let Some(database) = rawr_dirs::cache_path()
         {
-            let database = dirs.data_dir().join("cache.db").to_string_lossy().to_string();
+            let database = database.to_string_lossy().to_string();
             tracing::debug!(setting = "library.cache", value = &database, "Auto-configuring");
             library.insert("cache".into(), Value::String(library_tag, database));
         }
@@ -10,6 +10,7 @@
 //       to resort to anyhow+thiserror just because I don't want to deal with it.
 
 use derive_more::{Display, Error};
+pub use rawr_error::{Code, Domain, ErrorCode};
 use std::path::PathBuf;
 
 /// A configuration error with automatic location tracking via [`exn::Exn`].
@@ -51,6 +52,19 @@ impl ErrorKind {
     }
 }
 
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::Figment(_) => 400,
+            Self::NotFound { .. } => 404,
+            Self::NoConfigDiscovered => 412,
+            Self::Validation { .. } => 422,
+            Self::Io(_) => 500,
+        };
+        Code::new(Domain::Config, number)
+    }
+}
+
 /// A single validation problem found after parsing, tied to a dotted config
 /// path (e.g. `"library.targets.import"`).
 #[derive(Debug)]
@@ -35,6 +35,4 @@ mod validation;
 pub use crate::loader::Loader;
 pub use crate::models::Config;
 pub use crate::validation::Validator;
-
-/// Application name used for config-file discovery and platform directory resolution.
-pub const APP_NAME: &str = "rawr";
+pub use rawr_dirs::APP_NAME;
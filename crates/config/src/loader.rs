@@ -11,7 +11,6 @@ use crate::auto::Configurator as Autoconfigurator;
 use crate::error::{ConstraintViolation, ErrorKind, Result};
 use crate::models::Config;
 use crate::validation::Validator;
-use directories::ProjectDirs;
 use figment::Figment;
 use figment::providers::{self, Format};
 use std::path::{Path, PathBuf};
@@ -82,7 +81,7 @@ impl Loader for Config {
                 // - Linux: `$XDG_CONFIG_HOME/rawr/` or `~/.config/rawr/`
                 // - macOS: `~/Library/Application Support/rawr/`
                 // - Windows: `C:\Users\<User>\AppData\Roaming\rawr\`
-                ProjectDirs::from("", "", APP_NAME)
+                rawr_dirs::project_dirs()
                     .and_then(|d| search_in_dir(d.config_dir(), "config"))
                     .map(|p| (p, ConfigDiscoverySource::UserConfig))
             });
@@ -1,25 +1,55 @@
 use figment::value::magic::RelativePathBuf;
 use rawr_compress::Compression;
 use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// The pieces of the default layout, as macros (rather than consts) so that
+// the default templates and sub-templates can be `concat!`ed from them.
+macro_rules! fandom {
+    () => {
+        "{{ fandom|truncate: 255|slug }}"
+    };
+}
+macro_rules! series {
+    () => {
+        "{% if series %}{{ series.id }}-{{ series.name|truncate: 230|slug }}/{% endif %}"
+    };
+}
+macro_rules! title {
+    () => {
+        "{{ title|truncate: 220|slug }}"
+    };
+}
+
+/// Default named sub-templates, for templates to include with
+/// `{% include "name" %}`: the parts of the layout [`DEFAULT_TEMPLATE_IMPORT`]
+/// and [`DEFAULT_TEMPLATE_EXPORT`] are built from. The defaults themselves
+/// don't include them, so that they can be used without registering any.
+///
+/// - `fandom`: the directory named after the work's fandom.
+/// - `series`: the directory named after the work's series, if it's in one
+///   (with a trailing `/`, or nothing).
+/// - `title`: the work's title, as it appears in file names.
+/// - `stem`: the work ID and title, which exported file names consist of.
+///   Imported file names have the `{{ hash }}` between the two instead.
+pub const DEFAULT_TEMPLATE_PARTIALS: &[(&str, &str)] = &[
+    ("fandom", fandom!()),
+    ("series", series!()),
+    ("title", title!()),
+    ("stem", concat!("{{ work }}-", title!())),
+];
 
 /// Default Tera template for organizing imported works into the library.
 ///
 /// Includes `{{ hash }}` to allow multiple versions of the same work to
 /// coexist. Available variables: `fandom`, `series` (optional, with `.id`
 /// and `.name`), `work`, `hash`, `title`.
-pub const DEFAULT_TEMPLATE_IMPORT: &str = r#"
-    {{ fandom|truncate: 255|slug }}/
-    {% if series %}{{ series.id }}-{{ series.name|truncate: 230|slug }}/{% endif %}
-    {{ work }}-{{ hash }}-{{ title|truncate: 220|slug }}
-"#;
+pub const DEFAULT_TEMPLATE_IMPORT: &str = concat!(fandom!(), "/", series!(), "{{ work }}-{{ hash }}-", title!());
 
 /// Default Tera template for exported PDFs.
 ///
 /// Omits `{{ hash }}` since exports overwrite previous versions.
-pub const DEFAULT_TEMPLATE_EXPORT: &str = r#"
-    {{ fandom|truncate: 255|slug }}/
-    {{ work }}-{{ title|truncate: 220|slug }}
-"#;
+pub const DEFAULT_TEMPLATE_EXPORT: &str = concat!(fandom!(), "/{{ work }}-", title!());
 
 /// Core library settings: where to store data, how to compress, and how
 /// to organize files within storage targets.
@@ -44,6 +74,13 @@ pub struct LibraryConfig {
     /// `builtin:` prefixed names for bundled stylesheets.
     #[serde(default)]
     pub styles: Vec<String>,
+    /// Glob patterns for files that scanning (and every other operation on
+    /// a target) should ignore, such as partially-downloaded `*.part` files.
+    /// Patterns match the path relative to the target root, or any single
+    /// component of it. Omit to use the storage crate's defaults (common
+    /// temporary-file suffixes and dotfiles); an empty list ignores nothing.
+    #[serde(default)]
+    pub ignore: Option<Vec<String>>,
 }
 
 /// Maps logical operations (import, export, trash) to named
@@ -73,12 +110,52 @@ pub struct PathTemplates {
     /// [`DEFAULT_TEMPLATE_EXPORT`].
     #[serde(default = "default_template_export")]
     pub export: String,
+    /// Named sub-templates, which either template can include with
+    /// `{% include "name" %}`. Merged over [`DEFAULT_TEMPLATE_PARTIALS`], so
+    /// redefining one of those changes every template that includes it.
+    #[serde(default = "default_template_partials", deserialize_with = "deserialize_partials")]
+    pub partials: BTreeMap<String, String>,
+}
+impl PathTemplates {
+    /// Names of the sub-templates `template` includes, in order.
+    ///
+    /// Only `{% include "name" %}` blocks are looked at (with or without
+    /// whitespace control); expressions and comments are skipped.
+    pub fn includes(template: &str) -> impl Iterator<Item = &str> {
+        let mut rest = template;
+        std::iter::from_fn(move || {
+            loop {
+                let start = rest.find('{')?;
+                let Some((open, close)) = [("{%", "%}"), ("{{", "}}"), ("{#", "#}")]
+                    .into_iter()
+                    .find(|(open, _)| rest[start..].starts_with(open))
+                else {
+                    rest = &rest[start + 1..];
+                    continue;
+                };
+                let body = &rest[start + open.len()..];
+                let end = body.find(close).unwrap_or(body.len());
+                rest = body.get(end + close.len()..).unwrap_or_default();
+                if open != "{%" {
+                    continue;
+                }
+                let block = body[..end].strip_prefix('-').unwrap_or(&body[..end]);
+                let block = block.strip_suffix('-').unwrap_or(block).trim();
+                if let Some(name) = block.strip_prefix("include").filter(|name| name.starts_with(char::is_whitespace))
+                    && let Some(name) = name.trim_start().strip_prefix('"').and_then(|name| name.split('"').next())
+                {
+                    return Some(name);
+                }
+            }
+        })
+    }
 }
 impl Default for PathTemplates {
     fn default() -> Self {
         Self {
             import: DEFAULT_TEMPLATE_IMPORT.to_string(),
             export: DEFAULT_TEMPLATE_EXPORT.to_string(),
+            partials: default_template_partials(),
         }
     }
 }
@@ -91,6 +168,15 @@ where
     s.parse::<Compression>().map_err(serde::de::Error::custom)
 }
 
+fn deserialize_partials<'de, D>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mut partials = default_template_partials();
+    partials.extend(BTreeMap::<String, String>::deserialize(deserializer)?);
+    Ok(partials)
+}
+
 fn default_compression() -> Compression {
     Compression::default()
 }
@@ -102,3 +188,7 @@ fn default_template_import() -> String {
 fn default_template_export() -> String {
     DEFAULT_TEMPLATE_EXPORT.to_string()
 }
+
+fn default_template_partials() -> BTreeMap<String, String> {
+    DEFAULT_TEMPLATE_PARTIALS.iter().map(|(name, source)| (name.to_string(), source.to_string())).collect()
+}
@@ -10,7 +10,10 @@ mod library;
 mod target;
 
 pub use self::fandom::FandomConfig;
-pub use self::library::{LibraryConfig, LibraryTargets, PathTemplates};
+pub use self::library::{
+    DEFAULT_TEMPLATE_EXPORT, DEFAULT_TEMPLATE_IMPORT, DEFAULT_TEMPLATE_PARTIALS, LibraryConfig, LibraryTargets,
+    PathTemplates,
+};
 pub use self::target::TargetConfig;
 use serde::Deserialize;
 use std::collections::HashMap;
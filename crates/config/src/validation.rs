@@ -12,7 +12,7 @@
 //! warnings are returned alongside the valid [`Config`].
 
 use crate::error::ConstraintViolation;
-use crate::models::{Config, TargetConfig};
+use crate::models::{Config, PathTemplates, TargetConfig};
 use figment::value::magic::RelativePathBuf;
 
 /// Validates a deserialized configuration, producing any
@@ -32,6 +32,8 @@ impl Validator for Config {
         validate_library_targets(self, &mut errors);
         validate_targets(self, &mut errors);
         validate_database(self, &mut errors);
+        validate_ignore_patterns(self, &mut errors);
+        validate_template_includes(self, &mut errors);
         check_duplicate_fandom_renames(self, &mut errors);
         check_prefs_not_in_renames(self, &mut errors);
         errors
@@ -142,6 +144,32 @@ fn validate_database(config: &Config, errors: &mut Vec<ConstraintViolation>) {
     }
 }
 
+fn validate_ignore_patterns(config: &Config, errors: &mut Vec<ConstraintViolation>) {
+    for (i, pattern) in config.library.ignore.iter().flatten().enumerate() {
+        if let Err(e) = globset::Glob::new(pattern) {
+            errors.push(ConstraintViolation::error(format!("library.ignore[{i}]"), e.to_string()));
+        }
+    }
+}
+
+fn validate_template_includes(config: &Config, errors: &mut Vec<ConstraintViolation>) {
+    let templates = &config.library.path_templates;
+    let sources = [
+        ("library.path_templates.import".to_string(), &templates.import),
+        ("library.path_templates.export".to_string(), &templates.export),
+    ];
+    let partials =
+        templates.partials.iter().map(|(name, source)| (format!("library.path_templates.partials.{name}"), source));
+    for (key, source) in sources.into_iter().chain(partials) {
+        for name in PathTemplates::includes(source) {
+            if !templates.partials.contains_key(name) {
+                errors
+                    .push(ConstraintViolation::error(key.clone(), format!("includes undefined sub-template '{name}'")));
+            }
+        }
+    }
+}
+
 fn check_duplicate_fandom_renames(config: &Config, warnings: &mut Vec<ConstraintViolation>) {
     let mut seen_aliases: std::collections::HashMap<&str, &str> = std::collections::HashMap::new();
     for (display_name, aliases) in &config.fandoms.renames {
@@ -191,8 +219,10 @@ mod tests {
                 path_templates: PathTemplates {
                     import: "{{ fandom }}/{{ title }}.html".to_string(),
                     export: "".to_string(),
+                    partials: Default::default(),
                 },
                 styles: vec![],
+                ignore: None,
             },
             targets: HashMap::from([(
                 "local".to_string(),
@@ -223,6 +253,33 @@ mod tests {
         assert!(errors[0].message.contains("nonexistent"));
     }
 
+    #[test]
+    fn invalid_ignore_pattern() {
+        let mut config = minimal_config();
+        config.library.ignore = Some(vec!["*.part".to_string(), "[unclosed".to_string()]);
+        let errors = config.validate();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].severity, ViolationSeverity::Error);
+        assert_eq!(errors[0].path, "library.ignore[1]");
+    }
+
+    #[test]
+    fn undefined_template_include() {
+        let mut config = minimal_config();
+        config.library.path_templates = PathTemplates::default();
+        assert!(config.validate().is_empty());
+
+        config.library.path_templates.export = r#"{% include "fandom" %}/{%- include "missing" -%}"#.to_string();
+        let errors = config.validate();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].severity, ViolationSeverity::Error);
+        assert_eq!(errors[0].path, "library.path_templates.export");
+        assert!(errors[0].message.contains("'missing'"));
+
+        let template = r#"{%-include "a"-%}{#{% include "b" %}#}{{ "{% include \"c\" %}" }}{% include "d" with x %}{% if included %}"#;
+        assert_eq!(vec!["a", "d"], PathTemplates::includes(template).collect::<Vec<_>>());
+    }
+
     #[test]
     fn test_no_create_warning() {
         let mut config = minimal_config();
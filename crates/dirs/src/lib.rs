@@ -0,0 +1,60 @@
+//! Where rawr keeps its files unless told otherwise.
+//!
+//! Shared by the crates that need to agree on those locations (such as the
+//! config auto-configuring the cache database the cache itself defaults to)
+//! without depending on each other.
+
+use directories::ProjectDirs;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// Application name used for config-file discovery and platform directory resolution.
+pub const APP_NAME: &str = "rawr";
+/// Environment variable overriding the [default path](cache_path) of the
+/// cache database.
+pub const CACHE_PATH_ENV_VAR: &str = "RAWR_CACHE";
+const CACHE_FILENAME: &str = "cache.db";
+
+/// The application's platform directories, or `None` if there's no home
+/// directory.
+pub fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", APP_NAME)
+}
+
+/// Where the cache database is kept unless configured otherwise, without
+/// creating anything.
+///
+/// That's the path in the [`RAWR_CACHE`](CACHE_PATH_ENV_VAR) environment
+/// variable (if set, and not empty), or else `cache.db` in the platform's
+/// data directory:
+/// - Linux: `$XDG_DATA_HOME/rawr` (`~/.local/share/rawr` by default).
+/// - macOS: `~/Library/Application Support/rawr`.
+/// - Windows: `%APPDATA%\rawr\data`.
+///
+/// Returns `None` if there's no override and no home directory.
+pub fn cache_path() -> Option<PathBuf> {
+    cache_path_from(std::env::var_os(CACHE_PATH_ENV_VAR))
+}
+
+/// [`cache_path()`], given the value of the environment variable.
+fn cache_path_from(var: Option<OsString>) -> Option<PathBuf> {
+    match var {
+        Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+        _ => project_dirs().map(|dirs| dirs.data_dir().join(CACHE_FILENAME)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_path() {
+        let path = PathBuf::from("/somewhere/else.db");
+        assert_eq!(Some(path.clone()), cache_path_from(Some(path.into_os_string())));
+        let default = project_dirs().map(|dirs| dirs.data_dir().join(CACHE_FILENAME));
+        assert_eq!(default, cache_path_from(Some(OsString::new())));
+        assert_eq!(default, cache_path_from(None));
+        assert!(default.is_none_or(|path| path.ends_with("rawr/cache.db")));
+    }
+}
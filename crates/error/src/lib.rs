@@ -0,0 +1,193 @@
+//! Stable, machine-readable error codes.
+//!
+//! Every crate in the workspace has its own `ErrorKind`, describing what the
+//! caller should *do* in terms of that crate. Their categories overlap (most
+//! have some form of "not found" or "I/O error"), and their display messages
+//! aren't stable. This crate gives each kind a [`Code`] such as
+//! `RAWR-STORAGE-404` via the [`ErrorCode`] trait, so that embedding
+//! applications can branch on errors from any crate and users have something
+//! to search for.
+//!
+//! # Numbering
+//! Codes are loosely modelled on HTTP status codes:
+//! - `4xx`: the input is at fault (a missing file, invalid data, a conflict);
+//!   retrying with the same input will fail the same way.
+//! - `5xx`: something in the environment failed (I/O, network, a dependency,
+//!   an external process); see each kind's `is_retryable()`.
+//!
+//! Within a [`Domain`] every code identifies exactly one error kind, and once
+//! assigned a code is never reused for a different kind.
+//!
+//! # Example
+//!
+//! ```
+//! use rawr_error::{Code, Domain, ErrorCode};
+//!
+//! struct NotFound;
+//! impl ErrorCode for NotFound {
+//!     fn code(&self) -> Code {
+//!         Code::new(Domain::Storage, 404)
+//!     }
+//! }
+//!
+//! assert_eq!("RAWR-STORAGE-404", NotFound.code().to_string());
+//! assert!(NotFound.code().is_client_error());
+//! ```
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+/// The crate (or, for the library, the operation) an error code belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Domain {
+    Cache,
+    Compress,
+    Config,
+    Extract,
+    Library,
+    Render,
+    Storage,
+    /// Library scans.
+    Scan,
+    /// Library organize operations.
+    Organize,
+    /// Library imports.
+    Import,
+    /// Library verification.
+    Verify,
+    /// Library export bundles.
+    Bundle,
+    /// The HTTP interface for browsing the cache.
+    Serve,
+}
+impl Domain {
+    const ALL: [Self; 13] = [
+        Self::Cache,
+        Self::Compress,
+        Self::Config,
+        Self::Extract,
+        Self::Library,
+        Self::Render,
+        Self::Storage,
+        Self::Scan,
+        Self::Organize,
+        Self::Import,
+        Self::Verify,
+        Self::Bundle,
+        Self::Serve,
+    ];
+
+    /// The domain as it appears in codes, e.g. `STORAGE`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Cache => "CACHE",
+            Self::Compress => "COMPRESS",
+            Self::Config => "CONFIG",
+            Self::Extract => "EXTRACT",
+            Self::Library => "LIBRARY",
+            Self::Render => "RENDER",
+            Self::Storage => "STORAGE",
+            Self::Scan => "SCAN",
+            Self::Organize => "ORGANIZE",
+            Self::Import => "IMPORT",
+            Self::Verify => "VERIFY",
+            Self::Bundle => "BUNDLE",
+            Self::Serve => "SERVE",
+        }
+    }
+}
+impl Display for Domain {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A machine-readable error code, displayed as `RAWR-{DOMAIN}-{NUMBER}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Code {
+    domain: Domain,
+    number: u16,
+}
+impl Code {
+    pub const fn new(domain: Domain, number: u16) -> Self {
+        Self { domain, number }
+    }
+
+    pub fn domain(&self) -> Domain {
+        self.domain
+    }
+
+    pub fn number(&self) -> u16 {
+        self.number
+    }
+
+    /// Whether the input is at fault (a `4xx` code).
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.number)
+    }
+
+    /// Whether the environment is at fault (a `5xx` code).
+    pub fn is_system_error(&self) -> bool {
+        (500..600).contains(&self.number)
+    }
+}
+impl Display for Code {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "RAWR-{}-{:03}", self.domain, self.number)
+    }
+}
+impl FromStr for Code {
+    type Err = ParseCodeError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, '-');
+        let (Some(prefix), Some(domain), Some(number)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(ParseCodeError);
+        };
+        if !prefix.eq_ignore_ascii_case("RAWR") {
+            return Err(ParseCodeError);
+        }
+        let domain = Domain::ALL.into_iter().find(|d| d.as_str().eq_ignore_ascii_case(domain)).ok_or(ParseCodeError)?;
+        let number = number.parse().map_err(|_| ParseCodeError)?;
+        Ok(Self::new(domain, number))
+    }
+}
+
+/// Returned when parsing a string that isn't a valid [`Code`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCodeError;
+impl Display for ParseCodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("invalid error code, expected RAWR-{DOMAIN}-{NUMBER}")
+    }
+}
+impl std::error::Error for ParseCodeError {}
+
+/// Implemented by each crate's `ErrorKind` to expose a stable [`Code`].
+///
+/// `Exn<ErrorKind>` dereferences to the kind, so `error.code()` works
+/// directly on errors.
+pub trait ErrorCode {
+    fn code(&self) -> Code;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_and_parse() {
+        let code = Code::new(Domain::Storage, 404);
+        assert_eq!("RAWR-STORAGE-404", code.to_string());
+        assert_eq!(Ok(code), "RAWR-STORAGE-404".parse());
+        assert_eq!(Ok(code), "rawr-storage-404".parse());
+        assert_eq!("RAWR-CACHE-042", Code::new(Domain::Cache, 42).to_string());
+        assert_eq!(Err(ParseCodeError), "RAWR-STORAGE".parse::<Code>());
+        assert_eq!(Err(ParseCodeError), "RAWR-NOPE-404".parse::<Code>());
+        assert_eq!(Err(ParseCodeError), "RAWR-STORAGE-abc".parse::<Code>());
+        for domain in Domain::ALL {
+            let code = Code::new(domain, 500);
+            assert_eq!(Ok(code), code.to_string().parse());
+        }
+    }
+}
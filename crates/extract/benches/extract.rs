@@ -0,0 +1,94 @@
+//! Benchmarks of extraction, hashing and truncation, over generated documents
+//! sized like real downloads.
+//!
+//! To catch a regression, save a baseline before changing a selector or
+//! regex, then compare against it:
+//!
+//! ```sh
+//! cargo bench -p rawr-extract --features testing -- --save-baseline before
+//! # ...make the change...
+//! cargo bench -p rawr-extract --features testing -- --baseline before
+//! ```
+//!
+//! Criterion reports each benchmark that got significantly slower. The
+//! `fields` benchmark also prints where extraction spends its time, field by
+//! field (see [`rawr_extract::profile`]).
+
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use rawr_extract::profile::Stats;
+use rawr_extract::testing::{Generator, render};
+use rawr_extract::{
+    ESTIMATED_HEADER_SIZE_BYTES, Extractor, extract, extract_repairing, normalized_hash, safe_html_truncate,
+};
+use std::hint::black_box;
+
+/// Roughly the size of a download of a (very) long work.
+const LARGE_BYTES: usize = 4 * 1024 * 1024;
+/// Documents in the corpus of typical works.
+const CORPUS_SIZE: usize = 100;
+
+/// A document of at least [`LARGE_BYTES`], most of it chapter text.
+fn large_document() -> String {
+    let metadata = Generator::new(0xB16).metadata();
+    let paragraph = "<p>The tide came in under a silver moon, and the harbour lights went out one by one.</p>\n";
+    let body = paragraph.repeat(LARGE_BYTES / paragraph.len() + 1);
+    render(&metadata).replace("</div>\n</body>", &format!("{body}</div>\n</body>"))
+}
+
+fn corpus() -> Vec<String> {
+    Generator::new(0xBE7C4).take(CORPUS_SIZE).map(|g| g.html).collect()
+}
+
+fn bench_extract(c: &mut Criterion) {
+    let large = large_document();
+    let corpus = corpus();
+    let mut group = c.benchmark_group("extract");
+    group.throughput(Throughput::Bytes(large.len() as u64));
+    group.bench_function("large", |b| b.iter(|| extract(black_box(&large)).unwrap()));
+    group.bench_function("large_repairing", |b| b.iter(|| extract_repairing(black_box(&large)).unwrap()));
+    group.bench_function("large_untruncated", |b| {
+        b.iter(|| Extractor::from_html(black_box(&large)).metadata().unwrap())
+    });
+    group.throughput(Throughput::Elements(corpus.len() as u64));
+    group.bench_function("corpus", |b| {
+        b.iter(|| corpus.iter().map(|html| extract(black_box(html)).unwrap().metadata.words).sum::<u64>())
+    });
+    group.finish();
+}
+
+fn bench_fields(c: &mut Criterion) {
+    let corpus = corpus();
+    let mut stats = Stats::default();
+    c.bench_function("fields", |b| {
+        b.iter_batched(
+            || corpus.iter().map(Extractor::from_long_html).collect::<Vec<_>>(),
+            |extractors| {
+                for extractor in extractors {
+                    extractor.metadata_profiled(&mut stats).unwrap();
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    println!("\n{stats}\n");
+}
+
+fn bench_hash(c: &mut Criterion) {
+    let large = large_document();
+    let mut group = c.benchmark_group("hash");
+    group.throughput(Throughput::Bytes(large.len() as u64));
+    group.bench_function("blake3", |b| b.iter(|| blake3::hash(black_box(large.as_bytes()))));
+    group.bench_function("crc32", |b| b.iter(|| crc32fast::hash(black_box(large.as_bytes()))));
+    group.bench_function("normalized", |b| b.iter(|| normalized_hash(black_box(&large))));
+    group.finish();
+}
+
+fn bench_truncate(c: &mut Criterion) {
+    let large = large_document();
+    c.bench_function("truncate", |b| {
+        b.iter(|| safe_html_truncate(black_box(large.as_bytes()), ESTIMATED_HEADER_SIZE_BYTES).len())
+    });
+}
+
+criterion_group!(benches, bench_extract, bench_fields, bench_hash, bench_truncate);
+criterion_main!(benches);
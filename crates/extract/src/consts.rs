@@ -18,6 +18,7 @@ macro_rules! regex {
     };
 }
 
+selector!(PREFACE_SELECTOR, "div#preface");
 // Selector for the work URL in the preface. This is used to determine if the document is valid.
 selector!(WORK_URL_SELECTOR, "div#preface p.message a[href]");
 regex!(WORK_URL_REGEX, format!(r"{}/works/(\d+){}", SCHEME_HOST, SAFE_END).as_str());
@@ -32,6 +33,11 @@ selector!(TAGS_DL_SELECTOR, "#preface dl.tags");
 selector!(DT_SELECTOR, "dt");
 selector!(DD_SELECTOR, "dd");
 selector!(SUMMARY_SELECTOR, "#preface .meta blockquote.userstuff");
+// Stylesheets, among which the work skin (every rule of which is scoped to `#workskin`).
+selector!(STYLE_SELECTOR, "style");
+// The work's body: every chapter (with its headings and notes).
+selector!(CHAPTERS_SELECTOR, "#chapters");
+selector!(CHAPTER_HEADING_SELECTOR, "h2.heading");
 regex!(CHAPTERS_REGEX, r"Chapters:\s*(\d{1,3}(?:,?\d{3})*)/(\d{1,3}(?:,?\d{3})*|\?)");
 regex!(WORDS_REGEX, r"Words:\s*(\d{1,3}(?:,?\d{3})*)");
 regex!(DATE_REGEX, r"(Updated|Completed|Published):\s*(\d{4})-(\d{1,2})-(\d{1,2})");
@@ -0,0 +1,284 @@
+//! Differences between the prose of two versions of a work.
+//!
+//! [`diff_bodies`] compares two downloads of the same work chapter by chapter
+//! (the first chapter of one with the first of the other, and so on), and
+//! the paragraphs of each chapter as [plain text](crate::Extractor::body_text):
+//! changes to markup alone aren't changes. Each run of paragraphs removed
+//! and/or added is a [`Hunk`].
+
+use crate::consts::{CHAPTER_HEADING_SELECTOR, CHAPTERS_SELECTOR};
+use crate::extract::body::{Format, convert};
+use scraper::{ElementRef, Html};
+
+/// How a chapter differs between two versions of a work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ChapterStatus {
+    /// The chapter is only in the new version.
+    Added,
+    /// The chapter is only in the old version.
+    Removed,
+    /// The chapter is in both versions, but its title or text changed.
+    Changed,
+}
+
+/// The differences in one chapter between two versions of a work.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChapterDiff {
+    /// Position of the chapter in the work, starting at 1.
+    pub number: usize,
+    pub status: ChapterStatus,
+    /// The chapter's heading in the old version (`None` if it wasn't in the
+    /// old version, or the work has a single untitled chapter).
+    pub old_title: Option<String>,
+    /// The chapter's heading in the new version.
+    pub new_title: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+impl ChapterDiff {
+    /// Words added to the chapter, less words removed from it.
+    pub fn word_delta(&self) -> i64 {
+        self.hunks.iter().map(Hunk::word_delta).sum()
+    }
+}
+
+/// A run of paragraphs removed from a chapter, replaced by a run of
+/// paragraphs added (either of which may be empty).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hunk {
+    /// Index of the first removed paragraph in the old chapter (or where
+    /// paragraphs were added, if none were removed).
+    pub old_start: usize,
+    /// Index of the first added paragraph in the new chapter (or where
+    /// paragraphs were removed, if none were added).
+    pub new_start: usize,
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+}
+impl Hunk {
+    pub fn words_removed(&self) -> u64 {
+        words(&self.removed)
+    }
+
+    pub fn words_added(&self) -> u64 {
+        words(&self.added)
+    }
+
+    /// Words added, less words removed.
+    pub fn word_delta(&self) -> i64 {
+        self.words_added() as i64 - self.words_removed() as i64
+    }
+}
+
+/// Compares the prose of two versions of a work (as HTML), returning the
+/// chapters that differ. Returns nothing if the prose is the same.
+///
+/// Chapters are matched by position, so a chapter inserted part-way through
+/// a work shows as every chapter after it changing (and the last added).
+/// Documents without chapters have no prose to compare.
+pub fn diff_bodies(old: impl AsRef<[u8]>, new: impl AsRef<[u8]>) -> Vec<ChapterDiff> {
+    let old = chapters(old.as_ref());
+    let new = chapters(new.as_ref());
+    let mut diffs = Vec::new();
+    for number in 0..old.len().max(new.len()) {
+        let (status, old_title, new_title, hunks) = match (old.get(number), new.get(number)) {
+            (Some(old), Some(new)) => {
+                let hunks = hunks(&old.paragraphs, &new.paragraphs);
+                if hunks.is_empty() && old.title == new.title {
+                    continue;
+                }
+                (ChapterStatus::Changed, old.title.clone(), new.title.clone(), hunks)
+            },
+            (None, Some(new)) => {
+                let hunk = Hunk {
+                    old_start: 0,
+                    new_start: 0,
+                    removed: Vec::new(),
+                    added: new.paragraphs.clone(),
+                };
+                (ChapterStatus::Added, None, new.title.clone(), vec![hunk])
+            },
+            (Some(old), None) => {
+                let hunk = Hunk {
+                    old_start: 0,
+                    new_start: 0,
+                    removed: old.paragraphs.clone(),
+                    added: Vec::new(),
+                };
+                (ChapterStatus::Removed, old.title.clone(), None, vec![hunk])
+            },
+            (None, None) => unreachable!("chapter is in at least one version"),
+        };
+        diffs.push(ChapterDiff {
+            number: number + 1,
+            status,
+            old_title,
+            new_title,
+            hunks,
+        });
+    }
+    diffs
+}
+
+struct Chapter {
+    title: Option<String>,
+    paragraphs: Vec<String>,
+}
+
+/// Splits a work's body into chapters, each starting at its heading (works
+/// with a single chapter usually have none).
+fn chapters(html: &[u8]) -> Vec<Chapter> {
+    let document = Html::parse_document(&String::from_utf8_lossy(html));
+    let Some(body) = document.select(&CHAPTERS_SELECTOR).next() else {
+        return Vec::new();
+    };
+    let mut chapters: Vec<Chapter> = Vec::new();
+    for element in body.children().filter_map(ElementRef::wrap) {
+        let mut paragraphs = paragraphs(element);
+        let title = element.select(&CHAPTER_HEADING_SELECTOR).next().map(|heading| convert(heading, Format::Text));
+        match (title, chapters.last_mut()) {
+            (Some(title), _) => {
+                // The heading is the chapter's title, not its text.
+                if let Some(i) = paragraphs.iter().position(|p| *p == title) {
+                    paragraphs.remove(i);
+                }
+                chapters.push(Chapter { title: Some(title), paragraphs });
+            },
+            (None, Some(chapter)) => chapter.paragraphs.extend(paragraphs),
+            (None, None) => chapters.push(Chapter { title: None, paragraphs }),
+        }
+    }
+    chapters
+}
+
+fn paragraphs(element: ElementRef<'_>) -> Vec<String> {
+    convert(element, Format::Text).split("\n\n").filter(|p| !p.is_empty()).map(str::to_string).collect()
+}
+
+fn words(paragraphs: &[String]) -> u64 {
+    paragraphs.iter().map(|p| p.split_whitespace().count() as u64).sum()
+}
+
+/// The runs of paragraphs that differ between `old` and `new`, going by
+/// their longest common subsequence.
+fn hunks(old: &[String], new: &[String]) -> Vec<Hunk> {
+    // Edits are usually few and far between; only what's between the
+    // first and last needs comparing paragraph by paragraph.
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..]
+    // and b[j..].
+    let width = b.len() + 1;
+    let mut lcs = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = match a[i] == b[j] {
+                true => lcs[(i + 1) * width + j + 1] + 1,
+                false => lcs[(i + 1) * width + j].max(lcs[i * width + j + 1]),
+            };
+        }
+    }
+
+    let mut hunks = Vec::new();
+    let mut current: Option<Hunk> = None;
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            hunks.extend(current.take());
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let hunk = current.get_or_insert_with(|| Hunk {
+            old_start: prefix + i,
+            new_start: prefix + j,
+            removed: Vec::new(),
+            added: Vec::new(),
+        });
+        if j == b.len() || (i < a.len() && lcs[(i + 1) * width + j] >= lcs[i * width + j + 1]) {
+            hunk.removed.push(a[i].clone());
+            i += 1;
+        } else {
+            hunk.added.push(b[j].clone());
+            j += 1;
+        }
+    }
+    hunks.extend(current);
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn work(chapters: &[(&str, &str)]) -> String {
+        let chapters: String = chapters
+            .iter()
+            .map(|(title, text)| {
+                format!(r#"<div class="meta group"><h2 class="heading">{title}</h2></div><div class="userstuff">{text}</div>"#)
+            })
+            .collect();
+        format!(r#"<html><body><div id="chapters" class="userstuff">{chapters}</div></body></html>"#)
+    }
+
+    #[test]
+    fn test_diff_bodies() {
+        let old = work(&[
+            ("Chapter 1", "<p>One.</p><p>Two words.</p><p>Three.</p><p>Four.</p>"),
+            ("Chapter 2", "<p>Unchanged.</p>"),
+            ("Chapter 3", "<p>Cut.</p>"),
+        ]);
+        let new = work(&[
+            ("Chapter 1", "<p>One.</p><p>Two <em>whole</em> words.</p><p>Three.</p><p>Four.</p><p>Five.</p>"),
+            ("Chapter 2", "<p>  Unchanged.</p>"),
+        ]);
+        assert!(diff_bodies(&old, &old).is_empty());
+
+        let diffs = diff_bodies(&old, &new);
+        assert_eq!(2, diffs.len());
+        assert_eq!((1, ChapterStatus::Changed), (diffs[0].number, diffs[0].status));
+        let expected = vec![
+            Hunk {
+                old_start: 1,
+                new_start: 1,
+                removed: vec!["Two words.".into()],
+                added: vec!["Two whole words.".into()],
+            },
+            Hunk {
+                old_start: 4,
+                new_start: 4,
+                removed: vec![],
+                added: vec!["Five.".into()],
+            },
+        ];
+        assert_eq!(expected, diffs[0].hunks);
+        assert_eq!(2, diffs[0].word_delta());
+        assert_eq!((3, ChapterStatus::Removed), (diffs[1].number, diffs[1].status));
+        assert_eq!((Some("Chapter 3"), None), (diffs[1].old_title.as_deref(), diffs[1].new_title.as_deref()));
+        assert_eq!(-1, diffs[1].word_delta());
+
+        // A retitled chapter changes, even if its text doesn't.
+        let retitled = work(&[("Chapter 1: Tea", "<p>Text.</p>")]);
+        let diffs = diff_bodies(work(&[("Chapter 1", "<p>Text.</p>")]), retitled);
+        assert_eq!(ChapterStatus::Changed, diffs[0].status);
+        assert!(diffs[0].hunks.is_empty());
+    }
+
+    #[test]
+    fn test_untitled_chapter() {
+        let old =
+            r#"<div id="chapters" class="userstuff"><div class="userstuff"><p>A.</p><p>B.</p><p>C.</p></div></div>"#;
+        let new =
+            r#"<div id="chapters" class="userstuff"><div class="userstuff"><p>A.</p><p>C.</p><p>B.</p></div></div>"#;
+        let diffs = diff_bodies(old, new);
+        assert_eq!(1, diffs.len());
+        assert_eq!(None, diffs[0].new_title);
+        assert_eq!(2, diffs[0].hunks.len());
+        assert_eq!(0, diffs[0].word_delta());
+    }
+}
@@ -0,0 +1,210 @@
+//! Detecting (and repairing) downloads that aren't the UTF-8 they claim to be.
+//!
+//! AO3 has always served UTF-8, but older downloads have often been through a
+//! text editor, a browser's "save page" or a file-sharing tool since: some
+//! were saved as Latin-1 (or, more likely, Windows-1252), others were decoded
+//! as Windows-1252 and saved as UTF-8 *again*, turning `é` into `Ã©`. Parsed
+//! as UTF-8, titles and summaries of both come out garbled.
+
+use crate::error::{Error, ErrorKind};
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+/// How many bytes to search for a declared charset; the same as browsers.
+const SNIFF_BYTES: usize = 1024;
+
+/// Code points of Windows-1252 bytes `0x80`–`0x9F`, where it differs from
+/// Latin-1. Bytes it leaves undefined map to the C1 control of the same value
+/// (as they do in browsers), which makes the mapping reversible.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{02C6}',
+    '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}', '\u{0090}', '\u{2018}',
+    '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{02DC}', '\u{2122}', '\u{0161}',
+    '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// The character encoding a download was found to be in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// UTF-8, as served by AO3 (or nothing but ASCII).
+    #[default]
+    Utf8,
+    /// Windows-1252, a superset of (printable) Latin-1.
+    Windows1252,
+    /// UTF-8 that was decoded as Windows-1252 (or Latin-1) and encoded as
+    /// UTF-8 again; valid UTF-8, but garbled.
+    Mojibake,
+}
+impl Encoding {
+    /// Detects the encoding of an HTML document.
+    ///
+    /// A byte order mark means UTF-8. A Windows-1252 (or Latin-1) charset
+    /// declared in the first kilobyte is honoured. Otherwise, a document
+    /// that isn't valid UTF-8 is assumed to be Windows-1252, and one that is
+    /// valid UTF-8 is only considered garbled if *all* of its non-ASCII text
+    /// decodes to valid UTF-8 once encoded back to Windows-1252 bytes (which
+    /// ordinary text practically never does).
+    pub fn detect(html: impl AsRef<[u8]>) -> Self {
+        let html = html.as_ref();
+        if html.starts_with(&[0xEF, 0xBB, 0xBF]) || html.is_ascii() {
+            return Self::Utf8;
+        }
+        if declared_charset(html).is_some_and(|charset| is_single_byte(&charset)) {
+            return Self::Windows1252;
+        }
+        match std::str::from_utf8(html) {
+            Ok(text) if undo_mojibake(text).is_some() => Self::Mojibake,
+            Ok(_) => Self::Utf8,
+            // A multi-byte character cut off at the very end (a truncated
+            // download) doesn't make the rest of the document any less UTF-8.
+            Err(e) if e.error_len().is_none() => Self::Utf8,
+            Err(_) => Self::Windows1252,
+        }
+    }
+
+    /// Whether documents in this encoding have to be repaired to be read as
+    /// UTF-8.
+    pub fn needs_repair(&self) -> bool {
+        *self != Self::Utf8
+    }
+
+    /// Converts a document in this encoding to UTF-8.
+    ///
+    /// Decoding a document in an encoding other than the one it was
+    /// [detected](Self::detect) as is lossy at worst: text that can't be
+    /// repaired is left as it was.
+    pub fn decode<'a>(&self, html: &'a [u8]) -> Cow<'a, [u8]> {
+        match self {
+            Self::Utf8 => Cow::Borrowed(html),
+            Self::Windows1252 => Cow::Owned(html.iter().map(|&b| decode_byte(b)).collect::<String>().into_bytes()),
+            Self::Mojibake => match std::str::from_utf8(html).ok().and_then(undo_mojibake) {
+                Some(repaired) => Cow::Owned(repaired),
+                None => Cow::Borrowed(html),
+            },
+        }
+    }
+
+    /// Returns the name of the encoding, as recorded in a [`Version`](crate::models::Version).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Windows1252 => "windows-1252",
+            Self::Mojibake => "mojibake",
+        }
+    }
+}
+impl FromStr for Encoding {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Self::Utf8,
+            "windows-1252" | "cp1252" => Self::Windows1252,
+            "mojibake" => Self::Mojibake,
+            _ => exn::bail!(ErrorKind::ParseError {
+                field: "encoding",
+                value: format!("unknown encoding: {s}"),
+            }),
+        })
+    }
+}
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
+#[cfg(feature = "serde")]
+impl serde::Serialize for Encoding {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Encoding {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|_| serde::de::Error::custom(format!("unknown encoding: {s}")))
+    }
+}
+
+fn decode_byte(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
+
+/// The byte a character was misread from: as Latin-1 (including C1 controls)
+/// or as Windows-1252.
+fn encode_char(c: char) -> Option<u8> {
+    u8::try_from(u32::from(c)).ok().or_else(|| WINDOWS_1252.iter().position(|&w| w == c).map(|i| 0x80 + i as u8))
+}
+
+/// Encodes garbled text back to the bytes it was misread from, if they're
+/// valid UTF-8 (and so, presumably, the original text).
+fn undo_mojibake(text: &str) -> Option<Vec<u8>> {
+    let bytes = text.chars().map(encode_char).collect::<Option<Vec<u8>>>()?;
+    std::str::from_utf8(&bytes).ok()?;
+    Some(bytes)
+}
+
+/// The charset named by a `<meta charset>` or `<meta http-equiv>` tag near
+/// the start of the document, lowercased.
+fn declared_charset(html: &[u8]) -> Option<String> {
+    let head = String::from_utf8_lossy(&html[..html.len().min(SNIFF_BYTES)]).to_ascii_lowercase();
+    let start = head.find("charset=")? + "charset=".len();
+    let charset: String = head[start..]
+        .trim_start_matches(['"', '\''])
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        .collect();
+    (!charset.is_empty()).then_some(charset)
+}
+
+fn is_single_byte(charset: &str) -> bool {
+    matches!(charset, "windows-1252" | "cp1252" | "iso-8859-1" | "iso8859-1" | "latin1" | "l1" | "latin-1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Corruption;
+
+    #[test]
+    fn test_detect() {
+        let text = "<p>Café — naïve “quotes” ☕</p>";
+        assert_eq!(Encoding::Utf8, Encoding::detect(text));
+        assert_eq!(Encoding::Utf8, Encoding::detect("<p>ASCII</p>"));
+        // Cut off part-way through the last character.
+        assert_eq!(Encoding::Utf8, Encoding::detect(&text.as_bytes()[..text.len() - 6]));
+
+        let latin1 = Corruption::Latin1.apply("<p>Café naïve</p>".as_bytes());
+        assert_eq!(Encoding::Windows1252, Encoding::detect(&latin1));
+        assert_eq!("<p>Café naïve</p>".as_bytes(), &*Encoding::Windows1252.decode(&latin1));
+
+        let mojibake = Corruption::Mojibake.apply(text.as_bytes());
+        assert_eq!(Encoding::Mojibake, Encoding::detect(&mojibake));
+        assert_eq!(text.as_bytes(), &*Encoding::Mojibake.decode(&mojibake));
+    }
+
+    #[test]
+    fn test_windows_1252() {
+        // Mojibake from a Windows machine: `—` (E2 80 94) misread as `â€”`.
+        assert_eq!(Encoding::Mojibake, Encoding::detect("<p>A â€” B</p>"));
+        assert_eq!("<p>A — B</p>".as_bytes(), &*Encoding::Mojibake.decode("<p>A â€” B</p>".as_bytes()));
+        assert_eq!("“A”".as_bytes(), &*Encoding::Windows1252.decode(b"\x93A\x94"));
+
+        let declared = b"<meta charset=\"ISO-8859-1\"><p>Caf\xE9</p>";
+        assert_eq!(Encoding::Windows1252, Encoding::detect(declared));
+        let declared = b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=windows-1252\">\xE9";
+        assert_eq!(Encoding::Windows1252, Encoding::detect(declared));
+    }
+
+    #[test]
+    fn test_parse() {
+        for encoding in [Encoding::Utf8, Encoding::Windows1252, Encoding::Mojibake] {
+            assert_eq!(encoding, encoding.as_str().parse().unwrap());
+        }
+        assert!("ebcdic".parse::<Encoding>().is_err());
+    }
+}
@@ -8,6 +8,7 @@
 //!       to resort to anyhow+thiserror just because I don't want to deal with it.
 
 use derive_more::{Display, Error};
+pub use rawr_error::{Code, Domain, ErrorCode};
 
 /// An extraction error with automatic location tracking.
 pub type Error = exn::Exn<ErrorKind>;
@@ -46,3 +47,15 @@ impl ErrorKind {
         false
     }
 }
+
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::MalformedHtml(_) => 400,
+            Self::InvalidDocument => 415,
+            Self::MissingField(_) => 422,
+            Self::ParseError { .. } => 423,
+        };
+        Code::new(Domain::Extract, number)
+    }
+}
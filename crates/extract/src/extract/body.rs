@@ -0,0 +1,223 @@
+//! A work's body as plain text or Markdown.
+//!
+//! Converters from HTML to Markdown (like the one the summary goes through)
+//! lose the blank lines between paragraphs, which is most of what there is
+//! to a work's prose. Only what fanworks are written with is supported:
+//! paragraphs, headings, line breaks, emphasis, quotes, lists, rules and
+//! links. Anything else (such as tables) is reduced to its text.
+
+use scraper::{ElementRef, Node};
+
+/// Elements whose contents start (and end) a paragraph, besides quotes.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p", "div", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "pre", "center", "details", "summary", "table", "tr", "dl",
+    "dt", "dd", "ul", "ol", "li", "figure",
+];
+// Stand-ins for breaks while collecting text, since whitespace in the HTML
+// (including newlines) is collapsed. Neither appears in HTML text.
+const PARAGRAPH: char = '\u{1}';
+const LINE: char = '\u{2}';
+/// Stand-in for the spaces that indent list items' continuation lines.
+const INDENT: char = '\u{3}';
+
+/// What to convert the body to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// No markup at all.
+    Text,
+    Markdown,
+}
+
+/// The contents of `element` as paragraphs separated by blank lines, with
+/// line breaks (`<br>`) kept and any other whitespace collapsed.
+pub(crate) fn convert(element: ElementRef<'_>, format: Format) -> String {
+    let lines: Vec<_> = lines(element, format)
+        .into_iter()
+        .map(|line| line.map(|line| line.replace(INDENT, " ").trim_end().to_string()).unwrap_or_default())
+        .collect();
+    lines.join("\n")
+}
+
+/// Lines of the converted contents of `element`, with `None` for the blank
+/// lines between paragraphs.
+fn lines(element: ElementRef<'_>, format: Format) -> Vec<Option<String>> {
+    let mut collected = String::new();
+    collect(element, format, &mut collected);
+    let mut lines = Vec::new();
+    for paragraph in collected.split(PARAGRAPH) {
+        let paragraph: Vec<_> = paragraph
+            .split(LINE)
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|line| !line.is_empty())
+            .collect();
+        if paragraph.is_empty() {
+            continue;
+        }
+        if !lines.is_empty() {
+            lines.push(None);
+        }
+        lines.extend(paragraph.into_iter().map(Some));
+    }
+    lines
+}
+
+fn collect(element: ElementRef<'_>, format: Format, collected: &mut String) {
+    for child in element.children() {
+        let el = match child.value() {
+            Node::Text(text) => {
+                let text = text.chars().filter(|c| ![PARAGRAPH, LINE, INDENT].contains(c));
+                match format {
+                    Format::Text => collected.extend(text),
+                    Format::Markdown => text.for_each(|c| {
+                        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']') {
+                            collected.push('\\');
+                        }
+                        collected.push(c);
+                    }),
+                }
+                continue;
+            },
+            Node::Element(el) => el,
+            _ => continue,
+        };
+        let Some(child) = ElementRef::wrap(child) else {
+            continue;
+        };
+        let name = el.name();
+        let block = name == "blockquote" || BLOCK_ELEMENTS.contains(&name);
+        if block {
+            collected.push(PARAGRAPH);
+        }
+        match (format, name) {
+            (Format::Markdown, "br") => collected.extend(['\\', LINE]),
+            (_, "br") => collected.push(LINE),
+            (Format::Markdown, "hr") => collected.push_str("---"),
+            (Format::Markdown, "h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {
+                let level = usize::from(name.as_bytes()[1] - b'0');
+                let heading = lines(child, format).into_iter().flatten().collect::<Vec<_>>().join(" ");
+                collected.push_str(&format!("{} {heading}", "#".repeat(level)));
+            },
+            (Format::Markdown, "blockquote") => prefixed(collected, lines(child, format), |_| "> ", ">"),
+            (Format::Markdown, "ul" | "ol") => {
+                let items = child.children().filter_map(ElementRef::wrap).filter(|el| el.value().name() == "li");
+                for (i, item) in items.enumerate() {
+                    let marker = match name {
+                        "ol" => format!("{}. ", i + 1),
+                        _ => "- ".to_string(),
+                    };
+                    let indent = INDENT.to_string().repeat(marker.len());
+                    collected.push(PARAGRAPH);
+                    prefixed(collected, lines(item, format), |first| if first { &marker } else { &indent }, &indent);
+                }
+            },
+            (Format::Markdown, inline) => {
+                let marker = match inline {
+                    "em" | "i" | "cite" => "*",
+                    "strong" | "b" => "**",
+                    "s" | "strike" | "del" => "~~",
+                    "code" => "`",
+                    _ => "",
+                };
+                let href = (inline == "a").then(|| el.attr("href")).flatten();
+                if href.is_some() {
+                    collected.push('[');
+                }
+                collected.push_str(marker);
+                collect(child, format, collected);
+                collected.push_str(marker);
+                if let Some(href) = href {
+                    collected.push_str(&format!("]({href})"));
+                }
+            },
+            (Format::Text, _) => collect(child, format, collected),
+        }
+        if block {
+            collected.push(PARAGRAPH);
+        }
+    }
+}
+
+/// Writes already converted `lines` as one paragraph, prefixing each line
+/// (`blank` for blank lines, which mustn't end the paragraph).
+fn prefixed<'a>(collected: &mut String, lines: Vec<Option<String>>, prefix: impl Fn(bool) -> &'a str, blank: &str) {
+    for (i, line) in lines.into_iter().enumerate() {
+        if i > 0 {
+            collected.push(LINE);
+        }
+        match line {
+            Some(line) => collected.push_str(&format!("{}{line}", prefix(i == 0))),
+            None => collected.push_str(blank),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Extractor;
+
+    const HTML: &str = r#"<html><body><div id="preface"><p>Not the body.</p></div>
+<div id="chapters" class="userstuff">
+  <div class="meta group"><h2 class="heading">Chapter 1: Tea &amp; Biscuits</h2></div>
+  <div class="userstuff"><p>Once   upon
+    a <em>time</em>, in a *galaxy*.</p><p>Roses are red,<br/>violets are blue.</p>
+    <blockquote><p>A letter.</p><p>Yours, <a href="https://example.com">me</a></p></blockquote>
+    <ol><li>One</li><li><p>Two</p><p>Still two</p></li></ol></div>
+  <div class="meta group"><h2 class="heading">Chapter 2</h2></div>
+  <div class="userstuff">The end.<hr/></div>
+</div><div id="afterword"><p>Not the body either.</p></div></body></html>"#;
+
+    #[test]
+    fn test_body_text() {
+        let extractor = Extractor::from_html(HTML);
+        let expected = "Chapter 1: Tea & Biscuits
+
+Once upon a time, in a *galaxy*.
+
+Roses are red,
+violets are blue.
+
+A letter.
+
+Yours, me
+
+One
+
+Two
+
+Still two
+
+Chapter 2
+
+The end.";
+        assert_eq!(Some(expected), extractor.body_text().as_deref());
+        assert_eq!(None, Extractor::from_html("<p>Not a work.</p>").body_text());
+    }
+
+    #[test]
+    fn test_body_markdown() {
+        let extractor = Extractor::from_html(HTML);
+        let expected = r"## Chapter 1: Tea & Biscuits
+
+Once upon a *time*, in a \*galaxy\*.
+
+Roses are red,\
+violets are blue.
+
+> A letter.
+>
+> Yours, [me](https://example.com)
+
+1. One
+
+2. Two
+
+   Still two
+
+## Chapter 2
+
+The end.
+
+---";
+        assert_eq!(Some(expected), extractor.body_markdown().as_deref());
+    }
+}
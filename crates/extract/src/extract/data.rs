@@ -1,7 +1,7 @@
 use super::Stats;
 use crate::consts;
 use crate::error::{ErrorKind, Result};
-use crate::models::{Fandom, Language, Rating, SeriesPosition, Tag, TagKind, Warning};
+use crate::models::{Fandom, Language, Provenance, Rating, SeriesPosition, Tag, TagKind, Warning};
 use ::regex::{Regex, escape as regex_escape};
 use exn::{OptionExt, ResultExt};
 use scraper::{ElementRef, Html};
@@ -67,8 +67,15 @@ impl<'a> Datalist<'a> {
     }
 
     pub fn series(&self) -> Vec<SeriesPosition> {
+        self.series_with_provenance().0
+    }
+
+    /// Series memberships, [inferred](Provenance::Inferred) if the position
+    /// in any series had to be guessed.
+    pub(crate) fn series_with_provenance(&self) -> (Vec<SeriesPosition>, Provenance) {
+        let mut provenance = Provenance::Extracted;
         let Some(dd) = self.find_by_label(&["Series"]) else {
-            return Vec::new();
+            return (Vec::new(), provenance);
         };
         let dd_text = dd.text().collect::<String>();
         let mut series = Vec::new();
@@ -98,14 +105,17 @@ impl<'a> Datalist<'a> {
                 .and_then(|re| re.captures(&dd_text))
                 .and_then(|cap| cap.get(1))
                 .and_then(|m| m.as_str().replace(',', "").parse().ok())
-                .unwrap_or(1);
+                .unwrap_or_else(|| {
+                    provenance = Provenance::Inferred;
+                    1
+                });
             series.push(SeriesPosition {
                 id: series_id,
                 name: series_name,
                 position,
             });
         }
-        series
+        (series, provenance)
     }
 
     pub fn rating(&self) -> Result<Option<Rating>> {
@@ -141,6 +151,15 @@ impl<'a> Datalist<'a> {
     }
 
     pub fn language(&self) -> Language {
-        Language::from(self.extract_text(&["Language"]).unwrap_or_else(|| "Unknown".to_string()))
+        self.language_with_provenance().0
+    }
+
+    /// The work's language, [inferred](Provenance::Inferred) (as unknown) if
+    /// it isn't given.
+    pub(crate) fn language_with_provenance(&self) -> (Language, Provenance) {
+        match self.extract_text(&["Language"]) {
+            Some(language) => (Language::from(language), Provenance::Extracted),
+            None => (Language::from("Unknown".to_string()), Provenance::Inferred),
+        }
     }
 }
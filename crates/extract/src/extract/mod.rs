@@ -1,5 +1,6 @@
 //! Main extraction logic for AO3 HTML downloads.
 
+pub(crate) mod body;
 mod data;
 mod stats;
 
@@ -9,7 +10,8 @@ use std::str::FromStr;
 pub use self::data::Datalist;
 pub use self::stats::Stats;
 use crate::error::{Error, ErrorKind, Result};
-use crate::models::{Author, Metadata};
+use crate::models::{Author, Field, Metadata};
+use crate::profile;
 use crate::{ESTIMATED_HEADER_SIZE_BYTES, consts, safe_html_truncate};
 use exn::{OptionExt, ResultExt};
 #[cfg(feature = "markdown")]
@@ -67,30 +69,45 @@ impl Extractor {
     /// - Required fields cannot be found or parsed
     #[instrument()]
     pub fn metadata(self) -> Result<Metadata> {
+        self.metadata_profiled(&mut profile::Stats::default())
+    }
+
+    /// Like [`metadata`](Self::metadata), recording how long extracting each
+    /// field took in `stats`.
+    pub fn metadata_profiled(self, stats: &mut profile::Stats) -> Result<Metadata> {
         // Always attempt extraction of the Work ID first, it's
         // equivalent to quickly checking the HTML document validity.
-        let work_id = self.work_id().or_raise(|| ErrorKind::InvalidDocument)?;
+        let work_id = stats.time("work_id", || self.work_id()).or_raise(|| ErrorKind::InvalidDocument)?;
         let datalist = self.datalist();
-        let stats = datalist.stats()?;
-        let (published, last_modified) = stats.dates()?;
+        let text = stats.time("stats", || datalist.stats())?;
+        let (published, last_modified) = stats.time("dates", || text.dates())?;
+        let (series, series_provenance) = stats.time("series", || datalist.series_with_provenance());
+        let (language, language_provenance) = stats.time("language", || datalist.language_with_provenance());
         Ok(Metadata {
             // Main Document
             work_id,
-            title: self.title().or_raise(|| ErrorKind::MissingField("title"))?,
-            authors: self.authors(),
-            summary: self.summary(),
+            title: stats.time("title", || self.title()).or_raise(|| ErrorKind::MissingField("title"))?,
+            authors: stats.time("authors", || self.authors()),
+            summary: stats.time("summary", || self.summary()),
             // Datalist
-            fandoms: datalist.fandoms(),
-            series: datalist.series(),
-            rating: datalist.rating()?,
-            warnings: datalist.warnings(),
-            tags: datalist.tags(),
-            language: datalist.language(),
+            fandoms: stats.time("fandoms", || datalist.fandoms()),
+            series,
+            rating: stats.time("rating", || datalist.rating())?,
+            warnings: stats.time("warnings", || datalist.warnings()),
+            tags: stats.time("tags", || datalist.tags()),
+            language,
             // Datalist -> Stats
-            chapters: stats.chapters()?,
-            words: stats.words()?,
+            chapters: stats.time("chapters", || text.chapters())?,
+            words: stats.time("words", || text.words())?,
             published,
             last_modified,
+            work_skin: stats.time("work_skin", || self.work_skin()),
+            provenance: [
+                (Field::Series, series_provenance),
+                (Field::Language, language_provenance),
+            ]
+            .into_iter()
+            .collect(),
         })
     }
 
@@ -153,6 +170,36 @@ impl Extractor {
         })
     }
 
+    /// The work skin: every stylesheet scoped to `#workskin` (downloads only
+    /// ever embed the Archive's own stylesheet otherwise).
+    fn work_skin(&self) -> Option<String> {
+        let skins: Vec<String> = self
+            .document
+            .select(&consts::STYLE_SELECTOR)
+            .map(|el| el.text().collect::<String>().trim().to_string())
+            .filter(|css| css.contains("#workskin"))
+            .collect();
+        (!skins.is_empty()).then(|| skins.join("\n"))
+    }
+
+    /// The work's body (every chapter, with its headings and notes, but not
+    /// the preface or afterword) as Markdown.
+    ///
+    /// Returns `None` if the document has no chapters. The body is only
+    /// complete in an extractor made from the whole document (with
+    /// [`from_html`](Self::from_html), rather than [`from_long_html`](Self::from_long_html)).
+    pub fn body_markdown(&self) -> Option<String> {
+        let chapters = self.document.select(&consts::CHAPTERS_SELECTOR).next()?;
+        Some(body::convert(chapters, body::Format::Markdown))
+    }
+
+    /// The work's body (see [`body_markdown`](Self::body_markdown)) as plain
+    /// text: paragraphs separated by blank lines, and no markup at all.
+    pub fn body_text(&self) -> Option<String> {
+        let chapters = self.document.select(&consts::CHAPTERS_SELECTOR).next()?;
+        Some(body::convert(chapters, body::Format::Text))
+    }
+
     fn datalist(&self) -> Datalist<'_> {
         data::Datalist::new(&self.document)
     }
@@ -0,0 +1,175 @@
+//! Detecting the language a work's text is actually written in.
+//!
+//! Works declare a language when they're posted, but not always the right
+//! one: translations declared as the original's language, bilingual works,
+//! and plain mistakes (AO3 defaults to English). Detection samples the
+//! chapter text (skipping AO3's own headings and notes) and identifies its
+//! language statistically, with [`whatlang`].
+
+use crate::models::{DetectedLanguage, Language};
+use crate::safe_html_truncate;
+use memchr::memmem;
+use scraper::{ElementRef, Html, Node};
+use whatlang::Lang;
+
+/// How much chapter text to sample; plenty for a statistical guess, and it
+/// keeps detecting long works cheap.
+const SAMPLE_BYTES: usize = 32 * 1024;
+/// Too little text to guess from (such as a work that's all images).
+const MIN_SAMPLE_CHARS: usize = 40;
+
+/// Detects the language the chapter text of an AO3 download is written in.
+///
+/// Returns `None` if there's too little text to tell, or no language could
+/// be identified. HTML that isn't UTF-8 should be [decoded](crate::Encoding::decode)
+/// first.
+pub fn detect_language(html: impl AsRef<[u8]>) -> Option<DetectedLanguage> {
+    let html = html.as_ref();
+    // From the opening tag of the chapters (or, failing that, the body).
+    let start = memmem::find(html, b"id=\"chapters\"")
+        .and_then(|i| html[..i].iter().rposition(|&b| b == b'<'))
+        .or_else(|| memmem::find(html, b"<body"))
+        .unwrap_or(0);
+    let sample = String::from_utf8_lossy(safe_html_truncate(&html[start..], SAMPLE_BYTES));
+    let fragment = Html::parse_fragment(&sample);
+    let mut text = String::new();
+    collect_text(fragment.root_element(), &mut text);
+    if text.chars().filter(|c| c.is_alphabetic()).count() < MIN_SAMPLE_CHARS {
+        return None;
+    }
+    let info = whatlang::detect(&text)?;
+    let language = match ao3_code(info.lang()) {
+        Some(code) => Language::new(Language::iso_to_name(code)?),
+        None => Language {
+            name: info.lang().eng_name().to_string(),
+            iso_code: Some(info.lang().code().to_string()),
+        },
+    };
+    let confidence = (info.confidence() * 100.0).round().clamp(0.0, 100.0) as u8;
+    tracing::trace!(language = %language.name, confidence, "Detected language of chapter text");
+    Some(DetectedLanguage { language, confidence })
+}
+
+/// Appends the text of `element` to `text`, except for AO3's chapter
+/// headings and notes (which are in English whatever the work's language).
+fn collect_text(element: ElementRef, text: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(t) => {
+                text.push_str(t);
+                text.push(' ');
+            },
+            Node::Element(e) if e.classes().any(|c| c == "meta" || c == "heading" || c == "notes") => {},
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    collect_text(child, text);
+                }
+            },
+            _ => {},
+        }
+    }
+}
+
+/// The code AO3 uses for a language, if AO3 has it.
+fn ao3_code(lang: Lang) -> Option<&'static str> {
+    Some(match lang {
+        Lang::Afr => "afr",
+        Lang::Amh => "amh",
+        Lang::Ara => "ar",
+        Lang::Aze => "azj",
+        Lang::Bel => "be",
+        Lang::Ben => "bn",
+        Lang::Bul => "bg",
+        Lang::Cat => "ca",
+        Lang::Ces => "cs",
+        Lang::Cmn => "zh",
+        Lang::Dan => "da",
+        Lang::Deu => "de",
+        Lang::Ell => "el",
+        Lang::Eng => "en",
+        Lang::Epo => "eo",
+        Lang::Est => "et",
+        Lang::Fin => "fi",
+        Lang::Fra => "fr",
+        Lang::Heb => "he",
+        Lang::Hin => "hi",
+        Lang::Hrv => "hr",
+        Lang::Hun => "hu",
+        Lang::Hye => "hy",
+        Lang::Ind => "id",
+        Lang::Ita => "it",
+        Lang::Jav => "jv",
+        Lang::Jpn => "ja",
+        Lang::Kan => "kan",
+        Lang::Kat => "kat",
+        Lang::Khm => "khm",
+        Lang::Kor => "ko",
+        Lang::Lat => "la",
+        Lang::Lav => "lv",
+        Lang::Lit => "lt",
+        Lang::Mal => "ml",
+        Lang::Mar => "mr",
+        Lang::Mkd => "mk",
+        Lang::Mya => "my",
+        Lang::Nld => "nl",
+        Lang::Nob => "no",
+        Lang::Pan => "pa",
+        Lang::Pes => "fa",
+        Lang::Pol => "pl",
+        // Brazilian and European Portuguese can't be told apart; most of
+        // AO3's Portuguese works are Brazilian.
+        Lang::Por => "ptBR",
+        Lang::Ron => "ro",
+        Lang::Rus => "ru",
+        Lang::Sin => "si",
+        Lang::Slk => "sk",
+        Lang::Slv => "slv",
+        Lang::Spa => "es",
+        Lang::Srp => "sr",
+        Lang::Swe => "sv",
+        Lang::Tam => "ta",
+        Lang::Tel => "tel",
+        Lang::Tgl => "fil",
+        Lang::Tha => "th",
+        Lang::Tur => "tr",
+        Lang::Ukr => "uk",
+        Lang::Urd => "urd",
+        Lang::Vie => "vi",
+        Lang::Yid => "yi",
+        Lang::Zul => "zu",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn download(chapters: &str) -> String {
+        format!(
+            r#"<html><head></head><body><div id="preface"><p>Summary in English, as it happens.</p></div>
+<div id="chapters" class="userstuff"><div class="meta group"><h2 class="heading">Chapter 1: The Beginning</h2></div>
+<div class="userstuff">{chapters}</div></div></body></html>"#
+        )
+    }
+
+    #[test]
+    fn test_detect_language() {
+        let html = download(
+            "<p>Il était une fois une petite fille qui vivait dans un village près de la forêt. Tous les jours, elle \
+             allait voir sa grand-mère, qui habitait de l'autre côté du bois.</p>",
+        );
+        let detected = detect_language(&html).unwrap();
+        assert_eq!(Some("fr"), detected.language.iso_code.as_deref());
+        assert_eq!("Français", detected.language.name);
+        assert!(detected.differs_from(&Language::new("English"), 50));
+        assert!(!detected.differs_from(&Language::new("Français"), 0));
+
+        let html = download(
+            "<p>The rain had not stopped for three days, and the river was rising faster than anyone in the village \
+             could remember. Nobody wanted to be the first to leave.</p>",
+        );
+        assert_eq!("English", detect_language(&html).unwrap().language.name);
+        assert!(detect_language(download("<img src=\"art.png\"/>")).is_none());
+    }
+}
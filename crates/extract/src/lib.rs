@@ -1,18 +1,34 @@
 mod compare;
 mod consts;
+mod diff;
+mod encoding;
 pub mod error;
 mod extract;
+#[cfg(feature = "language-detection")]
+mod language;
 pub mod models;
+mod normalize;
+pub mod profile;
+pub mod reconstruct;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 mod truncate;
+mod validate;
 
 use exn::ResultExt;
 use time::UtcDateTime;
 use tracing::instrument;
 
+pub use crate::diff::{ChapterDiff, ChapterStatus, Hunk, diff_bodies};
+pub use crate::encoding::Encoding;
 use crate::error::{ErrorKind, Result};
 pub use crate::extract::{Datalist, Extractor, Stats, is_valid};
-use crate::models::Version;
+#[cfg(feature = "language-detection")]
+pub use crate::language::detect_language;
+use crate::models::{Metadata, Version};
+pub use crate::normalize::normalized_hash;
 pub use crate::truncate::{ESTIMATED_HEADER_SIZE_BYTES, safe_html_truncate};
+pub use crate::validate::{Issue, Section, Validation, validate_deep};
 
 /// Easy, top-level entrypoint for the extraction of [`Version`] from raw HTML bytes.
 ///
@@ -21,10 +37,31 @@ pub use crate::truncate::{ESTIMATED_HEADER_SIZE_BYTES, safe_html_truncate};
 ///
 /// Accepts raw bytes, instead of requiring HTML to be valid UTF-8. Invalid byte
 /// sequences are replaced with U+FFFD during parsing. See [`Extractor`] for
-/// more details.
+/// more details, and [`extract_repairing`] for HTML that might not be UTF-8.
 #[instrument(skip(html), fields(html_size = html.as_ref().len()))]
 pub fn extract(html: impl AsRef<[u8]>) -> Result<Version> {
     let html = html.as_ref();
+    version(html, Encoding::Utf8, Extractor::from_long_html(html).metadata()?)
+}
+
+/// Like [`extract`], but first [detects](Encoding::detect) the encoding of
+/// the HTML, extracting metadata from it as it would read once repaired to
+/// UTF-8 (instead of from garbled, lossily-converted text).
+///
+/// The detected encoding is recorded in the [`Version`], but its hash (and
+/// length) are still of the HTML as given.
+#[instrument(skip(html), fields(html_size = html.as_ref().len()))]
+pub fn extract_repairing(html: impl AsRef<[u8]>) -> Result<Version> {
+    let html = html.as_ref();
+    let encoding = Encoding::detect(html);
+    if encoding.needs_repair() {
+        tracing::debug!(%encoding, "HTML is not UTF-8; repairing before extraction");
+    }
+    let header = encoding.decode(safe_html_truncate(html, ESTIMATED_HEADER_SIZE_BYTES));
+    version(html, encoding, Extractor::from_html(header).metadata()?)
+}
+
+fn version(html: &[u8], encoding: Encoding, metadata: Metadata) -> Result<Version> {
     Ok(Version {
         hash: blake3::hash(html).to_string(),
         crc32: crc32fast::hash(html),
@@ -32,7 +69,9 @@ pub fn extract(html: impl AsRef<[u8]>) -> Result<Version> {
             field: "length",
             value: html.len().to_string(),
         })?,
+        encoding,
+        detected_language: None,
         extracted_at: UtcDateTime::now(),
-        metadata: Extractor::from_long_html(html).metadata()?,
+        metadata,
     })
 }
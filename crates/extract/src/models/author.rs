@@ -11,11 +11,41 @@ pub struct Author {
     pub pseudonym: Option<String>,
 }
 impl Author {
+    /// Creates an author, normalizing the pseudonym.
+    ///
+    /// Surrounding whitespace is trimmed, and a pseudonym that is blank or
+    /// merely repeats the username (ignoring case) is dropped, so that
+    /// `"user (user)"` and `"User (user)"` both describe the same author as
+    /// `"user"`.
     pub fn new<P: Into<String>>(username: impl Into<String>, pseudonym: Option<P>) -> Self {
-        let username = username.into();
-        let pseudonym = pseudonym.map(Into::into).filter(|p: &String| *p != username);
+        let username = username.into().trim().to_string();
+        let pseudonym = pseudonym
+            .map(|p| p.into().trim().to_string())
+            .filter(|p| !p.is_empty() && !p.eq_ignore_ascii_case(&username));
         Self { username, pseudonym }
     }
+
+    /// The username and pseudonym, lowercased: AO3 usernames and pseudonyms
+    /// are case-insensitive, so authors with equal keys are the same author.
+    pub fn key(&self) -> (String, Option<String>) {
+        (self.username.to_lowercase(), self.pseudonym.as_ref().map(|p| p.to_lowercase()))
+    }
+
+    /// Whether this author is the AO3 user `username` (ignoring case) writing
+    /// as `pseudonym`. Passing `None` matches any of the user's pseudonyms.
+    pub fn is(&self, username: &str, pseudonym: Option<&str>) -> bool {
+        if !self.username.eq_ignore_ascii_case(username.trim()) {
+            return false;
+        }
+        let Some(pseudonym) = pseudonym else {
+            return true;
+        };
+        match (&self.pseudonym, Self::new(username, Some(pseudonym)).pseudonym) {
+            (Some(a), Some(b)) => a.eq_ignore_ascii_case(&b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
 }
 impl FromStr for Author {
     type Err = Error;
@@ -89,6 +119,27 @@ mod test {
         assert_eq!(obj, expected);
     }
 
+    #[rstest]
+    #[case("user", None)]
+    #[case("user (user)", None)]
+    #[case("User (user)", None)]
+    #[case("  pseud  (user)", Some("pseud"))]
+    fn test_author_normalizes_pseudonym(#[case] input: &str, #[case] pseudonym: Option<&str>) {
+        let author: Author = input.parse().unwrap();
+        assert_eq!(author.username, "user");
+        assert_eq!(author.pseudonym.as_deref(), pseudonym);
+    }
+
+    #[test]
+    fn test_author_is() {
+        let author = Author::new("user", Some("Pseud"));
+        assert!(author.is("USER", None));
+        assert!(author.is("user", Some("pseud")));
+        assert!(!author.is("user", Some("user")));
+        assert!(!author.is("other", None));
+        assert!(Author::new("user", None::<&str>).is("user", Some("User")));
+    }
+
     #[test]
     fn test_author_vec_serialize() {
         let input = vec![
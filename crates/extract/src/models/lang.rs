@@ -3,14 +3,20 @@
 //! The mappings are derived from the `<select>` element in AO3's work search form,
 //! where each `<option>` has a `lang` attribute containing the ISO code and the
 //! element text contains the display name.
+//!
+//! AO3 adds languages from time to time; rather than waiting on a release,
+//! additions (and corrections) can be [registered](Language::register) at
+//! runtime, usually from a [`LanguagePack`](super::LanguagePack). The map
+//! built in is the baseline that registered languages are looked up before.
 
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::str::FromStr;
-use std::sync::LazyLock;
+use std::sync::{LazyLock, PoisonError, RwLock};
 
 /// Language information for a work.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Language {
     /// Language name as displayed on AO3 (e.g., "English")
     pub name: String,
@@ -33,7 +39,8 @@ impl Language {
     /// assert_eq!(Language::name_to_iso("Unknown Language"), None);
     /// ```
     pub fn name_to_iso(name: &str) -> Option<&'static str> {
-        LANGUAGES_REVERSED.get(name).copied()
+        let registered = REGISTERED.read().unwrap_or_else(PoisonError::into_inner).by_name.get(name).copied();
+        registered.or_else(|| LANGUAGES_REVERSED.get(name).copied())
     }
 
     /// Returns the AO3 language display name for a given ISO-639 code.
@@ -46,7 +53,35 @@ impl Language {
     /// assert_eq!(Language::iso_to_name("Unknown ISO"), None);
     /// ```
     pub fn iso_to_name(iso: &str) -> Option<&'static str> {
-        LANGUAGES.get(iso).copied()
+        let registered = REGISTERED.read().unwrap_or_else(PoisonError::into_inner).by_iso.get(iso).copied();
+        registered.or_else(|| LANGUAGES.get(iso).copied())
+    }
+
+    /// Adds a language to those AO3 is known to have, or renames one it
+    /// already has, for the rest of the process. The name the code had
+    /// before is still recognized by [`name_to_iso`](Self::name_to_iso),
+    /// so works downloaded before the rename keep their code.
+    ///
+    /// Meant for the handful of languages loaded on startup: registered names
+    /// are never freed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rawr_extract::models::Language;
+    /// Language::register("jbo", "la .lojban.");
+    /// assert_eq!(Language::iso_to_name("jbo"), Some("la .lojban."));
+    /// assert_eq!(Language::new("la .lojban.").iso_code.as_deref(), Some("jbo"));
+    /// ```
+    pub fn register(iso: &str, name: &str) {
+        if Self::iso_to_name(iso) == Some(name) && Self::name_to_iso(name) == Some(iso) {
+            return;
+        }
+        let iso: &'static str = Box::leak(iso.into());
+        let name: &'static str = Box::leak(name.into());
+        let mut registered = REGISTERED.write().unwrap_or_else(PoisonError::into_inner);
+        registered.by_iso.insert(iso, name);
+        registered.by_name.insert(name, iso);
     }
 }
 impl FromStr for Language {
@@ -61,6 +96,27 @@ impl From<String> for Language {
     }
 }
 
+/// The language a work's text was detected to be written in, which may not be
+/// the language it was declared as.
+///
+/// Only present on versions extracted with detection enabled (see the
+/// `language-detection` feature).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DetectedLanguage {
+    /// Named as on AO3 where AO3 has the language, otherwise in English.
+    pub language: Language,
+    /// How confident detection was, from 0 to 100.
+    pub confidence: u8,
+}
+impl DetectedLanguage {
+    /// Whether the text is (with at least `min_confidence`) written in a
+    /// language other than `declared`.
+    pub fn differs_from(&self, declared: &Language, min_confidence: u8) -> bool {
+        self.confidence >= min_confidence && !self.language.name.eq_ignore_ascii_case(&declared.name)
+    }
+}
+
 /// Map of AO3 language ISO codes to their display names.
 ///
 /// Built from  AO3's official language dropdown.
@@ -223,6 +279,22 @@ static LANGUAGES: LazyLock<HashMap<&'static str, &'static str>> = LazyLock::new(
 static LANGUAGES_REVERSED: LazyLock<HashMap<&'static str, &'static str>> =
     LazyLock::new(|| LANGUAGES.iter().map(|(k, v)| (*v, *k)).collect());
 
+/// Languages [registered](Language::register) at runtime, looked up before
+/// [`LANGUAGES`].
+static REGISTERED: LazyLock<RwLock<Registered>> = LazyLock::new(RwLock::default);
+
+#[derive(Default)]
+struct Registered {
+    by_iso: HashMap<&'static str, &'static str>,
+    by_name: HashMap<&'static str, &'static str>,
+}
+
+/// Whether AO3 was known to have the language with the ISO code under the
+/// name, before any were registered at runtime.
+pub(super) fn is_builtin(iso: &str, name: &str) -> bool {
+    LANGUAGES.get(iso) == Some(&name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -246,4 +318,18 @@ mod tests {
         assert_eq!(Language::iso_to_name("Not A Real ISO"), None);
         assert_eq!(Language::iso_to_name(""), None);
     }
+
+    #[test]
+    fn registered_languages() {
+        Language::register("xqt", "Test Language");
+        assert_eq!(Language::iso_to_name("xqt"), Some("Test Language"));
+        assert_eq!(Language::name_to_iso("Test Language"), Some("xqt"));
+
+        // Renaming a built-in language still recognizes its old name.
+        Language::register("tqx", "Thermian (Galaxy Quest)");
+        assert_eq!(Language::iso_to_name("tqx"), Some("Thermian (Galaxy Quest)"));
+        assert_eq!(Language::name_to_iso("Thermian (Galaxy Quest)"), Some("tqx"));
+        assert_eq!(Language::name_to_iso("Thermian"), Some("tqx"));
+        assert_eq!(Language::iso_to_name("en"), Some("English"));
+    }
 }
@@ -0,0 +1,140 @@
+//! Languages AO3 has that this crate was released without.
+//!
+//! A [`LanguagePack`] is generated from AO3's work search form whenever AO3
+//! adds (or renames) a language, kept as a TOML file of ISO codes to names:
+//!
+//! ```toml
+//! jbo = "la .lojban."
+//! tqx = "Thermian (Galaxy Quest)"
+//! ```
+//!
+//! and [installed](LanguagePack::install) on startup.
+
+use super::Language;
+use super::lang::is_builtin;
+#[cfg(feature = "language-packs")]
+use crate::error::{ErrorKind, Result};
+use scraper::{Html, Selector};
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+/// Language options in AO3's work search form.
+static LANGUAGE_OPTIONS: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse("select#work_search_language_id option[lang]").expect("valid selector"));
+
+/// Languages, by ISO code, to [register](Language::register) over those built in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "language-packs", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "language-packs", serde(transparent))]
+pub struct LanguagePack {
+    languages: BTreeMap<String, String>,
+}
+impl LanguagePack {
+    /// Generates a pack of the languages in AO3's work search form (as
+    /// downloaded from `https://archiveofourown.org/works/search`) that
+    /// aren't built in, or are built in under a different name.
+    pub fn from_search_form(html: &str) -> Self {
+        let document = Html::parse_document(html);
+        let languages = document
+            .select(&LANGUAGE_OPTIONS)
+            .filter_map(|option| {
+                let iso = option.value().attr("lang")?.trim();
+                let name = option.text().collect::<String>();
+                let name = name.trim();
+                (!iso.is_empty() && !name.is_empty() && !is_builtin(iso, name))
+                    .then(|| (iso.to_string(), name.to_string()))
+            })
+            .collect();
+        Self { languages }
+    }
+
+    /// Reads a pack from TOML, a table of ISO codes to names.
+    ///
+    /// # Errors
+    /// Returns [`ErrorKind::ParseError`] if the TOML isn't such a table.
+    #[cfg(feature = "language-packs")]
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml).map_err(|e| {
+            exn::Exn::new(ErrorKind::ParseError {
+                field: "language pack",
+                value: e.to_string(),
+            })
+        })
+    }
+
+    /// Writes the pack as TOML, to be read with [`from_toml`](Self::from_toml).
+    #[cfg(feature = "language-packs")]
+    pub fn to_toml(&self) -> String {
+        // Infallible: a table of strings is always valid TOML.
+        toml::to_string(self).expect("language pack serializes")
+    }
+
+    /// Adds a language to the pack, replacing any with the same ISO code.
+    pub fn insert(&mut self, iso: impl Into<String>, name: impl Into<String>) {
+        self.languages.insert(iso.into(), name.into());
+    }
+
+    pub fn len(&self) -> usize {
+        self.languages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.languages.is_empty()
+    }
+
+    /// The languages in the pack, as ISO codes and names.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.languages.iter().map(|(iso, name)| (iso.as_str(), name.as_str()))
+    }
+
+    /// [Registers](Language::register) every language in the pack, for the
+    /// rest of the process.
+    pub fn install(&self) {
+        for (iso, name) in self.iter() {
+            Language::register(iso, name);
+        }
+        tracing::debug!(languages = self.len(), "Installed language pack");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEARCH_FORM: &str = r#"<html><body><form>
+        <select id="work_search_language_id" name="work_search[language_id]">
+            <option value=""></option>
+            <option value="en" lang="en">English</option>
+            <option value="de" lang="de">Deutsch</option>
+            <option value="jbo" lang="jbo"> la .lojban. </option>
+            <option value="sjn" lang="sjn">Sindarin (Elvish)</option>
+        </select>
+        <select id="other"><option lang="xx">Not A Language</option></select>
+    </form></body></html>"#;
+
+    #[test]
+    fn test_from_search_form() {
+        let pack = LanguagePack::from_search_form(SEARCH_FORM);
+        assert_eq!(vec![("jbo", "la .lojban."), ("sjn", "Sindarin (Elvish)")], pack.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_install() {
+        let mut pack = LanguagePack::default();
+        pack.insert("xpk", "Pack Language");
+        assert_eq!(None, Language::iso_to_name("xpk"));
+        pack.install();
+        assert_eq!(Some("Pack Language"), Language::iso_to_name("xpk"));
+        assert_eq!(Some("xpk".to_string()), Language::new("Pack Language").iso_code);
+    }
+
+    #[cfg(feature = "language-packs")]
+    #[test]
+    fn test_toml() {
+        let pack = LanguagePack::from_search_form(SEARCH_FORM);
+        let toml = pack.to_toml();
+        assert_eq!("jbo = \"la .lojban.\"\nsjn = \"Sindarin (Elvish)\"\n", toml);
+        assert_eq!(pack, LanguagePack::from_toml(&toml).unwrap());
+        assert!(LanguagePack::from_toml("jbo = 3").is_err());
+    }
+}
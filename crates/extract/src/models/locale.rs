@@ -0,0 +1,116 @@
+//! Display names of ratings and warnings in languages other than English.
+//!
+//! AO3 only names [`Rating`]s and [`Warning`]s in English, and so do their
+//! [`as_str`](Rating::as_str) methods. A front-end in another language builds
+//! a [`Locale`] of its own names for them (or loads one, with the `serde`
+//! feature) instead of matching on the enums itself; any it leaves out are
+//! named in English.
+
+use super::{Rating, Warning};
+use std::collections::HashMap;
+
+/// Names of ratings and warnings in one language.
+///
+/// The [default](Locale::default) is English, naming each as AO3 does.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_extract::models::{Locale, Rating, Warning};
+///
+/// let german = Locale::new("de")
+///     .with_rating(Rating::Explicit, "Explizit")
+///     .with_warning(Warning::MajorCharacterDeath, "Tod einer Hauptfigur");
+/// assert_eq!("Explizit", german.rating(Rating::Explicit));
+/// assert_eq!("Tod einer Hauptfigur", german.warning(Warning::MajorCharacterDeath));
+/// // Names not translated fall back to English.
+/// assert_eq!("Mature", german.rating(Rating::Mature));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Locale {
+    /// ISO 639 code of the language the names are in.
+    language: String,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "HashMap::is_empty"))]
+    ratings: HashMap<Rating, String>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "HashMap::is_empty"))]
+    warnings: HashMap<Warning, String>,
+}
+impl Default for Locale {
+    fn default() -> Self {
+        Self::new("en")
+    }
+}
+impl Locale {
+    /// A locale for the language with the ISO 639 code, naming everything in
+    /// English until given names of its own.
+    pub fn new(language: impl Into<String>) -> Self {
+        Self {
+            language: language.into(),
+            ratings: HashMap::new(),
+            warnings: HashMap::new(),
+        }
+    }
+
+    /// Names `rating` as `name`.
+    pub fn with_rating(mut self, rating: Rating, name: impl Into<String>) -> Self {
+        self.ratings.insert(rating, name.into());
+        self
+    }
+
+    /// Names `warning` as `name`.
+    pub fn with_warning(mut self, warning: Warning, name: impl Into<String>) -> Self {
+        self.warnings.insert(warning, name.into());
+        self
+    }
+
+    /// ISO 639 code of the language the names are in.
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// The name of `rating`, or its English name if it has none in this locale.
+    pub fn rating(&self, rating: Rating) -> &str {
+        self.ratings.get(&rating).map_or_else(|| rating.as_str(), String::as_str)
+    }
+
+    /// The name of `warning`, or its English name if it has none in this locale.
+    pub fn warning(&self, warning: Warning) -> &str {
+        self.warnings.get(&warning).map_or_else(|| warning.as_str(), String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::from_str as from_json;
+
+    #[test]
+    fn test_english_by_default() {
+        let locale = Locale::default();
+        assert_eq!("en", locale.language());
+        assert_eq!("Teen And Up Audiences", locale.rating(Rating::TeenAndUp));
+        assert_eq!("Rape/Non-Con", locale.warning(Warning::NonCon));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let locale: Locale = from_json(
+            r#"{
+                "language": "fr",
+                "ratings": {"E": "Explicite", "Teen And Up Audiences": "Adolescents et plus"},
+                "warnings": {"NoWarningsApply": "Aucun avertissement"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!("fr", locale.language());
+        assert_eq!("Explicite", locale.rating(Rating::Explicit));
+        assert_eq!("Adolescents et plus", locale.rating(Rating::TeenAndUp));
+        assert_eq!("General Audiences", locale.rating(Rating::GeneralAudiences));
+        assert_eq!("Aucun avertissement", locale.warning(Warning::NoWarningsApply));
+        assert_eq!("Underage", locale.warning(Warning::Underage));
+
+        let locale: Locale = from_json(r#"{"language": "es"}"#).unwrap();
+        assert_eq!(Locale::new("es"), locale);
+    }
+}
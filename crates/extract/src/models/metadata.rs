@@ -1,7 +1,8 @@
-use super::{Author, Chapters, Fandom, Language, Rating, SeriesPosition, Tag, Warning};
+use super::{Author, Chapters, Fandom, Field, Language, Provenance, Provenances, Rating, SeriesPosition, Tag, Warning};
 use time::Date;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Metadata {
     /// AO3 Work ID (extracted from URL)
     pub work_id: u64,
@@ -31,4 +32,49 @@ pub struct Metadata {
     pub published: Date,
     /// Most recent modification date (update or completion)
     pub last_modified: Date,
+    /// The author's work skin (CSS, every rule of which is scoped to
+    /// `#workskin`), as embedded in the download; unsanitized
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub work_skin: Option<String>,
+    /// Where each field's value came from (if not extracted)
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Provenances::is_empty"))]
+    pub provenance: Provenances,
+}
+impl Metadata {
+    /// Returns true if the work is complete (planned chapters have been written).
+    ///
+    /// Works with an unknown total chapter count are never complete.
+    pub fn is_complete(&self) -> bool {
+        self.chapters.is_complete()
+    }
+
+    /// Changes a field with `edit`, marking it as [edited by a user](Provenance::UserEdited).
+    pub fn edit(&mut self, field: Field, edit: impl FnOnce(&mut Self)) {
+        edit(self);
+        self.provenance.set(field, Provenance::UserEdited);
+    }
+
+    /// Copies the fields a user edited in `previous` (metadata of the same
+    /// work, such as from before it was extracted again) over this metadata's,
+    /// so that re-extraction doesn't undo them.
+    pub fn keep_user_edits(&mut self, previous: &Metadata) {
+        for field in previous.provenance.user_edited() {
+            match field {
+                Field::Title => self.title.clone_from(&previous.title),
+                Field::Authors => self.authors.clone_from(&previous.authors),
+                Field::Fandoms => self.fandoms.clone_from(&previous.fandoms),
+                Field::Series => self.series.clone_from(&previous.series),
+                Field::Chapters => self.chapters = previous.chapters,
+                Field::Words => self.words = previous.words,
+                Field::Rating => self.rating = previous.rating,
+                Field::Warnings => self.warnings.clone_from(&previous.warnings),
+                Field::Tags => self.tags.clone_from(&previous.tags),
+                Field::Summary => self.summary.clone_from(&previous.summary),
+                Field::Language => self.language.clone_from(&previous.language),
+                Field::Published => self.published = previous.published,
+                Field::LastModified => self.last_modified = previous.last_modified,
+            }
+            self.provenance.set(field, Provenance::UserEdited);
+        }
+    }
 }
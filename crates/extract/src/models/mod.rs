@@ -2,7 +2,10 @@ mod author;
 mod chapters;
 mod fandom;
 mod lang;
+mod langpack;
+mod locale;
 mod metadata;
+mod provenance;
 mod rating;
 mod series;
 mod tag;
@@ -12,13 +15,17 @@ mod warning;
 pub use self::author::Author;
 pub use self::chapters::Chapters;
 pub use self::fandom::Fandom;
-pub use self::lang::Language;
+pub use self::lang::{DetectedLanguage, Language};
+pub use self::langpack::LanguagePack;
+pub use self::locale::Locale;
 pub use self::metadata::Metadata;
+pub use self::provenance::{Field, Provenance, Provenances};
 pub use self::rating::Rating;
 pub use self::series::SeriesPosition;
 pub use self::tag::{Tag, TagKind};
 pub use self::version::Version;
 pub use self::warning::Warning;
+pub use crate::encoding::Encoding;
 
 fn sanitize(s: impl AsRef<str>) -> String {
     s.as_ref().trim().to_lowercase().replace(['/', '-', '_', ' '], "")
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Where the value of a [`Metadata`](super::Metadata) field came from, and so
+/// how far it can be trusted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Provenance {
+    /// Read as-is from the document.
+    #[default]
+    Extracted,
+    /// Missing from (or unreadable in) the document, and filled in with a
+    /// best guess.
+    Inferred,
+    /// Set by a user; extracting the work again mustn't replace it.
+    UserEdited,
+}
+impl Provenance {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Extracted => "extracted",
+            Self::Inferred => "inferred",
+            Self::UserEdited => "user edited",
+        }
+    }
+}
+impl Display for Provenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A field of [`Metadata`](super::Metadata) that can have a [`Provenance`].
+///
+/// The work ID isn't one: it identifies the work, so is always extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Field {
+    Title,
+    Authors,
+    Fandoms,
+    Series,
+    Chapters,
+    Words,
+    Rating,
+    Warnings,
+    Tags,
+    Summary,
+    Language,
+    Published,
+    LastModified,
+}
+impl Field {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Title => "title",
+            Self::Authors => "authors",
+            Self::Fandoms => "fandoms",
+            Self::Series => "series",
+            Self::Chapters => "chapters",
+            Self::Words => "words",
+            Self::Rating => "rating",
+            Self::Warnings => "warnings",
+            Self::Tags => "tags",
+            Self::Summary => "summary",
+            Self::Language => "language",
+            Self::Published => "published",
+            Self::LastModified => "last_modified",
+        }
+    }
+}
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The [`Provenance`] of each field of a work's metadata.
+///
+/// Only fields that weren't [extracted](Provenance::Extracted) are recorded,
+/// so metadata extracted in full from a well-formed download has none.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Provenances(BTreeMap<Field, Provenance>);
+impl Provenances {
+    pub fn get(&self, field: Field) -> Provenance {
+        self.0.get(&field).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, field: Field, provenance: Provenance) {
+        match provenance {
+            Provenance::Extracted => self.0.remove(&field),
+            _ => self.0.insert(field, provenance),
+        };
+    }
+
+    /// Whether every field was extracted.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Fields that weren't extracted, and where they came from instead.
+    pub fn iter(&self) -> impl Iterator<Item = (Field, Provenance)> + '_ {
+        self.0.iter().map(|(field, provenance)| (*field, *provenance))
+    }
+
+    /// Fields set by a user.
+    pub fn user_edited(&self) -> impl Iterator<Item = Field> + '_ {
+        self.iter().filter(|(_, provenance)| *provenance == Provenance::UserEdited).map(|(field, _)| field)
+    }
+}
+impl FromIterator<(Field, Provenance)> for Provenances {
+    fn from_iter<T: IntoIterator<Item = (Field, Provenance)>>(iter: T) -> Self {
+        let mut provenances = Self::default();
+        for (field, provenance) in iter {
+            provenances.set(field, provenance);
+        }
+        provenances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Generator;
+    use serde_json::{from_str as from_json, to_string as to_json};
+
+    #[test]
+    fn test_provenances_only_record_non_extracted() {
+        let mut provenances: Provenances = [
+            (Field::Title, Provenance::Extracted),
+            (Field::Series, Provenance::Inferred),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(r#"{"series":"inferred"}"#, to_json(&provenances).unwrap());
+        provenances.set(Field::Series, Provenance::Extracted);
+        assert!(provenances.is_empty());
+        assert_eq!(provenances, from_json::<Provenances>("{}").unwrap());
+    }
+
+    #[test]
+    fn test_keep_user_edits() {
+        let mut generator = Generator::new(0x5EED);
+        let mut previous = generator.metadata();
+        previous.edit(Field::Title, |m| m.title = "Edited".to_string());
+        let mut extracted = previous.clone();
+        extracted.title = "Extracted".to_string();
+        extracted.words += 1;
+        extracted.provenance = Provenances::default();
+        extracted.keep_user_edits(&previous);
+        assert_eq!("Edited", extracted.title);
+        assert_eq!(previous.words + 1, extracted.words);
+        assert_eq!(Provenance::UserEdited, extracted.provenance.get(Field::Title));
+    }
+}
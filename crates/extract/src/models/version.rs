@@ -1,4 +1,4 @@
-use super::Metadata;
+use super::{DetectedLanguage, Encoding, Metadata};
 use time::{Date, UtcDateTime};
 
 /// A specific version of an AO3 work, representing the metadata extracted from
@@ -12,6 +12,7 @@ use time::{Date, UtcDateTime};
 /// as the primary key, providing natural deduplication: if two files have identical
 /// decompressed content, they reference the same Version.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Version {
     /// BLAKE3 hash of decompressed HTML (primary key)
     pub hash: String,
@@ -19,6 +20,15 @@ pub struct Version {
     pub length: u64,
     /// CRC32 hash of decompressed HTML
     pub crc32: u32,
+    /// Encoding the HTML was detected as; always UTF-8 unless extracted with
+    /// [`extract_repairing`](crate::extract_repairing).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub encoding: Encoding,
+    /// Language the text was detected to be written in, alongside the
+    /// declared [`language`](Metadata::language); `None` unless detected with
+    /// [`detect_language`](crate::detect_language).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub detected_language: Option<DetectedLanguage>,
     pub metadata: Metadata,
     pub extracted_at: UtcDateTime,
 }
@@ -33,3 +43,32 @@ impl Version {
         self.metadata.last_modified
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Language;
+    use crate::testing::Generator;
+    use serde_json::{from_str as from_json, to_string as to_json};
+
+    #[test]
+    fn test_version_json_round_trip() {
+        let mut generator = Generator::new(3157);
+        for _ in 0..20 {
+            let version = Version {
+                hash: "0".repeat(64),
+                length: 1234,
+                crc32: 0xDEAD_BEEF,
+                encoding: Encoding::Mojibake,
+                detected_language: Some(DetectedLanguage {
+                    language: Language::new("Español"),
+                    confidence: 87,
+                }),
+                metadata: generator.metadata(),
+                extracted_at: UtcDateTime::now(),
+            };
+            let json = to_json(&version).unwrap();
+            assert_eq!(version, from_json::<Version>(&json).unwrap(), "{json}");
+        }
+    }
+}
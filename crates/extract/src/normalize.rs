@@ -0,0 +1,60 @@
+//! Hashing the text of a work, rather than its bytes.
+//!
+//! Downloading the same work twice doesn't always give the same bytes: the
+//! Archive changes its stylesheets and boilerplate over the years (such as
+//! the link back to the work switching to HTTPS), and works saved by other
+//! means are formatted differently. [`normalized_hash`] only hashes the
+//! chapters' text, so downloads of the same chapters hash the same.
+
+use crate::consts::CHAPTERS_SELECTOR;
+use scraper::{Html, Selector};
+use std::sync::LazyLock;
+
+static BODY: LazyLock<Selector> = LazyLock::new(|| Selector::parse("body").expect("valid selector"));
+
+/// Hashes (with BLAKE3) the text of an AO3 download's chapters, ignoring
+/// markup, whitespace and everything outside the chapters (the preface,
+/// afterword and `<head>`).
+///
+/// Documents without a chapters element hash the text of their body.
+/// HTML that isn't UTF-8 should be [decoded](crate::Encoding::decode) first.
+pub fn normalized_hash(html: impl AsRef<[u8]>) -> String {
+    let document = Html::parse_document(&String::from_utf8_lossy(html.as_ref()));
+    let root = document.select(&CHAPTERS_SELECTOR).next().or_else(|| document.select(&BODY).next());
+    let mut hasher = blake3::Hasher::new();
+    let Some(root) = root else {
+        return hasher.finalize().to_string();
+    };
+    // Text nodes are separated, in case markup was all that separated them.
+    let text = root.text().collect::<Vec<_>>().join(" ");
+    for (i, word) in text.split_whitespace().enumerate() {
+        if i > 0 {
+            hasher.update(b" ");
+        }
+        hasher.update(word.as_bytes());
+    }
+    hasher.finalize().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalized_hash() {
+        let old = r#"<html><head><style>body { color: black; }</style></head><body>
+<div id="preface"><p class="message">Posted originally on the Archive of Our Own at http://archiveofourown.org/works/1.</p></div>
+<div id="chapters" class="userstuff"><p>Once upon   a time.</p>
+<p>The end.</p></div></body></html>"#;
+        let new = r#"<html><head></head><body>
+<div id="preface"><p class="message">Posted originally on the Archive of Our Own at https://archiveofourown.org/works/1.</p></div>
+<div id="chapters" class="userstuff"><p>Once upon a time.</p><p>The end.</p></div></body></html>"#;
+        assert_eq!(normalized_hash(old), normalized_hash(new));
+        assert_ne!(normalized_hash(old), normalized_hash(new.replace("The end.", "The End.")));
+        // Without chapters, the body's text.
+        assert_eq!(
+            normalized_hash("<p>Once upon a time. The end.</p>"),
+            normalized_hash("<body><div>Once upon a time.</div> The end.</body>")
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Where extraction spends its time, field by field.
+//!
+//! Pass a [`Stats`] to [`Extractor::metadata_profiled`](crate::Extractor::metadata_profiled)
+//! for every document extracted, then print it: a selector or regex that got
+//! slower shows up against the field it extracts, rather than as a vague
+//! regression of extraction as a whole.
+//!
+//! ```
+//! use rawr_extract::Extractor;
+//! use rawr_extract::profile::Stats;
+//!
+//! # fn example(documents: &[String]) -> rawr_extract::error::Result<()> {
+//! let mut stats = Stats::default();
+//! for html in documents {
+//!     Extractor::from_long_html(html).metadata_profiled(&mut stats)?;
+//! }
+//! println!("{stats}");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::time::{Duration, Instant};
+
+/// How long extracting one field took, over every document it was timed for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timing {
+    /// Times the field was extracted.
+    pub count: u64,
+    pub total: Duration,
+    /// The longest a single extraction took.
+    pub max: Duration,
+}
+impl Timing {
+    /// The average time extracting the field took.
+    pub fn mean(&self) -> Duration {
+        match u32::try_from(self.count) {
+            Ok(0) => Duration::ZERO,
+            Ok(count) => self.total / count,
+            Err(_) => Duration::from_secs_f64(self.total.as_secs_f64() / self.count as f64),
+        }
+    }
+
+    fn add(&mut self, elapsed: Duration) {
+        self.count += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+    }
+}
+
+/// Accumulated [`Timing`]s of each field extracted, by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    timings: BTreeMap<&'static str, Timing>,
+}
+impl Stats {
+    /// Runs `f`, recording how long it took against `field`.
+    pub fn time<T>(&mut self, field: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(field, start.elapsed());
+        result
+    }
+
+    /// Records `field` as having taken `elapsed` to extract.
+    pub fn record(&mut self, field: &'static str, elapsed: Duration) {
+        self.timings.entry(field).or_default().add(elapsed);
+    }
+
+    pub fn get(&self, field: &str) -> Option<&Timing> {
+        self.timings.get(field)
+    }
+
+    /// Fields' timings, by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &Timing)> {
+        self.timings.iter().map(|(field, timing)| (*field, timing))
+    }
+
+    /// Total time spent extracting every field.
+    pub fn total(&self) -> Duration {
+        self.timings.values().map(|t| t.total).sum()
+    }
+
+    /// Adds the timings of `other` (e.g. from another thread) to these.
+    pub fn merge(&mut self, other: &Stats) {
+        for (field, timing) in other.iter() {
+            let merged = self.timings.entry(field).or_default();
+            merged.count += timing.count;
+            merged.total += timing.total;
+            merged.max = merged.max.max(timing.max);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timings.is_empty()
+    }
+}
+impl Display for Stats {
+    /// A table of fields, slowest (in total) first.
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut timings: Vec<_> = self.iter().collect();
+        timings.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(b.0)));
+        let total = self.total().as_secs_f64();
+        write!(f, "{:<16} {:>8} {:>12} {:>12} {:>12} {:>6}", "field", "count", "total", "mean", "max", "share")?;
+        for (field, timing) in timings {
+            let share = match total {
+                0.0 => 0.0,
+                total => timing.total.as_secs_f64() / total * 100.0,
+            };
+            write!(
+                f,
+                "\n{field:<16} {:>8} {:>12} {:>12} {:>12} {share:>5.1}%",
+                timing.count,
+                format!("{:.2?}", timing.total),
+                format!("{:.2?}", timing.mean()),
+                format!("{:.2?}", timing.max),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accumulates() {
+        let mut stats = Stats::default();
+        stats.record("title", Duration::from_micros(10));
+        stats.record("title", Duration::from_micros(30));
+        assert_eq!(42, stats.time("words", || 42));
+        let title = stats.get("title").unwrap();
+        assert_eq!((2, Duration::from_micros(40), Duration::from_micros(30)), (title.count, title.total, title.max));
+        assert_eq!(Duration::from_micros(20), title.mean());
+
+        let mut merged = Stats::default();
+        merged.record("title", Duration::from_micros(50));
+        merged.merge(&stats);
+        assert_eq!(3, merged.get("title").unwrap().count);
+        assert_eq!(Duration::from_micros(50), merged.get("title").unwrap().max);
+        assert_eq!(1, merged.get("words").unwrap().count);
+
+        let table = merged.to_string();
+        assert_eq!(3, table.lines().count());
+        assert!(table.lines().nth(1).unwrap().starts_with("title"));
+    }
+}
@@ -0,0 +1,243 @@
+//! Regenerating AO3-like HTML from cached metadata.
+//!
+//! When the only copies of a work's download are lost, but its metadata is
+//! still in the cache (an orphaned version), [`reconstruct()`] produces a
+//! minimal stand-in document: the Archive's preface (rating, tags, stats,
+//! summary, ...) without any chapter text. Extracting it yields the same
+//! metadata, so the work can still be organized, browsed and rendered.
+//!
+//! Reconstructed documents are clearly marked, both for readers (a notice in
+//! place of the chapters) and for programs ([`is_reconstructed()`]).
+
+use crate::models::{Metadata, TagKind, Warning};
+use memchr::memmem;
+use std::fmt::Write;
+
+/// Content of the `<meta name="generator">` tag that marks a document as
+/// reconstructed.
+pub const GENERATOR: &str = "rawr (reconstructed from cached metadata)";
+
+/// Generates a minimal AO3-like HTML document from cached metadata, marked as
+/// reconstructed.
+///
+/// Extracting the result yields the same metadata (for any metadata the
+/// Archive could have produced), but its content hash will differ from the
+/// original download's.
+///
+/// # Example
+///
+/// ```
+/// use rawr_extract::reconstruct::{is_reconstructed, reconstruct};
+/// # use rawr_extract::models::*;
+/// # let metadata = Metadata {
+/// #     work_id: 123,
+/// #     title: "Lost Work".to_string(),
+/// #     authors: vec![],
+/// #     fandoms: vec![Fandom::from("Original Work".to_string())],
+/// #     series: vec![],
+/// #     chapters: Chapters { written: 1, total: Some(1) },
+/// #     words: 1000,
+/// #     rating: None,
+/// #     warnings: vec![Warning::NoWarningsApply],
+/// #     tags: vec![],
+/// #     summary: None,
+/// #     language: Language::new("English"),
+/// #     published: time::Date::from_calendar_date(2024, time::Month::January, 1).unwrap(),
+/// #     last_modified: time::Date::from_calendar_date(2024, time::Month::January, 1).unwrap(),
+/// #     provenance: Default::default(),
+/// #     work_skin: None,
+/// # };
+///
+/// let html = reconstruct(&metadata);
+/// assert!(is_reconstructed(&html));
+/// assert_eq!(metadata, rawr_extract::extract(&html).unwrap().metadata);
+/// ```
+pub fn reconstruct(metadata: &Metadata) -> String {
+    let head = format!("\n<meta name=\"generator\" content=\"{GENERATOR}\"/>");
+    let chapters = format!(
+        r#"<p class="rawr-reconstructed"><b>Reconstructed</b>: the original download of this work was lost, and this page was regenerated from cached metadata. It contains no chapter text. The work may still be available at <a href="https://archiveofourown.org/works/{0}">https://archiveofourown.org/works/{0}</a>.</p>"#,
+        metadata.work_id,
+    );
+    document(metadata, &head, &chapters)
+}
+
+/// Whether a document was generated by [`reconstruct()`].
+///
+/// Only the start of the document (where the marker is) is searched.
+pub fn is_reconstructed(html: impl AsRef<[u8]>) -> bool {
+    let html = html.as_ref();
+    let head = &html[..html.len().min(4096)];
+    memmem::find(head, GENERATOR.as_bytes()).is_some()
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Renders metadata as an AO3-like HTML download, mirroring the structure of
+/// the Archive's own HTML exports, with extra markup in the `<head>` and
+/// `chapters` (already-escaped HTML).
+pub(crate) fn document(m: &Metadata, head: &str, chapters: &str) -> String {
+    let mut html = String::new();
+    let links = |base: &str, names: &mut dyn Iterator<Item = &str>| {
+        names
+            .map(|name| format!(r#"<a rel="tag" href="https://archiveofourown.org/tags/{base}">{}</a>"#, escape(name)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let tags = |kind: TagKind| m.tags.iter().filter(move |t| t.kind == kind).map(|t| t.name.as_str());
+    let mut dl = String::new();
+    let mut entry = |label: &str, value: String| {
+        if !value.is_empty() {
+            _ = write!(dl, "\n<dt>{label}:</dt>\n<dd>{value}</dd>");
+        }
+    };
+    if let Some(rating) = m.rating {
+        entry("Rating", links("rating", &mut std::iter::once(rating.as_str())));
+    }
+    entry("Archive Warning", links("warning", &mut m.warnings.iter().map(Warning::as_str)));
+    entry("Fandom", links("fandom", &mut m.fandoms.iter().map(|f| f.name.as_str())));
+    entry("Relationship", links("relationship", &mut tags(TagKind::Relationship)));
+    entry("Character", links("character", &mut tags(TagKind::Character)));
+    entry("Additional Tags", links("freeform", &mut tags(TagKind::Freeform)));
+    entry("Language", escape(&m.language.name));
+    let series = m
+        .series
+        .iter()
+        .map(|s| {
+            format!(
+                r#"Part {} of <a href="https://archiveofourown.org/series/{}">{}</a>"#,
+                thousands(s.position.into()),
+                s.id,
+                escape(&s.name)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    entry("Series", series);
+    let modified = match (m.last_modified == m.published, m.chapters.is_complete()) {
+        (true, _) => String::new(),
+        (false, true) => format!("\n  Completed: {}", m.last_modified),
+        (false, false) => format!("\n  Updated: {}", m.last_modified),
+    };
+    let total = m.chapters.total.map_or("?".to_string(), |t| thousands(t.into()));
+    entry(
+        "Stats",
+        format!(
+            "\n  Published: {}{modified}\n  Words: {}\n  Chapters: {}/{total}\n",
+            m.published,
+            thousands(m.words),
+            thousands(m.chapters.written.into())
+        ),
+    );
+
+    let byline = m
+        .authors
+        .iter()
+        .map(|a| {
+            let pseud = a.pseudonym.as_deref().unwrap_or(&a.username);
+            format!(
+                r#"<a rel="author" href="https://archiveofourown.org/users/{}/pseuds/{}">{}</a>"#,
+                escape(&a.username),
+                escape(pseud),
+                escape(&a.to_string())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let byline = match byline.is_empty() {
+        true => "Anonymous".to_string(),
+        false => byline,
+    };
+    let summary = match &m.summary {
+        Some(s) => format!("\n<p>Summary</p>\n<blockquote class=\"userstuff\"><p>{}</p></blockquote>", escape(s)),
+        None => String::new(),
+    };
+    let title = escape(&m.title);
+    let work = m.work_id;
+    // Style elements end at the first `</style`, which CSS can't contain.
+    let skin = m
+        .work_skin
+        .as_ref()
+        .map_or(String::new(), |css| format!("\n<style type=\"text/css\">{}</style>", css.replace("</", "<\\/")));
+    _ = write!(
+        html,
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="UTF-8"/>
+<title>{title} - Archive of Our Own</title>{skin}{head}
+</head>
+<body>
+<div id="preface">
+<p class="message">
+<b>Preface</b>
+<br/>
+Posted originally on the <a href="http://archiveofourown.org/">Archive of Our Own</a> at <a href="https://archiveofourown.org/works/{work}">https://archiveofourown.org/works/{work}</a>.
+</p>
+<div class="meta">
+<dl class="tags">{dl}
+</dl>
+<h1>{title}</h1>
+<div class="byline">by {byline}</div>{summary}
+</div>
+</div>
+<div id="chapters" class="userstuff">
+{chapters}
+</div>
+</body>
+</html>
+"#
+    );
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract;
+    use crate::models::Author;
+    use crate::testing::Generator;
+
+    #[test]
+    fn test_reconstruct_round_trip() {
+        for (i, metadata) in (0..100).map(|seed| Generator::new(seed).metadata()).enumerate() {
+            let html = reconstruct(&metadata);
+            assert!(is_reconstructed(&html), "document {i}");
+            assert_eq!(metadata, extract(&html).unwrap().metadata, "document {i}");
+        }
+        assert!(!is_reconstructed(crate::testing::render(&Generator::new(1).metadata())));
+    }
+
+    #[test]
+    fn test_escape_byline() {
+        let mut metadata = Generator::new(3154).metadata();
+        metadata.authors = vec![Author::new("x\"><script>", Some("\"Quoted\" & Co"))];
+        let html = reconstruct(&metadata);
+        assert!(!html.contains(r#"x"><script>"#) && !html.contains(r#""Quoted" & Co"#));
+        assert_eq!(metadata, extract(&html).unwrap().metadata);
+    }
+
+    #[test]
+    fn test_work_skin() {
+        let mut metadata = Generator::new(3216).metadata();
+        assert_eq!(None, extract(reconstruct(&metadata)).unwrap().metadata.work_skin);
+        // The Archive's own stylesheet isn't a work skin.
+        metadata.work_skin = Some("#workskin .texting { font-family: monospace; }".to_string());
+        let html =
+            reconstruct(&metadata).replace("<head>", "<head>\n<style type=\"text/css\">body { color: black; }</style>");
+        assert_eq!(metadata, extract(&html).unwrap().metadata);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::extract;
+use std::io::{ErrorKind as IoErrorKind, Result as IoResult};
+use std::path::{Path, PathBuf};
+
+/// Environment variable that, when set, makes [`Sample::assert_golden`]
+/// (re)write golden files instead of comparing against them.
+pub const BLESS_ENV: &str = "RAWR_BLESS";
+
+/// A directory of real (uncompressed) AO3 downloads used as regression tests.
+///
+/// Every `*.html` file in the directory is a [`Sample`]. Next to each sample,
+/// a `*.golden` file holds a snapshot of what extracting it produced the last
+/// time it was reviewed: either the metadata, or the error.
+///
+/// # Example
+///
+/// ```no_run
+/// use rawr_extract::testing::Corpus;
+///
+/// #[test]
+/// fn golden_corpus() {
+///     for sample in Corpus::load("tests/fixtures").unwrap() {
+///         sample.assert_golden();
+///     }
+/// }
+/// ```
+///
+/// Run the tests with `RAWR_BLESS=1` to create golden files for new samples
+/// (and overwrite existing ones), then review the diff before committing.
+#[derive(Debug)]
+pub struct Corpus {
+    samples: Vec<Sample>,
+}
+impl Corpus {
+    /// Loads every `*.html` file in `dir` (not recursive), ordered by name.
+    pub fn load(dir: impl AsRef<Path>) -> IoResult<Self> {
+        let mut samples = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "html") && path.is_file() {
+                samples.push(Sample { html: std::fs::read(&path)?, path });
+            }
+        }
+        samples.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Self { samples })
+    }
+
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+}
+impl IntoIterator for Corpus {
+    type Item = Sample;
+    type IntoIter = std::vec::IntoIter<Sample>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.samples.into_iter()
+    }
+}
+
+/// A single document from a [`Corpus`].
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub path: PathBuf,
+    pub html: Vec<u8>,
+}
+impl Sample {
+    /// Path to the sample's golden file (its path, with a `.golden` extension).
+    pub fn golden_path(&self) -> PathBuf {
+        self.path.with_extension("golden")
+    }
+
+    /// Extracts the sample, rendered as the text stored in golden files.
+    pub fn snapshot(&self) -> String {
+        match extract(&self.html) {
+            Ok(version) => format!("{:#?}\n", version.metadata),
+            Err(e) => format!("error: {}\n", *e),
+        }
+    }
+
+    /// Asserts that extraction still matches the golden file, or writes the
+    /// golden file if the [`BLESS_ENV`] environment variable is set.
+    ///
+    /// # Panics
+    /// Panics if the snapshot differs from the golden file, the golden file
+    /// is missing, or it can't be read or written.
+    #[track_caller]
+    pub fn assert_golden(&self) {
+        let golden = self.golden_path();
+        let actual = self.snapshot();
+        if std::env::var_os(BLESS_ENV).is_some() {
+            std::fs::write(&golden, actual).unwrap_or_else(|e| panic!("writing {}: {e}", golden.display()));
+            return;
+        }
+        match std::fs::read_to_string(&golden) {
+            Ok(expected) => assert_eq!(expected, actual, "extraction of {} changed", self.path.display()),
+            Err(e) if e.kind() == IoErrorKind::NotFound => {
+                panic!("{} has no golden file; run with {BLESS_ENV}=1 to create it", self.path.display())
+            },
+            Err(e) => panic!("reading {}: {e}", golden.display()),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+/// A kind of damage that real-world downloads suffer.
+///
+/// Corruptions are split by whether a robust extractor should be unaffected
+/// by them (see [`preserves_metadata()`](Self::preserves_metadata)): for the
+/// rest, the only requirement is that extraction fails gracefully, or
+/// succeeds with degraded values, instead of panicking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corruption {
+    /// A UTF-8 byte order mark was prepended (e.g. by a Windows text editor).
+    Utf8Bom,
+    /// Line endings were converted to `\r\n`.
+    CrLf,
+    /// Newlines and indentation were stripped, as by an HTML minifier.
+    Minified,
+    /// The file was padded with NUL bytes (e.g. a preallocated download that
+    /// never finished).
+    NulPadding(usize),
+    /// The download was cut off after this many bytes.
+    Truncated(usize),
+    /// Non-ASCII text was re-encoded as Latin-1, replacing characters that
+    /// don't fit with `?`; the result is not valid UTF-8.
+    Latin1,
+    /// UTF-8 was misread as Latin-1 and encoded to UTF-8 again (`é` → `Ã©`).
+    Mojibake,
+}
+impl Corruption {
+    /// Every kind of corruption, with representative parameters.
+    pub const ALL: &[Self] = &[
+        Self::Utf8Bom,
+        Self::CrLf,
+        Self::Minified,
+        Self::NulPadding(4096),
+        Self::Truncated(2048),
+        Self::Latin1,
+        Self::Mojibake,
+    ];
+
+    /// Whether extracting a corrupted document should yield the same metadata
+    /// as the original.
+    pub fn preserves_metadata(&self) -> bool {
+        matches!(self, Self::Utf8Bom | Self::CrLf | Self::Minified | Self::NulPadding(_))
+    }
+
+    /// Applies the corruption to a document.
+    pub fn apply(&self, html: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Utf8Bom => [&[0xEF, 0xBB, 0xBF], html].concat(),
+            Self::CrLf => html.iter().flat_map(|&b| if b == b'\n' { vec![b'\r', b'\n'] } else { vec![b] }).collect(),
+            Self::Minified => {
+                let mut out = Vec::with_capacity(html.len());
+                let mut line_start = false;
+                for &b in html {
+                    match b {
+                        b'\n' => line_start = true,
+                        b' ' | b'\t' if line_start => (),
+                        _ => {
+                            line_start = false;
+                            out.push(b);
+                        },
+                    }
+                }
+                out
+            },
+            Self::NulPadding(n) => [html, &vec![0; *n]].concat(),
+            Self::Truncated(n) => html[..html.len().min(*n)].to_vec(),
+            Self::Latin1 => {
+                String::from_utf8_lossy(html).chars().map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?')).collect()
+            },
+            Self::Mojibake => html.iter().map(|&b| char::from(b)).collect::<String>().into_bytes(),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::models::{
+    Author, Chapters, Fandom, Language, Metadata, Provenances, Rating, SeriesPosition, Tag, TagKind, Warning,
+};
+use crate::reconstruct::{document, escape};
+use time::{Date, Duration, Month};
+
+const WORDS: &[&str] = &[
+    "after", "ashes", "between", "bright", "café", "comes", "dawn", "dreams", "ember", "falling", "ghost", "glass",
+    "harbour", "hollow", "iron", "light", "little", "moon", "night", "ocean", "paper", "quiet", "river", "rose",
+    "salt", "silver", "small", "stars", "storm", "summer", "tea", "things", "tide", "under", "winter", "wolves",
+];
+const FANDOMS: &[&str] = &[
+    "Original Work",
+    "Harry Potter - J. K. Rowling",
+    "Sherlock (TV)",
+    "Good Omens (TV)",
+    "僕のヒーローアカデミア | Boku no Hero Academia",
+    "Marvel Cinematic Universe",
+    "Star Wars - All Media Types",
+    "Pride & Prejudice - Jane Austen",
+];
+const NAMES: &[&str] = &[
+    "Aziraphale",
+    "Crowley",
+    "Elizabeth Bennet",
+    "Fitzwilliam Darcy",
+    "John Watson",
+    "Sherlock Holmes",
+    "Rey",
+    "Ben Solo",
+    "Midoriya Izuku",
+    "Bakugou Katsuki",
+    "Original Female Character(s)",
+    "Zoë",
+];
+const FREEFORMS: &[&str] = &[
+    "Fluff",
+    "Angst",
+    "Hurt/Comfort",
+    "Slow Burn",
+    "Alternate Universe - Coffee Shops & Cafés",
+    "Canon Divergence",
+    "Found Family",
+    "Happy Ending",
+    "Enemies to Lovers",
+    "Getting Together",
+    "Post-Canon",
+    "Whump",
+];
+const LANGUAGES: &[&str] = &["en", "en", "en", "es", "fr", "de", "ja", "ru", "zh", "ko", "it"];
+const RATINGS: &[Rating] = &[
+    Rating::GeneralAudiences,
+    Rating::TeenAndUp,
+    Rating::Mature,
+    Rating::Explicit,
+    Rating::NotRated,
+];
+const WARNINGS: &[Warning] = &[
+    Warning::NoWarningsApply,
+    Warning::CreatorChoseNotToUse,
+    Warning::GraphicViolence,
+    Warning::MajorCharacterDeath,
+    Warning::Underage,
+    Warning::NonCon,
+];
+
+/// A generated document, and the metadata extraction must produce from it.
+#[derive(Debug, Clone)]
+pub struct Generated {
+    pub html: String,
+    pub expected: Metadata,
+}
+
+/// Deterministic generator of random, valid AO3-like documents.
+///
+/// The same seed always produces the same sequence of documents, so a failing
+/// case can be reproduced from the seed alone. Documents vary every field the
+/// extractor reads (including anonymous works, unknown chapter totals,
+/// multiple series, HTML entities and non-ASCII text), and some are padded
+/// with chapter text well beyond the size at which extraction truncates.
+///
+/// Iterating a generator yields an infinite stream of [`Generated`] documents.
+pub struct Generator {
+    state: u64,
+}
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Generates the next document.
+    pub fn generate(&mut self) -> Generated {
+        let expected = self.metadata();
+        let mut html = render(&expected);
+        if self.chance(25) {
+            // Long works: make sure the preface survives truncation.
+            let paragraphs = self.range(50, 500);
+            let body = (0..paragraphs).map(|_| format!("<p>{}.</p>\n", self.words(8, 40))).collect::<String>();
+            html = html.replace("</div>\n</body>", &format!("{body}</div>\n</body>"));
+        }
+        Generated { html, expected }
+    }
+
+    /// Generates random (but valid) work metadata.
+    pub fn metadata(&mut self) -> Metadata {
+        let mut authors: Vec<Author> = (0..self.range(0, 3))
+            .map(|_| {
+                let username = format!("{}{}", self.pick(WORDS).replace('é', "e"), self.range(0, 999));
+                match self.chance(50) {
+                    true => Author::new(username, Some(capitalize(&self.words(1, 2)))),
+                    false => Author::new(username, None::<String>),
+                }
+            })
+            .collect();
+        // Mirror the extractor, which sorts and deduplicates authors.
+        authors.sort();
+        authors.dedup();
+        let written = self.range(1, 60) as u32;
+        let total = match self.chance(30) {
+            true => None,
+            false => Some(written + self.range(0, 10) as u32),
+        };
+        // Series IDs and names must be unique, and no name may prefix another.
+        let series = (0..self.range(0, 2))
+            .map(|i| SeriesPosition {
+                id: self.range(1, 4_999_999) * 2 + i,
+                name: format!("{} Saga {i}", capitalize(&self.words(1, 3))),
+                position: self.range(1, 30) as u32,
+            })
+            .collect();
+        let rating = match self.chance(10) {
+            true => None,
+            false => Some(*self.pick(RATINGS)),
+        };
+        let mut tags = Vec::new();
+        for i in self.distinct(NAMES.len(), 0, 3) {
+            let other = NAMES[(i + 1) % NAMES.len()];
+            tags.push(Tag {
+                name: format!("{}/{other}", NAMES[i]),
+                kind: TagKind::Relationship,
+            });
+        }
+        for i in self.distinct(NAMES.len(), 0, 5) {
+            tags.push(Tag {
+                name: NAMES[i].to_string(),
+                kind: TagKind::Character,
+            });
+        }
+        for i in self.distinct(FREEFORMS.len(), 0, 8) {
+            tags.push(Tag {
+                name: FREEFORMS[i].to_string(),
+                kind: TagKind::Freeform,
+            });
+        }
+        let published = Date::from_calendar_date(2009, Month::January, 1).expect("valid date")
+            + Duration::days(self.range(0, 5000) as i64);
+        let last_modified = match self.chance(40) {
+            true => published,
+            false => published + Duration::days(self.range(1, 1000) as i64),
+        };
+        let language = *self.pick(LANGUAGES);
+        Metadata {
+            work_id: self.range(1, 99_999_999),
+            title: capitalize(&self.words(1, 6)),
+            authors,
+            fandoms: self
+                .distinct(FANDOMS.len(), 1, 3)
+                .into_iter()
+                .map(|i| Fandom::from(FANDOMS[i].to_string()))
+                .collect(),
+            series,
+            chapters: Chapters { written, total },
+            words: self.range(100, 500_000),
+            rating,
+            warnings: self.distinct(WARNINGS.len(), 1, 2).into_iter().map(|i| WARNINGS[i]).collect(),
+            tags,
+            summary: match self.chance(30) {
+                true => None,
+                false => Some(format!("{}.", capitalize(&self.words(5, 30)))),
+            },
+            language: Language::new(Language::iso_to_name(language).unwrap_or("English")),
+            published,
+            last_modified,
+            provenance: Provenances::default(),
+            work_skin: None,
+        }
+    }
+
+    /* ========================== *\
+    |  SplitMix64 and Helpers      |
+    \* ========================== */
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Random number within `min..=max`.
+    fn range(&mut self, min: u64, max: u64) -> u64 {
+        min + self.next_u64() % (max - min + 1)
+    }
+
+    /// Returns `true` with a probability of `percent`%.
+    fn chance(&mut self, percent: u64) -> bool {
+        self.range(1, 100) <= percent
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0, items.len() as u64 - 1) as usize]
+    }
+
+    /// Between `min` and `max` distinct indexes into a list of length `len`.
+    fn distinct(&mut self, len: usize, min: u64, max: u64) -> Vec<usize> {
+        let count = self.range(min, max.min(len as u64));
+        let mut picked = Vec::new();
+        while (picked.len() as u64) < count {
+            let i = self.range(0, len as u64 - 1) as usize;
+            if !picked.contains(&i) {
+                picked.push(i);
+            }
+        }
+        picked
+    }
+
+    fn words(&mut self, min: u64, max: u64) -> String {
+        (0..self.range(min, max)).map(|_| *self.pick(WORDS)).collect::<Vec<_>>().join(" ")
+    }
+}
+impl Iterator for Generator {
+    type Item = Generated;
+    fn next(&mut self) -> Option<Generated> {
+        Some(self.generate())
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Renders metadata as an AO3-like HTML download, mirroring the structure of
+/// the Archive's own HTML exports.
+///
+/// Extracting the result yields the same metadata, provided it's the kind the
+/// Archive could produce: see [`Generator`] for what that entails (e.g.
+/// authors sorted and deduplicated, tag names unique within their kind).
+///
+/// Unlike [`reconstruct()`](crate::reconstruct::reconstruct), the document
+/// isn't marked as reconstructed.
+pub fn render(m: &Metadata) -> String {
+    document(m, "", &format!("<p>{}</p>", escape(&m.title)))
+}
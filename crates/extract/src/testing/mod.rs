@@ -0,0 +1,89 @@
+//! Test harness for extractors: golden corpora, generated documents and
+//! real-world corruptions.
+//!
+//! Enabled by the `testing` feature, which is intended for dev dependencies
+//! (of this crate, downstream crates, or anyone validating their own AO3-like
+//! dialect):
+//!
+//! - [`Corpus`] loads a directory of real downloads and checks each one
+//!   against a committed "golden" snapshot of its extracted metadata.
+//! - [`Generator`] deterministically produces random, valid AO3-like
+//!   documents alongside the [`Metadata`](crate::models::Metadata) that
+//!   extracting them must yield.
+//! - [`Corruption`] mutates documents the way downloads get damaged in the
+//!   wild (truncation, encoding mishaps, padding, ...).
+//!
+//! # Example
+//!
+//! ```
+//! use rawr_extract::extract;
+//! use rawr_extract::testing::{Corruption, Generator};
+//!
+//! for sample in Generator::new(42).take(10) {
+//!     let version = extract(&sample.html).unwrap();
+//!     assert_eq!(version.metadata, sample.expected);
+//!     // Corruptions that only change the encoding envelope must not affect extraction.
+//!     let bommed = Corruption::Utf8Bom.apply(sample.html.as_bytes());
+//!     assert_eq!(extract(&bommed).unwrap().metadata, sample.expected);
+//! }
+//! ```
+
+mod corpus;
+mod corrupt;
+mod generate;
+
+pub use self::corpus::{BLESS_ENV, Corpus, Sample};
+pub use self::corrupt::Corruption;
+pub use self::generate::{Generated, Generator, render};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Encoding;
+    use crate::{extract, extract_repairing};
+
+    #[test]
+    fn test_generated_documents_round_trip() {
+        for (i, sample) in Generator::new(0x5EED).take(200).enumerate() {
+            let version = extract(&sample.html).unwrap_or_else(|e| panic!("document {i} failed to extract: {e}"));
+            assert_eq!(version.metadata, sample.expected, "document {i}");
+        }
+    }
+
+    #[test]
+    fn test_generator_is_deterministic() {
+        let a: Vec<_> = Generator::new(7).take(5).map(|g| g.html).collect();
+        let b: Vec<_> = Generator::new(7).take(5).map(|g| g.html).collect();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_corruptions() {
+        for (i, sample) in Generator::new(0xBAD).take(50).enumerate() {
+            for corruption in Corruption::ALL {
+                let html = corruption.apply(sample.html.as_bytes());
+                let result = extract(&html);
+                if corruption.preserves_metadata() {
+                    let metadata = result.unwrap_or_else(|e| panic!("document {i} with {corruption:?}: {e}")).metadata;
+                    assert_eq!(metadata, sample.expected, "document {i} with {corruption:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_repaired_corruptions() {
+        for (i, sample) in Generator::new(0xBAD).take(50).enumerate() {
+            let version = extract_repairing(Corruption::Mojibake.apply(sample.html.as_bytes())).unwrap();
+            assert_eq!(version.metadata, sample.expected, "document {i}");
+            // Latin-1 can only hold (and so repair) some of the characters.
+            if sample.html.chars().all(|c| u32::from(c) < 0x80 || (0xA0..=0xFF).contains(&u32::from(c))) {
+                let version = extract_repairing(Corruption::Latin1.apply(sample.html.as_bytes())).unwrap();
+                assert_eq!(version.metadata, sample.expected, "document {i}");
+            }
+            let version = extract_repairing(&sample.html).unwrap();
+            assert_eq!(version.metadata, sample.expected, "document {i}");
+            assert_eq!(Encoding::Utf8, version.encoding, "document {i}");
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! Grading the health of a download beyond whether it can be extracted.
+//!
+//! [`is_valid`](crate::is_valid) (and extraction itself) only looks at the
+//! preface, so a download cut off halfway through its third chapter, or one
+//! garbled by a text editor, passes just as well as a pristine one.
+//! [`validate_deep`] reads the whole document and reports everything wrong
+//! with it, so damaged files can be found (and downloaded again) before the
+//! copy on AO3 is gone.
+
+use crate::consts;
+use crate::encoding::Encoding;
+use crate::extract::Datalist;
+use scraper::Html;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// The closing tag every complete download ends with.
+const END_TAG: &[u8] = b"</html>";
+
+/// A part of an AO3 download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Section {
+    /// The `#preface`, holding the work's metadata.
+    Preface,
+    /// The link to the work on AO3, in the preface.
+    WorkLink,
+    /// The work's title, in the preface.
+    Title,
+    /// The list of tags (rating, fandoms, and so on), in the preface.
+    Tags,
+    /// The work's stats (dates, words and chapters), in the list of tags.
+    Stats,
+    /// The `#chapters`, holding the work's body.
+    Chapters,
+}
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(match self {
+            Self::Preface => "preface",
+            Self::WorkLink => "work link",
+            Self::Title => "title",
+            Self::Tags => "tags",
+            Self::Stats => "stats",
+            Self::Chapters => "chapters",
+        })
+    }
+}
+
+/// Something wrong with a download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Issue {
+    /// A section every download has is missing.
+    MissingSection(Section),
+    /// The document doesn't end with `</html>`: the download was cut off.
+    Truncated,
+    /// The number of chapter headings in the body doesn't match the number
+    /// of chapters the stats declare (works with a single chapter have no
+    /// heading).
+    ChapterMarkers { declared: u32, found: u32 },
+    /// The document isn't UTF-8, and has to be repaired to be read.
+    Encoding(Encoding),
+    /// The document has this many characters that couldn't be decoded (even
+    /// once repaired), which read as `U+FFFD`.
+    ReplacementCharacters(u64),
+    /// The document has this many NUL bytes, which HTML never does (such as
+    /// the padding of a preallocated download that never finished).
+    NulBytes(u64),
+}
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::MissingSection(section) => write!(f, "missing {section}"),
+            Self::Truncated => f.write_str("truncated"),
+            Self::ChapterMarkers { declared, found } => {
+                write!(f, "{found} chapter headings for {declared} chapters")
+            },
+            Self::Encoding(encoding) => write!(f, "encoded as {encoding}"),
+            Self::ReplacementCharacters(count) => write!(f, "{count} undecodable characters"),
+            Self::NulBytes(count) => write!(f, "{count} NUL bytes"),
+        }
+    }
+}
+
+/// What [`validate_deep`] found wrong with a download.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Validation {
+    /// Every issue found, in the order of [`Issue`]'s variants.
+    pub issues: Vec<Issue>,
+}
+impl Validation {
+    /// Whether nothing is wrong with the download.
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Whether metadata can still be extracted from the download, whatever
+    /// else is wrong with it.
+    pub fn is_extractable(&self) -> bool {
+        !self.issues.iter().any(|issue| {
+            matches!(
+                issue,
+                Issue::MissingSection(Section::Preface | Section::WorkLink | Section::Title | Section::Stats)
+            )
+        })
+    }
+
+    /// Whether the download has lost part of the work (rather than being
+    /// readable, once repaired).
+    pub fn is_damaged(&self) -> bool {
+        self.issues.iter().any(|issue| {
+            matches!(
+                issue,
+                Issue::MissingSection(_)
+                    | Issue::Truncated
+                    | Issue::ChapterMarkers { .. }
+                    | Issue::ReplacementCharacters(_)
+            )
+        })
+    }
+}
+impl Display for Validation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if self.is_healthy() {
+            return f.write_str("healthy");
+        }
+        let issues: Vec<_> = self.issues.iter().map(Issue::to_string).collect();
+        f.write_str(&issues.join(", "))
+    }
+}
+
+/// Checks a whole download for damage that extraction doesn't notice:
+/// missing sections, a truncated body, chapter headings that don't match the
+/// declared number of chapters, and text that isn't (or doesn't decode as)
+/// UTF-8.
+///
+/// Unlike [`is_valid`](crate::is_valid), the whole document is parsed, so
+/// this is as slow as reading the work's body.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_extract::{Issue, validate_deep};
+///
+/// let validation = validate_deep(b"<html><body><p>Not a work</p>");
+/// assert!(!validation.is_healthy());
+/// assert!(validation.issues.contains(&Issue::Truncated));
+/// ```
+pub fn validate_deep(html: impl AsRef<[u8]>) -> Validation {
+    let html = html.as_ref();
+    let mut issues = Vec::new();
+
+    let encoding = Encoding::detect(html);
+    let decoded = encoding.decode(html);
+    let text = String::from_utf8_lossy(&decoded);
+    let document = Html::parse_document(&text);
+
+    let has = |selector| document.select(selector).next().is_some();
+    let preface = [
+        (Section::Preface, has(&consts::PREFACE_SELECTOR)),
+        (Section::WorkLink, has(&consts::WORK_URL_SELECTOR)),
+        (Section::Title, has(&consts::TITLE_SELECTOR)),
+        (Section::Tags, has(&consts::TAGS_DL_SELECTOR)),
+    ];
+    issues.extend(preface.into_iter().filter(|(_, found)| !found).map(|(section, _)| Issue::MissingSection(section)));
+    let declared = Datalist::new(&document).stats().and_then(|stats| stats.chapters()).ok();
+    if declared.is_none() {
+        issues.push(Issue::MissingSection(Section::Stats));
+    }
+    let body = document.select(&consts::CHAPTERS_SELECTOR).next();
+    if body.is_none() {
+        issues.push(Issue::MissingSection(Section::Chapters));
+    }
+
+    let end = html.iter().rposition(|&b| !b.is_ascii_whitespace() && b != 0).map_or(0, |i| i + 1);
+    if !html[end.saturating_sub(END_TAG.len())..end].eq_ignore_ascii_case(END_TAG) {
+        issues.push(Issue::Truncated);
+    }
+
+    if let (Some(declared), Some(body)) = (declared, body) {
+        let found = u32::try_from(body.select(&consts::CHAPTER_HEADING_SELECTOR).count()).unwrap_or(u32::MAX);
+        if (found > 0 || declared.written > 1) && found != declared.written {
+            issues.push(Issue::ChapterMarkers { declared: declared.written, found });
+        }
+    }
+
+    if encoding.needs_repair() {
+        issues.push(Issue::Encoding(encoding));
+    }
+    let replaced = text.chars().filter(|&c| c == char::REPLACEMENT_CHARACTER).count() as u64;
+    if replaced > 0 {
+        issues.push(Issue::ReplacementCharacters(replaced));
+    }
+    let nuls = memchr::memchr_iter(0, html).count() as u64;
+    if nuls > 0 {
+        issues.push(Issue::NulBytes(nuls));
+    }
+
+    Validation { issues }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reconstruct::document;
+    use crate::testing::{Corruption, Generator};
+    use rstest::rstest;
+
+    /// A download of a generated work, with a heading for each chapter (when
+    /// it has more than one).
+    fn download(seed: u64) -> Vec<u8> {
+        let metadata = Generator::new(seed).metadata();
+        let chapters = match metadata.chapters.written {
+            1 => "<div class=\"userstuff\"><p>Once upon a time.</p></div>".to_string(),
+            written => (1..=written)
+                .map(|n| {
+                    format!(
+                        "<div class=\"chapter\"><div class=\"meta group\"><h2 class=\"heading\">Chapter {n}</h2></div>\
+                         <div class=\"userstuff\"><p>Once upon a time.</p></div></div>"
+                    )
+                })
+                .collect(),
+        };
+        document(&metadata, "", &chapters).into_bytes()
+    }
+
+    #[test]
+    fn test_healthy() {
+        for seed in 0..20 {
+            let validation = validate_deep(download(seed));
+            assert!(validation.is_healthy(), "seed {seed}: {validation}");
+        }
+    }
+
+    #[rstest]
+    #[case::truncated(Corruption::Truncated(2048), Issue::Truncated)]
+    #[case::nul_padding(Corruption::NulPadding(16), Issue::NulBytes(16))]
+    #[case::latin1(Corruption::Latin1, Issue::Encoding(Encoding::Windows1252))]
+    #[case::mojibake(Corruption::Mojibake, Issue::Encoding(Encoding::Mojibake))]
+    fn test_corrupted(#[case] corruption: Corruption, #[case] issue: Issue) {
+        // A work with non-ASCII text, for the encodings to garble.
+        let html = String::from_utf8(download(3)).unwrap().replace("Once upon a time", "Il était une fois");
+        let validation = validate_deep(corruption.apply(html.as_bytes()));
+        assert!(validation.issues.contains(&issue), "{validation}");
+        assert!(validation.is_extractable());
+    }
+
+    #[test]
+    fn test_preserving_corruptions() {
+        for corruption in [Corruption::Utf8Bom, Corruption::CrLf, Corruption::Minified] {
+            let validation = validate_deep(corruption.apply(&download(5)));
+            assert!(validation.is_healthy(), "{corruption:?}: {validation}");
+        }
+    }
+
+    #[test]
+    fn test_chapter_markers() {
+        let mut metadata = Generator::new(7).metadata();
+        metadata.chapters.written = 3;
+        let chapters = "<div class=\"meta group\"><h2 class=\"heading\">Chapter 1</h2></div><p>Text</p>";
+        let validation = validate_deep(document(&metadata, "", chapters));
+        assert_eq!(vec![Issue::ChapterMarkers { declared: 3, found: 1 }], validation.issues);
+        assert!(validation.is_damaged());
+        assert!(validation.is_extractable());
+    }
+
+    #[test]
+    fn test_missing_sections() {
+        let validation = validate_deep(b"<html><body><div id=\"chapters\"><p>Text</p></div></body></html>");
+        assert_eq!(
+            vec![
+                Issue::MissingSection(Section::Preface),
+                Issue::MissingSection(Section::WorkLink),
+                Issue::MissingSection(Section::Title),
+                Issue::MissingSection(Section::Tags),
+                Issue::MissingSection(Section::Stats),
+            ],
+            validation.issues
+        );
+        assert!(!validation.is_extractable());
+        assert_eq!(
+            "missing preface, missing work link, missing title, missing tags, missing stats",
+            validation.to_string()
+        );
+    }
+
+    #[test]
+    fn test_replacement_characters() {
+        let html = String::from_utf8(download(2)).unwrap().replacen("Once upon a time", "\u{FFFD}\u{FFFD}", 1);
+        let validation = validate_deep(html);
+        assert_eq!(vec![Issue::ReplacementCharacters(2)], validation.issues);
+    }
+}
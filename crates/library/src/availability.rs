@@ -0,0 +1,169 @@
+//! Finding the best version of a work that can actually be read.
+//!
+//! [`Repository::get_best_for_work_id`] returns the best version on record,
+//! regardless of where its files are. If the only copies of that version are
+//! in the trash, or on a target that isn't currently connected, that's not
+//! much use to anything that wants to read (render, export, etc.) the work.
+
+use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
+use exn::ResultExt;
+use rawr_cache::Repository;
+use rawr_extract::models::Version;
+use rawr_storage::BackendHandle;
+use rawr_storage::file::{FileInfo, Processed};
+
+type File = FileInfo<Processed>;
+
+/// The result of [`best_available_for_work_id`].
+#[derive(Debug)]
+pub enum Availability {
+    /// The best version of the work is available; holds its available files.
+    Best(Version, Vec<File>),
+    /// The best version of the work is not available. The next-best version
+    /// that is has been promoted in its place.
+    Promoted {
+        /// The best version on record, which is not available.
+        best: Box<Version>,
+        /// The best version that *is* available.
+        promoted: Version,
+        /// The promoted version's available files.
+        files: Vec<File>,
+    },
+    /// No version of the work is available; holds the best version on record.
+    Unavailable(Version),
+}
+impl Availability {
+    /// The version to use, and its available files (if there is one).
+    pub fn available(&self) -> Option<(&Version, &[File])> {
+        match self {
+            Self::Best(version, files) => Some((version, files)),
+            Self::Promoted { promoted, files, .. } => Some((promoted, files)),
+            Self::Unavailable(_) => None,
+        }
+    }
+}
+
+/// Finds the best version of a work that has at least one file on the given
+/// `targets`, promoting the next-best version if the best isn't available.
+///
+/// A file is available if its target is one of `targets` and it still exists
+/// there. Files on any other target (including trash targets, and targets
+/// that aren't connected) are ignored, as are files on targets that error
+/// when checked. Returns `None` if there are no versions of the work on record.
+///
+/// Warnings are logged whenever the best version isn't available, since it
+/// usually means that a target needs reconnecting or the trash needs
+/// restoring from.
+///
+/// # Errors
+/// Returns [`Exn<LibraryErrorKind::Cache>`](LibraryErrorKind::Cache) if
+/// querying the cache fails.
+pub async fn best_available_for_work_id(
+    cache: &Repository,
+    targets: &[BackendHandle],
+    work_id: u64,
+) -> LibraryResult<Option<Availability>> {
+    // Sorted best/newest first.
+    let versions = cache.get_by_work_id(work_id).await.or_raise(|| LibraryErrorKind::Cache)?;
+    let mut versions = versions.into_iter();
+    let Some((best, files)) = versions.next() else {
+        return Ok(None);
+    };
+    let files = available_files(targets, files).await;
+    if !files.is_empty() {
+        return Ok(Some(Availability::Best(best, files)));
+    }
+    for (promoted, files) in versions {
+        let files = available_files(targets, files).await;
+        if !files.is_empty() {
+            tracing::warn!(
+                work_id,
+                best = best.hash,
+                promoted = promoted.hash,
+                "Best version of work is not available; using the next-best version that is",
+            );
+            return Ok(Some(Availability::Promoted { best: Box::new(best), promoted, files }));
+        }
+    }
+    tracing::warn!(work_id, best = best.hash, "No version of work is available on any connected target");
+    Ok(Some(Availability::Unavailable(best)))
+}
+
+async fn available_files(targets: &[BackendHandle], files: Vec<File>) -> Vec<File> {
+    let mut available = Vec::with_capacity(files.len());
+    for file in files {
+        let Some(backend) = targets.iter().find(|t| t.name() == file.target) else {
+            continue;
+        };
+        match backend.exists(&file.path).await {
+            Ok(true) => available.push(file),
+            Ok(false) => (),
+            Err(e) => tracing::debug!(
+                target = file.target,
+                path = %file.path.display(),
+                error = %*e,
+                "Could not check whether file exists; treating it as unavailable",
+            ),
+        }
+    }
+    available
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::Database;
+    use rawr_compress::Compression;
+    use rawr_extract::models::Encoding;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::backend::MockBackend;
+    use std::sync::Arc;
+    use time::{Duration, UtcDateTime};
+
+    fn version(hash: &str, age: i64) -> Version {
+        let mut metadata = Generator::new(1).metadata();
+        metadata.work_id = 123;
+        metadata.last_modified -= Duration::days(age);
+        Version {
+            hash: hash.to_string(),
+            length: 1000,
+            crc32: 0,
+            encoding: Encoding::Utf8,
+            detected_language: None,
+            metadata,
+            extracted_at: UtcDateTime::now(),
+        }
+    }
+
+    fn file(target: &str, path: &str, hash: &str) -> File {
+        FileInfo::new(target, path, 100, UtcDateTime::now(), Compression::None)
+            .with_file_hash(hash)
+            .with_content_hash(hash)
+    }
+
+    #[tokio::test]
+    async fn test_promotes_next_best() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        cache.upsert(&file("trash", "new.html", "new"), &version("new", 0)).await.unwrap();
+        cache.upsert(&file("library", "old.html", "old"), &version("old", 10)).await.unwrap();
+        cache.upsert(&file("library", "gone.html", "older"), &version("older", 20)).await.unwrap();
+        let library: BackendHandle = Arc::new(MockBackend::with_data([("old.html", "old")]).with_name("library"));
+        let trash: BackendHandle = Arc::new(MockBackend::with_data([("new.html", "new")]).with_name("trash"));
+
+        let availability =
+            best_available_for_work_id(&cache, std::slice::from_ref(&library), 123).await.unwrap().unwrap();
+        let Availability::Promoted { best, promoted, files } = availability else {
+            panic!("expected next-best version to be promoted");
+        };
+        assert_eq!("new", best.hash);
+        assert_eq!("old", promoted.hash);
+        assert_eq!(1, files.len());
+
+        let availability = best_available_for_work_id(&cache, &[library, trash], 123).await.unwrap().unwrap();
+        assert!(matches!(availability, Availability::Best(v, _) if v.hash == "new"));
+        let availability = best_available_for_work_id(&cache, &[], 123).await.unwrap().unwrap();
+        assert!(availability.available().is_none());
+        assert!(best_available_for_work_id(&cache, &[], 456).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,153 @@
+//! Reusable buffers for decompressed file contents.
+//!
+//! Scanning (and some organizing) decompresses every file it reads into
+//! memory, often several megabytes at a time for each of up to
+//! [`MAX_PROCESS_CONCURRENCY`](crate::MAX_PROCESS_CONCURRENCY) files. Rather
+//! than allocating (and growing) a fresh buffer each time, buffers are
+//! borrowed from a pool and returned to it once the file is done with.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// How many bytes of idle buffers the [global](BufferPool::global) pool
+/// keeps by default.
+pub const DEFAULT_BUFFER_POOL_BYTES: usize = 256 * 1024 * 1024;
+
+static GLOBAL: BufferPool = BufferPool::new(DEFAULT_BUFFER_POOL_BYTES);
+
+/// A pool of byte buffers, keeping up to a budget of bytes (by capacity) of
+/// idle buffers for reuse.
+///
+/// Scans and organizes share the [global](Self::global) pool. Buffers in use
+/// don't count towards the budget; buffers returned to a full pool (or
+/// larger than the entire budget) are freed instead.
+#[derive(Debug)]
+pub struct BufferPool {
+    max_bytes: AtomicUsize,
+    idle: Mutex<Idle>,
+}
+
+#[derive(Debug)]
+struct Idle {
+    buffers: Vec<Vec<u8>>,
+    bytes: usize,
+}
+
+impl BufferPool {
+    /// Creates an empty pool that keeps up to `max_bytes` of idle buffers.
+    pub const fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes: AtomicUsize::new(max_bytes),
+            idle: Mutex::new(Idle { buffers: Vec::new(), bytes: 0 }),
+        }
+    }
+
+    /// The pool shared by [`scan`](crate::scan::scan) and
+    /// [`organize`](crate::organize::organize).
+    pub fn global() -> &'static Self {
+        &GLOBAL
+    }
+
+    /// Changes how many bytes of idle buffers the pool keeps, freeing idle
+    /// buffers (largest first) until it's within the new budget. Zero
+    /// disables pooling.
+    pub fn set_max_bytes(&self, max_bytes: usize) {
+        self.max_bytes.store(max_bytes, Ordering::Relaxed);
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        // Sorted by capacity, largest last.
+        while idle.bytes > max_bytes {
+            let Some(buffer) = idle.buffers.pop() else { break };
+            idle.bytes -= buffer.capacity();
+        }
+    }
+
+    /// Total capacity of the idle buffers in the pool.
+    pub fn idle_bytes(&self) -> usize {
+        self.idle.lock().unwrap_or_else(PoisonError::into_inner).bytes
+    }
+
+    /// Borrows an empty buffer, preferably the largest idle one (so that it
+    /// rarely has to grow), returning it to the pool when dropped.
+    pub(crate) fn take(&self) -> PooledBuffer<'_> {
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        let buffer = match idle.buffers.pop() {
+            Some(buffer) => {
+                idle.bytes -= buffer.capacity();
+                buffer
+            },
+            None => Vec::new(),
+        };
+        PooledBuffer { pool: self, buffer }
+    }
+
+    fn put(&self, mut buffer: Vec<u8>) {
+        let capacity = buffer.capacity();
+        if capacity == 0 {
+            return;
+        }
+        let mut idle = self.idle.lock().unwrap_or_else(PoisonError::into_inner);
+        if idle.bytes.saturating_add(capacity) > self.max_bytes.load(Ordering::Relaxed) {
+            return;
+        }
+        buffer.clear();
+        idle.bytes += capacity;
+        let at = idle.buffers.partition_point(|b| b.capacity() <= capacity);
+        idle.buffers.insert(at, buffer);
+    }
+}
+
+/// A buffer borrowed from a [`BufferPool`]; returned to it when dropped.
+pub(crate) struct PooledBuffer<'a> {
+    pool: &'a BufferPool,
+    buffer: Vec<u8>,
+}
+impl Deref for PooledBuffer<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        self.pool.put(std::mem::take(&mut self.buffer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_pool() {
+        let pool = BufferPool::new(1024);
+        let mut a = pool.take();
+        a.extend_from_slice(&[1; 600]);
+        let capacity = a.capacity();
+        let mut b = pool.take();
+        b.reserve_exact(300);
+        drop(a);
+        assert_eq!(capacity, pool.idle_bytes());
+        // Idle buffers are reused, and ones that outgrow the budget freed.
+        let mut c = pool.take();
+        assert!(c.is_empty() && c.capacity() == capacity);
+        c.reserve_exact(2000);
+        drop(c);
+        assert_eq!(0, pool.idle_bytes());
+        drop(b);
+        assert!(pool.idle_bytes() >= 300);
+
+        let mut big = pool.take();
+        big.reserve_exact(512);
+        drop(big);
+        pool.set_max_bytes(0);
+        assert_eq!(0, pool.idle_bytes());
+        drop(pool.take());
+    }
+}
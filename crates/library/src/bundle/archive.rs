@@ -0,0 +1,152 @@
+use crate::bundle::error::{ErrorKind, Result};
+use exn::{OptionExt, ResultExt};
+use rawr_cache::Attributes;
+use rawr_compress::Compression;
+use rawr_extract::models::Version;
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Read};
+use time::UtcDateTime;
+
+/// Conventional file extension of bundles (without the leading dot).
+pub const EXTENSION: &str = "rawr";
+
+/// Current bundle format; bumped whenever older readers can't import newer bundles.
+const FORMAT: u32 = 1;
+const MANIFEST: &str = "manifest.json";
+const HTML: &str = "work.html";
+/// Most any one entry of a bundle may unpack to.
+const MAX_ENTRY_SIZE: u64 = 128 * 1024 * 1024;
+/// Most all entries of a bundle (including ignored ones) may unpack to.
+const MAX_UNPACKED_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    format: u32,
+    exported_at: UtcDateTime,
+    version: Version,
+    #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+    attributes: Attributes,
+}
+
+/// Packs a version, its attributes and its (decompressed) HTML into a bundle.
+pub(crate) fn pack(version: &Version, attributes: Attributes, html: &[u8]) -> Result<Vec<u8>> {
+    let manifest = Manifest {
+        format: FORMAT,
+        exported_at: UtcDateTime::now(),
+        version: version.clone(),
+        attributes,
+    };
+    let json = serde_json::to_vec_pretty(&manifest).or_raise(|| ErrorKind::Malformed("manifest"))?;
+    let mtime = u64::try_from(manifest.exported_at.unix_timestamp()).unwrap_or_default();
+
+    let mut bundle = Vec::new();
+    let writer = Compression::Gzip.wrap_writer(&mut bundle).or_raise(|| ErrorKind::Compression)?;
+    let mut builder = tar::Builder::new(writer);
+    for (name, data) in [(MANIFEST, json.as_slice()), (HTML, html)] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        builder.append_data(&mut header, name, data).or_raise(|| ErrorKind::Compression)?;
+    }
+    // Dropping the writer finishes the compressed stream.
+    drop(builder.into_inner().or_raise(|| ErrorKind::Compression)?);
+    Ok(bundle)
+}
+
+/// Unpacks a bundle, returning the version, its attributes and its HTML once
+/// the HTML has been checked against the version's hashes.
+///
+/// Any compression format is accepted (detected from magic bytes), as are
+/// uncompressed archives. Entries other than the manifest and HTML are
+/// ignored. Bundles with an entry larger than [`MAX_ENTRY_SIZE`], or entries
+/// totalling more than [`MAX_UNPACKED_SIZE`], are refused before they're read.
+pub(crate) fn unpack(bundle: &[u8]) -> Result<(Version, Attributes, Vec<u8>)> {
+    let compression = Compression::from_magic_bytes(bundle).unwrap_or_default();
+    let reader = compression.wrap_reader(Cursor::new(bundle)).or_raise(|| ErrorKind::Compression)?;
+    let mut archive = tar::Archive::new(reader);
+    let (mut manifest, mut html) = (None, None);
+    let mut total: u64 = 0;
+    for entry in archive.entries().or_raise(|| ErrorKind::Malformed("archive"))? {
+        let mut entry = entry.or_raise(|| ErrorKind::Malformed("archive"))?;
+        // Entries are read up to the size in their header, and no further.
+        total = total.saturating_add(entry.size());
+        if entry.size() > MAX_ENTRY_SIZE || total > MAX_UNPACKED_SIZE {
+            exn::bail!(ErrorKind::TooLarge);
+        }
+        let name = entry.path().or_raise(|| ErrorKind::Malformed("archive"))?.to_string_lossy().into_owned();
+        let slot = match name.as_str() {
+            MANIFEST => &mut manifest,
+            HTML => &mut html,
+            _ => continue,
+        };
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).or_raise(|| ErrorKind::Malformed("archive"))?;
+        *slot = Some(data);
+    }
+    let manifest = manifest.ok_or_raise(|| ErrorKind::Malformed("missing manifest"))?;
+    let html = html.ok_or_raise(|| ErrorKind::Malformed("missing HTML"))?;
+
+    let manifest: Manifest = serde_json::from_slice(&manifest).or_raise(|| ErrorKind::Malformed("manifest"))?;
+    if manifest.format > FORMAT {
+        exn::bail!(ErrorKind::UnsupportedFormat(manifest.format));
+    }
+    let Manifest { version, attributes, .. } = manifest;
+    if html.len() as u64 != version.length
+        || crc32fast::hash(&html) != version.crc32
+        || blake3::hash(&html).to_string() != version.hash
+    {
+        exn::bail!(ErrorKind::Corrupt);
+    }
+    Ok((version, attributes, html))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_extract::models::Encoding;
+    use rawr_extract::testing::Generator;
+    use std::ops::Deref;
+
+    fn version(html: &[u8]) -> Version {
+        Version {
+            hash: blake3::hash(html).to_string(),
+            length: html.len() as u64,
+            crc32: crc32fast::hash(html),
+            encoding: Encoding::Utf8,
+            detected_language: None,
+            metadata: Generator::new(3157).metadata(),
+            extracted_at: UtcDateTime::now(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let html = b"<html><body>Bundled</body></html>";
+        let version = version(html);
+        let attributes = Attributes::from([("external_id".to_string(), "abc".to_string())]);
+        let bundle = pack(&version, attributes.clone(), html).unwrap();
+        assert_eq!(Some(Compression::Gzip), Compression::from_magic_bytes(&bundle));
+        assert_eq!((version.clone(), attributes, html.to_vec()), unpack(&bundle).unwrap());
+        let bundle = pack(&version, Attributes::new(), html).unwrap();
+        assert!(unpack(&bundle).unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn test_corrupt() {
+        let version = version(b"<html><body>Bundled</body></html>");
+        let bundle = pack(&version, Attributes::new(), b"<html><body>Tampered</body></html>").unwrap();
+        assert!(matches!(unpack(&bundle).unwrap_err().deref(), ErrorKind::Corrupt));
+        assert!(matches!(unpack(b"not a bundle").unwrap_err().deref(), ErrorKind::Malformed(_)));
+    }
+
+    #[test]
+    fn test_too_large() {
+        // Only the header is needed: it's refused before any of it is read.
+        let mut header = tar::Header::new_gnu();
+        header.set_path(HTML).unwrap();
+        header.set_size(MAX_ENTRY_SIZE + 1);
+        header.set_cksum();
+        assert!(matches!(unpack(header.as_bytes()).unwrap_err().deref(), ErrorKind::TooLarge));
+    }
+}
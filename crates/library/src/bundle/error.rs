@@ -0,0 +1,70 @@
+//! Bundle Error Types
+//!
+//! This module provides structured errors using `exn` for automatic location
+//! tracking and error tree construction. See `ERRORS.md` for design rationale.
+
+use derive_more::{Display, Error};
+pub use rawr_error::{Code, Domain, ErrorCode};
+
+/// A bundle error with automatic location tracking.
+pub type Error = exn::Exn<ErrorKind>;
+/// Result type alias for bundle operations.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Classifies why exporting or importing a bundle failed.
+#[derive(Debug, Display, Error)]
+pub enum ErrorKind {
+    /// The bundle isn't a compressed tar archive of a manifest and HTML.
+    #[display("malformed bundle: {_0}")]
+    Malformed(#[error(not(source))] &'static str),
+    /// None of the version's files could be read (or still matched the
+    /// version's content hash) to export.
+    #[display("no readable copy of version to export")]
+    Unavailable,
+    /// The path an imported work belongs at is occupied by another file.
+    #[display("destination path is occupied by a different file")]
+    Conflict,
+    /// The bundle was written by a newer (unsupported) version of the format.
+    #[display("unsupported bundle format: {_0}")]
+    UnsupportedFormat(#[error(not(source))] u32),
+    /// An entry of the bundle, or the bundle as a whole, unpacks to more than
+    /// is allowed.
+    #[display("bundle is too large to unpack")]
+    TooLarge,
+    /// The bundled HTML doesn't match the hashes in its manifest.
+    #[display("bundled HTML doesn't match its manifest")]
+    Corrupt,
+    /// The [`PathGenerator`](crate::PathGenerator) could not render a path.
+    Template,
+    /// A [`Repository`](rawr_cache::Repository) query failed.
+    Cache,
+    /// A storage backend operation failed.
+    Storage,
+    /// Compressing or decompressing a file (or the bundle itself) failed.
+    Compression,
+}
+
+impl ErrorKind {
+    /// Returns `true` if retrying might succeed.
+    pub fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::Malformed(_) => 400,
+            Self::Unavailable => 404,
+            Self::Conflict => 409,
+            Self::TooLarge => 413,
+            Self::UnsupportedFormat(_) => 415,
+            Self::Corrupt => 417,
+            Self::Template => 422,
+            Self::Cache => 520,
+            Self::Storage => 521,
+            Self::Compression => 522,
+        };
+        Code::new(Domain::Bundle, number)
+    }
+}
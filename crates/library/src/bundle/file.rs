@@ -0,0 +1,200 @@
+use crate::Context;
+use crate::bundle::archive::{pack, unpack};
+use crate::bundle::error::{ErrorKind, Result as BundleResult};
+use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
+use crate::import::Import;
+use exn::ResultExt;
+use rawr_cache::Repository;
+use rawr_extract::models::Version;
+use rawr_storage::BackendHandle;
+use rawr_storage::error::ErrorKind as StorageErrorKind;
+use rawr_storage::file::{FileInfo, Processed};
+use time::UtcDateTime;
+
+/// Exports a version (with its attributes in `cache`) as a bundle, taking the
+/// HTML from the first of its `files` that can be read.
+///
+/// Files are only read from the `backends` matching their target; a file
+/// that can't be read, or whose contents no longer match the version's
+/// content hash, is skipped in favour of the next.
+///
+/// # Errors
+/// Returns [`Exn<LibraryErrorKind::Bundle>`](LibraryErrorKind::Bundle),
+/// raised from [`Unavailable`](ErrorKind::Unavailable) when no file could be
+/// exported.
+pub async fn export_bundle(
+    backends: &[BackendHandle],
+    cache: &Repository,
+    version: &Version,
+    files: &[FileInfo<Processed>],
+) -> LibraryResult<Vec<u8>> {
+    export_bundle_inner(backends, cache, version, files).await.or_raise(|| LibraryErrorKind::Bundle)
+}
+
+async fn export_bundle_inner(
+    backends: &[BackendHandle],
+    cache: &Repository,
+    version: &Version,
+    files: &[FileInfo<Processed>],
+) -> BundleResult<Vec<u8>> {
+    let attributes = cache.get_version_attributes(&version.hash).await.or_raise(|| ErrorKind::Cache)?;
+    for file in files.iter().filter(|f| f.content_hash == version.hash) {
+        let Some(backend) = backends.iter().find(|b| b.name() == file.target) else {
+            continue;
+        };
+        let html = match backend.read(&file.path).await {
+            Ok(data) => file.compression.decompress(&data),
+            Err(e) => {
+                tracing::debug!(target = backend.name(), path = %file.path.display(), error = ?e, "Could not read file to export");
+                continue;
+            },
+        };
+        match html {
+            Ok(html) if blake3::hash(&html).to_string() == version.hash => return pack(version, attributes, &html),
+            _ => {
+                tracing::debug!(target = backend.name(), path = %file.path.display(), "File no longer matches the version to export");
+            },
+        }
+    }
+    exn::bail!(ErrorKind::Unavailable);
+}
+
+/// Imports a bundle into the library on `backend`, writing the work to its
+/// template-derived path (in the context's compression, if any) and caching
+/// it.
+///
+/// - **[`Import::Imported`]**: the work was written and cached, along with
+///   the bundle's attributes of the version (other than those the library
+///   already has values for).
+/// - **[`Import::Outdated`]**: as above, but the library already has a
+///   newer version of the work.
+/// - **[`Import::AlreadyExists`]**: the version is already in the library on
+///   this target; nothing was written.
+///
+/// Language routes aren't applied; organize the library afterwards to route
+/// the imported work.
+///
+/// # Errors
+/// Returns [`Exn<LibraryErrorKind::Bundle>`](LibraryErrorKind::Bundle)
+/// raised from an inner [`Exn<ErrorKind>`](ErrorKind); notably, bundles
+/// whose HTML doesn't match the manifest fail with
+/// [`Corrupt`](ErrorKind::Corrupt).
+pub async fn import_bundle(
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    bundle: &[u8],
+) -> LibraryResult<Import> {
+    let (backend, cache) = ctx.guard(backend, cache);
+    import_bundle_inner(&backend, &cache, ctx, bundle).await.or_raise(|| LibraryErrorKind::Bundle)
+}
+
+async fn import_bundle_inner(
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    bundle: &[u8],
+) -> BundleResult<Import> {
+    let (version, attributes, html) = unpack(bundle)?;
+    if let Some((existing, files)) = cache.get_by_content_hash(&version.hash).await.or_raise(|| ErrorKind::Cache)?
+        && let Some(file) = files.into_iter().find(|f| f.target == backend.name())
+    {
+        return Ok(Import::AlreadyExists(file, existing));
+    }
+
+    let compression = ctx.compression.unwrap_or_default();
+    let path = ctx.template.generate_with_ext(&version, "html", compression).or_raise(|| ErrorKind::Template)?;
+    let data = compression.compress(&html).or_raise(|| ErrorKind::Compression)?;
+    match backend.write_if_absent(&path, &data).await {
+        Ok(()) => (),
+        Err(e) if matches!(&*e, StorageErrorKind::AlreadyExists(_)) => {
+            tracing::warn!(target = backend.name(), path = %path.display(), "Cannot import bundle; path is occupied by a different file");
+            exn::bail!(ErrorKind::Conflict);
+        },
+        Err(e) => Err(e).or_raise(|| ErrorKind::Storage)?,
+    }
+
+    let file = FileInfo::new(backend.name(), &path, data.len() as u64, UtcDateTime::now(), compression)
+        .with_file_hash(blake3::hash(&data).to_string())
+        .with_content_hash(&version.hash);
+    cache.upsert(&file, &version).await.or_raise(|| ErrorKind::Cache)?;
+    let existing = cache.get_version_attributes(&version.hash).await.or_raise(|| ErrorKind::Cache)?;
+    for (key, value) in attributes.iter().filter(|(key, _)| !existing.contains_key(*key)) {
+        cache.set_version_attribute(&version.hash, key, value).await.or_raise(|| ErrorKind::Cache)?;
+    }
+    let best = cache.get_best_for_work_id(version.metadata.work_id).await.or_raise(|| ErrorKind::Cache)?;
+    Ok(match best {
+        Some((best, _)) if best.hash != version.hash => Import::Outdated(file, version),
+        _ => Import::Imported(file, version),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PathGenerator;
+    use rawr_cache::Database;
+    use rawr_compress::Compression;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::backend::MockBackend;
+    use std::ops::Deref;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_export_and_import() {
+        let generated = Generator::new(3157).generate();
+        let html = generated.html.as_bytes();
+        let version = rawr_extract::extract(html).unwrap();
+        let source: BackendHandle = Arc::new(
+            MockBackend::with_data([("work.html.bz2", Compression::Bzip2.compress(html).unwrap())]).with_name("source"),
+        );
+        let file = FileInfo::new("source", "work.html.bz2", 0, UtcDateTime::now(), Compression::Bzip2)
+            .with_file_hash("irrelevant")
+            .with_content_hash(&version.hash);
+        let missing = FileInfo::new("elsewhere", "work.html", 0, UtcDateTime::now(), Compression::None)
+            .with_file_hash("irrelevant")
+            .with_content_hash(&version.hash);
+        let exporting = Repository::from(&Database::connect_in_memory().await.unwrap());
+        exporting.upsert(&file, &version).await.unwrap();
+        exporting.set_version_attribute(&version.hash, "external_id", "abc").await.unwrap();
+        exporting.set_version_attribute(&version.hash, "synced", "yes").await.unwrap();
+        let bundle =
+            export_bundle(std::slice::from_ref(&source), &exporting, &version, &[missing, file]).await.unwrap();
+
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let destination: BackendHandle = Arc::new(MockBackend::default().with_name("destination"));
+        let template: PathGenerator = "{{ work }}".parse().unwrap();
+        let ctx = Context::new(template, Compression::Gzip, None);
+        let Import::Imported(imported, _) = import_bundle(&destination, &cache, &ctx, &bundle).await.unwrap() else {
+            panic!("expected bundle to be imported");
+        };
+        let expected = PathBuf::from(format!("{}.html.gz", version.metadata.work_id));
+        assert_eq!(expected, imported.path);
+        let written = destination.read(&expected).await.unwrap();
+        assert_eq!(html, Compression::Gzip.decompress(&written).unwrap());
+        let (cached, files) = cache.get_by_content_hash(&version.hash).await.unwrap().unwrap();
+        assert_eq!(version.metadata, cached.metadata);
+        assert_eq!(vec![&imported.path], files.iter().map(|f| &f.path).collect::<Vec<_>>());
+        assert_eq!(
+            exporting.get_version_attributes(&version.hash).await.unwrap(),
+            cache.get_version_attributes(&version.hash).await.unwrap()
+        );
+
+        let again = import_bundle(&destination, &cache, &ctx, &bundle).await.unwrap();
+        assert!(matches!(again, Import::AlreadyExists(..)));
+    }
+
+    #[tokio::test]
+    async fn test_export_unavailable() {
+        let version = rawr_extract::extract(Generator::new(1).generate().html).unwrap();
+        let source: BackendHandle = Arc::new(MockBackend::default().with_name("source"));
+        let file = FileInfo::new("source", "gone.html", 0, UtcDateTime::now(), Compression::None)
+            .with_file_hash("irrelevant")
+            .with_content_hash(&version.hash);
+        let cache = Repository::from(&Database::connect_in_memory().await.unwrap());
+        let error = export_bundle_inner(&[source], &cache, &version, &[file]).await.unwrap_err();
+        assert!(matches!(error.deref(), ErrorKind::Unavailable));
+    }
+}
@@ -0,0 +1,24 @@
+//! Self-contained export bundles of single versions, for sharing individual
+//! works between libraries.
+//!
+//! A bundle (conventionally with the [`.rawr`](EXTENSION) extension) is a
+//! gzip-compressed tar archive holding two entries:
+//!
+//! - `manifest.json`: the bundle format, when it was exported, the
+//!   [`Version`](rawr_extract::models::Version) as cached (metadata and
+//!   content hashes), and its [attributes](rawr_cache::Repository::set_version_attribute).
+//! - `work.html`: the version's decompressed HTML.
+//!
+//! [`export_bundle`] builds a bundle from any readable copy of a version, and
+//! [`import_bundle`] restores both the file (named by the importing library's
+//! template, in its preferred compression) and its cache records (keeping
+//! any attributes the importing library already has). Because the
+//! manifest carries the content hash, a bundle that was damaged (or tampered
+//! with) in transit is rejected rather than imported.
+
+mod archive;
+pub(crate) mod error;
+mod file;
+
+pub use self::archive::EXTENSION;
+pub use self::file::{export_bundle, import_bundle};
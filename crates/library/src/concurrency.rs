@@ -0,0 +1,167 @@
+//! Adapting how many files are processed at once to how the backend copes.
+//!
+//! A fast local disk keeps up with as many files as the CPU can extract,
+//! while a NAS or S3 bucket slows to a crawl (or starts failing) long before
+//! then. Rather than a fixed number of files in flight, scans and organizes
+//! adjust it as they go with an AIMD controller, in the manner of TCP
+//! congestion control: the limit grows while files complete quickly and
+//! successfully, and halves when the backend shows signs of congestion
+//! (operations taking much longer than they used to, or failing).
+
+use std::time::Duration;
+
+/// Fewest files processed at once, however congested the backend.
+const MIN_CONCURRENCY: usize = 4;
+/// Files processed at once to begin with.
+const INITIAL_CONCURRENCY: usize = 16;
+/// How much slower than the baseline operations may get before the backend
+/// is considered congested.
+const LATENCY_TOLERANCE: f64 = 2.0;
+/// Weight of each operation's latency in the smoothed latency.
+const SMOOTHING: f64 = 0.2;
+/// How quickly (per operation) the baseline drifts up towards the smoothed
+/// latency, so that an unrepresentatively quick start doesn't count as
+/// congestion forever.
+const BASELINE_DRIFT: f64 = 0.01;
+
+/// An AIMD controller of how many operations to keep in flight.
+///
+/// Starts with a slow start (doubling the limit for every round of
+/// operations that completes without congestion) until the first sign of
+/// congestion, then grows by one per round. Congestion halves the limit, at
+/// most once per round, as the operations already in flight were started
+/// under the old limit.
+#[derive(Debug, Clone)]
+pub(crate) struct Concurrency {
+    limit: usize,
+    max: usize,
+    slow_start: bool,
+    /// Operations completed without congestion since the limit last changed.
+    successes: usize,
+    /// Operations to complete before the limit may be decreased again.
+    cooldown: usize,
+    smoothed: Option<f64>,
+    baseline: Option<f64>,
+}
+impl Concurrency {
+    /// A controller keeping at most `max` operations in flight.
+    pub(crate) fn new(max: usize) -> Self {
+        let max = max.max(1);
+        Self {
+            limit: INITIAL_CONCURRENCY.min(max),
+            max,
+            slow_start: true,
+            successes: 0,
+            cooldown: 0,
+            smoothed: None,
+            baseline: None,
+        }
+    }
+
+    /// How many operations to keep in flight.
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Records a completed operation, which took `latency` and `failed` (or
+    /// not) because of the backend, adjusting the limit.
+    pub(crate) fn record(&mut self, latency: Duration, failed: bool) {
+        let latency = latency.as_secs_f64();
+        let smoothed = match self.smoothed {
+            Some(smoothed) => smoothed + SMOOTHING * (latency - smoothed),
+            None => latency,
+        };
+        self.smoothed = Some(smoothed);
+        let baseline = match self.baseline {
+            Some(baseline) => smoothed.min(baseline + BASELINE_DRIFT * (smoothed - baseline)),
+            None => smoothed,
+        };
+        self.baseline = Some(baseline);
+        self.cooldown = self.cooldown.saturating_sub(1);
+
+        if failed || smoothed > baseline * LATENCY_TOLERANCE {
+            self.decrease(failed);
+            return;
+        }
+        self.successes += 1;
+        if self.successes >= self.limit {
+            let limit = match self.slow_start {
+                true => self.limit.saturating_mul(2),
+                false => self.limit + 1,
+            };
+            self.set_limit(limit);
+        }
+    }
+
+    fn decrease(&mut self, failed: bool) {
+        self.slow_start = false;
+        if self.cooldown > 0 {
+            return;
+        }
+        self.cooldown = self.limit;
+        self.set_limit(self.limit / 2);
+        tracing::debug!(limit = self.limit, failed, "Backend congested; processing fewer files at once");
+    }
+
+    fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.clamp(MIN_CONCURRENCY.min(self.max), self.max);
+        self.successes = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Duration = Duration::from_millis(10);
+
+    fn run(concurrency: &mut Concurrency, count: usize, latency: Duration, failed: bool) {
+        for _ in 0..count {
+            concurrency.record(latency, failed);
+        }
+    }
+
+    #[test]
+    fn test_slow_start() {
+        let mut concurrency = Concurrency::new(100);
+        assert_eq!(16, concurrency.limit());
+        run(&mut concurrency, 16, FAST, false);
+        assert_eq!(32, concurrency.limit());
+        run(&mut concurrency, 32 + 64, FAST, false);
+        assert_eq!(100, concurrency.limit());
+    }
+
+    #[test]
+    fn test_failures() {
+        let mut concurrency = Concurrency::new(100);
+        run(&mut concurrency, 16, FAST, false);
+        // Failures of the operations in flight halve the limit once.
+        run(&mut concurrency, 10, FAST, true);
+        assert_eq!(16, concurrency.limit());
+        // Then it grows additively.
+        run(&mut concurrency, 6 + 16, FAST, false);
+        assert_eq!(17, concurrency.limit());
+        run(&mut concurrency, 1000, FAST, true);
+        assert_eq!(MIN_CONCURRENCY, concurrency.limit());
+    }
+
+    #[test]
+    fn test_latency() {
+        let mut concurrency = Concurrency::new(100);
+        run(&mut concurrency, 16, FAST, false);
+        assert_eq!(32, concurrency.limit());
+        run(&mut concurrency, 10, FAST * 10, false);
+        assert_eq!(16, concurrency.limit());
+        // Latency staying high becomes the new normal.
+        run(&mut concurrency, 1000, FAST * 10, false);
+        assert!(concurrency.limit() > 16);
+    }
+
+    #[test]
+    fn test_small_maximum() {
+        let mut concurrency = Concurrency::new(2);
+        assert_eq!(2, concurrency.limit());
+        run(&mut concurrency, 10, FAST, true);
+        assert_eq!(2, concurrency.limit());
+    }
+}
@@ -64,7 +64,7 @@ pub(crate) async fn handle_conflict<S: HashState>(
         .or_raise(|| LibraryErrorKind::Conflict)?
     {
         Some((file, version)) => (file, version),
-        None => match scan_file_inner(backend, cache, existing.clone()).await {
+        None => match scan_file_inner(backend, cache, existing.clone(), ctx.repair_encoding).await {
             // We scanned the target file and now it's cached, ready for conflict resolution.
             Ok(Scan { file, version, .. }) => (file, version),
             // The target file doesn't exist in the cache, and when we tried to perform a scan, it wasn't valid.
@@ -91,14 +91,16 @@ pub(crate) async fn handle_conflict<S: HashState>(
     depth.push(existing_file.path.clone());
     // Pin that sucker! Otherwise you have some weird async recursion error
     // that is so complicated it makes your brain explode...
-    match Box::pin(organize_file_inner(backend, cache, ctx, existing_file, depth)).await {
+    match Box::pin(organize_file_inner(backend, cache, ctx, existing_file, depth, None)).await {
         // No conflict resolution is possible, return None.
-        Ok(Action::AlreadyCorrect(_)) => match incoming_version.partial_cmp(&existing_version) {
+        Ok((Action::AlreadyCorrect(_), _)) => match incoming_version.partial_cmp(&existing_version) {
             None => Ok(None),
             Some(Ordering::Greater) => Ok(Some(ConflictResolution::TrashExisting)),
             Some(_) => Ok(Some(ConflictResolution::TrashIncoming)),
         },
-        Ok(Action::Renamed(_)) | Ok(Action::CleanedUp(_)) => Ok(Some(ConflictResolution::TargetNowFree)),
+        Ok((Action::Renamed(_) | Action::Transferred(..) | Action::CleanedUp(_), _)) => {
+            Ok(Some(ConflictResolution::TargetNowFree))
+        },
         Err(e) => Err(e).or_raise(|| LibraryErrorKind::Conflict),
     }
 }
@@ -7,8 +7,11 @@
 //!       more crates. Designing errors in Rust is **hard** and I don't want
 //!       to resort to anyhow+thiserror just because I don't want to deal with it.
 
+pub use crate::bundle::error::ErrorKind as BundleErrorKind;
 pub use crate::scan::error::ErrorKind as ScanErrorKind;
+pub use crate::verify::error::ErrorKind as VerifyErrorKind;
 use derive_more::{Display, Error};
+pub use rawr_error::{Code, Domain, ErrorCode};
 
 /// A library error with automatic location tracking.
 pub type Error = exn::Exn<ErrorKind>;
@@ -20,16 +23,47 @@ pub enum ErrorKind {
     Scan,
     Organize,
     Import,
+    Verify,
+    Bundle,
     Conflict,
     #[display("issue with path generation from template")]
     Template,
+    /// A [`Repository`](rawr_cache::Repository) query failed.
+    Cache,
+    /// Restoring files from a [`Snapshot`](crate::Snapshot) failed.
+    Rollback,
+    /// Listing or deleting files in the trash failed.
+    Trash,
+    /// A [`Schedule`](crate::schedule::Schedule) isn't a valid cron expression.
+    #[display("invalid schedule {schedule:?}: {reason}")]
+    InvalidSchedule {
+        schedule: String,
+        reason: &'static str,
+    },
 }
 
 impl ErrorKind {
     /// Returns `true` if retrying might succeed.
     pub fn is_retryable(&self) -> bool {
-        match self {
-            _ => false,
-        }
+        false
+    }
+}
+
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::InvalidSchedule { .. } => 400,
+            Self::Conflict => 409,
+            Self::Template => 422,
+            Self::Cache => 520,
+            Self::Scan => 524,
+            Self::Organize => 525,
+            Self::Import => 526,
+            Self::Verify => 527,
+            Self::Bundle => 528,
+            Self::Rollback => 529,
+            Self::Trash => 530,
+        };
+        Code::new(Domain::Library, number)
     }
 }
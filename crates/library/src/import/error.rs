@@ -8,6 +8,7 @@
 //!       to resort to anyhow+thiserror just because I don't want to deal with it.
 
 use derive_more::{Display, Error};
+pub use rawr_error::{Code, Domain, ErrorCode};
 
 /// An import error with automatic location tracking via [`exn::Exn`].
 pub type Error = exn::Exn<ErrorKind>;
@@ -26,15 +27,33 @@ pub enum ErrorKind {
     Storage,
     /// The [`PathGenerator`](crate::PathGenerator) could not render a path.
     Template,
+    /// Recording an imported file in the cache (by scanning it) failed.
+    Scan,
     /// Importing the file required organizing others out of the way.
     Organize,
+    /// Reading or cleaning up the files being imported (such as a
+    /// [swept](super::sweep) download directory) failed.
+    Io,
 }
 
 impl ErrorKind {
     /// Returns `true` if retrying might succeed.
     pub fn is_retryable(&self) -> bool {
-        match self {
-            _ => false,
-        }
+        false
+    }
+}
+
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::Template => 422,
+            Self::Cache => 520,
+            Self::Storage => 521,
+            Self::Compression => 522,
+            Self::Io => 523,
+            Self::Scan => 524,
+            Self::Organize => 525,
+        };
+        Code::new(Domain::Import, number)
     }
 }
@@ -57,11 +57,11 @@ pub async fn import_file<W: AsyncRead>(
 }
 
 async fn import_file_inner<W: AsyncRead>(
-    backend: &BackendHandle,
-    cache: &Repository,
-    ctx: &Context,
-    file: Metadata,
-    data: W,
+    _backend: &BackendHandle,
+    _cache: &Repository,
+    _ctx: &Context,
+    _file: Metadata,
+    _data: W,
 ) -> ImportResult<Import> {
     // TODO... Do we implement almost everything again here, or shall we dump
     //         the import into a random file in the library and delegate to
@@ -1,4 +1,10 @@
 pub mod error;
 mod file;
+mod report;
+mod sweep;
 
 pub use self::file::{Import, import_file};
+pub use self::report::{
+    IMPORT_REPORT_VERSION, ImportReport, ImportSummary, Rejection, ReportedDownload, ReportedOutcome,
+};
+pub use self::sweep::{Cleanup, SweepOutcome, Swept, sweep};
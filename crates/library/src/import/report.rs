@@ -0,0 +1,217 @@
+//! Reports of what a [sweep](super::sweep) did, for automation.
+//!
+//! A [`Vec<Swept>`](Swept) is fine for the caller, but scripts running sweeps
+//! on a schedule want something to keep, and to compare with the previous
+//! run's. An [`ImportReport`] is that: every download swept, what happened to
+//! it and why, with a summary, serialized as JSON. Its schema is versioned
+//! ([`IMPORT_REPORT_VERSION`]); fields may be added without a new version, but
+//! nothing is renamed or removed without one.
+
+use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
+use crate::import::error::ErrorKind;
+use crate::import::{SweepOutcome, Swept};
+use exn::ResultExt;
+use rawr_extract::Issue;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+/// Version of the [`ImportReport`] schema.
+pub const IMPORT_REPORT_VERSION: u32 = 1;
+
+/// Why a download was left where it was, rather than imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum Rejection {
+    /// It isn't an AO3 work.
+    NotAWork,
+    /// The user deliberately deleted the work.
+    Tombstoned,
+}
+
+/// What happened to a download, in an [`ImportReport`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ReportedOutcome {
+    /// Imported into the library, at `path` on `target`.
+    Imported { target: String, path: PathBuf },
+    /// Skipped: the library already had the version, at `path` on `target`
+    /// (unknown if the library kept its own copy under its duplicate policy).
+    Duplicate {
+        target: Option<String>,
+        path: Option<PathBuf>,
+    },
+    /// Left where it was.
+    Rejected {
+        #[serde(flatten)]
+        rejection: Rejection,
+    },
+    /// Left where it was, because importing or cleaning it up failed (for
+    /// `reason`); the next sweep tries again.
+    Failed { reason: String },
+}
+
+/// A download in an [`ImportReport`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportedDownload {
+    /// Path of the download.
+    pub source: PathBuf,
+    /// ID of the work downloaded (`None` if it isn't an AO3 work).
+    pub work_id: Option<u64>,
+    #[serde(flatten)]
+    pub outcome: ReportedOutcome,
+    /// What a [deep check](rawr_extract::validate_deep) found wrong with the
+    /// download, if [checked](crate::Context::with_download_validation);
+    /// damaged downloads are imported all the same.
+    #[serde(default)]
+    pub issues: Vec<Issue>,
+}
+
+/// How many downloads had each kind of [`ReportedOutcome`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub imported: u64,
+    pub duplicates: u64,
+    pub rejected: u64,
+    #[serde(default)]
+    pub failed: u64,
+    /// Downloads with [issues](ReportedDownload::issues) (counted by their
+    /// outcome too).
+    pub damaged: u64,
+}
+
+/// What a [sweep](super::sweep) did, to keep or compare with other runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// Version of the report's schema ([`IMPORT_REPORT_VERSION`]).
+    pub version: u32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub generated: OffsetDateTime,
+    pub summary: ImportSummary,
+    /// Every download swept, in the order they were.
+    pub downloads: Vec<ReportedDownload>,
+}
+impl ImportReport {
+    /// A report of the downloads `swept`.
+    pub fn new(swept: &[Swept]) -> Self {
+        let mut summary = ImportSummary::default();
+        let downloads: Vec<_> = swept
+            .iter()
+            .map(|swept| {
+                let outcome = match &swept.outcome {
+                    SweepOutcome::Imported(target, path) => {
+                        summary.imported += 1;
+                        ReportedOutcome::Imported {
+                            target: target.clone(),
+                            path: path.clone(),
+                        }
+                    },
+                    SweepOutcome::AlreadyInLibrary(target, path) => {
+                        summary.duplicates += 1;
+                        ReportedOutcome::Duplicate {
+                            target: Some(target.clone()),
+                            path: Some(path.clone()),
+                        }
+                    },
+                    SweepOutcome::Discarded => {
+                        summary.duplicates += 1;
+                        ReportedOutcome::Duplicate { target: None, path: None }
+                    },
+                    SweepOutcome::NotAWork => {
+                        summary.rejected += 1;
+                        ReportedOutcome::Rejected { rejection: Rejection::NotAWork }
+                    },
+                    SweepOutcome::Tombstoned(_) => {
+                        summary.rejected += 1;
+                        ReportedOutcome::Rejected { rejection: Rejection::Tombstoned }
+                    },
+                    SweepOutcome::Failed(reason) => {
+                        summary.failed += 1;
+                        ReportedOutcome::Failed { reason: reason.clone() }
+                    },
+                };
+                let issues = swept.validation.as_ref().map(|v| v.issues.clone()).unwrap_or_default();
+                if !issues.is_empty() {
+                    summary.damaged += 1;
+                }
+                ReportedDownload {
+                    source: swept.source.clone(),
+                    work_id: swept.work_id,
+                    outcome,
+                    issues,
+                }
+            })
+            .collect();
+        Self {
+            version: IMPORT_REPORT_VERSION,
+            generated: OffsetDateTime::now_utc(),
+            summary,
+            downloads,
+        }
+    }
+
+    /// Writes the report to `path`, as JSON.
+    ///
+    /// # Errors
+    /// Returns [`Exn<LibraryErrorKind::Import>`](LibraryErrorKind::Import) if
+    /// the report can't be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> LibraryResult<()> {
+        let json = serde_json::to_vec_pretty(self).or_raise(|| ErrorKind::Io).or_raise(|| LibraryErrorKind::Import)?;
+        std::fs::write(path, json).or_raise(|| ErrorKind::Io).or_raise(|| LibraryErrorKind::Import)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_extract::Validation;
+    use serde_json::json;
+
+    #[test]
+    fn test_import_report() {
+        let swept = |source: &str, work_id, outcome, issues: Vec<Issue>| Swept {
+            source: PathBuf::from(source),
+            work_id,
+            outcome,
+            validation: work_id.map(|_| Validation { issues }),
+        };
+        let path = PathBuf::from("1.html.gz");
+        let report = ImportReport::new(&[
+            swept("First.html", Some(1), SweepOutcome::Imported("library".into(), path.clone()), vec![]),
+            swept("First (1).html", Some(1), SweepOutcome::AlreadyInLibrary("library".into(), path), vec![]),
+            swept("Notes.html", None, SweepOutcome::NotAWork, vec![]),
+            swept("Gone.html", Some(2), SweepOutcome::Tombstoned(2), vec![Issue::Truncated]),
+            swept("Broken.html", None, SweepOutcome::Failed("Io (RAWR-IMPORT-523)".into()), vec![]),
+        ]);
+        assert_eq!(
+            ImportSummary {
+                imported: 1,
+                duplicates: 1,
+                rejected: 2,
+                failed: 1,
+                damaged: 1,
+            },
+            report.summary
+        );
+
+        let value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json!(IMPORT_REPORT_VERSION), value["version"]);
+        assert_eq!(
+            json!({ "source": "First.html", "work_id": 1, "outcome": "imported", "target": "library", "path": "1.html.gz", "issues": [] }),
+            value["downloads"][0]
+        );
+        assert_eq!(
+            json!({ "source": "Notes.html", "work_id": null, "outcome": "rejected", "reason": "not_a_work", "issues": [] }),
+            value["downloads"][2]
+        );
+        assert_eq!(
+            json!({ "source": "Broken.html", "work_id": null, "outcome": "failed", "reason": "Io (RAWR-IMPORT-523)", "issues": [] }),
+            value["downloads"][4]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        report.save(dir.path().join("report.json")).unwrap();
+        let saved = std::fs::read_to_string(dir.path().join("report.json")).unwrap();
+        assert_eq!(report, serde_json::from_str(&saved).unwrap());
+    }
+}
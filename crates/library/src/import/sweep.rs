@@ -0,0 +1,479 @@
+//! Picking up AO3 downloads from a browser's download directory.
+//!
+//! AO3 names downloads after the work (`Work Title.html`), and browsers
+//! append ` (1)`, ` (2)`… when the same work is downloaded again. Sweeping
+//! imports each download that is an AO3 work into the library at its
+//! template path, recognising repeated downloads of the same version as
+//! duplicates, and then cleans up the download directory.
+
+use crate::Context;
+use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
+use crate::import::ImportReport;
+use crate::import::error::{Error as ImportError, ErrorCode, ErrorKind, Result as ImportResult};
+use crate::organize::file::{Action, organize_file_inner};
+use crate::scan::file::scan_file_inner;
+use exn::ResultExt;
+use rawr_cache::Repository;
+use rawr_compress::Compression;
+use rawr_extract::models::Version;
+use rawr_extract::{Validation, extract_repairing, validate_deep};
+use rawr_storage::BackendHandle;
+use std::path::{Path, PathBuf};
+use time::UtcDateTime;
+
+/// Where downloads are staged in the library before being organized.
+const STAGING_DIR: &str = ".rawr-import";
+/// Appended to a download's file name to name its receipt.
+const RECEIPT_SUFFIX: &str = ".imported.txt";
+
+/// What to do with a download once the library has it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Cleanup {
+    /// Replace the download with a small text file recording where it went
+    /// (the default).
+    #[default]
+    Receipt,
+    /// Delete the download.
+    Remove,
+}
+
+/// What happened to a single download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SweepOutcome {
+    /// The download was imported; holds the target's name and its path there.
+    Imported(String, PathBuf),
+    /// The library already had this version (perhaps imported earlier in the
+    /// same sweep, from the same download without the ` (1)`); holds the
+    /// target's name and the path of the existing copy.
+    AlreadyInLibrary(String, PathBuf),
+    /// The library kept another copy of the version instead, according to
+    /// its [duplicate policy](crate::Context::with_duplicate_policy).
+    Discarded,
+    /// Not an AO3 download; it was left where it was.
+    NotAWork,
+    /// A download of a work (with this ID) the user deliberately deleted
+    /// (see [`Repository::add_tombstone`]); it was left where it was.
+    Tombstoned(u64),
+    /// Importing or cleaning up the download failed (holds why); it was left
+    /// where it was, to be swept again.
+    Failed(String),
+}
+
+/// A download that was swept, and what happened to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Swept {
+    /// Path of the download.
+    pub source: PathBuf,
+    /// ID of the work downloaded (`None` if it isn't an AO3 work).
+    pub work_id: Option<u64>,
+    pub outcome: SweepOutcome,
+    /// What a [deep check](validate_deep) found wrong with the download
+    /// (`None` unless the context [asks for one](Context::with_download_validation),
+    /// or if it isn't an AO3 work). Damaged downloads are imported all the
+    /// same, but are worth downloading again.
+    pub validation: Option<Validation>,
+}
+
+/// Imports every AO3 download (`*.html`, compressed or not) in
+/// `download_dir` into the library, at the path given by the [`Context`]'s
+/// template (and compression, language routes, and so on, just like
+/// [`organize`](crate::organize::organize)).
+///
+/// Downloads are validated before anything is written: files that aren't
+/// AO3 works are reported as [`NotAWork`](SweepOutcome::NotAWork) and left
+/// alone, as are downloads of works the user deliberately deleted
+/// ([`Tombstoned`](SweepOutcome::Tombstoned)). Downloads of versions already
+/// in the library aren't imported again. Everything else found is [cleaned up](Cleanup) once the library
+/// has it. The directory isn't searched recursively, and receipts from
+/// earlier sweeps are ignored.
+///
+/// A download that can't be imported (or cleaned up) is reported as
+/// [`Failed`](SweepOutcome::Failed), and the sweep carries on with the rest;
+/// only failing to read the directory or to hold the lock ends it early.
+///
+/// The target is [locked](Repository::lock_target) for the duration. Results
+/// are in file name order, with browsers' numbered copies straight after the
+/// original download. If the context has a [report path](Context::with_import_report),
+/// an [`ImportReport`] of the results is saved there too.
+pub async fn sweep(
+    download_dir: impl AsRef<Path>,
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    cleanup: Cleanup,
+) -> LibraryResult<Vec<Swept>> {
+    let (backend, cache) = ctx.guard(backend, cache);
+    let swept = sweep_inner(download_dir.as_ref(), &backend, &cache, ctx, cleanup)
+        .await
+        .or_raise(|| LibraryErrorKind::Import)?;
+    if let Some(path) = &ctx.import_report {
+        ImportReport::new(&swept).save(path)?;
+    }
+    Ok(swept)
+}
+
+async fn sweep_inner(
+    download_dir: &Path,
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    cleanup: Cleanup,
+) -> ImportResult<Vec<Swept>> {
+    let mut downloads = Vec::new();
+    for entry in std::fs::read_dir(download_dir).or_raise(|| ErrorKind::Io)? {
+        let path = entry.or_raise(|| ErrorKind::Io)?.path();
+        if path.is_file() && is_download(&path) {
+            downloads.push(path);
+        }
+    }
+    downloads.sort_by_cached_key(|path| download_name(path));
+
+    let mut lock = cache.lock_target(backend.name(), "sweep").await.or_raise(|| ErrorKind::Cache)?;
+    let mut swept = Vec::with_capacity(downloads.len());
+    for source in downloads {
+        lock.refresh().await.or_raise(|| ErrorKind::Cache)?;
+        let (work_id, outcome, validation) = match sweep_file(&source, backend, cache, ctx).await {
+            Ok((work_id, outcome, validation)) => match finish(&source, cleanup, &outcome, ctx) {
+                Ok(()) => (work_id, outcome, validation),
+                Err(e) => (work_id, failed(&source, &e), validation),
+            },
+            Err(e) => (None, failed(&source, &e), None),
+        };
+        tracing::debug!(source = %source.display(), ?outcome, "Swept download");
+        swept.push(Swept { source, work_id, outcome, validation });
+    }
+    if let Err(e) = lock.release().await {
+        tracing::warn!(target = backend.name(), error = ?e, "Could not release target lock; it will expire");
+    }
+    Ok(swept)
+}
+
+async fn sweep_file(
+    source: &Path,
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+) -> ImportResult<(Option<u64>, SweepOutcome, Option<Validation>)> {
+    let data = std::fs::read(source).or_raise(|| ErrorKind::Io)?;
+    let compression = Compression::from_path(source);
+    let Ok(content) = compression.decompress(&data) else {
+        return Ok((None, SweepOutcome::NotAWork, None));
+    };
+    let Ok(version) = extract_repairing(&content) else {
+        return Ok((None, SweepOutcome::NotAWork, None));
+    };
+    let validation = ctx.validate_downloads.then(|| validate_deep(&content));
+    if let Some(validation) = validation.as_ref().filter(|v| !v.is_healthy()) {
+        tracing::warn!(source = %source.display(), %validation, "Download is not healthy");
+    }
+    let work_id = version.metadata.work_id;
+    let outcome = sweep_version(backend, cache, ctx, &data, compression, version).await?;
+    Ok((Some(work_id), outcome, validation))
+}
+
+/// Imports a download of `version` (`data`, compressed with `compression`).
+async fn sweep_version(
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    data: &[u8],
+    compression: Compression,
+    version: Version,
+) -> ImportResult<SweepOutcome> {
+    let work_id = version.metadata.work_id;
+    if cache.get_tombstone(work_id).await.or_raise(|| ErrorKind::Cache)?.is_some() {
+        return Ok(SweepOutcome::Tombstoned(work_id));
+    }
+    if let Some((_, files)) = cache.get_by_content_hash(&version.hash).await.or_raise(|| ErrorKind::Cache)?
+        && let Some(existing) = files.into_iter().next()
+    {
+        return Ok(SweepOutcome::AlreadyInLibrary(existing.target.clone(), existing.path.clone()));
+    }
+
+    // A dry run can't stage anything to organize, so reports where the
+    // download would have gone (had nothing been in the way).
+    if ctx.mode().is_dry_run() {
+        let compression = ctx.compression.unwrap_or(compression);
+        let (destination, path) = ctx.location(backend, &version, compression).or_raise(|| ErrorKind::Template)?;
+        return Ok(SweepOutcome::Imported(destination.name().to_string(), path));
+    }
+
+    // Stage the download in the library, then organize it into place.
+    let mut staged = Path::new(STAGING_DIR).join(&version.hash).with_extension("html");
+    if !matches!(compression, Compression::None) {
+        staged.add_extension(compression.extension().trim_matches('.'));
+    }
+    backend.write(&staged, data).await.or_raise(|| ErrorKind::Storage)?;
+    match organize_staged(backend, cache, ctx, &staged).await {
+        Ok(outcome) => Ok(outcome),
+        Err(e) => {
+            // Left in the library, the next scan would record the staged copy,
+            // and the next sweep would take the download for a duplicate of it
+            // (and clean it up).
+            discard_staged(backend, cache, &staged).await;
+            Err(e)
+        },
+    }
+}
+
+/// Organizes a download staged in the library at `staged` into place.
+async fn organize_staged(
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    staged: &Path,
+) -> ImportResult<SweepOutcome> {
+    let file = backend.stat(staged).await.or_raise(|| ErrorKind::Storage)?;
+    let (action, _) =
+        organize_file_inner(backend, cache, ctx, file, vec![], None).await.or_raise(|| ErrorKind::Organize)?;
+    Ok(match action {
+        Action::Renamed(path) | Action::AlreadyCorrect(path) => {
+            // Organizing doesn't fail for want of a record (it's left for
+            // the next scan), but later downloads of the same version need
+            // to find it now.
+            if cache.get_by_target_path(backend.name(), &path).await.or_raise(|| ErrorKind::Cache)?.is_none() {
+                let file = backend.stat(&path).await.or_raise(|| ErrorKind::Storage)?;
+                scan_file_inner(backend, cache, file, ctx.repair_encoding).await.or_raise(|| ErrorKind::Scan)?;
+            }
+            SweepOutcome::Imported(backend.name().to_string(), path)
+        },
+        Action::Transferred(target, path) => SweepOutcome::Imported(target, path),
+        Action::CleanedUp(_) => SweepOutcome::Discarded,
+    })
+}
+
+/// Removes a staged download (and any record of it) that couldn't be
+/// organized; the download itself is left where it was.
+async fn discard_staged(backend: &BackendHandle, cache: &Repository, staged: &Path) {
+    if backend.exists(staged).await.unwrap_or(true)
+        && let Err(e) = backend.delete(staged).await
+    {
+        tracing::warn!(path = %staged.display(), error = ?e, "Could not remove staged download");
+    }
+    if let Err(e) = cache.delete_by_target_path(backend.name(), staged).await {
+        tracing::warn!(path = %staged.display(), error = ?e, "Could not remove record of staged download");
+    }
+}
+
+/// The outcome of a download that failed to import (or be cleaned up).
+fn failed(source: &Path, e: &ImportError) -> SweepOutcome {
+    tracing::warn!(source = %source.display(), error = ?e, "Could not sweep download");
+    SweepOutcome::Failed(format!("{} ({})", &**e, e.code()))
+}
+
+/// Cleans up a download the library has, leaving a receipt if asked to.
+fn finish(source: &Path, cleanup: Cleanup, outcome: &SweepOutcome, ctx: &Context) -> ImportResult<()> {
+    let now = UtcDateTime::now();
+    let contents = match outcome {
+        SweepOutcome::NotAWork | SweepOutcome::Tombstoned(_) | SweepOutcome::Failed(_) => return Ok(()),
+        SweepOutcome::Imported(target, path) => format!("Imported into {target}:{} at {now}\n", path.display()),
+        SweepOutcome::AlreadyInLibrary(target, path) => {
+            format!("Already in the library as {target}:{}; removed at {now}\n", path.display())
+        },
+        SweepOutcome::Discarded => format!("Already in the library; discarded at {now}\n"),
+    };
+    if ctx.mode().is_dry_run() {
+        tracing::info!(path = %source.display(), ?cleanup, "Skipping download cleanup during dry run");
+        return Ok(());
+    }
+    if cleanup == Cleanup::Receipt {
+        let mut receipt = source.as_os_str().to_owned();
+        receipt.push(RECEIPT_SUFFIX);
+        std::fs::write(receipt, contents).or_raise(|| ErrorKind::Io)?;
+    }
+    std::fs::remove_file(source).or_raise(|| ErrorKind::Io)
+}
+
+/// Whether a file looks like an AO3 download (`.html`, possibly compressed).
+fn is_download(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    if name.ends_with(RECEIPT_SUFFIX) {
+        return false;
+    }
+    let name = Path::new(name);
+    let name = match Compression::from_path(name) {
+        Compression::None => name,
+        _ => Path::new(name.file_stem().unwrap_or_default()),
+    };
+    name.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm"))
+}
+
+/// The name of a download without any ` (n)` a browser added to tell
+/// repeated downloads apart, and that `n` (zero for the original download).
+fn download_name(path: &Path) -> (String, u32) {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let (stem, extension) = name.split_once('.').unwrap_or((&name, ""));
+    if let Some((original, copy)) = stem.strip_suffix(')').and_then(|s| s.rsplit_once(" ("))
+        && let Ok(copy) = copy.parse()
+    {
+        return (format!("{original}.{extension}"), copy);
+    }
+    (name.to_string(), 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::Database;
+    use rawr_extract::Issue;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::Mode;
+    use rawr_storage::backend::MockBackend;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_download_name() {
+        assert_eq!(("Work Title.html".to_string(), 0), download_name(Path::new("Work Title.html")));
+        assert_eq!(("Work Title.html".to_string(), 2), download_name(Path::new("dl/Work Title (2).html")));
+        assert_eq!(("Title (Remix).html".to_string(), 0), download_name(Path::new("Title (Remix).html")));
+        assert!(is_download(Path::new("Work Title (1).HTML")));
+        assert!(is_download(Path::new("work.html.gz")));
+        assert!(!is_download(Path::new("Work Title.html.imported.txt")));
+        assert!(!is_download(Path::new("Work Title.pdf")));
+    }
+
+    #[tokio::test]
+    async fn test_sweep() {
+        let downloads = tempfile::tempdir().unwrap();
+        let first = Generator::new(3178).generate();
+        let second = Generator::new(3179).generate();
+        std::fs::write(downloads.path().join("First.html"), &first.html).unwrap();
+        std::fs::write(downloads.path().join("First (1).html"), &first.html).unwrap();
+        std::fs::write(downloads.path().join("Second.html"), &second.html).unwrap();
+        std::fs::write(downloads.path().join("Notes.html"), "<p>Not a work</p>").unwrap();
+
+        let backend: BackendHandle = Arc::new(MockBackend::default().with_name("library"));
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::Gzip, None);
+        let swept = sweep(downloads.path(), &backend, &cache, &ctx, Cleanup::Receipt).await.unwrap();
+        let outcomes: Vec<_> =
+            swept.iter().map(|s| (s.source.file_name().unwrap().to_str().unwrap(), &s.outcome)).collect();
+        let imported = |work_id: u64| ("library".to_string(), PathBuf::from(format!("{work_id}.html.gz")));
+        let (library, first_path) = imported(first.expected.work_id);
+        let (_, second_path) = imported(second.expected.work_id);
+        assert_eq!(
+            vec![
+                ("First.html", &SweepOutcome::Imported(library.clone(), first_path.clone())),
+                ("First (1).html", &SweepOutcome::AlreadyInLibrary(library.clone(), first_path.clone())),
+                ("Notes.html", &SweepOutcome::NotAWork),
+                ("Second.html", &SweepOutcome::Imported(library, second_path.clone())),
+            ],
+            outcomes
+        );
+        assert!(backend.exists(&first_path).await.unwrap());
+        assert!(cache.get_by_target_path("library", &second_path).await.unwrap().is_some());
+
+        // Only receipts (and what wasn't a work) are left behind.
+        let mut left: Vec<_> = std::fs::read_dir(downloads.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        assert_eq!(
+            vec![
+                "First (1).html.imported.txt",
+                "First.html.imported.txt",
+                "Notes.html",
+                "Second.html.imported.txt"
+            ],
+            left
+        );
+        let receipt = std::fs::read_to_string(downloads.path().join("First.html.imported.txt")).unwrap();
+        assert!(receipt.starts_with("Imported into library:"));
+        assert!(sweep(downloads.path(), &backend, &cache, &ctx, Cleanup::Remove).await.unwrap().len() == 1);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_dry_run() {
+        let downloads = tempfile::tempdir().unwrap();
+        let work = Generator::new(3201).generate();
+        std::fs::write(downloads.path().join("Work.html"), &work.html).unwrap();
+
+        let backend: BackendHandle = Arc::new(MockBackend::default().with_name("library"));
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::Gzip, None).with_mode(Mode::DryRun);
+        let swept = sweep(downloads.path(), &backend, &cache, &ctx, Cleanup::Remove).await.unwrap();
+        let path = PathBuf::from(format!("{}.html.gz", work.expected.work_id));
+        assert_eq!(SweepOutcome::Imported("library".to_string(), path.clone()), swept[0].outcome);
+
+        // Nothing was imported, recorded, or cleaned up.
+        assert!(backend.list(None).await.unwrap().is_empty());
+        assert!(cache.get_by_target_path("library", &path).await.unwrap().is_none());
+        assert!(downloads.path().join("Work.html").exists());
+    }
+
+    #[tokio::test]
+    async fn test_sweep_tombstoned() {
+        let downloads = tempfile::tempdir().unwrap();
+        let work = Generator::new(3204).generate();
+        std::fs::write(downloads.path().join("Work.html"), &work.html).unwrap();
+
+        let backend: BackendHandle = Arc::new(MockBackend::default().with_name("library"));
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        cache.add_tombstone(work.expected.work_id, None).await.unwrap();
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::Gzip, None);
+        let swept = sweep(downloads.path(), &backend, &cache, &ctx, Cleanup::Remove).await.unwrap();
+        assert_eq!(SweepOutcome::Tombstoned(work.expected.work_id), swept[0].outcome);
+        assert!(backend.list(None).await.unwrap().is_empty());
+        assert!(downloads.path().join("Work.html").exists());
+    }
+
+    #[tokio::test]
+    async fn test_sweep_organize_failed() {
+        let downloads = tempfile::tempdir().unwrap();
+        let work = Generator::new(3178).generate();
+        std::fs::write(downloads.path().join("Work.html"), &work.html).unwrap();
+
+        let backend: BackendHandle = Arc::new(MockBackend::default().with_name("library"));
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        // Parses, but can't generate a path.
+        let report = downloads.path().join("report.json");
+        let broken = Context::new("{% include \"missing\" %}".parse().unwrap(), Compression::Gzip, None)
+            .with_import_report(&report);
+        std::fs::write(downloads.path().join("Second.html"), Generator::new(3179).generate().html).unwrap();
+        let swept = sweep(downloads.path(), &backend, &cache, &broken, Cleanup::Remove).await.unwrap();
+        // Both were tried.
+        assert_eq!(2, swept.len());
+        assert!(
+            swept
+                .iter()
+                .all(|swept| matches!(&swept.outcome, SweepOutcome::Failed(reason) if reason.contains("RAWR-IMPORT-")))
+        );
+        let saved: ImportReport = serde_json::from_slice(&std::fs::read(&report).unwrap()).unwrap();
+        assert_eq!(2, saved.summary.failed);
+        std::fs::remove_file(downloads.path().join("Second.html")).unwrap();
+        // The staged copy is gone (and unrecorded), and the download untouched.
+        assert!(backend.list(None).await.unwrap().is_empty());
+        assert!(cache.list_files_for_target("library").await.unwrap().is_empty());
+        assert!(downloads.path().join("Work.html").exists());
+
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::Gzip, None);
+        let swept = sweep(downloads.path(), &backend, &cache, &ctx, Cleanup::Remove).await.unwrap();
+        assert!(matches!(swept[0].outcome, SweepOutcome::Imported(..)));
+    }
+
+    #[tokio::test]
+    async fn test_sweep_damaged() {
+        let downloads = tempfile::tempdir().unwrap();
+        let work = Generator::new(3206).generate();
+        let html = work.html.trim_end().strip_suffix("</html>").unwrap();
+        std::fs::write(downloads.path().join("Work.html"), html).unwrap();
+        std::fs::write(downloads.path().join("Notes.html"), "<p>Not a work</p>").unwrap();
+
+        let backend: BackendHandle = Arc::new(MockBackend::default().with_name("library"));
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::None, None).with_download_validation(true);
+        let swept = sweep(downloads.path(), &backend, &cache, &ctx, Cleanup::Remove).await.unwrap();
+        assert_eq!((SweepOutcome::NotAWork, None), (swept[0].outcome.clone(), swept[0].validation.clone()));
+        // Damaged downloads are imported all the same.
+        assert!(matches!(swept[1].outcome, SweepOutcome::Imported(..)));
+        assert!(swept[1].validation.as_ref().unwrap().issues.contains(&Issue::Truncated));
+    }
+}
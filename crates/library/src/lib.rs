@@ -1,16 +1,46 @@
+mod availability;
+mod buffers;
+pub mod bundle;
+mod concurrency;
 pub(crate) mod conflict;
 pub mod error;
 pub mod import;
 pub mod organize;
+mod progress;
+mod route;
 pub mod scan;
+pub mod schedule;
+mod snapshot;
+mod tagging;
 mod template;
+mod trash;
+pub mod verify;
 
-pub use crate::template::PathGenerator;
+pub use crate::availability::{Availability, best_available_for_work_id};
+pub use crate::buffers::{BufferPool, DEFAULT_BUFFER_POOL_BYTES};
+use crate::organize::readahead::ReadAhead;
+use crate::organize::{DuplicateBasis, DuplicatePolicy};
+pub use crate::progress::{Bytes, Progress};
+pub use crate::route::LanguageRoute;
+pub use crate::scan::get_version;
+pub use crate::snapshot::{RollbackReport, Snapshot, SnapshotFile, Unrecoverable, rollback, snapshot};
+pub use crate::tagging::TagField;
+pub use crate::template::{PathCompat, PathGenerator};
+pub use crate::trash::purge_trash;
+use rawr_cache::Repository;
 use rawr_compress::Compression;
-use rawr_storage::BackendHandle;
+use rawr_extract::models::Version;
+use rawr_storage::{BackendHandle, Mode, ObjectTags};
+use std::collections::HashMap;
+use std::path::PathBuf;
+/// Cancels a streaming operation ([`scan`](scan::scan), [`organize`](organize::organize)
+/// or [`verify`](verify::verify)) gracefully, letting files in progress finish.
+pub use tokio_util::sync::CancellationToken;
 
 /// Maximum number of files being concurrently processed. Futures beyond this
 /// limit are queued in memory and promoted as in-flight extractions complete.
+/// Scans and organizes keep fewer in flight when the backend is slow to keep
+/// up (see `concurrency`).
 pub(crate) const MAX_PROCESS_CONCURRENCY: usize = 100;
 
 /// Shared configuration for a file importing/organizing passes.
@@ -18,10 +48,33 @@ pub(crate) const MAX_PROCESS_CONCURRENCY: usize = 100;
 /// Bundles the [`PathGenerator`] template, optional desired [`Compression`]
 /// format, and an optional trash [`BackendHandle`] used to preserve
 /// irreconcilable duplicates instead of permanently discarding them.
+///
+/// Multilingual libraries can additionally route works to a different target
+/// and/or path prefix by language; see [`with_language_route`](Self::with_language_route).
+/// What happens to duplicate content within a target is decided per target;
+/// see [`with_duplicate_policy`](Self::with_duplicate_policy). So are the
+/// naming rules paths must follow; see [`with_path_compat`](Self::with_path_compat),
+/// and the tags files are given; see [`with_object_tags`](Self::with_object_tags).
+///
+/// A [dry run](Self::with_mode) changes nothing: not the targets, the trash,
+/// the cache, the journal, nor the downloads swept.
 pub struct Context {
     template: PathGenerator,
     compression: Option<Compression>,
     trash: Option<BackendHandle>,
+    languages: HashMap<String, LanguageRoute>,
+    fallback: Option<LanguageRoute>,
+    read_ahead: Option<ReadAhead>,
+    duplicates: HashMap<String, DuplicatePolicy>,
+    duplicate_bases: HashMap<String, DuplicateBasis>,
+    path_compat: HashMap<String, PathCompat>,
+    object_tags: HashMap<String, Vec<TagField>>,
+    repair_encoding: bool,
+    validate_downloads: bool,
+    route_detected_language: Option<u8>,
+    journal: Option<PathBuf>,
+    import_report: Option<PathBuf>,
+    mode: Mode,
 }
 impl Context {
     /// Creates a new organization context.
@@ -42,6 +95,272 @@ impl Context {
             template,
             compression: compression.into(),
             trash: trash.into(),
+            languages: HashMap::new(),
+            fallback: None,
+            read_ahead: None,
+            duplicates: HashMap::new(),
+            duplicate_bases: HashMap::new(),
+            path_compat: HashMap::new(),
+            object_tags: HashMap::new(),
+            repair_encoding: false,
+            validate_downloads: false,
+            route_detected_language: None,
+            journal: None,
+            import_report: None,
+            mode: Mode::Real,
+        }
+    }
+
+    /// Routes works whose language has the ISO 639 code `iso_code` (e.g.
+    /// `"fr"`; case-insensitive) according to `route` when organizing.
+    pub fn with_language_route(mut self, iso_code: impl AsRef<str>, route: LanguageRoute) -> Self {
+        self.languages.insert(iso_code.as_ref().to_ascii_lowercase(), route.with_mode(self.mode));
+        self
+    }
+
+    /// Routes works by the language their text was [detected](rawr_extract::models::DetectedLanguage)
+    /// to be written in (when detected with at least `min_confidence`, from 0
+    /// to 100), rather than the language they were declared as.
+    ///
+    /// Works without a detected language are routed by their declared one.
+    pub fn with_detected_language_routing(mut self, min_confidence: u8) -> Self {
+        self.route_detected_language = Some(min_confidence);
+        self
+    }
+
+    /// Routes works in any language without its own route (including works
+    /// whose language has no known ISO 639 code) according to `route`.
+    ///
+    /// Without a fallback, such works stay on the target being organized.
+    pub fn with_fallback_route(mut self, route: LanguageRoute) -> Self {
+        self.fallback = Some(route.with_mode(self.mode));
+        self
+    }
+
+    /// Prefetches up to `files` source files (using at most `bytes` of memory)
+    /// while other files are being organized.
+    ///
+    /// Only files that need reading are prefetched: those being re-compressed
+    /// or transferred to another target. This mostly benefits high-latency
+    /// backends (such as S3), where reading a file can take as long as
+    /// re-compressing it. A single file larger than `bytes` is still
+    /// prefetched, but only when nothing else is. Zero `files` disables
+    /// read-ahead.
+    pub fn with_read_ahead(mut self, files: usize, bytes: u64) -> Self {
+        self.read_ahead = (files > 0).then(|| ReadAhead::new(files, bytes));
+        self
+    }
+
+    /// Applies `policy` to duplicate content found on the target named
+    /// `target` after organizing it. Targets default to
+    /// [`DuplicatePolicy::Allow`].
+    pub fn with_duplicate_policy(mut self, target: impl Into<String>, policy: DuplicatePolicy) -> Self {
+        self.duplicates.insert(target.into(), policy);
+        self
+    }
+
+    /// Decides what counts as duplicate content on the target named `target`
+    /// by `basis` (see [`with_duplicate_policy`](Self::with_duplicate_policy)).
+    /// Targets default to [`DuplicateBasis::ContentHash`].
+    pub fn with_duplicate_basis(mut self, target: impl Into<String>, basis: DuplicateBasis) -> Self {
+        self.duplicate_bases.insert(target.into(), basis);
+        self
+    }
+
+    /// Makes paths generated for the target named `target` follow `compat`'s
+    /// naming rules (for example, when the target is an SMB share). Targets
+    /// default to [`PathCompat::Posix`].
+    pub fn with_path_compat(mut self, target: impl Into<String>, compat: PathCompat) -> Self {
+        self.path_compat.insert(target.into(), compat);
+        self
+    }
+
+    /// Tags files organized onto the target named `target` with `fields` of
+    /// their work's metadata (see [`ObjectTags`]); duplicate fields are
+    /// ignored. Targets default to no tags, and only backends with object
+    /// tags (S3) apply them.
+    ///
+    /// Failing to tag a file is logged, and doesn't fail organizing it.
+    pub fn with_object_tags(mut self, target: impl Into<String>, fields: impl IntoIterator<Item = TagField>) -> Self {
+        let mut unique = Vec::new();
+        for field in fields {
+            if !unique.contains(&field) {
+                unique.push(field);
+            }
+        }
+        self.object_tags.insert(target.into(), unique);
+        self
+    }
+
+    /// Re-encodes files that scanning found weren't UTF-8 (see
+    /// [`Encoding`](rawr_extract::Encoding)) as UTF-8 while organizing them,
+    /// replacing their cache records with those of the repaired content.
+    /// Only scans that repair encodings (see [`scan_file`](scan::scan_file))
+    /// find such files; organizing a file not yet scanned scans it so.
+    ///
+    /// Off by default: repairing is heuristic, and the original file is
+    /// overwritten.
+    pub fn with_encoding_repair(mut self, enabled: bool) -> Self {
+        self.repair_encoding = enabled;
+        self
+    }
+
+    /// Checks each download [swept](import::sweep) in depth (see
+    /// [`validate_deep`](rawr_extract::validate_deep)), reporting what's
+    /// wrong with damaged ones (which are imported all the same).
+    ///
+    /// Off by default: it parses every download a second time.
+    pub fn with_download_validation(mut self, enabled: bool) -> Self {
+        self.validate_downloads = enabled;
+        self
+    }
+
+    /// Journals each run of [`organize`](organize::organize) to a new JSON
+    /// Lines file in `dir`: every file organized (with where it was and is
+    /// now, its hashes, and what happened) and every duplicate found. See
+    /// [`JournalEntry`](organize::JournalEntry).
+    pub fn with_journal(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.journal = Some(dir.into());
+        self
+    }
+
+    /// Saves an [`ImportReport`](import::ImportReport) of each [sweep](import::sweep)
+    /// to `path` (replacing the last one's), for scripts to check. Dry runs
+    /// are reported too, with the outcomes they would have had.
+    pub fn with_import_report(mut self, path: impl Into<PathBuf>) -> Self {
+        self.import_report = Some(path.into());
+        self
+    }
+
+    /// Makes changes, or in a [dry run](Mode::DryRun) only logs them: every
+    /// operation given this context (and its trash and route targets) goes
+    /// through the motions without writing to a target, the cache, or the
+    /// local filesystem, whether or not the backend and cache it's given are
+    /// themselves dry runs.
+    ///
+    /// A dry run can't be made real again: its trash and route targets stay
+    /// wrapped, so [`Mode::Real`] leaves a dry-run context as it is.
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        if self.mode.is_dry_run() || !mode.is_dry_run() {
+            return self;
+        }
+        self.mode = mode;
+        self.trash = self.trash.map(|trash| mode.backend(trash));
+        for route in self.languages.values_mut().chain(self.fallback.as_mut()) {
+            *route = std::mem::take(route).with_mode(mode);
         }
+        self
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The backend and cache for an operation to use: those given, or in a
+    /// dry run ones that change nothing.
+    pub(crate) fn guard(&self, backend: &BackendHandle, cache: &Repository) -> (BackendHandle, Repository) {
+        match self.mode {
+            Mode::Real => (backend.clone(), cache.clone()),
+            Mode::DryRun => (self.mode.backend(backend.clone()), cache.clone().with_mode(self.mode)),
+        }
+    }
+
+    /// The duplicate policy of a target.
+    pub(crate) fn duplicate_policy(&self, target: &str) -> DuplicatePolicy {
+        self.duplicates.get(target).copied().unwrap_or_default()
+    }
+
+    /// What counts as duplicate content on a target.
+    pub(crate) fn duplicate_basis(&self, target: &str) -> DuplicateBasis {
+        self.duplicate_bases.get(target).copied().unwrap_or_default()
+    }
+
+    /// The path naming rules of a target.
+    pub(crate) fn path_compat(&self, target: &str) -> PathCompat {
+        self.path_compat.get(target).copied().unwrap_or_default()
+    }
+
+    /// The tags for a version's file on a target, if the target has any.
+    pub(crate) fn object_tags(&self, target: &str, version: &Version) -> Option<ObjectTags> {
+        let fields = self.object_tags.get(target).filter(|fields| !fields.is_empty())?;
+        Some(tagging::object_tags(version, fields))
+    }
+
+    /// Where a version organized from `backend` in `compression` belongs: on
+    /// `backend` (unless [routed](Self::with_language_route) elsewhere), at
+    /// the path the template (and route) give it.
+    pub(crate) fn location<'a>(
+        &'a self,
+        backend: &'a BackendHandle,
+        version: &Version,
+        compression: Compression,
+    ) -> error::Result<(&'a BackendHandle, PathBuf)> {
+        let route = self.route(version);
+        let destination = route.map_or(backend, |route| route.destination(backend));
+        let path =
+            self.template.generate_with_compat(version, "html", compression, self.path_compat(destination.name()))?;
+        Ok(match route {
+            Some(route) => (destination, route.apply(path)),
+            None => (destination, path),
+        })
+    }
+
+    /// The route that applies to a version, if any.
+    pub(crate) fn route(&self, version: &Version) -> Option<&LanguageRoute> {
+        let detected = self
+            .route_detected_language
+            .and_then(|min| version.detected_language.as_ref().filter(|d| d.confidence >= min));
+        let language = detected.map_or(&version.metadata.language, |d| &d.language);
+        self.route_for_language(language.iso_code.as_deref())
+    }
+
+    fn route_for_language(&self, iso_code: Option<&str>) -> Option<&LanguageRoute> {
+        iso_code.and_then(|code| self.languages.get(&code.to_ascii_lowercase())).or(self.fallback.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_extract::models::{DetectedLanguage, Language};
+    use std::path::{Path, PathBuf};
+
+    fn route_path(ctx: &Context, iso_code: Option<&str>) -> Option<PathBuf> {
+        ctx.route_for_language(iso_code).map(|route| route.apply(PathBuf::from("123.html")))
+    }
+
+    #[test]
+    fn test_language_routes() {
+        let ctx = Context::new("{{ work }}".parse().unwrap(), None, None)
+            .with_language_route("FR", LanguageRoute::prefix("french"))
+            .with_language_route("ja", LanguageRoute::prefix("japanese"));
+        assert_eq!(route_path(&ctx, Some("fr")).unwrap(), Path::new("french/123.html"));
+        assert_eq!(route_path(&ctx, Some("JA")).unwrap(), Path::new("japanese/123.html"));
+        assert!(route_path(&ctx, Some("en")).is_none());
+        assert!(route_path(&ctx, None).is_none());
+
+        let ctx = ctx.with_fallback_route(LanguageRoute::prefix("other"));
+        assert_eq!(route_path(&ctx, Some("en")).unwrap(), Path::new("other/123.html"));
+        assert_eq!(route_path(&ctx, None).unwrap(), Path::new("other/123.html"));
+        assert_eq!(route_path(&ctx, Some("fr")).unwrap(), Path::new("french/123.html"));
+    }
+
+    #[test]
+    fn test_detected_language_routes() {
+        let html = rawr_extract::testing::Generator::new(3170).generate().html;
+        let mut version = rawr_extract::extract(&html).unwrap();
+        version.metadata.language = Language::new("English");
+        version.detected_language = Some(DetectedLanguage {
+            language: Language::new("Français"),
+            confidence: 80,
+        });
+        let route = |ctx: &Context| ctx.route(&version).map(|route| route.apply(PathBuf::from("123.html")));
+        let ctx = Context::new("{{ work }}".parse().unwrap(), None, None)
+            .with_language_route("fr", LanguageRoute::prefix("french"));
+        assert!(route(&ctx).is_none());
+        let ctx = ctx.with_detected_language_routing(50);
+        assert_eq!(Some(PathBuf::from("french/123.html")), route(&ctx));
+        let ctx = ctx.with_detected_language_routing(90);
+        assert!(route(&ctx).is_none());
     }
 }
@@ -0,0 +1,317 @@
+//! Duplicate content within a single target.
+//!
+//! The same version on a primary and a backup target is intentional, but two
+//! copies of it on the same target are wasted space. Organizing already
+//! discards duplicates that collide on the same path; the ones left over
+//! differ in compression (when the [`Context`] doesn't convert everything to
+//! one format), or live outside the template's paths. What happens to those
+//! is decided by the target's [`DuplicatePolicy`], and what counts as a
+//! duplicate by its [`DuplicateBasis`].
+
+use crate::Context;
+use crate::conflict::trash;
+use crate::organize::error::{ErrorKind as OrganizeErrorKind, Result as OrganizeResult};
+use crate::scan::is_member;
+use exn::ResultExt;
+use rawr_cache::Repository;
+use rawr_extract::models::Version;
+use rawr_storage::BackendHandle;
+use rawr_storage::file::{FileInfo, Processed};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// What organizing does with files on the same target that have identical
+/// content, configured per target with [`Context::with_duplicate_policy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Leave duplicates alone (the default).
+    #[default]
+    Allow,
+    /// Report duplicates, but leave them alone.
+    Warn,
+    /// Keep one copy and remove the others (moving them to the context's
+    /// trash, if it has one).
+    ///
+    /// The copy kept is the one in the context's preferred compression, then
+    /// the one at its template-conforming path, then the smallest.
+    Resolve,
+}
+
+/// What makes files on the same target duplicates of each other, configured
+/// per target with [`Context::with_duplicate_basis`].
+///
+/// The right choice depends on what the target is for: a target synced to a
+/// backup wants to keep a re-compressed copy, but not to waste space on a
+/// second download of the same work.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateBasis {
+    /// Byte-identical files (the same content, in the same compression).
+    FileHash,
+    /// Identical (decompressed) content, whatever the compression (the
+    /// default).
+    #[default]
+    ContentHash,
+    /// The same chapter text, ignoring markup, whitespace and the Archive's
+    /// boilerplate (see [`normalized_hash`](rawr_extract::normalized_hash)):
+    /// downloads of the same chapters made at different times, too.
+    ///
+    /// Files of the same work with different content are read to tell.
+    NormalizedContent,
+}
+
+/// Files on one target that are duplicates of each other (by the target's
+/// [`DuplicateBasis`]), found after organizing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicates {
+    /// The content hash of the copy that was kept.
+    pub content_hash: String,
+    /// The copy that was kept (or, when only warning, would be).
+    pub kept: PathBuf,
+    /// The other copies.
+    pub duplicates: Vec<PathBuf>,
+    /// Whether the other copies were removed (by [`DuplicatePolicy::Resolve`]).
+    pub resolved: bool,
+}
+
+/// Finds duplicates on the target of `backend` (by `basis`), resolving each
+/// set of duplicates according to `policy`.
+///
+/// Returns one result per set of duplicates, so that failing to resolve one
+/// doesn't prevent resolving the others.
+pub(crate) async fn deduplicate(
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    policy: DuplicatePolicy,
+    basis: DuplicateBasis,
+) -> OrganizeResult<Vec<OrganizeResult<Duplicates>>> {
+    if policy == DuplicatePolicy::Allow {
+        return Ok(Vec::new());
+    }
+    let mut files = cache.list_files_for_target(backend.name()).await.or_raise(|| OrganizeErrorKind::Cache)?;
+    // Files inside archives can't be removed on their own.
+    files.retain(|(file, _)| !is_member(&file.path));
+    let keys = match basis {
+        DuplicateBasis::FileHash => files.iter().map(|(file, _)| file.file_hash.clone()).collect(),
+        DuplicateBasis::ContentHash => files.iter().map(|(file, _)| file.content_hash.clone()).collect(),
+        DuplicateBasis::NormalizedContent => normalized_keys(backend, &files).await,
+    };
+    let mut by_key: HashMap<String, Vec<(FileInfo<Processed>, Version)>> = HashMap::new();
+    for (key, file) in keys.into_iter().zip(files) {
+        by_key.entry(key).or_default().push(file);
+    }
+    let mut sets: Vec<_> = by_key.into_values().filter(|files| files.len() > 1).collect();
+    sets.sort_by(|a, b| a[0].0.path.cmp(&b[0].0.path));
+
+    let mut results = Vec::with_capacity(sets.len());
+    for mut files in sets {
+        files.sort_by_cached_key(|(file, version)| {
+            (
+                ctx.compression.is_some_and(|c| c != file.compression),
+                !conforms(backend, ctx, file, version),
+                file.size,
+                file.path.clone(),
+            )
+        });
+        let mut files = files.into_iter().map(|(file, _)| file);
+        let kept = files.next().expect("sets of duplicates have at least two files");
+        let others: Vec<_> = files.collect();
+        tracing::warn!(
+            target = backend.name(),
+            kept = %kept.path.display(),
+            duplicates = others.len(),
+            content_hash = kept.content_hash,
+            "Found duplicate content within target"
+        );
+        let result = match policy {
+            DuplicatePolicy::Resolve => remove(backend, cache, ctx, &others).await.map(|()| true),
+            _ => Ok(false),
+        };
+        results.push(result.map(|resolved| Duplicates {
+            content_hash: kept.content_hash.clone(),
+            kept: kept.path.clone(),
+            duplicates: others.into_iter().map(|f| f.path.clone()).collect(),
+            resolved,
+        }));
+    }
+    Ok(results)
+}
+
+/// Keys identifying files by their [normalized](rawr_extract::normalized_hash)
+/// content, in the same order as `files`.
+///
+/// Only files of works with more than one version on the target are read
+/// (one file per version); other files are keyed by their content hash, as
+/// are files that can't be read.
+async fn normalized_keys(backend: &BackendHandle, files: &[(FileInfo<Processed>, Version)]) -> Vec<String> {
+    let mut versions: HashMap<u64, HashSet<&str>> = HashMap::new();
+    for (file, version) in files {
+        versions.entry(version.metadata.work_id).or_default().insert(&file.content_hash);
+    }
+    let mut normalized: HashMap<&str, String> = HashMap::new();
+    let mut keys = Vec::with_capacity(files.len());
+    for (file, version) in files {
+        let content_hash = file.content_hash.as_str();
+        if versions[&version.metadata.work_id].len() < 2 {
+            keys.push(file.content_hash.clone());
+            continue;
+        }
+        if !normalized.contains_key(content_hash) {
+            let read = async {
+                let data = backend.read(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)?;
+                let html = file.compression.decompress(&data).or_raise(|| OrganizeErrorKind::Compression)?;
+                OrganizeResult::Ok(rawr_extract::normalized_hash(version.encoding.decode(&html)))
+            };
+            match read.await {
+                Ok(hash) => _ = normalized.insert(content_hash, format!("normalized:{hash}")),
+                Err(e) => {
+                    tracing::warn!(path = %file.path.display(), error = ?e, "Could not read file to normalize its content");
+                    keys.push(file.content_hash.clone());
+                    continue;
+                },
+            }
+        }
+        keys.push(normalized[content_hash].clone());
+    }
+    keys
+}
+
+/// Whether a file is at the path the template gives it (in its current
+/// compression, on this target).
+fn conforms(backend: &BackendHandle, ctx: &Context, file: &FileInfo<Processed>, version: &Version) -> bool {
+    let route = ctx.route(version);
+    if route.is_some_and(|route| route.destination(backend).name() != backend.name()) {
+        return false;
+    }
+    let compat = ctx.path_compat(backend.name());
+    let Ok(mut location) = ctx.template.generate_with_compat(version, "html", file.compression, compat) else {
+        return false;
+    };
+    if let Some(route) = route {
+        location = route.apply(location);
+    }
+    file.path == location
+}
+
+async fn remove(
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    files: &[FileInfo<Processed>],
+) -> OrganizeResult<()> {
+    for file in files {
+        match ctx.trash.as_ref() {
+            Some(t) => trash(backend, t, file).await.or_raise(|| OrganizeErrorKind::Storage)?,
+            None => backend.delete(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)?,
+        }
+        // As elsewhere, a dangling record is cleaned up by the next scan.
+        _ = cache.delete_by_target_path(&file.target, &file.path).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::Database;
+    use rawr_compress::Compression;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::backend::MockBackend;
+    use std::sync::Arc;
+    use time::UtcDateTime;
+
+    #[tokio::test]
+    async fn test_deduplicate() {
+        let html = Generator::new(3160).generate().html;
+        let version = rawr_extract::extract(&html).unwrap();
+        let plain = PathBuf::from(format!("{}.html", version.metadata.work_id));
+        let gzipped = PathBuf::from(format!("{}.html.gz", version.metadata.work_id));
+        let compressed = Compression::Gzip.compress(html.as_bytes()).unwrap();
+        let backend: BackendHandle = Arc::new(
+            MockBackend::with_data([(&plain, html.as_bytes().to_vec()), (&gzipped, compressed.clone())])
+                .with_name("library"),
+        );
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        for (path, compression, size) in [
+            (&plain, Compression::None, html.len()),
+            (&gzipped, Compression::Gzip, compressed.len()),
+        ] {
+            let file = FileInfo::new("library", path, size as u64, UtcDateTime::now(), compression)
+                .with_file_hash(path.to_string_lossy())
+                .with_content_hash(&version.hash);
+            cache.upsert(&file, &version).await.unwrap();
+        }
+        // Keep uncompressed files, despite them being larger.
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::None, None);
+
+        assert!(
+            deduplicate(&backend, &cache, &ctx, DuplicatePolicy::Allow, DuplicateBasis::ContentHash)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let warned =
+            deduplicate(&backend, &cache, &ctx, DuplicatePolicy::Warn, DuplicateBasis::ContentHash).await.unwrap();
+        let expected = Duplicates {
+            content_hash: version.hash.clone(),
+            kept: plain.clone(),
+            duplicates: vec![gzipped.clone()],
+            resolved: false,
+        };
+        assert_eq!(expected, *warned[0].as_ref().unwrap());
+        assert!(backend.exists(&gzipped).await.unwrap());
+
+        let resolved =
+            deduplicate(&backend, &cache, &ctx, DuplicatePolicy::Resolve, DuplicateBasis::ContentHash).await.unwrap();
+        assert_eq!(Duplicates { resolved: true, ..expected }, *resolved[0].as_ref().unwrap());
+        assert!(!backend.exists(&gzipped).await.unwrap());
+        assert!(backend.exists(&plain).await.unwrap());
+        assert_eq!(1, cache.list_files_for_target("library").await.unwrap().len());
+        assert!(
+            deduplicate(&backend, &cache, &ctx, DuplicatePolicy::Resolve, DuplicateBasis::ContentHash)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_basis() {
+        let html = Generator::new(3185).generate().html;
+        // A later download of the same chapters, formatted differently.
+        let redownload = html.replacen("<body>", "<body>\n\n", 1);
+        let first = rawr_extract::extract(&html).unwrap();
+        let second = rawr_extract::extract(&redownload).unwrap();
+        assert_ne!(first.hash, second.hash);
+        let compressed = Compression::Gzip.compress(html.as_bytes()).unwrap();
+        let files = [
+            ("a.html", html.as_bytes().to_vec(), Compression::None, &first),
+            ("a.html.gz", compressed, Compression::Gzip, &first),
+            ("b.html", redownload.as_bytes().to_vec(), Compression::None, &second),
+        ];
+        let backend: BackendHandle = Arc::new(
+            MockBackend::with_data(files.iter().map(|(path, data, ..)| (PathBuf::from(path), data.clone())))
+                .with_name("library"),
+        );
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        for (path, data, compression, version) in &files {
+            let file = FileInfo::new("library", path, data.len() as u64, UtcDateTime::now(), *compression)
+                .with_file_hash(blake3::hash(data).to_string())
+                .with_content_hash(&version.hash);
+            cache.upsert(&file, version).await.unwrap();
+        }
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::None, None);
+        let duplicates = async |basis| {
+            let found = deduplicate(&backend, &cache, &ctx, DuplicatePolicy::Warn, basis).await.unwrap();
+            let mut found: Vec<_> = found.into_iter().map(|d| d.unwrap().duplicates.len()).collect();
+            found.sort();
+            found
+        };
+
+        assert!(duplicates(DuplicateBasis::FileHash).await.is_empty());
+        assert_eq!(vec![1], duplicates(DuplicateBasis::ContentHash).await);
+        assert_eq!(vec![2], duplicates(DuplicateBasis::NormalizedContent).await);
+    }
+}
@@ -8,6 +8,10 @@
 //!       to resort to anyhow+thiserror just because I don't want to deal with it.
 
 use derive_more::{Display, Error};
+use rawr_cache::error::ErrorKind as CacheErrorKind;
+pub use rawr_error::{Code, Domain, ErrorCode};
+use rawr_storage::ByteSize;
+use std::ops::Deref;
 
 /// An organize error with automatic location tracking via [`exn::Exn`].
 pub type Error = exn::Exn<ErrorKind>;
@@ -22,11 +26,15 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// ### Operational Errors
 /// - [`ErrorKind::Template`]
 /// - [`ErrorKind::Conflict`]
+/// - [`ErrorKind::Locked`]
+/// - [`ErrorKind::Journal`]
+/// - [`ErrorKind::InsufficientSpace`]
 ///
 /// ### Dependency Errors
 /// - [`ErrorKind::Compression`]
 /// - [`ErrorKind::Cache`]
 /// - [`ErrorKind::Storage`]
+/// - [`ErrorKind::Extract`]
 /// - [`ErrorKind::Scan`] - dependency error, but happened during an
 ///   implicit scan of an unknown file.
 #[derive(Debug, Display, Error)]
@@ -41,16 +49,55 @@ pub enum ErrorKind {
     Template,
     /// A scan was required to resolve a conflict but failed.
     Scan,
+    /// A re-encoded file could no longer be extracted.
+    Extract,
     /// Recursive conflict resolution exceeded the depth limit or encountered
     /// an irreconcilable collision.
     Conflict,
+    /// The target is locked by another operation (or the lock was lost
+    /// part-way through).
+    Locked,
+    /// The [journal](crate::Context::with_journal) could not be created or
+    /// written to.
+    Journal,
+    /// The target doesn't have the space organizing it could take up (see
+    /// [`PlanReport::space_needed`](crate::organize::PlanReport::space_needed));
+    /// nothing was organized.
+    #[display("not enough space on target: {needed} needed, {available} available")]
+    InsufficientSpace { needed: ByteSize, available: ByteSize },
 }
 
 impl ErrorKind {
     /// Returns `true` if retrying might succeed.
     pub fn is_retryable(&self) -> bool {
-        match self {
-            _ => false,
+        // Once the other operation finishes (or its lock expires).
+        matches!(self, Self::Locked)
+    }
+
+    /// Classifies a failure to take (or keep) the target lock: the target
+    /// being locked by another operation, or the cache itself failing.
+    pub(crate) fn from_lock(e: &rawr_cache::error::Error) -> Self {
+        match e.deref() {
+            CacheErrorKind::Locked(_) | CacheErrorKind::LockLost(_) => Self::Locked,
+            _ => Self::Cache,
         }
     }
 }
+
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::Conflict => 409,
+            Self::Template => 422,
+            Self::Locked => 423,
+            Self::InsufficientSpace { .. } => 507,
+            Self::Cache => 520,
+            Self::Storage => 521,
+            Self::Compression => 522,
+            Self::Extract => 523,
+            Self::Scan => 524,
+            Self::Journal => 525,
+        };
+        Code::new(Domain::Organize, number)
+    }
+}
@@ -0,0 +1,252 @@
+//! Forecasting the payoff of re-compressing a target.
+//!
+//! Organizing with a [`Context`] that sets a compression format re-compresses
+//! every file stored in any other format: for a large target, hours of work
+//! that may or may not save much space. [`estimate`] re-compresses a sample
+//! of those files in memory (nothing is written), and extrapolates to the
+//! whole target.
+
+use crate::Context;
+use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
+use crate::organize::error::{ErrorKind as OrganizeErrorKind, Result as OrganizeResult};
+use crate::organize::file::convert;
+use crate::scan::is_member;
+use exn::ResultExt;
+use rawr_cache::Repository;
+use rawr_storage::BackendHandle;
+use rawr_storage::file::{FileInfo, Processed};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Files of similar size (within a power of two), and how the sample of them
+/// re-compressed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EstimateBucket {
+    /// Smallest file size (inclusive) in the bucket.
+    pub min_size: u64,
+    /// Largest file size (inclusive) in the bucket.
+    pub max_size: u64,
+    /// Files in the bucket that would be re-compressed.
+    pub files: u64,
+    /// Total size of those files, as currently stored.
+    pub bytes: u64,
+    /// Files that were sampled.
+    pub sampled: u64,
+    /// Total size of the sampled files, as currently stored.
+    pub sampled_bytes: u64,
+    /// Total size of the sampled files once re-compressed.
+    pub sampled_converted_bytes: u64,
+    /// Time spent re-compressing the sampled files.
+    pub sampled_time: Duration,
+}
+impl EstimateBucket {
+    /// Expected total size of the bucket's files once re-compressed.
+    pub fn estimated_bytes(&self) -> u64 {
+        self.extrapolate(u128::from(self.sampled_converted_bytes)) as u64
+    }
+
+    /// Expected time to re-compress all of the bucket's files.
+    pub fn estimated_time(&self) -> Duration {
+        Duration::from_nanos(self.extrapolate(self.sampled_time.as_nanos()) as u64)
+    }
+
+    /// Scales a measurement of the sample up to the whole bucket, by size.
+    fn extrapolate(&self, measured: u128) -> u128 {
+        match self.sampled_bytes {
+            0 => 0,
+            sampled => measured * u128::from(self.bytes) / u128::from(sampled),
+        }
+    }
+}
+
+/// The expected outcome of re-compressing a target, from [`estimate`].
+///
+/// Times are measured on this machine, and don't include reading or writing
+/// files; on slow storage (such as S3), transferring them can take longer
+/// than re-compressing them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Estimate {
+    /// Files that would be re-compressed, grouped by size (smallest first).
+    pub buckets: Vec<EstimateBucket>,
+    /// Sampled files that couldn't be read or re-compressed (and were left
+    /// out of the estimate).
+    pub failed: u64,
+}
+impl Estimate {
+    /// Number of files that would be re-compressed.
+    pub fn files(&self) -> u64 {
+        self.buckets.iter().map(|b| b.files).sum()
+    }
+
+    /// Total size of the files that would be re-compressed, as currently stored.
+    pub fn bytes(&self) -> u64 {
+        self.buckets.iter().map(|b| b.bytes).sum()
+    }
+
+    /// Expected total size of those files once re-compressed.
+    pub fn estimated_bytes(&self) -> u64 {
+        self.buckets.iter().map(EstimateBucket::estimated_bytes).sum()
+    }
+
+    /// Expected space saved; negative if re-compressing would take up more.
+    pub fn estimated_savings(&self) -> i64 {
+        self.bytes() as i64 - self.estimated_bytes() as i64
+    }
+
+    /// Expected (CPU) time to re-compress all of the files.
+    pub fn estimated_time(&self) -> Duration {
+        self.buckets.iter().map(EstimateBucket::estimated_time).sum()
+    }
+}
+
+/// Estimates the space saved (and time taken) by re-compressing the files on
+/// the target of `backend` that organizing with `ctx` would re-compress.
+///
+/// Known files (from the `cache`) are grouped by size, and up to
+/// `samples_per_bucket` files of each group, spread across it, are read and
+/// re-compressed in memory. Nothing is written to the target or the cache. A
+/// context without a compression format re-compresses nothing, so its
+/// estimate is empty.
+///
+/// # Errors
+/// Returns [`Exn<LibraryErrorKind::Organize>`](LibraryErrorKind::Organize)
+/// raised from an inner [`Exn<OrganizeErrorKind>`](OrganizeErrorKind) if the
+/// target's files can't be listed; files that can't be sampled are only
+/// counted as [failed](Estimate::failed).
+pub async fn estimate(
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    samples_per_bucket: usize,
+) -> LibraryResult<Estimate> {
+    estimate_inner(backend, cache, ctx, samples_per_bucket).await.or_raise(|| LibraryErrorKind::Organize)
+}
+
+pub(crate) async fn estimate_inner(
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    samples_per_bucket: usize,
+) -> OrganizeResult<Estimate> {
+    let Some(target) = ctx.compression else {
+        return Ok(Estimate::default());
+    };
+    let files = cache.list_files_for_target(backend.name()).await.or_raise(|| OrganizeErrorKind::Cache)?;
+    let mut buckets: BTreeMap<u32, Vec<FileInfo<Processed>>> = BTreeMap::new();
+    for (file, _) in files.into_iter().filter(|(file, _)| file.compression != target && !is_member(&file.path)) {
+        buckets.entry(u64::BITS - file.size.leading_zeros()).or_default().push(file);
+    }
+
+    let mut estimate = Estimate::default();
+    for (bits, mut files) in buckets {
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut bucket = EstimateBucket {
+            min_size: if bits == 0 { 0 } else { 1 << (bits - 1) },
+            max_size: if bits == 0 { 0 } else { u64::MAX >> (u64::BITS - bits) },
+            files: files.len() as u64,
+            bytes: files.iter().map(|f| f.size).sum(),
+            ..Default::default()
+        };
+        for file in sample(&files, samples_per_bucket) {
+            let data = match backend.read(&file.path).await {
+                Ok(data) => data,
+                Err(e) => {
+                    tracing::warn!(path = %file.path.display(), error = %e, "Could not read file to sample");
+                    estimate.failed += 1;
+                    continue;
+                },
+            };
+            let started = Instant::now();
+            match convert(&data, file.compression, target) {
+                Ok(converted) => {
+                    bucket.sampled_time += started.elapsed();
+                    bucket.sampled += 1;
+                    bucket.sampled_bytes += data.len() as u64;
+                    bucket.sampled_converted_bytes += converted.data.len() as u64;
+                },
+                Err(e) => {
+                    tracing::warn!(path = %file.path.display(), error = %e, "Could not re-compress file to sample");
+                    estimate.failed += 1;
+                },
+            }
+        }
+        estimate.buckets.push(bucket);
+    }
+    tracing::debug!(
+        target = backend.name(),
+        files = estimate.files(),
+        savings = estimate.estimated_savings(),
+        "Estimated re-compression of target"
+    );
+    Ok(estimate)
+}
+
+/// Up to `n` files spread evenly across `files`.
+fn sample(files: &[FileInfo<Processed>], n: usize) -> impl Iterator<Item = &FileInfo<Processed>> {
+    let n = n.min(files.len());
+    (0..n).map(move |i| &files[i * files.len() / n])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::Database;
+    use rawr_compress::Compression;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::backend::MockBackend;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use time::UtcDateTime;
+
+    #[tokio::test]
+    async fn test_estimate() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let mut data = Vec::new();
+        for seed in 0..6 {
+            let html = Generator::new(3163 + seed).generate().html;
+            let version = rawr_extract::extract(&html).unwrap();
+            let path = PathBuf::from(format!("{}.html", version.metadata.work_id));
+            let file = FileInfo::new("library", &path, html.len() as u64, UtcDateTime::now(), Compression::None)
+                .with_file_hash(path.to_string_lossy())
+                .with_content_hash(&version.hash);
+            cache.upsert(&file, &version).await.unwrap();
+            data.push((path, html.into_bytes()));
+        }
+        // A file the cache knows about, but that's gone from the target.
+        data.pop();
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+
+        let ctx = Context::new("{{ work }}".parse().unwrap(), None, None);
+        assert_eq!(Estimate::default(), estimate(&backend, &cache, &ctx, 2).await.unwrap());
+
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::Gzip, None);
+        let estimate = estimate(&backend, &cache, &ctx, usize::MAX).await.unwrap();
+        assert_eq!(6, estimate.files());
+        assert_eq!(5, estimate.buckets.iter().map(|b| b.sampled).sum::<u64>());
+        assert_eq!(1, estimate.failed);
+        assert!(estimate.estimated_savings() > 0);
+        assert!(estimate.estimated_bytes() < estimate.bytes());
+        for bucket in &estimate.buckets {
+            assert!(bucket.min_size <= bucket.max_size);
+            assert!(bucket.bytes >= bucket.files * bucket.min_size);
+        }
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        let bucket = EstimateBucket {
+            min_size: 512,
+            max_size: 1023,
+            files: 10,
+            bytes: 8000,
+            sampled: 2,
+            sampled_bytes: 1600,
+            sampled_converted_bytes: 400,
+            sampled_time: Duration::from_millis(10),
+        };
+        assert_eq!(2000, bucket.estimated_bytes());
+        assert_eq!(Duration::from_millis(50), bucket.estimated_time());
+        assert_eq!(0, EstimateBucket::default().estimated_bytes());
+    }
+}
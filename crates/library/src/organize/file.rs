@@ -1,18 +1,20 @@
-use crate::Context;
 use crate::conflict::{ConflictResolution, handle_conflict, trash};
 use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
 use crate::organize::error::{ErrorKind as OrganizeErrorKind, Result as OrganizeResult};
 use crate::scan::error::ErrorKind as ScanErrorKind;
-use crate::scan::{Scan, file::scan_file_inner};
+use crate::scan::{Scan, file::scan_file_inner, is_member};
+use crate::{BufferPool, Bytes, Context, tagging};
 use exn::ResultExt;
 use rawr_cache::Repository;
-use rawr_compress::Compression;
+use rawr_compress::{Compression, HashingWriter};
+use rawr_extract::models::Version;
 use rawr_storage::BackendHandle;
 use rawr_storage::error::ErrorKind as StorageErrorKind;
-use rawr_storage::file::{FileInfo, HashState};
+use rawr_storage::file::{FileInfo, HashState, Processed};
 use std::io::{self, Cursor};
 use std::ops::Deref;
 use std::path::PathBuf;
+use time::UtcDateTime;
 
 /// The outcome of (successfully) organizing a single file.
 ///
@@ -22,6 +24,10 @@ use std::path::PathBuf;
 pub enum Action {
     /// File was moved (and optionally re-compressed) to the correct path.
     Renamed(PathBuf),
+    /// File was moved (and optionally re-compressed) to another target by a
+    /// [`LanguageRoute`](crate::LanguageRoute); holds the target's name and
+    /// the new path on that target.
+    Transferred(String, PathBuf),
     /// File was already at the correct path; no work performed.
     AlreadyCorrect(PathBuf),
     /// File no longer exists on disk (or a duplicate already existed in the
@@ -38,13 +44,20 @@ pub enum Action {
 ///
 /// - **[`Action::AlreadyCorrect`]** — the file is already where it belongs.
 /// - **[`Action::Renamed`]** — the file was moved (re-compressed if needed).
+/// - **[`Action::Transferred`]** — the file's language is routed to another
+///   target, and it was moved there (re-compressed if needed).
 /// - **[`Action::CleanedUp`]** — either:
 ///   - the file did not exist, and its cache entry was cleaned up, or
 ///   - a duplicate of that particular version already existed in the target
 ///     location, and the original was cleaned up.
 ///
 /// When the target path is occupied by a file of a different version, conflict
-/// resolution recursively relocates the occupant first.
+/// resolution recursively relocates the occupant first. Conflicts on another
+/// target are not resolved: unless the occupant is a duplicate, the transfer
+/// fails with a conflict error.
+///
+/// If the context [repairs encodings](Context::with_encoding_repair), a file
+/// that scanning found wasn't UTF-8 is re-encoded (in place) first.
 ///
 /// # Errors
 /// Returns [`Exn<LibraryErrorKind::Organize>`](LibraryErrorKind::Organize)
@@ -55,25 +68,42 @@ pub async fn organize_file<S: HashState>(
     ctx: &Context,
     file: FileInfo<S>,
 ) -> LibraryResult<Action> {
-    organize_file_inner(backend, cache, ctx, file, vec![]).await.or_raise(|| LibraryErrorKind::Organize)
+    let (backend, cache) = ctx.guard(backend, cache);
+    let (action, _bytes) =
+        organize_file_inner(&backend, &cache, ctx, file, vec![], None).await.or_raise(|| LibraryErrorKind::Organize)?;
+    Ok(action)
 }
 
 /// Inner implementation that carries a `depth` stack for cycle detection
 /// during recursive conflict resolution.
+///
+/// Also returns the [`Bytes`] moved while organizing this file (a plain
+/// rename moves none); relocations performed during conflict resolution are
+/// not included.
+///
+/// `prefetched` is the file's contents if they were read ahead of time; they
+/// are only used if they still match the file hash on record.
 pub(crate) async fn organize_file_inner<S: HashState>(
     backend: &BackendHandle,
     cache: &Repository,
     ctx: &Context,
     file: FileInfo<S>,
     depth: Vec<PathBuf>,
-) -> OrganizeResult<Action> {
+    prefetched: Option<Vec<u8>>,
+) -> OrganizeResult<(Action, Bytes)> {
+    let mut bytes = Bytes::default();
     if file.target != backend.name() {
         exn::bail!(OrganizeErrorKind::Storage);
     }
 
+    // Files inside archives can't be moved (or re-compressed) on their own.
+    if is_member(&file.path) {
+        return Ok((Action::AlreadyCorrect(file.path.clone()), bytes));
+    }
+
     if !backend.exists(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)? {
         cache.delete_by_target_path(&file.target, &file.path).await.or_raise(|| OrganizeErrorKind::Cache)?;
-        return Ok(Action::CleanedUp(file.path.clone()));
+        return Ok((Action::CleanedUp(file.path.clone()), bytes));
     }
 
     let file_path = file.path.clone();
@@ -83,28 +113,53 @@ pub(crate) async fn organize_file_inner<S: HashState>(
             // File not in cache, we need to scan it first to get the metadata for
             // path generation. This is NOT the intended use-case (organizing files
             // not already in cache), but the function is public, so...
-            None => match scan_file_inner(backend, cache, file).await {
+            None => match scan_file_inner(backend, cache, file, ctx.repair_encoding).await {
                 // We scanned the file and now it's cached.
-                Ok(Scan { file, version, .. }) => (file, version),
+                Ok(Scan { file, version, bytes: scanned, .. }) => {
+                    bytes += scanned;
+                    (file, version)
+                },
                 // The file doesn't exist in the cache and, when we tried to perform a scan, it wasn't valid.
                 Err(e) if matches!(e.deref(), ScanErrorKind::Extract) => {
                     backend.delete(&file_path).await.or_raise(|| OrganizeErrorKind::Storage)?;
-                    return Ok(Action::CleanedUp(file_path));
+                    return Ok((Action::CleanedUp(file_path), bytes));
                 },
                 // An operational error occured during scanning.
                 Err(e) => return Err(e).or_raise(|| OrganizeErrorKind::Scan),
             },
         };
 
+    let (file, version, prefetched) = match ctx.repair_encoding && version.encoding.needs_repair() {
+        true => {
+            let (file, version, reencoded) = reencode(backend, cache, file, &version, prefetched).await?;
+            bytes += reencoded;
+            (file, version, None)
+        },
+        false => (file, version, prefetched),
+    };
+
     let compression_source = file.compression;
     let compression_target = ctx.compression.unwrap_or(compression_source);
 
-    let correct_location = ctx
-        .template
-        .generate_with_ext(&version, "html", compression_target)
-        .or_raise(|| OrganizeErrorKind::Template)?;
+    let (destination, correct_location) =
+        ctx.location(backend, &version, compression_target).or_raise(|| OrganizeErrorKind::Template)?;
+    let tags = ctx.object_tags(destination.name(), &version);
+    if destination.name() != backend.name() {
+        let prefetched = verify_prefetched(&file, prefetched);
+        let transferred =
+            transfer(backend, destination, cache, (file, version), correct_location, compression_target, prefetched)
+                .await?;
+        if let (Action::Transferred(_, path), Some(tags)) = (&transferred.0, &tags) {
+            tagging::tag(destination, path, tags).await;
+        }
+        return Ok(transferred);
+    }
     if file.path == correct_location {
-        return Ok(Action::AlreadyCorrect(file.path.clone()));
+        // Tagged again even though it hasn't moved, in case it never was.
+        if let Some(tags) = &tags {
+            tagging::tag(backend, &file.path, tags).await;
+        }
+        return Ok((Action::AlreadyCorrect(file.path.clone()), bytes));
     }
 
     if let Some(existing) = match backend.stat(&correct_location).await {
@@ -123,14 +178,14 @@ pub(crate) async fn organize_file_inner<S: HashState>(
                     Some(t) => trash(backend, t, &file).await.or_raise(|| OrganizeErrorKind::Storage)?,
                     None => backend.delete(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)?,
                 };
-                return Ok(Action::CleanedUp(file.path.clone()));
+                return Ok((Action::CleanedUp(file.path.clone()), bytes));
             },
             Ok(Some(ConflictResolution::DiscardExisting)) => {
                 backend.delete(&existing.path).await.or_raise(|| OrganizeErrorKind::Storage)?;
             },
             Ok(Some(ConflictResolution::DiscardIncoming)) => {
                 backend.delete(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)?;
-                return Ok(Action::CleanedUp(file.path.clone()));
+                return Ok((Action::CleanedUp(file.path.clone()), bytes));
             },
             Err(e) => return Err(e).or_raise(|| OrganizeErrorKind::Conflict),
             Ok(None) => {
@@ -142,35 +197,388 @@ pub(crate) async fn organize_file_inner<S: HashState>(
     // Target location is now free. If there was a cache entry at the target location
     // it isn't there now, delete old entry. Silently ignore errors if it couldn't
     // be deleted, it's a dangling record anyway.
-    _ = cache.delete_by_target_path(&file.target, &file.path).await;
+    _ = cache.delete_by_target_path(&file.target, &correct_location).await;
 
-    if compression_source == compression_target {
+    let converted = if compression_source == compression_target {
         // The file is already compressed using the correct format, a simple rename will do.
         backend.rename(&file.path, &correct_location).await.or_raise(|| OrganizeErrorKind::Storage)?;
+        None
     } else {
-        let converted = convert(
-            &backend.read(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)?,
-            compression_source,
-            compression_target,
-        )
-        .or_raise(|| OrganizeErrorKind::Compression)?;
-        backend.write(&correct_location, &converted).await.or_raise(|| OrganizeErrorKind::Storage)?;
+        let source = match verify_prefetched(&file, prefetched) {
+            Some(data) => data,
+            None => backend.read(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)?,
+        };
+        let converted =
+            convert(&source, compression_source, compression_target).or_raise(|| OrganizeErrorKind::Compression)?;
+        backend.write(&correct_location, &converted.data).await.or_raise(|| OrganizeErrorKind::Storage)?;
+        bytes += Bytes {
+            read: Bytes::len(&source),
+            decompressed: converted.decompressed,
+            written: Bytes::len(&converted.data),
+        };
         backend.delete(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)?;
+        Some(converted)
+    };
+
+    // Update the cache with the new location (and, if it was re-compressed,
+    // the new file), but silently ignore errors since it can be cleaned up on
+    // the next library scan operation.
+    match converted {
+        None => _ = cache.update_target_path(&file.target, &file.path, &correct_location).await,
+        Some(converted) => {
+            _ = cache.delete_by_target_path(&file.target, &file.path).await;
+            let recompressed = FileInfo::new(
+                &file.target,
+                &correct_location,
+                Bytes::len(&converted.data),
+                UtcDateTime::now(),
+                compression_target,
+            )
+            .with_file_hash(converted.file_hash)
+            .with_content_hash(&file.content_hash);
+            _ = cache.upsert(&recompressed, &version).await;
+        },
+    }
+    if let Some(tags) = &tags {
+        tagging::tag(backend, &correct_location, tags).await;
     }
+    Ok((Action::Renamed(correct_location), bytes))
+}
+
+/// Moves a file to another target, re-compressing it if needed.
+///
+/// The destination is only checked for an identical copy of the file (in
+/// which case the incoming file is discarded); any other occupant is a
+/// conflict, since resolving it would mean organizing the other target.
+async fn transfer(
+    source: &BackendHandle,
+    destination: &BackendHandle,
+    cache: &Repository,
+    (file, version): (FileInfo<Processed>, Version),
+    location: PathBuf,
+    compression_target: Compression,
+    prefetched: Option<Vec<u8>>,
+) -> OrganizeResult<(Action, Bytes)> {
+    let mut bytes = Bytes::default();
+    if destination.exists(&location).await.or_raise(|| OrganizeErrorKind::Storage)? {
+        match cache.get_by_target_path(destination.name(), &location).await.or_raise(|| OrganizeErrorKind::Cache)? {
+            Some((existing, _)) if existing.content_hash == file.content_hash => {
+                source.delete(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)?;
+                _ = cache.delete_by_target_path(&file.target, &file.path).await;
+                return Ok((Action::CleanedUp(file.path.clone()), bytes));
+            },
+            _ => {
+                tracing::warn!(
+                    source = source.name(),
+                    destination = destination.name(),
+                    path = %location.display(),
+                    "Cannot transfer file; destination path is occupied by a different file"
+                );
+                exn::bail!(OrganizeErrorKind::Conflict);
+            },
+        }
+    }
+
+    let data = match prefetched {
+        Some(data) => data,
+        None => source.read(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)?,
+    };
+    bytes.read = Bytes::len(&data);
+    let (data, file_hash) = match file.compression == compression_target {
+        true => {
+            let file_hash = blake3::hash(&data).to_string();
+            (data, file_hash)
+        },
+        false => {
+            let converted =
+                convert(&data, file.compression, compression_target).or_raise(|| OrganizeErrorKind::Compression)?;
+            bytes.decompressed = converted.decompressed;
+            (converted.data, converted.file_hash)
+        },
+    };
+    // Another transfer may have claimed the location since it was checked.
+    match destination.write_if_absent(&location, &data).await {
+        Ok(()) => (),
+        Err(e) if matches!(e.deref(), StorageErrorKind::AlreadyExists(_)) => {
+            tracing::warn!(
+                source = source.name(),
+                destination = destination.name(),
+                path = %location.display(),
+                "Cannot transfer file; destination path was claimed by another file"
+            );
+            exn::bail!(OrganizeErrorKind::Conflict);
+        },
+        Err(e) => Err(e).or_raise(|| OrganizeErrorKind::Storage)?,
+    }
+    bytes.written = Bytes::len(&data);
+    source.delete(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)?;
+
+    // As with renames, cache errors are ignored: the next scan of either
+    // target will clean up after them.
+    _ = cache.delete_by_target_path(&file.target, &file.path).await;
+    let transferred =
+        FileInfo::new(destination.name(), &location, bytes.written, UtcDateTime::now(), compression_target)
+            .with_file_hash(file_hash)
+            .with_content_hash(&file.content_hash);
+    _ = cache.upsert(&transferred, &version).await;
+    Ok((Action::Transferred(destination.name().to_string(), location), bytes))
+}
+
+/// Rewrites a file that isn't UTF-8 as UTF-8, in place and in the same
+/// compression, replacing its cache record with one for the repaired content.
+async fn reencode(
+    backend: &BackendHandle,
+    cache: &Repository,
+    file: FileInfo<Processed>,
+    version: &Version,
+    prefetched: Option<Vec<u8>>,
+) -> OrganizeResult<(FileInfo<Processed>, Version, Bytes)> {
+    let data = match verify_prefetched(&file, prefetched) {
+        Some(data) => data,
+        None => backend.read(&file.path).await.or_raise(|| OrganizeErrorKind::Storage)?,
+    };
+    let mut html = BufferPool::global().take();
+    file.compression.decompress_into(&data, &mut html).or_raise(|| OrganizeErrorKind::Compression)?;
+    let repaired = version.encoding.decode(&html);
+    let repaired_version = rawr_extract::extract(&repaired).or_raise(|| OrganizeErrorKind::Extract)?;
+    // Same text, same language.
+    let repaired_version = Version {
+        detected_language: version.detected_language.clone(),
+        ..repaired_version
+    };
+    let compressed = file.compression.compress(&repaired).or_raise(|| OrganizeErrorKind::Compression)?;
+    backend.write(&file.path, &compressed).await.or_raise(|| OrganizeErrorKind::Storage)?;
+    let bytes = Bytes {
+        read: Bytes::len(&data),
+        decompressed: Bytes::len(&html),
+        written: Bytes::len(&compressed),
+    };
+    tracing::info!(
+        target = backend.name(),
+        path = %file.path.display(),
+        encoding = %version.encoding,
+        "Re-encoded file as UTF-8"
+    );
 
-    // Update the cache with the new location, but silently ignore errors since
-    // it can be cleaned up on the next library scan operation.
-    _ = cache.update_target_path(&file.target, &file.path, &correct_location).await;
-    Ok(Action::Renamed(correct_location))
+    let repaired_file = FileInfo::new(&file.target, &file.path, bytes.written, UtcDateTime::now(), file.compression)
+        .with_file_hash(blake3::hash(&compressed).to_string())
+        .with_content_hash(&repaired_version.hash);
+    cache.delete_by_target_path(&file.target, &file.path).await.or_raise(|| OrganizeErrorKind::Cache)?;
+    cache.upsert(&repaired_file, &repaired_version).await.or_raise(|| OrganizeErrorKind::Cache)?;
+    Ok((repaired_file, repaired_version, bytes))
 }
 
-/// Convert from one compression format to another
-fn convert(data: &[u8], source: Compression, target: Compression) -> OrganizeResult<Vec<u8>> {
+/// Discards prefetched contents that no longer match the file on record (it
+/// was modified or replaced after being read ahead).
+fn verify_prefetched(file: &FileInfo<Processed>, prefetched: Option<Vec<u8>>) -> Option<Vec<u8>> {
+    let data = prefetched?;
+    if blake3::hash(&data).to_string() != file.file_hash {
+        tracing::debug!(path = %file.path.display(), "Prefetched contents are stale; reading file again");
+        return None;
+    }
+    Some(data)
+}
+
+/// Convert from one compression format to another, hashing the result as
+/// it's written.
+pub(crate) fn convert(data: &[u8], source: Compression, target: Compression) -> OrganizeResult<Converted> {
     let reader = Cursor::new(data);
     let mut decompressor = source.wrap_reader(reader).or_raise(|| OrganizeErrorKind::Compression)?;
-    let mut writer = Cursor::new(Vec::new());
+    let mut writer = HashingWriter::new(Vec::new());
     let mut compressor = target.wrap_writer(&mut writer).or_raise(|| OrganizeErrorKind::Compression)?;
-    io::copy(&mut decompressor, &mut compressor).or_raise(|| OrganizeErrorKind::Compression)?;
+    let decompressed = io::copy(&mut decompressor, &mut compressor).or_raise(|| OrganizeErrorKind::Compression)?;
     drop(compressor);
-    Ok(writer.into_inner())
+    let (data, digests) = writer.finish().or_raise(|| OrganizeErrorKind::Compression)?;
+    Ok(Converted {
+        data,
+        decompressed,
+        file_hash: digests.blake3,
+    })
+}
+
+/// Data converted to another compression format.
+pub(crate) struct Converted {
+    pub(crate) data: Vec<u8>,
+    /// Number of decompressed bytes that passed through.
+    pub(crate) decompressed: u64,
+    /// Hash of the converted data, computed while it was written.
+    pub(crate) file_hash: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LanguageRoute;
+    use crate::scan::scan_file;
+    use rawr_cache::Database;
+    use rawr_extract::Encoding;
+    use rawr_extract::testing::{Corruption, Generator};
+    use rawr_storage::Mode;
+    use rawr_storage::backend::MockBackend;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_encoding_repair() {
+        let html = (3165..).map(|seed| Generator::new(seed).generate().html).find(|html| !html.is_ascii()).unwrap();
+        let garbled = Corruption::Mojibake.apply(html.as_bytes());
+        let backend: BackendHandle =
+            Arc::new(MockBackend::with_data([("download.html", garbled.clone())]).with_name("library"));
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let file =
+            FileInfo::new("library", "download.html", garbled.len() as u64, UtcDateTime::now(), Compression::None);
+        let scanned = scan_file(&backend, &cache, file.clone(), true).await.unwrap();
+        assert_eq!(Encoding::Mojibake, scanned.version.encoding);
+
+        let ctx = Context::new("{{ work }}".parse().unwrap(), None, None).with_encoding_repair(true);
+        let Action::Renamed(path) = organize_file(&backend, &cache, &ctx, scanned.file).await.unwrap() else {
+            panic!("file was not moved");
+        };
+        assert_eq!(html.as_bytes(), backend.read(&path).await.unwrap());
+        let file = FileInfo::new("library", &path, html.len() as u64, UtcDateTime::now(), Compression::None);
+        let rescanned = scan_file(&backend, &cache, file, true).await.unwrap();
+        assert_eq!(Encoding::Utf8, rescanned.version.encoding);
+        assert_eq!(scanned.version.metadata, rescanned.version.metadata);
+    }
+
+    #[tokio::test]
+    async fn test_recompress() {
+        let work = Generator::new(3156).generate();
+        let backend: BackendHandle =
+            Arc::new(MockBackend::with_data([("download.html", work.html.clone().into_bytes())]).with_name("library"));
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let file =
+            FileInfo::new("library", "download.html", work.html.len() as u64, UtcDateTime::now(), Compression::None);
+        let scanned = scan_file(&backend, &cache, file, false).await.unwrap();
+
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::Gzip, None);
+        let Action::Renamed(path) = organize_file(&backend, &cache, &ctx, scanned.file).await.unwrap() else {
+            panic!("file was not moved");
+        };
+        // The record is of the re-compressed file, not the original.
+        let data = backend.read(&path).await.unwrap();
+        let (file, version) = cache.get_by_target_path("library", &path).await.unwrap().unwrap();
+        assert_eq!(
+            (Compression::Gzip, data.len() as u64, blake3::hash(&data).to_string()),
+            (file.compression, file.size, file.file_hash)
+        );
+        assert_eq!(scanned.version.hash, version.hash);
+        assert!(cache.get_by_target_path("library", "download.html").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rename() {
+        let work = Generator::new(3186).generate();
+        let backend: BackendHandle =
+            Arc::new(MockBackend::with_data([("download.html", work.html.clone().into_bytes())]).with_name("library"));
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let file =
+            FileInfo::new("library", "download.html", work.html.len() as u64, UtcDateTime::now(), Compression::None);
+        let scanned = scan_file(&backend, &cache, file, false).await.unwrap();
+
+        let ctx = Context::new("{{ work }}".parse().unwrap(), None, None);
+        let Action::Renamed(path) = organize_file(&backend, &cache, &ctx, scanned.file).await.unwrap() else {
+            panic!("file was not moved");
+        };
+        // The record moved with the file.
+        let (file, _) = cache.get_by_target_path("library", &path).await.unwrap().unwrap();
+        assert_eq!(scanned.version.hash, file.content_hash);
+        assert!(cache.get_by_target_path("library", "download.html").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let work = Generator::new(3201).generate();
+        let backend: BackendHandle =
+            Arc::new(MockBackend::with_data([("download.html", work.html.clone().into_bytes())]).with_name("library"));
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let file =
+            FileInfo::new("library", "download.html", work.html.len() as u64, UtcDateTime::now(), Compression::None);
+        let scanned = scan_file(&backend, &cache, file, false).await.unwrap();
+
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::Gzip, None).with_mode(Mode::DryRun);
+        let action = organize_file(&backend, &cache, &ctx, scanned.file).await.unwrap();
+        let path = PathBuf::from(format!("{}.html.gz", work.expected.work_id));
+        assert!(matches!(action, Action::Renamed(p) if p == path));
+        // The file is still where it was, and recorded as such.
+        let paths: Vec<_> = backend.list(None).await.unwrap().into_iter().map(|f| f.path.clone()).collect();
+        assert_eq!(vec![PathBuf::from("download.html")], paths);
+        assert!(cache.get_by_target_path("library", "download.html").await.unwrap().is_some());
+        assert!(cache.get_by_target_path("library", &path).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_transfer() {
+        let work = Generator::new(3141).generate();
+        let library: BackendHandle =
+            Arc::new(MockBackend::with_data([("download.html", work.html.clone().into_bytes())]).with_name("library"));
+        let archive: BackendHandle = Arc::new(MockBackend::from_files::<&str>([]).with_name("archive"));
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let file =
+            FileInfo::new("library", "download.html", work.html.len() as u64, UtcDateTime::now(), Compression::None);
+        let scanned = scan_file(&library, &cache, file, false).await.unwrap();
+
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::Gzip, None)
+            .with_fallback_route(LanguageRoute::target(archive.clone()));
+        let action = organize_file(&library, &cache, &ctx, scanned.file).await.unwrap();
+        let path = PathBuf::from(format!("{}.html.gz", work.expected.work_id));
+        assert!(matches!(action, Action::Transferred(target, p) if target == "archive" && p == path));
+        // The file (and its record) moved between targets, re-compressed on the way.
+        assert!(library.list(None).await.unwrap().is_empty());
+        let data = archive.read(&path).await.unwrap();
+        assert_eq!(work.html.as_bytes(), Compression::Gzip.decompress(&data).unwrap());
+        let (file, version) = cache.get_by_target_path("archive", &path).await.unwrap().unwrap();
+        assert_eq!(
+            (Compression::Gzip, data.len() as u64, blake3::hash(&data).to_string()),
+            (file.compression, file.size, file.file_hash)
+        );
+        assert_eq!(scanned.version.hash, version.hash);
+        assert!(cache.get_by_target_path("library", "download.html").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_transfer_occupied() {
+        let work = Generator::new(3141).generate();
+        let path = PathBuf::from(format!("{}.html", work.expected.work_id));
+        let library: BackendHandle =
+            Arc::new(MockBackend::with_data([("download.html", work.html.clone())]).with_name("library"));
+        let archive: BackendHandle = Arc::new(MockBackend::with_data([(&path, "occupied")]).with_name("archive"));
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let size = work.html.len() as u64;
+        let scanned = scan_file(
+            &library,
+            &cache,
+            FileInfo::new("library", "download.html", size, UtcDateTime::now(), Compression::None),
+            false,
+        )
+        .await
+        .unwrap();
+        let ctx = Context::new("{{ work }}".parse().unwrap(), None, None)
+            .with_fallback_route(LanguageRoute::target(archive.clone()));
+
+        // A different file at the destination is a conflict, and nothing moves.
+        let Err(e) = organize_file_inner(&library, &cache, &ctx, scanned.file.clone(), vec![], None).await else {
+            panic!("file was transferred");
+        };
+        assert!(matches!(e.deref(), OrganizeErrorKind::Conflict));
+        assert_eq!(work.html.as_bytes(), library.read(Path::new("download.html")).await.unwrap());
+        assert_eq!(b"occupied", &archive.read(&path).await.unwrap()[..]);
+        assert!(cache.get_by_target_path("library", "download.html").await.unwrap().is_some());
+
+        // A copy of the same file at the destination makes the incoming one redundant.
+        archive.write(&path, work.html.as_bytes()).await.unwrap();
+        let copy = FileInfo::new("archive", &path, size, UtcDateTime::now(), Compression::None);
+        scan_file(&archive, &cache, copy, false).await.unwrap();
+        let action = organize_file(&library, &cache, &ctx, scanned.file).await.unwrap();
+        assert!(matches!(action, Action::CleanedUp(p) if p == Path::new("download.html")));
+        assert!(!library.exists(Path::new("download.html")).await.unwrap());
+        assert!(cache.get_by_target_path("library", "download.html").await.unwrap().is_none());
+        assert!(cache.get_by_target_path("archive", &path).await.unwrap().is_some());
+    }
 }
@@ -0,0 +1,233 @@
+//! A record of what organizing did, written to a file.
+//!
+//! With a journal directory set (see [`Context::with_journal`](crate::Context::with_journal)),
+//! each run of [`organize`](crate::organize::organize) writes a new JSON Lines
+//! file there, named `organize-<target>-<timestamp>.jsonl`: one
+//! [`JournalEntry`] per file organized (or that failed to be), and per
+//! duplicate found afterwards. Entries are written as they happen, so the
+//! journal survives a crash part-way through.
+//!
+//! Unlike the cache, the journal remembers where files *were*: it's an audit
+//! trail, and something for scripts to follow up on (such as updating links
+//! to files that moved).
+
+use crate::Bytes;
+use crate::organize::dedupe::Duplicates;
+use crate::organize::error::{ErrorKind as OrganizeErrorKind, Result as OrganizeResult};
+use crate::organize::file::Action;
+use exn::ResultExt;
+use rawr_storage::file::{FileInfo, Processed};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+/// What happened to a file, in a [`JournalEntry`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalOutcome {
+    /// Moved (and maybe re-compressed) to `to`; see [`Action::Renamed`].
+    Renamed,
+    /// Moved to `to` on `to_target`; see [`Action::Transferred`].
+    Transferred,
+    /// Already where it belongs; see [`Action::AlreadyCorrect`].
+    AlreadyCorrect,
+    /// Gone from storage, or discarded as a duplicate of the file already at
+    /// its path; see [`Action::CleanedUp`].
+    CleanedUp,
+    /// The same content as `duplicate_of`, and left alone.
+    Duplicate,
+    /// The same content as `duplicate_of`, and removed (or trashed).
+    DuplicateRemoved,
+    /// Organizing the file failed; see `error`.
+    Failed,
+}
+
+/// One line of an organize journal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    #[serde(with = "time::serde::rfc3339")]
+    pub at: OffsetDateTime,
+    pub outcome: JournalOutcome,
+    /// The target being organized.
+    pub target: String,
+    /// Where the file was.
+    pub from: PathBuf,
+    /// Where the file is now, if it's still anywhere.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<PathBuf>,
+    /// The target the file is now on, if it was transferred to another.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_target: Option<String>,
+    /// Hash of the file as stored, before it was organized.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_hash: Option<String>,
+    /// Hash of the file's (decompressed) content.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// The copy of the same content that was kept, for duplicates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<PathBuf>,
+    /// Why organizing the file failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+impl JournalEntry {
+    fn new(outcome: JournalOutcome, target: &str, from: PathBuf) -> Self {
+        Self {
+            at: OffsetDateTime::now_utc(),
+            outcome,
+            target: target.to_string(),
+            from,
+            to: None,
+            to_target: None,
+            file_hash: None,
+            content_hash: None,
+            duplicate_of: None,
+            error: None,
+        }
+    }
+}
+
+/// An open journal file, for one run of organizing a target.
+pub(crate) struct Journal {
+    path: PathBuf,
+    target: String,
+    writer: BufWriter<File>,
+}
+impl Journal {
+    /// Creates a new journal in `dir` (creating it, too, if need be) for
+    /// organizing `target`.
+    pub(crate) fn create(dir: &Path, target: &str) -> OrganizeResult<Self> {
+        fs::create_dir_all(dir).or_raise(|| OrganizeErrorKind::Journal)?;
+        let now = OffsetDateTime::now_utc();
+        let name: String =
+            target.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect();
+        let stem = format!(
+            "organize-{name}-{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+            now.year(),
+            u8::from(now.month()),
+            now.day(),
+            now.hour(),
+            now.minute(),
+            now.second(),
+        );
+        // Never overwrite the journal of an earlier run (in the same second).
+        for n in 1.. {
+            let path = match n {
+                1 => dir.join(format!("{stem}.jsonl")),
+                n => dir.join(format!("{stem}-{n}.jsonl")),
+            };
+            match File::create_new(&path) {
+                Ok(file) => {
+                    return Ok(Self {
+                        path,
+                        target: target.to_string(),
+                        writer: BufWriter::new(file),
+                    });
+                },
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e).or_raise(|| OrganizeErrorKind::Journal),
+            }
+        }
+        unreachable!("an unused journal file name is found")
+    }
+
+    /// Path of the journal file.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records the result of organizing `file`.
+    pub(crate) fn organized(
+        &mut self,
+        file: &FileInfo<Processed>,
+        result: &OrganizeResult<(Action, Bytes)>,
+    ) -> OrganizeResult<()> {
+        let (outcome, to, to_target) = match result {
+            Ok((Action::Renamed(to), _)) => (JournalOutcome::Renamed, Some(to.clone()), None),
+            Ok((Action::Transferred(target, to), _)) => {
+                (JournalOutcome::Transferred, Some(to.clone()), Some(target.clone()))
+            },
+            Ok((Action::AlreadyCorrect(to), _)) => (JournalOutcome::AlreadyCorrect, Some(to.clone()), None),
+            Ok((Action::CleanedUp(_), _)) => (JournalOutcome::CleanedUp, None, None),
+            Err(_) => (JournalOutcome::Failed, None, None),
+        };
+        let entry = JournalEntry {
+            to,
+            to_target,
+            file_hash: Some(file.file_hash.clone()),
+            content_hash: Some(file.content_hash.clone()),
+            error: result.as_ref().err().map(ToString::to_string),
+            ..JournalEntry::new(outcome, &self.target, file.path.clone())
+        };
+        self.write(&entry)
+    }
+
+    /// Records a set of duplicates found after organizing.
+    pub(crate) fn duplicates(&mut self, duplicates: &Duplicates) -> OrganizeResult<()> {
+        let outcome = match duplicates.resolved {
+            true => JournalOutcome::DuplicateRemoved,
+            false => JournalOutcome::Duplicate,
+        };
+        for path in &duplicates.duplicates {
+            let entry = JournalEntry {
+                to: (!duplicates.resolved).then(|| path.clone()),
+                content_hash: Some(duplicates.content_hash.clone()),
+                duplicate_of: Some(duplicates.kept.clone()),
+                ..JournalEntry::new(outcome, &self.target, path.clone())
+            };
+            self.write(&entry)?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, entry: &JournalEntry) -> OrganizeResult<()> {
+        serde_json::to_writer(&mut self.writer, entry).or_raise(|| OrganizeErrorKind::Journal)?;
+        self.writer.write_all(b"\n").or_raise(|| OrganizeErrorKind::Journal)?;
+        self.writer.flush().or_raise(|| OrganizeErrorKind::Journal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_compress::Compression;
+    use time::UtcDateTime;
+
+    #[test]
+    fn test_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = FileInfo::new("local nas", "old.html", 100, UtcDateTime::now(), Compression::None)
+            .with_file_hash("file")
+            .with_content_hash("content");
+        let mut journal = Journal::create(&dir.path().join("journals"), "local nas").unwrap();
+        let name = journal.path().file_name().unwrap().to_str().unwrap().to_string();
+        assert!(name.starts_with("organize-local_nas-") && name.ends_with(".jsonl"));
+        journal.organized(&file, &Ok((Action::Renamed("new.html".into()), Bytes::default()))).unwrap();
+        journal.organized(&file, &Err(exn::Exn::new(OrganizeErrorKind::Conflict))).unwrap();
+        let duplicates = Duplicates {
+            content_hash: "content".to_string(),
+            kept: "new.html".into(),
+            duplicates: vec!["new.html.gz".into()],
+            resolved: true,
+        };
+        journal.duplicates(&duplicates).unwrap();
+        // A second run in the same second gets a journal of its own.
+        let second = Journal::create(&dir.path().join("journals"), "local nas").unwrap();
+        assert_ne!(journal.path(), second.path());
+
+        let contents = fs::read_to_string(journal.path()).unwrap();
+        let entries: Vec<JournalEntry> = contents.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(3, entries.len());
+        assert_eq!(JournalOutcome::Renamed, entries[0].outcome);
+        assert_eq!((Path::new("old.html"), Some(Path::new("new.html"))), (&*entries[0].from, entries[0].to.as_deref()));
+        assert_eq!(Some("file"), entries[0].file_hash.as_deref());
+        assert_eq!(JournalOutcome::Failed, entries[1].outcome);
+        assert!(entries[1].error.is_some() && entries[1].to.is_none());
+        assert_eq!(JournalOutcome::DuplicateRemoved, entries[2].outcome);
+        assert_eq!(Some(Path::new("new.html")), entries[2].duplicate_of.as_deref());
+        assert!(contents.starts_with(r#"{"at":""#) && contents.contains(r#""outcome":"renamed""#));
+    }
+}
@@ -12,10 +12,27 @@
 //! specified [backend](rawr_storage)) from the [cache](rawr_cache) and streams
 //! the resulting [`Action`]s from passing each discovered file to [`organize_file`]
 //! (accepting any [`HashState`](rawr_storage::file::HashState)).
+//!
+//! Once every file has been organized, duplicate content left on the target
+//! is reported or removed according to its [`DuplicatePolicy`].
+//!
+//! Before re-compressing a whole target, [`estimate`] forecasts how much
+//! space doing so would save, and how long it would take. Before organizing
+//! at all, [`plan`] reports what would happen to each file, for review.
+//! Afterwards, the [journal](JournalEntry) (if enabled) records what did.
 
+mod dedupe;
 pub mod error;
+mod estimate;
 pub(crate) mod file;
+mod journal;
+mod plan;
+pub(crate) mod readahead;
 mod stream;
 
+pub use self::dedupe::{DuplicateBasis, DuplicatePolicy, Duplicates};
+pub use self::estimate::{Estimate, EstimateBucket, estimate};
 pub use self::file::{Action, organize_file};
+pub use self::journal::{JournalEntry, JournalOutcome};
+pub use self::plan::{PlanDifference, PlanReport, PlanSummary, PlannedAction, PlannedFile, plan};
 pub use self::stream::{OrganizeEvent, organize};
@@ -0,0 +1,510 @@
+//! Reviewing what organizing a target would do, before doing it.
+//!
+//! [`plan`] simulates [`organize`](crate::organize::organize) from the cache
+//! alone (nothing is written, and only samples are read): where each file
+//! would end up, which would be re-compressed or moved to another target,
+//! and which would be discarded or trashed when they collide. The resulting
+//! [`PlanReport`] serializes to JSON for review, and two plans (say, for
+//! different templates) can be [compared](PlanReport::differences).
+//!
+//! The simulation only knows about files on record: files on the target that
+//! were never scanned, and files changed since they were, can make the real
+//! thing turn out differently.
+
+use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
+use crate::organize::error::{ErrorKind as OrganizeErrorKind, Result as OrganizeResult};
+use crate::organize::estimate::estimate_inner;
+use crate::scan::is_member;
+use crate::{Context, MAX_PROCESS_CONCURRENCY};
+use exn::ResultExt;
+use rawr_cache::Repository;
+use rawr_compress::Compression;
+use rawr_extract::models::Version;
+use rawr_storage::file::{FileInfo, Processed};
+use rawr_storage::{BackendHandle, ByteSize};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// What organizing would do with a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlannedAction {
+    /// The file is already where it belongs.
+    Keep,
+    /// The file would be renamed (without reading it).
+    Move { to: PathBuf },
+    /// The file would be re-compressed to `compression`, at its new path.
+    Recompress {
+        to: PathBuf,
+        #[serde(with = "compression")]
+        compression: Compression,
+    },
+    /// The file would be moved to another target by a
+    /// [`LanguageRoute`](crate::LanguageRoute) (re-compressed to
+    /// `compression`, if it isn't already).
+    Transfer {
+        target: String,
+        to: PathBuf,
+        #[serde(with = "compression")]
+        compression: Compression,
+    },
+    /// The same content already is (or would first be moved) where the file
+    /// belongs; the file would be deleted.
+    Discard { duplicate_of: PathBuf },
+    /// Another version of the same work would take the path; this one is
+    /// worse, and would be moved to the context's trash (or deleted, without
+    /// one).
+    Trash { displaced_by: PathBuf },
+    /// Another version of the same work takes the path, and neither is
+    /// better; organizing the file would fail.
+    Conflict { with: PathBuf },
+    /// The file's path couldn't be generated; organizing it would fail.
+    Error { message: String },
+}
+
+/// A file on record, and what organizing would do with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    /// Size of the file, as stored.
+    pub size: u64,
+    pub action: PlannedAction,
+    /// Whether the file would be re-encoded to UTF-8 first (see
+    /// [`Context::with_encoding_repair`]).
+    pub reencode: bool,
+}
+
+/// How many files each kind of [`PlannedAction`] applies to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanSummary {
+    pub keep: u64,
+    pub moves: u64,
+    pub recompressions: u64,
+    pub transfers: u64,
+    pub discards: u64,
+    pub trashes: u64,
+    pub conflicts: u64,
+    pub errors: u64,
+    pub reencodes: u64,
+}
+
+/// What organizing a target would do, from [`plan`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanReport {
+    pub target: String,
+    pub summary: PlanSummary,
+    /// Every file on record for the target, sorted by path.
+    pub files: Vec<PlannedFile>,
+    /// Bytes that would be read from storage (for re-compressing,
+    /// re-encoding, transferring and trashing files).
+    pub bytes_read: u64,
+    /// Bytes that would be written to storage; re-compressed sizes are
+    /// estimated.
+    pub bytes_written: u64,
+    /// Space the target could need at most while organizing: files are
+    /// organized concurrently, and each one written to the target (when
+    /// re-compressed or re-encoded) is written in full before the file it
+    /// replaces is deleted.
+    #[serde(default)]
+    pub space_needed: u64,
+    /// Expected (CPU) time to re-compress the files; see [`Estimate`](crate::organize::Estimate).
+    /// Zero unless files were sampled.
+    pub estimated_duration: Duration,
+}
+impl PlanReport {
+    /// Files whose planned action differs between this plan and `other` (or
+    /// that only one of them knows of), sorted by path.
+    pub fn differences(&self, other: &PlanReport) -> Vec<PlanDifference> {
+        let mut files: BTreeMap<&PathBuf, (Option<&PlannedFile>, Option<&PlannedFile>)> = BTreeMap::new();
+        for file in &self.files {
+            files.entry(&file.path).or_default().0 = Some(file);
+        }
+        for file in &other.files {
+            files.entry(&file.path).or_default().1 = Some(file);
+        }
+        files
+            .into_iter()
+            .filter(|(_, (ours, theirs))| {
+                ours.map(|f| (&f.action, f.reencode)) != theirs.map(|f| (&f.action, f.reencode))
+            })
+            .map(|(path, (ours, theirs))| PlanDifference {
+                path: path.clone(),
+                ours: ours.map(|f| f.action.clone()),
+                theirs: theirs.map(|f| f.action.clone()),
+            })
+            .collect()
+    }
+
+    /// The report as (pretty-printed) JSON.
+    pub fn to_json(&self) -> String {
+        // Paths that aren't UTF-8 can't be on record, so this can't fail.
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+/// A file that two [plans](PlanReport::differences) would treat differently.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanDifference {
+    pub path: PathBuf,
+    /// What the first plan would do with the file; `None` if it doesn't
+    /// know of it.
+    pub ours: Option<PlannedAction>,
+    /// What the second plan would do with the file.
+    pub theirs: Option<PlannedAction>,
+}
+
+/// Simulates organizing the target of `backend` with `ctx`, without writing
+/// to storage (or the cache), and reports what would happen.
+///
+/// Conflicts are resolved as organizing would, for files on record: a file
+/// moving onto the path of a file that stays put is discarded if it has the
+/// same content, and otherwise the worse version of the two is trashed.
+///
+/// If `samples_per_bucket` is non-zero, files that would be re-compressed are
+/// sampled just like by [`estimate`](crate::organize::estimate) (reading them
+/// from storage), for the expected sizes and duration. Otherwise, sizes are
+/// assumed not to change.
+///
+/// # Errors
+/// Returns [`Exn<LibraryErrorKind::Organize>`](LibraryErrorKind::Organize)
+/// raised from an inner [`Exn<OrganizeErrorKind>`](OrganizeErrorKind) if the
+/// target's files can't be listed.
+pub async fn plan(
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    samples_per_bucket: usize,
+) -> LibraryResult<PlanReport> {
+    plan_inner(backend, cache, ctx, samples_per_bucket).await.or_raise(|| LibraryErrorKind::Organize)
+}
+
+/// Files sampled (per bucket) by [`preflight`] checks, to estimate the
+/// sizes of re-compressed files.
+const PREFLIGHT_SAMPLES: usize = 2;
+
+/// Checks that the target of `backend` has the space organizing it with
+/// `ctx` could take up, [planning](plan) it if need be.
+///
+/// Only re-compressing and re-encoding files take up space on the target
+/// being organized; targets that don't know how much space they have left
+/// are assumed to have enough.
+///
+/// # Errors
+/// Returns [`InsufficientSpace`](OrganizeErrorKind::InsufficientSpace) if
+/// the target is too full, or [`Cache`](OrganizeErrorKind::Cache) if its
+/// files can't be listed.
+pub(crate) async fn preflight(backend: &BackendHandle, cache: &Repository, ctx: &Context) -> OrganizeResult<()> {
+    if ctx.mode.is_dry_run() || (ctx.compression.is_none() && !ctx.repair_encoding) {
+        return Ok(());
+    }
+    let available = match backend.available_space().await {
+        Ok(Some(available)) => available,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            tracing::warn!(target = backend.name(), error = ?e, "Could not check space available; organizing anyway");
+            return Ok(());
+        },
+    };
+    let needed = plan_inner(backend, cache, ctx, PREFLIGHT_SAMPLES).await?.space_needed;
+    tracing::debug!(
+        target = backend.name(),
+        needed = %ByteSize(needed),
+        available = %ByteSize(available),
+        "Checked space for organize"
+    );
+    if needed > available {
+        exn::bail!(OrganizeErrorKind::InsufficientSpace {
+            needed: ByteSize(needed),
+            available: ByteSize(available),
+        });
+    }
+    Ok(())
+}
+
+/// Where a file would go: its target (`None` for the one being organized),
+/// path and compression.
+type Destination = (Option<String>, PathBuf, Compression);
+
+async fn plan_inner(
+    backend: &BackendHandle,
+    cache: &Repository,
+    ctx: &Context,
+    samples_per_bucket: usize,
+) -> OrganizeResult<PlanReport> {
+    let mut files = cache.list_files_for_target(backend.name()).await.or_raise(|| OrganizeErrorKind::Cache)?;
+    files.sort_by(|(a, _), (b, _)| a.path.cmp(&b.path));
+    let destinations: Vec<_> = files.iter().map(|(file, version)| destination(backend, ctx, file, version)).collect();
+
+    let mut actions: Vec<Option<PlannedAction>> = vec![None; files.len()];
+    // Files at their path (so far), by path: those staying put, then those
+    // moving in.
+    let mut occupants: HashMap<PathBuf, usize> = HashMap::new();
+    for (i, ((file, _), destination)) in files.iter().zip(&destinations).enumerate() {
+        // Files inside archives stay put, wherever they belong.
+        if is_member(&file.path) {
+            actions[i] = Some(PlannedAction::Keep);
+            continue;
+        }
+        if let Ok((None, to, _)) = destination
+            && *to == file.path
+        {
+            actions[i] = Some(PlannedAction::Keep);
+            occupants.insert(to.clone(), i);
+        }
+    }
+    for (i, ((file, version), destination)) in files.iter().zip(&destinations).enumerate() {
+        if actions[i].is_some() {
+            continue;
+        }
+        let (target, to, compression) = match destination {
+            Ok(destination) => destination.clone(),
+            Err(message) => {
+                actions[i] = Some(PlannedAction::Error { message: message.clone() });
+                continue;
+            },
+        };
+        if let Some(target) = target {
+            actions[i] = Some(PlannedAction::Transfer { target, to, compression });
+            continue;
+        }
+        let arrive = match compression == file.compression {
+            true => PlannedAction::Move { to: to.clone() },
+            false => PlannedAction::Recompress { to: to.clone(), compression },
+        };
+        let Some(&j) = occupants.get(&to) else {
+            actions[i] = Some(arrive);
+            occupants.insert(to, i);
+            continue;
+        };
+        let (occupant, occupant_version) = &files[j];
+        if occupant.content_hash == file.content_hash {
+            actions[i] = Some(PlannedAction::Discard { duplicate_of: to });
+            continue;
+        }
+        actions[i] = Some(match version.partial_cmp(occupant_version) {
+            None => PlannedAction::Conflict { with: to },
+            Some(Ordering::Greater) => {
+                actions[j] = Some(PlannedAction::Trash { displaced_by: file.path.clone() });
+                occupants.insert(to, i);
+                arrive
+            },
+            Some(_) => PlannedAction::Trash { displaced_by: occupant.path.clone() },
+        });
+    }
+
+    let estimate = match samples_per_bucket {
+        0 => None,
+        n => Some(estimate_inner(backend, cache, ctx, n).await?),
+    };
+    // How much smaller (or larger) re-compressed files are expected to be.
+    let ratio = estimate
+        .as_ref()
+        .filter(|e| e.bytes() > 0 && e.estimated_bytes() > 0)
+        .map_or(1.0, |e| e.estimated_bytes() as f64 / e.bytes() as f64);
+    let mut report = PlanReport {
+        target: backend.name().to_string(),
+        summary: PlanSummary::default(),
+        files: Vec::with_capacity(files.len()),
+        bytes_read: 0,
+        bytes_written: 0,
+        space_needed: 0,
+        estimated_duration: estimate.map(|e| e.estimated_time()).unwrap_or_default(),
+    };
+    // Sizes of the files that would be written to the target.
+    let mut writes = Vec::new();
+    for ((file, version), action) in files.into_iter().zip(actions) {
+        let action = action.unwrap_or(PlannedAction::Keep);
+        let reencode = ctx.repair_encoding && version.encoding.needs_repair();
+        let summary = &mut report.summary;
+        let size = file.size;
+        let recompressed = (size as f64 * ratio).round() as u64;
+        let (read, written) = match &action {
+            PlannedAction::Keep => {
+                summary.keep += 1;
+                (0, 0)
+            },
+            PlannedAction::Move { .. } => {
+                summary.moves += 1;
+                (0, 0)
+            },
+            PlannedAction::Recompress { .. } => {
+                summary.recompressions += 1;
+                (size, recompressed)
+            },
+            PlannedAction::Transfer { compression, .. } => {
+                summary.transfers += 1;
+                (size, if *compression == file.compression { size } else { recompressed })
+            },
+            PlannedAction::Discard { .. } => {
+                summary.discards += 1;
+                (0, 0)
+            },
+            PlannedAction::Trash { .. } => {
+                summary.trashes += 1;
+                if ctx.trash.is_some() { (size, size) } else { (0, 0) }
+            },
+            PlannedAction::Conflict { .. } => {
+                summary.conflicts += 1;
+                (0, 0)
+            },
+            PlannedAction::Error { .. } => {
+                summary.errors += 1;
+                (0, 0)
+            },
+        };
+        summary.reencodes += u64::from(reencode);
+        // Re-encoding reads and rewrites the file in place first.
+        let reencoded = if reencode { size } else { 0 };
+        report.bytes_read += read.max(reencoded);
+        report.bytes_written += written + reencoded;
+        // Files leaving the target (or deleted from it) only free up space.
+        let written_here = match &action {
+            PlannedAction::Recompress { .. } => written,
+            _ => 0,
+        };
+        if let Some(written) = [written_here, reencoded].into_iter().filter(|w| *w > 0).max() {
+            writes.push(written);
+        }
+        report.space_needed += written_here.saturating_sub(size);
+        report.files.push(PlannedFile {
+            path: file.path.clone(),
+            size,
+            action,
+            reencode,
+        });
+    }
+    // On top of what the target grows by, room for the largest files to be
+    // written all at once.
+    writes.sort_unstable_by(|a, b| b.cmp(a));
+    report.space_needed += writes.iter().take(MAX_PROCESS_CONCURRENCY).sum::<u64>();
+    tracing::debug!(target = backend.name(), summary = ?report.summary, "Planned organize of target");
+    Ok(report)
+}
+
+/// Where organizing would put a file, mirroring [`organize_file`](crate::organize::organize_file).
+fn destination(
+    backend: &BackendHandle,
+    ctx: &Context,
+    file: &FileInfo<Processed>,
+    version: &Version,
+) -> Result<Destination, String> {
+    let compression = ctx.compression.unwrap_or(file.compression);
+    let (destination, path) = ctx.location(backend, version, compression).map_err(|e| e.to_string())?;
+    let target = (destination.name() != backend.name()).then(|| destination.name().to_string());
+    Ok((target, path, compression))
+}
+
+/// (De)serializes a [`Compression`] by its name.
+mod compression {
+    use rawr_compress::Compression;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(compression: &Compression, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(compression.as_str())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Compression, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(|_| serde::de::Error::custom(format!("unknown compression format: {name}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::Database;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::Mode;
+    use rawr_storage::backend::{MockBackend, QuotaBackend};
+    use std::path::Path;
+    use std::sync::Arc;
+    use time::UtcDateTime;
+
+    #[tokio::test]
+    async fn test_plan() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let mut data = Vec::new();
+        let mut record = async |seed: u64, path: &str, compression: Compression| {
+            let html = Generator::new(3173 + seed).generate().html;
+            let version = rawr_extract::extract(&html).unwrap();
+            let file = FileInfo::new("library", path, html.len() as u64, UtcDateTime::now(), compression)
+                .with_file_hash(path)
+                .with_content_hash(&version.hash);
+            cache.upsert(&file, &version).await.unwrap();
+            data.push((path.to_string(), html));
+            version.metadata.work_id
+        };
+        let work = record(0, "misplaced.html", Compression::None).await;
+        record(1, "1.html", Compression::None).await;
+        // The same content as the first file, already at its correct path.
+        let html = Generator::new(3173).generate().html;
+        let version = rawr_extract::extract(&html).unwrap();
+        let path = format!("{work}.html");
+        let file = FileInfo::new("library", &path, html.len() as u64, UtcDateTime::now(), Compression::None)
+            .with_file_hash("copy")
+            .with_content_hash(&version.hash);
+        cache.upsert(&file, &version).await.unwrap();
+        data.push((path.clone(), html));
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+
+        let ctx = Context::new("{{ work }}".parse().unwrap(), None, None);
+        let report = plan(&backend, &cache, &ctx, 0).await.unwrap();
+        assert_eq!(3, report.files.len());
+        let action = |report: &PlanReport, path: &str| {
+            report.files.iter().find(|f| f.path == Path::new(path)).map(|f| f.action.clone()).unwrap()
+        };
+        assert_eq!(PlannedAction::Keep, action(&report, &path));
+        assert_eq!(PlannedAction::Discard { duplicate_of: path.clone().into() }, action(&report, "misplaced.html"));
+        assert_eq!((1, 1), (report.summary.keep, report.summary.discards));
+        assert_eq!((0, 0), (report.bytes_read, report.bytes_written));
+
+        let gzip = Context::new("{{ work }}".parse().unwrap(), Compression::Gzip, None);
+        let recompressed = plan(&backend, &cache, &gzip, 1).await.unwrap();
+        assert_eq!(2, recompressed.summary.recompressions);
+        assert!(recompressed.bytes_written < recompressed.bytes_read);
+        let json = recompressed.to_json();
+        assert!(json.contains(r#""action": "recompress""#) && json.contains(r#""compression": "gzip""#));
+        assert_eq!(recompressed, serde_json::from_str(&json).unwrap());
+
+        let differences = report.differences(&recompressed);
+        assert_eq!(3, differences.len());
+        assert!(matches!(&differences[0].theirs, Some(PlannedAction::Recompress { .. })));
+        assert!(report.differences(&report).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_preflight() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let html = Generator::new(3217).generate().html;
+        let version = rawr_extract::extract(&html).unwrap();
+        let file = FileInfo::new("library", "work.html", html.len() as u64, UtcDateTime::now(), Compression::None)
+            .with_file_hash("work")
+            .with_content_hash(&version.hash);
+        cache.upsert(&file, &version).await.unwrap();
+        let size = html.len() as u64;
+        let quota = |quota: u64| -> BackendHandle {
+            let inner = MockBackend::with_data([("work.html", html.as_bytes())]).with_name("library");
+            Arc::new(QuotaBackend::new(Arc::new(inner), ByteSize(quota)))
+        };
+
+        // Renaming files takes up no space.
+        let ctx = Context::new("{{ work }}".parse().unwrap(), None, None);
+        preflight(&quota(size), &cache, &ctx).await.unwrap();
+
+        // Re-compressing one needs room for its new copy.
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::Gzip, None);
+        let needed = plan(&quota(size), &cache, &ctx, PREFLIGHT_SAMPLES).await.unwrap().space_needed;
+        assert!(needed > 0 && needed < size);
+        preflight(&quota(size + needed), &cache, &ctx).await.unwrap();
+        let err = preflight(&quota(size + needed - 1), &cache, &ctx).await.unwrap_err();
+        assert!(
+            matches!(&*err, OrganizeErrorKind::InsufficientSpace { available, .. } if available.as_u64() == needed - 1)
+        );
+        preflight(&quota(size), &cache, &ctx.with_mode(Mode::DryRun)).await.unwrap();
+    }
+}
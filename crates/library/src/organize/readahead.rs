@@ -0,0 +1,77 @@
+//! Speculative read-ahead for the organize pipeline.
+//!
+//! Only files that have to be *read* to be organized (re-compressed, or
+//! transferred to another target) benefit from prefetching; plain renames
+//! never touch the file contents.
+
+/// Limits on how much source data may be held in memory ahead of being
+/// organized, configured with [`Context::with_read_ahead`](crate::Context::with_read_ahead).
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReadAhead {
+    files: usize,
+    bytes: u64,
+}
+impl ReadAhead {
+    pub(crate) fn new(files: usize, bytes: u64) -> Self {
+        Self { files, bytes }
+    }
+
+    pub(crate) fn budget(self) -> Budget {
+        Budget { limits: self, files: 0, bytes: 0 }
+    }
+}
+
+/// Tracks the files (and their sizes) currently being prefetched or waiting
+/// in memory to be organized.
+#[derive(Debug)]
+pub(crate) struct Budget {
+    limits: ReadAhead,
+    files: usize,
+    bytes: u64,
+}
+impl Budget {
+    /// Reserves room for a file of `size` bytes, returning `false` if there's
+    /// not enough room (in which case nothing is reserved).
+    ///
+    /// A file larger than the entire byte budget is still admitted when
+    /// nothing else is buffered, otherwise it could never be prefetched.
+    pub(crate) fn reserve(&mut self, size: u64) -> bool {
+        if self.files >= self.limits.files {
+            return false;
+        }
+        if self.files > 0 && self.bytes.saturating_add(size) > self.limits.bytes {
+            return false;
+        }
+        self.files += 1;
+        self.bytes = self.bytes.saturating_add(size);
+        true
+    }
+
+    /// Releases a reservation once the file has been handed over to be
+    /// organized.
+    pub(crate) fn release(&mut self, size: u64) {
+        self.files = self.files.saturating_sub(1);
+        self.bytes = self.bytes.saturating_sub(size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget() {
+        let mut budget = ReadAhead::new(3, 100).budget();
+        assert!(budget.reserve(60));
+        assert!(!budget.reserve(50));
+        assert!(budget.reserve(40));
+        assert!(!budget.reserve(1));
+        budget.release(60);
+        budget.release(40);
+        // Oversized files are only admitted on their own.
+        assert!(budget.reserve(500));
+        assert!(!budget.reserve(1));
+        budget.release(500);
+        assert!(budget.reserve(1));
+    }
+}
@@ -1,14 +1,26 @@
+use crate::concurrency::Concurrency;
 use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
+use crate::organize::dedupe::{Duplicates, deduplicate};
 use crate::organize::error::{ErrorKind as OrganizeErrorKind, Result as OrganizeResult};
 use crate::organize::file::{Action, organize_file_inner};
-use crate::{Context, MAX_PROCESS_CONCURRENCY};
+use crate::organize::journal::Journal;
+use crate::organize::plan::preflight;
+use crate::organize::readahead::ReadAhead;
+use crate::progress::{Bytes, Heartbeat, Progress};
+use crate::{CancellationToken, Context, MAX_PROCESS_CONCURRENCY};
 use async_stream::stream;
 use exn::ResultExt;
 use futures::stream::FuturesUnordered;
 use futures::{Stream, StreamExt};
 use rawr_cache::Repository;
+use rawr_extract::models::Version;
 use rawr_storage::BackendHandle;
+use rawr_storage::file::{FileInfo, Processed};
 use std::collections::VecDeque;
+use std::ops::Deref;
+use std::time::Instant;
+
+type File = FileInfo<Processed>;
 
 /// Progress events emitted by [`organize`] as it works through a storage
 /// backend's cached files.
@@ -17,21 +29,38 @@ use std::collections::VecDeque;
 /// 1. [`Started`](Self::Started) — exactly once.
 /// 2. [`DiscoveryComplete`](Self::DiscoveryComplete) — exactly once, with the
 ///    total file count.
-/// 3. [`Organized`](Self::Organized) — zero or more times, one per file.
-/// 4. [`Complete`](Self::Complete) — exactly once, signalling the stream is
+/// 3. [`Organized`](Self::Organized) — zero or more times, one per file,
+///    interleaved with occasional [`Heartbeat`](Self::Heartbeat)s.
+/// 4. [`Duplicates`](Self::Duplicates) — zero or more times, one per set of
+///    files with identical content.
+/// 5. [`Heartbeat`](Self::Heartbeat) — exactly once, with the final totals.
+/// 6. [`Complete`](Self::Complete) — exactly once, signalling the stream is
 ///    finished.
 ///
 /// An error may terminate the stream early, in which case [`Complete`](Self::Complete)
-/// is never emitted.
+/// is never emitted. If the stream is cancelled, no (more) files are
+/// organized after those in progress, duplicates aren't looked for, and
+/// [`Cancelled`](Self::Cancelled) is emitted instead of `Complete`.
 pub enum OrganizeEvent {
     /// Organizing has begun; emitted exactly once before any other event.
     Started,
     /// All cache entries have been discovered; the total count is now known.
     DiscoveryComplete(u64),
-    /// A file has been organized.
-    Organized(Action),
+    /// A file has been organized, along with the bytes moved to do so.
+    Organized(Action, Bytes),
+    /// Files with identical content were found on the target once all files
+    /// were organized (and removed, if its
+    /// [`DuplicatePolicy`](crate::organize::DuplicatePolicy) says so). Never
+    /// emitted for targets that allow duplicates.
+    Duplicates(Duplicates),
+    /// Aggregate progress across all files organized so far.
+    Heartbeat(Progress),
     /// All discovered cache entries have been organized; the stream is finished.
     Complete,
+    /// Organizing was cancelled; files already being organized were
+    /// finished, but no others were started. Holds the final progress. The
+    /// stream is finished.
+    Cancelled(Progress),
 }
 
 /// Streams [`OrganizeEvent`]s for every cached file in `backend`, relocating
@@ -39,57 +68,307 @@ pub enum OrganizeEvent {
 ///
 /// Files are discovered by querying the [`Repository`] cache for all entries
 /// belonging to the given [`BackendHandle`], then organized concurrently up to
-/// `MAX_PROCESS_CONCURRENCY` (100) at a time. Additional files are promoted as
-/// in-flight operations complete.
+/// `MAX_PROCESS_CONCURRENCY` (100) at a time (fewer when the backend is slow
+/// to keep up). Additional files are promoted as in-flight operations
+/// complete.
+///
+/// If the context has [read-ahead](Context::with_read_ahead) enabled, files
+/// that need to be re-compressed or transferred to another target are read
+/// into memory ahead of time, while other files are being organized.
 ///
 /// The stream yields events in the order documented on [`OrganizeEvent`].
 /// Individual file failures are surfaced as `Err` items without terminating
-/// the stream — only a cache discovery failure is fatal.
+/// the stream — only a cache discovery failure (or losing the target lock) is
+/// fatal.
+///
+/// The target is [locked](Repository::lock_target) for the duration: if
+/// another organize or scan holds the lock, the stream ends with a
+/// [`Locked`](OrganizeErrorKind::Locked) error straight after
+/// [`Started`](OrganizeEvent::Started). Other targets that files are
+/// transferred to by [language routes](crate::LanguageRoute) aren't locked.
+///
+/// If re-compressing (or re-encoding) files could take up more space than
+/// the target has [available](rawr_storage::backend::StorageBackend::available_space),
+/// the stream ends with an [`InsufficientSpace`](OrganizeErrorKind::InsufficientSpace)
+/// error before any file is organized, rather than running out part-way
+/// through.
+///
+/// If the context has a [journal](Context::with_journal), each file organized
+/// and set of duplicates found is recorded in it as it happens. Failing to
+/// create the journal is fatal; failing to write to it is not (the failure
+/// is surfaced as an `Err` item).
+///
+/// Dropping the stream part-way through can abandon a file half-moved (for
+/// example, written to its new path but not yet deleted from its old one). To
+/// stop early, cancel `cancel` instead: files already being organized are
+/// finished, and the stream ends with [`Cancelled`](OrganizeEvent::Cancelled).
 pub fn organize<'a>(
     backend: &'a BackendHandle,
     cache: &'a Repository,
     ctx: &'a Context,
+    cancel: CancellationToken,
 ) -> impl Stream<Item = LibraryResult<OrganizeEvent>> + 'a {
     // `rustfmt` does not format macro-specific syntax such as
     // `for await` even using the parentheses trick.
     stream! {
-        for await event in organize_inner(backend, cache, ctx) {
+        let (backend, cache) = ctx.guard(backend, cache);
+        for await event in organize_inner(&backend, &cache, ctx, cancel) {
             yield event.or_raise(|| LibraryErrorKind::Organize);
         }
     }
 }
 
+/// Whether organizing a file will (most likely) have to read its contents:
+/// re-compressing it, or moving it to another target.
+fn needs_read(backend: &BackendHandle, ctx: &Context, file: &File, version: &Version) -> bool {
+    ctx.compression.is_some_and(|c| c != file.compression)
+        || (ctx.repair_encoding && version.encoding.needs_repair())
+        || ctx.route(version).is_some_and(|route| route.destination(backend).name() != backend.name())
+}
+
+/// Reads a file ahead of organizing it. Errors are not reported here:
+/// organizing the file without the prefetched contents will read it again,
+/// and report the error if it persists.
+async fn prefetch(backend: &BackendHandle, file: File) -> (File, Option<Vec<u8>>) {
+    match backend.read(&file.path).await {
+        Ok(data) => (file, Some(data)),
+        Err(e) => {
+            tracing::debug!(path = %file.path.display(), error = %*e, "Prefetching file for organize failed");
+            (file, None)
+        },
+    }
+}
+
 fn organize_inner<'a>(
     backend: &'a BackendHandle,
     cache: &'a Repository,
     ctx: &'a Context,
+    cancel: CancellationToken,
 ) -> impl Stream<Item = OrganizeResult<OrganizeEvent>> + 'a {
     // `rustfmt` does not format macros that use braces. Wrap in parentheses!
     stream!({
         yield Ok(OrganizeEvent::Started);
+        let mut lock = match cache.lock_target(backend.name(), "organize").await {
+            Ok(lock) => lock,
+            Err(e) => {
+                let kind = OrganizeErrorKind::from_lock(&e);
+                yield Err(e).or_raise(|| kind);
+                return;
+            },
+        };
+
+        if let Err(e) = preflight(backend, cache, ctx).await {
+            _ = lock.release().await;
+            yield Err(e);
+            return;
+        }
+
+        // A dry run has nothing to undo, so nothing to journal.
+        let dir = ctx.journal.as_deref().filter(|_| !ctx.mode.is_dry_run());
+        let mut journal = match dir.map(|dir| Journal::create(dir, backend.name())).transpose() {
+            Ok(journal) => journal,
+            Err(e) => {
+                _ = lock.release().await;
+                yield Err(e);
+                return;
+            },
+        };
+        if let Some(journal) = &journal {
+            tracing::info!(target = backend.name(), path = %journal.path().display(), "Journaling organize");
+        }
 
         let files = match cache.list_files_for_target(backend.name()).await.or_raise(|| OrganizeErrorKind::Cache) {
             Ok(f) => f,
             Err(e) => {
+                _ = lock.release().await;
                 yield Err(e);
                 return;
             },
         };
         // Infallible: a usize (either 32- or 64-bit) will always fit in a u64.
-        yield Ok(OrganizeEvent::DiscoveryComplete(u64::try_from(files.len()).unwrap_or(0)));
+        let total = u64::try_from(files.len()).unwrap_or(0);
+        let mut heartbeat = Heartbeat::new();
+        heartbeat.set_total(total);
+        yield Ok(OrganizeEvent::DiscoveryComplete(total));
 
-        let mut futures: VecDeque<_> =
-            files.into_iter().map(|(file, _version)| organize_file_inner(backend, cache, ctx, file, vec![])).collect();
+        // Files that have to be read to be organized are prefetched (when
+        // read-ahead is enabled) while other files are being organized.
+        let mut budget = ctx.read_ahead.map(ReadAhead::budget);
+        let (mut reads, mut rest): (VecDeque<_>, VecDeque<_>) =
+            files.into_iter().partition(|(file, version)| budget.is_some() && needs_read(backend, ctx, file, version));
+        let mut prefetching = FuturesUnordered::new();
+        let mut prefetched: VecDeque<(File, Option<Vec<u8>>)> = VecDeque::new();
         let mut processing = FuturesUnordered::new();
-        processing.extend(futures.drain(..MAX_PROCESS_CONCURRENCY.min(futures.len())));
-        while let Some(result) = processing.next().await {
-            yield result.map(OrganizeEvent::Organized);
-            // Pop-n-push, but FIFO instead of LIFO.
-            if let Some(f) = futures.pop_front() {
-                processing.push(f);
+        let mut concurrency = Concurrency::new(MAX_PROCESS_CONCURRENCY);
+        let mut cancelled = false;
+        loop {
+            // Prefetched files are organized first so their memory is freed,
+            // then (FIFO) everything else.
+            while processing.len() < concurrency.limit() {
+                let (file, data) = match prefetched.pop_front() {
+                    Some((file, data)) => {
+                        if let Some(budget) = budget.as_mut() {
+                            budget.release(file.size);
+                        }
+                        (file, data)
+                    },
+                    None => match rest.pop_front() {
+                        Some((file, _version)) => (file, None),
+                        None => break,
+                    },
+                };
+                // The journal records where the file was, even if organizing it fails.
+                let source = journal.is_some().then(|| file.clone());
+                processing.push(async move {
+                    let started = Instant::now();
+                    let result = organize_file_inner(backend, cache, ctx, file, vec![], data).await;
+                    (source, started.elapsed(), result)
+                });
+            }
+            while let Some(budget) = budget.as_mut()
+                && let Some((file, _version)) = reads.front()
+                && budget.reserve(file.size)
+            {
+                if let Some((file, _version)) = reads.pop_front() {
+                    prefetching.push(prefetch(backend, file));
+                }
+            }
+
+            tokio::select! {
+                biased;
+
+                // Prefetches only read, so they can be abandoned along with
+                // everything not yet started. Once nothing is in flight there's
+                // nothing left to cancel (and the `else` arm must be reachable).
+                _ = cancel.cancelled(), if !cancelled && (!processing.is_empty() || !prefetching.is_empty()) => {
+                    cancelled = true;
+                    reads.clear();
+                    rest.clear();
+                    prefetched.clear();
+                    prefetching.clear();
+                    tracing::info!(target = backend.name(), in_flight = processing.len(), "Organize cancelled");
+                },
+
+                Some((source, latency, result)) = processing.next(), if !processing.is_empty() => {
+                    let failed = result.as_ref().is_err_and(|e| matches!(e.deref(), OrganizeErrorKind::Storage));
+                    concurrency.record(latency, failed);
+                    if let (Some(journal), Some(source)) = (journal.as_mut(), source)
+                        && let Err(e) = journal.organized(&source, &result)
+                    {
+                        yield Err(e);
+                    }
+                    let bytes = result.as_ref().map_or(Bytes::default(), |(_, bytes)| *bytes);
+                    yield result.map(|(action, bytes)| OrganizeEvent::Organized(action, bytes));
+                    if let Some(progress) = heartbeat.record(bytes) {
+                        yield Ok(OrganizeEvent::Heartbeat(progress));
+                    }
+                    if let Err(e) = lock.refresh().await {
+                        let kind = OrganizeErrorKind::from_lock(&e);
+                        yield Err(e).or_raise(|| kind);
+                        return;
+                    }
+                },
+
+                Some(fetched) = prefetching.next(), if !prefetching.is_empty() => prefetched.push_back(fetched),
+
+                else => break,
             }
         }
 
-        yield Ok(OrganizeEvent::Complete);
+        if !cancelled {
+            match deduplicate(
+                backend,
+                cache,
+                ctx,
+                ctx.duplicate_policy(backend.name()),
+                ctx.duplicate_basis(backend.name()),
+            )
+            .await
+            {
+                Ok(results) => {
+                    for result in results {
+                        if let (Some(journal), Ok(duplicates)) = (journal.as_mut(), &result)
+                            && let Err(e) = journal.duplicates(duplicates)
+                        {
+                            yield Err(e);
+                        }
+                        yield result.map(OrganizeEvent::Duplicates);
+                    }
+                },
+                Err(e) => yield Err(e),
+            }
+        }
+
+        if let Err(e) = lock.release().await {
+            tracing::warn!(target = backend.name(), error = ?e, "Could not release target lock; it will expire");
+        }
+        let progress = heartbeat.progress();
+        yield Ok(OrganizeEvent::Heartbeat(progress));
+        yield Ok(match cancelled {
+            true => OrganizeEvent::Cancelled(progress),
+            false => OrganizeEvent::Complete,
+        });
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::Database;
+    use rawr_compress::Compression;
+    use rawr_extract::models::Encoding;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::backend::MockBackend;
+    use std::sync::Arc;
+    use time::UtcDateTime;
+
+    #[tokio::test]
+    async fn test_cancelled_organize() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let mut data = vec![];
+        // One more than can be organized at once, so at least one isn't started.
+        let total = MAX_PROCESS_CONCURRENCY + 1;
+        for i in 0..total as u64 {
+            let (path, contents) = (format!("download-{i}.html"), format!("work {i}"));
+            let mut metadata = Generator::new(i).metadata();
+            metadata.work_id = i + 1;
+            let version = Version {
+                hash: contents.clone(),
+                length: contents.len() as u64,
+                crc32: crc32fast::hash(contents.as_bytes()),
+                encoding: Encoding::Utf8,
+                detected_language: None,
+                metadata,
+                extracted_at: UtcDateTime::now(),
+            };
+            let file = FileInfo::new("library", &path, contents.len() as u64, UtcDateTime::now(), Compression::None)
+                .with_file_hash(blake3::hash(contents.as_bytes()).to_string())
+                .with_content_hash(&contents);
+            cache.upsert(&file, &version).await.unwrap();
+            data.push((path, contents));
+        }
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+        let ctx = Context::new("{{ work }}".parse().unwrap(), None, None);
+        let renamed = |events: &[LibraryResult<OrganizeEvent>]| {
+            events.iter().filter(|e| matches!(e, Ok(OrganizeEvent::Organized(Action::Renamed(_), _)))).count()
+        };
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let events: Vec<_> = organize(&backend, &cache, &ctx, cancel).collect().await;
+        assert!(matches!(events.last(), Some(Ok(OrganizeEvent::Cancelled(_)))));
+        // Files already being organized were finished; the rest weren't started.
+        let first = renamed(&events);
+        assert!(first > 0 && first < total);
+        let files = backend.list(None).await.unwrap();
+        let left = files.iter().filter(|f| f.path.to_string_lossy().starts_with("download-")).count();
+        assert_eq!(total - first, left);
+        assert!(!events.iter().any(|e| matches!(e, Ok(OrganizeEvent::Duplicates(_)))));
+
+        // The lock was released, so organizing again moves the rest.
+        let events: Vec<_> = organize(&backend, &cache, &ctx, CancellationToken::new()).collect().await;
+        assert!(matches!(events.last(), Some(Ok(OrganizeEvent::Complete))));
+        assert_eq!(total - first, renamed(&events));
+    }
+}
@@ -0,0 +1,183 @@
+//! Byte accounting and throughput reporting for long-running passes.
+//!
+//! Every per-file event carries the [`Bytes`] it cost, and the streams
+//! periodically emit an aggregate [`Progress`] "heartbeat" so that UIs can
+//! display throughput (MB/s) and an ETA without summing events themselves.
+
+use std::ops::{Add, AddAssign};
+use std::time::{Duration, Instant};
+
+/// Minimum time between two heartbeats of the same stream.
+pub(crate) const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bytes moved while processing one (or, when aggregated, many) files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Bytes {
+    /// Raw (possibly compressed) bytes read from storage.
+    pub read: u64,
+    /// Decompressed HTML bytes processed.
+    pub decompressed: u64,
+    /// Raw (possibly compressed) bytes written to storage.
+    pub written: u64,
+}
+impl Bytes {
+    pub(crate) fn len(bytes: &[u8]) -> u64 {
+        // Infallible: a usize (either 32- or 64-bit) will always fit in a u64.
+        u64::try_from(bytes.len()).unwrap_or(u64::MAX)
+    }
+}
+impl Add for Bytes {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            read: self.read.saturating_add(rhs.read),
+            decompressed: self.decompressed.saturating_add(rhs.decompressed),
+            written: self.written.saturating_add(rhs.written),
+        }
+    }
+}
+impl AddAssign for Bytes {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+/// A snapshot of the aggregate progress of a stream.
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    /// Files processed so far, including those that failed.
+    pub processed: u64,
+    /// Total files to process, once discovery has finished.
+    pub total: Option<u64>,
+    /// Bytes moved across all processed files.
+    pub bytes: Bytes,
+    /// Time since the stream started.
+    pub elapsed: Duration,
+}
+impl Progress {
+    /// Average rate at which bytes were read from storage, in bytes per second.
+    pub fn read_per_second(&self) -> f64 {
+        per_second(self.bytes.read, self.elapsed)
+    }
+
+    /// Average rate at which decompressed HTML was processed, in bytes per second.
+    pub fn decompressed_per_second(&self) -> f64 {
+        per_second(self.bytes.decompressed, self.elapsed)
+    }
+
+    /// Estimated time remaining, extrapolated from the average time per file.
+    ///
+    /// Returns `None` until the total is known and at least one file has been
+    /// processed.
+    pub fn eta(&self) -> Option<Duration> {
+        let total = self.total?;
+        if self.processed == 0 {
+            return None;
+        }
+        let remaining = total.saturating_sub(self.processed);
+        Some(self.elapsed.mul_f64(remaining as f64 / self.processed as f64))
+    }
+}
+
+fn per_second(bytes: u64, elapsed: Duration) -> f64 {
+    match elapsed.as_secs_f64() {
+        0.0 => 0.0,
+        secs => bytes as f64 / secs,
+    }
+}
+
+/// Accumulates per-file results into a [`Progress`], deciding when the next
+/// heartbeat is due.
+///
+/// Every stream emits heartbeats at most once every [`HEARTBEAT_INTERVAL`]
+/// (a second), and once more with the final totals before it completes.
+///
+/// Heartbeats are only considered when a file finishes processing, which
+/// happens when the consumer polls the stream: a slow consumer receives fewer
+/// heartbeats rather than a backlog of stale ones.
+pub(crate) struct Heartbeat {
+    started: Instant,
+    last: Instant,
+    processed: u64,
+    total: Option<u64>,
+    bytes: Bytes,
+}
+impl Heartbeat {
+    pub(crate) fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            last: now,
+            processed: 0,
+            total: None,
+            bytes: Bytes::default(),
+        }
+    }
+
+    pub(crate) fn set_total(&mut self, total: u64) {
+        self.total = Some(total);
+    }
+
+    /// Records one processed file, returning a heartbeat if one is due.
+    pub(crate) fn record(&mut self, bytes: Bytes) -> Option<Progress> {
+        self.processed += 1;
+        self.bytes += bytes;
+        if self.last.elapsed() < HEARTBEAT_INTERVAL {
+            return None;
+        }
+        self.last = Instant::now();
+        Some(self.progress())
+    }
+
+    pub(crate) fn progress(&self) -> Progress {
+        Progress {
+            processed: self.processed,
+            total: self.total,
+            bytes: self.bytes,
+            elapsed: self.started.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bytes_accumulate() {
+        let mut total = Bytes::default();
+        total += Bytes { read: 10, decompressed: 40, written: 0 };
+        total += Bytes { read: 5, decompressed: 20, written: 7 };
+        assert_eq!(total, Bytes { read: 15, decompressed: 60, written: 7 });
+    }
+
+    #[test]
+    fn test_rates_and_eta() {
+        let progress = Progress {
+            processed: 25,
+            total: Some(100),
+            bytes: Bytes {
+                read: 2_000,
+                decompressed: 8_000,
+                written: 0,
+            },
+            elapsed: Duration::from_secs(2),
+        };
+        assert_eq!(progress.read_per_second(), 1_000.0);
+        assert_eq!(progress.decompressed_per_second(), 4_000.0);
+        assert_eq!(progress.eta(), Some(Duration::from_secs(6)));
+        assert_eq!(Progress { total: None, ..progress }.eta(), None);
+    }
+
+    #[test]
+    fn test_heartbeat_is_rate_limited() {
+        let mut heartbeat = Heartbeat::new();
+        assert!(heartbeat.record(Bytes::default()).is_none());
+        heartbeat.last -= HEARTBEAT_INTERVAL;
+        let progress = heartbeat.record(Bytes { read: 1, ..Bytes::default() }).unwrap();
+        assert_eq!(progress.processed, 2);
+        assert_eq!(progress.bytes.read, 1);
+        assert!(heartbeat.record(Bytes::default()).is_none());
+    }
+}
@@ -0,0 +1,68 @@
+//! Language-based routing for multilingual libraries.
+//!
+//! A [`Context`](crate::Context) may map ISO 639 language codes to a
+//! [`LanguageRoute`], sending works in that language to a different storage
+//! target and/or path prefix when organizing.
+
+use rawr_storage::{BackendHandle, Mode};
+use std::path::PathBuf;
+
+/// Where works in a particular language should be organized to.
+///
+/// A route can change the storage target, prefix the template-generated path,
+/// or both. A route with neither behaves as if no route was configured.
+///
+/// # Example
+///
+/// ```
+/// use rawr_library::LanguageRoute;
+///
+/// // Keep French works on the same target, but under a "fr/" directory.
+/// let route = LanguageRoute::prefix("fr");
+/// ```
+#[derive(Clone, Default)]
+pub struct LanguageRoute {
+    target: Option<BackendHandle>,
+    prefix: Option<PathBuf>,
+}
+impl LanguageRoute {
+    /// Routes works to a directory (relative to the target root) on the
+    /// target being organized.
+    pub fn prefix(prefix: impl Into<PathBuf>) -> Self {
+        Self {
+            target: None,
+            prefix: Some(prefix.into()),
+        }
+    }
+
+    /// Routes works to a different storage target.
+    pub fn target(target: BackendHandle) -> Self {
+        Self { target: Some(target), prefix: None }
+    }
+
+    /// Also places routed works under `prefix` on the route's target.
+    pub fn with_prefix(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// The route, with its target (if any) wrapped for `mode`.
+    pub(crate) fn with_mode(mut self, mode: Mode) -> Self {
+        self.target = self.target.map(|target| mode.backend(target));
+        self
+    }
+
+    /// The target that routed works belong on: the route's own target if it
+    /// has one, otherwise the `source` target they're being organized from.
+    pub(crate) fn destination<'a>(&'a self, source: &'a BackendHandle) -> &'a BackendHandle {
+        self.target.as_ref().unwrap_or(source)
+    }
+
+    /// Applies the route's prefix (if any) to a template-generated path.
+    pub(crate) fn apply(&self, path: PathBuf) -> PathBuf {
+        match &self.prefix {
+            Some(prefix) => prefix.join(path),
+            None => path,
+        }
+    }
+}
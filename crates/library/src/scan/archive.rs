@@ -0,0 +1,297 @@
+//! Scanning the works inside archives.
+//!
+//! Bulk downloads tend to arrive as ZIP files (or tarballs) of HTML files.
+//! Rather than having to unpack them onto the target first, a [scan](crate::scan::scan)
+//! descends into every [archive](FileKind::Archive) it lists and scans the
+//! HTML files inside, recording each one at its [member path](member_path):
+//! `exports/2024.zip!/work.html`.
+//!
+//! Archives are only ever read. Their members can't be moved, re-compressed
+//! or deleted on their own, so organizing leaves them where they are, and
+//! they aren't counted as duplicates of anything.
+
+use crate::Bytes;
+use crate::scan::Scan;
+use crate::scan::error::{ErrorKind, Result as ScanResult};
+use crate::scan::file::{cached, scan_contents};
+use exn::ResultExt;
+use rawr_cache::Repository;
+use rawr_compress::Compression;
+use rawr_storage::file::{Discovered, FileInfo};
+use rawr_storage::{BackendHandle, FileKind};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+/// Separates an archive's path from the path of a file inside it.
+pub const ARCHIVE_SEPARATOR: &str = "!/";
+
+/// Largest file inside an archive that's read (far larger than any work).
+const MAX_MEMBER_BYTES: u64 = 128 * 1024 * 1024;
+/// Most files (of any kind) an archive can have.
+const MAX_MEMBERS: usize = 10_000;
+/// Most bytes read from the HTML files inside an archive, altogether.
+const MAX_ARCHIVE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Magic bytes of a ZIP archive: its first local file header, or (for an
+/// empty archive) its end of central directory record.
+const ZIP_MAGIC_BYTES: [&[u8]; 2] = [b"PK\x03\x04", b"PK\x05\x06"];
+
+/// The path of the file at `member` inside the archive at `archive`.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_library::scan::member_path;
+/// use std::path::Path;
+///
+/// let path = member_path("exports/2024.zip", "Fandom/work.html");
+/// assert_eq!(Path::new("exports/2024.zip!/Fandom/work.html"), path);
+/// ```
+pub fn member_path(archive: impl AsRef<Path>, member: impl AsRef<Path>) -> PathBuf {
+    let (archive, member) = (archive.as_ref(), member.as_ref());
+    PathBuf::from(format!("{}{ARCHIVE_SEPARATOR}{}", archive.display(), member.display()))
+}
+
+/// Splits the path of a file inside an archive into the archive's path and
+/// the file's path inside it; `None` for paths of files that aren't.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_library::scan::split_member_path;
+/// use std::path::Path;
+///
+/// let (archive, member) = split_member_path("exports/2024.zip!/work.html").unwrap();
+/// assert_eq!((Path::new("exports/2024.zip"), Path::new("work.html")), (archive, member));
+/// assert!(split_member_path("exports/work.html").is_none());
+/// ```
+pub fn split_member_path(path: &(impl AsRef<Path> + ?Sized)) -> Option<(&Path, &Path)> {
+    let (archive, member) = path.as_ref().to_str()?.split_once(ARCHIVE_SEPARATOR)?;
+    (FileKind::from_path(archive) == FileKind::Archive).then(|| (Path::new(archive), Path::new(member)))
+}
+
+/// Whether `path` is of a file inside an archive.
+pub(crate) fn is_member(path: impl AsRef<Path>) -> bool {
+    split_member_path(&path).is_some()
+}
+
+/// Whether `path` is of a file inside the archive at `archive`.
+pub(crate) fn is_member_of(path: impl AsRef<Path>, archive: impl AsRef<Path>) -> bool {
+    split_member_path(&path).is_some_and(|(a, _)| a == archive.as_ref())
+}
+
+/// Scans every HTML file inside the archive `file`, with one result per file
+/// (or a single error, if the archive can't be read at all).
+///
+/// Files whose path and size match their record are taken from the cache,
+/// but the archive is read regardless: there's no telling what's in it
+/// otherwise.
+pub(crate) async fn scan_archive_inner(
+    backend: &BackendHandle,
+    cache: &Repository,
+    file: FileInfo<Discovered>,
+    repair_encoding: bool,
+) -> Vec<ScanResult<Scan>> {
+    let members = match read_members(backend, &file).await {
+        Ok(members) => members,
+        Err(e) => return vec![Err(e)],
+    };
+    tracing::debug!(target = backend.name(), path = %file.path.display(), members = members.len(), "Scanning archive");
+    let mut scans = Vec::with_capacity(members.len());
+    for (member, data) in members {
+        let path = member_path(&file.path, &member);
+        let compression = Compression::from_path(&member);
+        let member = FileInfo::new(backend.name(), path, Bytes::len(&data), file.discovered_at, compression);
+        scans.push(match cached(backend, cache, &member).await {
+            Ok(Some(scan)) => Ok(scan),
+            Ok(None) => scan_contents(backend, cache, member, &data, repair_encoding).await,
+            Err(e) => Err(e),
+        });
+    }
+    scans
+}
+
+/// Reads the archive `file`, returning the path and contents of each HTML
+/// file inside it.
+///
+/// Sizes in archive headers aren't trusted: archives with more than
+/// [`MAX_MEMBERS`] files, HTML files larger than [`MAX_MEMBER_BYTES`], or more
+/// than [`MAX_ARCHIVE_BYTES`] of them altogether aren't read.
+async fn read_members(backend: &BackendHandle, file: &FileInfo<Discovered>) -> ScanResult<Vec<(PathBuf, Vec<u8>)>> {
+    let bytes = backend.read_contents(&file.path).await.or_raise(|| ErrorKind::Storage)?;
+    let archive = || ErrorKind::Archive(file.path.clone());
+    let mut members = Members::new(&file.path);
+    if ZIP_MAGIC_BYTES.iter().any(|magic| bytes.starts_with(magic)) {
+        let mut zip = zip::ZipArchive::new(Cursor::new(&*bytes)).or_raise(archive)?;
+        for i in 0..zip.len() {
+            members.count()?;
+            let mut entry = zip.by_index(i).or_raise(archive)?;
+            // Names that would escape the archive (`../`) have no enclosed name.
+            let Some(name) = entry.enclosed_name().filter(|name| entry.is_file() && is_html(name)) else {
+                continue;
+            };
+            members.read(name, &mut entry)?;
+        }
+        return Ok(members.read);
+    }
+    // Tarballs are compressed as a whole (whatever the extension says).
+    let compression = Compression::from_magic_bytes(&bytes).unwrap_or(Compression::None);
+    let tar = compression.wrap_reader(&*bytes).or_raise(|| ErrorKind::Compression)?;
+    for entry in tar::Archive::new(tar).entries().or_raise(archive)? {
+        members.count()?;
+        let mut entry = entry.or_raise(archive)?;
+        let name = entry.path().or_raise(archive)?.into_owned();
+        if !entry.header().entry_type().is_file() || !is_html(&name) || !is_enclosed(&name) {
+            continue;
+        }
+        members.read(name, &mut entry)?;
+    }
+    Ok(members.read)
+}
+
+/// The HTML files read from an archive so far, within the limits.
+struct Members<'a> {
+    archive: &'a Path,
+    read: Vec<(PathBuf, Vec<u8>)>,
+    seen: usize,
+    bytes: u64,
+}
+impl<'a> Members<'a> {
+    fn new(archive: &'a Path) -> Self {
+        Self {
+            archive,
+            read: Vec::new(),
+            seen: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Counts a file in the archive, failing once there are too many.
+    fn count(&mut self) -> ScanResult<()> {
+        self.seen += 1;
+        if self.seen > MAX_MEMBERS {
+            exn::bail!(ErrorKind::Archive(self.archive.to_path_buf()));
+        }
+        Ok(())
+    }
+
+    /// Reads the file `name` from `entry`, failing if it (or all the files
+    /// read so far) are too large.
+    fn read(&mut self, name: PathBuf, entry: &mut impl Read) -> ScanResult<()> {
+        let archive = || ErrorKind::Archive(self.archive.to_path_buf());
+        let limit = MAX_MEMBER_BYTES.min(MAX_ARCHIVE_BYTES - self.bytes);
+        let mut data = Vec::new();
+        entry.take(limit + 1).read_to_end(&mut data).or_raise(archive)?;
+        if data.len() as u64 > limit {
+            tracing::warn!(archive = %self.archive.display(), member = %name.display(), "Archive member is too large");
+            exn::bail!(archive());
+        }
+        self.bytes += data.len() as u64;
+        self.read.push((name, data));
+        Ok(())
+    }
+}
+
+fn is_html(path: &Path) -> bool {
+    FileKind::from_path(path) == FileKind::Html
+}
+
+/// Whether a path inside a tarball stays inside it: relative, and without
+/// any `..`.
+fn is_enclosed(path: &Path) -> bool {
+    path.components().all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::Database;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::backend::MockBackend;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn tar(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut tar = tar::Builder::new(Vec::new());
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, name, contents.as_bytes()).unwrap();
+        }
+        tar.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_member_path() {
+        assert_eq!(Some((Path::new("a.tar.gz"), Path::new("b/c.html"))), split_member_path("a.tar.gz!/b/c.html"));
+        assert!(split_member_path("notes!/c.html").is_none());
+        assert!(is_member_of("a.zip!/c.html", "a.zip"));
+        assert!(!is_member_of("ab.zip!/c.html", "a.zip"));
+        assert!(!is_member("a.zip"));
+    }
+
+    #[tokio::test]
+    async fn test_scan_archive() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let (first, second) = (Generator::new(3218).generate(), Generator::new(3219).generate());
+        let zipped = zip(&[
+            ("one.html", &first.html),
+            ("notes.txt", "not a work"),
+            ("two/two.html", &second.html),
+        ]);
+        let tarred = Compression::Gzip.compress(&tar(&[("one.html", &first.html)])).unwrap();
+        let backend: BackendHandle = Arc::new(
+            MockBackend::with_data([
+                ("works.zip", zipped),
+                ("works.tgz", tarred),
+                ("broken.zip", b"PK\x03\x04".to_vec()),
+            ])
+            .with_name("library"),
+        );
+
+        let file = backend.stat(Path::new("works.zip")).await.unwrap();
+        let scans: Vec<_> =
+            scan_archive_inner(&backend, &cache, file, false).await.into_iter().map(Result::unwrap).collect();
+        assert_eq!(2, scans.len());
+        assert_eq!(Path::new("works.zip!/one.html"), scans[0].file.path);
+        assert_eq!(first.expected, scans[0].version.metadata);
+        assert_eq!(Path::new("works.zip!/two/two.html"), scans[1].file.path);
+        let (cached, _) = cache.get_by_target_path("library", "works.zip!/two/two.html").await.unwrap().unwrap();
+        assert_eq!(second.html.len() as u64, cached.size);
+
+        let file = backend.stat(Path::new("works.tgz")).await.unwrap();
+        let scans = scan_archive_inner(&backend, &cache, file, false).await;
+        assert_eq!(Path::new("works.tgz!/one.html"), scans[0].as_ref().unwrap().file.path);
+
+        let file = backend.stat(Path::new("broken.zip")).await.unwrap();
+        let scans = scan_archive_inner(&backend, &cache, file, false).await;
+        assert!(
+            matches!(&scans[..], [Err(e)] if matches!(&**e, ErrorKind::Archive(p) if p == Path::new("broken.zip")))
+        );
+    }
+
+    #[test]
+    fn test_member_limits() {
+        let mut members = Members::new(Path::new("bomb.zip"));
+        (0..MAX_MEMBERS).for_each(|_| members.count().unwrap());
+        assert!(members.count().is_err());
+
+        // Files are read no further than the limit, whatever they claim.
+        let mut members = Members::new(Path::new("bomb.zip"));
+        members.read(PathBuf::from("a.html"), &mut &b"<html>"[..]).unwrap();
+        members.bytes = MAX_ARCHIVE_BYTES - 10;
+        assert!(members.read(PathBuf::from("b.html"), &mut std::io::repeat(0)).is_err());
+        assert_eq!(1, members.read.len());
+    }
+}
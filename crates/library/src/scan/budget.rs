@@ -0,0 +1,52 @@
+use crate::progress::Progress;
+use rawr_storage::ByteSize;
+use std::time::Duration;
+
+/// How much one [scan](crate::scan::scan) may do before it stops: for
+/// getting through an enormous (remote) target a bit at a time, such as
+/// nightly.
+///
+/// A scan that runs out of budget stops like a cancelled one (files already
+/// being scanned are finished), and can be [resumed](crate::scan::resume)
+/// from where it stopped. The default budget is unlimited.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_library::scan::ScanBudget;
+/// use rawr_storage::ByteSize;
+/// use std::time::Duration;
+///
+/// // At most 10 GiB, or half an hour, whichever comes first.
+/// let budget = ScanBudget::default().with_duration(Duration::from_secs(30 * 60)).with_bytes(ByteSize::gib(10));
+/// assert!(!budget.is_unlimited());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanBudget {
+    /// How long the scan may run for.
+    pub duration: Option<Duration>,
+    /// How many bytes the scan may read from storage. Files are read whole,
+    /// so the last ones scanned can take it over.
+    pub bytes: Option<ByteSize>,
+}
+impl ScanBudget {
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    pub fn with_bytes(mut self, bytes: ByteSize) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.duration.is_none() && self.bytes.is_none()
+    }
+
+    /// Whether a scan that has made `progress` has spent its budget.
+    pub(crate) fn is_spent(&self, progress: &Progress) -> bool {
+        self.duration.is_some_and(|duration| progress.elapsed >= duration)
+            || self.bytes.is_some_and(|bytes| progress.bytes.read >= bytes.as_u64())
+    }
+}
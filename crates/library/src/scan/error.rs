@@ -7,7 +7,12 @@
 //!       more crates. Designing errors in Rust is **hard** and I don't want
 //!       to resort to anyhow+thiserror just because I don't want to deal with it.
 
+use crate::scan::file::Damage;
 use derive_more::{Display, Error};
+use rawr_cache::error::ErrorKind as CacheErrorKind;
+pub use rawr_error::{Code, Domain, ErrorCode};
+use std::ops::Deref;
+use std::path::PathBuf;
 
 /// A library error with automatic location tracking.
 pub type Error = exn::Exn<ErrorKind>;
@@ -23,15 +28,59 @@ pub enum ErrorKind {
     Storage,
     /// Decompressing file contents failed.
     Compression,
+    /// The file at the path is empty, truncated, or not in the format its
+    /// extension says; see [`Damage`].
+    #[display("{}: {_1}", _0.display())]
+    Damaged(#[error(not(source))] PathBuf, #[error(not(source))] Damage),
     /// Metadata extraction via [`rawr_extract`] failed.
     Extract,
+    /// The file at the path looks like an [archive](crate::scan::member_path),
+    /// but isn't a ZIP file or tarball that can be read.
+    #[display("{}: unreadable archive", _0.display())]
+    Archive(#[error(not(source))] PathBuf),
+    /// The file at the path is of a work (with this ID) that the user
+    /// deliberately deleted; see [`Repository::add_tombstone`](rawr_cache::Repository::add_tombstone).
+    #[display("{}: work {_1} was deleted", _0.display())]
+    Tombstoned(#[error(not(source))] PathBuf, #[error(not(source))] u64),
+    /// The target is locked by another operation (or the lock was lost
+    /// part-way through).
+    Locked,
+    /// There's no unfinished [scan run](rawr_cache::ScanRun) of the target
+    /// with this ID to resume.
+    #[display("unknown scan run: {_0}")]
+    UnknownRun(#[error(not(source))] String),
 }
 
 impl ErrorKind {
     /// Returns `true` if retrying might succeed.
     pub fn is_retryable(&self) -> bool {
-        match self {
-            _ => false,
+        // Once the other operation finishes (or its lock expires).
+        matches!(self, Self::Locked)
+    }
+
+    /// Classifies a failure to take (or keep) the target lock: the target
+    /// being locked by another operation, or the cache itself failing.
+    pub(crate) fn from_lock(e: &rawr_cache::error::Error) -> Self {
+        match e.deref() {
+            CacheErrorKind::Locked(_) | CacheErrorKind::LockLost(_) => Self::Locked,
+            _ => Self::Cache,
         }
     }
 }
+
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::Locked => 423,
+            Self::Cache => 520,
+            Self::Storage => 521,
+            Self::Compression => 522,
+            Self::Extract => 523,
+            Self::Damaged(..) => 422,
+            Self::Archive(_) => 415,
+            Self::Tombstoned(..) => 410,
+            Self::UnknownRun(_) => 404,
+        };
+        Code::new(Domain::Scan, number)
+    }
+}
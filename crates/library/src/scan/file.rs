@@ -1,11 +1,17 @@
 use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
 use crate::scan::error::{ErrorKind, Result as ScanResult};
+use crate::{BufferPool, Bytes};
 use exn::ResultExt;
 use rawr_cache::{ExistenceResult, Repository};
-use rawr_extract::extract;
+use rawr_compress::Compression;
+use rawr_compress::error::ErrorKind as CompressErrorKind;
 use rawr_extract::models::Version;
+use rawr_extract::{extract, extract_repairing};
 use rawr_storage::BackendHandle;
-use rawr_storage::file::{FileInfo, HashState, Processed};
+use rawr_storage::file::{Discovered, FileInfo, HashState, Processed};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::ops::Deref;
+use std::path::Path;
 
 /// Indicates how much work was required to produce a [`Scan`] result.
 ///
@@ -27,12 +33,67 @@ pub enum ScanEffort {
 /// The result of scanning a single file.
 ///
 /// Contains the fully-hashed [`FileInfo`] (with both file and content hashes
-/// computed), the extracted [`Version`] metadata, and a [`ScanEffort`]
-/// indicating whether the result came from cache or fresh extraction.
+/// computed), the extracted [`Version`] metadata, a [`ScanEffort`]
+/// indicating whether the result came from cache or fresh extraction, and the
+/// [`Bytes`] read and decompressed to get there. Scanning never writes.
 pub struct Scan {
     pub file: FileInfo<Processed>,
     pub version: Version,
     pub effort: ScanEffort,
+    pub bytes: Bytes,
+}
+
+/// Length of the longest magic bytes of any compression format (XZ's).
+const MAGIC_BYTES_LEN: usize = 6;
+
+/// Why a file can't be scanned, short of being corrupt: the cause of a
+/// [`ScanErrorKind::Damaged`](crate::error::ScanErrorKind::Damaged) error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Damage {
+    /// The file is empty (zero bytes).
+    Empty,
+    /// The file ends part-way through its compressed stream: it's shorter
+    /// than any complete stream in its format, or decompressing it ran out
+    /// of data. Usually an interrupted download or copy.
+    Truncated(Compression),
+    /// The file isn't compressed the way its extension says: it's compressed
+    /// in another format (`found`), or isn't recognisably compressed at all.
+    WrongFormat {
+        expected: Compression,
+        found: Option<Compression>,
+    },
+}
+impl Display for Damage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Empty => write!(f, "file is empty"),
+            Self::Truncated(compression) => write!(f, "file ends part-way through its {compression} stream"),
+            Self::WrongFormat { expected, found: Some(found) } => {
+                write!(f, "file is {found}-compressed, not {expected}-compressed")
+            },
+            Self::WrongFormat { expected, found: None } => write!(f, "file is not {expected}-compressed"),
+        }
+    }
+}
+impl Damage {
+    /// Inspects a file's contents for damage obvious before decompressing it:
+    /// being empty, too short for its format, or not starting with its
+    /// format's magic bytes.
+    pub(crate) fn inspect(compression: Compression, bytes: &[u8]) -> Option<Self> {
+        if bytes.is_empty() {
+            return Some(Self::Empty);
+        }
+        if compression == Compression::None {
+            return None;
+        }
+        // Files too short to hold their format's magic bytes are truncated,
+        // whatever they do hold.
+        if bytes.len() >= MAGIC_BYTES_LEN && !compression.check_magic_bytes(bytes) {
+            let found = Compression::from_magic_bytes(bytes);
+            return Some(Self::WrongFormat { expected: compression, found });
+        }
+        (bytes.len() < compression.minimum_size()).then_some(Self::Truncated(compression))
+    }
 }
 
 /// Scans a single file, extracting its metadata or returning a cached result.
@@ -41,12 +102,24 @@ pub struct Scan {
 /// full extraction:
 ///
 /// 1. **Path + size match** — if the cache has an entry at the same path with
-///    the same file size, the cached result is returned immediately (no I/O).
+///    the same file size, the cached result is returned immediately (no I/O),
+///    unless a [`reconcile`](crate::scan::reconcile) flagged it as stale.
 /// 2. **Hash match at different path** — if the file's BLAKE3 hash matches a
 ///    record elsewhere, the content hash is reused (content deduplication).
 /// 3. **Hash mismatch** — if the path exists in cache but hashes differ, the
-///    old entry is deleted and the file is re-extracted.
-/// 4. **Not found** — the file is decompressed and fully extracted.
+///    file is re-extracted and replaces the old entry, keeping any fields
+///    of the old version a user [edited](rawr_extract::models::Metadata::keep_user_edits)
+///    (if it's still the same work).
+/// 4. **Not found** — the file is decompressed and fully extracted. With
+///    `repair_encoding`, files that aren't UTF-8 are [repaired](rawr_extract::extract_repairing)
+///    for extraction (but not on disk; see [`Context::with_encoding_repair`](crate::Context::with_encoding_repair)).
+///    Off by default, as detecting the encoding is heuristic.
+///    With the `language-detection` feature, the language of the text is
+///    [detected](rawr_extract::models::DetectedLanguage) too.
+///
+/// Files of works the user deliberately deleted (that have a
+/// [tombstone](rawr_cache::Tombstone)) aren't cached; scanning them fails
+/// with [`ScanErrorKind::Tombstoned`](crate::error::ScanErrorKind::Tombstoned).
 ///
 /// The input [`FileInfo`] can be in any [`HashState`]; existing hashes are
 /// stripped and recomputed from the file contents.
@@ -54,51 +127,285 @@ pub async fn scan_file<S: HashState>(
     backend: &BackendHandle,
     cache: &Repository,
     file: FileInfo<S>,
+    repair_encoding: bool,
 ) -> LibraryResult<Scan> {
-    scan_file_inner(backend, cache, file).await.or_raise(|| LibraryErrorKind::Scan)
+    scan_file_inner(backend, cache, file, repair_encoding).await.or_raise(|| LibraryErrorKind::Scan)
 }
 
 pub(crate) async fn scan_file_inner<S: HashState>(
     backend: &BackendHandle,
     cache: &Repository,
     file: FileInfo<S>,
+    repair_encoding: bool,
 ) -> ScanResult<Scan> {
     let file = file.strip_hashes();
+    if let Some(scan) = cached(backend, cache, &file).await? {
+        return Ok(scan);
+    }
+    // All that effort with Read/Write traits? Apparently pointless... Now the
+    // entire file contents is going to be stored in the future's state machine.
+    let bytes = backend.read_contents(&file.path).await.or_raise(|| ErrorKind::Storage)?;
+    scan_contents(backend, cache, file, &bytes, repair_encoding).await
+}
+
+/// The cached result for a file, if the cache has an entry at the same path
+/// with the same size that a [`reconcile`](crate::scan::reconcile) hasn't
+/// flagged as stale.
+pub(crate) async fn cached(
+    backend: &BackendHandle,
+    cache: &Repository,
+    file: &FileInfo<Discovered>,
+) -> ScanResult<Option<Scan>> {
     let existing = cache.get_by_target_path(backend.name(), &file.path).await.or_raise(|| ErrorKind::Cache)?;
     if let Some((cached_file, version)) = existing
         && file.size == cached_file.size
+        && !cache.is_stale(backend.name(), &file.path).await.or_raise(|| ErrorKind::Cache)?
     {
         let effort = ScanEffort::Cached;
-        return Ok(Scan { file: cached_file, version, effort });
+        let bytes = Bytes::default();
+        return Ok(Some(Scan {
+            file: cached_file,
+            version,
+            effort,
+            bytes,
+        }));
     }
-    // All that effort with Read/Write traits? Apparently pointless... Now the
-    // entire file contents is going to be stored in the future's state machine.
-    let bytes = backend.read(&file.path).await.or_raise(|| ErrorKind::Storage)?;
-    let file = file.with_file_hash(blake3::hash(&bytes).to_string());
+    Ok(None)
+}
+
+/// Scans a file from its (already read) contents: the rest of
+/// [`scan_file`], once the path and size didn't match a cache entry.
+pub(crate) async fn scan_contents(
+    backend: &BackendHandle,
+    cache: &Repository,
+    file: FileInfo<Discovered>,
+    bytes: &[u8],
+    repair_encoding: bool,
+) -> ScanResult<Scan> {
+    let mut counted = Bytes {
+        read: Bytes::len(bytes),
+        ..Bytes::default()
+    };
+    let file = file.with_file_hash(blake3::hash(bytes).to_string());
     let existing = cache.exists(backend.name(), &file.path, &file.file_hash).await.or_raise(|| ErrorKind::Cache)?;
-    let effort = match existing {
+    let (effort, previous) = match existing {
         // If we get to this point with an ExactMatch (unlikely) it means that
         // the file hash was the same but the file size wasn't. Data integrity
         // is now in question: recalculate.
-        ExistenceResult::ExactMatch(_, _) | ExistenceResult::HashMismatch(_, _) => {
-            cache.delete_by_target_path(backend.name(), &file.path).await.or_raise(|| ErrorKind::Cache)?;
+        ExistenceResult::ExactMatch(_, previous) | ExistenceResult::HashMismatch(_, previous) => {
             tracing::info!(target = backend.name(), path = %file.path.display(), "Cached file has changed on disk; recalculating");
-            ScanEffort::Recalculated
+            (ScanEffort::Recalculated, Some(previous))
         },
         ExistenceResult::LocatedElsewhere(other, version) => {
+            check_tombstone(cache, &file.path, &version).await?;
             let file = file.with_content_hash(other.content_hash);
             cache.upsert(&file, &version).await.or_raise(|| ErrorKind::Cache)?;
             return Ok(Scan {
                 file,
                 version,
                 effort: ScanEffort::Cached,
+                bytes: counted,
             });
         },
-        ExistenceResult::NotFound => ScanEffort::Processed,
+        ExistenceResult::NotFound => (ScanEffort::Processed, None),
+    };
+    if let Some(damage) = Damage::inspect(file.compression, bytes) {
+        exn::bail!(ErrorKind::Damaged(file.path.clone(), damage));
+    }
+    let mut content = BufferPool::global().take();
+    if let Err(e) = file.compression.decompress_into(bytes, &mut content) {
+        let kind = match e.deref() {
+            CompressErrorKind::Truncated => ErrorKind::Damaged(file.path.clone(), Damage::Truncated(file.compression)),
+            _ => ErrorKind::Compression,
+        };
+        return Err(e).or_raise(|| kind);
+    }
+    counted.decompressed = Bytes::len(&content);
+    let extracted = match repair_encoding {
+        true => extract_repairing(&*content),
+        false => extract(&*content),
     };
-    let content = file.compression.decompress(&bytes).or_raise(|| ErrorKind::Compression)?;
-    let version = extract(&content).or_raise(|| ErrorKind::Extract)?;
+    let mut version = extracted.or_raise(|| ErrorKind::Extract)?;
+    let replacing = previous.is_some();
+    if let Some(previous) = previous.filter(|p| p.metadata.work_id == version.metadata.work_id) {
+        version.metadata.keep_user_edits(&previous.metadata);
+    }
+    #[cfg(feature = "language-detection")]
+    {
+        version.detected_language = rawr_extract::detect_language(version.encoding.decode(&content));
+    }
+    check_tombstone(cache, &file.path, &version).await?;
+    // Only now that the file has been inspected, extracted and allowed is the
+    // old record replaced: a damaged (or tombstoned) copy mustn't lose the
+    // record of a good one.
+    if replacing {
+        cache.delete_by_target_path(backend.name(), &file.path).await.or_raise(|| ErrorKind::Cache)?;
+    }
     let file = file.with_content_hash(&version.hash);
     cache.upsert(&file, &version).await.or_raise(|| ErrorKind::Cache)?;
-    Ok(Scan { file, version, effort })
+    Ok(Scan { file, version, effort, bytes: counted })
+}
+
+/// Gets the [`Version`] of the file at `path` on `backend`: the one on record,
+/// or (if there's none, or the file has changed since) the one extracted
+/// from the file, which is cached for next time.
+///
+/// The single-file equivalent of a [scan](crate::scan::scan), for when only
+/// one file's metadata is wanted (such as to show it), going through the
+/// same cache lookups as [`scan_file`] (and likewise only repairing
+/// encodings with `repair_encoding`).
+pub async fn get_version(
+    backend: &BackendHandle,
+    cache: &Repository,
+    path: impl AsRef<Path>,
+    repair_encoding: bool,
+) -> LibraryResult<Version> {
+    get_version_inner(backend, cache, path.as_ref(), repair_encoding).await.or_raise(|| LibraryErrorKind::Scan)
+}
+
+async fn get_version_inner(
+    backend: &BackendHandle,
+    cache: &Repository,
+    path: &Path,
+    repair_encoding: bool,
+) -> ScanResult<Version> {
+    let file = backend.stat(path).await.or_raise(|| ErrorKind::Storage)?;
+    Ok(scan_file_inner(backend, cache, file, repair_encoding).await?.version)
+}
+
+/// Refuses to cache a version of a work the user deliberately deleted.
+async fn check_tombstone(cache: &Repository, path: &Path, version: &Version) -> ScanResult<()> {
+    let work_id = version.metadata.work_id;
+    if cache.get_tombstone(work_id).await.or_raise(|| ErrorKind::Cache)?.is_some() {
+        exn::bail!(ErrorKind::Tombstoned(path.to_path_buf(), work_id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::Database;
+    use rawr_extract::Encoding;
+    use rawr_extract::models::{Field, Provenance};
+    use rawr_extract::testing::{Corruption, Generator};
+    use rawr_storage::backend::MockBackend;
+    use std::sync::Arc;
+    use time::UtcDateTime;
+
+    #[tokio::test]
+    async fn test_get_version() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let work = Generator::new(3208).generate();
+        let backend: BackendHandle =
+            Arc::new(MockBackend::with_data([("work.html", work.html.as_str())]).with_name("library"));
+
+        let version = get_version(&backend, &cache, "work.html", false).await.unwrap();
+        assert_eq!(work.expected, version.metadata);
+        let (file, cached) = cache.get_by_target_path("library", "work.html").await.unwrap().unwrap();
+        assert_eq!(version.hash, cached.hash);
+        assert_eq!(version.hash, file.content_hash);
+
+        // Once cached, the file isn't read again.
+        let backend: BackendHandle =
+            Arc::new(MockBackend::with_data([("work.html", "x".repeat(work.html.len()))]).with_name("library"));
+        let cached = get_version(&backend, &cache, "work.html", false).await.unwrap();
+        assert_eq!((version.hash, version.metadata), (cached.hash, cached.metadata));
+
+        let error = get_version(&backend, &cache, "missing.html", false).await.unwrap_err();
+        assert!(matches!(*error, LibraryErrorKind::Scan));
+    }
+
+    #[tokio::test]
+    async fn test_keep_user_edits() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let work = Generator::new(3192).generate();
+        let backend: BackendHandle =
+            Arc::new(MockBackend::with_data([("work.html", work.html.as_str())]).with_name("library"));
+
+        // An older copy of the file, whose title the user corrected.
+        let mut previous = get_version(&backend, &cache, "work.html", false).await.unwrap();
+        cache.delete_by_target_path("library", "work.html").await.unwrap();
+        previous.hash = "previous".to_string();
+        previous.metadata.edit(Field::Title, |metadata| metadata.title = "Corrected".to_string());
+        let file = FileInfo::new("library", "work.html", 1, UtcDateTime::now(), Compression::None)
+            .with_file_hash("previous")
+            .with_content_hash("previous");
+        cache.upsert(&file, &previous).await.unwrap();
+
+        let version = get_version(&backend, &cache, "work.html", false).await.unwrap();
+        assert_ne!(previous.hash, version.hash);
+        assert_eq!("Corrected", version.metadata.title);
+        assert_eq!(Provenance::UserEdited, version.metadata.provenance.get(Field::Title));
+        assert_eq!(work.expected.summary, version.metadata.summary);
+    }
+
+    #[tokio::test]
+    async fn test_damaged_replacement() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let work = Generator::new(3188).generate();
+        let backend: BackendHandle =
+            Arc::new(MockBackend::with_data([("work.html", work.html.as_str())]).with_name("library"));
+        let version = get_version(&backend, &cache, "work.html", false).await.unwrap();
+
+        // The file is replaced with a damaged copy: the record of the good
+        // one is kept.
+        let backend: BackendHandle = Arc::new(MockBackend::with_data([("work.html", "")]).with_name("library"));
+        let file = backend.stat(Path::new("work.html")).await.unwrap();
+        let Err(error) = scan_file_inner(&backend, &cache, file, false).await else {
+            panic!("damaged file scanned");
+        };
+        assert!(matches!(&*error, ErrorKind::Damaged(_, Damage::Empty)));
+        let (_, cached) = cache.get_by_target_path("library", "work.html").await.unwrap().unwrap();
+        assert_eq!(version.hash, cached.hash);
+    }
+
+    #[tokio::test]
+    async fn test_tombstoned_replacement() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let work = Generator::new(3204).generate();
+        let backend: BackendHandle =
+            Arc::new(MockBackend::with_data([("work.html", work.html.as_str())]).with_name("library"));
+        let version = get_version(&backend, &cache, "work.html", false).await.unwrap();
+
+        // The file is replaced with a work the user deleted: it isn't
+        // cached, and the record of the live one is kept.
+        let deleted = Generator::new(3205).generate();
+        assert_ne!(work.expected.work_id, deleted.expected.work_id);
+        cache.add_tombstone(deleted.expected.work_id, None).await.unwrap();
+        let backend: BackendHandle =
+            Arc::new(MockBackend::with_data([("work.html", deleted.html.as_str())]).with_name("library"));
+        let file = backend.stat(Path::new("work.html")).await.unwrap();
+        let Err(error) = scan_file_inner(&backend, &cache, file, false).await else {
+            panic!("tombstoned file scanned");
+        };
+        assert!(matches!(&*error, ErrorKind::Tombstoned(_, id) if *id == deleted.expected.work_id));
+        let (_, cached) = cache.get_by_target_path("library", "work.html").await.unwrap().unwrap();
+        assert_eq!(version.hash, cached.hash);
+    }
+
+    #[tokio::test]
+    async fn test_repair_encoding() {
+        let work = (3165..).map(|seed| Generator::new(seed).generate()).find(|work| !work.html.is_ascii()).unwrap();
+        let garbled = Corruption::Mojibake.apply(work.html.as_bytes());
+        let backend: BackendHandle =
+            Arc::new(MockBackend::with_data([("download.html", garbled)]).with_name("library"));
+
+        // Off by default: the file is read as UTF-8, garbled as it is.
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let version = get_version(&backend, &cache, "download.html", false).await.unwrap();
+        assert_eq!(Encoding::Utf8, version.encoding);
+        assert_ne!(work.expected, version.metadata);
+
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let version = get_version(&backend, &cache, "download.html", true).await.unwrap();
+        assert_eq!(Encoding::Mojibake, version.encoding);
+        assert_eq!(work.expected, version.metadata);
+    }
 }
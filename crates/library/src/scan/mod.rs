@@ -7,14 +7,29 @@
 //! - **Single-file**: [`scan_file`] processes one
 //!   [`FileInfo`](rawr_storage::file::FileInfo) through a multi-layered cache
 //!   lookup (path match, hash match, content dedup) before falling back to
-//!   full extraction.
+//!   full extraction. [`get_version`] does the same for a file by path,
+//!   returning just its metadata.
 //! - **Streaming**: [`scan`] concurrently scans an entire backend, emitting
 //!   [`ScanEvent`]s that separate file discovery from processing — enabling
-//!   progress reporting with known totals.
+//!   progress reporting with known totals. Scans that don't complete can be
+//!   [resumed](resume) from where they got to.
+//! - **Metadata-only**: [`reconcile`] compares a backend's listing against
+//!   the cache without reading any file contents, flagging changed files for
+//!   the next scan and removing records of deleted ones.
+//!
+//! ZIP files and tarballs on the backend are scanned [into](member_path):
+//! the HTML files inside them are recorded at paths of their own.
 
+mod archive;
+mod budget;
 pub(crate) mod error;
 pub(crate) mod file;
+mod reconcile;
 mod stream;
 
-pub use self::file::{Scan, ScanEffort, scan_file};
-pub use self::stream::{ScanEvent, scan};
+pub use self::archive::{ARCHIVE_SEPARATOR, member_path, split_member_path};
+pub(crate) use self::archive::{is_member, is_member_of};
+pub use self::budget::ScanBudget;
+pub use self::file::{Damage, Scan, ScanEffort, get_version, scan_file};
+pub use self::reconcile::{ReconcileEvent, Reconciled, reconcile};
+pub use self::stream::{ScanEvent, resume, scan};
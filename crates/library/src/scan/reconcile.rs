@@ -0,0 +1,299 @@
+use crate::CancellationToken;
+use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
+use crate::scan::error::{ErrorKind as ScanErrorKind, Result as ScanResult};
+use crate::scan::is_member_of;
+use async_stream::stream;
+use exn::ResultExt;
+use futures::{Stream, StreamExt};
+use rawr_cache::Repository;
+use rawr_storage::file::FileMeta;
+use rawr_storage::{BackendHandle, FileKind};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use time::UtcDateTime;
+
+/// Progress events emitted during a metadata-only [`reconcile`].
+///
+/// Events are emitted in a predictable lifecycle: [`Started`](Self::Started)
+/// → [`New`](Self::New) and [`Changed`](Self::Changed) (interleaved, as files
+/// are listed) → [`Removed`](Self::Removed) → [`Complete`](Self::Complete)
+/// (or [`Cancelled`](Self::Cancelled)).
+pub enum ReconcileEvent {
+    /// Reconciling has begun; emitted exactly once before any other event.
+    Started,
+    /// A file the cache doesn't know about; the next full [`scan`](crate::scan::scan)
+    /// will process it.
+    New(PathBuf),
+    /// A file whose size or modification time no longer matches its record;
+    /// it was [flagged as stale](Repository::mark_stale) for the next full
+    /// scan to re-read.
+    Changed(PathBuf),
+    /// A file that's gone from storage; its record was deleted.
+    Removed(PathBuf),
+    /// Every file was listed (and every deleted one removed); the stream is
+    /// finished.
+    Complete(Reconciled),
+    /// Reconciling was cancelled part-way through listing, so no records
+    /// were removed. Holds the counts so far. The stream is finished.
+    Cancelled(Reconciled),
+}
+
+/// Counts of the files found by a [`reconcile`], by how they compared to the
+/// cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reconciled {
+    pub unchanged: u64,
+    pub new: u64,
+    pub changed: u64,
+    pub removed: u64,
+}
+
+/// Compares a storage backend's listing against the cache, without reading
+/// any file contents: a cheap "what changed" pass for backends that charge
+/// for egress (such as S3), where a full [`scan`](crate::scan::scan) downloads
+/// every file it doesn't recognise.
+///
+/// Files whose size or modification time differ from their records are
+/// [flagged as stale](Repository::mark_stale), so that the next full scan
+/// re-reads them (rather than trusting a record whose size still matches).
+/// Records of files that are no longer listed are deleted, as are those of
+/// files inside [archives](crate::scan::member_path) that are no longer
+/// listed; the files inside an archive that was modified are flagged as
+/// stale (and the archive reported as changed). New files are only
+/// reported; nothing is known about them until they're read. Files listed
+/// without a size (which some backends' listings leave out) are stat'ed
+/// individually. A modification
+/// time is only a hint: files written by [`organize`](crate::organize::organize)
+/// may be flagged too, and are found unchanged when they're re-read.
+///
+/// An optional `prefix` restricts reconciling to a subdirectory of the
+/// backend (and records outside of it are left alone). If listing fails
+/// part-way through (or is cancelled), no records are deleted, since files
+/// missing from the listing may well still exist.
+///
+/// The target is [locked](Repository::lock_target) for the duration, just
+/// like a [`scan`](crate::scan::scan).
+pub fn reconcile<'a>(
+    backend: &'a BackendHandle,
+    cache: &'a Repository,
+    prefix: Option<impl AsRef<Path>>,
+    cancel: CancellationToken,
+) -> impl Stream<Item = LibraryResult<ReconcileEvent>> + 'a {
+    let prefix = prefix.map(|p| p.as_ref().to_path_buf());
+    stream! {
+        for await event in reconcile_inner(backend, cache, prefix, cancel) {
+            yield event.or_raise(|| LibraryErrorKind::Scan);
+        }
+    }
+}
+
+fn reconcile_inner<'a>(
+    backend: &'a BackendHandle,
+    cache: &'a Repository,
+    prefix: Option<PathBuf>,
+    cancel: CancellationToken,
+) -> impl Stream<Item = ScanResult<ReconcileEvent>> + 'a {
+    stream!({
+        yield Ok(ReconcileEvent::Started);
+        let mut lock = match cache.lock_target(backend.name(), "reconcile").await {
+            Ok(lock) => lock,
+            Err(e) => {
+                let kind = ScanErrorKind::from_lock(&e);
+                yield Err(e).or_raise(|| kind);
+                return;
+            },
+        };
+        let mut known: HashMap<PathBuf, FileMeta> = match cache.list_files_for_target(backend.name()).await {
+            Ok(files) => files
+                .into_iter()
+                .filter(|(file, _)| prefix.as_ref().is_none_or(|prefix| file.path.starts_with(prefix)))
+                .map(|(file, _)| (file.path.clone(), (*file).clone()))
+                .collect(),
+            Err(e) => {
+                _ = lock.release().await;
+                yield Err(e).or_raise(|| ScanErrorKind::Cache);
+                return;
+            },
+        };
+        let mut file_stream = match backend.list_stream(prefix.as_deref()) {
+            Ok(s) => pin!(s),
+            Err(e) => {
+                _ = lock.release().await;
+                yield Err(e).or_raise(|| ScanErrorKind::Storage);
+                return;
+            },
+        };
+
+        let mut counts = Reconciled::default();
+        let mut complete_listing = true;
+        let mut cancelled = false;
+        loop {
+            let file = tokio::select! {
+                biased;
+                _ = cancel.cancelled() => {
+                    cancelled = true;
+                    tracing::info!(target = backend.name(), "Reconcile cancelled");
+                    break;
+                },
+                file = file_stream.next() => file,
+            };
+            let file = match file {
+                Some(Ok(file)) => file,
+                Some(Err(e)) => {
+                    complete_listing = false;
+                    yield Err(e).or_raise(|| ScanErrorKind::Storage);
+                    continue;
+                },
+                None => break,
+            };
+            if let Err(e) = lock.refresh().await {
+                let kind = ScanErrorKind::from_lock(&e);
+                yield Err(e).or_raise(|| kind);
+                return;
+            }
+            // Some listings leave sizes out; ask for them (with a HEAD
+            // request, for S3) instead.
+            let file = match file.size {
+                0 => match backend.stat(&file.path).await {
+                    Ok(file) => file,
+                    Err(e) => {
+                        yield Err(e).or_raise(|| ScanErrorKind::Storage);
+                        continue;
+                    },
+                },
+                _ => file,
+            };
+            // Archives aren't on record themselves, but the files inside
+            // them are (as of when the archive was last modified).
+            if file.kind() == FileKind::Archive {
+                let members: Vec<_> = known.extract_if(|path, _| is_member_of(path, &file.path)).collect();
+                if !members.is_empty() {
+                    if !members.iter().any(|(_, record)| modified_since(record, &file)) {
+                        counts.unchanged += 1;
+                        continue;
+                    }
+                    for (path, _) in &members {
+                        if let Err(e) = cache.mark_stale(backend.name(), path).await {
+                            yield Err(e).or_raise(|| ScanErrorKind::Cache);
+                        }
+                    }
+                    tracing::debug!(target = backend.name(), path = %file.path.display(), "Archive changed in storage; flagged its files as stale");
+                    counts.changed += 1;
+                    yield Ok(ReconcileEvent::Changed(file.path.clone()));
+                    continue;
+                }
+            }
+            let Some(record) = known.remove(&file.path) else {
+                counts.new += 1;
+                yield Ok(ReconcileEvent::New(file.path.clone()));
+                continue;
+            };
+            if record.size == file.size && !modified_since(&record, &file) {
+                counts.unchanged += 1;
+                continue;
+            }
+            if let Err(e) = cache.mark_stale(backend.name(), &file.path).await {
+                yield Err(e).or_raise(|| ScanErrorKind::Cache);
+                continue;
+            }
+            tracing::debug!(target = backend.name(), path = %file.path.display(), "File changed in storage; flagged as stale");
+            counts.changed += 1;
+            yield Ok(ReconcileEvent::Changed(file.path.clone()));
+        }
+
+        if !cancelled && !complete_listing && !known.is_empty() {
+            tracing::warn!(
+                target = backend.name(),
+                unlisted = known.len(),
+                "Listing was incomplete; not removing records of unlisted files"
+            );
+        } else if !cancelled {
+            let mut removed: Vec<PathBuf> = known.into_keys().collect();
+            removed.sort();
+            for path in removed {
+                match cache.delete_by_target_path(backend.name(), &path).await {
+                    Ok(_) => {
+                        counts.removed += 1;
+                        yield Ok(ReconcileEvent::Removed(path));
+                    },
+                    Err(e) => yield Err(e).or_raise(|| ScanErrorKind::Cache),
+                }
+            }
+        }
+        if let Err(e) = lock.release().await {
+            tracing::warn!(target = backend.name(), error = ?e, "Could not release target lock; it will expire");
+        }
+        yield Ok(match cancelled {
+            true => ReconcileEvent::Cancelled(counts),
+            false => ReconcileEvent::Complete(counts),
+        });
+    })
+}
+
+/// Whether the file was modified after its record was made. Backends that
+/// don't report modification times give the Unix epoch, which says nothing.
+fn modified_since(record: &FileMeta, file: &FileMeta) -> bool {
+    let unknown = |time: UtcDateTime| time == UtcDateTime::UNIX_EPOCH;
+    if unknown(record.discovered_at) || unknown(file.discovered_at) {
+        return false;
+    }
+    // Records are kept to the second.
+    record.discovered_at.unix_timestamp() != file.discovered_at.unix_timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scan::{ScanEffort, scan_file};
+    use rawr_cache::Database;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::backend::MockBackend;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_reconcile() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let html = |seed: u64| Generator::new(3169 + seed).generate().html;
+        let data = (0..3).map(|seed| (format!("{seed}.html"), html(seed)));
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+        for path in ["0.html", "1.html", "2.html"] {
+            let file = backend.stat(Path::new(path)).await.unwrap();
+            scan_file(&backend, &cache, file, false).await.unwrap();
+        }
+
+        // One file edited (changing its size), one deleted, one added.
+        let data = [
+            ("0.html", html(0)),
+            ("1.html", html(1) + "<!-- edited -->"),
+            ("3.html", html(3)),
+        ];
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+        let events: Vec<_> = reconcile(&backend, &cache, None::<&Path>, CancellationToken::new()).collect().await;
+        let Some(Ok(ReconcileEvent::Complete(counts))) = events.last() else {
+            panic!("reconcile did not complete");
+        };
+        let expected = Reconciled {
+            unchanged: 1,
+            new: 1,
+            changed: 1,
+            removed: 1,
+        };
+        assert_eq!(&expected, counts);
+        assert!(cache.get_by_target_path("library", "2.html").await.unwrap().is_none());
+        assert_eq!(vec!["1.html"], cache.list_stale_paths_for_target("library").await.unwrap());
+
+        // The next full scan re-reads the stale file, and clears the flag.
+        let file = backend.stat(Path::new("1.html")).await.unwrap();
+        let scanned = scan_file(&backend, &cache, file, false).await.unwrap();
+        assert!(matches!(scanned.effort, ScanEffort::Recalculated));
+        assert!(cache.list_stale_paths_for_target("library").await.unwrap().is_empty());
+
+        // Even if its size is the same as on record.
+        cache.mark_stale("library", "0.html").await.unwrap();
+        let file = backend.stat(Path::new("0.html")).await.unwrap();
+        let scanned = scan_file(&backend, &cache, file, false).await.unwrap();
+        assert!(matches!(scanned.effort, ScanEffort::Recalculated));
+    }
+}
@@ -1,27 +1,50 @@
-use crate::MAX_PROCESS_CONCURRENCY;
+use crate::concurrency::Concurrency;
 use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
-use crate::scan::Scan;
+use crate::progress::{Bytes, Heartbeat, Progress};
+use crate::scan::archive::scan_archive_inner;
 use crate::scan::error::{ErrorKind as ScanErrorKind, Result as ScanResult};
 use crate::scan::file::scan_file_inner;
+use crate::scan::{Damage, Scan, ScanBudget, is_member};
+use crate::{CancellationToken, MAX_PROCESS_CONCURRENCY};
 use async_stream::stream;
 use exn::ResultExt;
 use futures::stream::FuturesUnordered;
 use futures::{Stream, StreamExt};
-use rawr_cache::Repository;
-use rawr_storage::BackendHandle;
+use rawr_cache::{Repository, ScanRun};
+use rawr_compress::Compression;
+use rawr_storage::{BackendHandle, FileKind};
 use std::collections::VecDeque;
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::pin::pin;
+use std::time::{Duration, Instant};
 
 /// Progress events emitted during a streaming [`scan`].
 ///
 /// Events are emitted in a predictable lifecycle:
 /// [`Started`](Self::Started) → [`FileDiscovered`](Self::FileDiscovered) →
 /// [`DiscoveryComplete`](Self::DiscoveryComplete) →
-/// [`Scanned`](Self::Scanned) → [`Complete`](Self::Complete).
+/// [`Scanned`](Self::Scanned) → [`Complete`](Self::Complete) (or
+/// [`Cancelled`](Self::Cancelled), or [`BudgetExhausted`](Self::BudgetExhausted)).
+///
+/// Files that can't be scanned because they're [damaged](Damage) are
+/// reported by [`Damaged`](Self::Damaged) in place of `Scanned`, and files of
+/// works the user deliberately deleted by [`Tombstoned`](Self::Tombstoned);
+/// any other failure to scan a file is yielded as an error. A file scanned
+/// that isn't in the desired compression is followed by a
+/// [`CompressionMismatch`](Self::CompressionMismatch).
+///
+/// Archives aren't scanned themselves: each HTML file inside one is, at its
+/// [member path](crate::scan::member_path), as if it had been listed (but
+/// without being [discovered](Self::FileDiscovered) on its own). Files
+/// inside archives are never reported as mismatching compression, since
+/// they can't be re-compressed.
 ///
 /// **Note:** `FileDiscovered` and `Scanned` events interleave during the
 /// discovery phase, since extraction begins before all files are known.
+/// [`Heartbeat`](Self::Heartbeat) events may appear anywhere after `Started`,
+/// and exactly one is always emitted immediately before `Complete`,
+/// `Cancelled` or `BudgetExhausted`.
 pub enum ScanEvent {
     /// Scanning has begun; emitted exactly once before any other event.
     Started,
@@ -33,8 +56,36 @@ pub enum ScanEvent {
     /// A file has been scanned (from cache or fresh extraction). Boxed to keep
     /// the enum's overall size small.
     Scanned(Box<Scan>),
+    /// A file couldn't be scanned because it's damaged (empty, truncated, or
+    /// not compressed the way its extension says). Such files are left as
+    /// they are, and not cached; they'll need replacing.
+    Damaged(PathBuf, Damage),
+    /// A file was skipped because it's of a work (with this ID) the user
+    /// deliberately deleted (see [`Repository::add_tombstone`]). It's left
+    /// as it is, and not cached.
+    Tombstoned(PathBuf, u64),
+    /// The file just [scanned](Self::Scanned) isn't compressed the way the
+    /// scan was asked to expect, so it can be queued for recompression (with
+    /// [`organize_file`](crate::organize::organize_file)) straight away
+    /// rather than waiting for the next organize.
+    CompressionMismatch {
+        path: PathBuf,
+        current: Compression,
+        desired: Compression,
+    },
+    /// Aggregate progress across all files scanned so far.
+    Heartbeat(Progress),
     /// All discovered files have been scanned; the stream is finished.
     Complete,
+    /// The scan was cancelled; files already being scanned were finished, but
+    /// no others were started (and `DiscoveryComplete` may never have been
+    /// emitted). Holds the final progress. The stream is finished.
+    Cancelled(Progress),
+    /// The scan ran out of [budget](ScanBudget); as when it's cancelled,
+    /// files already being scanned were finished, but no others were
+    /// started. Holds the final progress. The stream is finished, and the
+    /// run can be [resumed](resume).
+    BudgetExhausted(Progress),
 }
 
 /// Scans all files in a storage backend, emitting [`ScanEvent`]s as progress
@@ -43,30 +94,201 @@ pub enum ScanEvent {
 /// Discovery and extraction run concurrently: up to
 /// `MAX_PROCESS_CONCURRENCY` (100) files are extracted in parallel while new
 /// files are still being discovered. This allows callers (e.g. a TUI) to
-/// show progress bars with known totals as early as possible.
+/// show progress bars with known totals as early as possible. How many files
+/// are actually in flight adapts to how quickly (and reliably) the backend
+/// responds, so that slow network storage isn't overwhelmed.
 ///
 /// An optional `prefix` restricts scanning to a subdirectory of the backend.
+/// An optional `compression` is the format files are meant to be in (as in
+/// [`Context::new`](crate::Context::new)); files scanned in any other are
+/// reported by [`CompressionMismatch`](ScanEvent::CompressionMismatch).
+/// With `repair_encoding`, files that aren't UTF-8 are repaired for
+/// extraction, as in [`scan_file`](crate::scan::scan_file).
+///
+/// The target is [locked](Repository::lock_target) for the duration of the
+/// scan: if another scan or organize holds the lock, the stream ends with a
+/// [`Locked`](ScanErrorKind::Locked) error straight after
+/// [`Started`](ScanEvent::Started). Dropping the stream before it completes
+/// leaves the lock in place until it expires.
+///
+/// To stop scanning early, cancel `cancel` rather than dropping the stream:
+/// files already being scanned are finished (and cached), the lock is
+/// released, and the stream ends with [`Cancelled`](ScanEvent::Cancelled).
+/// A scan given a (limited) `budget` stops the same way once it's spent,
+/// ending with [`BudgetExhausted`](ScanEvent::BudgetExhausted) instead.
+///
+/// The scan is recorded as a [run](rawr_cache::ScanRun) in the cache, with
+/// a checkpoint of how far through the listing it has got (saved at most once
+/// a second). Should it not complete (because it was cancelled, or the
+/// process stopped), it can be [resumed](resume) from there.
 pub fn scan<'a>(
     backend: &'a BackendHandle,
     cache: &'a Repository,
     prefix: Option<impl AsRef<Path>>,
+    compression: impl Into<Option<Compression>>,
+    repair_encoding: bool,
+    budget: ScanBudget,
+    cancel: CancellationToken,
 ) -> impl Stream<Item = LibraryResult<ScanEvent>> + 'a {
     // I've been using AsRef too much, and need to start using Into more.
     let prefix = prefix.map(|p| p.as_ref().to_path_buf());
+    let compression = compression.into();
+    stream! {
+        for await event in scan_inner(backend, cache, Run::New(prefix), compression, repair_encoding, budget, cancel) {
+            yield event.or_raise(|| LibraryErrorKind::Scan);
+        }
+    }
+}
+
+/// Resumes a [`scan`] that didn't complete, as recorded in the cache (see
+/// [`Repository::list_scan_runs`]), continuing from its checkpoint.
+///
+/// The backend is listed again, but files listed before the checkpoint are
+/// passed over rather than scanned (and aren't [discovered](ScanEvent::FileDiscovered)),
+/// so the listing must come back in the same order. Should the checkpoint
+/// file have gone, every file is scanned after all.
+///
+/// The desired `compression` isn't part of the run, so is given again, as are
+/// `repair_encoding` and the `budget` (which starts afresh). The stream is otherwise the same as a scan's; it ends with an
+/// [`UnknownRun`](ScanErrorKind::UnknownRun) error straight after
+/// [`Started`](ScanEvent::Started) if the run isn't one of `backend`'s, or
+/// has already completed.
+pub fn resume<'a>(
+    backend: &'a BackendHandle,
+    cache: &'a Repository,
+    run_id: impl Into<String>,
+    compression: impl Into<Option<Compression>>,
+    repair_encoding: bool,
+    budget: ScanBudget,
+    cancel: CancellationToken,
+) -> impl Stream<Item = LibraryResult<ScanEvent>> + 'a {
+    let run_id = run_id.into();
+    let compression = compression.into();
     stream! {
-        for await event in scan_inner(backend, cache, prefix) {
+        for await event in scan_inner(backend, cache, Run::Resume(run_id), compression, repair_encoding, budget, cancel) {
             yield event.or_raise(|| LibraryErrorKind::Scan);
         }
     }
 }
 
+/// Which run a scan is.
+enum Run {
+    /// A new run, of the subdirectory if given.
+    New(Option<PathBuf>),
+    /// Resuming the run with this ID.
+    Resume(String),
+}
+
+/// Minimum time between saving a run's checkpoint.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How far a scan run has got through the listing: the last file listed
+/// that, along with every file listed before it, has been scanned.
+struct Checkpoint {
+    /// Files listed since the checkpoint, in order, and whether each has been
+    /// scanned.
+    pending: VecDeque<(PathBuf, bool)>,
+    /// Sequence number of the first file in `pending`.
+    first: u64,
+    path: Option<PathBuf>,
+    scanned: u64,
+    /// Whether the checkpoint has moved since it was last saved.
+    moved: bool,
+    saved: Instant,
+}
+impl Checkpoint {
+    fn new(run: &ScanRun) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            first: 0,
+            path: run.checkpoint.clone(),
+            scanned: run.scanned,
+            moved: false,
+            saved: Instant::now(),
+        }
+    }
+
+    /// Records a file as listed, returning its sequence number.
+    fn listed(&mut self, path: PathBuf) -> u64 {
+        self.pending.push_back((path, false));
+        self.first + self.pending.len() as u64 - 1
+    }
+
+    /// Records the file with the sequence number as scanned, moving the
+    /// checkpoint past it if every file listed before it has been too.
+    fn scanned(&mut self, sequence: u64) {
+        if let Some((_, scanned)) = usize::try_from(sequence - self.first).ok().and_then(|i| self.pending.get_mut(i)) {
+            *scanned = true;
+        }
+        while let Some((_, true)) = self.pending.front() {
+            // Infallible: there's a front to pop.
+            let (path, _) = self.pending.pop_front().unwrap();
+            self.path = Some(path);
+            self.first += 1;
+            self.scanned += 1;
+            self.moved = true;
+        }
+    }
+
+    /// Saves the checkpoint of the run if it has moved, once the cache has
+    /// written everything scanned up to it.
+    async fn save(&mut self, cache: &Repository, run: &ScanRun) -> ScanResult<()> {
+        let Some(path) = self.path.as_deref().filter(|_| self.moved) else {
+            return Ok(());
+        };
+        cache.flush().await.or_raise(|| ScanErrorKind::Cache)?;
+        cache.checkpoint_scan_run(&run.run_id, path, self.scanned).await.or_raise(|| ScanErrorKind::Cache)?;
+        self.moved = false;
+        self.saved = Instant::now();
+        Ok(())
+    }
+
+    /// Whether the checkpoint has moved since it was last saved, long enough
+    /// ago to save it again.
+    fn is_due(&self) -> bool {
+        self.moved && self.saved.elapsed() >= CHECKPOINT_INTERVAL
+    }
+}
+
 fn scan_inner<'a>(
     backend: &'a BackendHandle,
     cache: &'a Repository,
-    prefix: Option<PathBuf>,
+    run: Run,
+    compression: Option<Compression>,
+    repair_encoding: bool,
+    budget: ScanBudget,
+    cancel: CancellationToken,
 ) -> impl Stream<Item = ScanResult<ScanEvent>> + 'a {
     stream!({
         yield Ok(ScanEvent::Started);
+        let mut lock = match cache.lock_target(backend.name(), "scan").await {
+            Ok(lock) => lock,
+            Err(e) => {
+                let kind = ScanErrorKind::from_lock(&e);
+                yield Err(e).or_raise(|| kind);
+                return;
+            },
+        };
+        let run = match run {
+            Run::New(prefix) => cache.start_scan_run(backend.name(), prefix.as_deref()).await,
+            Run::Resume(run_id) => match cache.get_scan_run(&run_id).await {
+                Ok(Some(run)) if run.target == backend.name() => Ok(run),
+                Ok(_) => {
+                    _ = lock.release().await;
+                    yield Err(exn::Exn::new(ScanErrorKind::UnknownRun(run_id)));
+                    return;
+                },
+                Err(e) => Err(e),
+            },
+        };
+        let run = match run {
+            Ok(run) => run,
+            Err(e) => {
+                _ = lock.release().await;
+                yield Err(e).or_raise(|| ScanErrorKind::Cache);
+                return;
+            },
+        };
 
         // Three options:
         // 1. We fetch all the files into memory first, then we can tell the
@@ -125,17 +347,29 @@ fn scan_inner<'a>(
         // I'm missing something. Guess I'll come back to this later on when I
         // start encountering bugs in the main CLI application...
 
-        let mut file_stream = match backend.list_stream(prefix.as_deref()) {
+        let mut file_stream = match backend.list_stream(run.prefix.as_deref()) {
             Ok(s) => pin!(s),
             Err(e) => {
+                _ = lock.release().await;
                 yield Err(e).or_raise(|| ScanErrorKind::Storage);
                 return;
             },
         };
         let mut discovery_complete = false;
+        // Running out of budget stops the scan just like cancelling it.
+        let mut cancelled = false;
+        let mut exhausted = false;
+        let deadline = budget.duration.map(|duration| tokio::time::Instant::now() + duration);
         let mut discovered = 0u64;
         let mut not_processing_yet = VecDeque::new();
         let mut processing = FuturesUnordered::new();
+        let mut concurrency = Concurrency::new(MAX_PROCESS_CONCURRENCY);
+        let mut heartbeat = Heartbeat::new();
+        let mut checkpoint = Checkpoint::new(&run);
+        // Files listed up to the checkpoint of a resumed run are passed over,
+        // but kept in case the checkpoint is never reached.
+        let mut passing_over = run.checkpoint.clone();
+        let mut passed_over = Vec::new();
         loop {
             // I really, REALLY, want to replace this with `futures::select_biased!`
             // so I can completely remove Tokio as a dependency entirely, but I
@@ -146,36 +380,144 @@ fn scan_inner<'a>(
             tokio::select! {
                 biased;
 
-                file = file_stream.next(), if !discovery_complete => match file {
-                    Some(Ok(file)) => {
+                // Files not yet being scanned haven't been polled, so they
+                // can be dropped without leaving anything half-done. Once
+                // everything is discovered and scanned there's nothing left to
+                // cancel (and the `else` arm must be reachable).
+                _ = cancel.cancelled(), if !cancelled && (!discovery_complete || !processing.is_empty()) => {
+                    cancelled = true;
+                    not_processing_yet.clear();
+                    tracing::info!(target = backend.name(), in_flight = processing.len(), "Scan cancelled");
+                },
+
+                // A timer would only fire on the next tick, even once it's too late.
+                _ = async {
+                    if let Some(deadline) = deadline && tokio::time::Instant::now() < deadline {
+                        tokio::time::sleep_until(deadline).await;
+                    }
+                }, if deadline.is_some() && !cancelled && (!discovery_complete || !processing.is_empty()) => {
+                    (cancelled, exhausted) = (true, true);
+                    not_processing_yet.clear();
+                    tracing::info!(target = backend.name(), in_flight = processing.len(), "Scan ran out of time");
+                },
+
+                file = file_stream.next(), if !discovery_complete && !cancelled => {
+                    let files = match file {
+                        Some(Ok(file)) => match &passing_over {
+                            Some(path) => {
+                                if file.path == *path {
+                                    tracing::info!(target = backend.name(), passed_over = passed_over.len() + 1, "Resuming scan from checkpoint");
+                                    passing_over = None;
+                                    passed_over.clear();
+                                } else {
+                                    passed_over.push(file);
+                                }
+                                Vec::new()
+                            },
+                            None => vec![file],
+                        },
+                        Some(Err(e)) => {
+                            yield Err(e).or_raise(|| ScanErrorKind::Storage);
+                            Vec::new()
+                        },
+                        None => {
+                            discovery_complete = true;
+                            if passing_over.take().is_some() {
+                                tracing::warn!(target = backend.name(), "Checkpoint file is gone; scanning every file");
+                            }
+                            std::mem::take(&mut passed_over)
+                        },
+                    };
+                    for file in files {
                         discovered += 1;
                         let path = file.path.clone();
+                        let sequence = checkpoint.listed(path.clone());
                         // TODO is the initial state of the future at the "here are the
                         // arguments to the function, and the function body is ready to
                         // execute on next poll" OR "everything up to the first .await call"?
                         // Because that could potentially change the size of elements
                         // in `not_processing_yet` if there are sync operations between
                         // function call and first await?
-                        let future = scan_file_inner(backend, cache, file);
-                        if processing.len() < MAX_PROCESS_CONCURRENCY {
+                        let future = async move {
+                            let started = Instant::now();
+                            let results = match file.kind() {
+                                FileKind::Archive => scan_archive_inner(backend, cache, file, repair_encoding).await,
+                                _ => vec![scan_file_inner(backend, cache, file, repair_encoding).await],
+                            };
+                            (sequence, started.elapsed(), results)
+                        };
+                        if processing.len() < concurrency.limit() {
                             processing.push(future);
                         } else {
                             not_processing_yet.push_back(future);
                         }
                         yield Ok(ScanEvent::FileDiscovered(path));
-                    },
-                    Some(Err(e)) => yield Err(e).or_raise(|| ScanErrorKind::Storage),
-                    None => {
-                        discovery_complete = true;
+                    }
+                    if discovery_complete {
+                        heartbeat.set_total(discovered);
                         yield Ok(ScanEvent::DiscoveryComplete(discovered));
                     }
                 },
 
-                Some(result) = processing.next(), if !processing.is_empty() => {
-                    yield result.map(|s| ScanEvent::Scanned(Box::new(s)));
-                    if let Some(future) = not_processing_yet.pop_front() {
+                Some((sequence, latency, results)) = processing.next(), if !processing.is_empty() => {
+                    checkpoint.scanned(sequence);
+                    let failed = results.iter().any(|r| r.as_ref().is_err_and(|e| matches!(e.deref(), ScanErrorKind::Storage)));
+                    concurrency.record(latency, failed);
+                    // An archive counts as one file, however many it holds.
+                    let mut bytes = Bytes::default();
+                    for result in results {
+                        if let Ok(scan) = &result {
+                            bytes += scan.bytes;
+                        }
+                        let mismatch = match (&result, compression) {
+                            (Ok(scan), Some(desired)) if scan.file.compression != desired && !is_member(&scan.file.path) => {
+                                Some(ScanEvent::CompressionMismatch {
+                                    path: scan.file.path.clone(),
+                                    current: scan.file.compression,
+                                    desired,
+                                })
+                            },
+                            _ => None,
+                        };
+                        yield match result {
+                            Ok(scan) => Ok(ScanEvent::Scanned(Box::new(scan))),
+                            Err(e) => match e.deref() {
+                                ScanErrorKind::Damaged(path, damage) => {
+                                    tracing::warn!(target = backend.name(), path = %path.display(), %damage, "Damaged file");
+                                    Ok(ScanEvent::Damaged(path.clone(), *damage))
+                                },
+                                ScanErrorKind::Tombstoned(path, work_id) => {
+                                    tracing::info!(target = backend.name(), path = %path.display(), work_id, "Skipping file of deleted work");
+                                    Ok(ScanEvent::Tombstoned(path.clone(), *work_id))
+                                },
+                                _ => Err(e),
+                            },
+                        };
+                        if let Some(mismatch) = mismatch {
+                            yield Ok(mismatch);
+                        }
+                    }
+                    while processing.len() < concurrency.limit()
+                        && let Some(future) = not_processing_yet.pop_front()
+                    {
                         processing.push(future);
                     }
+                    if let Some(progress) = heartbeat.record(bytes) {
+                        yield Ok(ScanEvent::Heartbeat(progress));
+                    }
+                    if !cancelled && budget.bytes.is_some() && budget.is_spent(&heartbeat.progress()) {
+                        (cancelled, exhausted) = (true, true);
+                        not_processing_yet.clear();
+                        tracing::info!(target = backend.name(), in_flight = processing.len(), "Scan read as much as it may");
+                    }
+                    if let Err(e) = lock.refresh().await {
+                        let kind = ScanErrorKind::from_lock(&e);
+                        yield Err(e).or_raise(|| kind);
+                        return;
+                    }
+                    if checkpoint.is_due() && let Err(e) = checkpoint.save(cache, &run).await {
+                        yield Err(e);
+                    }
                 },
 
                 else => {
@@ -186,7 +528,7 @@ fn scan_inner<'a>(
                     // doesn't mean I'm confident about it.
                     if !not_processing_yet.is_empty() {
                         let yet_to_process = not_processing_yet.len();
-                        let batch = MAX_PROCESS_CONCURRENCY.min(yet_to_process);
+                        let batch = concurrency.limit().min(yet_to_process);
                         processing.extend(not_processing_yet.drain(..batch));
                     } else {
                         // All done!
@@ -195,6 +537,278 @@ fn scan_inner<'a>(
                 },
             }
         }
-        yield Ok(ScanEvent::Complete);
+        // Upserts the cache is coalescing are written while the target is
+        // still locked.
+        if let Err(e) = cache.flush().await {
+            yield Err(e).or_raise(|| ScanErrorKind::Cache);
+        }
+        let recorded = match cancelled {
+            true => checkpoint.save(cache, &run).await,
+            false => cache.finish_scan_run(&run.run_id).await.map(|_| ()).or_raise(|| ScanErrorKind::Cache),
+        };
+        if let Err(e) = recorded {
+            yield Err(e);
+        }
+        if let Err(e) = lock.release().await {
+            tracing::warn!(target = backend.name(), error = ?e, "Could not release target lock; it will expire");
+        }
+        let progress = heartbeat.progress();
+        yield Ok(ScanEvent::Heartbeat(progress));
+        yield Ok(match (cancelled, exhausted) {
+            (_, true) => ScanEvent::BudgetExhausted(progress),
+            (true, false) => ScanEvent::Cancelled(progress),
+            (false, _) => ScanEvent::Complete,
+        });
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::Database;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::ByteSize;
+    use rawr_storage::backend::MockBackend;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_cancelled_scan() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let data = (0..3).map(|seed| (format!("{seed}.html"), Generator::new(3164 + seed).generate().html));
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let events: Vec<_> =
+            scan(&backend, &cache, None::<&Path>, None, false, ScanBudget::default(), cancel).collect().await;
+        let Some(Ok(ScanEvent::Cancelled(progress))) = events.last() else {
+            panic!("scan was not cancelled");
+        };
+        assert_eq!(0, progress.processed);
+        assert!(!events.iter().any(|e| matches!(e, Ok(ScanEvent::Scanned(_)))));
+
+        // The lock was released, so scanning again runs to completion.
+        let events: Vec<_> =
+            scan(&backend, &cache, None::<&Path>, None, false, ScanBudget::default(), CancellationToken::new())
+                .collect()
+                .await;
+        assert!(matches!(events.last(), Some(Ok(ScanEvent::Complete))));
+        assert_eq!(3, events.iter().filter(|e| matches!(e, Ok(ScanEvent::Scanned(_)))).count());
+    }
+
+    #[tokio::test]
+    async fn test_damaged_files() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let html = Generator::new(3188).generate().html;
+        let gzipped = Compression::Gzip.compress(html.as_bytes()).unwrap();
+        let data = [
+            ("ok.html.gz", gzipped.clone()),
+            ("empty.html.bz2", Vec::new()),
+            ("short.html.bz2", b"BZh9".to_vec()),
+            ("cut.html.gz", gzipped[..gzipped.len() / 2].to_vec()),
+            ("mislabeled.html.bz2", gzipped),
+        ];
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+
+        let events: Vec<_> =
+            scan(&backend, &cache, None::<&Path>, None, false, ScanBudget::default(), CancellationToken::new())
+                .collect()
+                .await;
+        assert!(matches!(events.last(), Some(Ok(ScanEvent::Complete))));
+        assert_eq!(1, events.iter().filter(|e| matches!(e, Ok(ScanEvent::Scanned(_)))).count());
+        let mut damaged: Vec<_> = events
+            .into_iter()
+            .filter_map(|e| match e {
+                Ok(ScanEvent::Damaged(path, damage)) => Some((path.to_string_lossy().into_owned(), damage)),
+                _ => None,
+            })
+            .collect();
+        damaged.sort_by(|a, b| a.0.cmp(&b.0));
+        let expected = [
+            ("cut.html.gz", Damage::Truncated(Compression::Gzip)),
+            ("empty.html.bz2", Damage::Empty),
+            (
+                "mislabeled.html.bz2",
+                Damage::WrongFormat {
+                    expected: Compression::Bzip2,
+                    found: Some(Compression::Gzip),
+                },
+            ),
+            ("short.html.bz2", Damage::Truncated(Compression::Bzip2)),
+        ];
+        assert_eq!(expected.map(|(path, damage)| (path.to_string(), damage)).to_vec(), damaged);
+        assert_eq!(1, cache.list_files_for_target("library").await.unwrap().len());
+        assert_eq!("file is gzip-compressed, not bzip2-compressed", damaged[2].1.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_compression_mismatch() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let html = Generator::new(3214).generate().html;
+        let data = [
+            ("plain.html", html.clone().into_bytes()),
+            ("gzipped.html.gz", Compression::Gzip.compress(html.as_bytes()).unwrap()),
+        ];
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+
+        let events: Vec<_> = scan(
+            &backend,
+            &cache,
+            None::<&Path>,
+            Compression::Gzip,
+            false,
+            ScanBudget::default(),
+            CancellationToken::new(),
+        )
+        .collect()
+        .await;
+        let mismatches: Vec<_> = events
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| match e {
+                Ok(ScanEvent::CompressionMismatch { path, current, desired }) => Some((i, path, *current, *desired)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(1, mismatches.len());
+        let (i, path, current, desired) = mismatches[0];
+        assert_eq!((Path::new("plain.html"), Compression::None, Compression::Gzip), (path.as_path(), current, desired));
+        // Straight after the file's own event.
+        assert!(matches!(&events[i - 1], Ok(ScanEvent::Scanned(scan)) if scan.file.path == *path));
+    }
+
+    #[tokio::test]
+    async fn test_tombstoned() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let kept = Generator::new(3204).generate();
+        let deleted = Generator::new(3205).generate();
+        let data = [("kept.html", kept.html), ("deleted.html", deleted.html)];
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("backup"));
+        cache.add_tombstone(deleted.expected.work_id, Some("deleted by the user")).await.unwrap();
+
+        let events: Vec<_> =
+            scan(&backend, &cache, None::<&Path>, None, false, ScanBudget::default(), CancellationToken::new())
+                .collect()
+                .await;
+        assert!(matches!(events.last(), Some(Ok(ScanEvent::Complete))));
+        let tombstoned: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                Ok(ScanEvent::Tombstoned(path, work_id)) => Some((path.clone(), *work_id)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![(PathBuf::from("deleted.html"), deleted.expected.work_id)], tombstoned);
+        assert!(cache.get_by_work_id(deleted.expected.work_id).await.unwrap().is_empty());
+        assert_eq!(1, cache.get_by_work_id(kept.expected.work_id).await.unwrap().len());
+
+        // Once the tombstone is removed, the work is added back.
+        cache.remove_tombstone(deleted.expected.work_id).await.unwrap();
+        let events: Vec<_> =
+            scan(&backend, &cache, None::<&Path>, None, false, ScanBudget::default(), CancellationToken::new())
+                .collect()
+                .await;
+        assert_eq!(2, events.iter().filter(|e| matches!(e, Ok(ScanEvent::Scanned(_)))).count());
+    }
+
+    #[tokio::test]
+    async fn test_resume() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let data = (0..4).map(|seed| (format!("{seed}.html"), Generator::new(3195 + seed).generate().html));
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+        let listed: Vec<PathBuf> = backend.list_stream(None).unwrap().map(|f| f.unwrap().path.clone()).collect().await;
+
+        // A run that got through the first two files before stopping.
+        let run = cache.start_scan_run("library", None).await.unwrap();
+        cache.checkpoint_scan_run(&run.run_id, &listed[1], 2).await.unwrap();
+
+        let events: Vec<_> =
+            resume(&backend, &cache, &run.run_id, None, false, ScanBudget::default(), CancellationToken::new())
+                .collect()
+                .await;
+        assert!(matches!(events.last(), Some(Ok(ScanEvent::Complete))));
+        let mut scanned: Vec<_> = events
+            .iter()
+            .filter_map(|e| match e {
+                Ok(ScanEvent::Scanned(scan)) => Some(scan.file.path.clone()),
+                _ => None,
+            })
+            .collect();
+        scanned.sort();
+        assert_eq!(listed[2..].to_vec(), scanned);
+        assert!(cache.get_scan_run(&run.run_id).await.unwrap().is_none());
+
+        // The run is finished, so can't be resumed again.
+        let events: Vec<_> =
+            resume(&backend, &cache, &run.run_id, None, false, ScanBudget::default(), CancellationToken::new())
+                .collect()
+                .await;
+        assert!(events.last().unwrap().is_err());
+        assert!(!events.iter().any(|e| matches!(e, Ok(ScanEvent::Scanned(_)))));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_scan_can_resume() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let data = (0..3).map(|seed| (format!("{seed}.html"), Generator::new(3164 + seed).generate().html));
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let _: Vec<_> =
+            scan(&backend, &cache, None::<&Path>, None, false, ScanBudget::default(), cancel).collect().await;
+        let runs = cache.list_scan_runs().await.unwrap();
+        assert_eq!(1, runs.len());
+        assert_eq!((None, 0), (runs[0].checkpoint.clone(), runs[0].scanned));
+
+        // Nothing was scanned, so resuming scans everything.
+        let events: Vec<_> =
+            resume(&backend, &cache, &runs[0].run_id, None, false, ScanBudget::default(), CancellationToken::new())
+                .collect()
+                .await;
+        assert!(matches!(events.last(), Some(Ok(ScanEvent::Complete))));
+        assert_eq!(3, events.iter().filter(|e| matches!(e, Ok(ScanEvent::Scanned(_)))).count());
+        assert!(cache.list_scan_runs().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scan_budget() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let data = (0..20).map(|seed| (format!("{seed:02}.html"), Generator::new(3219 + seed).generate().html));
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+        let scanned = |events: &[LibraryResult<ScanEvent>]| {
+            events.iter().filter(|e| matches!(e, Ok(ScanEvent::Scanned(_)))).count()
+        };
+
+        // Out of time before anything is scanned.
+        let budget = ScanBudget::default().with_duration(Duration::ZERO);
+        let events: Vec<_> =
+            scan(&backend, &cache, None::<&Path>, None, false, budget, CancellationToken::new()).collect().await;
+        assert!(matches!(events.last(), Some(Ok(ScanEvent::BudgetExhausted(p))) if p.processed == 0));
+        let run_id = cache.list_scan_runs().await.unwrap()[0].run_id.clone();
+
+        // Resumed, the files in flight once the first byte was read are finished.
+        let budget = ScanBudget::default().with_bytes(ByteSize(1));
+        let events: Vec<_> =
+            resume(&backend, &cache, &run_id, None, false, budget, CancellationToken::new()).collect().await;
+        assert!(matches!(events.last(), Some(Ok(ScanEvent::BudgetExhausted(_)))));
+        let first = scanned(&events);
+        assert!(first > 0 && first < 20);
+
+        // And the rest, with no limit.
+        let events: Vec<_> =
+            resume(&backend, &cache, &run_id, None, false, ScanBudget::default(), CancellationToken::new())
+                .collect()
+                .await;
+        assert!(matches!(events.last(), Some(Ok(ScanEvent::Complete))));
+        assert!(first + scanned(&events) >= 20);
+        assert!(cache.list_scan_runs().await.unwrap().is_empty());
+    }
+}
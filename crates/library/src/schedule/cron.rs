@@ -0,0 +1,209 @@
+use crate::error::{Error, ErrorKind, Result};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use time::{Date, Duration, UtcDateTime};
+
+/// How far ahead to look for a time matching a schedule. Long enough for any
+/// schedule that can match at all to match (February 29th, on a weekday, is
+/// the rarest), so schedules that find nothing within it never will.
+const HORIZON: Duration = Duration::days(366 * 8);
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// When a scheduled job runs, as a cron expression evaluated in UTC.
+///
+/// The five fields are minute (0–59), hour (0–23), day of the month (1–31),
+/// month (1–12, or `jan`–`dec`) and day of the week (0–7, Sunday being 0 and
+/// 7, or `sun`–`sat`). Each is `*`, a value, a range `a-b`, or a list of them
+/// separated by commas; `*` and ranges can be stepped (`*/15`, `9-17/2`). As
+/// with cron, when both days are restricted, a day matching either matches.
+///
+/// `@hourly`, `@daily` (or `@midnight`), `@weekly`, `@monthly` and `@yearly`
+/// (or `@annually`) stand for the usual expressions.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_library::schedule::Schedule;
+/// use time::macros::utc_datetime;
+///
+/// let nightly: Schedule = "30 3 * * *".parse().unwrap();
+/// assert_eq!(Some(utc_datetime!(2024-05-02 03:30)), nightly.next_after(utc_datetime!(2024-05-01 12:00)));
+///
+/// let weekdays: Schedule = "0 9-17/4 * * mon-fri".parse().unwrap();
+/// assert_eq!(Some(utc_datetime!(2024-05-06 09:00)), weekdays.next_after(utc_datetime!(2024-05-03 17:00)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    expression: String,
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    /// Whether the day of the month, and the day of the week, were `*`.
+    any_day: bool,
+    any_weekday: bool,
+}
+impl Schedule {
+    /// The first time after `after` (to the minute) the schedule matches, or
+    /// `None` if it never does (such as on February 30th).
+    pub fn next_after(&self, after: UtcDateTime) -> Option<UtcDateTime> {
+        let limit = after.checked_add(HORIZON)?;
+        let mut time = after.truncate_to_minute().checked_add(Duration::MINUTE)?;
+        while time <= limit {
+            time = if !self.matches_date(time.date()) {
+                time.truncate_to_day().checked_add(Duration::DAY)?
+            } else if !has(self.hours.into(), time.hour()) {
+                time.truncate_to_hour().checked_add(Duration::HOUR)?
+            } else if !has(self.minutes, time.minute()) {
+                time.checked_add(Duration::MINUTE)?
+            } else {
+                return Some(time);
+            };
+        }
+        None
+    }
+
+    fn matches_date(&self, date: Date) -> bool {
+        if !has(self.months.into(), u8::from(date.month())) {
+            return false;
+        }
+        let day = has(self.days.into(), date.day());
+        let weekday = has(self.weekdays.into(), date.weekday().number_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+}
+impl FromStr for Schedule {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let invalid = |reason| exn::Exn::new(ErrorKind::InvalidSchedule { schedule: expression.to_string(), reason });
+        let fields = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            fields => fields,
+        };
+        let fields: Vec<_> = fields.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(invalid("expected five fields: minute, hour, day, month and day of the week"));
+        };
+        let field =
+            |field: &str, range, names: &[&str], name| parse_field(field, range, names).ok_or_else(|| invalid(name));
+        let weekday_set = field(weekdays, 0..=7, &WEEKDAYS, "invalid day of the week")?;
+        let schedule = Self {
+            expression: expression.trim().to_string(),
+            minutes: field(minutes, 0..=59, &[], "invalid minute")?,
+            hours: field(hours, 0..=23, &[], "invalid hour")? as u32,
+            days: field(days, 1..=31, &[], "invalid day of the month")? as u32,
+            months: field(months, 1..=12, &MONTHS, "invalid month")? as u16,
+            // Sunday is both 0 and 7.
+            weekdays: ((weekday_set | weekday_set >> 7) & 0x7F) as u8,
+            any_day: days.starts_with('*'),
+            any_weekday: weekdays.starts_with('*'),
+        };
+        match schedule.next_after(UtcDateTime::UNIX_EPOCH) {
+            Some(_) => Ok(schedule),
+            None => Err(invalid("never matches")),
+        }
+    }
+}
+impl Display for Schedule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(&self.expression)
+    }
+}
+
+fn has(set: u64, value: u8) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parses a field into the set of values it matches, as bits.
+fn parse_field(field: &str, range: RangeInclusive<u8>, names: &[&str]) -> Option<u64> {
+    let value = |value: &str| -> Option<u8> {
+        let value = match names.iter().position(|name| name.eq_ignore_ascii_case(value)) {
+            // Names count from the start of the range (January is 1, Sunday 0).
+            Some(index) => range.start() + index as u8,
+            None => value.parse().ok()?,
+        };
+        range.contains(&value).then_some(value)
+    };
+    let mut set = 0;
+    for item in field.split(',') {
+        let (values, step) = match item.split_once('/') {
+            Some((values, step)) => (values, step.parse::<u8>().ok().filter(|step| *step > 0)?),
+            None => (item, 1),
+        };
+        let (first, last) = match values.split_once('-') {
+            _ if values == "*" => (*range.start(), *range.end()),
+            Some((first, last)) => (value(first)?, value(last)?),
+            // A single value is stepped through to the end of the range.
+            None if step > 1 => (value(values)?, *range.end()),
+            None => (value(values)?, value(values)?),
+        };
+        if first > last {
+            return None;
+        }
+        for value in (first..=last).step_by(usize::from(step)) {
+            set |= 1 << value;
+        }
+    }
+    Some(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use time::macros::utc_datetime;
+
+    #[rstest]
+    #[case::every_minute("* * * * *", utc_datetime!(2024-05-01 12:00:30), utc_datetime!(2024-05-01 12:01))]
+    #[case::hourly("@hourly", utc_datetime!(2024-05-01 12:00), utc_datetime!(2024-05-01 13:00))]
+    #[case::daily("@daily", utc_datetime!(2024-12-31 00:00), utc_datetime!(2025-01-01 00:00))]
+    #[case::weekly("@weekly", utc_datetime!(2024-05-01 12:00), utc_datetime!(2024-05-05 00:00))]
+    #[case::monthly("@monthly", utc_datetime!(2024-05-01 00:00), utc_datetime!(2024-06-01 00:00))]
+    #[case::steps("*/15 * * * *", utc_datetime!(2024-05-01 12:16), utc_datetime!(2024-05-01 12:30))]
+    #[case::stepped_value("5/20 * * * *", utc_datetime!(2024-05-01 12:46), utc_datetime!(2024-05-01 13:05))]
+    #[case::lists("0 6,18 * * *", utc_datetime!(2024-05-01 06:00), utc_datetime!(2024-05-01 18:00))]
+    #[case::months("0 0 1 jan,jul *", utc_datetime!(2024-05-01 12:00), utc_datetime!(2024-07-01 00:00))]
+    #[case::sunday_as_seven("0 0 * * 7", utc_datetime!(2024-05-01 12:00), utc_datetime!(2024-05-05 00:00))]
+    #[case::day_or_weekday("0 0 13 * fri", utc_datetime!(2024-05-01 12:00), utc_datetime!(2024-05-03 00:00))]
+    #[case::leap_day("0 0 29 2 *", utc_datetime!(2024-03-01 00:00), utc_datetime!(2028-02-29 00:00))]
+    fn test_next_after(#[case] expression: &str, #[case] after: UtcDateTime, #[case] expected: UtcDateTime) {
+        let schedule: Schedule = expression.parse().unwrap();
+        assert_eq!(Some(expected), schedule.next_after(after));
+    }
+
+    #[rstest]
+    #[case::too_few_fields("* * * *")]
+    #[case::too_many_fields("* * * * * *")]
+    #[case::out_of_range("60 * * * *")]
+    #[case::backwards_range("0 17-9 * * *")]
+    #[case::zero_step("*/0 * * * *")]
+    #[case::unknown_name("0 0 * * someday")]
+    #[case::unknown_macro("@fortnightly")]
+    #[case::never("0 0 30 2 *")]
+    fn test_invalid(#[case] expression: &str) {
+        let error = expression.parse::<Schedule>().unwrap_err();
+        assert!(matches!(*error, ErrorKind::InvalidSchedule { .. }));
+    }
+
+    #[test]
+    fn test_display() {
+        let schedule: Schedule = " @daily ".parse().unwrap();
+        assert_eq!("@daily", schedule.to_string());
+    }
+}
@@ -0,0 +1,405 @@
+//! Running maintenance jobs on a schedule.
+//!
+//! A [`Scheduler`] runs [`Job`]s — scans, verifications, cache maintenance
+//! and trash purges — whenever their [`Schedule`] comes due, so a daemon
+//! only has to configure one and [run](Scheduler::run) it until shut down.
+//!
+//! When each job last ran is recorded in the [cache](Repository), so a
+//! scheduler that's restarted picks up where it left off: a job that came due
+//! while it was down (or that has never run) runs as soon as it starts, once,
+//! however many runs it missed. A job still running when it next comes due
+//! isn't started again; that run is skipped.
+//!
+//! Jobs that fail are logged, and recorded as failed, but don't stop the
+//! scheduler or any other job.
+//!
+//! A scheduler in a [dry run](Scheduler::with_mode) changes nothing: trash
+//! purges only log what they would delete, the cache database isn't
+//! maintained, and no job's results (nor runs) are recorded in the cache.
+
+mod cron;
+
+pub use self::cron::Schedule;
+use crate::error::{ErrorCode, ErrorKind, Result};
+use crate::scan::{ScanBudget, resume, scan};
+use crate::verify::{Sampling, VerifyEvent, VerifyMode, verify};
+use crate::{CancellationToken, purge_trash};
+use exn::ResultExt;
+use futures::stream::FuturesUnordered;
+use futures::{Stream, StreamExt};
+use rawr_cache::{Database, JobRun, Repository};
+use rawr_storage::{BackendHandle, Mode};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::pin::pin;
+use std::time::Duration;
+use time::UtcDateTime;
+
+/// Maintenance that a [`Scheduler`] can run.
+pub enum Job {
+    /// [Scan](scan) a target, or just `prefix` of it.
+    ///
+    /// A scan with a (limited) `budget` that runs out of it is
+    /// [resumed](resume) the next time the job runs, so an enormous target
+    /// is scanned a bit at a time. Files that aren't UTF-8 are repaired for
+    /// extraction with `repair_encoding`.
+    Scan {
+        backend: BackendHandle,
+        prefix: Option<PathBuf>,
+        repair_encoding: bool,
+        budget: ScanBudget,
+    },
+    /// [Verify](verify) a target's files, or a sample of them.
+    ///
+    /// A random sample has the same seed every run, so checks the same
+    /// files; sampling [by date](Sampling::by_date) spreads the checks more
+    /// evenly. A [quick](VerifyMode::Quick) check makes frequent runs
+    /// cheaper.
+    Verify {
+        backend: BackendHandle,
+        sampling: Sampling,
+        mode: VerifyMode,
+    },
+    /// [Maintain](Database::maintain) the cache database, when it
+    /// [needs it](Database::needs_maintenance).
+    Maintenance(Database),
+    /// [Purge](purge_trash) the trash of files kept longer than `retention`.
+    PurgeTrash { trash: BackendHandle, retention: Duration },
+}
+impl Job {
+    async fn run(&self, cache: &Repository, cancel: CancellationToken) -> Result<()> {
+        match self {
+            Self::Scan { backend, prefix, repair_encoding, budget } => {
+                let runs = match budget.is_unlimited() {
+                    true => Vec::new(),
+                    false => cache.list_scan_runs().await.or_raise(|| ErrorKind::Cache)?,
+                };
+                let unfinished = runs.into_iter().find(|run| run.target == backend.name() && run.prefix == *prefix);
+                match unfinished {
+                    Some(run) => {
+                        tracing::info!(target = backend.name(), run_id = run.run_id, "Resuming unfinished scan");
+                        drain(resume(backend, cache, &run.run_id, None, *repair_encoding, *budget, cancel), |_| {})
+                            .await
+                    },
+                    None => {
+                        drain(scan(backend, cache, prefix.as_deref(), None, *repair_encoding, *budget, cancel), |_| {})
+                            .await
+                    },
+                }
+            },
+            Self::Verify { backend, sampling, mode } => {
+                let events = verify(backend, cache, *sampling, *mode, cancel);
+                drain(events, |event| {
+                    if let VerifyEvent::Complete(report) | VerifyEvent::Cancelled(report) = event
+                        && report.failures() > 0
+                    {
+                        tracing::warn!(
+                            target = backend.name(),
+                            failures = report.failures(),
+                            "Files failed verification"
+                        );
+                    }
+                })
+                .await
+            },
+            Self::Maintenance(_) if cache.mode().is_dry_run() => {
+                tracing::info!("Skipping cache database maintenance during dry run");
+                Ok(())
+            },
+            Self::Maintenance(db) => {
+                if db.needs_maintenance().await.or_raise(|| ErrorKind::Cache)? {
+                    let report = db.maintain().await.or_raise(|| ErrorKind::Cache)?;
+                    tracing::info!(reclaimed = report.reclaimed(), "Maintained cache database");
+                }
+                Ok(())
+            },
+            Self::PurgeTrash { trash, retention } => purge_trash(trash, *retention, cache.mode()).await.map(|_| ()),
+        }
+    }
+}
+
+/// Runs a stream of events to the end, passing each to `inspect` and
+/// logging any errors along the way; returns the first error.
+async fn drain<E>(events: impl Stream<Item = Result<E>>, mut inspect: impl FnMut(&E)) -> Result<()> {
+    let mut events = pin!(events);
+    let mut first = None;
+    while let Some(event) = events.next().await {
+        match event {
+            Ok(event) => inspect(&event),
+            Err(e) => {
+                tracing::warn!(error = ?e, "Scheduled job hit an error");
+                first.get_or_insert(e);
+            },
+        }
+    }
+    first.map_or(Ok(()), Err)
+}
+
+struct Scheduled {
+    name: String,
+    schedule: Schedule,
+    job: Job,
+}
+
+/// Runs [`Job`]s on their [`Schedule`]s.
+///
+/// Only one scheduler should run against a cache at a time; jobs are only
+/// kept from overlapping with themselves within one scheduler.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rawr_library::CancellationToken;
+/// use rawr_library::scan::ScanBudget;
+/// use rawr_library::schedule::{Job, Scheduler};
+/// # use rawr_cache::{Database, Repository};
+/// # use rawr_storage::BackendHandle;
+/// # use std::time::Duration;
+///
+/// # async fn example(db: Database, library: BackendHandle, trash: BackendHandle) -> rawr_library::error::Result<()> {
+/// let scheduler = Scheduler::new(Repository::from(&db))
+///     .with_job("scan", "0 2 * * *".parse()?, Job::Scan {
+///         backend: library,
+///         prefix: None,
+///         repair_encoding: false,
+///         budget: ScanBudget::default().with_duration(Duration::from_secs(30 * 60)),
+///     })
+///     .with_job("maintenance", "@daily".parse()?, Job::Maintenance(db))
+///     .with_job("purge", "@weekly".parse()?, Job::PurgeTrash {
+///         trash,
+///         retention: Duration::from_secs(30 * 86_400),
+///     });
+/// let cancel = CancellationToken::new();
+/// scheduler.run(cancel).await
+/// # }
+/// ```
+pub struct Scheduler {
+    cache: Repository,
+    jobs: Vec<Scheduled>,
+}
+impl Scheduler {
+    pub fn new(cache: Repository) -> Self {
+        Self { cache, jobs: Vec::new() }
+    }
+
+    /// Runs jobs for real, or in a [dry run](Mode::DryRun) only logs the
+    /// changes they would make. A dry run doesn't record when jobs ran
+    /// either, so each is due again whenever the scheduler starts.
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.cache = self.cache.with_mode(mode);
+        self
+    }
+
+    /// Runs `job` whenever `schedule` comes due, recording its runs under
+    /// `name` (replacing any job already added under it).
+    pub fn with_job(mut self, name: impl Into<String>, schedule: Schedule, job: Job) -> Self {
+        let name = name.into();
+        self.jobs.retain(|scheduled| scheduled.name != name);
+        self.jobs.push(Scheduled { name, schedule, job });
+        self
+    }
+
+    /// Runs jobs as they come due, until cancelled.
+    ///
+    /// Cancelling stops any scans or verifications running (gracefully, as
+    /// their streams do), and returns once every job running has finished.
+    ///
+    /// # Errors
+    /// Returns [`ErrorKind::Cache`] if the previous runs of jobs can't be
+    /// read from the cache. Jobs that fail don't return an error.
+    pub async fn run(&self, cancel: CancellationToken) -> Result<()> {
+        let runs = self.cache.list_job_runs().await.or_raise(|| ErrorKind::Cache)?;
+        let mut timetable = Timetable::new(&self.jobs, runs, UtcDateTime::now());
+        let mut running = FuturesUnordered::new();
+        loop {
+            let now = UtcDateTime::now();
+            for index in timetable.due(&self.jobs, now) {
+                running.push(self.run_job(index, cancel.clone()));
+            }
+            let wait = timetable.next_due().map(|due| Duration::try_from(due - now).unwrap_or_default());
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                Some(index) = running.next(), if !running.is_empty() => timetable.finished(index),
+                _ = tokio::time::sleep(wait.unwrap_or_default()), if wait.is_some() => {},
+            }
+        }
+        while running.next().await.is_some() {}
+        Ok(())
+    }
+
+    /// Runs the job at `index`, recording its run; returns `index` once
+    /// it's finished.
+    async fn run_job(&self, index: usize, cancel: CancellationToken) -> usize {
+        let Scheduled { name, job, .. } = &self.jobs[index];
+        tracing::info!(job = name, "Starting scheduled job");
+        if let Err(e) = self.cache.start_job_run(name).await {
+            tracing::warn!(job = name, error = ?e, "Could not record start of scheduled job");
+        }
+        let error = match job.run(&self.cache, cancel).await {
+            Ok(()) => {
+                tracing::info!(job = name, "Scheduled job finished");
+                None
+            },
+            Err(e) => {
+                tracing::error!(job = name, error = ?e, "Scheduled job failed");
+                Some(format!("{} ({})", &*e, e.code()))
+            },
+        };
+        if let Err(e) = self.cache.finish_job_run(name, error.as_deref()).await {
+            tracing::warn!(job = name, error = ?e, "Could not record end of scheduled job");
+        }
+        index
+    }
+}
+
+/// When each job is next due, and which are running.
+struct Timetable {
+    next: Vec<Option<UtcDateTime>>,
+    running: Vec<bool>,
+}
+impl Timetable {
+    fn new(jobs: &[Scheduled], runs: Vec<JobRun>, now: UtcDateTime) -> Self {
+        let mut runs: HashMap<_, _> = runs.into_iter().map(|run| (run.name.clone(), run)).collect();
+        let next = jobs
+            .iter()
+            .map(|scheduled| match runs.remove(&scheduled.name) {
+                Some(run) => scheduled.schedule.next_after(run.started_at),
+                // Never having run, it's missed every run so far.
+                None => Some(now),
+            })
+            .collect();
+        Self { next, running: vec![false; jobs.len()] }
+    }
+
+    /// The jobs due at `now` to be started, marking them as running. Jobs
+    /// due but still running are skipped; either way, they're next due when
+    /// their schedule next matches after `now`.
+    fn due(&mut self, jobs: &[Scheduled], now: UtcDateTime) -> Vec<usize> {
+        let mut due = Vec::new();
+        for (index, scheduled) in jobs.iter().enumerate() {
+            if self.next[index].is_none_or(|next| next > now) {
+                continue;
+            }
+            self.next[index] = scheduled.schedule.next_after(now);
+            if self.running[index] {
+                tracing::warn!(job = scheduled.name, "Scheduled job is still running; skipping this run");
+                continue;
+            }
+            self.running[index] = true;
+            due.push(index);
+        }
+        due
+    }
+
+    fn finished(&mut self, index: usize) {
+        self.running[index] = false;
+    }
+
+    /// When the next job is due, if any ever are.
+    fn next_due(&self) -> Option<UtcDateTime> {
+        self.next.iter().flatten().min().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_storage::backend::MockBackend;
+    use std::path::Path;
+    use std::sync::Arc;
+    use time::macros::utc_datetime;
+
+    fn purge_job(trash: &BackendHandle) -> Job {
+        Job::PurgeTrash {
+            trash: trash.clone(),
+            retention: Duration::from_secs(86_400),
+        }
+    }
+
+    #[test]
+    fn test_timetable() {
+        let trash: BackendHandle = Arc::new(MockBackend::default());
+        let jobs = [
+            Scheduled {
+                name: "hourly".into(),
+                schedule: "@hourly".parse().unwrap(),
+                job: purge_job(&trash),
+            },
+            Scheduled {
+                name: "daily".into(),
+                schedule: "@daily".parse().unwrap(),
+                job: purge_job(&trash),
+            },
+            Scheduled {
+                name: "new".into(),
+                schedule: "@weekly".parse().unwrap(),
+                job: purge_job(&trash),
+            },
+        ];
+        let run = |name: &str, started_at| JobRun {
+            name: name.into(),
+            started_at,
+            finished_at: Some(started_at),
+            error: None,
+        };
+        let runs = vec![
+            run("hourly", utc_datetime!(2024-05-01 09:00)),
+            run("daily", utc_datetime!(2024-05-01 00:00)),
+        ];
+        let mut timetable = Timetable::new(&jobs, runs, utc_datetime!(2024-05-01 11:30));
+
+        // Missed runs (however many) and jobs that never ran are due at once.
+        assert_eq!(vec![0, 2], timetable.due(&jobs, utc_datetime!(2024-05-01 11:30)));
+        assert_eq!(Some(utc_datetime!(2024-05-01 12:00)), timetable.next_due());
+        assert!(timetable.due(&jobs, utc_datetime!(2024-05-01 11:59)).is_empty());
+
+        // The hourly job overran, so its next run is skipped.
+        timetable.finished(2);
+        assert!(timetable.due(&jobs, utc_datetime!(2024-05-01 12:00)).is_empty());
+        timetable.finished(0);
+        assert_eq!(vec![0], timetable.due(&jobs, utc_datetime!(2024-05-01 13:00)));
+        assert_eq!(vec![1], timetable.due(&jobs, utc_datetime!(2024-05-02 00:00)));
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let expired = format!("{}-0.html", blake3::hash(b"trashed").to_hex());
+        let trash: BackendHandle = Arc::new(MockBackend::with_data([(expired.as_str(), "old")]));
+        let scheduler = Scheduler::new(cache.clone()).with_job("purge", "@daily".parse().unwrap(), purge_job(&trash));
+
+        // Never having run, the job runs as soon as the scheduler starts.
+        let cancel = CancellationToken::new();
+        let (result, run) = tokio::join!(scheduler.run(cancel.clone()), async {
+            let run = loop {
+                match cache.get_job_run("purge").await.unwrap() {
+                    Some(run) if run.finished_at.is_some() => break run,
+                    _ => tokio::time::sleep(Duration::from_millis(10)).await,
+                }
+            };
+            cancel.cancel();
+            run
+        });
+        result.unwrap();
+        assert!(run.succeeded());
+        assert!(!trash.exists(Path::new(&expired)).await.unwrap());
+
+        // Restarted, the scheduler remembers the job ran, and it's not due until tomorrow.
+        let runs = cache.list_job_runs().await.unwrap();
+        let timetable = Timetable::new(&scheduler.jobs, runs, UtcDateTime::now());
+        assert!(timetable.next_due().unwrap() > UtcDateTime::now());
+    }
+
+    #[tokio::test]
+    async fn test_run_dry_run() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let scheduler = Scheduler::new(Repository::from(&db)).with_mode(Mode::DryRun);
+        let cache = &scheduler.cache;
+        let expired = format!("{}-0.html", blake3::hash(b"trashed").to_hex());
+        let trash: BackendHandle = Arc::new(MockBackend::with_data([(expired.as_str(), "old")]));
+        purge_job(&trash).run(cache, CancellationToken::new()).await.unwrap();
+        assert!(trash.exists(Path::new(&expired)).await.unwrap());
+        Job::Maintenance(db).run(cache, CancellationToken::new()).await.unwrap();
+    }
+}
@@ -0,0 +1,334 @@
+//! Undoing a reorganization.
+//!
+//! A [`Snapshot`] records where every file on a target is (going by the
+//! cache, so scan the target first) and what it holds. Take one before a big
+//! [`organize`](crate::organize::organize), keep it (it serializes to JSON),
+//! and [`rollback`] puts files back where the snapshot had them if the
+//! result isn't what was wanted.
+//!
+//! Files are found again by their content, so it doesn't matter how many
+//! times they've moved (or been re-compressed) since: each is moved back to
+//! its old path, in its old compression. Duplicates removed since are
+//! recreated from another copy of the same content, if there still is one.
+//! Rolling back is best-effort; anything that can't be restored (a path
+//! that's been taken by something else, content that's gone) is reported
+//! rather than forced, and nothing is ever overwritten.
+
+use crate::error::{Error as LibraryError, ErrorKind as LibraryErrorKind, Result as LibraryResult};
+use crate::organize::file::convert;
+use exn::ResultExt;
+use rawr_cache::Repository;
+use rawr_compress::Compression;
+use rawr_extract::models::Version;
+use rawr_storage::BackendHandle;
+use rawr_storage::file::{FileInfo, Processed};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use time::{OffsetDateTime, UtcDateTime};
+
+type File = FileInfo<Processed>;
+
+/// Where every file on a target was, at the time it was taken.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(with = "time::serde::rfc3339")]
+    pub taken: OffsetDateTime,
+    /// The target the snapshot is of.
+    pub target: String,
+    pub files: Vec<SnapshotFile>,
+}
+
+/// A file in a [`Snapshot`].
+///
+/// The file's compression is that of its path's extension.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    pub path: PathBuf,
+    pub file_hash: String,
+    pub content_hash: String,
+}
+
+/// What [`rollback`] did.
+#[derive(Debug, Default)]
+pub struct RollbackReport {
+    /// Number of files that were still where the snapshot had them.
+    pub unchanged: usize,
+    /// Files moved back, from where they were to where they were restored.
+    pub restored: Vec<(PathBuf, PathBuf)>,
+    /// Files recreated from another copy of the same content.
+    pub recreated: Vec<PathBuf>,
+    /// Files that couldn't be restored, and why.
+    pub unrecoverable: Vec<(PathBuf, Unrecoverable)>,
+}
+
+/// Why a file couldn't be [rolled back](rollback).
+#[derive(Debug)]
+pub enum Unrecoverable {
+    /// The file's content is no longer anywhere on the target.
+    Missing,
+    /// Something else is at the file's path.
+    Occupied,
+    /// Restoring the file failed.
+    Failed(LibraryError),
+}
+
+/// Takes a snapshot of the files on record for the target named `target`.
+///
+/// # Errors
+/// Returns [`Exn<LibraryErrorKind::Cache>`](LibraryErrorKind::Cache) if
+/// querying the cache fails.
+pub async fn snapshot(cache: &Repository, target: impl AsRef<str>) -> LibraryResult<Snapshot> {
+    let target = target.as_ref();
+    let files = cache.list_files_for_target(target).await.or_raise(|| LibraryErrorKind::Cache)?;
+    let mut files: Vec<_> = files
+        .into_iter()
+        .map(|(file, _)| SnapshotFile {
+            path: file.path.clone(),
+            file_hash: file.file_hash,
+            content_hash: file.content_hash,
+        })
+        .collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Snapshot {
+        taken: OffsetDateTime::now_utc(),
+        target: target.to_string(),
+        files,
+    })
+}
+
+/// Moves files on the target of `backend` back to where `snapshot` had them,
+/// updating the cache to match.
+///
+/// Files that weren't on the target when the snapshot was taken are left
+/// where they are, unless they're in the way of a file being restored (in
+/// which case that file is reported as [occupied](Unrecoverable::Occupied)).
+/// If `cache` is a [dry run](rawr_storage::Mode::DryRun), so is the rollback:
+/// the report is of what would have been restored.
+///
+/// # Errors
+/// Returns [`Exn<LibraryErrorKind::Rollback>`](LibraryErrorKind::Rollback)
+/// if the snapshot is of another target, or [`Exn<LibraryErrorKind::Cache>`](LibraryErrorKind::Cache)
+/// if querying the cache fails. Failing to restore a file isn't an error;
+/// see [`RollbackReport::unrecoverable`].
+pub async fn rollback(
+    backend: &BackendHandle,
+    cache: &Repository,
+    snapshot: &Snapshot,
+) -> LibraryResult<RollbackReport> {
+    if snapshot.target != backend.name() {
+        exn::bail!(LibraryErrorKind::Rollback);
+    }
+    let backend = &cache.mode().backend(backend.clone());
+    let mut report = RollbackReport::default();
+    let current = cache.list_files_for_target(&snapshot.target).await.or_raise(|| LibraryErrorKind::Cache)?;
+    let by_path: HashMap<_, _> = current.iter().enumerate().map(|(i, (file, _))| (&file.path, i)).collect();
+
+    // Files still where they were aren't candidates for moving anywhere else.
+    let mut claimed = vec![false; current.len()];
+    let mut moved = Vec::new();
+    for entry in &snapshot.files {
+        match by_path.get(&entry.path) {
+            Some(&i) if current[i].0.content_hash == entry.content_hash => {
+                claimed[i] = true;
+                report.unchanged += 1;
+            },
+            _ => moved.push(entry),
+        }
+    }
+    let mut unclaimed: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, (file, _)) in current.iter().enumerate().rev() {
+        if !claimed[i] {
+            unclaimed.entry(&file.content_hash).or_default().push(i);
+        }
+    }
+    let mut pending = Vec::new();
+    let mut removed = Vec::new();
+    for entry in moved {
+        match unclaimed.get_mut(entry.content_hash.as_str()).and_then(Vec::pop) {
+            Some(i) => pending.push((entry, i)),
+            None => removed.push(entry),
+        }
+    }
+
+    // A file's old path may be taken by another file yet to be moved back
+    // (to its own old path), so keep going until no more can be.
+    while !pending.is_empty() {
+        let mut blocked = Vec::new();
+        let before = pending.len();
+        for (entry, i) in pending {
+            let (file, version) = &current[i];
+            match backend.exists(&entry.path).await.or_raise(|| LibraryErrorKind::Rollback) {
+                Ok(false) => {},
+                Ok(true) => {
+                    blocked.push((entry, i));
+                    continue;
+                },
+                Err(e) => {
+                    report.unrecoverable.push((entry.path.clone(), Unrecoverable::Failed(e)));
+                    continue;
+                },
+            }
+            match restore(backend, cache, entry, file, version, false).await {
+                Ok(()) => report.restored.push((file.path.clone(), entry.path.clone())),
+                Err(e) => report.unrecoverable.push((entry.path.clone(), Unrecoverable::Failed(e))),
+            }
+        }
+        if blocked.len() == before {
+            report
+                .unrecoverable
+                .extend(blocked.into_iter().map(|(entry, _)| (entry.path.clone(), Unrecoverable::Occupied)));
+            break;
+        }
+        pending = blocked;
+    }
+
+    // Removed duplicates are copied from wherever their content is now.
+    if !removed.is_empty() {
+        let current = cache.list_files_for_target(&snapshot.target).await.or_raise(|| LibraryErrorKind::Cache)?;
+        let by_content: HashMap<_, _> =
+            current.iter().map(|(file, version)| (&file.content_hash, (file, version))).collect();
+        for entry in removed {
+            let Some((file, version)) = by_content.get(&entry.content_hash) else {
+                report.unrecoverable.push((entry.path.clone(), Unrecoverable::Missing));
+                continue;
+            };
+            match backend.exists(&entry.path).await.or_raise(|| LibraryErrorKind::Rollback) {
+                Ok(false) => {},
+                Ok(true) => {
+                    report.unrecoverable.push((entry.path.clone(), Unrecoverable::Occupied));
+                    continue;
+                },
+                Err(e) => {
+                    report.unrecoverable.push((entry.path.clone(), Unrecoverable::Failed(e)));
+                    continue;
+                },
+            }
+            match restore(backend, cache, entry, file, version, true).await {
+                Ok(()) => report.recreated.push(entry.path.clone()),
+                Err(e) => report.unrecoverable.push((entry.path.clone(), Unrecoverable::Failed(e))),
+            }
+        }
+    }
+    if !report.unrecoverable.is_empty() {
+        tracing::warn!(
+            target = %snapshot.target,
+            unrecoverable = report.unrecoverable.len(),
+            "Some files could not be rolled back"
+        );
+    }
+    Ok(report)
+}
+
+/// Puts the content of `file` at the snapshot entry's path (in the path's
+/// compression), moving it unless `copy`.
+async fn restore(
+    backend: &BackendHandle,
+    cache: &Repository,
+    entry: &SnapshotFile,
+    file: &File,
+    version: &Version,
+    copy: bool,
+) -> LibraryResult<()> {
+    let compression_source = file.compression;
+    let compression_target = Compression::from_path(&entry.path);
+    let (size, file_hash) = if compression_source == compression_target && !copy {
+        backend.rename(&file.path, &entry.path).await.or_raise(|| LibraryErrorKind::Rollback)?;
+        (file.size, file.file_hash.clone())
+    } else {
+        let data = backend.read(&file.path).await.or_raise(|| LibraryErrorKind::Rollback)?;
+        let converted =
+            convert(&data, compression_source, compression_target).or_raise(|| LibraryErrorKind::Rollback)?;
+        backend.write(&entry.path, &converted.data).await.or_raise(|| LibraryErrorKind::Rollback)?;
+        if !copy {
+            backend.delete(&file.path).await.or_raise(|| LibraryErrorKind::Rollback)?;
+        }
+        (converted.data.len() as u64, converted.file_hash)
+    };
+
+    // As when organizing, cache errors are ignored: the next scan cleans up
+    // after them.
+    if !copy {
+        _ = cache.delete_by_target_path(&file.target, &file.path).await;
+    }
+    let restored = FileInfo::new(&file.target, &entry.path, size, UtcDateTime::now(), compression_target)
+        .with_file_hash(file_hash)
+        .with_content_hash(&entry.content_hash);
+    _ = cache.upsert(&restored, version).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organize::organize;
+    use crate::{CancellationToken, Context};
+    use futures::StreamExt;
+    use rawr_cache::Database;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::backend::MockBackend;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_rollback() {
+        let html = Generator::new(3186).generate().html;
+        let version = rawr_extract::extract(&html).unwrap();
+        let compressed = Compression::Gzip.compress(html.as_bytes()).unwrap();
+        let other = Generator::new(3187).generate().html;
+        let other_version = rawr_extract::extract(&other).unwrap();
+        let files = [
+            ("old/a.html", html.as_bytes().to_vec(), &version),
+            ("old/a.html.gz", compressed, &version),
+            ("old/b.html", other.as_bytes().to_vec(), &other_version),
+        ];
+        let backend: BackendHandle = Arc::new(
+            MockBackend::with_data(files.iter().map(|(path, data, _)| (*path, data.clone()))).with_name("library"),
+        );
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        for (path, data, version) in &files {
+            let file =
+                FileInfo::new("library", path, data.len() as u64, UtcDateTime::now(), Compression::from_path(path))
+                    .with_file_hash(blake3::hash(data).to_string())
+                    .with_content_hash(&version.hash);
+            cache.upsert(&file, version).await.unwrap();
+        }
+        let snapshot = snapshot(&cache, "library").await.unwrap();
+        assert_eq!(3, snapshot.files.len());
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(snapshot, serde_json::from_str(&json).unwrap());
+
+        // The duplicate is removed, then the rest organized into bzip2.
+        backend.delete(Path::new("old/a.html.gz")).await.unwrap();
+        cache.delete_by_target_path("library", "old/a.html.gz").await.unwrap();
+        let ctx = Context::new("{{ work }}".parse().unwrap(), Compression::Bzip2, None);
+        let results: Vec<_> = organize(&backend, &cache, &ctx, CancellationToken::new()).collect().await;
+        assert!(results.iter().all(Result::is_ok));
+        assert!(!backend.exists(Path::new("old/a.html")).await.unwrap());
+        // Something new takes the place of one of the old files.
+        backend.write(Path::new("old/b.html"), b"new").await.unwrap();
+
+        let report = rollback(&backend, &cache, &snapshot).await.unwrap();
+        assert_eq!(0, report.unchanged);
+        assert_eq!(
+            vec![PathBuf::from("old/a.html")],
+            report.restored.iter().map(|(_, to)| to.clone()).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![PathBuf::from("old/a.html.gz")], report.recreated);
+        assert_eq!(1, report.unrecoverable.len());
+        assert!(
+            matches!(report.unrecoverable[0], (ref path, Unrecoverable::Occupied) if path == Path::new("old/b.html"))
+        );
+        assert_eq!(html.as_bytes(), backend.read(Path::new("old/a.html")).await.unwrap());
+        let gzipped = backend.read(Path::new("old/a.html.gz")).await.unwrap();
+        assert_eq!(html.as_bytes(), Compression::Gzip.decompress(&gzipped).unwrap());
+        assert_eq!(b"new".to_vec(), backend.read(Path::new("old/b.html")).await.unwrap());
+
+        // Rolling back again finds everything that could be restored already
+        // where it should be.
+        let again = rollback(&backend, &cache, &snapshot).await.unwrap();
+        assert_eq!(2, again.unchanged);
+        assert!(again.restored.is_empty() && again.recreated.is_empty());
+    }
+}
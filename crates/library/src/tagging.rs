@@ -0,0 +1,114 @@
+//! Tagging organized files with their work's metadata.
+//!
+//! Object storage can't list a bucket by anything but key prefix, so bucket
+//! lifecycle rules (and external tools) that should only apply to some works
+//! select them by [object tags](ObjectTags) instead. Each target can be given
+//! the [`TagField`]s to tag files with (see
+//! [`Context::with_object_tags`](crate::Context::with_object_tags)); files
+//! are tagged wherever organizing places them, and again each time they're
+//! found already in place (so a tag that failed is retried).
+
+use rawr_extract::models::Version;
+use rawr_storage::{BackendHandle, ObjectTags};
+use rslug::slugify;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A piece of a work's metadata a file can be tagged with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagField {
+    /// `work_id`: the work's AO3 ID.
+    WorkId,
+    /// `fandom`: the slug of the alphabetically-first fandom (as in path
+    /// templates).
+    Fandom,
+    /// `rating`: the rating's short form (`G`, `T`, `M`, `E` or `N`).
+    Rating,
+    /// `language`: the ISO 639 code of the work's language, or its name if
+    /// it has none.
+    Language,
+    /// `complete`: `true` or `false`.
+    Complete,
+}
+impl TagField {
+    /// The tag's key.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::WorkId => "work_id",
+            Self::Fandom => "fandom",
+            Self::Rating => "rating",
+            Self::Language => "language",
+            Self::Complete => "complete",
+        }
+    }
+
+    /// The tag's value for a version, if it has one.
+    fn value(&self, version: &Version) -> Option<String> {
+        let metadata = &version.metadata;
+        match self {
+            Self::WorkId => Some(metadata.work_id.to_string()),
+            Self::Fandom => metadata.fandoms.iter().map(|f| f.name.as_str()).min().map(|name| slugify!(name)),
+            Self::Rating => metadata.rating.as_ref().map(|rating| rating.as_short_str().to_string()),
+            Self::Language => {
+                Some(metadata.language.iso_code.clone().unwrap_or_else(|| metadata.language.name.clone()))
+            },
+            Self::Complete => Some(metadata.is_complete().to_string()),
+        }
+    }
+}
+
+/// The tags for a version's file, made of `fields` (those the version has
+/// no value for are left out).
+pub(crate) fn object_tags(version: &Version, fields: &[TagField]) -> ObjectTags {
+    let mut tags = ObjectTags::new();
+    for field in fields {
+        let Some(value) = field.value(version) else { continue };
+        // Every key is valid, and there are fewer fields than tags allowed.
+        if let Err(e) = tags.insert(field.key(), value) {
+            tracing::warn!(error = ?e, "Could not build object tag");
+        }
+    }
+    tags
+}
+
+/// Tags a file, logging rather than failing when the backend can't: the file
+/// is where it belongs either way, and the next organize tags it again.
+pub(crate) async fn tag(backend: &BackendHandle, path: &Path, tags: &ObjectTags) {
+    if let Err(e) = backend.tag(path, tags).await {
+        tracing::warn!(target = backend.name(), path = %path.display(), error = ?e, "Could not tag file");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_extract::models::{Fandom, Rating};
+
+    #[test]
+    fn test_object_tags() {
+        let html = rawr_extract::testing::Generator::new(3213).generate().html;
+        let mut version = rawr_extract::extract(&html).unwrap();
+        version.metadata.work_id = 12345;
+        version.metadata.fandoms = vec![
+            Fandom { name: "Sherlock (TV)".into() },
+            Fandom {
+                name: "Harry Potter - J. K. Rowling".into(),
+            },
+        ];
+        version.metadata.rating = Some(Rating::Mature);
+        let tags = object_tags(&version, &[TagField::WorkId, TagField::Fandom, TagField::Rating]);
+        let tags: Vec<_> = tags.iter().collect();
+        assert_eq!(
+            vec![
+                ("work_id", "12345"),
+                ("fandom", "harry-potter-j-k-rowling"),
+                ("rating", "M")
+            ],
+            tags
+        );
+
+        version.metadata.rating = None;
+        assert!(object_tags(&version, &[TagField::Rating]).is_empty());
+    }
+}
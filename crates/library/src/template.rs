@@ -9,6 +9,11 @@
 //!   first to avoid artifacts like leading/trailing hyphens.
 //! - **`truncate`** — Truncates strings to a maximum byte length at a character
 //!   boundary, usable as either `truncate(value, n)` or `{{ value|truncate: n }}`.
+//! - **`default`** — Falls back to another value when one is missing or empty,
+//!   as in `{{ rating|default: "unrated" }}` or `default(series?.name, "standalone")`.
+//! - **`omit_empty`** — Drops the whole path segment it's in when the value is
+//!   missing or empty, so `{{ fandom }}/series-{{ series?.name|omit_empty|slug }}/{{ work }}`
+//!   gives `fandom/123` for a work in no series, rather than `fandom/series-/123`.
 //!
 //! # Template Variables
 //!
@@ -20,12 +25,46 @@
 //! | `words`             | `u64`            | Word count                                  |
 //! | `chapters.written`  | `u64`            | Number of posted chapters                   |
 //! | `chapters.total`    | `Option<u64>`    | Planned total chapters                      |
+//! | `complete`          | `bool`           | Whether all planned chapters are posted     |
 //! | `fandom`            | `String`         | Alphabetically-first fandom name            |
+//! | `author`            | `Option<Dict>`   | Alphabetically-first author, if any         |
+//! | `author.username`   | `?String`        | AO3 username of that author                 |
+//! | `author.pseudonym`  | `?String`        | Pseudonym, if different from the username   |
+//! | `author.name`       | `?String`        | Pseudonym if present, otherwise username    |
+//! | `author_count`      | `u64`            | Number of credited authors                  |
+//! | `relationship`      | `Option<String>` | Alphabetically-first relationship tag       |
+//! | `character`         | `Option<String>` | Alphabetically-first character tag          |
 //! | `series`            | `Option<Dict>`   | Collection; the lowest-ID series, if exists |
 //! | `series.id`         | `?u64`           | ID of the lowest-ID series                  |
 //! | `series.name`       | `?String`        | Name of that series                         |
 //! | `series.position`   | `?u64`           | Position within that series                 |
 //! | `hash`              | `String`         | Zero-padded 8-hex-digit CRC32 of content    |
+//! | `volume`            | `Option<Dict>`   | Volume of a work split for rendering        |
+//! | `volume.number`     | `?u64`           | Number of the volume, counting from 1       |
+//! | `volume.count`      | `?u64`           | Number of volumes the work was split into   |
+//!
+//! `volume` is only set by [`PathGenerator::generate_volume`], so templates
+//! should check for it (`{% if volume %}-vol{{ volume.number }}{% endif %}`).
+//! Missing dictionaries are empty, so their fields can be looked up with `?.`
+//! (such as `series?.name`) to get nothing, rather than an error.
+//!
+//! Relationship tags separate characters with `/` (or ` & `), which would
+//! otherwise start a new directory: render them with `slug` (e.g.
+//! `{{ relationship|slug }}` gives `draco-malfoy-harry-potter`).
+//!
+//! # Sub-templates
+//!
+//! Templates can include named sub-templates (registered with
+//! [`PathGenerator::with_partial`]) with `{% include "name" %}`, so that
+//! several templates can share parts of a layout, such as the same file name
+//! stem for imports and exports. Sub-templates see the same variables, and
+//! can include each other.
+//!
+//! # Compatibility
+//!
+//! Generated paths are only checked for safety (no directory traversal), not
+//! for whether a filesystem accepts them. Targets on Windows or SMB shares
+//! reject names that Linux is happy with; see [`PathCompat`].
 //!
 //! > **IMPORTANT:** in order to save multiple versions of the same work, you
 //! > **must** include the `hash` variable in your path templates. It is the only
@@ -43,7 +82,7 @@
 //! # use std::str::FromStr;
 //! # use time::{Date, Month, UtcDateTime};
 //! # let version = Version {
-//! #     hash: String::new(), length: 0, crc32: 0,
+//! #     hash: String::new(), length: 0, crc32: 0, encoding: Encoding::Utf8, detected_language: None,
 //! #     metadata: Metadata {
 //! #         work_id: 12345, title: "My Story".into(), authors: vec![],
 //! #         fandoms: vec![Fandom { name: "Marvel".into() }],
@@ -53,6 +92,8 @@
 //! #         words: 5000,
 //! #         published: Date::from_calendar_date(2024, Month::January, 1).unwrap(),
 //! #         last_modified: Date::from_calendar_date(2024, Month::January, 1).unwrap(),
+//! #         provenance: Default::default(),
+//! #         work_skin: None,
 //! #         series: vec![],
 //! #     },
 //! #     extracted_at: UtcDateTime::now(),
@@ -71,7 +112,7 @@
 use crate::error::{Error, ErrorKind, Result};
 use exn::ResultExt;
 use rawr_compress::Compression;
-use rawr_extract::models::Version;
+use rawr_extract::models::{TagKind, Version};
 use rawr_storage::ValidatedPath;
 use std::{path::PathBuf, str::FromStr};
 use tracing::instrument;
@@ -97,8 +138,9 @@ impl FromStr for PathGenerator {
 
     /// Compiles the given template string into a reusable [`PathGenerator`].
     ///
-    /// Registers the `slug` formatter and `truncate` function before compiling,
-    /// so both are available in the template. Returns [`ErrorKind::Template`] if
+    /// Registers the `slug` formatter and the `truncate`, `default` and
+    /// `omit_empty` functions before compiling, so all are available in the
+    /// template. Returns [`ErrorKind::Template`] if
     /// the template syntax is invalid.
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         let mut engine = Engine::new();
@@ -119,6 +161,30 @@ impl PathGenerator {
     //     template.as_ref().parse()?.with_config(config)
     // }
 
+    /// Compiles `template`, registering each of `partials` as a named
+    /// sub-template (see [`with_partial`](Self::with_partial)).
+    pub fn with_partials<N, S>(template: &str, partials: impl IntoIterator<Item = (N, S)>) -> Result<Self>
+    where
+        N: Into<String>,
+        S: Into<String>,
+    {
+        partials
+            .into_iter()
+            .try_fold(template.parse::<Self>()?, |generator, (name, source)| generator.with_partial(name, source))
+    }
+
+    /// Registers a named sub-template, which the template (and other
+    /// sub-templates) can include with `{% include "name" %}`.
+    ///
+    /// Includes are resolved when rendering, so sub-templates can be
+    /// registered in any order, and replace any registered under the same
+    /// name; including one that was never registered fails [`generate`](Self::generate).
+    /// Returns [`ErrorKind::Template`] if the sub-template's syntax is invalid.
+    pub fn with_partial(mut self, name: impl Into<String>, source: impl Into<String>) -> Result<Self> {
+        self.engine.add_template(name.into(), source.into()).or_raise(|| ErrorKind::Template)?;
+        Ok(self)
+    }
+
     /// Renders the template against the given [`Version`]'s metadata, returning
     /// the normalized path without any file extension.
     ///
@@ -128,12 +194,40 @@ impl PathGenerator {
     pub fn generate(&self, version: impl AsRef<Version>) -> Result<PathBuf> {
         let path = self
             .template
-            .render(&self.engine, Self::parameters(version.as_ref()))
+            .render(&self.engine, Self::parameters(version.as_ref(), None))
             .to_string()
             .or_raise(|| ErrorKind::Template)?;
         Self::normalize(path)
     }
 
+    /// Renders the template for volume `number` of the `count` volumes a work
+    /// was split into when rendering, returning the path without any file
+    /// extension.
+    ///
+    /// Templates that don't use `volume` get `-vol{number}` appended to the
+    /// file name, so that volumes never overwrite each other; a work that
+    /// wasn't split (`count` of 1) gets the same path as from
+    /// [`generate`](Self::generate).
+    #[instrument(skip_all, fields(work_id = version.as_ref().metadata.work_id, volume = number))]
+    pub fn generate_volume(&self, version: impl AsRef<Version>, number: u32, count: u32) -> Result<PathBuf> {
+        let version = version.as_ref();
+        if count <= 1 {
+            return self.generate(version);
+        }
+        let path = self
+            .template
+            .render(&self.engine, Self::parameters(version, Some((number, count))))
+            .to_string()
+            .or_raise(|| ErrorKind::Template)?;
+        let mut path = Self::normalize(path)?;
+        if path == self.generate(version)? {
+            let mut name = path.file_name().unwrap_or_default().to_os_string();
+            name.push(format!("-vol{number}"));
+            path.set_file_name(name);
+        }
+        Ok(path)
+    }
+
     /// Renders the template and appends a file extension and optional compression suffix.
     ///
     /// The extension is dot-separated and trimmed of leading/trailing dots, so both
@@ -152,29 +246,56 @@ impl PathGenerator {
         ext: impl AsRef<str>,
         compression: impl Into<Option<Compression>>,
     ) -> Result<PathBuf> {
-        let mut path = self.generate(version)?;
+        self.generate_with_compat(version, ext, compression, PathCompat::Posix)
+    }
+
+    /// Like [`generate_with_ext`](Self::generate_with_ext), but also makes
+    /// the path acceptable under `compat`'s naming rules. Extensions are kept
+    /// intact when a file name has to be shortened.
+    pub fn generate_with_compat(
+        &self,
+        version: impl AsRef<Version>,
+        ext: impl AsRef<str>,
+        compression: impl Into<Option<Compression>>,
+        compat: PathCompat,
+    ) -> Result<PathBuf> {
         let compression = compression.into().unwrap_or(Compression::None);
-        path.add_extension(ext.as_ref().trim().trim_matches('.'));
+        let mut extensions = vec![ext.as_ref().trim().trim_matches('.')];
         if !matches!(compression, Compression::None) {
-            path.add_extension(compression.extension().trim_matches('.'));
+            extensions.push(compression.extension().trim_matches('.'));
+        }
+        let reserved = extensions.iter().map(|ext| ext.len() + 1).sum();
+        let mut path = compat.apply(self.generate(version)?, reserved)?;
+        for ext in extensions {
+            path.add_extension(ext);
         }
         Ok(path)
     }
 
-    /// Trims each path segment, joins them with `/`, then validates via
-    /// [`rawr_storage::ValidatedPath`].
+    /// Trims each path segment, drops those marked by `omit_empty`, joins the
+    /// rest with `/`, then validates via [`rawr_storage::ValidatedPath`].
     fn normalize(s: impl Into<String>) -> Result<PathBuf> {
-        let path = s.into().trim().split('/').map(str::trim).collect::<Vec<_>>().join("/");
+        let path = s
+            .into()
+            .trim()
+            .split('/')
+            .filter(|segment| !segment.contains(addons::OMIT_SEGMENT))
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join("/");
         let validated_path = ValidatedPath::new(path).or_raise(|| ErrorKind::Template)?;
         Ok(validated_path.into())
     }
 
     /// Builds the [`upon::Value`] map exposed to the template engine.
     ///
-    /// When a [`Version`] has multiple fandoms or series entries, only one is
-    /// selected — the alphabetically-first fandom and the lowest-ID series —
-    /// so that the generated path is deterministic regardless of ordering.
-    fn parameters(version: &Version) -> upon::Value {
+    /// When a [`Version`] has multiple fandoms, authors, relationship or
+    /// character tags, or series entries, only one is selected — the
+    /// alphabetically-first fandom, author and tags, and the lowest-ID series
+    /// — so that the generated path is deterministic regardless of ordering.
+    ///
+    /// `volume` is the (number, count) of the volume being rendered, if any.
+    fn parameters(version: &Version, volume: Option<(u32, u32)>) -> upon::Value {
         // TODO rename and re-order fandoms according to preferences when `rawr-config` is complete
         let fandom = version
             .metadata
@@ -183,6 +304,16 @@ impl PathGenerator {
             // The path should always be deterministic according to the version metadata.
             .min_by(|a, b| a.name.cmp(&b.name))
             .map(|f| f.name.clone());
+        let author = version.metadata.authors.iter().min_by_key(|author| author.key()).map(|author| {
+            upon::value! {
+                username: author.username.as_str(),
+                pseudonym: author.pseudonym.as_deref(),
+                name: author.pseudonym.as_deref().unwrap_or(&author.username),
+            }
+        });
+        let first_tag = |kind: TagKind| {
+            version.metadata.tags.iter().filter(|tag| tag.kind == kind).map(|tag| tag.name.as_str()).min()
+        };
         let series = version
             .metadata
             .series
@@ -196,6 +327,8 @@ impl PathGenerator {
                     position: series.position,
                 }
             });
+        // Missing dictionaries are empty (so still falsy) for `?.` lookups.
+        let empty = || upon::Value::Map(Default::default());
         upon::value! {
             work: version.metadata.work_id.to_string(),
             title: &version.metadata.title,
@@ -205,9 +338,85 @@ impl PathGenerator {
                 written: version.metadata.chapters.written,
                 total: version.metadata.chapters.total,
             },
+            complete: version.metadata.is_complete(),
             fandom: fandom.unwrap_or_default(),
-            series: series,
+            author: author.unwrap_or_else(empty),
+            author_count: version.metadata.authors.len(),
+            relationship: first_tag(TagKind::Relationship),
+            character: first_tag(TagKind::Character),
+            series: series.unwrap_or_else(empty),
             hash: format!("{:08x}", version.crc32),
+            volume: volume.map(|(number, count)| upon::value! { number: number, count: count }).unwrap_or_else(empty),
+        }
+    }
+}
+
+/// Naming rules that generated paths must follow, beyond being safe; chosen
+/// per target with [`Context::with_path_compat`](crate::Context::with_path_compat).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathCompat {
+    /// Paths are used as generated (the default).
+    #[default]
+    Posix,
+    /// Paths are made acceptable to Windows and SMB shares: characters they
+    /// forbid (`<>:"\|?*` and control characters) are replaced with `_`,
+    /// trailing dots and spaces are removed, reserved device names (`CON`,
+    /// `NUL`, `COM1`, ...) get a `_` appended, and names longer than 255 bytes
+    /// are shortened.
+    Windows,
+}
+impl PathCompat {
+    /// Longest name (in bytes) of a single path component. Windows counts
+    /// UTF-16 units rather than bytes, which never number more.
+    const MAX_COMPONENT_LEN: usize = 255;
+
+    /// Applies the naming rules to each component of `path`, leaving room for
+    /// `reserved` bytes (of extensions) at the end of its file name.
+    fn apply(&self, path: PathBuf, reserved: usize) -> Result<PathBuf> {
+        if *self == Self::Posix {
+            return Ok(path);
+        }
+        let path = path.to_string_lossy();
+        let components: Vec<&str> = path.split('/').collect();
+        let last = components.len() - 1;
+        let path = components
+            .iter()
+            .enumerate()
+            .map(|(i, component)| {
+                let max_len = match i == last {
+                    true => Self::MAX_COMPONENT_LEN.saturating_sub(reserved),
+                    false => Self::MAX_COMPONENT_LEN,
+                };
+                Self::windows_component(component, max_len)
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        let validated_path = ValidatedPath::new(path).or_raise(|| ErrorKind::Template)?;
+        Ok(validated_path.into())
+    }
+
+    fn windows_component(component: &str, max_len: usize) -> String {
+        const RESERVED: &[&str] = &["CON", "PRN", "AUX", "NUL"];
+        let mut name: String = component
+            .chars()
+            .map(|c| match c {
+                '<' | '>' | ':' | '"' | '\\' | '|' | '?' | '*' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .collect();
+        let stem = name.split('.').next().unwrap_or_default().to_ascii_uppercase();
+        let numbered = |prefix: &str| {
+            stem.strip_prefix(prefix).is_some_and(|n| n.len() == 1 && n.as_bytes()[0].is_ascii_digit() && n != "0")
+        };
+        if RESERVED.contains(&stem.as_str()) || numbered("COM") || numbered("LPT") {
+            name.insert(stem.len(), '_');
+        }
+        name.truncate(name.floor_char_boundary(max_len.max(1)));
+        let name = name.trim_end_matches(['.', ' ']);
+        match name.is_empty() {
+            true => "_".to_string(),
+            false => name.to_string(),
         }
     }
 }
@@ -218,12 +427,18 @@ mod addons {
     use std::fmt::Write;
     use upon::{Engine, Value, fmt as upon_fmt};
 
+    /// Marks a path segment to be dropped. Null bytes can't be in a valid
+    /// path, so it can't be mistaken for anything a work could contain.
+    pub(crate) const OMIT_SEGMENT: char = '\0';
+
     /// Custom formatter that converts strings to URL-safe slugs.
     ///
     /// Strips quotation marks before slugifying to avoid awkward slug output
     /// like `"hello"` becoming `-hello-`.
     fn slug_formatter(f: &mut upon_fmt::Formatter<'_>, value: &Value) -> upon_fmt::Result {
         match value {
+            // Slugifying would strip the mark.
+            Value::String(s) if s.contains(OMIT_SEGMENT) => write!(f, "{s}")?,
             Value::String(s) => {
                 // Various quotation marks: '"''""„"`«»
                 let marks = [
@@ -246,17 +461,50 @@ mod addons {
         s[..s.floor_char_boundary(max_bytes)].to_string()
     }
 
-    /// Registers the `slug` formatter and `truncate` function on the given engine.
+    /// Whether a value would render as nothing (or only whitespace).
+    fn is_empty(value: &Value) -> bool {
+        match value {
+            Value::None => true,
+            Value::String(s) => s.trim().is_empty(),
+            Value::List(list) => list.is_empty(),
+            Value::Map(map) => map.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// The value, or `fallback` if it's missing or empty.
+    fn default(value: &Value, fallback: Value) -> Value {
+        match is_empty(value) {
+            true => fallback,
+            false => value.clone(),
+        }
+    }
+
+    /// The value, or a mark dropping its path segment if it's missing or
+    /// empty.
+    fn omit_empty(value: &Value) -> Value {
+        match is_empty(value) {
+            true => Value::String(OMIT_SEGMENT.to_string()),
+            false => value.clone(),
+        }
+    }
+
+    /// Registers the `slug` formatter and the `truncate`, `default` and
+    /// `omit_empty` functions on the given engine.
     pub(crate) fn configure(engine: &mut Engine<'_>) {
         engine.add_formatter("slug", slug_formatter);
         engine.add_function("truncate", truncate_to_char_boundary);
+        engine.add_function("default", default);
+        engine.add_function("omit_empty", omit_empty);
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rawr_extract::models::{Chapters, Fandom, Language, Metadata, Rating, Version};
+    use rawr_extract::models::{
+        Author, Chapters, Encoding, Fandom, Language, Metadata, Rating, SeriesPosition, Tag, Version,
+    };
     use std::path::Path;
     use time::{Date, Month, UtcDateTime};
 
@@ -265,6 +513,8 @@ mod tests {
             hash: "abc123".to_string(),
             length: 1000,
             crc32: 3_735_928_559,
+            encoding: Encoding::Utf8,
+            detected_language: None,
             metadata: Metadata {
                 work_id,
                 title: title.to_string(),
@@ -279,6 +529,8 @@ mod tests {
                 words: 25000,
                 published: Date::from_calendar_date(2024, Month::January, 1).unwrap(),
                 last_modified: Date::from_calendar_date(2024, Month::June, 15).unwrap(),
+                provenance: Default::default(),
+                work_skin: None,
                 series: vec![],
             },
             extracted_at: UtcDateTime::now(),
@@ -360,6 +612,170 @@ mod tests {
         assert_eq!(path, Path::new("a-very-lon"));
     }
 
+    #[test]
+    fn test_complete_variable() {
+        let template = "{% if complete %}complete{% else %}wip{% endif %}/{{ work }}";
+        let mut version = make_test_version(123, "Title", "Fandom");
+
+        let generator: PathGenerator = template.parse().unwrap();
+        assert_eq!(generator.generate(&version).unwrap(), Path::new("wip/123"));
+        version.metadata.chapters = Chapters { written: 10, total: Some(10) };
+        assert_eq!(generator.generate(&version).unwrap(), Path::new("complete/123"));
+    }
+
+    #[test]
+    fn test_author_folders() {
+        let template =
+            "{% if author %}{{ author.name|slug }}{% else %}anonymous{% endif %}/{{ author_count }}-{{ work }}";
+        let mut version = make_test_version(123, "Title", "Fandom");
+
+        let generator: PathGenerator = template.parse().unwrap();
+        assert_eq!(generator.generate(&version).unwrap(), Path::new("anonymous/0-123"));
+        version.metadata.authors = vec![
+            Author::new("zed", None::<&str>),
+            Author::new("amy", Some("Amelia Pond")),
+        ];
+        assert_eq!(generator.generate(&version).unwrap(), Path::new("amelia-pond/2-123"));
+    }
+
+    #[test]
+    fn test_relationship_and_character_folders() {
+        let template =
+            "{% if relationship %}{{ relationship|slug }}{% else %}gen{% endif %}/{{ character|slug }}/{{ work }}";
+        let mut version = make_test_version(123, "Title", "Fandom");
+
+        let generator: PathGenerator = template.parse().unwrap();
+        assert_eq!(generator.generate(&version).unwrap(), Path::new("gen/123"));
+        let tag = |name: &str, kind: TagKind| Tag { name: name.to_string(), kind };
+        version.metadata.tags = vec![
+            tag("Sirius Black/Remus Lupin", TagKind::Relationship),
+            tag("Harry Potter", TagKind::Character),
+            tag("Draco Malfoy/Harry Potter", TagKind::Relationship),
+            tag("Draco Malfoy", TagKind::Character),
+            tag("Angst", TagKind::Freeform),
+        ];
+        assert_eq!(generator.generate(&version).unwrap(), Path::new("draco-malfoy-harry-potter/draco-malfoy/123"));
+    }
+
+    #[test]
+    fn test_optional_fields() {
+        let template = "{{ rating|default: \"unrated\" }}/{{ default(series?.name, \"standalone\")|slug }}/\
+                        part-{{ series?.position|omit_empty }}/{{ work }}";
+        let mut version = make_test_version(123, "Title", "Fandom");
+        version.metadata.rating = None;
+
+        let generator: PathGenerator = template.parse().unwrap();
+        assert_eq!(generator.generate(&version).unwrap(), Path::new("unrated/standalone/123"));
+        version.metadata.rating = Some(Rating::Mature);
+        version.metadata.series = vec![SeriesPosition {
+            id: 7,
+            name: "The Saga".to_string(),
+            position: 2,
+        }];
+        assert_eq!(generator.generate(&version).unwrap(), Path::new("M/the-saga/part-2/123"));
+
+        // Slugs keep the mark, whitespace counts as empty, and a path of nothing is still invalid.
+        let generator: PathGenerator = "{{ relationship|omit_empty|slug }}/{{ work }}".parse().unwrap();
+        assert_eq!(generator.generate(&version).unwrap(), Path::new("123"));
+        let generator: PathGenerator = "{{ \" \"|omit_empty }}".parse().unwrap();
+        assert!(generator.generate(&version).is_err());
+    }
+
+    #[test]
+    fn test_windows_compat() {
+        let generator: PathGenerator = "{{ fandom }}/{{ title }}".parse().unwrap();
+        let generate = |title: &str, fandom: &str| {
+            let version = make_test_version(1, title, fandom);
+            generator.generate_with_compat(&version, "html", Compression::Gzip, PathCompat::Windows).unwrap()
+        };
+        assert_eq!(generate("Who? What: Why*", "A | B"), Path::new("A _ B/Who_ What_ Why_.html.gz"));
+        assert_eq!(generate("Ellipsis...", "Fandom. "), Path::new("Fandom/Ellipsis.html.gz"));
+        assert_eq!(generate("con", "Nul.x"), Path::new("Nul_.x/con_.html.gz"));
+        assert_eq!(generate("COM1", "COM0"), Path::new("COM0/COM1_.html.gz"));
+        assert_eq!(generate("...", "Fandom"), Path::new("Fandom/_.html.gz"));
+
+        let long = generate(&"é".repeat(200), &"x".repeat(300));
+        let components: Vec<_> = long.iter().map(|c| c.to_str().unwrap()).collect();
+        assert_eq!(255, components[0].len());
+        assert!(components[1].len() <= 255);
+        assert!(components[1].ends_with("é.html.gz"));
+
+        // Other targets are left alone.
+        let version = make_test_version(1, "Who? What: Why*", "Fandom");
+        assert_eq!(
+            generator.generate_with_ext(&version, "html", None).unwrap(),
+            Path::new("Fandom/Who? What: Why*.html")
+        );
+    }
+
+    #[test]
+    fn test_partials() {
+        let partials = [
+            ("fandom", "{{ fandom|truncate: 255|slug }}"),
+            ("stem", "{{ work }}-{% include \"title\" %}"),
+            ("title", "{{ title|truncate: 220|slug }}"),
+        ];
+        let version = make_test_version(123, "My Story", "Harry Potter");
+
+        let import =
+            PathGenerator::with_partials("{% include \"fandom\" %}/{% include \"stem\" %}-{{ hash }}", partials);
+        let export = PathGenerator::with_partials("{% include \"fandom\" %}/{% include \"stem\" %}", partials);
+        assert_eq!(import.unwrap().generate(&version).unwrap(), Path::new("harry-potter/123-my-story-deadbeef"));
+        assert_eq!(export.unwrap().generate(&version).unwrap(), Path::new("harry-potter/123-my-story"));
+
+        // Redefining a sub-template changes every template including it.
+        let generator = PathGenerator::with_partials("{% include \"stem\" %}", partials).unwrap();
+        let generator = generator.with_partial("title", "{{ title }}").unwrap();
+        assert_eq!(generator.generate(&version).unwrap(), Path::new("123-My Story"));
+
+        let generator: PathGenerator = "{{ work }}/{% include \"missing\" %}".parse().unwrap();
+        assert!(matches!(*generator.generate(&version).unwrap_err(), ErrorKind::Template));
+        assert!(generator.with_partial("broken", "{{ work").is_err());
+    }
+
+    #[test]
+    fn test_default_templates() {
+        use rawr_config::models::{DEFAULT_TEMPLATE_EXPORT, DEFAULT_TEMPLATE_IMPORT, DEFAULT_TEMPLATE_PARTIALS};
+
+        let version = make_test_version(123, "My Story", "Harry Potter");
+        let import: PathGenerator = DEFAULT_TEMPLATE_IMPORT.parse().unwrap();
+        let export: PathGenerator = DEFAULT_TEMPLATE_EXPORT.parse().unwrap();
+        assert_eq!(import.generate(&version).unwrap(), Path::new("harry-potter/123-deadbeef-my-story"));
+        assert_eq!(export.generate(&version).unwrap(), Path::new("harry-potter/123-my-story"));
+
+        // The default sub-templates lay works out the same way.
+        let partials = DEFAULT_TEMPLATE_PARTIALS.iter().copied();
+        let template = "{% include \"fandom\" %}/{% include \"series\" %}{{ work }}-{{ hash }}-{% include \"title\" %}";
+        let generator = PathGenerator::with_partials(template, partials.clone()).unwrap();
+        assert_eq!(import.generate(&version).unwrap(), generator.generate(&version).unwrap());
+        let mut in_series = version.clone();
+        in_series.metadata.series = vec![SeriesPosition {
+            id: 456,
+            name: "My Series".to_string(),
+            position: 1,
+        }];
+        let path = Path::new("harry-potter/456-my-series/123-deadbeef-my-story");
+        assert_eq!(path, import.generate(&in_series).unwrap());
+        assert_eq!(path, generator.generate(&in_series).unwrap());
+        let template = "{% include \"fandom\" %}/{% include \"stem\" %}";
+        let generator = PathGenerator::with_partials(template, partials).unwrap();
+        assert_eq!(export.generate(&version).unwrap(), generator.generate(&version).unwrap());
+    }
+
+    #[test]
+    fn test_generate_volume() {
+        let version = make_test_version(123, "My Story", "Harry Potter");
+
+        let generator: PathGenerator = "{{ fandom|slug }}/{{ work }}".parse().unwrap();
+        assert_eq!(generator.generate_volume(&version, 2, 3).unwrap(), Path::new("harry-potter/123-vol2"));
+        assert_eq!(generator.generate_volume(&version, 1, 1).unwrap(), Path::new("harry-potter/123"));
+
+        let template = "{{ work }}{% if volume %} ({{ volume.number }} of {{ volume.count }}){% endif %}";
+        let generator: PathGenerator = template.parse().unwrap();
+        assert_eq!(generator.generate_volume(&version, 2, 3).unwrap(), Path::new("123 (2 of 3)"));
+        assert_eq!(generator.generate(&version).unwrap(), Path::new("123"));
+    }
+
     #[test]
     fn test_generates_compressed_extension() {
         let template = "{{ work }}";
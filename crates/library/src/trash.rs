@@ -0,0 +1,71 @@
+//! Purging the trash of files kept long enough.
+//!
+//! Irreconcilable duplicates are written to the trash under names recording
+//! when they were trashed (`{hash}-{unix timestamp}.html{ext}`), so how long
+//! a file has been kept doesn't depend on the trash backend preserving
+//! modification times.
+
+use crate::error::{ErrorKind, Result};
+use exn::ResultExt;
+use rawr_storage::{BackendHandle, Mode};
+use std::path::Path;
+use std::time::Duration;
+use time::UtcDateTime;
+
+/// Deletes the files in `trash` that were trashed more than `retention` ago,
+/// returning how many were deleted (or in a [dry run](Mode::DryRun), would
+/// have been).
+///
+/// Files not named as trashed files are left alone.
+pub async fn purge_trash(trash: &BackendHandle, retention: Duration, mode: Mode) -> Result<u64> {
+    let trash = &mode.backend(trash.clone());
+    let cutoff = UtcDateTime::now().unix_timestamp().saturating_sub_unsigned(retention.as_secs());
+    let files = trash.list(None).await.or_raise(|| ErrorKind::Trash)?;
+    let mut purged = 0;
+    for file in files {
+        if trashed_at(&file.path).is_none_or(|trashed_at| trashed_at > cutoff) {
+            continue;
+        }
+        trash.delete(&file.path).await.or_raise(|| ErrorKind::Trash)?;
+        tracing::debug!(path = %file.path.display(), "Purged file from trash");
+        purged += 1;
+    }
+    tracing::info!(trash = trash.name(), purged, "Purged trash");
+    Ok(purged)
+}
+
+/// When the file at `path` was trashed (as a Unix timestamp), if it's named
+/// as a trashed file.
+fn trashed_at(path: &Path) -> Option<i64> {
+    let name = path.file_name()?.to_str()?;
+    let (stem, _) = name.split_once(".html")?;
+    let (hash, timestamp) = stem.rsplit_once('-')?;
+    (!hash.is_empty() && hash.bytes().all(|b| b.is_ascii_hexdigit())).then(|| timestamp.parse().ok())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_storage::backend::MockBackend;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_purge_trash() {
+        let now = UtcDateTime::now().unix_timestamp();
+        let hash = blake3::hash(b"trashed").to_hex();
+        let old = format!("{hash}-{}.html.bz2", now - 3 * 86_400);
+        let recent = format!("{hash}-{}.html", now - 3_600);
+        let trash: BackendHandle = Arc::new(MockBackend::with_data([
+            (old.as_str(), "old"),
+            (recent.as_str(), "recent"),
+            ("notes.txt", "mine"),
+        ]));
+
+        assert_eq!(1, purge_trash(&trash, Duration::from_secs(86_400), Mode::DryRun).await.unwrap());
+        assert!(trash.exists(Path::new(&old)).await.unwrap());
+        assert_eq!(1, purge_trash(&trash, Duration::from_secs(86_400), Mode::Real).await.unwrap());
+        assert!(!trash.exists(Path::new(&old)).await.unwrap());
+        assert!(trash.exists(Path::new(&recent)).await.unwrap());
+        assert!(trash.exists(Path::new("notes.txt")).await.unwrap());
+    }
+}
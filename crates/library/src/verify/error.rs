@@ -0,0 +1,44 @@
+//! Verify Error Types
+//!
+//! This module provides structured errors using `exn` for automatic location
+//! tracking and error tree construction. See `ERRORS.md` for design rationale.
+
+use derive_more::{Display, Error};
+pub use rawr_error::{Code, Domain, ErrorCode};
+
+/// A verify error with automatic location tracking.
+pub type Error = exn::Exn<ErrorKind>;
+/// Result type alias for verify operations.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Classifies the subsystem where a verify operation failed.
+///
+/// A file that is missing or whose hash doesn't match is *not* an error; see
+/// [`Outcome`](crate::verify::Outcome).
+#[derive(Debug, Display, Error)]
+pub enum ErrorKind {
+    /// A [`Repository`](rawr_cache::Repository) query failed.
+    Cache,
+    /// Reading from the [`BackendHandle`](rawr_storage::BackendHandle) failed.
+    Storage,
+    /// The background task hashing the file's contents panicked.
+    Hashing,
+}
+
+impl ErrorKind {
+    /// Returns `true` if retrying might succeed.
+    pub fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::Hashing => 500,
+            Self::Cache => 520,
+            Self::Storage => 521,
+        };
+        Code::new(Domain::Verify, number)
+    }
+}
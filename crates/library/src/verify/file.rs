@@ -0,0 +1,184 @@
+use crate::Bytes;
+use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
+use crate::verify::error::{ErrorKind, Result as VerifyResult};
+use exn::ResultExt;
+use rawr_extract::{Validation, validate_deep};
+use rawr_storage::BackendHandle;
+use rawr_storage::error::ErrorKind as StorageErrorKind;
+use rawr_storage::file::{FileInfo, Processed};
+use std::ops::Deref;
+
+/// Files at least this large are hashed across multiple threads. Below this,
+/// the overhead of splitting the work outweighs the gains.
+const MULTITHREAD_THRESHOLD: usize = 128 * 1024;
+
+/// How thoroughly to [verify](crate::verify::verify) files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VerifyMode {
+    /// Hash every file as stored with BLAKE3, comparing it to the file hash
+    /// on record.
+    #[default]
+    Full,
+    /// Verify every file in [full](Self::Full), then also [check intact
+    /// files in depth](validate_deep), to grade their health (which means
+    /// parsing every one of them, too).
+    Deep,
+    /// Decompress every file and compare the CRC32 of its contents to the
+    /// version on record, as a cheap screening pass: only files that don't
+    /// match (or don't decompress) are verified in full. Intact files aren't
+    /// checked in depth.
+    Quick,
+}
+
+/// What verifying a file found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The file's hash matches the file hash on record.
+    Intact,
+    /// The file's hash doesn't match the file hash on record; holds the
+    /// actual hash. The file has been damaged, or replaced since it was
+    /// last scanned.
+    Corrupt(String),
+    /// The file no longer exists in the storage backend.
+    Missing,
+}
+
+/// The result of verifying a single file.
+#[derive(Debug)]
+pub struct Verification {
+    pub file: FileInfo<Processed>,
+    pub outcome: Outcome,
+    /// What a [deep check](validate_deep) of an intact file found wrong with
+    /// it (`None` unless [asked for](VerifyMode::Deep), or if the file wasn't
+    /// intact or couldn't be decompressed).
+    /// A file can be exactly what was scanned, and still have been a damaged
+    /// download to begin with.
+    pub validation: Option<Validation>,
+    /// Whether a [quick](VerifyMode::Quick) check of the file failed, so
+    /// that it was verified in full.
+    pub escalated: bool,
+    pub bytes: Bytes,
+}
+
+/// Verifies a single cached file, by re-reading it from `backend` and
+/// comparing its BLAKE3 hash to the file hash on record.
+///
+/// Hashing runs on a blocking thread (using several threads for large
+/// files), so verifying many files concurrently isn't limited by the async
+/// runtime's worker threads.
+pub async fn verify_file(backend: &BackendHandle, file: FileInfo<Processed>) -> LibraryResult<Verification> {
+    verify_file_inner(backend, file, None, false).await.or_raise(|| LibraryErrorKind::Verify)
+}
+
+/// Verifies a single cached file like [`verify_file`], then [checks it in
+/// depth](validate_deep) if it's intact, to grade its health.
+pub async fn verify_file_deep(backend: &BackendHandle, file: FileInfo<Processed>) -> LibraryResult<Verification> {
+    verify_file_inner(backend, file, None, true).await.or_raise(|| LibraryErrorKind::Verify)
+}
+
+/// Verifies a single cached file [quickly](VerifyMode::Quick), by
+/// comparing the CRC32 of its decompressed contents to `crc32` (that of the
+/// version on record), falling back to [`verify_file`] when they don't match.
+pub async fn verify_file_quick(
+    backend: &BackendHandle,
+    file: FileInfo<Processed>,
+    crc32: u32,
+) -> LibraryResult<Verification> {
+    verify_file_inner(backend, file, Some(crc32), false).await.or_raise(|| LibraryErrorKind::Verify)
+}
+
+/// Verifies a file in full or, given the CRC32 of its version, quickly; and
+/// checks it in depth too if `deep`.
+pub(crate) async fn verify_file_inner(
+    backend: &BackendHandle,
+    file: FileInfo<Processed>,
+    crc32: Option<u32>,
+    deep: bool,
+) -> VerifyResult<Verification> {
+    let contents = match backend.read_contents(&file.path).await {
+        Ok(contents) => contents,
+        Err(e) if matches!(e.deref(), StorageErrorKind::NotFound(_)) => {
+            tracing::warn!(target = backend.name(), path = %file.path.display(), "File on record is missing from storage");
+            let bytes = Bytes::default();
+            return Ok(Verification {
+                file,
+                outcome: Outcome::Missing,
+                validation: None,
+                escalated: false,
+                bytes,
+            });
+        },
+        Err(e) => return Err(e).or_raise(|| ErrorKind::Storage),
+    };
+    let bytes = Bytes {
+        read: Bytes::len(&contents),
+        ..Bytes::default()
+    };
+    let compression = file.compression;
+    let expected = file.file_hash.clone();
+    let checked = tokio::task::spawn_blocking(move || {
+        if let Some(crc32) = crc32
+            && compression.decompress(&contents).is_ok_and(|html| crc32fast::hash(&html) == crc32)
+        {
+            return None;
+        }
+        let mut hasher = blake3::Hasher::new();
+        if contents.len() >= MULTITHREAD_THRESHOLD {
+            hasher.update_rayon(&contents);
+        } else {
+            hasher.update(&contents);
+        }
+        let hash = hasher.finalize().to_string();
+        let validation = match deep && hash == expected {
+            true => compression.decompress(&contents).ok().map(validate_deep),
+            false => None,
+        };
+        Some((hash, validation))
+    })
+    .await
+    .or_raise(|| ErrorKind::Hashing)?;
+    let Some((hash, validation)) = checked else {
+        return Ok(Verification {
+            file,
+            outcome: Outcome::Intact,
+            validation: None,
+            escalated: false,
+            bytes,
+        });
+    };
+    let escalated = crc32.is_some();
+    if escalated {
+        tracing::info!(
+            target = backend.name(),
+            path = %file.path.display(),
+            "Contents do not match the CRC32 on record; verifying in full",
+        );
+    }
+    let outcome = if hash == file.file_hash {
+        if let Some(validation) = validation.as_ref().filter(|v| !v.is_healthy()) {
+            tracing::warn!(
+                target = backend.name(),
+                path = %file.path.display(),
+                %validation,
+                "File is intact, but not a healthy download",
+            );
+        }
+        Outcome::Intact
+    } else {
+        tracing::warn!(
+            target = backend.name(),
+            path = %file.path.display(),
+            expected = file.file_hash,
+            actual = hash,
+            "File hash does not match the hash on record",
+        );
+        Outcome::Corrupt(hash)
+    };
+    Ok(Verification {
+        file,
+        outcome,
+        validation,
+        escalated,
+        bytes,
+    })
+}
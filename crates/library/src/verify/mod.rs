@@ -0,0 +1,32 @@
+//! Integrity verification of files in storage backends.
+//!
+//! Re-reads files and compares their BLAKE3 hash against the file hash on
+//! record in the [cache](rawr_cache::Repository), detecting bit rot, partial
+//! writes and files that have gone missing. It operates at two levels:
+//!
+//! - **Single-file**: [`verify_file`] checks one cached file.
+//! - **Streaming**: [`verify`] checks every cached file of a backend, or a
+//!   [`Sampling`] of them, emitting [`VerifyEvent`]s and finishing with a
+//!   [`VerifyReport`].
+//!
+//! Reading every file of a large library from a remote target (such as S3)
+//! is slow and costly, so a sample can be verified instead. The report then
+//! estimates (with 95% confidence) how many files across the whole target
+//! could be damaged. Alternatively, periodic checks can verify only the files
+//! that haven't been verified recently.
+//!
+//! Hashing whole files with BLAKE3 is thorough, but a [quick](VerifyMode::Quick)
+//! pass can screen them first: the CRC32 of each file's decompressed contents
+//! is compared to the version on record, and only files that don't match are
+//! hashed in full.
+
+pub(crate) mod error;
+pub(crate) mod file;
+mod report;
+mod sample;
+mod stream;
+
+pub use self::file::{Outcome, Verification, VerifyMode, verify_file, verify_file_deep, verify_file_quick};
+pub use self::report::VerifyReport;
+pub use self::sample::Sampling;
+pub use self::stream::{VerifyEvent, verify};
@@ -0,0 +1,146 @@
+use crate::verify::{Outcome, Verification};
+
+/// Z-score for a two-sided 95% confidence interval.
+const Z_95: f64 = 1.959_964;
+
+/// Summary of a [verification](crate::verify::verify), with an estimate of
+/// how many files across the whole target are damaged when only a sample
+/// was verified.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Number of files on record for the target.
+    pub population: u64,
+    /// Number of files selected for verification.
+    pub sampled: u64,
+    /// Files whose hash matched the hash on record.
+    pub intact: u64,
+    /// Intact files that a [deep check](rawr_extract::validate_deep) found
+    /// something wrong with (counted in `intact` too).
+    pub unhealthy: u64,
+    /// Files that failed a [quick](crate::verify::VerifyMode::Quick) check, and
+    /// were verified in full (counted by their outcome too).
+    pub escalated: u64,
+    /// Files whose hash didn't match the hash on record.
+    pub corrupt: u64,
+    /// Files that no longer exist in the storage backend.
+    pub missing: u64,
+    /// Files that couldn't be verified (storage errors); these are excluded
+    /// from the estimates.
+    pub errored: u64,
+}
+impl VerifyReport {
+    pub(crate) fn new(population: u64, sampled: u64) -> Self {
+        Self { population, sampled, ..Self::default() }
+    }
+
+    pub(crate) fn record(&mut self, verification: Option<&Verification>) {
+        if verification.and_then(|v| v.validation.as_ref()).is_some_and(|v| !v.is_healthy()) {
+            self.unhealthy += 1;
+        }
+        if verification.is_some_and(|v| v.escalated) {
+            self.escalated += 1;
+        }
+        match verification.map(|v| &v.outcome) {
+            Some(Outcome::Intact) => self.intact += 1,
+            Some(Outcome::Corrupt(_)) => self.corrupt += 1,
+            Some(Outcome::Missing) => self.missing += 1,
+            None => self.errored += 1,
+        }
+    }
+
+    /// Files that were successfully verified (intact or not).
+    pub fn verified(&self) -> u64 {
+        self.intact + self.corrupt + self.missing
+    }
+
+    /// Files found to be corrupt or missing.
+    pub fn failures(&self) -> u64 {
+        self.corrupt + self.missing
+    }
+
+    /// Whether every file on record was verified, in which case the failure
+    /// rate is exact rather than an estimate.
+    pub fn is_exhaustive(&self) -> bool {
+        self.verified() >= self.population
+    }
+
+    /// Proportion of verified files that are corrupt or missing.
+    pub fn failure_rate(&self) -> f64 {
+        match self.verified() {
+            0 => 0.0,
+            verified => self.failures() as f64 / verified as f64,
+        }
+    }
+
+    /// Upper bound of the proportion of *all* files on record that are
+    /// corrupt or missing, with 95% confidence.
+    ///
+    /// Uses the Wilson score interval, corrected for sampling without
+    /// replacement from a finite population: the larger the share of the
+    /// target that was verified, the tighter the bound. Verifying 300
+    /// files without finding any failures bounds the rate at ~1.3%,
+    /// however large the target.
+    pub fn failure_rate_upper_bound(&self) -> f64 {
+        let verified = self.verified();
+        if verified == 0 {
+            return 1.0;
+        }
+        let rate = self.failure_rate();
+        if self.is_exhaustive() {
+            return rate;
+        }
+        // Finite population correction, applied as an effective sample size.
+        let n = verified as f64 * (self.population as f64 - 1.0) / (self.population - verified) as f64;
+        let z2 = Z_95 * Z_95;
+        let centre = rate + z2 / (2.0 * n);
+        let margin = Z_95 * (rate * (1.0 - rate) / n + z2 / (4.0 * n * n)).sqrt();
+        ((centre + margin) / (1.0 + z2 / n)).min(1.0)
+    }
+
+    /// Upper bound of the number of files on record that are corrupt or
+    /// missing, with 95% confidence. Never less than the failures actually
+    /// found.
+    pub fn max_failures(&self) -> u64 {
+        let estimate = (self.failure_rate_upper_bound() * self.population as f64).ceil() as u64;
+        estimate.clamp(self.failures(), self.population.saturating_sub(self.intact).max(self.failures()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(population: u64, intact: u64, corrupt: u64) -> VerifyReport {
+        VerifyReport {
+            population,
+            sampled: intact + corrupt,
+            intact,
+            corrupt,
+            ..VerifyReport::default()
+        }
+    }
+
+    #[test]
+    fn test_confidence() {
+        // "Rule of three": no failures in n files bounds the rate at ~3/n.
+        let clean = report(1_000_000, 300, 0);
+        assert!((clean.failure_rate_upper_bound() - 0.0126).abs() < 0.001);
+        assert_eq!(0.0, clean.failure_rate());
+
+        // Verifying more of a population tightens the bound.
+        assert!(report(400, 300, 0).failure_rate_upper_bound() < clean.failure_rate_upper_bound());
+
+        let damaged = report(10_000, 990, 10);
+        assert_eq!(0.01, damaged.failure_rate());
+        assert!(damaged.failure_rate_upper_bound() > 0.01);
+        assert!(damaged.failure_rate_upper_bound() < 0.02);
+        assert!((100..200).contains(&damaged.max_failures()));
+
+        // Exhaustive verification is exact.
+        let all = report(100, 97, 3);
+        assert!(all.is_exhaustive());
+        assert_eq!(3, all.max_failures());
+        assert_eq!(1.0, VerifyReport::new(10, 0).failure_rate_upper_bound());
+        assert_eq!(10, VerifyReport::new(10, 0).max_failures());
+    }
+}
@@ -0,0 +1,153 @@
+use rawr_storage::file::{FileInfo, Processed};
+use std::collections::BTreeMap;
+use time::UtcDateTime;
+
+type File = FileInfo<Processed>;
+
+/// Which of a target's files to [verify](crate::verify::verify).
+///
+/// Sampling is deterministic: the same files (and the same cache records)
+/// always produce the same sample, so a verification can be repeated to
+/// confirm its results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    /// Verify every file.
+    All,
+    /// Verify `percent`% of files, chosen pseudo-randomly from `seed`. Use a
+    /// different seed each run to eventually cover the whole library.
+    Random { percent: f64, seed: u64 },
+    /// Verify `percent`% of the files from each calendar month in which files
+    /// were discovered, evenly spread (by path) within each month.
+    ///
+    /// Files written to storage at the same time tend to share their fate
+    /// (the same disk, the same faulty sync), so every period of the
+    /// library's history is represented in the sample.
+    DateBuckets { percent: f64 },
+    /// Verify the files that haven't been verified intact since `since`
+    /// (including files never verified at all), for periodic checks that
+    /// only touch files not checked recently.
+    ///
+    /// This isn't a random sample, so the estimates of the report are only
+    /// a rough guide: they assume the files verified recently are no more
+    /// likely to be intact than the others.
+    NotVerifiedSince { since: UtcDateTime },
+}
+impl Sampling {
+    pub fn random(percent: f64, seed: u64) -> Self {
+        Self::Random { percent, seed }
+    }
+
+    pub fn by_date(percent: f64) -> Self {
+        Self::DateBuckets { percent }
+    }
+
+    pub fn not_verified_since(since: UtcDateTime) -> Self {
+        Self::NotVerifiedSince { since }
+    }
+
+    /// Selects the files to verify, sorted by path.
+    ///
+    /// Any non-zero percentage selects at least one file (per date bucket);
+    /// percentages are clamped to 0–100. Files not verified recently are
+    /// only known to the cache, so [`NotVerifiedSince`](Self::NotVerifiedSince)
+    /// selects every file given (which the caller has already narrowed down).
+    pub(crate) fn select(&self, mut files: Vec<File>) -> Vec<File> {
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut selected = match *self {
+            Self::All | Self::NotVerifiedSince { .. } => return files,
+            Self::Random { percent, seed } => {
+                let count = sample_size(files.len(), percent);
+                let mut keyed: Vec<_> = files.into_iter().map(|file| (sort_key(seed, &file), file)).collect();
+                keyed.sort_unstable_by_key(|(key, _)| *key);
+                keyed.into_iter().take(count).map(|(_, file)| file).collect::<Vec<_>>()
+            },
+            Self::DateBuckets { percent } => {
+                let mut buckets: BTreeMap<_, Vec<File>> = BTreeMap::new();
+                for file in files {
+                    let date = file.discovered_at.date();
+                    buckets.entry((date.year(), date.month() as u8)).or_default().push(file);
+                }
+                buckets.into_values().flat_map(|bucket| spread(bucket, percent)).collect()
+            },
+        };
+        selected.sort_by(|a, b| a.path.cmp(&b.path));
+        selected
+    }
+}
+
+/// Number of files to sample from a population of `len`.
+fn sample_size(len: usize, percent: f64) -> usize {
+    let fraction = percent.clamp(0.0, 100.0) / 100.0;
+    if len == 0 || fraction == 0.0 {
+        return 0;
+    }
+    ((len as f64 * fraction).ceil() as usize).clamp(1, len)
+}
+
+/// A seeded, stable pseudo-random ordering of files.
+fn sort_key(seed: u64, file: &File) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&seed.to_le_bytes());
+    hasher.update(file.path.as_os_str().as_encoded_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+/// Picks files at evenly spaced positions through a (sorted) bucket.
+fn spread(bucket: Vec<File>, percent: f64) -> impl Iterator<Item = File> {
+    let len = bucket.len();
+    let count = sample_size(len, percent);
+    bucket.into_iter().enumerate().filter_map(move |(i, file)| {
+        // Position i is picked when it's the first to reach the next multiple
+        // of len/count.
+        (count > 0 && (i * count) / len != ((i + 1) * count) / len).then_some(file)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_compress::Compression;
+    use time::{Date, Month, Time};
+
+    fn files(month: Month, count: usize) -> Vec<File> {
+        let date = Date::from_calendar_date(2024, month, 1).unwrap();
+        (0..count)
+            .map(|i| {
+                FileInfo::new(
+                    "library",
+                    format!("{month}/{i:03}.html"),
+                    100,
+                    UtcDateTime::new(date, Time::MIDNIGHT),
+                    Compression::None,
+                )
+                .with_file_hash("hash")
+                .with_content_hash("hash")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_random_sampling() {
+        let all = files(Month::January, 200);
+        let sample = Sampling::random(10.0, 1).select(all.clone());
+        assert_eq!(20, sample.len());
+        let paths = |files: Vec<File>| files.iter().map(|f| f.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(sample.clone()), paths(Sampling::random(10.0, 1).select(all.clone())));
+        assert_ne!(paths(sample), paths(Sampling::random(10.0, 2).select(all.clone())));
+        assert_eq!(1, Sampling::random(0.01, 1).select(all.clone()).len());
+        assert_eq!(0, Sampling::random(0.0, 1).select(all.clone()).len());
+        assert_eq!(200, Sampling::random(150.0, 1).select(all).len());
+    }
+
+    #[test]
+    fn test_date_bucket_sampling() {
+        let mut all = files(Month::January, 100);
+        all.extend(files(Month::June, 5));
+        let sample = Sampling::by_date(10.0).select(all);
+        // 10 from January, and 1 (rounded up) from June.
+        assert_eq!(11, sample.len());
+        assert_eq!(1, sample.iter().filter(|f| f.path.starts_with("June")).count());
+        assert!(sample.iter().any(|f| f.path.ends_with("January/009.html")));
+        assert!(sample.iter().any(|f| f.path.ends_with("January/099.html")));
+    }
+}
@@ -0,0 +1,353 @@
+use crate::error::{ErrorKind as LibraryErrorKind, Result as LibraryResult};
+use crate::progress::{Bytes, Heartbeat, Progress};
+use crate::scan::is_member;
+use crate::verify::error::{ErrorKind as VerifyErrorKind, Result as VerifyResult};
+use crate::verify::file::{Outcome, Verification, verify_file_inner};
+use crate::verify::{Sampling, VerifyMode, VerifyReport};
+use crate::{CancellationToken, MAX_PROCESS_CONCURRENCY};
+use async_stream::stream;
+use exn::ResultExt;
+use futures::stream::FuturesUnordered;
+use futures::{Stream, StreamExt};
+use rawr_cache::Repository;
+use rawr_storage::BackendHandle;
+use std::collections::{HashMap, HashSet, VecDeque};
+use time::UtcDateTime;
+
+/// Progress events emitted by [`verify`], whether verifying every file or a
+/// sample of them.
+///
+/// Events follow a strict ordering:
+/// 1. [`Started`](Self::Started) — exactly once.
+/// 2. [`DiscoveryComplete`](Self::DiscoveryComplete) — exactly once, with the
+///    number of files on record and the number selected for verification.
+/// 3. [`Verified`](Self::Verified) — zero or more times, one per file,
+///    interleaved with occasional [`Heartbeat`](Self::Heartbeat)s.
+/// 4. [`Heartbeat`](Self::Heartbeat) — exactly once, with the final totals.
+/// 5. [`Complete`](Self::Complete) — exactly once, with the final report.
+///
+/// An error may terminate the stream early, in which case [`Complete`](Self::Complete)
+/// is never emitted. If the stream is cancelled, [`Cancelled`](Self::Cancelled)
+/// is emitted instead.
+pub enum VerifyEvent {
+    /// Verification has begun; emitted exactly once before any other event.
+    Started,
+    /// All cache entries have been discovered and sampled.
+    DiscoveryComplete {
+        /// Number of files on record for the target.
+        population: u64,
+        /// Number of files that will be verified.
+        sampled: u64,
+    },
+    /// A file has been verified. Boxed to keep the enum's overall size small.
+    Verified(Box<Verification>),
+    /// Aggregate progress across all files verified so far.
+    Heartbeat(Progress),
+    /// All sampled files have been verified; the stream is finished.
+    Complete(VerifyReport),
+    /// Verification was cancelled; files already being verified were
+    /// finished, but no others were started. Holds the report of those that
+    /// were verified. The stream is finished.
+    Cancelled(VerifyReport),
+}
+
+/// Streams [`VerifyEvent`]s while verifying the cached files of `backend`
+/// selected by `sampling`, as thoroughly as `mode` says, finishing with a
+/// [`VerifyReport`].
+///
+/// Files are verified concurrently, up to `MAX_PROCESS_CONCURRENCY` (100) at
+/// a time, with hashing spread across blocking threads. Individual file
+/// failures are surfaced as `Err` items (and counted in the report) without
+/// terminating the stream — only a cache discovery failure is fatal.
+///
+/// The time each file is found intact is recorded in the cache (see
+/// [`Sampling::NotVerifiedSince`]), unless the cache is read-only.
+///
+/// Cancelling `cancel` stops verification once the files in progress have
+/// been verified.
+pub fn verify<'a>(
+    backend: &'a BackendHandle,
+    cache: &'a Repository,
+    sampling: Sampling,
+    mode: VerifyMode,
+    cancel: CancellationToken,
+) -> impl Stream<Item = LibraryResult<VerifyEvent>> + 'a {
+    stream! {
+        for await event in verify_inner(backend, cache, sampling, mode, cancel) {
+            yield event.or_raise(|| LibraryErrorKind::Verify);
+        }
+    }
+}
+
+fn verify_inner<'a>(
+    backend: &'a BackendHandle,
+    cache: &'a Repository,
+    sampling: Sampling,
+    mode: VerifyMode,
+    cancel: CancellationToken,
+) -> impl Stream<Item = VerifyResult<VerifyEvent>> + 'a {
+    stream!({
+        yield Ok(VerifyEvent::Started);
+
+        let files = match cache.list_files_for_target(backend.name()).await.or_raise(|| VerifyErrorKind::Cache) {
+            // Files inside archives can't be read on their own.
+            Ok(f) => f.into_iter().filter(|(file, _)| !is_member(&file.path)).collect::<Vec<_>>(),
+            Err(e) => {
+                yield Err(e);
+                return;
+            },
+        };
+        // Infallible: a usize (either 32- or 64-bit) will always fit in a u64.
+        let population = u64::try_from(files.len()).unwrap_or(0);
+        // The CRC32 of each file's version, for quick checks.
+        let crc32s: HashMap<_, _> = match mode {
+            VerifyMode::Full | VerifyMode::Deep => HashMap::new(),
+            VerifyMode::Quick => files.iter().map(|(file, version)| (file.path.clone(), version.crc32)).collect(),
+        };
+        let mut files: Vec<_> = files.into_iter().map(|(file, _)| file).collect();
+        if let Sampling::NotVerifiedSince { since } = sampling {
+            let unverified = match cache.list_stale(backend.name(), since).await.or_raise(|| VerifyErrorKind::Cache) {
+                Ok(f) => f.into_iter().map(|(file, _)| file.path.clone()).collect::<HashSet<_>>(),
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                },
+            };
+            files.retain(|file| unverified.contains(&file.path));
+        }
+        let mut files: VecDeque<_> = sampling.select(files).into();
+        let sampled = u64::try_from(files.len()).unwrap_or(0);
+        let mut report = VerifyReport::new(population, sampled);
+        let mut heartbeat = Heartbeat::new();
+        heartbeat.set_total(sampled);
+        yield Ok(VerifyEvent::DiscoveryComplete { population, sampled });
+
+        let mut processing = FuturesUnordered::new();
+        let mut cancelled = false;
+        loop {
+            while processing.len() < MAX_PROCESS_CONCURRENCY
+                && let Some(file) = files.pop_front()
+            {
+                let crc32 = crc32s.get(&file.path).copied();
+                processing.push(verify_file_inner(backend, file, crc32, mode == VerifyMode::Deep));
+            }
+            let result = tokio::select! {
+                biased;
+
+                _ = cancel.cancelled(), if !cancelled && !processing.is_empty() => {
+                    cancelled = true;
+                    files.clear();
+                    tracing::info!(target = backend.name(), in_flight = processing.len(), "Verification cancelled");
+                    continue;
+                },
+
+                Some(result) = processing.next(), if !processing.is_empty() => result,
+
+                else => break,
+            };
+            if let Ok(verification) = &result {
+                record_verification(cache, verification).await;
+            }
+            let bytes = result.as_ref().map_or(Bytes::default(), |v| v.bytes);
+            report.record(result.as_ref().ok());
+            yield result.map(|v| VerifyEvent::Verified(Box::new(v)));
+            if let Some(progress) = heartbeat.record(bytes) {
+                yield Ok(VerifyEvent::Heartbeat(progress));
+            }
+        }
+
+        tracing::info!(
+            target = backend.name(),
+            verified = report.verified(),
+            failures = report.failures(),
+            unhealthy = report.unhealthy,
+            escalated = report.escalated,
+            max_failures = report.max_failures(),
+            "Verification complete",
+        );
+        yield Ok(VerifyEvent::Heartbeat(heartbeat.progress()));
+        yield Ok(match cancelled {
+            true => VerifyEvent::Cancelled(report),
+            false => VerifyEvent::Complete(report),
+        });
+    })
+}
+
+/// Records when a file was verified intact in the cache (or forgets that it
+/// ever was, if it's damaged), for [`Sampling::NotVerifiedSince`]. Failing
+/// to doesn't fail the verification.
+async fn record_verification(cache: &Repository, verification: &Verification) {
+    if cache.is_read_only() {
+        return;
+    }
+    let file = &verification.file;
+    let verified_at = matches!(verification.outcome, Outcome::Intact).then(UtcDateTime::now);
+    if let Err(e) = cache.mark_verified(&file.target, &file.path, verified_at).await {
+        tracing::warn!(target = file.target, path = %file.path.display(), error = ?e, "Could not record verification");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::Database;
+    use rawr_compress::Compression;
+    use rawr_extract::models::{Encoding, Version};
+    use rawr_extract::testing::Generator;
+    use rawr_storage::backend::MockBackend;
+    use rawr_storage::file::FileInfo;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_verify() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let mut data = vec![];
+        for (i, contents) in ["intact", "corrupt", "missing"].into_iter().enumerate() {
+            let path = format!("{contents}.html");
+            let mut metadata = Generator::new(i as u64).metadata();
+            metadata.work_id = i as u64 + 1;
+            let version = Version {
+                hash: contents.to_string(),
+                length: 100,
+                crc32: 0,
+                encoding: Encoding::Utf8,
+                detected_language: None,
+                metadata,
+                extracted_at: UtcDateTime::now(),
+            };
+            let file = FileInfo::new("library", &path, 6, UtcDateTime::now(), Compression::None)
+                .with_file_hash(blake3::hash(contents.as_bytes()).to_string())
+                .with_content_hash(contents);
+            cache.upsert(&file, &version).await.unwrap();
+            match contents {
+                "intact" => data.push((path, contents.to_string())),
+                "corrupt" => data.push((path, "c0rrupt".to_string())),
+                _ => (),
+            }
+        }
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+
+        let events: Vec<_> =
+            verify(&backend, &cache, Sampling::All, VerifyMode::Full, CancellationToken::new()).collect().await;
+        let Some(Ok(VerifyEvent::Complete(report))) = events.last() else {
+            panic!("verification did not complete");
+        };
+        assert_eq!((3, 3), (report.population, report.sampled));
+        assert_eq!((1, 1, 1, 0), (report.intact, report.corrupt, report.missing, report.errored));
+        // Not checked in depth.
+        assert_eq!(0, report.unhealthy);
+        assert!(report.is_exhaustive());
+        assert_eq!(2, report.max_failures());
+
+        let events: Vec<_> =
+            verify(&backend, &cache, Sampling::All, VerifyMode::Deep, CancellationToken::new()).collect().await;
+        let Some(Ok(VerifyEvent::Complete(report))) = events.last() else {
+            panic!("verification did not complete");
+        };
+        assert_eq!((1, 1, 1), (report.intact, report.corrupt, report.missing));
+        // Intact, but not an AO3 download at all.
+        assert_eq!(1, report.unhealthy);
+
+        let events: Vec<_> =
+            verify(&backend, &cache, Sampling::random(1.0, 7), VerifyMode::Full, CancellationToken::new())
+                .collect()
+                .await;
+        let Some(Ok(VerifyEvent::Complete(report))) = events.last() else {
+            panic!("verification did not complete");
+        };
+        assert_eq!((3, 1, 1), (report.population, report.sampled, report.verified()));
+
+        // Only the damaged files are left to check, having not been found intact.
+        let since = Sampling::not_verified_since(UtcDateTime::now() - time::Duration::days(30));
+        let events: Vec<_> =
+            verify(&backend, &cache, since, VerifyMode::Full, CancellationToken::new()).collect().await;
+        let Some(Ok(VerifyEvent::Complete(report))) = events.last() else {
+            panic!("verification did not complete");
+        };
+        assert_eq!((3, 2), (report.population, report.sampled));
+        assert_eq!((0, 1, 1), (report.intact, report.corrupt, report.missing));
+    }
+
+    #[tokio::test]
+    async fn test_verify_quick() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let mut data = vec![];
+        // (contents on record, contents in storage, CRC32 on record)
+        let files = [
+            ("intact", "intact", crc32fast::hash(b"intact")),
+            ("corrupt", "c0rrupt", crc32fast::hash(b"corrupt")),
+            ("miscounted", "miscounted", 0),
+        ];
+        for (i, (contents, stored, crc32)) in files.into_iter().enumerate() {
+            let path = format!("{contents}.html");
+            let mut metadata = Generator::new(i as u64).metadata();
+            metadata.work_id = i as u64 + 1;
+            let version = Version {
+                hash: contents.to_string(),
+                length: 100,
+                crc32,
+                encoding: Encoding::Utf8,
+                detected_language: None,
+                metadata,
+                extracted_at: UtcDateTime::now(),
+            };
+            let file = FileInfo::new("library", &path, 6, UtcDateTime::now(), Compression::None)
+                .with_file_hash(blake3::hash(contents.as_bytes()).to_string())
+                .with_content_hash(contents);
+            cache.upsert(&file, &version).await.unwrap();
+            data.push((path, stored));
+        }
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+
+        let events: Vec<_> =
+            verify(&backend, &cache, Sampling::All, VerifyMode::Quick, CancellationToken::new()).collect().await;
+        let Some(Ok(VerifyEvent::Complete(report))) = events.last() else {
+            panic!("verification did not complete");
+        };
+        assert_eq!((2, 1, 0), (report.intact, report.corrupt, report.missing));
+        assert_eq!(2, report.escalated);
+        // Quick checks never check in depth.
+        assert_eq!(0, report.unhealthy);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_verify() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        let mut data = vec![];
+        // One more than can be verified at once, so at least one isn't started.
+        for i in 0..=MAX_PROCESS_CONCURRENCY as u64 {
+            let (path, contents) = (format!("{i}.html"), format!("work {i}"));
+            let mut metadata = Generator::new(i).metadata();
+            metadata.work_id = i + 1;
+            let version = Version {
+                hash: contents.clone(),
+                length: contents.len() as u64,
+                crc32: crc32fast::hash(contents.as_bytes()),
+                encoding: Encoding::Utf8,
+                detected_language: None,
+                metadata,
+                extracted_at: UtcDateTime::now(),
+            };
+            let file = FileInfo::new("library", &path, contents.len() as u64, UtcDateTime::now(), Compression::None)
+                .with_file_hash(blake3::hash(contents.as_bytes()).to_string())
+                .with_content_hash(&contents);
+            cache.upsert(&file, &version).await.unwrap();
+            data.push((path, contents));
+        }
+        let backend: BackendHandle = Arc::new(MockBackend::with_data(data).with_name("library"));
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let events: Vec<_> = verify(&backend, &cache, Sampling::All, VerifyMode::Full, cancel).collect().await;
+        let Some(Ok(VerifyEvent::Cancelled(report))) = events.last() else {
+            panic!("verification was not cancelled");
+        };
+        // Files already being verified were finished; the rest weren't started.
+        assert_eq!(MAX_PROCESS_CONCURRENCY as u64 + 1, report.sampled);
+        assert_eq!(MAX_PROCESS_CONCURRENCY as u64, report.verified());
+        assert_eq!(report.verified(), report.intact);
+    }
+}
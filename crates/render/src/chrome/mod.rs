@@ -0,0 +1,365 @@
+#[cfg(feature = "remote")]
+mod remote;
+mod sandbox;
+
+pub use self::sandbox::Sandbox;
+use crate::error::{ErrorKind, Result};
+use exn::ResultExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::instrument;
+
+/// Default maximum time to wait for Chrome to finish rendering before killing.
+const CHROME_TIMEOUT: Duration = Duration::from_secs(180);
+/// How often to poll for process completion.
+const CHROME_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How the [`Renderer`](crate::Renderer) should locate (or connect to) Chrome.
+///
+/// By default Chrome is auto-discovered: well-known executable names are
+/// searched for in `PATH`, followed by Flatpak installations. Use the builder
+/// methods to override discovery with an explicit binary, pass additional
+/// command-line flags, or (with the `remote` feature) connect to an
+/// already-running browser such as a sidecar container.
+///
+/// # Example
+///
+/// ```no_run
+/// use rawr_render::{ChromeConfig, Renderer, StyleConfig};
+/// # use rawr_render::error::Result;
+///
+/// # fn example() -> Result<()> {
+/// let chrome = ChromeConfig::new()
+///     .with_binary("/opt/chromium/chrome")
+///     .with_args(["--lang=en-GB"]);
+/// let renderer = Renderer::with_chrome(StyleConfig::new(), chrome)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ChromeConfig {
+    binary: Option<PathBuf>,
+    args: Vec<String>,
+    #[cfg(feature = "remote")]
+    remote: Option<String>,
+    sandbox: Sandbox,
+}
+impl ChromeConfig {
+    /// Creates a configuration that auto-discovers Chrome with no extra flags.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the given executable instead of auto-discovery.
+    ///
+    /// Either an absolute path or a bare executable name (resolved via `PATH`).
+    pub fn with_binary(mut self, path: impl Into<PathBuf>) -> Self {
+        self.binary = Some(path.into());
+        self
+    }
+
+    /// Appends a single command-line flag passed to every Chrome invocation.
+    pub fn with_arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Appends multiple command-line flags passed to every Chrome invocation.
+    pub fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Connects to an already-running Chrome via its DevTools websocket URL
+    /// (e.g. `ws://chrome:9222/devtools/browser/<id>`) instead of launching a
+    /// local process. Takes precedence over [`with_binary`](Self::with_binary);
+    /// launch flags are ignored since the browser is already running.
+    ///
+    /// Only plain `ws://` endpoints are supported.
+    #[cfg(feature = "remote")]
+    pub fn with_remote(mut self, endpoint: impl Into<String>) -> Self {
+        self.remote = Some(endpoint.into());
+        self
+    }
+
+    /// Applies the given restrictions and resource limits to every render.
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+}
+
+/// How Chrome/Chromium is launched (or reached).
+#[derive(Debug)]
+enum Launcher {
+    /// A directly executable binary.
+    Binary { path: PathBuf },
+    /// A Flatpak-installed application.
+    Flatpak { app_id: String },
+    /// An already-running browser reachable over the DevTools protocol.
+    #[cfg(feature = "remote")]
+    Remote { endpoint: String },
+}
+
+/// Represents a Chrome/Chromium installation, and how it should be run.
+#[derive(Debug)]
+pub(crate) struct Chrome {
+    launcher: Launcher,
+    args: Vec<String>,
+    sandbox: Sandbox,
+}
+impl Chrome {
+    pub(crate) fn discover(config: &ChromeConfig) -> Result<Self> {
+        let launcher = Self::discover_launcher(config)?;
+        let sandbox = config.sandbox.clone();
+        sandbox.check_args(&config.args)?;
+        #[cfg(feature = "remote")]
+        if matches!(launcher, Launcher::Remote { .. }) {
+            if sandbox.memory_limit.is_some() {
+                exn::bail!(ErrorKind::Sandbox("memory limits cannot be enforced on a remote browser".into()));
+            }
+            if sandbox.minimal_profile {
+                exn::bail!(ErrorKind::Sandbox("a remote browser's profile cannot be changed".into()));
+            }
+        }
+        #[cfg(not(target_os = "linux"))]
+        if sandbox.memory_limit.is_some() {
+            exn::bail!(ErrorKind::Sandbox("memory limits are only supported on Linux".into()));
+        }
+        Ok(Self {
+            launcher,
+            args: config.args.clone(),
+            sandbox,
+        })
+    }
+
+    fn discover_launcher(config: &ChromeConfig) -> Result<Launcher> {
+        #[cfg(feature = "remote")]
+        if let Some(endpoint) = &config.remote {
+            if !endpoint.starts_with("ws://") {
+                exn::bail!(ErrorKind::ChromeRemote);
+            }
+            return Ok(Launcher::Remote { endpoint: endpoint.clone() });
+        }
+        if let Some(binary) = &config.binary {
+            return match which::which(binary) {
+                Ok(path) => Ok(Launcher::Binary { path }),
+                Err(_) => {
+                    tracing::info!(binary = %binary.display(), "Configured Chrome executable not found");
+                    exn::bail!(ErrorKind::ChromeNotFound);
+                },
+            };
+        }
+        // Check for direct executables
+        // TODO: What are the executable names on Windows? macOS?
+        let executables = ["google-chrome", "chromium", "chromium-browser", "chrome"];
+        for exe in executables {
+            if let Ok(path) = which::which(exe) {
+                return Ok(Launcher::Binary { path });
+            }
+        }
+        tracing::info!("Chrome executable not found in PATH");
+        if let Ok(flatpak) = which::which("flatpak") {
+            tracing::trace!(flatpak = %flatpak.display(), "Discovered Flatpak on system; searching installed apps");
+            // Check Flatpak installations
+            let flatpak_apps = ["com.google.Chrome", "org.chromium.Chromium"];
+            for app_id in flatpak_apps {
+                if Command::new(&flatpak).args(["info", app_id]).output().is_ok_and(|o| o.status.success()) {
+                    return Ok(Launcher::Flatpak { app_id: app_id.to_string() });
+                }
+            }
+        } else {
+            tracing::info!("Flatpak not found; skipping containerized Chrome checks.");
+        }
+        exn::bail!(ErrorKind::ChromeNotFound);
+    }
+
+    /// Prints `html` to `pdf`, killing Chrome (and removing whatever it wrote
+    /// of the PDF) if `cancel` is cancelled first. Remote browsers can't be
+    /// interrupted mid-print; cancellation only stops them starting.
+    #[instrument(skip(cancel))]
+    pub(crate) fn execute(&self, html: &Path, pdf: &Path, cancel: &CancellationToken) -> Result<()> {
+        if !html.exists() || !pdf.is_absolute() || pdf.is_dir() {
+            exn::bail!(ErrorKind::Io);
+        }
+        if cancel.is_cancelled() {
+            exn::bail!(ErrorKind::Cancelled);
+        }
+        // A throwaway profile, so nothing leaks between renders.
+        let profile = match self.sandbox.minimal_profile {
+            true => Some(tempfile::tempdir().or_raise(|| ErrorKind::Io)?),
+            false => None,
+        };
+        let mut cmd = match &self.launcher {
+            Launcher::Binary { path } => Command::new(path),
+            Launcher::Flatpak { app_id } => {
+                let mut c = Command::new("flatpak");
+                c.args([
+                    "run",
+                    &format!("--filesystem={}", html.parent().unwrap().display()),
+                    &format!("--filesystem={}", pdf.parent().unwrap().display()),
+                ]);
+                if let Some(profile) = &profile {
+                    c.arg(format!("--filesystem={}", profile.path().display()));
+                }
+                c.args([app_id, "--"]);
+                c
+            },
+            #[cfg(feature = "remote")]
+            Launcher::Remote { endpoint } => return remote::print_to_pdf(endpoint, &self.sandbox, html, pdf),
+        };
+        cmd.args([
+            "--headless=new",
+            "--disable-gpu",
+            "--no-margins",
+            "--run-all-compositor-stages-before-draw",
+            "--font-render-hinting=none",
+            "--no-pdf-header-footer",
+            "--generate-pdf-document-outline",
+        ]);
+        cmd.args(self.sandbox.launch_args());
+        if let Some(profile) = &profile {
+            cmd.arg(format!("--user-data-dir={}", profile.path().display()));
+        }
+        // User-supplied flags come after the defaults so they take precedence.
+        cmd.args(&self.args);
+        cmd.args([
+            &format!("--print-to-pdf={}", pdf.display()),
+            &format!("file://{}", html.display()),
+        ]);
+        let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().or_raise(|| ErrorKind::Io)?;
+        let deadline = Instant::now() + self.sandbox.timeout;
+        'child: loop {
+            match child.try_wait().or_raise(|| ErrorKind::Io)? {
+                Some(_) => break 'child,
+                None if Instant::now() >= deadline => {
+                    _ = child.kill();
+                    _ = child.wait();
+                    exn::bail!(ErrorKind::ChromeTimeout);
+                },
+                None if cancel.is_cancelled() => {
+                    _ = child.kill();
+                    _ = child.wait();
+                    _ = std::fs::remove_file(pdf);
+                    tracing::info!("Rendering cancelled; killed Chrome.");
+                    exn::bail!(ErrorKind::Cancelled);
+                },
+                None => {
+                    #[cfg(target_os = "linux")]
+                    if let Some(limit) = self.sandbox.memory_limit
+                        && let Some(used) = sandbox::process_tree_memory(child.id())
+                        && used > limit
+                    {
+                        tracing::warn!(used, limit, "Chrome exceeded memory limit; killing.");
+                        _ = child.kill();
+                        _ = child.wait();
+                        exn::bail!(ErrorKind::MemoryLimit(limit));
+                    }
+                    sleep(CHROME_POLL_INTERVAL)
+                },
+            }
+        }
+        let output = child.wait_with_output().or_raise(|| ErrorKind::Io)?;
+        if !output.status.success() {
+            tracing::warn!(
+                stdout = %String::from_utf8_lossy(&output.stdout),
+                stderr = %String::from_utf8_lossy(&output.stderr),
+                "Chrome rendering failed.",
+            );
+        }
+        match output.status.code() {
+            Some(0) => Ok(()),
+            Some(c) => exn::bail!(ErrorKind::ChromeFailed(c)),
+            None => exn::bail!(ErrorKind::ChromeTimeout),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_configured_binary_not_found() {
+        let config = ChromeConfig::new().with_binary("/definitely/not/a/real/chrome");
+        let err = Chrome::discover(&config).unwrap_err();
+        assert!(matches!(&*err, ErrorKind::ChromeNotFound));
+    }
+
+    #[test]
+    fn test_configured_args_are_kept() {
+        // Any executable will do, Chrome is never launched.
+        let Ok(sh) = which::which("sh") else { return };
+        let config = ChromeConfig::new().with_binary(&sh).with_arg("--one").with_args(["--two", "--three"]);
+        let chrome = Chrome::discover(&config).unwrap();
+        assert!(matches!(&chrome.launcher, Launcher::Binary { path } if *path == sh));
+        assert_eq!(chrome.args, ["--one", "--two", "--three"]);
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn test_remote_takes_precedence() {
+        let config = ChromeConfig::new().with_binary("/not/used").with_remote("ws://chrome:9222/devtools/browser/abc");
+        assert!(matches!(Chrome::discover(&config).unwrap().launcher, Launcher::Remote { .. }));
+        let config = ChromeConfig::new().with_remote("http://chrome:9222");
+        assert!(matches!(&*Chrome::discover(&config).unwrap_err(), ErrorKind::ChromeRemote));
+    }
+
+    #[test]
+    fn test_sandbox_rejects_weakening_flags() {
+        let Ok(sh) = which::which("sh") else { return };
+        let config = ChromeConfig::new().with_binary(&sh).with_arg("--no-sandbox").with_sandbox(Sandbox::strict());
+        assert!(matches!(&*Chrome::discover(&config).unwrap_err(), ErrorKind::Sandbox(_)));
+        // User flags come last, so would otherwise win over the sandbox's.
+        let sandbox = Sandbox::new().with_network(false);
+        let config = ChromeConfig::new().with_binary(&sh).with_arg("--proxy-server=proxy:3128").with_sandbox(sandbox);
+        assert!(matches!(&*Chrome::discover(&config).unwrap_err(), ErrorKind::Sandbox(_)));
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn test_remote_cannot_enforce_memory_limit() {
+        let sandbox = Sandbox::new().with_memory_limit(1024);
+        let config = ChromeConfig::new().with_remote("ws://chrome:9222/devtools/browser/abc").with_sandbox(sandbox);
+        assert!(matches!(&*Chrome::discover(&config).unwrap_err(), ErrorKind::Sandbox(_)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cancel() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let (binary, html, pdf) =
+            (dir.path().join("chrome"), dir.path().join("work.html"), dir.path().join("work.pdf"));
+        // Stands in for a Chrome that never finishes printing.
+        std::fs::write(&binary, "#!/bin/sh\nexec sleep 60\n").unwrap();
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(&html, "<html></html>").unwrap();
+        let chrome = Chrome::discover(&ChromeConfig::new().with_binary(&binary)).unwrap();
+
+        // Cancelled before it starts, Chrome is never launched.
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        assert!(matches!(&*chrome.execute(&html, &pdf, &cancel).unwrap_err(), ErrorKind::Cancelled));
+
+        // Cancelled part-way through, Chrome is killed and its partial PDF removed.
+        std::fs::write(&pdf, "partial").unwrap();
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        std::thread::spawn(move || {
+            sleep(Duration::from_millis(200));
+            canceller.cancel();
+        });
+        let started = Instant::now();
+        assert!(matches!(&*chrome.execute(&html, &pdf, &cancel).unwrap_err(), ErrorKind::Cancelled));
+        assert!(started.elapsed() < Duration::from_secs(30));
+        assert!(!pdf.exists());
+    }
+}
@@ -0,0 +1,141 @@
+//! Minimal Chrome DevTools Protocol client for printing via a remote browser.
+//!
+//! Only the handful of commands needed to load a document into a fresh tab and
+//! print it are implemented. The remote browser cannot see our filesystem, so
+//! the HTML is sent as document content and the PDF is streamed back over the
+//! socket in chunks (avoiding websocket message size limits on large works).
+
+use super::Sandbox;
+use crate::error::{ErrorKind, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use exn::{OptionExt, ResultExt};
+use serde_json::{Value, json};
+use std::fs::File;
+use std::io::{ErrorKind as IoErrorKind, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::time::Duration;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+/// URL patterns blocked when the [`Sandbox`] disallows network access. Inline
+/// `data:` URIs are still permitted since they never leave the browser.
+const BLOCKED_URLS: [&str; 5] = ["http://*", "https://*", "ws://*", "wss://*", "ftp://*"];
+
+/// A single websocket connection to the browser endpoint. Commands are sent
+/// sequentially, so responses are matched by ID and events are ignored.
+struct Connection {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    next_id: u64,
+}
+impl Connection {
+    fn open(endpoint: &str, timeout: Duration) -> Result<Self> {
+        let (socket, _) = tungstenite::connect(endpoint).or_raise(|| ErrorKind::ChromeRemote)?;
+        if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+            stream.set_read_timeout(Some(timeout)).or_raise(|| ErrorKind::Io)?;
+        }
+        Ok(Self { socket, next_id: 0 })
+    }
+
+    fn call(&mut self, session: Option<&str>, method: &str, params: Value) -> Result<Value> {
+        self.next_id += 1;
+        let id = self.next_id;
+        let mut command = json!({ "id": id, "method": method, "params": params });
+        if let Some(session) = session {
+            command["sessionId"] = json!(session);
+        }
+        self.socket.send(Message::text(command.to_string())).or_raise(|| ErrorKind::ChromeRemote)?;
+        loop {
+            let text = match self.socket.read() {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Close(_)) => exn::bail!(ErrorKind::ChromeRemote),
+                Ok(_) => continue,
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), IoErrorKind::WouldBlock | IoErrorKind::TimedOut) =>
+                {
+                    exn::bail!(ErrorKind::ChromeTimeout)
+                },
+                Err(e) => return Err(e).or_raise(|| ErrorKind::ChromeRemote),
+            };
+            let mut response: Value = serde_json::from_str(&text).or_raise(|| ErrorKind::ChromeRemote)?;
+            if response["id"].as_u64() != Some(id) {
+                // An event, or a response to something we're not waiting for.
+                continue;
+            }
+            if let Some(error) = response.get("error") {
+                tracing::warn!(method, %error, "Remote Chrome rejected command.");
+                exn::bail!(ErrorKind::ChromeRemote);
+            }
+            return Ok(response["result"].take());
+        }
+    }
+}
+
+/// Prints the HTML file at `html` to a PDF at `pdf` using the browser at the
+/// DevTools websocket `endpoint`. A new tab is opened for the duration of the
+/// render and closed afterwards, regardless of outcome.
+///
+/// The [`Sandbox`] timeout bounds each individual protocol round-trip rather
+/// than the render as a whole.
+pub(crate) fn print_to_pdf(endpoint: &str, sandbox: &Sandbox, html: &Path, pdf: &Path) -> Result<()> {
+    let content = std::fs::read(html).or_raise(|| ErrorKind::Io)?;
+    let content = String::from_utf8_lossy(&content);
+    let mut conn = Connection::open(endpoint, sandbox.timeout)?;
+    let target = conn.call(None, "Target.createTarget", json!({ "url": "about:blank" }))?;
+    let target_id = target["targetId"].as_str().ok_or_raise(|| ErrorKind::ChromeRemote)?.to_string();
+    let result = print_in_target(&mut conn, sandbox, &target_id, &content, pdf);
+    _ = conn.call(None, "Target.closeTarget", json!({ "targetId": target_id }));
+    result
+}
+
+fn print_in_target(conn: &mut Connection, sandbox: &Sandbox, target_id: &str, content: &str, pdf: &Path) -> Result<()> {
+    let attached = conn.call(None, "Target.attachToTarget", json!({ "targetId": target_id, "flatten": true }))?;
+    let session = attached["sessionId"].as_str().ok_or_raise(|| ErrorKind::ChromeRemote)?.to_string();
+    let session = Some(session.as_str());
+
+    if !sandbox.javascript {
+        conn.call(session, "Emulation.setScriptExecutionDisabled", json!({ "value": true }))?;
+    }
+    if !sandbox.network {
+        conn.call(session, "Network.enable", json!({}))?;
+        conn.call(session, "Network.setBlockedURLs", json!({ "urls": BLOCKED_URLS }))?;
+    }
+
+    let tree = conn.call(session, "Page.getFrameTree", json!({}))?;
+    let frame_id = tree["frameTree"]["frame"]["id"].as_str().ok_or_raise(|| ErrorKind::ChromeRemote)?.to_string();
+    conn.call(session, "Page.setDocumentContent", json!({ "frameId": frame_id, "html": content }))?;
+
+    // Mirrors the flags used when launching a local binary.
+    let printed = conn.call(
+        session,
+        "Page.printToPDF",
+        json!({
+            "displayHeaderFooter": false,
+            "marginTop": 0,
+            "marginBottom": 0,
+            "marginLeft": 0,
+            "marginRight": 0,
+            "preferCSSPageSize": true,
+            "generateDocumentOutline": true,
+            "transferMode": "ReturnAsStream",
+        }),
+    )?;
+    let handle = printed["stream"].as_str().ok_or_raise(|| ErrorKind::ChromeRemote)?.to_string();
+
+    let mut file = File::create(pdf).or_raise(|| ErrorKind::Io)?;
+    loop {
+        let chunk = conn.call(session, "IO.read", json!({ "handle": handle }))?;
+        let data = chunk["data"].as_str().unwrap_or_default();
+        if chunk["base64Encoded"].as_bool().unwrap_or(false) {
+            file.write_all(&BASE64.decode(data).or_raise(|| ErrorKind::ChromeRemote)?).or_raise(|| ErrorKind::Io)?;
+        } else {
+            file.write_all(data.as_bytes()).or_raise(|| ErrorKind::Io)?;
+        }
+        if chunk["eof"].as_bool().unwrap_or(true) {
+            break;
+        }
+    }
+    _ = conn.call(session, "IO.close", json!({ "handle": handle }));
+    file.flush().or_raise(|| ErrorKind::Io)
+}
@@ -0,0 +1,268 @@
+//! Restrictions applied to Chrome while rendering untrusted HTML.
+
+use super::CHROME_TIMEOUT;
+use crate::error::{ErrorKind, Result};
+use std::time::Duration;
+
+/// Flags that would undo the protections of the minimal profile. Rejected
+/// (rather than silently dropped) when passed as extra launch flags.
+const FORBIDDEN_FLAGS: [&str; 7] = [
+    "--no-sandbox",
+    "--disable-web-security",
+    "--allow-file-access-from-files",
+    "--allow-running-insecure-content",
+    "--remote-debugging-port",
+    "--remote-debugging-pipe",
+    "--user-data-dir",
+];
+
+/// Flags that could turn JavaScript back on once it's disabled. Chrome lets
+/// the last copy of a flag win, and user flags come last.
+const JAVASCRIPT_FLAGS: [&str; 1] = ["--blink-settings"];
+
+/// Flags that could let requests out once the network is blocked.
+const NETWORK_FLAGS: [&str; 6] = [
+    "--proxy-server",
+    "--no-proxy-server",
+    "--proxy-pac-url",
+    "--proxy-auto-detect",
+    "--proxy-bypass-list",
+    "--host-resolver-rules",
+];
+
+/// Launch flags for the minimal profile: no extensions, no background
+/// services phoning home, and nothing persisted between renders (a fresh
+/// user data directory is supplied separately for every render).
+const MINIMAL_PROFILE_FLAGS: [&str; 10] = [
+    "--disable-extensions",
+    "--disable-plugins",
+    "--disable-background-networking",
+    "--disable-component-update",
+    "--disable-default-apps",
+    "--disable-sync",
+    "--disable-breakpad",
+    "--no-default-browser-check",
+    "--no-first-run",
+    "--mute-audio",
+];
+
+/// Limits and restrictions applied to every render.
+///
+/// The default matches Chrome's normal behaviour (JavaScript and network
+/// enabled) with a three minute timeout. Use [`Sandbox::strict`] as a starting
+/// point when rendering HTML from sources you don't trust.
+///
+/// Requirements that cannot be enforced for the chosen Chrome installation
+/// (such as a memory cap on a remote browser) are reported as
+/// [`ErrorKind::Sandbox`] when the [`Renderer`](crate::Renderer) is
+/// constructed, never silently ignored.
+///
+/// # Example
+///
+/// ```
+/// use rawr_render::Sandbox;
+/// use std::time::Duration;
+///
+/// let sandbox = Sandbox::strict()
+///     .with_timeout(Duration::from_secs(30))
+///     .with_memory_limit(1024 * 1024 * 1024);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Sandbox {
+    pub(crate) javascript: bool,
+    pub(crate) network: bool,
+    pub(crate) timeout: Duration,
+    pub(crate) memory_limit: Option<u64>,
+    pub(crate) minimal_profile: bool,
+}
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self {
+            javascript: true,
+            network: true,
+            timeout: CHROME_TIMEOUT,
+            memory_limit: None,
+            minimal_profile: false,
+        }
+    }
+}
+impl Sandbox {
+    /// Creates a permissive sandbox matching Chrome's default behaviour.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a locked-down sandbox: JavaScript disabled, network access
+    /// blocked, and the minimal browser profile enabled.
+    pub fn strict() -> Self {
+        Self {
+            javascript: false,
+            network: false,
+            minimal_profile: true,
+            ..Self::default()
+        }
+    }
+
+    /// Enables or disables JavaScript execution in rendered documents.
+    pub fn with_javascript(mut self, enabled: bool) -> Self {
+        self.javascript = enabled;
+        self
+    }
+
+    /// Allows or blocks network requests (remote images, fonts, etc.).
+    pub fn with_network(mut self, enabled: bool) -> Self {
+        self.network = enabled;
+        self
+    }
+
+    /// Sets the maximum time a single render may take before Chrome is killed
+    /// and [`ErrorKind::ChromeTimeout`] is returned.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Caps the resident memory (in bytes) of Chrome and all of its child
+    /// processes. Exceeding it kills Chrome and returns
+    /// [`ErrorKind::MemoryLimit`]. Only supported for locally launched Chrome
+    /// on Linux.
+    pub fn with_memory_limit(mut self, bytes: u64) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Runs Chrome with a throwaway profile and without extensions, background
+    /// services or any flag that weakens Chrome's own sandbox.
+    pub fn with_minimal_profile(mut self, enabled: bool) -> Self {
+        self.minimal_profile = enabled;
+        self
+    }
+
+    /// Checks that user-supplied launch flags don't undermine any restriction
+    /// of the sandbox that's switched on.
+    pub(crate) fn check_args(&self, args: &[String]) -> Result<()> {
+        let restrictions = [
+            (self.minimal_profile, &FORBIDDEN_FLAGS[..], "the minimal profile"),
+            (!self.javascript, &JAVASCRIPT_FLAGS[..], "disabling JavaScript"),
+            (!self.network, &NETWORK_FLAGS[..], "blocking the network"),
+        ];
+        for arg in args {
+            let name = arg.split_once('=').map_or(arg.as_str(), |(name, _)| name);
+            for (enabled, flags, restriction) in restrictions {
+                if enabled && flags.contains(&name) {
+                    exn::bail!(ErrorKind::Sandbox(format!("launch flag {name} conflicts with {restriction}")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Launch flags enforcing this sandbox on a locally executed Chrome.
+    pub(crate) fn launch_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if !self.javascript {
+            args.push("--blink-settings=scriptEnabled=false".to_string());
+        }
+        if !self.network {
+            // Fail every DNS lookup, and send anything addressed by IP to a
+            // proxy that can't possibly answer.
+            args.push("--host-resolver-rules=MAP * ~NOTFOUND".to_string());
+            args.push("--proxy-server=127.0.0.1:9".to_string());
+        }
+        if self.minimal_profile {
+            args.extend(MINIMAL_PROFILE_FLAGS.iter().map(ToString::to_string));
+        }
+        args
+    }
+}
+
+/// Total resident memory (in bytes) of the process `root` and all of its
+/// descendants, or `None` if `/proc` is unreadable.
+#[cfg(target_os = "linux")]
+pub(crate) fn process_tree_memory(root: u32) -> Option<u64> {
+    use std::collections::HashMap;
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    let mut resident: HashMap<u32, u64> = HashMap::new();
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+            continue;
+        };
+        // Processes can exit between listing and reading; that's fine.
+        let Ok(status) = std::fs::read_to_string(entry.path().join("status")) else {
+            continue;
+        };
+        for line in status.lines() {
+            if let Some(ppid) = line.strip_prefix("PPid:").and_then(|v| v.trim().parse().ok()) {
+                children.entry(ppid).or_default().push(pid);
+            } else if let Some(kb) =
+                line.strip_prefix("VmRSS:").and_then(|v| v.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            {
+                resident.insert(pid, kb.saturating_mul(1024));
+            }
+        }
+    }
+    let mut total = 0u64;
+    let mut stack = vec![root];
+    while let Some(pid) = stack.pop() {
+        total = total.saturating_add(resident.get(&pid).copied().unwrap_or(0));
+        stack.extend(children.get(&pid).into_iter().flatten());
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_adds_no_flags() {
+        assert!(Sandbox::default().launch_args().is_empty());
+    }
+
+    #[test]
+    fn test_strict_flags() {
+        let args = Sandbox::strict().launch_args();
+        assert!(args.contains(&"--blink-settings=scriptEnabled=false".to_string()));
+        assert!(args.iter().any(|a| a.starts_with("--host-resolver-rules")));
+        assert!(args.contains(&"--disable-extensions".to_string()));
+    }
+
+    #[test]
+    fn test_forbidden_flags_rejected() {
+        let args = vec!["--lang=en-GB".to_string(), "--no-sandbox".to_string()];
+        assert!(Sandbox::default().check_args(&args).is_ok());
+        let err = Sandbox::strict().check_args(&args).unwrap_err();
+        assert!(matches!(&*err, ErrorKind::Sandbox(_)));
+        let args = vec!["--remote-debugging-port=9222".to_string()];
+        assert!(Sandbox::strict().check_args(&args).is_err());
+    }
+
+    #[test]
+    fn test_conflicting_flags_rejected() {
+        let javascript = vec!["--blink-settings=scriptEnabled=true".to_string()];
+        assert!(Sandbox::default().check_args(&javascript).is_ok());
+        let err = Sandbox::default().with_javascript(false).check_args(&javascript).unwrap_err();
+        assert!(matches!(&*err, ErrorKind::Sandbox(_)));
+
+        for flag in [
+            "--proxy-server=proxy:3128",
+            "--host-resolver-rules=MAP * 10.0.0.1",
+            "--no-proxy-server",
+        ] {
+            let args = vec![flag.to_string()];
+            assert!(Sandbox::default().check_args(&args).is_ok());
+            let err = Sandbox::default().with_network(false).check_args(&args).unwrap_err();
+            assert!(matches!(&*err, ErrorKind::Sandbox(_)), "{flag}");
+        }
+        // Each restriction only rejects the flags that would undo it.
+        assert!(Sandbox::default().with_network(false).check_args(&javascript).is_ok());
+        let args = vec!["--no-sandbox".to_string()];
+        assert!(Sandbox::default().with_javascript(false).with_network(false).check_args(&args).is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_measures_own_memory() {
+        assert!(process_tree_memory(std::process::id()).is_some_and(|bytes| bytes > 0));
+    }
+}
@@ -0,0 +1,237 @@
+//! Generated title pages for rendered works.
+//!
+//! A [`CoverTemplate`] is an [upon] template rendered against a work's
+//! [`Metadata`] and injected immediately after the opening `<body>` tag, so
+//! that exported PDFs open with a book-like title page. All values are HTML
+//! escaped; templates cannot inject markup through metadata.
+//!
+//! # Template Variables
+//!
+//! | Variable             | Type                  | Description                              |
+//! |----------------------|-----------------------|------------------------------------------|
+//! | `work`               | `u64`                 | The AO3 work ID                          |
+//! | `title`              | `String`              | Work title                               |
+//! | `authors`            | `List<String>`        | Author display names                     |
+//! | `fandoms`            | `List<String>`        | Fandom names                             |
+//! | `series`             | `List<Dict>`          | `id`, `name` and `position` of each      |
+//! | `rating`             | `Option<String>`      | Full rating (e.g. `"Teen And Up"`)       |
+//! | `warnings`           | `List<String>`        | Archive warnings                         |
+//! | `tags.relationships` | `List<String>`        | Relationship tags                        |
+//! | `tags.characters`    | `List<String>`        | Character tags                           |
+//! | `tags.freeforms`     | `List<String>`        | Additional (freeform) tags               |
+//! | `summary`            | `List<String>`        | Summary, one entry per paragraph         |
+//! | `words`              | `String`              | Word count with thousands separators     |
+//! | `chapters.written`   | `u64`                 | Number of posted chapters                |
+//! | `chapters.total`     | `Option<u64>`         | Planned total chapters                   |
+//! | `language`           | `String`              | Language name                            |
+//! | `published`          | `String`              | Date first published (`YYYY-MM-DD`)      |
+//! | `updated`            | `String`              | Date last modified (`YYYY-MM-DD`)        |
+//!
+//! Ratings and warnings are named in the cover's [`Locale`] (English unless
+//! [set](CoverTemplate::with_locale)).
+
+use crate::error::{ErrorKind, Result};
+use exn::ResultExt;
+use rawr_extract::models::{Locale, Metadata, TagKind};
+use std::fmt::Write;
+use std::path::Path;
+use std::str::FromStr;
+use upon::{Engine, Template, Value, fmt as upon_fmt};
+
+/// The builtin cover page, sourced from `assets/templates/cover.html`.
+const BUILTIN_COVER: &str = include_str!("../../../assets/templates/cover.html");
+
+/// A compiled title page template.
+///
+/// Use [`CoverTemplate::builtin`] for the default cover, or parse a custom
+/// template with [`FromStr`] / [`CoverTemplate::from_file`]. Attach it to a
+/// [`StyleConfig`](crate::StyleConfig) via
+/// [`with_cover`](crate::StyleConfig::with_cover); covers are only rendered by
+/// the [`Renderer::render_work`](crate::Renderer::render_work) family of
+/// methods since they need the work's metadata.
+pub struct CoverTemplate {
+    engine: Engine<'static>,
+    template: Template<'static>,
+    locale: Locale,
+}
+impl FromStr for CoverTemplate {
+    type Err = crate::error::Error;
+
+    /// Compiles a custom cover template. Returns [`ErrorKind::Template`] if
+    /// the template syntax is invalid.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut engine = Engine::new();
+        engine.set_default_formatter(&escape_html);
+        let template = engine.compile(s.to_string()).or_raise(|| ErrorKind::Template)?;
+        Ok(Self {
+            engine,
+            template,
+            locale: Locale::default(),
+        })
+    }
+}
+impl Default for CoverTemplate {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+impl CoverTemplate {
+    /// The builtin cover page: title, authors, fandoms, key stats, tags and
+    /// summary, followed by a page break.
+    pub fn builtin() -> Self {
+        // Infallible: the builtin template is covered by tests.
+        BUILTIN_COVER.parse().expect("builtin cover template compiles")
+    }
+
+    /// Compiles a custom cover template read from a file on disk.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            exn::bail!(ErrorKind::AssetNotFound(path.display().to_string()));
+        }
+        std::fs::read_to_string(path).or_raise(|| ErrorKind::Io)?.parse()
+    }
+
+    /// Names ratings and warnings in `locale`, rather than in English.
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Renders the cover page HTML for a work.
+    pub(crate) fn render(&self, metadata: &Metadata) -> Result<String> {
+        self.template
+            .render(&self.engine, parameters(metadata, &self.locale))
+            .to_string()
+            .or_raise(|| ErrorKind::Template)
+    }
+}
+
+/// Builds the [`upon::Value`] map exposed to cover templates.
+pub(crate) fn parameters(m: &Metadata, locale: &Locale) -> Value {
+    let tags = |kind: TagKind| m.tags.iter().filter(|t| t.kind == kind).map(|t| t.name.clone()).collect::<Vec<_>>();
+    let summary = m
+        .summary
+        .as_deref()
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let series = m
+        .series
+        .iter()
+        .map(|s| upon::value! { id: s.id, name: s.name.as_str(), position: s.position })
+        .collect::<Vec<_>>();
+    upon::value! {
+        work: m.work_id,
+        title: &m.title,
+        authors: m.authors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        fandoms: m.fandoms.iter().map(|f| f.name.clone()).collect::<Vec<_>>(),
+        series: series,
+        rating: m.rating.map(|r| locale.rating(r)),
+        warnings: m.warnings.iter().map(|w| locale.warning(*w)).collect::<Vec<_>>(),
+        tags: upon::value! {
+            relationships: tags(TagKind::Relationship),
+            characters: tags(TagKind::Character),
+            freeforms: tags(TagKind::Freeform),
+        },
+        summary: summary,
+        words: crate::style::variables::human_number(m.words),
+        chapters: upon::value! {
+            written: m.chapters.written,
+            total: m.chapters.total,
+        },
+        language: &m.language.name,
+        published: m.published.to_string(),
+        updated: m.last_modified.to_string(),
+    }
+}
+
+/// Default formatter: escapes every rendered value so that metadata can never
+/// inject markup into the document.
+fn escape_html(f: &mut upon_fmt::Formatter<'_>, value: &Value) -> upon_fmt::Result {
+    let Value::String(s) = value else {
+        // Numbers, booleans and none can't contain markup.
+        return upon_fmt::default(f, value);
+    };
+    for c in s.chars() {
+        match c {
+            '&' => f.write_str("&amp;")?,
+            '<' => f.write_str("&lt;")?,
+            '>' => f.write_str("&gt;")?,
+            '"' => f.write_str("&quot;")?,
+            '\'' => f.write_str("&#39;")?,
+            c => f.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rawr_extract::models::{Author, Chapters, Fandom, Language, Rating, Tag, Warning};
+    use time::{Date, Month};
+
+    pub(crate) fn make_test_metadata() -> Metadata {
+        Metadata {
+            work_id: 12345,
+            title: "Tea & <Biscuits>".to_string(),
+            authors: vec![Author::new("writer", None::<&str>), Author::new("user", Some("pseud"))],
+            fandoms: vec![Fandom { name: "Original Work".to_string() }],
+            series: vec![],
+            chapters: Chapters { written: 3, total: None },
+            words: 21837,
+            rating: Some(Rating::TeenAndUp),
+            warnings: vec![],
+            tags: vec![Tag {
+                name: "Fluff".to_string(),
+                kind: TagKind::Freeform,
+            }],
+            summary: Some("First paragraph.\n\nSecond paragraph.".to_string()),
+            language: Language::new("English"),
+            published: Date::from_calendar_date(2024, Month::January, 1).unwrap(),
+            last_modified: Date::from_calendar_date(2024, Month::June, 15).unwrap(),
+            provenance: Default::default(),
+            work_skin: None,
+        }
+    }
+
+    #[test]
+    fn test_builtin_cover_renders() {
+        let html = CoverTemplate::builtin().render(&make_test_metadata()).unwrap();
+        assert!(html.contains("Tea &amp; &lt;Biscuits&gt;"));
+        assert!(html.contains("writer, pseud (user)"));
+        assert!(html.contains("21,837"));
+        assert!(html.contains("3/?"));
+        assert!(html.contains("<p>First paragraph.</p><p>Second paragraph.</p>"));
+        assert!(html.contains("Fluff"));
+        // Empty collections are skipped entirely.
+        assert!(!html.contains("rawr-cover-series"));
+        assert!(!html.contains("rawr-cover-relationships"));
+    }
+
+    #[test]
+    fn test_custom_cover() {
+        let cover: CoverTemplate = "<h1>{{ title }}</h1><p>{{ work }}</p>".parse().unwrap();
+        let html = cover.render(&make_test_metadata()).unwrap();
+        assert_eq!(html, "<h1>Tea &amp; &lt;Biscuits&gt;</h1><p>12345</p>");
+    }
+
+    #[test]
+    fn test_localized_cover() {
+        let cover: CoverTemplate = "{{ rating }}{% for w in warnings %}|{{ w }}{% endfor %}".parse().unwrap();
+        let cover = cover.with_locale(Locale::new("de").with_rating(Rating::TeenAndUp, "Ab 13"));
+        let mut metadata = make_test_metadata();
+        metadata.warnings = vec![Warning::NoWarningsApply];
+        assert_eq!("Ab 13|No Archive Warnings Apply", cover.render(&metadata).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_cover() {
+        let err = "{{ title ".parse::<CoverTemplate>().err().unwrap();
+        assert!(matches!(&*err, ErrorKind::Template));
+    }
+}
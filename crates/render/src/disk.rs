@@ -0,0 +1,147 @@
+//! Keeping batch exports from filling the disk.
+//!
+//! Rendering a whole library writes a PDF per work, and nothing else stops
+//! it until the disk is full (taking the cache database and everything else
+//! on it down too). A [`DiskBudget`] caps how much a renderer may write, and
+//! how little free space it must leave, checked before every render.
+
+use crate::error::{ErrorKind, Result};
+use exn::ResultExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How much disk a renderer may use (see
+/// [`Renderer::with_disk_budget`](crate::Renderer::with_disk_budget)).
+///
+/// Both limits are checked before each render, so the render that takes the
+/// output over its cap still finishes; only the renders after it fail. The
+/// default budget is unlimited.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_render::DiskBudget;
+///
+/// // At most 20 GiB of PDFs, stopping early if less than 1 GiB would be left.
+/// let budget = DiskBudget::default().with_output(20 << 30).with_min_free(1 << 30);
+/// assert!(!budget.is_unlimited());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskBudget {
+    /// How many bytes of PDFs may be rendered, in total.
+    pub output: Option<u64>,
+    /// How many bytes must be left free where PDFs (and temporary files)
+    /// are written. Only enforced on Unix.
+    pub min_free: Option<u64>,
+}
+impl DiskBudget {
+    pub fn with_output(mut self, bytes: u64) -> Self {
+        self.output = Some(bytes);
+        self
+    }
+
+    pub fn with_min_free(mut self, bytes: u64) -> Self {
+        self.min_free = Some(bytes);
+        self
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.output.is_none() && self.min_free.is_none()
+    }
+}
+
+/// A [`DiskBudget`], and how much of it has been spent.
+#[derive(Debug, Default)]
+pub(crate) struct DiskUsage {
+    pub(crate) budget: DiskBudget,
+    rendered: AtomicU64,
+}
+impl DiskUsage {
+    pub(crate) fn new(budget: DiskBudget) -> Self {
+        Self { budget, rendered: AtomicU64::new(0) }
+    }
+
+    /// Bytes of PDFs rendered so far.
+    pub(crate) fn rendered(&self) -> u64 {
+        self.rendered.load(Ordering::Relaxed)
+    }
+
+    /// Fails if another render would go over budget, writing to `dirs`.
+    pub(crate) fn check<'a>(&self, dirs: impl IntoIterator<Item = &'a Path>) -> Result<()> {
+        if let Some(cap) = self.budget.output {
+            let rendered = self.rendered();
+            if rendered >= cap {
+                exn::bail!(ErrorKind::OutputLimit(rendered));
+            }
+        }
+        let Some(min_free) = self.budget.min_free else {
+            return Ok(());
+        };
+        for dir in dirs {
+            if let Some(available) = available_space(dir)?
+                && available < min_free
+            {
+                tracing::warn!(dir = %dir.display(), available, min_free, "Not enough free space left to render");
+                exn::bail!(ErrorKind::InsufficientSpace(available));
+            }
+        }
+        Ok(())
+    }
+
+    /// Accounts for the PDF rendered at `path`.
+    pub(crate) fn record(&self, path: &Path) -> Result<()> {
+        let size = std::fs::metadata(path).or_raise(|| ErrorKind::Io)?.len();
+        let rendered = self.rendered.fetch_add(size, Ordering::Relaxed).saturating_add(size);
+        tracing::debug!(size, rendered, "Rendered PDF accounted for");
+        Ok(())
+    }
+}
+
+/// Bytes free to unprivileged users in the filesystem holding `dir`, where
+/// that's known.
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Result<Option<u64>> {
+    let stat = rustix::fs::statvfs(dir).or_raise(|| ErrorKind::Io)?;
+    Ok(Some(stat.f_bavail.saturating_mul(stat.f_frsize)))
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> Result<Option<u64>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+
+    #[test]
+    fn test_disk_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let pdf = dir.path().join("work.pdf");
+        std::fs::write(&pdf, [0u8; 100]).unwrap();
+
+        let unlimited = DiskUsage::default();
+        unlimited.check([dir.path()]).unwrap();
+        unlimited.record(&pdf).unwrap();
+        assert_eq!(100, unlimited.rendered());
+
+        // The render taking the output over its cap isn't stopped, the next is.
+        let capped = DiskUsage::new(DiskBudget::default().with_output(150));
+        capped.check([dir.path()]).unwrap();
+        capped.record(&pdf).unwrap();
+        capped.check([dir.path()]).unwrap();
+        capped.record(&pdf).unwrap();
+        let error = capped.check([dir.path()]).unwrap_err();
+        assert!(matches!(&*error, ErrorKind::OutputLimit(200)));
+        assert_eq!("RAWR-RENDER-413", (*error).code().to_string());
+
+        #[cfg(unix)]
+        {
+            let full = DiskUsage::new(DiskBudget::default().with_min_free(u64::MAX));
+            let error = full.check([dir.path()]).unwrap_err();
+            assert!(matches!(&*error, ErrorKind::InsufficientSpace(_)));
+            DiskUsage::new(DiskBudget::default().with_min_free(1)).check([dir.path()]).unwrap();
+        }
+    }
+}
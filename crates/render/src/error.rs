@@ -8,6 +8,7 @@
 //!       to resort to anyhow+thiserror just because I don't want to deal with it.
 
 use derive_more::{Display, Error};
+pub use rawr_error::{Code, Domain, ErrorCode};
 
 /// A render error with automatic location tracking.
 pub type Error = exn::Exn<ErrorKind>;
@@ -26,8 +27,48 @@ pub enum ErrorKind {
     /// Chrome exited with a non-zero exit code.
     #[display("Chrome exited with code: {_0}")]
     ChromeFailed(#[error(not(source))] i32),
+    /// Chrome (and its child processes) exceeded the configured memory limit.
+    #[display("Chrome exceeded memory limit of {_0} bytes")]
+    MemoryLimit(#[error(not(source))] u64),
+    /// The renderer's [disk budget](crate::DiskBudget) for output has been
+    /// spent, having rendered this many bytes of PDFs.
+    #[display("output limit reached after rendering {_0} bytes")]
+    OutputLimit(#[error(not(source))] u64),
+    /// Less than the [disk budget](crate::DiskBudget)'s minimum is free
+    /// where PDFs are written, with this many bytes available.
+    #[display("not enough free disk space to render: {_0} bytes available")]
+    InsufficientSpace(#[error(not(source))] u64),
+    /// A [`Sandbox`](crate::Sandbox) requirement cannot be enforced, or a
+    /// launch flag would weaken it.
+    #[display("sandbox requirement cannot be met: {_0}")]
+    Sandbox(#[error(not(source))] String),
+    /// Communicating with a remote Chrome over the DevTools protocol failed.
+    #[display("remote chrome/chromium unreachable or returned an error")]
+    ChromeRemote,
+    /// A cover page template failed to compile or render.
+    #[display("invalid cover page template")]
+    Template,
+    /// A user stylesheet has a syntax error, described by its path and the
+    /// line and column the error was found at.
+    #[display("invalid CSS: {_0}")]
+    InvalidCss(#[error(not(source))] String),
+    /// A font file isn't a TrueType, OpenType or WOFF font.
+    #[display("unsupported font file: {_0}")]
+    InvalidFont(#[error(not(source))] String),
+    /// A rendered PDF cannot be made to conform to PDF/A-2b, for the reasons
+    /// given.
+    #[display("cannot produce PDF/A-2b: {_0}")]
+    PdfA(#[error(not(source))] String),
+    /// An [`HtmlTransform`](crate::HtmlTransform) could not rewrite a
+    /// document, for the reason given.
+    #[display("HTML transform failed: {_0}")]
+    Transform(#[error(not(source))] String),
     /// Asset was not loadable (either file or builtin).
     AssetNotFound(#[error(not(source))] String),
+    /// Rendering was cancelled through the renderer's
+    /// [cancellation token](crate::Renderer::with_cancellation).
+    #[display("rendering cancelled")]
+    Cancelled,
     /// An underlying I/O operation failed (file read, temp file creation, etc.).
     Io,
 }
@@ -35,9 +76,30 @@ pub enum ErrorKind {
 impl ErrorKind {
     /// Returns `true` if retrying might succeed.
     pub fn is_retryable(&self) -> bool {
-        match self {
-            Self::ChromeTimeout | Self::Io => true,
-            _ => false,
-        }
+        matches!(self, Self::ChromeTimeout | Self::ChromeRemote | Self::Io)
+    }
+}
+
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::InvalidCss(_) => 400,
+            Self::Sandbox(_) => 403,
+            Self::InvalidFont(_) => 415,
+            Self::AssetNotFound(_) => 404,
+            Self::Template => 422,
+            Self::Transform(_) => 425,
+            Self::OutputLimit(_) => 413,
+            Self::Cancelled => 499,
+            Self::PdfA(_) => 424,
+            Self::Io => 500,
+            Self::ChromeFailed(_) => 502,
+            Self::ChromeNotFound => 503,
+            Self::ChromeTimeout => 504,
+            Self::MemoryLimit(_) => 507,
+            Self::InsufficientSpace(_) => 508,
+            Self::ChromeRemote => 520,
+        };
+        Code::new(Domain::Render, number)
     }
 }
@@ -0,0 +1,261 @@
+//! Running headers and footers for rendered works.
+//!
+//! Chrome's own headers and footers show the date and the URL of the
+//! temporary file being printed, so they're turned off. [`RunningHeaders`]
+//! puts text of our own in the page margins instead: [upon] templates
+//! rendered against a work's [`Metadata`] (with the same variables as
+//! [covers](crate::CoverTemplate)), turned into CSS `@page` margin boxes. The
+//! margin boxes' fonts and colours come from the stylesheets, like those of
+//! `book.css`; only their content is replaced.
+//!
+//! # Template Variables
+//!
+//! Besides every variable of [cover templates](crate::CoverTemplate):
+//!
+//! | Variable  | Type             | Description                                  |
+//! |-----------|------------------|----------------------------------------------|
+//! | `author`  | `String`         | Author display names, comma separated        |
+//! | `chapter` | `Option<String>` | Heading of the chapter the page is in        |
+//! | `page`    | -                | The page number                              |
+//! | `pages`   | -                | How many pages there are                     |
+//!
+//! Pages only know their chapter because each chapter is laid out on pages
+//! of its own (CSS named pages), so templates using `chapter` start every
+//! chapter on a new page.
+
+use crate::error::{ErrorKind, Result};
+use crate::transform::{element_end, find_open, has_attribute_word, strip_tags};
+use crate::volume::find_chapters;
+use exn::ResultExt;
+use rawr_extract::models::{Locale, Metadata};
+use std::fmt::Write;
+use upon::{Engine, Template, Value};
+
+/// Stands in for the page number in rendered templates.
+const PAGE: char = '\u{E000}';
+/// Stands in for the number of pages in rendered templates.
+const PAGES: char = '\u{E001}';
+/// Stands in for the chapter, to tell whether a template uses it.
+const CHAPTER: &str = "\u{E002}";
+
+/// Compiled header and footer templates.
+///
+/// Attach them to a [`StyleConfig`](crate::StyleConfig) via
+/// [`with_headers`](crate::StyleConfig::with_headers); like covers, they're
+/// only rendered by the [`Renderer::render_work`](crate::Renderer::render_work)
+/// family of methods since they need the work's metadata.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_render::RunningHeaders;
+///
+/// let headers = RunningHeaders::new()
+///     .with_header("{{ title }}{% if chapter %}: {{ chapter }}{% endif %}")
+///     .unwrap()
+///     .with_footer("{{ page }} of {{ pages }}")
+///     .unwrap();
+/// ```
+pub struct RunningHeaders {
+    engine: Engine<'static>,
+    header: Option<Template<'static>>,
+    footer: Option<Template<'static>>,
+    locale: Locale,
+}
+impl Default for RunningHeaders {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+impl RunningHeaders {
+    /// No header or footer, until [set](Self::with_header).
+    pub fn new() -> Self {
+        Self {
+            engine: Engine::new(),
+            header: None,
+            footer: None,
+            locale: Locale::default(),
+        }
+    }
+
+    /// The work's title and author in the header, and the page number in the
+    /// footer.
+    pub fn builtin() -> Self {
+        // Infallible: the builtin templates are covered by tests.
+        Self::new()
+            .with_header("{{ title }} by {{ author }}")
+            .and_then(|headers| headers.with_footer("{{ page }}"))
+            .expect("builtin header templates compile")
+    }
+
+    /// Prints `template` centred at the top of every page.
+    ///
+    /// Returns [`ErrorKind::Template`] if the template syntax is invalid.
+    pub fn with_header(mut self, template: &str) -> Result<Self> {
+        self.header = Some(self.engine.compile(template.to_string()).or_raise(|| ErrorKind::Template)?);
+        Ok(self)
+    }
+
+    /// Prints `template` centred at the bottom of every page.
+    ///
+    /// Returns [`ErrorKind::Template`] if the template syntax is invalid.
+    pub fn with_footer(mut self, template: &str) -> Result<Self> {
+        self.footer = Some(self.engine.compile(template.to_string()).or_raise(|| ErrorKind::Template)?);
+        Ok(self)
+    }
+
+    /// Names ratings and warnings in `locale`, rather than in English.
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Lays a work's chapters out on pages of their own (when the templates
+    /// use `chapter`), returning the document and the stylesheet printing the
+    /// headers and footers.
+    pub(crate) fn apply(&self, mut html: String, metadata: &Metadata) -> Result<(String, String)> {
+        let mut css = String::new();
+        self.page_rule(&mut css, "@page", metadata, None)?;
+        if !self.uses_chapter(metadata)? {
+            return Ok((html, css));
+        }
+
+        let lower = html.to_ascii_lowercase();
+        let Some((content, starts)) = find_chapters(&html, &lower) else {
+            return Ok((html, css));
+        };
+        let mut bounds: Vec<_> = starts.windows(2).map(|w| (w[0], w[1])).collect();
+        bounds.extend(starts.last().map(|&start| (start, content.end)));
+        for (i, &(start, end)) in bounds.iter().enumerate() {
+            let heading = find_open(&lower, "h2", start)
+                .filter(|&at| at < end && has_attribute_word(&html[at..], "class", "heading"))
+                .and_then(|at| element_end(&lower, "h2", at).map(|end| strip_tags(&html[at..end])));
+            let name = format!("rawr-chapter-{}", i + 1);
+            _ = write!(css, ".{name} {{ page: {name}; }}");
+            self.page_rule(&mut css, &format!("@page {name}"), metadata, heading.as_deref())?;
+        }
+        // From the end, so that earlier offsets stay put.
+        for (i, &(start, end)) in bounds.iter().enumerate().rev() {
+            html.insert_str(end, "</div>");
+            html.insert_str(start, &format!("<div class=\"rawr-chapter-{}\">", i + 1));
+        }
+        tracing::debug!(chapters = bounds.len(), "Chapters laid out on named pages for running headers");
+        Ok((html, css))
+    }
+
+    /// Writes an `@page` rule (`selector`) printing the header and footer.
+    fn page_rule(&self, css: &mut String, selector: &str, metadata: &Metadata, chapter: Option<&str>) -> Result<()> {
+        _ = write!(css, "{selector} {{");
+        if let Some(header) = &self.header {
+            let text = self.render(header, metadata, chapter.map(decode_entities))?;
+            _ = write!(css, " @top-center {{ content: {}; }}", css_content(&text));
+        }
+        if let Some(footer) = &self.footer {
+            let text = self.render(footer, metadata, chapter.map(decode_entities))?;
+            _ = write!(css, " @bottom-center {{ content: {}; }}", css_content(&text));
+        }
+        css.push_str(" }\n");
+        Ok(())
+    }
+
+    /// Whether either template prints the chapter.
+    fn uses_chapter(&self, metadata: &Metadata) -> Result<bool> {
+        for template in self.header.iter().chain(&self.footer) {
+            if self.render(template, metadata, Some(CHAPTER.to_string()))?.contains(CHAPTER) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn render(&self, template: &Template<'static>, metadata: &Metadata, chapter: Option<String>) -> Result<String> {
+        let mut parameters = crate::cover::parameters(metadata, &self.locale);
+        if let Value::Map(map) = &mut parameters {
+            let authors = metadata.authors.iter().map(ToString::to_string).collect::<Vec<_>>();
+            map.insert("author".to_string(), Value::String(authors.join(", ")));
+            map.insert("chapter".to_string(), chapter.map(|c| Value::String(c.trim().to_string())).into());
+            map.insert("page".to_string(), Value::String(PAGE.to_string()));
+            map.insert("pages".to_string(), Value::String(PAGES.to_string()));
+        }
+        template.render(&self.engine, parameters).to_string().or_raise(|| ErrorKind::Template)
+    }
+}
+
+/// A CSS `content` value printing `text`, with page counters in place of
+/// the [`PAGE`] and [`PAGES`] placeholders.
+fn css_content(text: &str) -> String {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let flush = |parts: &mut Vec<String>, literal: &mut String| {
+        if !literal.is_empty() {
+            parts.push(format!("\"{}\"", std::mem::take(literal)));
+        }
+    };
+    for c in text.chars() {
+        match c {
+            PAGE | PAGES => {
+                flush(&mut parts, &mut literal);
+                parts.push(if c == PAGE { "counter(page)" } else { "counter(pages)" }.to_string());
+            },
+            // Escaped so that metadata can't end the string (or the
+            // `<style>` element it's in).
+            '"' | '\\' | '<' | '>' => _ = write!(literal, "\\{:x} ", c as u32),
+            c if c.is_control() => literal.push(' '),
+            c => literal.push(c),
+        }
+    }
+    flush(&mut parts, &mut literal);
+    match parts.is_empty() {
+        true => "none".to_string(),
+        false => parts.join(" "),
+    }
+}
+
+/// Decodes the entities chapter headings are likely to contain.
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cover::tests::make_test_metadata;
+
+    #[test]
+    fn test_running_headers() {
+        let mut metadata = make_test_metadata();
+        metadata.title = "Tea & \"Biscuits\" </style>".to_string();
+        let html = "<html><head></head><body><div id=\"chapters\">\
+            <h2 class=\"heading\">Chapter 1: Start</h2><p>One</p>\
+            <h2 class=\"heading\">Chapter 2: Tom &amp; Jerry</h2><p>Two</p>\
+            </div></body></html>"
+            .to_string();
+
+        // Without the chapter, the document is left as it was.
+        let headers = RunningHeaders::new().with_header("{{ title }}").unwrap().with_footer("{{ page }} / {{ pages }}");
+        let (document, css) = headers.unwrap().apply(html.clone(), &metadata).unwrap();
+        assert_eq!(html, document);
+        assert_eq!(
+            "@page { @top-center { content: \"Tea & \\22 Biscuits\\22  \\3c /style\\3e \"; } \
+             @bottom-center { content: counter(page) \" / \" counter(pages); } }\n",
+            css
+        );
+
+        let headers = RunningHeaders::new().with_header("{% if chapter %}{{ chapter }}{% endif %}").unwrap();
+        let (document, css) = headers.apply(html, &metadata).unwrap();
+        assert!(document.contains("<div id=\"chapters\"><div class=\"rawr-chapter-1\"><h2 class=\"heading\">Chapter 1: Start</h2><p>One</p></div><div class=\"rawr-chapter-2\">"));
+        assert!(document.contains("<p>Two</p></div></div></body>"));
+        assert!(css.starts_with("@page { @top-center { content: none; } }\n"));
+        assert!(css.contains(".rawr-chapter-2 { page: rawr-chapter-2; }"));
+        assert!(css.contains("@page rawr-chapter-2 { @top-center { content: \"Chapter 2: Tom & Jerry\"; } }"));
+
+        let builtin = RunningHeaders::builtin();
+        let header = builtin.render(builtin.header.as_ref().unwrap(), &metadata, None).unwrap();
+        assert_eq!(format!("{} by writer, pseud (user)", metadata.title), header);
+        assert!(RunningHeaders::new().with_footer("{{ page").is_err());
+    }
+}
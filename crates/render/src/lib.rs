@@ -1,9 +1,11 @@
 //! Chrome/Chromium-based HTML-to-PDF rendering.
 //!
 //! This crate converts HTML documents into PDFs by driving a locally installed
-//! Chrome or Chromium browser in headless mode. CSS stylesheets (both built-in
+//! Chrome or Chromium browser in headless mode (or, with the `remote` feature,
+//! an already-running browser via its DevTools endpoint; see [`ChromeConfig`]). CSS stylesheets (both built-in
 //! and user-provided) are injected into the HTML before rendering, and optional
-//! CSS custom properties can be set via [`CssVariables`].
+//! CSS custom properties can be set via [`CssVariables`]. Documents can be
+//! rewritten before rendering by [`HtmlTransform`]s (see [`transform`]).
 //!
 //! # Usage
 //!
@@ -23,19 +25,43 @@
 //! ```
 
 mod chrome;
+#[cfg(feature = "metadata")]
+mod cover;
+mod disk;
 pub mod error;
+#[cfg(feature = "metadata")]
+mod headers;
+#[cfg(feature = "pdfa")]
+mod pdfa;
 mod render;
+mod skin;
 mod style;
+mod temp;
+pub mod transform;
+mod volume;
 
 use crate::chrome::Chrome;
+pub use crate::chrome::{ChromeConfig, Sandbox};
+#[cfg(feature = "metadata")]
+pub use crate::cover::CoverTemplate;
+pub use crate::disk::DiskBudget;
+use crate::disk::DiskUsage;
 use crate::error::{Error, Result};
+#[cfg(feature = "metadata")]
+pub use crate::headers::RunningHeaders;
 pub use crate::render::Output;
+pub use crate::skin::sanitize_work_skin;
 pub use crate::style::{StyleConfig, variables::CssVariables};
+pub use crate::temp::TempConfig;
+pub use crate::transform::HtmlTransform;
+pub use crate::volume::{Volume, VolumeBudget, WORDS_PER_PAGE, split_volumes};
+use tokio_util::sync::CancellationToken;
 
 /// Handle to a temporary file that is deleted when dropped.
 ///
 /// Render operations that don't specify an output path return an [`Output::Temporary`]
 /// wrapping this type. Hold onto the [`Output`] value for as long as you need the PDF.
+/// Where temporary files are created is configured with [`TempConfig`].
 pub type TempFile = tempfile::NamedTempFile;
 
 /// An HTML-to-PDF renderer backed by a discovered Chrome/Chromium installation.
@@ -46,6 +72,15 @@ pub type TempFile = tempfile::NamedTempFile;
 pub struct Renderer {
     chrome: Chrome,
     styles: StyleConfig,
+    temp: TempConfig,
+    cancel: CancellationToken,
+    transforms: Vec<Box<dyn HtmlTransform>>,
+    volumes: Option<VolumeBudget>,
+    disk: DiskUsage,
+    #[cfg(feature = "metadata")]
+    work_skin: bool,
+    #[cfg(feature = "pdfa")]
+    pdfa: bool,
 }
 impl Renderer {
     /// Creates a new renderer with the given style configuration.
@@ -56,10 +91,126 @@ impl Renderer {
     pub fn new(styles: StyleConfig) -> Result<Self> {
         styles.try_into()
     }
+
+    /// Creates a new renderer, locating Chrome according to `chrome` rather
+    /// than relying solely on auto-discovery.
+    ///
+    /// Returns [`ErrorKind::ChromeNotFound`](error::ErrorKind::ChromeNotFound)
+    /// if a configured binary does not exist, or
+    /// [`ErrorKind::ChromeRemote`](error::ErrorKind::ChromeRemote) if a
+    /// configured remote endpoint is not a websocket URL.
+    pub fn with_chrome(styles: StyleConfig, chrome: ChromeConfig) -> Result<Self> {
+        let temp = TempConfig::default().prepare()?;
+        temp.clean()?;
+        Ok(Self {
+            chrome: Chrome::discover(&chrome)?,
+            styles,
+            temp,
+            cancel: CancellationToken::new(),
+            transforms: Vec::new(),
+            volumes: None,
+            disk: DiskUsage::default(),
+            #[cfg(feature = "metadata")]
+            work_skin: false,
+            #[cfg(feature = "pdfa")]
+            pdfa: false,
+        })
+    }
+
+    /// Keeps temporary files where (and as) `temp` configures, cleaning up
+    /// any abandoned temporary files there.
+    ///
+    /// Returns [`ErrorKind::Io`](error::ErrorKind::Io) if the directory
+    /// cannot be created or read.
+    pub fn with_temp(mut self, temp: TempConfig) -> Result<Self> {
+        let temp = temp.prepare()?;
+        temp.clean()?;
+        self.temp = temp;
+        Ok(self)
+    }
+
+    /// Stops rendering once `cancel` is cancelled: a render in progress is
+    /// killed (removing its partial PDF), and later renders fail straight
+    /// away, with [`ErrorKind::Cancelled`](error::ErrorKind::Cancelled).
+    ///
+    /// Renders through a [remote](ChromeConfig) browser can only be cancelled
+    /// before they start.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Rewrites every document with `transform` before it's rendered, after
+    /// any transforms registered before it.
+    ///
+    /// See [`transform`] for the built-in transforms; closures taking and
+    /// returning the whole document work too. Rendering fails with
+    /// [`ErrorKind::Transform`](error::ErrorKind::Transform) if a transform
+    /// does (or the document isn't UTF-8).
+    pub fn with_transform(mut self, transform: impl HtmlTransform + 'static) -> Self {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Splits works into volumes of at most `budget` each, at chapter
+    /// boundaries, when rendered with
+    /// [`render_work_volumes`](Self::render_work_volumes) (see
+    /// [`split_volumes`]).
+    ///
+    /// Each volume is rendered on its own, with its number added to the title
+    /// on its cover, so huge works don't exhaust Chrome's memory.
+    pub fn with_volumes(mut self, budget: impl Into<Option<VolumeBudget>>) -> Self {
+        self.volumes = budget.into();
+        self
+    }
+
+    /// Stops rendering before it fills the disk: once `budget`'s output
+    /// cap has been rendered, later renders fail with
+    /// [`ErrorKind::OutputLimit`](error::ErrorKind::OutputLimit), and renders
+    /// that would start with less than its minimum free space fail with
+    /// [`ErrorKind::InsufficientSpace`](error::ErrorKind::InsufficientSpace).
+    ///
+    /// Output is accounted for from when the budget is set; see
+    /// [`rendered_bytes`](Self::rendered_bytes).
+    pub fn with_disk_budget(mut self, budget: DiskBudget) -> Self {
+        self.disk = DiskUsage::new(budget);
+        self
+    }
+
+    /// How many bytes of PDFs this renderer has rendered, for reporting how
+    /// much of its [disk budget](Self::with_disk_budget) is spent.
+    pub fn rendered_bytes(&self) -> u64 {
+        self.disk.rendered()
+    }
+
+    /// Renders works (with [`render_work`](Self::render_work)) with their
+    /// [work skin](rawr_extract::models::Metadata::work_skin) sanitized (see
+    /// [`sanitize_work_skin`]), in place of the skin embedded in the download.
+    ///
+    /// Otherwise, documents are rendered with whatever stylesheets they
+    /// embed (which [`StripWorkSkin`](transform::StripWorkSkin) removes).
+    #[cfg(feature = "metadata")]
+    pub fn with_work_skin(mut self, enabled: bool) -> Self {
+        self.work_skin = enabled;
+        self
+    }
+
+    /// Post-processes every rendered PDF into a PDF/A-2b archival document,
+    /// with XMP metadata describing the work (for
+    /// [`render_work`](Self::render_work)) and an sRGB output intent.
+    ///
+    /// Rendering fails with [`ErrorKind::PdfA`](error::ErrorKind::PdfA) if a
+    /// PDF cannot be made to conform (for example, because it uses a font
+    /// Chrome didn't embed).
+    #[cfg(feature = "pdfa")]
+    pub fn with_pdfa(mut self, enabled: bool) -> Self {
+        self.pdfa = enabled;
+        self
+    }
 }
 impl TryFrom<StyleConfig> for Renderer {
     type Error = Error;
     fn try_from(styles: StyleConfig) -> std::result::Result<Self, Self::Error> {
-        Ok(Self { chrome: Chrome::discover()?, styles })
+        Self::with_chrome(styles, ChromeConfig::default())
     }
 }
@@ -0,0 +1,550 @@
+//! PDF/A-2b archival output.
+//!
+//! Chrome writes ordinary PDFs: they look right, but lack what PDF/A requires
+//! of a document that must still render identically decades from now. When
+//! enabled with [`Renderer::with_pdfa()`](crate::Renderer::with_pdfa), each
+//! rendered PDF is post-processed in place:
+//!
+//! - an XMP metadata packet declaring PDF/A-2b conformance (and describing the
+//!   work, for [`render_work`](crate::Renderer::render_work)) is embedded, with
+//!   the document information dictionary rewritten to match it,
+//! - an sRGB output intent (with an embedded ICC profile) is added, since
+//!   Chrome paints everything in device RGB,
+//! - annotations are marked as printable, and the file is given an ID.
+//!
+//! Some requirements can't be met after the fact. Chrome embeds (subsets of)
+//! every font it uses, but a font it could only reference by name, or any
+//! scripting, makes the document fail with
+//! [`ErrorKind::PdfA`](crate::error::ErrorKind::PdfA) rather than claim a
+//! conformance it doesn't have.
+
+use crate::error::{ErrorKind, Result};
+use exn::ResultExt;
+use lopdf::{Dictionary, Document, Object, Stream, StringFormat, decode_text_string, text_string};
+use rawr_extract::models::Metadata;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::Path;
+use time::UtcDateTime;
+
+/// Identifies the output condition of the embedded ICC profile.
+const OUTPUT_CONDITION: &str = "sRGB IEC61966-2.1";
+/// Actions that PDF/A forbids (ISO 19005-2, 6.6.1).
+const FORBIDDEN_ACTIONS: [&[u8]; 10] = [
+    b"Launch",
+    b"Sound",
+    b"Movie",
+    b"ResetForm",
+    b"ImportData",
+    b"JavaScript",
+    b"Hide",
+    b"SetOCGState",
+    b"Rendition",
+    b"GoTo3DView",
+];
+/// Annotation flags: invisible, hidden, print and no-view.
+const ANNOT_INVISIBLE: i64 = 1;
+const ANNOT_HIDDEN: i64 = 1 << 1;
+const ANNOT_PRINT: i64 = 1 << 2;
+const ANNOT_NO_VIEW: i64 = 1 << 5;
+
+/// Converts the PDF at `path` to PDF/A-2b, in place.
+///
+/// Returns [`ErrorKind::PdfA`] listing every requirement the PDF can't be
+/// made to meet, in which case the file is left untouched.
+pub(crate) fn convert(path: &Path, metadata: Option<&Metadata>) -> Result<()> {
+    let mut doc = Document::load(path).or_raise(|| ErrorKind::Io)?;
+    let violations = validate(&doc);
+    if !violations.is_empty() {
+        exn::bail!(ErrorKind::PdfA(violations.join("; ")));
+    }
+    let now = UtcDateTime::now();
+    let info = DocumentInfo::new(&doc, metadata, now);
+    fix_annotations(&mut doc);
+
+    let xmp = Stream::new(
+        Dictionary::from_iter([("Type", Object::Name(b"Metadata".to_vec())), ("Subtype", Object::Name(b"XML".to_vec()))]),
+        info.xmp(metadata).into_bytes(),
+    )
+    // Metadata must stay readable by tools that know nothing about PDF filters.
+    .with_compression(false);
+    let xmp = doc.add_object(xmp);
+    let profile = doc.add_object(Stream::new(Dictionary::from_iter([("N", Object::Integer(3))]), srgb_profile()));
+    let intent = Dictionary::from_iter([
+        ("Type", Object::Name(b"OutputIntent".to_vec())),
+        ("S", Object::Name(b"GTS_PDFA1".to_vec())),
+        ("OutputConditionIdentifier", text_string(OUTPUT_CONDITION)),
+        ("Info", text_string(OUTPUT_CONDITION)),
+        ("DestOutputProfile", Object::Reference(profile)),
+    ]);
+    let info_id = doc.add_object(info.dictionary());
+
+    let catalog = doc.catalog_mut().or_raise(|| ErrorKind::PdfA("document has no catalog".to_string()))?;
+    catalog.set("Metadata", Object::Reference(xmp));
+    catalog.set("OutputIntents", Object::Array(vec![Object::Dictionary(intent)]));
+    if let Some(lang) = metadata.and_then(|m| m.language.iso_code.as_deref()) {
+        catalog.set("Lang", text_string(lang));
+    }
+    doc.trailer.set("Info", Object::Reference(info_id));
+    if doc.trailer.get(b"ID").is_err() {
+        let id = Object::String(file_id(path, now), StringFormat::Hexadecimal);
+        doc.trailer.set("ID", Object::Array(vec![id.clone(), id]));
+    }
+    doc.save(path).or_raise(|| ErrorKind::Io)?;
+    tracing::debug!(path = %path.display(), "Converted PDF to PDF/A-2b");
+    Ok(())
+}
+
+/// Lists the reasons `doc` can't be made to conform to PDF/A-2b.
+fn validate(doc: &Document) -> Vec<String> {
+    let mut violations = Vec::new();
+    if doc.is_encrypted() {
+        violations.push("document is encrypted".to_string());
+    }
+    for dict in doc.objects.values().filter_map(|o| match o {
+        Object::Dictionary(d) => Some(d),
+        Object::Stream(s) => Some(&s.dict),
+        _ => None,
+    }) {
+        if has_name(dict, b"Type", b"Font") && !is_embedded(doc, dict) {
+            let name = dict.get(b"BaseFont").and_then(Object::as_name).unwrap_or(b"(unnamed)");
+            violations.push(format!("font {} is not embedded", String::from_utf8_lossy(name)));
+        }
+        if let Ok(action) = dict.get(b"S").and_then(Object::as_name)
+            && FORBIDDEN_ACTIONS.contains(&action)
+        {
+            violations.push(format!("document contains a {} action", String::from_utf8_lossy(action)));
+        }
+        if dict.has(b"AA") {
+            violations.push("document contains additional actions".to_string());
+        }
+        if dict.has(b"EmbeddedFiles") {
+            violations.push("document contains embedded files".to_string());
+        }
+    }
+    violations.sort();
+    violations.dedup();
+    violations
+}
+
+fn has_name(dict: &Dictionary, key: &[u8], name: &[u8]) -> bool {
+    dict.get(key).and_then(Object::as_name).is_ok_and(|n| n == name)
+}
+
+/// Whether a font's program is embedded in the document.
+///
+/// Composite (Type 0) fonts are embedded if their descendant is, which is a
+/// font object of its own (and checked as such); Type 3 fonts are defined by
+/// content streams within the document.
+fn is_embedded(doc: &Document, font: &Dictionary) -> bool {
+    if has_name(font, b"Subtype", b"Type0") || has_name(font, b"Subtype", b"Type3") {
+        return true;
+    }
+    let Ok(descriptor) = doc.get_dict_in_dict(font, b"FontDescriptor") else {
+        return false;
+    };
+    [b"FontFile".as_slice(), b"FontFile2", b"FontFile3"].iter().any(|key| descriptor.has(key))
+}
+
+/// PDF/A requires every annotation to print as displayed.
+fn fix_annotations(doc: &mut Document) {
+    for object in doc.objects.values_mut() {
+        let Object::Dictionary(dict) = object else {
+            continue;
+        };
+        if !has_name(dict, b"Type", b"Annot") || has_name(dict, b"Subtype", b"Popup") {
+            continue;
+        }
+        let flags = dict.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+        dict.set("F", (flags | ANNOT_PRINT) & !(ANNOT_INVISIBLE | ANNOT_HIDDEN | ANNOT_NO_VIEW));
+    }
+}
+
+/// A file ID derived from where and when the document was converted; it
+/// only has to distinguish this document from others.
+fn file_id(path: &Path, now: UtcDateTime) -> Vec<u8> {
+    let mut id = Vec::with_capacity(16);
+    for seed in 0u8..2 {
+        let mut hasher = DefaultHasher::new();
+        (seed, path, now.unix_timestamp_nanos()).hash(&mut hasher);
+        id.extend_from_slice(&hasher.finish().to_be_bytes());
+    }
+    id
+}
+
+/// Document information, which PDF/A requires to be identical in the
+/// information dictionary and the XMP packet.
+struct DocumentInfo {
+    title: Option<String>,
+    author: Option<String>,
+    subject: Option<String>,
+    keywords: Option<String>,
+    creator: Option<String>,
+    producer: Option<String>,
+    date: UtcDateTime,
+}
+impl DocumentInfo {
+    /// Describes the work, keeping whatever Chrome said about itself (and,
+    /// for documents that aren't works, the title).
+    fn new(doc: &Document, metadata: Option<&Metadata>, date: UtcDateTime) -> Self {
+        let existing = doc.trailer.get(b"Info").and_then(|o| doc.dereference(o)).and_then(|(_, o)| o.as_dict()).ok();
+        let text = |key: &[u8]| {
+            existing
+                .and_then(|d| d.get(key).ok())
+                .and_then(|o| decode_text_string(o).ok())
+                .filter(|s| !s.trim().is_empty())
+        };
+        let Some(m) = metadata else {
+            return Self {
+                title: text(b"Title"),
+                author: None,
+                subject: None,
+                keywords: None,
+                creator: text(b"Creator"),
+                producer: text(b"Producer"),
+                date,
+            };
+        };
+        let authors: Vec<_> = m.authors.iter().map(ToString::to_string).collect();
+        let keywords: Vec<_> =
+            m.fandoms.iter().map(|f| f.name.as_str()).chain(m.tags.iter().map(|t| t.name.as_str())).collect();
+        Self {
+            title: Some(m.title.clone()),
+            author: (!authors.is_empty()).then(|| authors.join(", ")),
+            subject: m.summary.clone().filter(|s| !s.trim().is_empty()),
+            keywords: (!keywords.is_empty()).then(|| keywords.join(", ")),
+            creator: text(b"Creator"),
+            producer: text(b"Producer"),
+            date,
+        }
+    }
+
+    fn dictionary(&self) -> Dictionary {
+        let mut dict = Dictionary::new();
+        for (key, value) in [
+            ("Title", &self.title),
+            ("Author", &self.author),
+            ("Subject", &self.subject),
+            ("Keywords", &self.keywords),
+            ("Creator", &self.creator),
+            ("Producer", &self.producer),
+        ] {
+            if let Some(value) = value {
+                dict.set(key, text_string(value));
+            }
+        }
+        let d = self.date;
+        let date = format!(
+            "D:{:04}{:02}{:02}{:02}{:02}{:02}Z",
+            d.year(),
+            u8::from(d.month()),
+            d.day(),
+            d.hour(),
+            d.minute(),
+            d.second()
+        );
+        dict.set("CreationDate", Object::string_literal(date.clone()));
+        dict.set("ModDate", Object::string_literal(date));
+        dict
+    }
+
+    fn xmp(&self, metadata: Option<&Metadata>) -> String {
+        let d = self.date;
+        let date = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            d.year(),
+            u8::from(d.month()),
+            d.day(),
+            d.hour(),
+            d.minute(),
+            d.second()
+        );
+        let mut properties = String::new();
+        let mut property = |name: &str, value: String| properties.push_str(&format!("<{name}>{value}</{name}>\n"));
+        property("pdfaid:part", "2".to_string());
+        property("pdfaid:conformance", "B".to_string());
+        property("dc:format", "application/pdf".to_string());
+        let alt = |s: &str| format!(r#"<rdf:Alt><rdf:li xml:lang="x-default">{}</rdf:li></rdf:Alt>"#, escape(s));
+        if let Some(title) = &self.title {
+            property("dc:title", alt(title));
+        }
+        if let Some(author) = &self.author {
+            property("dc:creator", format!("<rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq>", escape(author)));
+        }
+        if let Some(subject) = &self.subject {
+            property("dc:description", alt(subject));
+        }
+        if let Some(m) = metadata {
+            property("dc:identifier", format!("https://archiveofourown.org/works/{}", m.work_id));
+            if let Some(lang) = &m.language.iso_code {
+                property("dc:language", format!("<rdf:Bag><rdf:li>{}</rdf:li></rdf:Bag>", escape(lang)));
+            }
+        }
+        if let Some(keywords) = &self.keywords {
+            property("pdf:Keywords", escape(keywords));
+        }
+        if let Some(producer) = &self.producer {
+            property("pdf:Producer", escape(producer));
+        }
+        if let Some(creator) = &self.creator {
+            property("xmp:CreatorTool", escape(creator));
+        }
+        property("xmp:CreateDate", date.clone());
+        property("xmp:ModifyDate", date.clone());
+        property("xmp:MetadataDate", date);
+        format!(
+            concat!(
+                "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+                "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+                "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+                "<rdf:Description rdf:about=\"\"",
+                " xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\"",
+                " xmlns:dc=\"http://purl.org/dc/elements/1.1/\"",
+                " xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\"",
+                " xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">\n",
+                "{}",
+                "</rdf:Description>\n",
+                "</rdf:RDF>\n",
+                "</x:xmpmeta>\n",
+                "<?xpacket end=\"w\"?>"
+            ),
+            properties
+        )
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// An ICC (version 2) display profile for sRGB.
+///
+/// Built rather than bundled: it's nothing but the D50-adapted sRGB primaries
+/// and a sampled sRGB transfer curve.
+fn srgb_profile() -> Vec<u8> {
+    fn s15f16(v: f64) -> [u8; 4] {
+        ((v * 65536.0).round() as i32).to_be_bytes()
+    }
+    fn xyz(x: f64, y: f64, z: f64) -> Vec<u8> {
+        [b"XYZ \0\0\0\0".as_slice(), &s15f16(x), &s15f16(y), &s15f16(z)].concat()
+    }
+    fn text(s: &str) -> Vec<u8> {
+        [b"text\0\0\0\0".as_slice(), s.as_bytes(), b"\0"].concat()
+    }
+    fn desc(s: &str) -> Vec<u8> {
+        let mut data = b"desc\0\0\0\0".to_vec();
+        data.extend_from_slice(&(s.len() as u32 + 1).to_be_bytes());
+        data.extend_from_slice(s.as_bytes());
+        // Terminator, then empty Unicode and ScriptCode descriptions.
+        data.extend_from_slice(&[0; 1 + 4 + 4 + 2 + 1 + 67]);
+        data
+    }
+    const SAMPLES: u32 = 1024;
+    let mut curve = b"curv\0\0\0\0".to_vec();
+    curve.extend_from_slice(&SAMPLES.to_be_bytes());
+    for i in 0..SAMPLES {
+        let v = f64::from(i) / f64::from(SAMPLES - 1);
+        let linear = if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) };
+        curve.extend_from_slice(&((linear * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    let tags: [(&[u8; 4], Vec<u8>); 9] = [
+        (b"desc", desc(OUTPUT_CONDITION)),
+        (b"cprt", text("No copyright, use freely")),
+        (b"wtpt", xyz(0.9642, 1.0, 0.8249)),
+        (b"rXYZ", xyz(0.4361, 0.2225, 0.0139)),
+        (b"gXYZ", xyz(0.3851, 0.7169, 0.0971)),
+        (b"bXYZ", xyz(0.1431, 0.0606, 0.7141)),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+    ];
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let data_offset = 128 + 4 + 12 * tags.len();
+    for (signature, tag) in &tags {
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&((data_offset + data.len()) as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+        data.extend_from_slice(tag);
+        // Tags start on 4-byte boundaries.
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+
+    let size = (data_offset + data.len()) as u32;
+    let mut profile = Vec::with_capacity(size as usize);
+    profile.extend_from_slice(&size.to_be_bytes());
+    profile.extend_from_slice(&[0; 4]); // Preferred CMM
+    profile.extend_from_slice(&[2, 0x10, 0, 0]); // Version 2.1
+    profile.extend_from_slice(b"mntrRGB XYZ ");
+    for part in [2024u16, 1, 1, 0, 0, 0] {
+        profile.extend_from_slice(&part.to_be_bytes());
+    }
+    profile.extend_from_slice(b"acsp");
+    profile.extend_from_slice(&[0; 24]); // Platform, flags, manufacturer, model, attributes
+    profile.extend_from_slice(&[0; 4]); // Perceptual rendering intent
+    profile.extend_from_slice(&xyz(0.9642, 1.0, 0.8249)[8..]); // PCS illuminant (D50)
+    profile.resize(128, 0);
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::content::{Content, Operation};
+    use rawr_extract::models::{Author, Chapters, Language};
+    use time::{Date, Month};
+
+    /// A single page PDF with some text in `font`, like the ones Chrome writes.
+    fn pdf(font: impl FnOnce(&mut Document) -> Dictionary) -> Document {
+        let mut doc = Document::with_version("1.4");
+        let pages_id = doc.new_object_id();
+        let font = font(&mut doc);
+        let font_id = doc.add_object(font);
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                Operation::new("Tj", vec![Object::string_literal("Hello")]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let content_id = doc.add_object(Stream::new(Dictionary::new(), content.encode().unwrap()));
+        let annot_id = doc.add_object(Dictionary::from_iter([
+            ("Type", Object::Name(b"Annot".to_vec())),
+            ("Subtype", Object::Name(b"Link".to_vec())),
+            ("Rect", vec![0.into(), 0.into(), 10.into(), 10.into()].into()),
+        ]));
+        let page_id = doc.add_object(Dictionary::from_iter([
+            ("Type", Object::Name(b"Page".to_vec())),
+            ("Parent", pages_id.into()),
+            ("Contents", content_id.into()),
+            ("Annots", vec![annot_id.into()].into()),
+            ("MediaBox", vec![0.into(), 0.into(), 595.into(), 842.into()].into()),
+            (
+                "Resources",
+                Dictionary::from_iter([("Font", Object::from(Dictionary::from_iter([("F1", font_id.into())])))]).into(),
+            ),
+        ]));
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(Dictionary::from_iter([
+                ("Type", Object::Name(b"Pages".to_vec())),
+                ("Kids", vec![page_id.into()].into()),
+                ("Count", 1.into()),
+            ])),
+        );
+        let catalog_id = doc.add_object(Dictionary::from_iter([
+            ("Type", Object::Name(b"Catalog".to_vec())),
+            ("Pages", pages_id.into()),
+        ]));
+        let info_id = doc.add_object(Dictionary::from_iter([
+            ("Creator", text_string("Chromium")),
+            ("Producer", text_string("Skia/PDF")),
+            ("Title", text_string("Untitled")),
+        ]));
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+        doc
+    }
+
+    fn embedded_font(doc: &mut Document) -> Dictionary {
+        let program = doc.add_object(Stream::new(Dictionary::new(), b"not really a font".to_vec()));
+        let descriptor = doc.add_object(Dictionary::from_iter([
+            ("Type", Object::Name(b"FontDescriptor".to_vec())),
+            ("FontName", Object::Name(b"AAAAAA+Serif".to_vec())),
+            ("FontFile2", program.into()),
+        ]));
+        Dictionary::from_iter([
+            ("Type", Object::Name(b"Font".to_vec())),
+            ("Subtype", Object::Name(b"TrueType".to_vec())),
+            ("BaseFont", Object::Name(b"AAAAAA+Serif".to_vec())),
+            ("FontDescriptor", descriptor.into()),
+        ])
+    }
+
+    fn metadata() -> Metadata {
+        Metadata {
+            work_id: 3161,
+            title: "Kept <Forever> & Ever".to_string(),
+            authors: vec![Author::new("writer", None::<&str>), Author::new("user", Some("pseud"))],
+            fandoms: vec![],
+            series: vec![],
+            chapters: Chapters { written: 1, total: Some(1) },
+            words: 1000,
+            rating: None,
+            warnings: vec![],
+            tags: vec![],
+            summary: Some("Über alles.".to_string()),
+            language: Language {
+                name: "English".to_string(),
+                iso_code: Some("en".to_string()),
+            },
+            published: Date::from_calendar_date(2024, Month::January, 1).unwrap(),
+            last_modified: Date::from_calendar_date(2024, Month::January, 1).unwrap(),
+            provenance: Default::default(),
+            work_skin: None,
+        }
+    }
+
+    #[test]
+    fn test_convert() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("work.pdf");
+        pdf(embedded_font).save(&path).unwrap();
+        convert(&path, Some(&metadata())).unwrap();
+
+        let doc = Document::load(&path).unwrap();
+        let catalog = doc.catalog().unwrap();
+        assert_eq!(b"en", catalog.get(b"Lang").unwrap().as_str().unwrap());
+        let intents = catalog.get(b"OutputIntents").unwrap().as_array().unwrap();
+        let intent = intents[0].as_dict().unwrap();
+        assert!(has_name(intent, b"S", b"GTS_PDFA1"));
+        let profile = doc.get_object(intent.get(b"DestOutputProfile").unwrap().as_reference().unwrap()).unwrap();
+        let profile = &profile.as_stream().unwrap().content;
+        assert_eq!(profile.len(), u32::from_be_bytes(profile[..4].try_into().unwrap()) as usize);
+        assert_eq!(b"acsp", &profile[36..40]);
+
+        let xmp = doc.get_object(catalog.get(b"Metadata").unwrap().as_reference().unwrap()).unwrap();
+        let xmp = String::from_utf8(xmp.as_stream().unwrap().content.clone()).unwrap();
+        assert!(xmp.contains("<pdfaid:part>2</pdfaid:part>"), "{xmp}");
+        assert!(xmp.contains("<pdfaid:conformance>B</pdfaid:conformance>"), "{xmp}");
+        assert!(xmp.contains("Kept &lt;Forever&gt; &amp; Ever"), "{xmp}");
+        assert!(xmp.contains("<rdf:li>writer, pseud (user)</rdf:li>"), "{xmp}");
+        assert!(xmp.contains("<xmp:CreatorTool>Chromium</xmp:CreatorTool>"), "{xmp}");
+
+        let info = doc.get_dict_in_dict(&doc.trailer, b"Info").unwrap();
+        assert_eq!("Kept <Forever> & Ever", decode_text_string(info.get(b"Title").unwrap()).unwrap());
+        assert_eq!("Über alles.", decode_text_string(info.get(b"Subject").unwrap()).unwrap());
+        assert_eq!(2, doc.trailer.get(b"ID").unwrap().as_array().unwrap().len());
+        let annotations = doc.get_page_annotations(doc.page_iter().next().unwrap()).unwrap();
+        assert_eq!(ANNOT_PRINT, annotations[0].get(b"F").unwrap().as_i64().unwrap());
+    }
+
+    #[test]
+    fn test_violations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("document.pdf");
+        let mut doc = pdf(|_| {
+            Dictionary::from_iter([
+                ("Type", Object::Name(b"Font".to_vec())),
+                ("Subtype", Object::Name(b"Type1".to_vec())),
+                ("BaseFont", Object::Name(b"Helvetica".to_vec())),
+            ])
+        });
+        doc.add_object(Dictionary::from_iter([
+            ("S", Object::Name(b"JavaScript".to_vec())),
+            ("JS", Object::string_literal("app.alert('hi')")),
+        ]));
+        doc.save(&path).unwrap();
+        let before = std::fs::read(&path).unwrap();
+        let error = convert(&path, None).unwrap_err();
+        assert!(matches!(&*error, ErrorKind::PdfA(_)));
+        assert_eq!(
+            "cannot produce PDF/A-2b: document contains a JavaScript action; font Helvetica is not embedded",
+            error.to_string()
+        );
+        assert_eq!(before, std::fs::read(&path).unwrap());
+    }
+}
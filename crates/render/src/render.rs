@@ -1,7 +1,14 @@
 use crate::error::{ErrorKind, Result};
+use crate::style::write_style;
+#[cfg(feature = "metadata")]
+use crate::transform::{HtmlTransform, StripWorkSkin, insert_head_style};
 use crate::{Renderer, TempFile, style::CssVariables};
+#[cfg(feature = "metadata")]
+use crate::{Volume, sanitize_work_skin};
 use exn::ResultExt;
-use std::io::{Cursor, Read, Write};
+#[cfg(feature = "metadata")]
+use rawr_extract::models::Metadata;
+use std::io::{Chain, Cursor, Read, Write};
 use std::path::{Path, PathBuf};
 use tracing::instrument;
 
@@ -35,7 +42,7 @@ impl Renderer {
     /// closing `</head>` tag. The returned [`Output::Temporary`] is deleted when
     /// dropped — hold the value for as long as you need the PDF.
     pub fn render<R: Read>(&self, html: R, variables: impl Into<Option<CssVariables>>) -> Result<Output> {
-        let output = TempFile::new().or_raise(|| ErrorKind::Io)?;
+        let output = self.temp.output()?;
         _ = self.render_to(html, variables, output.path().to_path_buf())?;
         Ok(Output::Temporary(output))
     }
@@ -52,8 +59,14 @@ impl Renderer {
         save_to: impl Into<PathBuf>,
     ) -> Result<Output> {
         let save_to = save_to.into();
-        let input = self.persist_html(html, variables.into())?;
-        self.chrome.execute(input.path(), &save_to)?;
+        self.check_disk(&save_to)?;
+        let input = self.persist_html(html, variables.into(), None, None)?;
+        self.chrome.execute(input.path(), &save_to, &self.cancel)?;
+        #[cfg(feature = "pdfa")]
+        if self.pdfa {
+            crate::pdfa::convert(&save_to, None)?;
+        }
+        self.disk.record(&save_to)?;
         Ok(Output::Persisted(save_to))
     }
 
@@ -72,49 +85,190 @@ impl Renderer {
         self.render_to(Cursor::new(html), variables, save_to)
     }
 
-    // Lol, apparently this is called a "ring-buffer algorithm". I call it a "overlapping search".
-    fn persist_html<R: Read>(&self, mut html: R, variables: Option<CssVariables>) -> Result<TempFile> {
-        let mut tmp = TempFile::new().or_raise(|| ErrorKind::Io)?;
-        const NEEDLE: &[u8] = b"</head";
-        const CARRY_SIZE: usize = NEEDLE.len() - 1;
-        // We want to load 2 pages into memory each time.
-        const BUFFER_CAPACITY: usize = 8192;
-        const BUFFER_WINDOW: usize = BUFFER_CAPACITY - CARRY_SIZE;
-        // All because I REFUSE to read the entire file into memory... tsk tsk.
-        // Assuming NEEDLE=`123` + BUFFER_CAPACITY=12 (CARRY_SIZE=2, BUFFER_WINDOW=10):
-        //
-        // First loop:   BBBBBBBBbb...................123.......... carry=0, buf[0..10], bytes=10, filled=10, safe=8,  (consumed  0..10)
-        //               \--buffer--/
-        // Second loop:  rrrrrrrrCCBBBBBBBBbb.........123.......... carry=2, buf[2..12], bytes=10, filled=12, safe=10, (consumed 10..20)
-        //                       \--buffer--/
-        // Third loop:   rrrrrrrrrrrrrrrrrrCCBBBBBBBBbb23.......... carry=2, buf[2..12], bytes=10, filled=12, safe=10, (consumed 20..30)
-        //                                 \--buffer--/
-        // Fourth loop:  rrrrrrrrrrrrrrrrrrrrrrrrrrrrC123---------- carry=2, buf[2..12], bytes=10, filled=12, write_all + io::copy.
-        //                                           \--buffer--/
-        let mut buffer = vec![0; BUFFER_WINDOW + CARRY_SIZE];
-        let mut carry: usize = 0;
-        'chunk: loop {
-            let bytes = html.read(&mut buffer[carry..carry + BUFFER_WINDOW]).or_raise(|| ErrorKind::Io)?;
-            if bytes == 0 {
-                tmp.write_all(&buffer[..carry]).or_raise(|| ErrorKind::Io)?;
-                break 'chunk;
-            }
-            // Well, kinda. We've consumed $bytes. But we overlap each time by CARRY_SIZE.
-            let filled = carry + bytes;
-            if let Some(pos) = buffer[..filled].windows(NEEDLE.len()).position(|w| w.eq_ignore_ascii_case(NEEDLE)) {
-                tmp.write_all(&buffer[..pos]).or_raise(|| ErrorKind::Io)?;
-                let blocks = self.inject_css(&mut tmp, variables)?;
-                tmp.write_all(&buffer[pos..filled]).or_raise(|| ErrorKind::Io)?;
-                tracing::debug!(position = pos, blocks = blocks, "Custom CSS stylesheets injected into HTML");
-                std::io::copy(&mut html, &mut tmp).or_raise(|| ErrorKind::Io)?;
-                return Ok(tmp);
-            }
-            let safe = filled.saturating_sub(CARRY_SIZE);
-            tmp.write_all(&buffer[..safe]).or_raise(|| ErrorKind::Io)?;
-            buffer.copy_within(safe..filled, 0);
-            carry = filled - safe;
+    /// Renders a work's HTML to a PDF stored in a temporary file.
+    ///
+    /// Like [`render()`](Self::render), but derives [`CssVariables`] from the
+    /// work's `metadata` and, if the [`StyleConfig`](crate::StyleConfig) has a
+    /// [cover](crate::StyleConfig::with_cover), inserts the generated title
+    /// page immediately after the opening `<body>` tag.
+    #[cfg(feature = "metadata")]
+    pub fn render_work<R: Read>(&self, html: R, metadata: &Metadata) -> Result<Output> {
+        let output = self.temp.output()?;
+        _ = self.render_work_to(html, metadata, output.path().to_path_buf())?;
+        Ok(Output::Temporary(output))
+    }
+
+    /// Renders a work's HTML to a PDF at the specified path.
+    ///
+    /// Like [`render_work()`](Self::render_work), but writes the PDF to
+    /// `save_to` instead of a temporary file.
+    #[cfg(feature = "metadata")]
+    #[instrument(skip_all, fields(work_id = metadata.work_id))]
+    pub fn render_work_to<R: Read>(&self, html: R, metadata: &Metadata, save_to: impl Into<PathBuf>) -> Result<Output> {
+        let save_to = save_to.into();
+        self.check_disk(&save_to)?;
+        let cover = self.styles.cover.as_ref().map(|c| c.render(metadata)).transpose()?;
+        let input = match (self.work_skin, &self.styles.headers) {
+            (false, None) => self.persist_html(html, Some(metadata.into()), cover, None)?,
+            (work_skin, headers) => {
+                let mut html = read_document(html)?;
+                if work_skin {
+                    html = sanitized_skin(html, metadata)?;
+                }
+                let (html, margins) = match headers {
+                    Some(headers) => headers.apply(html, metadata).map(|(html, css)| (html, Some(css)))?,
+                    None => (html, None),
+                };
+                self.persist_html(Cursor::new(html), Some(metadata.into()), cover, margins)?
+            },
+        };
+        self.chrome.execute(input.path(), &save_to, &self.cancel)?;
+        #[cfg(feature = "pdfa")]
+        if self.pdfa {
+            crate::pdfa::convert(&save_to, Some(metadata))?;
+        }
+        self.disk.record(&save_to)?;
+        Ok(Output::Persisted(save_to))
+    }
+
+    /// Renders a work's HTML to one PDF per volume, stored in temporary files.
+    ///
+    /// Like [`render_work()`](Self::render_work), but first splits the work
+    /// into volumes as [configured](Self::with_volumes); each volume's cover
+    /// titles it as such. Works that don't need splitting (or renderers
+    /// without a budget) give a single PDF.
+    #[cfg(feature = "metadata")]
+    pub fn render_work_volumes<R: Read>(&self, html: R, metadata: &Metadata) -> Result<Vec<Output>> {
+        let volumes = self.split_work(html)?;
+        let count = volumes.len();
+        let mut outputs = Vec::with_capacity(count);
+        for (i, volume) in volumes.into_iter().enumerate() {
+            let output = self.temp.output()?;
+            let metadata = volume_metadata(metadata, i, count);
+            _ = self.render_work_to(Cursor::new(volume), &metadata, output.path().to_path_buf())?;
+            outputs.push(Output::Temporary(output));
         }
-        tracing::warn!("Custom CSS stylesheets not injected; closing head tag not found");
+        Ok(outputs)
+    }
+
+    /// Renders a work's HTML to one PDF per volume, at the paths `save_to`
+    /// gives for each.
+    ///
+    /// Like [`render_work_volumes()`](Self::render_work_volumes), but writes
+    /// each volume to `save_to(volume)` instead of a temporary file.
+    #[cfg(feature = "metadata")]
+    pub fn render_work_volumes_to<R: Read>(
+        &self,
+        html: R,
+        metadata: &Metadata,
+        save_to: impl Fn(Volume) -> PathBuf,
+    ) -> Result<Vec<Output>> {
+        let volumes = self.split_work(html)?;
+        let count = volumes.len();
+        volumes
+            .into_iter()
+            .enumerate()
+            .map(|(i, volume)| {
+                let save_to = save_to(Volume {
+                    number: i as u32 + 1,
+                    count: count as u32,
+                });
+                self.render_work_to(Cursor::new(volume), &volume_metadata(metadata, i, count), save_to)
+            })
+            .collect()
+    }
+
+    /// Reads the whole work and splits it into volumes, if configured to.
+    #[cfg(feature = "metadata")]
+    fn split_work<R: Read>(&self, mut html: R) -> Result<Vec<String>> {
+        let mut buf = Vec::new();
+        html.read_to_end(&mut buf).or_raise(|| ErrorKind::Io)?;
+        let html =
+            String::from_utf8(buf).or_raise(|| ErrorKind::Transform("document is not valid UTF-8".to_string()))?;
+        Ok(match self.volumes {
+            Some(budget) => crate::split_volumes(html, budget),
+            None => vec![html],
+        })
+    }
+
+    /// Fails if rendering to `save_to` would go over the disk budget, checking
+    /// for free space where both the PDF and the HTML it's rendered from go.
+    fn check_disk(&self, save_to: &Path) -> Result<()> {
+        let output = save_to.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let temp = self.temp.dir();
+        self.disk.check([output, temp.as_path()])
+    }
+
+    /// Writes the document to be printed: `html` (transformed), with styles
+    /// injected into its head (`margins` after the configured stylesheets,
+    /// so that its page margin boxes take precedence) and `cover` at the
+    /// start of its body.
+    fn persist_html<R: Read>(
+        &self,
+        html: R,
+        variables: Option<CssVariables>,
+        cover: Option<String>,
+        margins: Option<String>,
+    ) -> Result<TempFile> {
+        if self.transforms.is_empty() {
+            return self.write_html(html, variables, cover, margins);
+        }
+        let html = self.transform_html(html)?;
+        self.write_html(Cursor::new(html), variables, cover, margins)
+    }
+
+    /// Reads the whole document and runs it through every transform.
+    fn transform_html<R: Read>(&self, mut html: R) -> Result<String> {
+        let mut buf = Vec::new();
+        html.read_to_end(&mut buf).or_raise(|| ErrorKind::Io)?;
+        let mut html =
+            String::from_utf8(buf).or_raise(|| ErrorKind::Transform("document is not valid UTF-8".to_string()))?;
+        for transform in &self.transforms {
+            let name = transform.name();
+            html = transform.transform(html).or_raise(|| ErrorKind::Transform(name.to_string()))?;
+            tracing::debug!(transform = name, "HTML transformed");
+        }
+        Ok(html)
+    }
+
+    fn write_html<R: Read>(
+        &self,
+        html: R,
+        variables: Option<CssVariables>,
+        cover: Option<String>,
+        margins: Option<String>,
+    ) -> Result<TempFile> {
+        let mut tmp = self.temp.input()?;
+        let Some(mut rest) = copy_until(html, &mut tmp, b"</head")? else {
+            tracing::warn!("Custom CSS stylesheets not injected; closing head tag not found");
+            return Ok(tmp);
+        };
+        let mut blocks = self.inject_css(&mut tmp, variables)?;
+        if let Some(margins) = margins {
+            write_style(&mut tmp, margins.as_bytes()).or_raise(|| ErrorKind::Io)?;
+            blocks += 1;
+        }
+        tracing::debug!(blocks = blocks, "Custom CSS stylesheets injected into HTML");
+        let Some(cover) = cover else {
+            std::io::copy(&mut rest, &mut tmp).or_raise(|| ErrorKind::Io)?;
+            return Ok(tmp);
+        };
+        // The cover goes *inside* the body, so skip past the attributes of
+        // the opening tag too.
+        let Some(rest) = copy_until(rest, &mut tmp, b"<body")? else {
+            tracing::warn!("Cover page not injected; opening body tag not found");
+            return Ok(tmp);
+        };
+        let Some(mut rest) = copy_until(rest, &mut tmp, b">")? else {
+            tracing::warn!("Cover page not injected; opening body tag not closed");
+            return Ok(tmp);
+        };
+        let mut gt = [0u8; 1];
+        rest.read_exact(&mut gt).or_raise(|| ErrorKind::Io)?;
+        tmp.write_all(&gt).or_raise(|| ErrorKind::Io)?;
+        tmp.write_all(cover.as_bytes()).or_raise(|| ErrorKind::Io)?;
+        tracing::debug!("Cover page injected into HTML");
+        std::io::copy(&mut rest, &mut tmp).or_raise(|| ErrorKind::Io)?;
         Ok(tmp)
     }
 
@@ -127,3 +281,109 @@ impl Renderer {
         Ok(blocks)
     }
 }
+
+/// Reads a whole (UTF-8) document.
+#[cfg(feature = "metadata")]
+fn read_document<R: Read>(mut html: R) -> Result<String> {
+    let mut buf = Vec::new();
+    html.read_to_end(&mut buf).or_raise(|| ErrorKind::Io)?;
+    String::from_utf8(buf).or_raise(|| ErrorKind::Transform("document is not valid UTF-8".to_string()))
+}
+
+/// Replaces the work skin embedded in a work's HTML with its sanitized
+/// version from `metadata`.
+#[cfg(feature = "metadata")]
+fn sanitized_skin(html: String, metadata: &Metadata) -> Result<String> {
+    let html = StripWorkSkin.transform(html)?;
+    let skin = metadata.work_skin.as_deref().map(sanitize_work_skin).filter(|css| !css.is_empty());
+    Ok(match skin {
+        Some(css) => insert_head_style(html, &css),
+        None => html,
+    })
+}
+
+/// The metadata of the `index`th of `count` volumes of a work: its title
+/// numbers the volume, when there's more than one.
+#[cfg(feature = "metadata")]
+fn volume_metadata(metadata: &Metadata, index: usize, count: usize) -> Metadata {
+    let mut metadata = metadata.clone();
+    if count > 1 {
+        let volume = Volume {
+            number: index as u32 + 1,
+            count: count as u32,
+        };
+        metadata.title = format!("{} ({volume})", metadata.title);
+    }
+    metadata
+}
+
+/// Streams `html` into `w` up to (but not including) the first
+/// case-insensitive occurrence of `needle`.
+///
+/// Returns a reader positioned at the start of the needle, or `None` if the
+/// needle was never found (in which case everything has been written).
+// Lol, apparently this is called a "ring-buffer algorithm". I call it a "overlapping search".
+fn copy_until<R: Read>(mut html: R, w: &mut impl Write, needle: &[u8]) -> Result<Option<Chain<Cursor<Vec<u8>>, R>>> {
+    let carry_size: usize = needle.len() - 1;
+    // We want to load 2 pages into memory each time.
+    const BUFFER_CAPACITY: usize = 8192;
+    let buffer_window: usize = BUFFER_CAPACITY - carry_size;
+    // All because I REFUSE to read the entire file into memory... tsk tsk.
+    // Assuming NEEDLE=`123` + BUFFER_CAPACITY=12 (CARRY_SIZE=2, BUFFER_WINDOW=10):
+    //
+    // First loop:   BBBBBBBBbb...................123.......... carry=0, buf[0..10], bytes=10, filled=10, safe=8,  (consumed  0..10)
+    //               \--buffer--/
+    // Second loop:  rrrrrrrrCCBBBBBBBBbb.........123.......... carry=2, buf[2..12], bytes=10, filled=12, safe=10, (consumed 10..20)
+    //                       \--buffer--/
+    // Third loop:   rrrrrrrrrrrrrrrrrrCCBBBBBBBBbb23.......... carry=2, buf[2..12], bytes=10, filled=12, safe=10, (consumed 20..30)
+    //                                 \--buffer--/
+    // Fourth loop:  rrrrrrrrrrrrrrrrrrrrrrrrrrrrC123---------- carry=2, buf[2..12], bytes=10, filled=12, write_all + chain.
+    //                                           \--buffer--/
+    let mut buffer = vec![0; buffer_window + carry_size];
+    let mut carry: usize = 0;
+    'chunk: loop {
+        let bytes = html.read(&mut buffer[carry..carry + buffer_window]).or_raise(|| ErrorKind::Io)?;
+        if bytes == 0 {
+            w.write_all(&buffer[..carry]).or_raise(|| ErrorKind::Io)?;
+            break 'chunk;
+        }
+        // Well, kinda. We've consumed $bytes. But we overlap each time by CARRY_SIZE.
+        let filled = carry + bytes;
+        if let Some(pos) = buffer[..filled].windows(needle.len()).position(|w| w.eq_ignore_ascii_case(needle)) {
+            w.write_all(&buffer[..pos]).or_raise(|| ErrorKind::Io)?;
+            buffer.truncate(filled);
+            buffer.drain(..pos);
+            return Ok(Some(Cursor::new(buffer).chain(html)));
+        }
+        let safe = filled.saturating_sub(carry_size);
+        w.write_all(&buffer[..safe]).or_raise(|| ErrorKind::Io)?;
+        buffer.copy_within(safe..filled, 0);
+        carry = filled - safe;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_until_across_chunks() {
+        // Straddle the needle over the first buffer boundary.
+        let mut html = vec![b'.'; 8190];
+        html.extend_from_slice(b"</HEAD><body class=\"x\">text");
+        let mut out = Vec::new();
+        let mut rest = copy_until(Cursor::new(&html), &mut out, b"</head").unwrap().unwrap();
+        assert_eq!(out.len(), 8190);
+        let mut remaining = String::new();
+        rest.read_to_string(&mut remaining).unwrap();
+        assert_eq!(remaining, "</HEAD><body class=\"x\">text");
+    }
+
+    #[test]
+    fn test_copy_until_not_found() {
+        let mut out = Vec::new();
+        assert!(copy_until(Cursor::new(b"<p>no head</p>"), &mut out, b"</head").unwrap().is_none());
+        assert_eq!(out, b"<p>no head</p>");
+    }
+}
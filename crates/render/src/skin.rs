@@ -0,0 +1,198 @@
+//! Sanitizing work skins for rendering.
+//!
+//! Work skins are stylesheets authors attach to their works, and a download
+//! embeds them as they were written. The Archive only accepts rules scoped to
+//! `#workskin` and a limited set of properties, but a download could have
+//! been edited since. [`sanitize_work_skin`] keeps the skin confined to the
+//! work (see [`Renderer::with_work_skin`](crate::Renderer::with_work_skin)).
+
+/// Values that would make Chrome fetch or run something while rendering.
+const FORBIDDEN_VALUES: [&str; 5] = ["url(", "image-set(", "expression(", "javascript:", "@import"];
+/// Properties that reach outside the element they style.
+const FORBIDDEN_PROPERTIES: [&str; 2] = ["behavior", "-moz-binding"];
+
+/// Reduces a work skin to the rules that only style the work itself.
+///
+/// Kept are rules whose every selector is scoped to `#workskin`, within
+/// `@media` and `@supports` blocks or not, and only those of their
+/// declarations that don't load anything (no `url()` or `@import`) or fix
+/// elements to every printed page. Every other at-rule (`@font-face`,
+/// `@import`, `@page`, and so on) is dropped, as are comments.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_render::sanitize_work_skin;
+///
+/// let css = "body { color: red; } #workskin .texting { font-family: monospace; background: url(https://example.com/x.png); }";
+/// assert_eq!("#workskin .texting { font-family: monospace; }", sanitize_work_skin(css));
+/// ```
+pub fn sanitize_work_skin(css: &str) -> String {
+    // Style elements end at the first `</style`, whatever the CSS says.
+    sanitize_rules(&strip_comments(css)).join("\n").replace('<', "\\3C ")
+}
+
+fn sanitize_rules(css: &str) -> Vec<String> {
+    let mut rules = Vec::new();
+    let mut rest = css;
+    while let Some(end) = rest.find(['{', ';']) {
+        let prelude = rest[..end].trim();
+        if rest.as_bytes()[end] == b';' {
+            // A statement at-rule (such as `@import`), or stray text.
+            rest = &rest[end + 1..];
+            continue;
+        }
+        let Some(close) = matching_brace(rest, end) else {
+            break;
+        };
+        let body = &rest[end + 1..close];
+        rest = &rest[close + 1..];
+
+        let lower = prelude.to_ascii_lowercase();
+        if lower.starts_with("@media") || lower.starts_with("@supports") {
+            let inner = sanitize_rules(body);
+            if !inner.is_empty() {
+                rules.push(format!("{prelude} {{ {} }}", inner.join(" ")));
+            }
+            continue;
+        }
+        let scoped = prelude.split(',').all(|selector| {
+            let selector = selector.trim_start();
+            selector.starts_with("#workskin")
+                && !selector["#workskin".len()..].starts_with(|c: char| c.is_alphanumeric() || c == '-' || c == '_')
+        });
+        if prelude.starts_with('@') || !scoped {
+            continue;
+        }
+        let declarations: Vec<_> = body.split(';').map(str::trim).filter(|d| is_allowed(d)).collect();
+        if !declarations.is_empty() {
+            let selectors: Vec<_> = prelude.split(',').map(str::trim).collect();
+            rules.push(format!("{} {{ {}; }}", selectors.join(", "), declarations.join("; ")));
+        }
+    }
+    rules
+}
+
+/// Whether a declaration (`property: value`) may stay.
+fn is_allowed(declaration: &str) -> bool {
+    // Escapes are decoded first, so that `\75 rl(` is recognised as `url(`.
+    let declaration = unescape(declaration);
+    let Some((property, value)) = declaration.split_once(':') else {
+        return false;
+    };
+    let property = property.trim().to_ascii_lowercase();
+    let value: String = value.to_ascii_lowercase().split_whitespace().collect();
+    let forbidden = property.is_empty()
+        || FORBIDDEN_PROPERTIES.contains(&property.as_str())
+        || FORBIDDEN_VALUES.iter().any(|forbidden| value.contains(forbidden))
+        || (property == "position" && value.starts_with("fixed"));
+    !forbidden
+}
+
+/// Decodes CSS escapes: `\` and up to six hex digits (and a space ending
+/// them) for that code point, or `\` and any other character for itself.
+fn unescape(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut chars = css.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let mut hex = String::new();
+        while hex.len() < 6
+            && let Some(digit) = chars.next_if(char::is_ascii_hexdigit)
+        {
+            hex.push(digit);
+        }
+        if hex.is_empty() {
+            // An escaped newline is a line continuation.
+            if let Some(c) = chars.next().filter(|&c| c != '\n') {
+                out.push(c);
+            }
+            continue;
+        }
+        chars.next_if(char::is_ascii_whitespace);
+        let decoded = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).filter(|&c| c != '\0');
+        out.push(decoded.unwrap_or(char::REPLACEMENT_CHARACTER));
+    }
+    out
+}
+
+/// The offset of the `}` closing the `{` at `open`.
+fn matching_brace(css: &str, open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, c) in css[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            },
+            _ => (),
+        }
+    }
+    None
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = rest[start + 2..].find("*/").map_or("", |end| &rest[start + 2 + end + 2..]);
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::unscoped("body { color: red; }", "")]
+    #[case::partly_scoped("#workskin p, p { color: red; }", "")]
+    #[case::lookalike("#workskinny p { color: red; }", "")]
+    #[case::scoped(
+        "#workskin p,#workskin .note { color: red }",
+        "#workskin p, #workskin .note { color: red; }"
+    )]
+    #[case::url(
+        "#workskin p { background: URL( 'x.png' ); color: red; }",
+        "#workskin p { color: red; }"
+    )]
+    #[case::escaped_url(
+        "#workskin p { background: \\75 rl(x.png); border-image: u\\rl(x.png); color: red; }",
+        "#workskin p { color: red; }"
+    )]
+    #[case::escaped_property("#workskin p { pos\\ition: fixed; top: 0; }", "#workskin p { top: 0; }")]
+    #[case::escaped_content(
+        "#workskin p::before { content: '\\2014 '; }",
+        "#workskin p::before { content: '\\2014 '; }"
+    )]
+    #[case::fixed("#workskin .header { position: fixed; top: 0; }", "#workskin .header { top: 0; }")]
+    #[case::import(
+        "@import url(https://example.com/skin.css); #workskin p { color: red; }",
+        "#workskin p { color: red; }"
+    )]
+    #[case::font_face(
+        "@font-face { font-family: x; src: url(x.woff); } #workskin p { font-family: x; }",
+        "#workskin p { font-family: x; }"
+    )]
+    #[case::media(
+        "@media (min-width: 42em) { #workskin p { margin: 0; } body { margin: 0; } }",
+        "@media (min-width: 42em) { #workskin p { margin: 0; } }"
+    )]
+    #[case::comments("/* #workskin */ #workskin p { /* } */ color: red; }", "#workskin p { color: red; }")]
+    #[case::closing_tag(
+        "#workskin p::after { content: '</style><script>'; }",
+        "#workskin p::after { content: '\\3C /style>\\3C script>'; }"
+    )]
+    fn test_sanitize_work_skin(#[case] css: &str, #[case] expected: &str) {
+        assert_eq!(expected, sanitize_work_skin(css));
+    }
+}
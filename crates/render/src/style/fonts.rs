@@ -0,0 +1,153 @@
+//! Font files embedded into rendered documents.
+//!
+//! Documents otherwise render with whatever fonts the machine running Chrome
+//! happens to have installed, so the same stylesheet produces different PDFs
+//! on different machines. Registered fonts are read at construction time and
+//! injected as `@font-face` rules with `data:` URIs, so they reach Chrome
+//! without any filesystem access (which also works for a remote Chrome).
+
+use crate::error::{ErrorKind, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use exn::ResultExt;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::Path;
+
+/// Extensions (lowercase) of the font files picked up from directories.
+const EXTENSIONS: &[&str] = &["woff2", "woff", "ttf", "otf"];
+
+/// A font file, as an `@font-face` rule.
+pub(crate) struct Font {
+    family: String,
+    weight: u16,
+    italic: bool,
+    format: &'static str,
+    data: Vec<u8>,
+}
+impl Font {
+    /// Reads a font file, guessing its weight and style from its name (such
+    /// as `Literata-SemiBoldItalic.ttf`).
+    pub(crate) fn read(family: impl Into<String>, path: &Path) -> Result<Self> {
+        if !path.exists() {
+            exn::bail!(ErrorKind::AssetNotFound(path.display().to_string()));
+        }
+        let data = std::fs::read(path).or_raise(|| ErrorKind::Io)?;
+        let Some(format) = format(&data) else {
+            exn::bail!(ErrorKind::InvalidFont(path.display().to_string()));
+        };
+        let (weight, italic) = path.file_stem().and_then(|s| s.to_str()).map(variant).unwrap_or((400, false));
+        Ok(Self {
+            family: family.into(),
+            weight,
+            italic,
+            format,
+            data,
+        })
+    }
+
+    /// Reads every font file in a directory (not recursively), taking the
+    /// family name from the file name before the first hyphen:
+    /// `Literata-Italic.woff2` is the italic of the `Literata` family.
+    pub(crate) fn read_dir(dir: &Path) -> Result<Vec<Self>> {
+        if !dir.is_dir() {
+            exn::bail!(ErrorKind::AssetNotFound(dir.display().to_string()));
+        }
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(dir).or_raise(|| ErrorKind::Io)? {
+            let path = entry.or_raise(|| ErrorKind::Io)?.path();
+            let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+            if extension.is_some_and(|e| EXTENSIONS.contains(&e.as_str())) && path.is_file() {
+                paths.push(path);
+            }
+        }
+        // Deterministic output, whatever order the directory is listed in.
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| {
+                let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+                let family = stem.split('-').next().unwrap_or(stem).trim();
+                Self::read(family, path)
+            })
+            .collect()
+    }
+}
+impl Display for Font {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let family = self.family.replace('\\', "\\\\").replace('"', "\\\"");
+        write!(
+            f,
+            "@font-face{{font-family:\"{family}\";font-weight:{};font-style:{};src:url(\"data:font/{};base64,{}\") format(\"{}\");}}",
+            self.weight,
+            if self.italic { "italic" } else { "normal" },
+            match self.format {
+                "truetype" => "ttf",
+                "opentype" => "otf",
+                format => format,
+            },
+            BASE64.encode(&self.data),
+            self.format,
+        )
+    }
+}
+
+/// The CSS `format()` of a font file, from its magic bytes.
+fn format(data: &[u8]) -> Option<&'static str> {
+    match data.get(..4)? {
+        b"wOF2" => Some("woff2"),
+        b"wOFF" => Some("woff"),
+        b"OTTO" => Some("opentype"),
+        b"\x00\x01\x00\x00" | b"true" => Some("truetype"),
+        _ => None,
+    }
+}
+
+/// The weight and whether it's italic, from the style part of a font's file
+/// name (everything after the family name).
+fn variant(stem: &str) -> (u16, bool) {
+    let style = stem.split_once('-').map_or("", |(_, style)| style).to_ascii_lowercase();
+    let italic = style.contains("italic") || style.contains("oblique");
+    // Longest names first, so that "extrabold" isn't taken for "bold".
+    let weights = [
+        ("extralight", 200),
+        ("ultralight", 200),
+        ("semibold", 600),
+        ("demibold", 600),
+        ("extrabold", 800),
+        ("ultrabold", 800),
+        ("thin", 100),
+        ("light", 300),
+        ("medium", 500),
+        ("bold", 700),
+        ("black", 900),
+        ("heavy", 900),
+    ];
+    let weight = weights.iter().find(|(name, _)| style.contains(name)).map_or(400, |(_, weight)| *weight);
+    (weight, italic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("Literata-ExtraBoldItalic.ttf"), b"\x00\x01\x00\x00glyphs").unwrap();
+        std::fs::write(dir.path().join("Literata-Regular.woff2"), b"wOF2glyphs").unwrap();
+        std::fs::write(dir.path().join("LICENSE.txt"), b"OFL").unwrap();
+        let fonts = Font::read_dir(dir.path()).unwrap();
+        assert_eq!(2, fonts.len());
+        assert_eq!(
+            "@font-face{font-family:\"Literata\";font-weight:800;font-style:italic;\
+             src:url(\"data:font/ttf;base64,AAEAAGdseXBocw==\") format(\"truetype\");}",
+            fonts[0].to_string()
+        );
+        assert_eq!((400, false), (fonts[1].weight, fonts[1].italic));
+        assert_eq!("woff2", fonts[1].format);
+
+        std::fs::write(dir.path().join("Broken.otf"), b"<html>").unwrap();
+        let error = Font::read_dir(dir.path()).err().unwrap();
+        assert!(matches!(&*error, ErrorKind::InvalidFont(path) if path.ends_with("Broken.otf")));
+    }
+}
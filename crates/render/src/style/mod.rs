@@ -4,16 +4,29 @@
 //! compile-time embedded builtins (see [`StyleConfig::list_builtins`]) with
 //! user-provided files or raw CSS content. All styles are read eagerly at
 //! construction time so that missing files fail fast rather than at render time.
+//! User files are syntax-checked as they're read; [watched](StyleConfig::with_watched_file)
+//! files are additionally re-read whenever they change. Font files can be
+//! [embedded](StyleConfig::with_font) too, for consistent typography whatever
+//! fonts the rendering machine has installed.
 
 mod assets;
+mod fonts;
+mod validate;
 pub(crate) mod variables;
 
 pub(crate) use self::variables::CssVariables;
+#[cfg(feature = "metadata")]
+use crate::cover::CoverTemplate;
 use crate::error::{ErrorKind, Result};
+#[cfg(feature = "metadata")]
+use crate::headers::RunningHeaders;
 use crate::style::assets::Builtins;
+use crate::style::fonts::Font;
 use exn::ResultExt;
 use std::borrow::Cow;
-use std::{fs::File, path::Path};
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+use std::{fs::File, path::Path, path::PathBuf};
 use std::{io::Read, io::Write};
 
 enum Style {
@@ -22,17 +35,72 @@ enum Style {
     // file contents during construction. We'd have to load them at render
     // time anyway, so do here and fail fast.
     UserContent(String),
+    // Long-running processes want to iterate on styles without restarting,
+    // so these get re-read (if modified) before every render.
+    Watched(Watched),
 }
 impl Style {
     fn write_all_to(&self, w: &mut impl Write) -> std::io::Result<()> {
-        let content = match self {
+        match self {
             // Infallible: business logic dictates that the builtin exists.
-            Self::Builtin(name) => Builtins::load(name).expect("builting validated at construction"),
-            Self::UserContent(content) => Cow::Borrowed(content.as_bytes()),
-        };
-        w.write_all(b"<style>")?;
-        w.write_all(&content)?;
-        w.write_all(b"</style>\n")
+            Self::Builtin(name) => write_style(w, &Builtins::load(name).expect("builting validated at construction")),
+            Self::UserContent(content) => write_style(w, content.as_bytes()),
+            Self::Watched(watched) => watched.write_all_to(w),
+        }
+    }
+}
+
+pub(crate) fn write_style(w: &mut impl Write, content: &[u8]) -> std::io::Result<()> {
+    w.write_all(b"<style>")?;
+    w.write_all(content)?;
+    w.write_all(b"</style>\n")
+}
+
+/// Reads a user stylesheet and checks its syntax.
+fn read_css(path: &Path) -> Result<String> {
+    if !path.exists() {
+        exn::bail!(ErrorKind::AssetNotFound(path.display().to_string()));
+    }
+    let mut file = File::open(path).or_raise(|| ErrorKind::Io)?;
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).or_raise(|| ErrorKind::Io)?;
+    if let Err(syntax) = validate::validate(&buf) {
+        exn::bail!(ErrorKind::InvalidCss(format!("{}, {syntax}", path.display())));
+    }
+    Ok(buf)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().and_then(|m| m.modified()).ok()
+}
+
+struct Watched {
+    path: PathBuf,
+    // Modification time the content was last (attempted to be) read at, and
+    // the last content that was read successfully.
+    state: Mutex<(Option<SystemTime>, String)>,
+}
+impl Watched {
+    fn write_all_to(&self, w: &mut impl Write) -> std::io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let modified = modified(&self.path);
+        if modified != state.0 {
+            // Record the modification time even if reading fails, so that a
+            // broken stylesheet is only reported once per change.
+            state.0 = modified;
+            match read_css(&self.path) {
+                Ok(content) => {
+                    tracing::info!(path = %self.path.display(), "Reloaded modified stylesheet");
+                    state.1 = content;
+                },
+                Err(e) => tracing::warn!(
+                    path = %self.path.display(),
+                    error = %*e,
+                    "Modified stylesheet could not be reloaded; using previous version",
+                ),
+            }
+        }
+        write_style(w, state.1.as_bytes())
     }
 }
 
@@ -58,6 +126,11 @@ impl Style {
 #[derive(Default)]
 pub struct StyleConfig {
     styles: Vec<Style>,
+    fonts: Vec<Font>,
+    #[cfg(feature = "metadata")]
+    pub(crate) cover: Option<CoverTemplate>,
+    #[cfg(feature = "metadata")]
+    pub(crate) headers: Option<RunningHeaders>,
 }
 impl StyleConfig {
     /// Creates an empty style configuration with no stylesheets.
@@ -88,29 +161,124 @@ impl StyleConfig {
     ///
     /// The file is read immediately so that missing or unreadable files
     /// surface as errors during construction rather than at render time.
+    ///
+    /// Returns [`ErrorKind::InvalidCss`](crate::error::ErrorKind::InvalidCss),
+    /// including the line and column, if the file has unbalanced braces or
+    /// brackets, or an unterminated string or comment.
     pub fn with_file(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        self.styles.push(Style::UserContent(read_css(path.as_ref())?));
+        Ok(self)
+    }
+
+    /// Appends a stylesheet read from a file on disk, which is re-read before
+    /// each render if it has been modified since.
+    ///
+    /// Like [`with_file()`](Self::with_file), the file is read (and validated)
+    /// immediately. If a modified file can't be read or fails validation, a
+    /// warning is logged and the last valid version continues to be used.
+    pub fn with_watched_file(mut self, path: impl AsRef<Path>) -> Result<Self> {
         let path = path.as_ref();
-        if !path.exists() {
-            exn::bail!(ErrorKind::AssetNotFound(path.display().to_string()));
-        }
-        let mut file = File::open(path).or_raise(|| ErrorKind::Io)?;
-        let mut buf = String::new();
-        file.read_to_string(&mut buf).or_raise(|| ErrorKind::Io)?;
-        self.styles.push(Style::UserContent(buf));
+        let modified = modified(path);
+        let content = read_css(path)?;
+        self.styles.push(Style::Watched(Watched {
+            path: path.to_path_buf(),
+            state: Mutex::new((modified, content)),
+        }));
         Ok(self)
     }
 
     /// Appends raw CSS content as a stylesheet. This is infallible since no
-    /// I/O is involved.
+    /// I/O is involved (and, unlike files, the content is not validated).
     pub fn with_content(mut self, content: impl Into<String>) -> Self {
         self.styles.push(Style::UserContent(content.into()));
         self
     }
 
+    /// Embeds a font file (WOFF2, WOFF, TrueType or OpenType) as part of
+    /// `family`, so that stylesheets can use it without it being installed.
+    ///
+    /// The weight and style are taken from the file name (for example,
+    /// `Literata-SemiBoldItalic.ttf` is the semi-bold italic). Returns
+    /// [`ErrorKind::InvalidFont`](crate::error::ErrorKind::InvalidFont) if the
+    /// file isn't a font.
+    pub fn with_font(mut self, family: impl Into<String>, path: impl AsRef<Path>) -> Result<Self> {
+        self.fonts.push(Font::read(family, path.as_ref())?);
+        Ok(self)
+    }
+
+    /// Embeds every font file in a directory, like [`with_font()`](Self::with_font),
+    /// taking each family name from the file name before its first hyphen
+    /// (`Literata-Italic.woff2` is in the `Literata` family). Other files are
+    /// ignored.
+    pub fn with_font_dir(mut self, dir: impl AsRef<Path>) -> Result<Self> {
+        self.fonts.extend(Font::read_dir(dir.as_ref())?);
+        Ok(self)
+    }
+
+    /// Prepends a generated title page to every work rendered with
+    /// [`Renderer::render_work`](crate::Renderer::render_work).
+    #[cfg(feature = "metadata")]
+    pub fn with_cover(mut self, cover: CoverTemplate) -> Self {
+        self.cover = Some(cover);
+        self
+    }
+
+    /// Prints running headers and footers on the pages of every work rendered
+    /// with [`Renderer::render_work`](crate::Renderer::render_work), in place
+    /// of those of the stylesheets.
+    #[cfg(feature = "metadata")]
+    pub fn with_headers(mut self, headers: RunningHeaders) -> Self {
+        self.headers = Some(headers);
+        self
+    }
+
     pub(crate) fn write_all_to(&self, w: &mut impl Write) -> std::io::Result<usize> {
+        // Fonts come first, so that every stylesheet can use them.
+        if !self.fonts.is_empty() {
+            let faces: String = self.fonts.iter().map(Font::to_string).collect();
+            write_style(w, faces.as_bytes())?;
+        }
         for style in &self.styles {
             style.write_all_to(w)?;
         }
-        Ok(self.styles.len())
+        Ok(self.styles.len() + usize::from(!self.fonts.is_empty()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn written(styles: &StyleConfig) -> String {
+        let mut out = Vec::new();
+        styles.write_all_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn invalid_file_reports_line() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"body {\n  margin: 0;\n\np { color: red; }\n").unwrap();
+        let Err(e) = StyleConfig::new().with_file(file.path()) else {
+            panic!("unbalanced stylesheet accepted");
+        };
+        assert!(e.to_string().contains("line 1, column 6: unclosed block"), "{e}");
+    }
+
+    #[test]
+    fn watched_file_reloads() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "p { color: red; }").unwrap();
+        let styles = StyleConfig::new().with_watched_file(file.path()).unwrap();
+        assert_eq!("<style>p { color: red; }</style>\n", written(&styles));
+        // Invalid changes are ignored; the last valid version is kept.
+        let mtime = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        std::fs::write(file.path(), "p { color: blue; ").unwrap();
+        file.as_file().set_modified(mtime(1_000)).unwrap();
+        assert_eq!("<style>p { color: red; }</style>\n", written(&styles));
+        std::fs::write(file.path(), "p { color: blue; }").unwrap();
+        file.as_file().set_modified(mtime(2_000)).unwrap();
+        assert_eq!("<style>p { color: blue; }</style>\n", written(&styles));
     }
 }
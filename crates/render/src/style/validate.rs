@@ -0,0 +1,156 @@
+//! Basic syntax checking for user-provided CSS.
+//!
+//! This is not a full CSS parser: it only tokenizes enough to catch the
+//! mistakes that would otherwise silently break a rendered document (Chrome
+//! discards everything after an unbalanced brace or unterminated string).
+//! Unknown properties, invalid values and the like are left for Chrome to
+//! ignore, the same as it would for any other stylesheet.
+
+use derive_more::Display;
+
+/// Location and description of a CSS syntax error.
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[display("line {line}, column {column}: {message}")]
+pub(crate) struct Syntax {
+    /// 1-indexed line number.
+    pub(crate) line: usize,
+    /// 1-indexed column number (in characters, not bytes).
+    pub(crate) column: usize,
+    pub(crate) message: &'static str,
+}
+
+#[derive(Clone, Copy)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+impl Position {
+    fn error(self, message: &'static str) -> Syntax {
+        Syntax {
+            line: self.line,
+            column: self.column,
+            message,
+        }
+    }
+}
+
+enum State {
+    Normal,
+    String(char, Position),
+    Comment(Position),
+}
+
+/// Checks that comments and strings are terminated, and that braces,
+/// brackets and parentheses are balanced.
+///
+/// Returns the first syntax error found.
+pub(crate) fn validate(css: &str) -> Result<(), Syntax> {
+    let mut pos = Position { line: 1, column: 0 };
+    let mut state = State::Normal;
+    let mut open: Vec<(char, Position)> = Vec::new();
+    let mut chars = css.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            pos.line += 1;
+            pos.column = 0;
+        } else {
+            pos.column += 1;
+        }
+        state = match state {
+            State::Comment(_) if c == '*' && chars.peek() == Some(&'/') => {
+                chars.next();
+                pos.column += 1;
+                State::Normal
+            },
+            State::String(quote, start) => match c {
+                '\\' => {
+                    // Escaped newlines are line continuations within strings.
+                    if chars.next() == Some('\n') {
+                        pos.line += 1;
+                        pos.column = 0;
+                    } else {
+                        pos.column += 1;
+                    }
+                    State::String(quote, start)
+                },
+                '\n' => return Err(start.error("unterminated string")),
+                c if c == quote => State::Normal,
+                _ => State::String(quote, start),
+            },
+            State::Normal => match c {
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    let start = pos;
+                    pos.column += 1;
+                    State::Comment(start)
+                },
+                '"' | '\'' => State::String(c, pos),
+                '\\' => {
+                    // Escaped character in an identifier (e.g. `.sm\:hidden`).
+                    if chars.next().is_some() {
+                        pos.column += 1;
+                    }
+                    State::Normal
+                },
+                '{' | '(' | '[' => {
+                    open.push((c, pos));
+                    State::Normal
+                },
+                '}' | ')' | ']' => {
+                    let expected = match c {
+                        '}' => '{',
+                        ')' => '(',
+                        _ => '[',
+                    };
+                    match open.pop() {
+                        Some((opener, _)) if opener == expected => State::Normal,
+                        Some((_, _)) => return Err(pos.error("mismatched closing bracket")),
+                        None => return Err(pos.error("unexpected closing bracket")),
+                    }
+                },
+                _ => State::Normal,
+            },
+            comment @ State::Comment(_) => comment,
+        };
+    }
+    match state {
+        State::String(_, start) => Err(start.error("unterminated string")),
+        State::Comment(start) => Err(start.error("unterminated comment")),
+        State::Normal => match open.pop() {
+            Some(('{', start)) => Err(start.error("unclosed block")),
+            Some((_, start)) => Err(start.error("unclosed bracket")),
+            None => Ok(()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::assets::Builtins;
+
+    #[test]
+    fn builtins_are_valid() {
+        for name in Builtins::list() {
+            let css = Builtins::load(&name).unwrap();
+            let css = std::str::from_utf8(&css).unwrap();
+            assert_eq!(Ok(()), validate(css), "builtin {name}");
+        }
+    }
+
+    #[test]
+    fn valid_edge_cases() {
+        let css = "/* } */ a::after { content: \"}\\\n{\"; }\n.sm\\{x { background: url('a(b.png'); }\n@media (x) { a[b] { } }";
+        assert_eq!(Ok(()), validate(css));
+    }
+
+    #[test]
+    fn reports_position() {
+        let error = |line, column, message| Err(Syntax { line, column, message });
+        assert_eq!(error(2, 3, "unclosed block"), validate("a { }\nb {\n  color: red;\n"));
+        assert_eq!(error(1, 12, "unexpected closing bracket"), validate("a { color }}"));
+        assert_eq!(error(3, 22, "unterminated string"), validate("\n\np::before { content: \"oops;\n}"));
+        assert_eq!(error(1, 7, "unterminated comment"), validate("a { } /* b { }"));
+        assert_eq!(error(1, 21, "mismatched closing bracket"), validate("a { width: calc(1px }"));
+    }
+}
@@ -40,18 +40,6 @@ impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for CssVariables {
 #[cfg(feature = "metadata")]
 impl From<&Metadata> for CssVariables {
     fn from(m: &Metadata) -> Self {
-        /// Format a number with comma separators: 21837 → "21,837"
-        fn human_number(n: u64) -> String {
-            let s = n.to_string();
-            let mut result = String::new();
-            for (i, c) in s.chars().rev().enumerate() {
-                if i > 0 && i % 3 == 0 {
-                    result.insert(0, ',');
-                }
-                result.insert(0, c);
-            }
-            result
-        }
         let variables = [
             ("work-id", m.work_id.to_string()),
             ("summary", m.summary.as_deref().unwrap_or_default().to_string()),
@@ -65,6 +53,21 @@ impl From<&Metadata> for CssVariables {
         variables.into_iter().collect()
     }
 }
+
+/// Format a number with comma separators: 21837 → "21,837"
+#[cfg(feature = "metadata")]
+pub(crate) fn human_number(n: u64) -> String {
+    let s = n.to_string();
+    let mut result = String::new();
+    for (i, c) in s.chars().rev().enumerate() {
+        if i > 0 && i % 3 == 0 {
+            result.insert(0, ',');
+        }
+        result.insert(0, c);
+    }
+    result
+}
+
 impl Display for CssVariables {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         writeln!(f, "<style>\n:root {{")?;
@@ -0,0 +1,211 @@
+//! Temporary files for render inputs (styled HTML) and outputs (PDFs).
+//!
+//! By default these go in the system's temporary directory, which on many
+//! systems is a small `tmpfs`: rendering a batch of long works can fill it.
+//! [`TempConfig`] moves them elsewhere, and names them so that files
+//! abandoned by a crashed (or killed) process can be recognised and cleaned
+//! up by the janitor the next time a [`Renderer`](crate::Renderer) is created.
+
+use crate::TempFile;
+use crate::error::{ErrorKind, Result};
+use exn::ResultExt;
+use std::fs::{create_dir_all, read_dir, remove_file};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// Default file name prefix of temporary files.
+const DEFAULT_PREFIX: &str = "rawr-render-";
+/// Default age after which temporary files are considered abandoned.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+/// Suffix of temporary input (HTML) files.
+const INPUT_SUFFIX: &str = ".html";
+/// Suffix of temporary output (PDF) files.
+const OUTPUT_SUFFIX: &str = ".pdf";
+
+/// Where the [`Renderer`](crate::Renderer) keeps temporary files, what it
+/// names them, and when abandoned ones are cleaned up.
+///
+/// Temporary files are named `{prefix}{random}.html` (inputs) and
+/// `{prefix}{random}.pdf` (outputs). When a renderer is created, the janitor
+/// deletes any such files in the directory older than the maximum age: files
+/// that outlived the process that created them (which normally deletes them
+/// when dropped).
+///
+/// # Example
+///
+/// ```no_run
+/// use rawr_render::{Renderer, StyleConfig, TempConfig};
+/// use std::time::Duration;
+/// # use rawr_render::error::Result;
+///
+/// # fn example() -> Result<()> {
+/// let temp = TempConfig::new()
+///     .with_dir("/var/tmp/rawr")
+///     .with_prefix("batch-")
+///     .with_max_age(Duration::from_secs(60 * 60));
+/// let renderer = Renderer::new(StyleConfig::new())?.with_temp(temp)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct TempConfig {
+    dir: Option<PathBuf>,
+    prefix: String,
+    max_age: Option<Duration>,
+}
+impl Default for TempConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            prefix: DEFAULT_PREFIX.to_string(),
+            max_age: Some(DEFAULT_MAX_AGE),
+        }
+    }
+}
+impl TempConfig {
+    /// Creates a configuration using the system's temporary directory, the
+    /// `rawr-render-` prefix, and cleaning up files older than a day.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps temporary files in `dir` (created if it doesn't exist) instead
+    /// of the system's temporary directory.
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Starts the name of every temporary file with `prefix`.
+    ///
+    /// The janitor only ever deletes files with this prefix, so choose one
+    /// that nothing else in the directory uses. An empty prefix is replaced
+    /// by the default.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        self.prefix = if prefix.is_empty() { DEFAULT_PREFIX.to_string() } else { prefix };
+        self
+    }
+
+    /// Deletes temporary files older than `max_age` when a renderer is
+    /// created; `None` disables the janitor.
+    pub fn with_max_age(mut self, max_age: impl Into<Option<Duration>>) -> Self {
+        self.max_age = max_age.into();
+        self
+    }
+
+    /// Resolves (and creates) the temporary directory as an absolute path;
+    /// Chrome is given absolute paths only.
+    pub(crate) fn prepare(mut self) -> Result<Self> {
+        let dir = self.dir.take().unwrap_or_else(std::env::temp_dir);
+        let dir = std::path::absolute(&dir).or_raise(|| ErrorKind::Io)?;
+        create_dir_all(&dir).or_raise(|| ErrorKind::Io)?;
+        self.dir = Some(dir);
+        Ok(self)
+    }
+
+    pub(crate) fn dir(&self) -> PathBuf {
+        self.dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+
+    fn create(&self, suffix: &str) -> Result<TempFile> {
+        tempfile::Builder::new()
+            .prefix(&self.prefix)
+            .suffix(suffix)
+            .tempfile_in(self.dir())
+            .or_raise(|| ErrorKind::Io)
+    }
+
+    /// Creates a temporary file for styled HTML to be rendered.
+    pub(crate) fn input(&self) -> Result<TempFile> {
+        self.create(INPUT_SUFFIX)
+    }
+
+    /// Creates a temporary file for a rendered PDF.
+    pub(crate) fn output(&self) -> Result<TempFile> {
+        self.create(OUTPUT_SUFFIX)
+    }
+
+    /// Deletes abandoned temporary files, returning how many were deleted.
+    ///
+    /// Files that can't be inspected or deleted (for example, because another
+    /// user owns them) are skipped.
+    pub(crate) fn clean(&self) -> Result<usize> {
+        let Some(max_age) = self.max_age else {
+            return Ok(0);
+        };
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for entry in read_dir(self.dir()).or_raise(|| ErrorKind::Io)? {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if !name.starts_with(&self.prefix) || !(name.ends_with(INPUT_SUFFIX) || name.ends_with(OUTPUT_SUFFIX)) {
+                continue;
+            }
+            let abandoned = entry
+                .metadata()
+                .ok()
+                .filter(|m| m.is_file())
+                .and_then(|m| m.modified().ok())
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age > max_age);
+            if !abandoned {
+                continue;
+            }
+            match remove_file(entry.path()) {
+                Ok(()) => removed += 1,
+                Err(e) => {
+                    tracing::debug!(path = %entry.path().display(), error = %e, "Could not remove abandoned temporary file")
+                },
+            }
+        }
+        if removed > 0 {
+            tracing::info!(removed, dir = %self.dir().display(), "Removed abandoned temporary render files");
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_naming() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = TempConfig::new().with_dir(dir.path().join("renders")).with_prefix("test-").prepare().unwrap();
+        let output = temp.output().unwrap();
+        let name = output.path().file_name().unwrap().to_str().unwrap();
+        assert!(output.path().starts_with(dir.path().join("renders")));
+        assert!(name.starts_with("test-") && name.ends_with(".pdf"), "{name}");
+        assert!(temp.input().unwrap().path().extension().is_some_and(|e| e == "html"));
+    }
+
+    #[test]
+    fn test_janitor() {
+        let dir = tempfile::tempdir().unwrap();
+        let temp = TempConfig::new().with_dir(dir.path()).with_max_age(Duration::from_secs(60)).prepare().unwrap();
+        let old = SystemTime::now() - Duration::from_secs(120);
+        for name in [
+            "rawr-render-old.pdf",
+            "rawr-render-old.html",
+            "rawr-render-new.pdf",
+            "other.pdf",
+        ] {
+            let file = File::create(dir.path().join(name)).unwrap();
+            if name != "rawr-render-new.pdf" {
+                file.set_modified(old).unwrap();
+            }
+        }
+        assert_eq!(2, temp.clean().unwrap());
+        assert!(dir.path().join("rawr-render-new.pdf").exists());
+        assert!(dir.path().join("other.pdf").exists());
+        assert_eq!(0, temp.with_max_age(None).clean().unwrap());
+    }
+}
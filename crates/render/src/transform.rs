@@ -0,0 +1,408 @@
+//! Preprocessing of HTML before it's printed.
+//!
+//! [`HtmlTransform`]s registered on a [`Renderer`](crate::Renderer) (with
+//! [`with_transform`](crate::Renderer::with_transform)) rewrite every document,
+//! in the order they were registered, before stylesheets are injected and
+//! Chrome prints it. Transforms see the whole document as a string, so a
+//! renderer with transforms reads each document into memory (a renderer
+//! without them streams documents through, as before).
+//!
+//! Built-in transforms work on the markup of the Archive's downloads (and of
+//! work pages saved from a browser); documents without that markup are left
+//! as they were. Custom transforms can be any
+//! `Fn(String) -> Result<String>`.
+
+use crate::error::{ErrorKind, Result};
+
+/// A rewrite of an HTML document, applied before it's printed.
+pub trait HtmlTransform: Send + Sync {
+    /// Rewrites the whole document.
+    fn transform(&self, html: String) -> Result<String>;
+
+    /// Names the transform in logs and errors.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+impl<F> HtmlTransform for F
+where
+    F: Fn(String) -> Result<String> + Send + Sync,
+{
+    fn transform(&self, html: String) -> Result<String> {
+        self(html)
+    }
+}
+
+/// Removes the Archive's navigation and boilerplate: the "Posted originally
+/// on the Archive of Our Own" and "Please drop by the Archive and comment"
+/// messages of downloads, and the header, footer and navigation menus of
+/// saved work pages.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripNavigation;
+impl HtmlTransform for StripNavigation {
+    fn transform(&self, html: String) -> Result<String> {
+        let html = remove_elements(&html, "p", |el| has_attribute_word(el, "class", "message"));
+        let html = remove_elements(&html, "div", |el| {
+            has_attribute_word(el, "id", "header") || has_attribute_word(el, "id", "footer")
+        });
+        let html = remove_elements(&html, "ul", |el| has_attribute_word(el, "class", "navigation"));
+        Ok(html)
+    }
+
+    fn name(&self) -> &str {
+        "strip-navigation"
+    }
+}
+
+/// Scales the text (and everything sized relative to it) of the document by
+/// a factor, such as `1.25` for a quarter larger.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnlargeFonts(pub f32);
+impl HtmlTransform for EnlargeFonts {
+    fn transform(&self, html: String) -> Result<String> {
+        if !self.0.is_finite() || self.0 <= 0.0 {
+            exn::bail!(ErrorKind::Transform(format!("font scale must be positive, not {}", self.0)));
+        }
+        // Stylesheets set font sizes in every unit imaginable; zooming the
+        // body scales them all alike (and reflows the text to fit the page).
+        Ok(insert_head_style(html, &format!("body {{ zoom: {} !important; }}", self.0)))
+    }
+
+    fn name(&self) -> &str {
+        "enlarge-fonts"
+    }
+}
+
+/// Starts the chapters, every chapter after the first, and the afterword of
+/// downloads on a new page.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChapterBreaks;
+impl HtmlTransform for ChapterBreaks {
+    fn transform(&self, html: String) -> Result<String> {
+        Ok(insert_head_style(
+            html,
+            "#chapters, #chapters > .meta:not(:first-child), #afterword { break-before: page; }",
+        ))
+    }
+
+    fn name(&self) -> &str {
+        "chapter-breaks"
+    }
+}
+
+/// Removes work skins: the stylesheets authors attach to their works (every
+/// rule of which the Archive scopes to `#workskin`), which often fight with
+/// the renderer's own.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StripWorkSkin;
+impl HtmlTransform for StripWorkSkin {
+    fn transform(&self, html: String) -> Result<String> {
+        let html = remove_elements(&html, "style", |el| el.contains("#workskin"));
+        // Skins linked from saved work pages, rather than inlined.
+        Ok(remove_tags(&html, "link", |tag| tag.to_ascii_lowercase().contains("/skins/")))
+    }
+
+    fn name(&self) -> &str {
+        "strip-work-skin"
+    }
+}
+
+/// Inserts a table of contents page at the start of the document (after the
+/// [cover](crate::StyleConfig::with_cover), if there is one), linking to each
+/// chapter of a download; Chrome turns the links into PDF links to the
+/// chapters' pages, for e-readers to jump through long works with.
+///
+/// Works with fewer than two chapters are left as they were.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TableOfContents;
+impl HtmlTransform for TableOfContents {
+    fn transform(&self, mut html: String) -> Result<String> {
+        let lower = html.to_ascii_lowercase();
+        let Some(chapters) = find_element(&html, &lower, "div", |el| has_attribute_word(el, "id", "chapters")) else {
+            return Ok(html);
+        };
+        // Chapter headings, as (offset to insert an ID at, anchor, title).
+        let mut headings = Vec::new();
+        let mut at = chapters.start;
+        while let Some(start) = find_open(&lower, "h2", at).filter(|&start| start < chapters.end) {
+            at = start + 1;
+            let Some(end) = element_end(&lower, "h2", start) else {
+                continue;
+            };
+            let element = &html[start..end];
+            if !has_attribute_word(element, "class", "heading") {
+                continue;
+            }
+            let title = strip_tags(element);
+            headings.push(match attribute(element, "id").filter(|id| !id.is_empty()) {
+                Some(id) => (None, id.to_string(), title),
+                None => (Some(start + "<h2".len()), format!("rawr-chapter-{}", headings.len() + 1), title),
+            });
+        }
+        if headings.len() < 2 {
+            return Ok(html);
+        }
+        let mut toc = String::from("<nav class=\"rawr-toc\"><h2>Contents</h2><ol>");
+        for (_, anchor, title) in &headings {
+            toc.push_str(&format!("<li><a href=\"#{anchor}\">{}</a></li>", title.trim()));
+        }
+        toc.push_str("</ol></nav>\n");
+        // From the end, so that earlier offsets stay put.
+        for (at, anchor, _) in headings.iter().rev() {
+            if let Some(at) = at {
+                html.insert_str(*at, &format!(" id=\"{anchor}\""));
+            }
+        }
+        let lower = html.to_ascii_lowercase();
+        let body = find_open(&lower, "body", 0).and_then(|start| lower[start..].find('>').map(|i| start + i + 1));
+        html.insert_str(body.unwrap_or(0), &toc);
+        Ok(insert_head_style(
+            html,
+            ".rawr-toc { break-after: page; } .rawr-toc ol { list-style: none; padding: 0; }",
+        ))
+    }
+
+    fn name(&self) -> &str {
+        "table-of-contents"
+    }
+}
+
+/// Inserts a stylesheet at the end of the document's `<head>` (or, without
+/// one, at the start of the document).
+pub(crate) fn insert_head_style(mut html: String, css: &str) -> String {
+    let style = format!("<style>{css}</style>\n");
+    let at = html.to_ascii_lowercase().find("</head").unwrap_or(0);
+    html.insert_str(at, &style);
+    html
+}
+
+/// Finds the next opening `<tag` at or after `from`, in a lowercased
+/// document.
+pub(crate) fn find_open(lower: &str, tag: &str, from: usize) -> Option<usize> {
+    find_tag(lower, &format!("<{tag}"), from)
+}
+
+/// Finds the next `prefix` (such as `<p` or `</p`) that isn't the start of a
+/// longer tag name (such as `<pre`).
+fn find_tag(lower: &str, prefix: &str, from: usize) -> Option<usize> {
+    let mut at = from;
+    while let Some(found) = lower[at..].find(prefix) {
+        let start = at + found;
+        let next = lower.as_bytes().get(start + prefix.len()).copied();
+        if matches!(next, Some(b'>' | b'/') | None) || next.is_some_and(|b| b.is_ascii_whitespace()) {
+            return Some(start);
+        }
+        at = start + prefix.len();
+    }
+    None
+}
+
+/// The end of the element opened at `start`, after its (matching) closing
+/// tag, in a lowercased document.
+pub(crate) fn element_end(lower: &str, tag: &str, start: usize) -> Option<usize> {
+    let close = format!("</{tag}");
+    let mut depth = 0usize;
+    let mut at = start;
+    loop {
+        let open = find_open(lower, tag, at);
+        let closed = find_tag(lower, &close, at)?;
+        match open {
+            Some(open) if open < closed => {
+                depth += 1;
+                at = open + 1;
+            },
+            _ => {
+                depth -= 1;
+                let end = lower[closed..].find('>').map(|i| closed + i + 1)?;
+                if depth == 0 {
+                    return Some(end);
+                }
+                at = end;
+            },
+        }
+    }
+}
+
+/// The span of the first `tag` element that `matches` returns `true` for,
+/// given the element's markup.
+pub(crate) fn find_element(
+    html: &str,
+    lower: &str,
+    tag: &str,
+    matches: impl Fn(&str) -> bool,
+) -> Option<std::ops::Range<usize>> {
+    let mut at = 0;
+    while let Some(start) = find_open(lower, tag, at) {
+        at = start + 1;
+        let Some(end) = element_end(lower, tag, start) else {
+            continue;
+        };
+        if matches(&html[start..end]) {
+            return Some(start..end);
+        }
+    }
+    None
+}
+
+/// Removes every `tag` element that `remove` returns `true` for, given the
+/// element's markup (opening tag to closing tag). Elements inside elements
+/// that are kept are considered too; unclosed elements are kept.
+pub(crate) fn remove_elements(html: &str, tag: &str, remove: impl Fn(&str) -> bool) -> String {
+    // Lowercasing ASCII doesn't move anything, so offsets are shared.
+    let lower = html.to_ascii_lowercase();
+    let mut out = String::with_capacity(html.len());
+    let mut copied = 0;
+    let mut at = 0;
+    while let Some(start) = find_open(&lower, tag, at) {
+        at = start + 1;
+        let Some(end) = element_end(&lower, tag, start) else {
+            continue;
+        };
+        if remove(&html[start..end]) {
+            out.push_str(&html[copied..start]);
+            copied = end;
+            at = end;
+        }
+    }
+    out.push_str(&html[copied..]);
+    out
+}
+
+/// Removes every (void) `tag` that `remove` returns `true` for, given the
+/// tag's markup.
+fn remove_tags(html: &str, tag: &str, remove: impl Fn(&str) -> bool) -> String {
+    let lower = html.to_ascii_lowercase();
+    let mut out = String::with_capacity(html.len());
+    let mut copied = 0;
+    let mut at = 0;
+    while let Some(start) = find_open(&lower, tag, at) {
+        let Some(end) = lower[start..].find('>').map(|i| start + i + 1) else {
+            break;
+        };
+        if remove(&html[start..end]) {
+            out.push_str(&html[copied..start]);
+            copied = end;
+        }
+        at = end;
+    }
+    out.push_str(&html[copied..]);
+    out
+}
+
+/// The text of some markup, without its tags (entities are kept as they
+/// are).
+pub(crate) fn strip_tags(markup: &str) -> String {
+    let mut text = String::with_capacity(markup.len());
+    let mut in_tag = false;
+    for c in markup.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {},
+        }
+    }
+    text
+}
+
+/// Whether the opening tag of `element` has an attribute `name` with `word`
+/// among its (whitespace-separated) words.
+pub(crate) fn has_attribute_word(element: &str, name: &str, word: &str) -> bool {
+    attribute_values(element, name).any(|value| value.split_ascii_whitespace().any(|w| w == word))
+}
+
+/// The value of the first attribute `name` of the opening tag of `element`.
+fn attribute<'a>(element: &'a str, name: &str) -> Option<&'a str> {
+    attribute_values(element, name).next()
+}
+
+/// The values of every attribute `name` of the opening tag of `element`.
+fn attribute_values<'a>(element: &'a str, name: &str) -> impl Iterator<Item = &'a str> {
+    let tag = &element[..element.find('>').unwrap_or(element.len())];
+    let lower = tag.to_ascii_lowercase();
+    let needle = format!("{name}=");
+    let mut at = 0;
+    std::iter::from_fn(move || {
+        while let Some(found) = lower[at..].find(&needle) {
+            let start = at + found;
+            at = start + needle.len();
+            // Not the end of a longer attribute name, such as `data-id=`.
+            if !lower[..start].ends_with(|c: char| c.is_ascii_whitespace()) {
+                continue;
+            }
+            let value = &tag[at..];
+            return Some(match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+                _ => value.split(|c: char| c.is_ascii_whitespace() || c == '>' || c == '/').next().unwrap_or_default(),
+            });
+        }
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOWNLOAD: &str = r#"<html><head><style type="text/css">body { color: black; }</style>
+<style>#workskin .texting { font-family: monospace; }</style>
+<link rel="stylesheet" href="/stylesheets/skins/skin_1234_work_skin/1_site_screen_.css"/>
+</head><body>
+<div id="preface"><p class="message"><b>Preface</b> Posted originally on the Archive of Our Own</p>
+<div class="meta"><h1>Title</h1></div></div>
+<div id="chapters" class="userstuff"><div class="meta group"><h2 class="heading">Chapter 1</h2></div>
+<div class="userstuff"><p class="messages">Kept.</p><pre>Code</pre><div><div>Nested</div></div></div></div>
+<div id="afterword"><p class="message">Please drop by the Archive and comment</p></div>
+</body></html>"#;
+
+    #[test]
+    fn test_strip_navigation() {
+        let html = StripNavigation.transform(DOWNLOAD.to_string()).unwrap();
+        assert!(!html.contains("Posted originally"));
+        assert!(!html.contains("Please drop by"));
+        assert!(html.contains("<p class=\"messages\">Kept.</p>"));
+        assert!(html.contains("<div id=\"afterword\"></div>"));
+
+        let saved = r#"<body><div id="outer"><div id="header"><div>Menu</div></div><p>Text</p></div>
+<ul class="work navigation actions"><li>Kudos</li></ul><div id="footer">Footer</div></body>"#;
+        let html = StripNavigation.transform(saved.to_string()).unwrap();
+        assert_eq!("<body><div id=\"outer\"><p>Text</p></div>\n</body>", html);
+    }
+
+    #[test]
+    fn test_strip_work_skin() {
+        let html = StripWorkSkin.transform(DOWNLOAD.to_string()).unwrap();
+        assert!(html.contains("body { color: black; }"));
+        assert!(!html.contains("#workskin"));
+        assert!(!html.contains("skin_1234"));
+    }
+
+    #[test]
+    fn test_styles() {
+        let html = ChapterBreaks.transform(DOWNLOAD.to_string()).unwrap();
+        assert!(html.contains("break-before: page; }</style>\n</head>"));
+        let html = EnlargeFonts(1.5).transform("<p>No head</p>".to_string()).unwrap();
+        assert!(html.starts_with("<style>body { zoom: 1.5 !important; }</style>"));
+        assert!(EnlargeFonts(0.0).transform(String::new()).is_err());
+    }
+
+    #[test]
+    fn test_table_of_contents() {
+        let html = r#"<html><head></head><body class="work">
+<div id="preface"><h2 class="heading">Not a chapter</h2></div>
+<div id="chapters" class="userstuff">
+<div class="meta group"><h2 class="heading">Chapter 1: <em>Tea</em> &amp; Biscuits</h2></div><div class="userstuff">One</div>
+<div class="meta group"><h2 id="two" class="heading">Chapter 2</h2></div><div class="userstuff">Two</div>
+</div></body></html>"#;
+        let html = TableOfContents.transform(html.to_string()).unwrap();
+        let toc = r##"<body class="work"><nav class="rawr-toc"><h2>Contents</h2><ol><li><a href="#rawr-chapter-1">Chapter 1: Tea &amp; Biscuits</a></li><li><a href="#two">Chapter 2</a></li></ol></nav>"##;
+        assert!(html.contains(toc));
+        assert!(html.contains(r#"<h2 id="rawr-chapter-1" class="heading">Chapter 1"#));
+        assert!(html.contains(r#"<h2 id="two" class="heading">"#));
+        assert!(html.contains("break-after: page;"));
+
+        // Nothing to navigate between.
+        let oneshot = r#"<body><div id="chapters" class="userstuff"><p>Text</p></div></body>"#;
+        assert_eq!(oneshot, TableOfContents.transform(oneshot.to_string()).unwrap());
+    }
+}
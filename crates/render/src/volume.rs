@@ -0,0 +1,215 @@
+//! Splitting huge works into volumes.
+//!
+//! Chrome holds the whole document (and every page it lays out) in memory
+//! while printing, so million-word works can exhaust it, and the PDFs it
+//! does manage are unwieldy on e-readers. [`split_volumes`] cuts a download
+//! into several smaller documents at chapter boundaries, each of which is
+//! rendered on its own.
+
+use crate::transform::{element_end, find_element, find_open, has_attribute_word, remove_elements, strip_tags};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::ops::Range;
+
+/// Roughly how many words of a work fit on a printed page.
+pub const WORDS_PER_PAGE: u64 = 300;
+
+/// How large each volume of a work may be (see
+/// [`Renderer::with_volumes`](crate::Renderer::with_volumes)).
+///
+/// Volumes only end between chapters, so a volume with a single chapter
+/// longer than the budget is larger than it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeBudget {
+    /// At most this many words of chapter text per volume.
+    Words(u64),
+    /// At most this many pages per volume, estimated at [`WORDS_PER_PAGE`].
+    Pages(u64),
+}
+impl VolumeBudget {
+    /// The budget, in words.
+    pub fn words(&self) -> u64 {
+        match self {
+            Self::Words(words) => *words,
+            Self::Pages(pages) => pages.saturating_mul(WORDS_PER_PAGE),
+        }
+    }
+}
+
+/// Which volume of a work a document is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Volume {
+    /// The volume's number, counting from 1.
+    pub number: u32,
+    /// How many volumes the work was split into; `1` when it wasn't.
+    pub count: u32,
+}
+impl Volume {
+    /// Whether the work was split at all.
+    pub fn is_split(&self) -> bool {
+        self.count > 1
+    }
+}
+impl Display for Volume {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "Volume {} of {}", self.number, self.count)
+    }
+}
+
+/// Splits a download into documents of at most `budget` each, at chapter
+/// boundaries.
+///
+/// Every volume keeps the document's head and preface (so that it's
+/// recognisably the same work); only the last keeps the afterword. Chapters
+/// are found by their headings inside `#chapters`, so documents without the
+/// Archive's markup, works with a single chapter, and works within the budget
+/// come back as the one document they were.
+pub fn split_volumes(html: String, budget: VolumeBudget) -> Vec<String> {
+    // Lowercasing ASCII doesn't move anything, so offsets are shared.
+    let lower = html.to_ascii_lowercase();
+    let Some((Range { start: content, end: content_end }, starts)) = find_chapters(&html, &lower) else {
+        return vec![html];
+    };
+    if starts.len() < 2 {
+        return vec![html];
+    }
+
+    // Chapters, as (start, end), the first taking anything before its
+    // heading too.
+    let mut bounds: Vec<_> = starts.windows(2).map(|w| (w[0], w[1])).collect();
+    bounds.push((starts[starts.len() - 1], content_end));
+    bounds[0].0 = content;
+
+    let limit = budget.words().max(1);
+    let mut volumes: Vec<(usize, usize)> = Vec::new();
+    let mut words = 0;
+    for (start, end) in bounds {
+        let count = strip_tags(&html[start..end]).split_whitespace().count() as u64;
+        match volumes.last_mut() {
+            Some(volume) if words + count <= limit => {
+                volume.1 = end;
+                words += count;
+            },
+            _ => {
+                volumes.push((start, end));
+                words = count;
+            },
+        }
+    }
+    if volumes.len() < 2 {
+        return vec![html];
+    }
+
+    let prefix = &html[..content];
+    let suffix = &html[content_end..];
+    let last = volumes.len() - 1;
+    let without_afterword = remove_elements(suffix, "div", |el| has_attribute_word(el, "id", "afterword"));
+    tracing::debug!(volumes = volumes.len(), "Work split into volumes");
+    volumes
+        .into_iter()
+        .enumerate()
+        .map(|(i, (start, end))| {
+            let suffix = if i == last { suffix } else { &without_afterword };
+            [prefix, &html[start..end], suffix].concat()
+        })
+        .collect()
+}
+
+/// The content of a download's `#chapters`, and where each chapter in it
+/// starts (see [`chapter_starts`]), given the document and its lowercased
+/// copy.
+pub(crate) fn find_chapters(html: &str, lower: &str) -> Option<(Range<usize>, Vec<usize>)> {
+    let chapters = find_element(html, lower, "div", |el| has_attribute_word(el, "id", "chapters"))?;
+    let content = lower[chapters.start..].find('>').map(|i| chapters.start + i + 1)?;
+    let content_end = lower[..chapters.end].rfind("</div").filter(|&end| end >= content)?;
+    Some((content..content_end, chapter_starts(html, lower, content..content_end)))
+}
+
+/// Where each chapter within `content` starts: at the `div.meta` wrapping its
+/// heading (along with the chapter's notes), or at the heading itself.
+fn chapter_starts(html: &str, lower: &str, content: Range<usize>) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut metas = Vec::new();
+    let mut at = content.start;
+    while let Some(start) = find_open(lower, "div", at).filter(|&start| start < content.end) {
+        at = start + 1;
+        if has_attribute_word(&html[start..], "class", "meta") {
+            metas.extend(element_end(lower, "div", start).map(|end| start..end));
+        }
+    }
+    let mut at = content.start;
+    while let Some(start) = find_open(lower, "h2", at).filter(|&start| start < content.end) {
+        at = start + 1;
+        if !has_attribute_word(&html[start..], "class", "heading") {
+            continue;
+        }
+        let meta = metas.iter().rfind(|meta| meta.start < start && start < meta.end).map(|meta| meta.start);
+        starts.push(meta.unwrap_or(start));
+    }
+    starts.dedup();
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A download with a chapter of `words` words for each of `chapters`.
+    fn download(chapters: &[usize]) -> String {
+        let body: String = chapters
+            .iter()
+            .enumerate()
+            .map(|(i, &words)| {
+                format!(
+                    "<div class=\"meta group\"><h2 class=\"heading\">Chapter {}</h2></div>\
+                     <div class=\"userstuff\"><p>{}</p></div>\n",
+                    i + 1,
+                    vec!["word"; words].join(" ")
+                )
+            })
+            .collect();
+        format!(
+            "<html><head><title>Work</title></head><body><div id=\"preface\"><h1>Work</h1></div>\
+             <div id=\"chapters\" class=\"userstuff\">{body}</div>\
+             <div id=\"afterword\"><p>Kudos</p></div></body></html>"
+        )
+    }
+
+    #[test]
+    fn test_split_volumes() {
+        let volumes = split_volumes(download(&[100, 100, 100, 100, 100]), VolumeBudget::Words(250));
+        assert_eq!(3, volumes.len());
+        let headings: Vec<_> = volumes.iter().map(|volume| volume.matches("<h2").count()).collect();
+        assert_eq!(vec![2, 2, 1], headings);
+        for (i, volume) in volumes.iter().enumerate() {
+            assert!(volume.contains("<div id=\"preface\">"), "volume {i} lost the preface");
+            assert!(volume.ends_with("</div></body></html>"));
+            assert_eq!(i == 2, volume.contains("afterword"), "volume {i}");
+        }
+        assert!(volumes[1].contains("Chapter 3") && volumes[1].contains("Chapter 4"));
+    }
+
+    #[test]
+    fn test_oversized_chapter() {
+        let volumes = split_volumes(download(&[50, 1000, 50]), VolumeBudget::Pages(1));
+        assert_eq!(3, volumes.len());
+        assert!(volumes[1].contains("Chapter 2"));
+    }
+
+    #[test]
+    fn test_unsplit() {
+        let html = download(&[100, 100]);
+        assert_eq!(vec![html.clone()], split_volumes(html, VolumeBudget::Words(1000)));
+        let html = download(&[5000]);
+        assert_eq!(vec![html.clone()], split_volumes(html, VolumeBudget::Words(10)));
+        let html = "<html><body><p>Not a download</p></body></html>".to_string();
+        assert_eq!(vec![html.clone()], split_volumes(html, VolumeBudget::Words(1)));
+    }
+
+    #[test]
+    fn test_headings_without_meta() {
+        let html = download(&[100, 100]).replace("<div class=\"meta group\">", "<div>");
+        let volumes = split_volumes(html, VolumeBudget::Words(150));
+        assert_eq!(2, volumes.len());
+        assert!(volumes[1].contains("<div id=\"chapters\" class=\"userstuff\"><h2 class=\"heading\">Chapter 2"));
+    }
+}
@@ -0,0 +1,56 @@
+//! Serve Error Types
+//!
+//! This module provides structured errors using `exn` for automatic location
+//! tracking and error tree construction. See `ERRORS.md` for design rationale.
+
+use derive_more::{Display, Error};
+pub use rawr_error::{Code, Domain, ErrorCode};
+
+/// A serve error with automatic location tracking.
+pub type Error = exn::Exn<ErrorKind>;
+/// Result type alias for serve operations.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Actionable error categories.
+///
+/// Codes are the HTTP status each kind is answered with.
+#[derive(Debug, Display, Error)]
+pub enum ErrorKind {
+    /// A query parameter (such as a search) couldn't be understood.
+    #[display("invalid query: {_0}")]
+    InvalidQuery(#[error(not(source))] String),
+    /// Nothing is at the requested path: no such page, work or file.
+    #[display("not found: {_0}")]
+    NotFound(#[error(not(source))] String),
+    /// Only `GET` and `HEAD` requests are answered; the interface is read-only.
+    #[display("method not allowed")]
+    MethodNotAllowed,
+    /// A [`Repository`](rawr_cache::Repository) query failed.
+    #[display("cache query failed")]
+    Cache,
+    /// Reading a file from its target failed.
+    #[display("could not read file from storage")]
+    Storage,
+    /// Listening for, or answering, connections failed.
+    Io,
+}
+impl ErrorKind {
+    /// Returns `true` if retrying might succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Storage | Self::Io)
+    }
+}
+
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::InvalidQuery(_) => 400,
+            Self::NotFound(_) => 404,
+            Self::MethodNotAllowed => 405,
+            Self::Cache => 500,
+            Self::Storage => 502,
+            Self::Io => 503,
+        };
+        Code::new(Domain::Serve, number)
+    }
+}
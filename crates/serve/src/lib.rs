@@ -0,0 +1,77 @@
+//! Read-only HTTP interface for browsing the library cache.
+//!
+//! A [`Server`] answers requests from a small web page (served at `/`) and
+//! the JSON API behind it, so the library can be browsed from a phone while
+//! a daemon keeps it up to date on a NAS. Nothing is ever changed: works are
+//! listed and searched from the [cache](Repository), and files downloaded
+//! from the targets they're on.
+//!
+//! | Route                         | Answers with                                            |
+//! |-------------------------------|---------------------------------------------------------|
+//! | `/`                           | The web page                                            |
+//! | `/api/works?q=&after=&limit=` | A page of works matching the [`Filter`] expression `q`  |
+//! | `/api/works/{work_id}`        | Every version of a work, and the files it's stored in   |
+//! | `/api/files/{target}/{path}`  | The contents of a file the cache knows of, as stored    |
+//! | `/opds?q=&after=`             | The same, as an [OPDS catalog](opds) for e-reader apps  |
+//! | `/opds/search.xml`            | How e-reader apps can search the catalog                |
+//!
+//! Searches (`q`) are filter expressions, or plain text that any fandom,
+//! author or tag contains. Listings are paginated: each page of works has a
+//! `next` cursor, passed as `after` to get the page following it. Errors are
+//! answered with the HTTP status matching their [code](error::ErrorCode), and
+//! a JSON body holding the code and message.
+//!
+//! With the `http` feature, the server can [listen for connections](Server::serve)
+//! itself; otherwise [`handle`](Server::handle) answers requests received
+//! any other way.
+//!
+//! > **IMPORTANT:** there's no authentication. Only listen on networks
+//! > trusted with the whole library (or put a proxy that authenticates in
+//! > front).
+//!
+//! [`Filter`]: rawr_cache::Filter
+
+pub mod error;
+#[cfg(feature = "http")]
+mod listen;
+pub mod opds;
+mod server;
+
+pub use crate::server::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use rawr_cache::Repository;
+use rawr_storage::BackendHandle;
+use std::collections::HashMap;
+
+/// Answers requests to browse the library.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rawr_serve::Server;
+/// # use rawr_cache::{Database, Repository};
+/// # use rawr_storage::BackendHandle;
+///
+/// # async fn example(db: Database, library: BackendHandle) {
+/// let server = Server::new(Repository::read_only(db.pool().clone())).with_backend(library);
+/// let request = http::Request::get("/api/works?q=complete:yes").body(()).unwrap();
+/// let response = server.handle(&request).await;
+/// assert!(response.status().is_success());
+/// # }
+/// ```
+pub struct Server {
+    cache: Repository,
+    backends: HashMap<String, BackendHandle>,
+}
+impl Server {
+    /// Creates a server browsing `cache`. Without any [backends](Self::with_backend),
+    /// works can be browsed but no files downloaded.
+    pub fn new(cache: Repository) -> Self {
+        Self { cache, backends: HashMap::new() }
+    }
+
+    /// Lets files on `backend` (the target of the same name) be downloaded.
+    pub fn with_backend(mut self, backend: BackendHandle) -> Self {
+        self.backends.insert(backend.name().to_string(), backend);
+        self
+    }
+}
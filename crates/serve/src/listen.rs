@@ -0,0 +1,78 @@
+//! Listening for connections, with the `http` feature.
+
+use crate::Server;
+use crate::error::{ErrorKind, Result};
+use exn::ResultExt;
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+impl Server {
+    /// Answers connections to `listener` until `cancel` is cancelled, then
+    /// waits for the requests being answered to finish.
+    ///
+    /// Each connection is answered on its own task. Returns [`ErrorKind::Io`]
+    /// if accepting connections fails; connections that fail are only logged.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use rawr_serve::Server;
+    /// use tokio::net::TcpListener;
+    /// use tokio_util::sync::CancellationToken;
+    /// # use rawr_cache::Repository;
+    ///
+    /// # async fn example(cache: Repository) -> rawr_serve::error::Result<()> {
+    /// let listener = TcpListener::bind("0.0.0.0:8080").await.unwrap();
+    /// Server::new(cache).serve(listener, CancellationToken::new()).await
+    /// # }
+    /// ```
+    pub async fn serve(self, listener: TcpListener, cancel: CancellationToken) -> Result<()> {
+        let server = Arc::new(self);
+        let tasks = TaskTracker::new();
+        if let Ok(address) = listener.local_addr() {
+            tracing::info!(%address, "Serving library");
+        }
+        let accepted = loop {
+            let (stream, peer) = tokio::select! {
+                _ = cancel.cancelled() => break Ok(()),
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => break Err(e).or_raise(|| ErrorKind::Io),
+                },
+            };
+            let server = Arc::clone(&server);
+            let cancel = cancel.clone();
+            tasks.spawn(async move {
+                let service = service_fn(|request: hyper::Request<Incoming>| {
+                    let server = Arc::clone(&server);
+                    async move {
+                        Ok::<_, Infallible>(server.handle(&request).await.map(|body| Full::new(Bytes::from(body))))
+                    }
+                });
+                let connection = http1::Builder::new().serve_connection(TokioIo::new(stream), service);
+                let mut connection = std::pin::pin!(connection);
+                let served = tokio::select! {
+                    served = connection.as_mut() => served,
+                    _ = cancel.cancelled() => {
+                        connection.as_mut().graceful_shutdown();
+                        connection.await
+                    },
+                };
+                if let Err(e) = served {
+                    tracing::debug!(%peer, error = %e, "Connection failed");
+                }
+            });
+        };
+        tasks.close();
+        tasks.wait().await;
+        accepted
+    }
+}
@@ -0,0 +1,191 @@
+//! OPDS catalog feeds, for e-reader apps.
+//!
+//! E-reader apps such as KOReader and Moon+ Reader browse libraries through
+//! [OPDS 1.2](https://specs.opds.io/opds-1.2) catalogs: Atom feeds whose
+//! entries link to the files to download. `/opds` is an acquisition feed of
+//! every work, a page at a time, each entry linking to the files of the
+//! work's best version; it takes the same `q` and `after` parameters as
+//! `/api/works`. `/opds/search.xml` describes how to search it, so apps show
+//! a search box.
+
+use crate::Server;
+use crate::error::Result;
+use crate::server::content_type;
+use http::header::CONTENT_TYPE;
+use http::{HeaderValue, Response};
+use rawr_extract::models::Version;
+use rawr_storage::file::{FileInfo, Processed};
+use std::fmt::Write;
+
+const ACQUISITION_FEED: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+const OPENSEARCH: &str = "application/opensearchdescription+xml";
+
+impl Server {
+    /// An acquisition feed of the page of works asked for by `query`.
+    pub(crate) async fn opds_feed(&self, query: &str) -> Result<Response<Vec<u8>>> {
+        let page = self.page(query).await?;
+        let q = form_urlencoded::parse(query.as_bytes()).find(|(key, _)| key == "q").map(|(_, q)| q.into_owned());
+        let href = |after: Option<&str>| {
+            let mut params = form_urlencoded::Serializer::new(String::new());
+            if let Some(q) = q.as_deref().filter(|q| !q.is_empty()) {
+                params.append_pair("q", q);
+            }
+            if let Some(after) = after {
+                params.append_pair("after", after);
+            }
+            match params.finish() {
+                params if params.is_empty() => "/opds".to_string(),
+                params => format!("/opds?{params}"),
+            }
+        };
+        let after = form_urlencoded::parse(query.as_bytes()).find(|(key, _)| key == "after").map(|(_, a)| a);
+
+        // Each work's best version comes first.
+        let mut works: Vec<&(Version, Vec<FileInfo<Processed>>)> = Vec::new();
+        for item in &page.items {
+            if works.last().is_none_or(|(best, _)| best.metadata.work_id != item.0.metadata.work_id) {
+                works.push(item);
+            }
+        }
+        let updated = works.iter().map(|(version, _)| version.metadata.last_modified).max();
+
+        let mut feed = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        feed.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:opds="http://opds-spec.org/2010/catalog">"#);
+        feed.push_str("<id>urn:rawr:works</id><title>rawr</title>");
+        if let Some(updated) = updated {
+            _ = write!(feed, "<updated>{updated}T00:00:00Z</updated>");
+        }
+        link(&mut feed, "self", &href(after.as_deref()), ACQUISITION_FEED);
+        link(&mut feed, "start", "/opds", ACQUISITION_FEED);
+        link(&mut feed, "search", "/opds/search.xml", OPENSEARCH);
+        if let Some(next) = &page.next {
+            link(&mut feed, "next", &href(Some(next.as_str())), ACQUISITION_FEED);
+        }
+        for (version, files) in works {
+            self.entry(&mut feed, version, files);
+        }
+        feed.push_str("</feed>");
+        Ok(xml(feed, ACQUISITION_FEED))
+    }
+
+    fn entry(&self, feed: &mut String, version: &Version, files: &[FileInfo<Processed>]) {
+        let metadata = &version.metadata;
+        feed.push_str("<entry>");
+        _ = write!(feed, "<id>urn:rawr:work:{}</id>", metadata.work_id);
+        _ = write!(feed, "<title>{}</title>", escape(&metadata.title));
+        _ = write!(feed, "<updated>{}T00:00:00Z</updated>", metadata.last_modified);
+        _ = write!(feed, "<published>{}T00:00:00Z</published>", metadata.published);
+        for author in &metadata.authors {
+            _ = write!(feed, "<author><name>{}</name></author>", escape(&author.to_string()));
+        }
+        for fandom in &metadata.fandoms {
+            _ = write!(feed, r#"<category term="{0}" label="{0}"/>"#, escape(&fandom.name));
+        }
+        if let Some(summary) = &metadata.summary {
+            _ = write!(feed, r#"<summary type="text">{}</summary>"#, escape(summary));
+        }
+        let url = format!("https://archiveofourown.org/works/{}", metadata.work_id);
+        link(feed, "alternate", &url, "text/html");
+        for file in files {
+            if let Some(download) = self.download_url(file) {
+                link(feed, "http://opds-spec.org/acquisition", &download, content_type(&file.path));
+            }
+        }
+        feed.push_str("</entry>");
+    }
+
+    /// An OpenSearch description of how to search the feed.
+    pub(crate) fn opds_search(&self) -> Response<Vec<u8>> {
+        let mut description = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        description.push_str(r#"<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">"#);
+        description
+            .push_str("<ShortName>rawr</ShortName><Description>Search works by fandom, author or tag</Description>");
+        _ = write!(description, r#"<Url type="{ACQUISITION_FEED}" template="/opds?q={{searchTerms}}"/>"#);
+        description.push_str("</OpenSearchDescription>");
+        xml(description, OPENSEARCH)
+    }
+}
+
+fn link(feed: &mut String, rel: &str, href: &str, kind: &str) {
+    _ = write!(feed, r#"<link rel="{}" href="{}" type="{}"/>"#, escape(rel), escape(href), escape(kind));
+}
+
+fn xml(body: String, kind: &'static str) -> Response<Vec<u8>> {
+    let mut response = Response::new(body.into_bytes());
+    response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static(kind));
+    response
+}
+
+/// Escapes text for XML content and (double-quoted) attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            // Control characters aren't allowed in XML 1.0 at all.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::{Database, Repository};
+    use rawr_compress::Compression;
+    use rawr_extract::models::Encoding;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::BackendHandle;
+    use rawr_storage::backend::MockBackend;
+    use std::sync::Arc;
+    use time::UtcDateTime;
+
+    #[tokio::test]
+    async fn test_opds_feed() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        for work_id in 1..=3 {
+            let path = format!("{work_id}.html");
+            let mut metadata = Generator::new(work_id).metadata();
+            metadata.work_id = work_id;
+            metadata.title = format!("Tom & \"Jerry\" <{work_id}>");
+            let version = Version {
+                hash: path.clone(),
+                length: 1000,
+                crc32: 0,
+                encoding: Encoding::Utf8,
+                detected_language: None,
+                metadata,
+                extracted_at: UtcDateTime::now(),
+            };
+            let file = FileInfo::new("library", &path, 4, UtcDateTime::now(), Compression::None)
+                .with_file_hash(&path)
+                .with_content_hash(&path);
+            cache.upsert(&file, &version).await.unwrap();
+        }
+        let library: BackendHandle = Arc::new(MockBackend::default().with_name("library"));
+        let server = Server::new(cache).with_backend(library);
+
+        let feed = server.opds_feed("limit=2").await.unwrap();
+        assert_eq!(ACQUISITION_FEED, feed.headers()[CONTENT_TYPE]);
+        let feed = String::from_utf8(feed.into_body()).unwrap();
+        assert_eq!(2, feed.matches("<entry>").count());
+        assert!(feed.contains("<title>Tom &amp; &quot;Jerry&quot; &lt;1&gt;</title>"));
+        assert!(feed.contains(r#"<link rel="http://opds-spec.org/acquisition" href="/api/files/library/1%2Ehtml" type="text/html; charset=utf-8"/>"#));
+        let next = feed.split(r#"<link rel="next" href=""#).nth(1).unwrap().split('"').next().unwrap();
+        assert!(next.starts_with("/opds?after="));
+
+        // Plain text searches, as typed into e-readers, match fandoms, authors and tags.
+        let feed = server.opds_feed("q=no+such+fandom").await.unwrap();
+        let feed = String::from_utf8(feed.into_body()).unwrap();
+        assert!(!feed.contains("<entry>") && !feed.contains(r#"rel="next""#));
+
+        let search = String::from_utf8(server.opds_search().into_body()).unwrap();
+        assert!(search.contains(r#"template="/opds?q={searchTerms}""#));
+    }
+}
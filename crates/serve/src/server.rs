@@ -0,0 +1,364 @@
+//! Routing requests, and answering them from the cache and the targets.
+
+use crate::Server;
+use crate::error::{ErrorCode, ErrorKind, Result};
+use exn::ResultExt;
+use http::header::{CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_SECURITY_POLICY, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS};
+use http::{HeaderValue, Method, Request, Response, StatusCode};
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use rawr_cache::{Cursor, Filter, Page};
+use rawr_compress::Compression;
+use rawr_extract::models::{Chapters, Version};
+use rawr_storage::FileKind;
+use rawr_storage::file::{FileInfo, Processed};
+use serde::Serialize;
+use std::path::Path;
+
+/// Works in a page when a request doesn't say how many.
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+/// The most works a page can hold, however many are asked for.
+pub const MAX_PAGE_SIZE: usize = 200;
+
+const INDEX_HTML: &str = include_str!("index.html");
+
+impl Server {
+    /// Answers `request`. Only the method and URI are looked at; anything
+    /// but a `GET` or `HEAD` is refused.
+    pub async fn handle<B>(&self, request: &Request<B>) -> Response<Vec<u8>> {
+        let response = match *request.method() {
+            Method::GET | Method::HEAD => self.route(request.uri().path(), request.uri().query()).await,
+            _ => Err(exn::Exn::new(ErrorKind::MethodNotAllowed)),
+        };
+        response.unwrap_or_else(|e| {
+            let code = e.code();
+            match code.is_system_error() {
+                true => tracing::warn!(error = ?e, "Could not answer request"),
+                false => tracing::debug!(path = request.uri().path(), %code, "Refused request"),
+            }
+            let status = StatusCode::from_u16(code.number()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            let body = ErrorBody {
+                code: code.to_string(),
+                error: e.to_string(),
+            };
+            json(status, &body)
+        })
+    }
+
+    async fn route(&self, path: &str, query: Option<&str>) -> Result<Response<Vec<u8>>> {
+        let segments: Vec<&str> = path.trim_start_matches('/').splitn(3, '/').collect();
+        match segments[..] {
+            [""] | ["index.html"] => {
+                let mut response = Response::new(INDEX_HTML.as_bytes().to_vec());
+                response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static("text/html; charset=utf-8"));
+                Ok(response)
+            },
+            ["api", "works"] => self.works(query.unwrap_or_default()).await,
+            ["api", "works", work_id] => match work_id.parse() {
+                Ok(work_id) => self.work(work_id).await,
+                Err(_) => exn::bail!(ErrorKind::NotFound(path.to_string())),
+            },
+            ["opds"] => self.opds_feed(query.unwrap_or_default()).await,
+            ["opds", "search.xml"] => Ok(self.opds_search()),
+            ["api", "files", rest] => match rest.split_once('/') {
+                Some((target, path)) => self.file(&decode(target)?, &decode(path)?).await,
+                None => exn::bail!(ErrorKind::NotFound(path.to_string())),
+            },
+            _ => exn::bail!(ErrorKind::NotFound(path.to_string())),
+        }
+    }
+
+    /// A page of the works matching the `q` parameter, continuing `after` a
+    /// cursor.
+    async fn works(&self, query: &str) -> Result<Response<Vec<u8>>> {
+        let page = self.page(query).await?;
+        // Each work's best version comes first.
+        let mut works: Vec<WorkSummary> = Vec::new();
+        for (version, _) in &page.items {
+            match works.last_mut() {
+                Some(work) if work.work_id == version.metadata.work_id => work.versions += 1,
+                _ => works.push(WorkSummary::new(version)),
+            }
+        }
+        let next = page.next.map(|cursor| cursor.to_string());
+        Ok(json(StatusCode::OK, &WorkList { works, next }))
+    }
+
+    /// The page of versions (and their files) asked for by the `q`, `after`
+    /// and `limit` parameters of `query`.
+    pub(crate) async fn page(&self, query: &str) -> Result<Page<(Version, Vec<FileInfo<Processed>>)>> {
+        let (mut filter, mut after, mut limit) = (Filter::All(Vec::new()), None, DEFAULT_PAGE_SIZE);
+        for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+            let invalid = || ErrorKind::InvalidQuery(format!("{key}={value}"));
+            match &*key {
+                "q" => filter = search(&value).or_raise(invalid)?,
+                "after" if !value.is_empty() => after = Some(value.parse::<Cursor>().or_raise(invalid)?),
+                "limit" => limit = value.parse::<usize>().or_raise(invalid)?.clamp(1, MAX_PAGE_SIZE),
+                _ => (),
+            }
+        }
+        self.cache.filter_versions_page(&filter, after.as_ref(), limit).await.or_raise(|| ErrorKind::Cache)
+    }
+
+    /// Every version of a work, and the files holding each.
+    async fn work(&self, work_id: u64) -> Result<Response<Vec<u8>>> {
+        let versions = self.cache.get_by_work_id(work_id).await.or_raise(|| ErrorKind::Cache)?;
+        if versions.is_empty() {
+            exn::bail!(ErrorKind::NotFound(format!("work {work_id}")));
+        }
+        let versions = versions
+            .iter()
+            .map(|(version, files)| VersionDetail {
+                version,
+                files: files.iter().map(|file| self.file_detail(file)).collect(),
+            })
+            .collect();
+        Ok(json(StatusCode::OK, &WorkDetail { work_id, versions }))
+    }
+
+    fn file_detail<'a>(&self, file: &'a FileInfo<Processed>) -> FileDetail<'a> {
+        FileDetail {
+            target: &file.target,
+            path: &file.path,
+            size: file.size,
+            compression: file.compression.as_str(),
+            download: self.download_url(file),
+        }
+    }
+
+    /// Where to download a file from; `None` if its target isn't served.
+    pub(crate) fn download_url(&self, file: &FileInfo<Processed>) -> Option<String> {
+        self.backends.contains_key(&file.target).then(|| {
+            let path = file.path.to_string_lossy();
+            let path: Vec<_> = path.split('/').map(encode).collect();
+            format!("/api/files/{}/{}", encode(&file.target), path.join("/"))
+        })
+    }
+
+    /// The contents of a file, if the cache knows of it: nothing else on a
+    /// target can be read. Compressed files are served decompressed, as
+    /// e-readers wouldn't know what to do with them otherwise.
+    async fn file(&self, target: &str, path: &str) -> Result<Response<Vec<u8>>> {
+        let not_found = || ErrorKind::NotFound(format!("{target}/{path}"));
+        let Some(backend) = self.backends.get(target) else {
+            exn::bail!(not_found());
+        };
+        let known = self.cache.get_by_target_path(target, path).await.or_raise(|| ErrorKind::Cache)?;
+        let Some((known, _)) = known else {
+            exn::bail!(not_found());
+        };
+        let contents = match backend.read(Path::new(path)).await {
+            Ok(contents) => contents,
+            Err(e) if matches!(&*e, rawr_storage::error::ErrorKind::NotFound(_)) => exn::bail!(not_found()),
+            Err(e) => return Err(e).or_raise(|| ErrorKind::Storage),
+        };
+        let (contents, name) = match known.compression {
+            Compression::None => (contents, Path::new(path).file_name()),
+            compression => {
+                let contents = compression.decompress(&contents).or_raise(|| ErrorKind::Storage)?;
+                (contents, Path::new(path).file_stem())
+            },
+        };
+        let mut response = Response::new(contents);
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type(Path::new(path))));
+        // Downloaded, never displayed: an edited work could hold scripts.
+        let name = name.unwrap_or_default().to_string_lossy();
+        let disposition = format!("attachment; filename*=UTF-8''{}", encode(&name));
+        if let Ok(disposition) = HeaderValue::from_str(&disposition) {
+            headers.insert(CONTENT_DISPOSITION, disposition);
+        }
+        headers.insert(CONTENT_SECURITY_POLICY, HeaderValue::from_static("sandbox"));
+        headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+        Ok(response)
+    }
+}
+
+/// Parses a search: a [`Filter`] expression, or (when it has no operators)
+/// plain text that any fandom, author or tag contains, as typed into an
+/// e-reader's search box.
+fn search(q: &str) -> rawr_cache::error::Result<Filter> {
+    match q.parse::<Filter>() {
+        Err(_) if !q.contains([':', '=', '<', '>']) => {
+            let text = q.trim();
+            Ok(Filter::Any(vec![Filter::fandom(text), Filter::author(text), Filter::tag(text)]))
+        },
+        parsed => parsed,
+    }
+}
+
+fn json(status: StatusCode, body: &impl Serialize) -> Response<Vec<u8>> {
+    // Bodies are built from strings, numbers and dates; serializing can't fail.
+    let mut response = Response::new(serde_json::to_vec(body).unwrap_or_default());
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
+
+fn encode(segment: &str) -> String {
+    utf8_percent_encode(segment, NON_ALPHANUMERIC).to_string()
+}
+
+fn decode(segment: &str) -> Result<String> {
+    let decoded = percent_decode_str(segment).decode_utf8();
+    Ok(decoded.or_raise(|| ErrorKind::NotFound(segment.to_string()))?.into_owned())
+}
+
+/// The type of a file's contents as served: compressed files are the type
+/// of what they decompress to.
+pub(crate) fn content_type(path: &Path) -> &'static str {
+    match FileKind::from_path(path) {
+        FileKind::Html => "text/html; charset=utf-8",
+        FileKind::Pdf => "application/pdf",
+        FileKind::Epub => "application/epub+zip",
+        _ => "application/octet-stream",
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    code: String,
+    error: String,
+}
+
+#[derive(Serialize)]
+struct WorkList<'a> {
+    works: Vec<WorkSummary<'a>>,
+    /// Passed as `after` for the next page; `None` on the last.
+    next: Option<String>,
+}
+
+/// A work, as its best version describes it.
+#[derive(Serialize)]
+struct WorkSummary<'a> {
+    work_id: u64,
+    title: &'a str,
+    authors: Vec<String>,
+    fandoms: Vec<&'a str>,
+    rating: Option<&'static str>,
+    words: u64,
+    chapters: Chapters,
+    complete: bool,
+    /// Number of versions of the work matching the search.
+    versions: usize,
+}
+impl<'a> WorkSummary<'a> {
+    fn new(version: &'a Version) -> Self {
+        let metadata = &version.metadata;
+        Self {
+            work_id: metadata.work_id,
+            title: &metadata.title,
+            authors: metadata.authors.iter().map(ToString::to_string).collect(),
+            fandoms: metadata.fandoms.iter().map(|fandom| fandom.name.as_str()).collect(),
+            rating: metadata.rating.map(|rating| rating.as_str()),
+            words: metadata.words,
+            chapters: metadata.chapters,
+            complete: metadata.is_complete(),
+            versions: 1,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WorkDetail<'a> {
+    work_id: u64,
+    /// Best (newest) first.
+    versions: Vec<VersionDetail<'a>>,
+}
+
+#[derive(Serialize)]
+struct VersionDetail<'a> {
+    #[serde(flatten)]
+    version: &'a Version,
+    files: Vec<FileDetail<'a>>,
+}
+
+#[derive(Serialize)]
+struct FileDetail<'a> {
+    target: &'a str,
+    path: &'a Path,
+    size: u64,
+    compression: &'static str,
+    /// Where to download the file from; `None` if its target isn't served.
+    download: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rawr_cache::{Database, Repository};
+    use rawr_extract::models::Encoding;
+    use rawr_extract::testing::Generator;
+    use rawr_storage::BackendHandle;
+    use rawr_storage::backend::MockBackend;
+    use serde_json::Value;
+    use std::sync::Arc;
+    use time::UtcDateTime;
+
+    fn version(work_id: u64, hash: &str) -> Version {
+        let mut metadata = Generator::new(work_id).metadata();
+        metadata.work_id = work_id;
+        Version {
+            hash: hash.to_string(),
+            length: 1000,
+            crc32: 0,
+            encoding: Encoding::Utf8,
+            detected_language: None,
+            metadata,
+            extracted_at: UtcDateTime::now(),
+        }
+    }
+
+    async fn get(server: &Server, uri: &str) -> (StatusCode, Value) {
+        let response = server.handle(&Request::get(uri).body(()).unwrap()).await;
+        (response.status(), serde_json::from_slice(response.body()).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_handle() {
+        let db = Database::connect_in_memory().await.unwrap();
+        let cache = Repository::from(&db);
+        for (work_id, path) in [(1, "one.html"), (2, "a b/two.html.gz"), (3, "three.html")] {
+            let file = FileInfo::new("library", path, 4, UtcDateTime::now(), Compression::from_path(path))
+                .with_file_hash(path)
+                .with_content_hash(path);
+            cache.upsert(&file, &version(work_id, path)).await.unwrap();
+        }
+        let library: BackendHandle = Arc::new(MockBackend::with_data([("secret.txt", "no")]).with_name("library"));
+        library.write(Path::new("a b/two.html.gz"), &Compression::Gzip.compress(b"two").unwrap()).await.unwrap();
+        let server = Server::new(cache).with_backend(library);
+
+        let (status, page) = get(&server, "/api/works?limit=2").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(2, page["works"].as_array().unwrap().len());
+        let after = page["next"].as_str().unwrap();
+        let (_, page) = get(&server, &format!("/api/works?limit=2&after={after}")).await;
+        assert_eq!(3, page["works"][0]["work_id"]);
+        assert!(page["next"].is_null());
+        let (status, error) = get(&server, "/api/works?q=rating%3CX").await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+        assert_eq!("RAWR-SERVE-400", error["code"]);
+
+        let (status, work) = get(&server, "/api/works/2").await;
+        assert_eq!(StatusCode::OK, status);
+        let download = work["versions"][0]["files"][0]["download"].as_str().unwrap();
+        assert_eq!("/api/files/library/a%20b/two%2Ehtml%2Egz", download);
+        assert_eq!(StatusCode::NOT_FOUND, get(&server, "/api/works/4").await.0);
+
+        let response = server.handle(&Request::get(download).body(()).unwrap()).await;
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(b"two", &response.body()[..]);
+        assert_eq!("text/html; charset=utf-8", response.headers()[CONTENT_TYPE]);
+        assert_eq!("attachment; filename*=UTF-8''two%2Ehtml", response.headers()[CONTENT_DISPOSITION]);
+        // Only files the cache knows of, on targets being served, can be read.
+        assert_eq!(StatusCode::NOT_FOUND, get(&server, "/api/files/library/secret.txt").await.0);
+        assert_eq!(StatusCode::NOT_FOUND, get(&server, "/api/files/library/one.html").await.0);
+        assert_eq!(StatusCode::NOT_FOUND, get(&server, "/api/files/trash/one.html").await.0);
+
+        let response = server.handle(&Request::delete("/api/works/1").body(()).unwrap()).await;
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, response.status());
+        let response = server.handle(&Request::get("/").body(()).unwrap()).await;
+        assert!(response.headers()[CONTENT_TYPE].to_str().unwrap().starts_with("text/html"));
+    }
+}
@@ -0,0 +1,428 @@
+//! Read-through caching storage backend decorator.
+//!
+//! This module provides a storage backend implementation that wraps another
+//! (usually remote) backend and keeps local copies of the files read through
+//! it in a cache directory, so repeated reads (scans, renders) of the same
+//! files don't have to fetch them again.
+
+use crate::backend::{BoxedReader, BoxedWriter, FileInfoStream, LocalBackend, OperatorAware, attribute_list_errors};
+use crate::error::{ErrorKind, Result};
+use crate::{BackendHandle, Contents, ObjectTags, StorageBackend, ValidatedPath, file::FileInfo};
+use async_trait::async_trait;
+use opendal::Operator;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{create_dir_all as sync_create_dir, read_dir as sync_read_dir, remove_dir_all as sync_remove_dir};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// Directory (inside the cache directory) that files are written to before
+/// being moved into place, so that a half-written file is never served.
+const STAGING_DIR: &str = ".staging";
+
+/// Caching storage backend.
+///
+/// Wraps another backend with a local disk cache: files read in full through
+/// [`read()`](StorageBackend::read) or [`read_contents()`](StorageBackend::read_contents)
+/// are copied to the cache directory, and served from there on subsequent
+/// reads. Once the cache grows beyond its byte budget, the least recently
+/// read files are evicted.
+///
+/// # Notes
+/// - Writes, deletes and renames pass through to the wrapped backend, and
+///   invalidate any cached copy of the affected paths.
+/// - Changes made to the wrapped backend by anything else are not noticed;
+///   cached copies are only ever evicted, never revalidated.
+/// - Listing, [`exists()`](StorageBackend::exists) and [`stat()`](StorageBackend::stat)
+///   always query the wrapped backend.
+/// - Files larger than the entire byte budget are never cached.
+/// - Recency isn't persisted: on startup, files already in the cache
+///   directory are ordered by when they were cached.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rawr_storage::backend::CachingBackend;
+/// use rawr_storage::{BackendHandle, ByteSize};
+/// use std::sync::Arc;
+///
+/// # fn example(s3: BackendHandle) -> Result<(), Box<dyn std::error::Error>> {
+/// // Keep up to 2 GiB of the remote library on local disk.
+/// let backend = CachingBackend::new(s3, "/var/cache/rawr/s3", ByteSize::gib(2).as_u64())?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CachingBackend {
+    inner: BackendHandle,
+    local: LocalBackend,
+    budget: u64,
+    index: Mutex<Index>,
+    staged: AtomicU64,
+}
+impl CachingBackend {
+    /// Wraps `inner` with a cache in the directory `dir` (created if it
+    /// doesn't exist), holding at most `budget` bytes.
+    ///
+    /// Files already in the directory (from a previous run) are kept, as long
+    /// as they fit in the budget.
+    ///
+    /// Returns an [`InvalidPath`](ErrorKind::InvalidPath) if the path is not
+    /// absolute.
+    pub fn new(inner: BackendHandle, dir: impl AsRef<Path>, budget: u64) -> Result<Self> {
+        let dir = dir.as_ref();
+        let name = format!("{} (cache)", inner.name());
+        let local = LocalBackend::new(name, dir, true)?;
+        // Use non-async here; it'll only happen once on library initialization
+        // and it's not worth the hassle of making the constructor async.
+        let staging = dir.join(STAGING_DIR);
+        if staging.exists() {
+            sync_remove_dir(&staging).map_err(ErrorKind::Io)?;
+        }
+        sync_create_dir(&staging).map_err(ErrorKind::Io)?;
+        let mut found = Vec::new();
+        walk(dir, Path::new(""), &mut found)?;
+        // Oldest first, so they're the first to be evicted.
+        found.sort_by_key(|(_, _, modified)| *modified);
+
+        let mut index = Index::default();
+        for (path, size, _) in found {
+            for evicted in index.admit(path, size, budget) {
+                let _ = std::fs::remove_file(dir.join(evicted));
+            }
+        }
+        Ok(Self {
+            inner,
+            local,
+            budget,
+            index: Mutex::new(index),
+            staged: AtomicU64::new(0),
+        })
+    }
+
+    /// Total size of the files currently cached, in bytes.
+    pub fn cached_bytes(&self) -> u64 {
+        self.index().bytes
+    }
+
+    fn index(&self) -> MutexGuard<'_, Index> {
+        // The index is never left in an inconsistent state mid-update.
+        self.index.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Marks a path as just used, returning whether it's cached.
+    fn hit(&self, path: &Path) -> bool {
+        ValidatedPath::new(path).is_ok_and(|validated| self.index().touch(Path::new(validated.as_str())))
+    }
+
+    /// Copies data read from the wrapped backend into the cache. Failing to
+    /// do so only costs a future read, so errors are logged and ignored.
+    async fn fill(&self, path: &Path, data: &[u8]) {
+        let size = u64::try_from(data.len()).unwrap_or(u64::MAX);
+        if size > self.budget {
+            return;
+        }
+        let Ok(validated) = ValidatedPath::new(path) else {
+            return;
+        };
+        let key = PathBuf::from(validated.as_str());
+        let staged = Path::new(STAGING_DIR).join(self.staged.fetch_add(1, Ordering::Relaxed).to_string());
+        let result = async {
+            self.local.write(&staged, data).await?;
+            self.local.rename(&staged, &key).await
+        };
+        if let Err(e) = result.await {
+            tracing::debug!(backend = self.inner.name(), path = %path.display(), error = %e, "Could not cache file");
+            let _ = self.local.delete(&staged).await;
+            return;
+        }
+        let evicted = self.index().admit(key, size, self.budget);
+        for path in evicted {
+            tracing::trace!(backend = self.inner.name(), path = %path.display(), "Evicting file from cache");
+            let _ = self.local.delete(&path).await;
+        }
+    }
+
+    /// Forgets (and deletes) any cached copy of a path.
+    async fn invalidate(&self, path: &Path) {
+        let Ok(validated) = ValidatedPath::new(path) else {
+            return;
+        };
+        let key = PathBuf::from(validated.as_str());
+        if self.index().remove(&key) {
+            let _ = self.local.delete(&key).await;
+        }
+    }
+
+    /// Reads a cached copy, or `None` if there isn't one (or it couldn't be
+    /// read, in which case it's forgotten).
+    async fn cached<T>(&self, path: &Path, read: impl AsyncFnOnce(&LocalBackend) -> Result<T>) -> Option<T> {
+        if !self.hit(path) {
+            return None;
+        }
+        match read(&self.local).await {
+            Ok(value) => Some(value),
+            Err(e) => {
+                tracing::debug!(backend = self.inner.name(), path = %path.display(), error = %e, "Could not read cached file");
+                self.invalidate(path).await;
+                None
+            },
+        }
+    }
+}
+impl OperatorAware for CachingBackend {
+    fn operator(&self) -> &Operator {
+        self.inner.operator()
+    }
+}
+#[async_trait]
+impl StorageBackend for CachingBackend {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn list_stream<'a>(&'a self, prefix: Option<&'a Path>) -> Result<FileInfoStream<'a>> {
+        Ok(attribute_list_errors(self.name(), self.inner.list_stream(prefix)?))
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        if let Some(data) = self.cached(path, async |local| local.read(path).await).await {
+            return Ok(data);
+        }
+        let data = self.inner.read(path).await?;
+        self.fill(path, &data).await;
+        Ok(data)
+    }
+
+    async fn read_contents(&self, path: &Path) -> Result<Contents> {
+        if let Some(contents) = self.cached(path, async |local| local.read_contents(path).await).await {
+            return Ok(contents);
+        }
+        let contents = self.inner.read_contents(path).await?;
+        self.fill(path, &contents).await;
+        Ok(contents)
+    }
+
+    async fn read_head(&self, path: &Path, bytes: usize) -> Result<Vec<u8>> {
+        if let Some(head) = self.cached(path, async |local| local.read_head(path, bytes).await).await {
+            return Ok(head);
+        }
+        self.inner.read_head(path, bytes).await
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.invalidate(path).await;
+        self.inner.write(path, data).await
+    }
+
+    async fn write_if_absent(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.invalidate(path).await;
+        self.inner.write_if_absent(path, data).await
+    }
+
+    async fn delete(&self, path: &Path) -> Result<()> {
+        self.invalidate(path).await;
+        self.inner.delete(path).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.invalidate(from).await;
+        self.invalidate(to).await;
+        self.inner.rename(from, to).await
+    }
+
+    async fn tag(&self, path: &Path, tags: &ObjectTags) -> Result<()> {
+        self.inner.tag(path, tags).await
+    }
+
+    async fn available_space(&self) -> Result<Option<u64>> {
+        self.inner.available_space().await
+    }
+
+    async fn stat(&self, path: &Path) -> Result<FileInfo> {
+        self.inner.stat(path).await
+    }
+
+    async fn reader(&self, path: &Path) -> Result<BoxedReader> {
+        if let Some(reader) = self.cached(path, async |local| local.reader(path).await).await {
+            return Ok(reader);
+        }
+        self.inner.reader(path).await
+    }
+
+    async fn writer(&self, path: &Path) -> Result<BoxedWriter> {
+        self.invalidate(path).await;
+        self.inner.writer(path).await
+    }
+
+    async fn writer_if_absent(&self, path: &Path) -> Result<BoxedWriter> {
+        self.invalidate(path).await;
+        self.inner.writer_if_absent(path).await
+    }
+}
+
+/// Recursively finds cached files (relative path, size, modified time),
+/// skipping the staging directory.
+fn walk(root: &Path, relative: &Path, found: &mut Vec<(PathBuf, u64, SystemTime)>) -> Result<()> {
+    for entry in sync_read_dir(root.join(relative)).map_err(ErrorKind::Io)? {
+        let entry = entry.map_err(ErrorKind::Io)?;
+        let path = relative.join(entry.file_name());
+        if path == Path::new(STAGING_DIR) {
+            continue;
+        }
+        let metadata = entry.metadata().map_err(ErrorKind::Io)?;
+        if metadata.is_dir() {
+            walk(root, &path, found)?;
+        } else if metadata.is_file() {
+            found.push((path, metadata.len(), metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)));
+        }
+    }
+    Ok(())
+}
+
+/// Which files are cached, and in which order they were last used.
+#[derive(Debug, Default)]
+struct Index {
+    /// Path → (size, tick of last use).
+    entries: HashMap<PathBuf, (u64, u64)>,
+    /// Tick of last use → path; the first entry is the least recently used.
+    recency: BTreeMap<u64, PathBuf>,
+    bytes: u64,
+    tick: u64,
+}
+impl Index {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn touch(&mut self, path: &Path) -> bool {
+        let tick = self.next_tick();
+        let Some((_, used)) = self.entries.get_mut(path) else {
+            return false;
+        };
+        if let Some(path) = self.recency.remove(used) {
+            self.recency.insert(tick, path);
+        }
+        *used = tick;
+        true
+    }
+
+    /// Records a newly cached file, returning the files evicted to make room
+    /// for it (which the caller must delete).
+    fn admit(&mut self, path: PathBuf, size: u64, budget: u64) -> Vec<PathBuf> {
+        self.remove(&path);
+        let mut evicted = Vec::new();
+        while self.bytes.saturating_add(size) > budget {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some((size, _)) = self.entries.remove(&oldest) {
+                self.bytes -= size;
+            }
+            evicted.push(oldest);
+        }
+        if size <= budget {
+            let tick = self.next_tick();
+            self.recency.insert(tick, path.clone());
+            self.entries.insert(path, (size, tick));
+            self.bytes += size;
+        } else {
+            evicted.push(path);
+        }
+        evicted
+    }
+
+    fn remove(&mut self, path: &Path) -> bool {
+        let Some((size, used)) = self.entries.remove(path) else {
+            return false;
+        };
+        self.recency.remove(&used);
+        self.bytes -= size;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use std::sync::Arc;
+
+    fn setup(budget: u64) -> (Arc<MockBackend>, CachingBackend, tempfile::TempDir) {
+        let remote = Arc::new(MockBackend::with_data([
+            ("a.html", "aaaa"),
+            ("b.html", "bbbb"),
+            ("c.html", "cccc"),
+            ("d/e.html", "eeee"),
+        ]));
+        let dir = tempfile::tempdir().unwrap();
+        let backend = CachingBackend::new(remote.clone(), dir.path(), budget).unwrap();
+        (remote, backend, dir)
+    }
+
+    async fn read(backend: &CachingBackend, path: &str) -> Result<Vec<u8>> {
+        backend.read(Path::new(path)).await
+    }
+
+    #[tokio::test]
+    async fn test_reads_fill_cache() {
+        let (remote, backend, _dir) = setup(100);
+        assert_eq!(b"aaaa", read(&backend, "a.html").await.unwrap().as_slice());
+        assert_eq!(b"eeee", &*backend.read_contents(Path::new("d/e.html")).await.unwrap());
+        assert_eq!(8, backend.cached_bytes());
+        // Served from the cache, even though the remote copy has gone.
+        remote.delete(Path::new("a.html")).await.unwrap();
+        assert_eq!(b"aaaa", read(&backend, "a.html").await.unwrap().as_slice());
+        assert_eq!(b"aa", backend.read_head(Path::new("a.html"), 2).await.unwrap().as_slice());
+        assert!(!backend.exists(Path::new("a.html")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used() {
+        let (remote, backend, _dir) = setup(12);
+        read(&backend, "a.html").await.unwrap();
+        read(&backend, "b.html").await.unwrap();
+        read(&backend, "c.html").await.unwrap();
+        read(&backend, "a.html").await.unwrap();
+        read(&backend, "d/e.html").await.unwrap();
+        assert_eq!(12, backend.cached_bytes());
+        for path in ["a.html", "b.html", "c.html", "d/e.html"] {
+            remote.delete(Path::new(path)).await.unwrap();
+        }
+        assert!(read(&backend, "a.html").await.is_ok());
+        assert!(read(&backend, "b.html").await.is_err());
+        assert!(read(&backend, "c.html").await.is_ok());
+        assert!(read(&backend, "d/e.html").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_writes_invalidate() {
+        let (remote, backend, _dir) = setup(100);
+        read(&backend, "a.html").await.unwrap();
+        backend.write(Path::new("a.html"), b"new").await.unwrap();
+        assert_eq!(0, backend.cached_bytes());
+        assert_eq!(b"new", read(&backend, "a.html").await.unwrap().as_slice());
+        backend.rename(Path::new("a.html"), Path::new("z.html")).await.unwrap();
+        assert_eq!(b"new", remote.read(Path::new("z.html")).await.unwrap().as_slice());
+        assert!(read(&backend, "a.html").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reopens_existing_cache() {
+        let (_remote, backend, dir) = setup(100);
+        read(&backend, "a.html").await.unwrap();
+        read(&backend, "d/e.html").await.unwrap();
+        drop(backend);
+        let reopened = CachingBackend::new(Arc::new(MockBackend::default()), dir.path(), 100).unwrap();
+        assert_eq!(8, reopened.cached_bytes());
+        assert_eq!(b"eeee", read(&reopened, "d/e.html").await.unwrap().as_slice());
+        // Shrinking the budget evicts files on startup.
+        let shrunk = CachingBackend::new(Arc::new(MockBackend::default()), dir.path(), 4).unwrap();
+        assert_eq!(4, shrunk.cached_bytes());
+    }
+}
@@ -0,0 +1,505 @@
+//! Chunk-aware storage backend decorator.
+//!
+//! Stores files on another backend as [content-defined chunks](crate::chunk),
+//! so that writing a file again after a small edit (as [mirroring](super::MirrorBackend)
+//! to a remote replica does) only uploads the chunks that changed.
+
+use crate::backend::{BoxedReader, BoxedWriter, FileInfoStream, OperatorAware, attribute_list_errors};
+use crate::chunk::{Chunk, ChunkConfig, ChunkIndex, chunk};
+use crate::error::{ErrorKind, Result};
+use crate::{BackendHandle, Contents, ObjectTags, StorageBackend, file::FileInfo};
+use async_trait::async_trait;
+use futures::StreamExt;
+use futures::future::{BoxFuture, join_all};
+use futures::io::AsyncWrite;
+use opendal::Operator;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Where chunks are kept on the wrapped backend.
+const CHUNK_DIR: &str = ".chunks";
+/// First line of every manifest, followed by the file's size.
+const MANIFEST_HEADER: &str = "rawr-chunks 1";
+
+/// Chunk-aware storage backend.
+///
+/// Each file is stored on the wrapped backend as a small manifest (at the
+/// file's path) listing the chunks it's made of, which are stored once each
+/// under `.chunks/`, by hash. Writing a file uploads only the chunks the
+/// backend doesn't already have: those of unchanged parts of the file, and
+/// those it shares with other files, are skipped.
+///
+/// Which chunks are stored is asked of the backend one chunk at a time. A
+/// [`ChunkIndex`] (such as the cache), if [given](Self::with_index), narrows
+/// down which to ask about: chunks it doesn't know of are uploaded without
+/// asking, and those it does are only skipped if they're still there.
+///
+/// # Notes
+/// - The wrapped backend only makes sense read through a `ChunkedBackend`:
+///   its files are manifests. Don't wrap it in decorators that reject paths
+///   (such as [`HtmlOnlyBackend`](super::HtmlOnlyBackend)), which would
+///   reject the chunks.
+/// - Chunks are never deleted (other files may still need them), so deleting
+///   and overwriting files leaves unused chunks behind.
+/// - Files are chunked whole, so streamed writes through
+///   [`writer()`](StorageBackend::writer) are buffered in memory, and only
+///   stored when the writer is closed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rawr_storage::BackendHandle;
+/// use rawr_storage::backend::{ChunkedBackend, MirrorBackend};
+/// use std::sync::Arc;
+///
+/// # fn example(local: BackendHandle, s3: BackendHandle) {
+/// // Back up the local library to S3, uploading only what changed in each file.
+/// let backend = MirrorBackend::new(local.clone(), [s3.clone()]).with_chunked_replicas(None);
+/// // Which is the same as wrapping the replica yourself.
+/// let replica: BackendHandle = Arc::new(ChunkedBackend::new(s3));
+/// let backend = MirrorBackend::new(local, [replica]);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ChunkedBackend {
+    inner: BackendHandle,
+    config: ChunkConfig,
+    index: Option<Arc<dyn ChunkIndex>>,
+}
+impl ChunkedBackend {
+    pub fn new(inner: BackendHandle) -> Self {
+        Self {
+            inner,
+            config: ChunkConfig::default(),
+            index: None,
+        }
+    }
+
+    /// Chunks files written from now on as `config` says. Files already
+    /// stored are read the same whatever their chunks' sizes.
+    pub fn with_config(mut self, config: ChunkConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Looks up (and records) which chunks are stored in `index`.
+    pub fn with_index(mut self, index: Arc<dyn ChunkIndex>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// Reads the chunks the file at `path` is made of, and its size.
+    async fn manifest(&self, path: &Path) -> Result<(u64, Vec<Chunk>)> {
+        check(path)?;
+        let manifest = self.inner.read(path).await?;
+        parse_manifest(path, &manifest)
+    }
+
+    /// Reads a chunk, checking it's intact.
+    async fn read_chunk(&self, path: &Path, chunk: &Chunk) -> Result<Vec<u8>> {
+        let data = self.inner.read(&chunk_path(&chunk.hash)).await?;
+        if data.len() as u64 != chunk.length || blake3::hash(&data).to_hex().as_str() != chunk.hash {
+            exn::bail!(ErrorKind::BackendError(format!("chunk {} of {} is corrupt", chunk.hash, path.display())));
+        }
+        Ok(data)
+    }
+
+    /// Of `chunks`, the hashes of those already stored.
+    async fn stored(&self, chunks: &[Chunk]) -> HashSet<String> {
+        let mut hashes: Vec<&str> = chunks.iter().map(|c| c.hash.as_str()).collect();
+        if let Some(index) = &self.index {
+            match index.stored_chunks(self.name(), &hashes).await {
+                Ok(indexed) => hashes.retain(|hash| indexed.contains(*hash)),
+                Err(e) => tracing::warn!(backend = self.name(), error = %e, "Chunk index lookup failed"),
+            }
+        }
+        // Even chunks the index knows of are checked: it's only a hint, and a
+        // chunk that has gone missing since would leave the file unreadable.
+        let exists =
+            join_all(hashes.iter().map(|hash| async move { self.inner.exists(&chunk_path(hash)).await })).await;
+        hashes
+            .into_iter()
+            .zip(exists)
+            .filter_map(|(hash, exists)| matches!(exists, Ok(true)).then(|| hash.to_string()))
+            .collect()
+    }
+
+    /// Stores `data` at `path`, uploading only the chunks not already stored.
+    async fn store(&self, path: &Path, data: &[u8], if_absent: bool) -> Result<()> {
+        check(path)?;
+        let chunks = chunk(data, &self.config);
+        let mut stored = self.stored(&chunks).await;
+        let mut uploaded = 0usize;
+        for chunk in &chunks {
+            if !stored.insert(chunk.hash.clone()) {
+                continue;
+            }
+            let range = chunk.offset as usize..(chunk.offset + chunk.length) as usize;
+            self.inner.write(&chunk_path(&chunk.hash), &data[range]).await?;
+            uploaded += 1;
+        }
+        let manifest = write_manifest(data.len() as u64, &chunks);
+        match if_absent {
+            true => self.inner.write_if_absent(path, manifest.as_bytes()).await?,
+            false => self.inner.write(path, manifest.as_bytes()).await?,
+        }
+        tracing::debug!(
+            backend = self.name(),
+            path = %path.display(),
+            chunks = chunks.len(),
+            uploaded,
+            "Stored file as chunks"
+        );
+        self.record(path, &chunks).await;
+        Ok(())
+    }
+
+    /// Records the chunks of the file at `path` in the index, if any. The
+    /// index is only a hint, so failing to update it isn't an error.
+    async fn record(&self, path: &Path, chunks: &[Chunk]) {
+        if let Some(index) = &self.index
+            && let Err(e) = index.record_chunks(self.name(), path, chunks).await
+        {
+            tracing::warn!(backend = self.name(), path = %path.display(), error = %e, "Recording chunks failed");
+        }
+    }
+
+    async fn forget(&self, path: &Path) {
+        if let Some(index) = &self.index
+            && let Err(e) = index.forget_chunks(self.name(), path).await
+        {
+            tracing::warn!(backend = self.name(), path = %path.display(), error = %e, "Forgetting chunks failed");
+        }
+    }
+}
+impl OperatorAware for ChunkedBackend {
+    fn operator(&self) -> &Operator {
+        self.inner.operator()
+    }
+}
+#[async_trait]
+impl StorageBackend for ChunkedBackend {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    /// Lists the files stored, with their sizes (reading each manifest).
+    fn list_stream<'a>(&'a self, prefix: Option<&'a Path>) -> Result<FileInfoStream<'a>> {
+        let stream = attribute_list_errors(self.name(), self.inner.list_stream(prefix)?);
+        let stream = stream
+            .filter(|item| std::future::ready(item.as_ref().map_or(true, |info| !info.path.starts_with(CHUNK_DIR))))
+            .then(move |item| async move {
+                let info = item?;
+                let (size, _) = self.manifest(&info.path).await?;
+                Ok(resized(info, size))
+            });
+        Ok(Box::pin(stream))
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        check(path)?;
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let (size, chunks) = self.manifest(path).await?;
+        let mut data = Vec::with_capacity(size as usize);
+        for chunk in &chunks {
+            data.extend(self.read_chunk(path, chunk).await?);
+        }
+        Ok(data)
+    }
+
+    async fn read_contents(&self, path: &Path) -> Result<Contents> {
+        Ok(Contents::from(self.read(path).await?))
+    }
+
+    async fn read_head(&self, path: &Path, bytes: usize) -> Result<Vec<u8>> {
+        let (_, chunks) = self.manifest(path).await?;
+        let mut data = Vec::new();
+        for chunk in chunks.iter().take_while(|chunk| (chunk.offset as usize) < bytes) {
+            data.extend(self.read_chunk(path, chunk).await?);
+        }
+        data.truncate(bytes);
+        Ok(data)
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.store(path, data, false).await
+    }
+
+    async fn write_if_absent(&self, path: &Path, data: &[u8]) -> Result<()> {
+        if self.exists(path).await? {
+            exn::bail!(ErrorKind::AlreadyExists(path.to_path_buf()));
+        }
+        self.store(path, data, true).await
+    }
+
+    async fn delete(&self, path: &Path) -> Result<()> {
+        check(path)?;
+        self.inner.delete(path).await?;
+        self.forget(path).await;
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        check(from)?;
+        check(to)?;
+        self.inner.rename(from, to).await?;
+        self.forget(from).await;
+        if self.index.is_some() {
+            let (_, chunks) = self.manifest(to).await?;
+            self.record(to, &chunks).await;
+        }
+        Ok(())
+    }
+
+    async fn tag(&self, path: &Path, tags: &ObjectTags) -> Result<()> {
+        check(path)?;
+        self.inner.tag(path, tags).await
+    }
+
+    async fn available_space(&self) -> Result<Option<u64>> {
+        self.inner.available_space().await
+    }
+
+    async fn stat(&self, path: &Path) -> Result<FileInfo> {
+        let info = self.inner.stat(path).await?;
+        let (size, _) = self.manifest(path).await?;
+        Ok(resized(info, size))
+    }
+
+    async fn reader(&self, path: &Path) -> Result<BoxedReader> {
+        Ok(Box::new(futures::io::Cursor::new(self.read(path).await?)))
+    }
+
+    async fn writer(&self, path: &Path) -> Result<BoxedWriter> {
+        check(path)?;
+        Ok(Box::new(ChunkedWriter::new(self.clone(), path, false)))
+    }
+
+    async fn writer_if_absent(&self, path: &Path) -> Result<BoxedWriter> {
+        if self.exists(path).await? {
+            exn::bail!(ErrorKind::AlreadyExists(path.to_path_buf()));
+        }
+        Ok(Box::new(ChunkedWriter::new(self.clone(), path, true)))
+    }
+}
+
+/// Buffers a streamed write, storing it as chunks once closed.
+struct ChunkedWriter {
+    backend: ChunkedBackend,
+    path: PathBuf,
+    if_absent: bool,
+    buffer: Vec<u8>,
+    storing: Option<BoxFuture<'static, Result<()>>>,
+}
+impl ChunkedWriter {
+    fn new(backend: ChunkedBackend, path: &Path, if_absent: bool) -> Self {
+        Self {
+            backend,
+            path: path.to_path_buf(),
+            if_absent,
+            buffer: Vec::new(),
+            storing: None,
+        }
+    }
+}
+impl AsyncWrite for ChunkedWriter {
+    fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.storing.is_some() {
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::BrokenPipe, "writer is closed")));
+        }
+        this.buffer.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let storing = this.storing.get_or_insert_with(|| {
+            let (backend, path, if_absent) = (this.backend.clone(), this.path.clone(), this.if_absent);
+            let data = std::mem::take(&mut this.buffer);
+            Box::pin(async move { backend.store(&path, &data, if_absent).await })
+        });
+        storing.as_mut().poll(cx).map_err(|e| io::Error::other(e.to_string()))
+    }
+}
+
+/// Rejects paths inside the chunk store.
+fn check(path: &Path) -> Result<()> {
+    if path.starts_with(CHUNK_DIR) {
+        exn::bail!(ErrorKind::FilteredPath(path.to_path_buf()));
+    }
+    Ok(())
+}
+
+/// The info of a file (listed as its manifest) with its actual size.
+fn resized(info: FileInfo, size: u64) -> FileInfo {
+    let meta = info.into_meta();
+    FileInfo::new(meta.target, meta.path, size, meta.discovered_at, meta.compression)
+}
+
+fn chunk_path(hash: &str) -> PathBuf {
+    Path::new(CHUNK_DIR).join(&hash[..2.min(hash.len())]).join(hash)
+}
+
+fn write_manifest(size: u64, chunks: &[Chunk]) -> String {
+    let mut manifest = format!("{MANIFEST_HEADER} {size}\n");
+    for chunk in chunks {
+        manifest.push_str(&format!("{} {}\n", chunk.hash, chunk.length));
+    }
+    manifest
+}
+
+fn parse_manifest(path: &Path, manifest: &[u8]) -> Result<(u64, Vec<Chunk>)> {
+    let invalid = || ErrorKind::BackendError(format!("not a chunk manifest: {}", path.display()));
+    let manifest = std::str::from_utf8(manifest).map_err(|_| invalid())?;
+    let mut lines = manifest.lines();
+    let size = lines
+        .next()
+        .and_then(|header| header.strip_prefix(MANIFEST_HEADER))
+        .and_then(|size| size.trim().parse().ok())
+        .ok_or_else(invalid)?;
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    for line in lines {
+        let (hash, length) = line.split_once(' ').ok_or_else(invalid)?;
+        let length: u64 = length.parse().map_err(|_| invalid())?;
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            exn::bail!(invalid());
+        }
+        chunks.push(Chunk { offset, length, hash: hash.to_string() });
+        offset += length;
+    }
+    if offset != size {
+        exn::bail!(invalid());
+    }
+    Ok((size, chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use futures::TryStreamExt;
+
+    fn data(len: usize) -> Vec<u8> {
+        let mut state = 7u64;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    /// Remembers every chunk recorded, and forgets nothing.
+    #[derive(Default)]
+    struct MemoryIndex(std::sync::Mutex<HashSet<String>>);
+    #[async_trait]
+    impl ChunkIndex for MemoryIndex {
+        async fn stored_chunks(&self, _: &str, hashes: &[&str]) -> Result<HashSet<String>> {
+            let known = self.0.lock().unwrap();
+            Ok(hashes.iter().filter(|hash| known.contains(**hash)).map(|hash| hash.to_string()).collect())
+        }
+
+        async fn record_chunks(&self, _: &str, _: &Path, chunks: &[Chunk]) -> Result<()> {
+            self.0.lock().unwrap().extend(chunks.iter().map(|c| c.hash.clone()));
+            Ok(())
+        }
+
+        async fn forget_chunks(&self, _: &str, _: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn chunks_stored(inner: &BackendHandle) -> usize {
+        inner.list(Some(Path::new(CHUNK_DIR))).await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn test_chunked_round_trip() {
+        let inner: BackendHandle = Arc::new(MockBackend::default());
+        let config = ChunkConfig::new(1024, 4096, 16 * 1024).unwrap();
+        let backend = ChunkedBackend::new(inner.clone()).with_config(config);
+        let path = Path::new("Fandom/work.html");
+        let original = data(200 * 1024);
+
+        backend.write(path, &original).await.unwrap();
+        assert_eq!(original, backend.read(path).await.unwrap());
+        assert_eq!(original[..10], backend.read_head(path, 10).await.unwrap()[..]);
+        assert_eq!(original.len() as u64, backend.stat(path).await.unwrap().size);
+        let listed: Vec<_> = backend.list_stream(None).unwrap().try_collect().await.unwrap();
+        assert_eq!(1, listed.len());
+        assert_eq!((path, original.len() as u64), (listed[0].path.as_path(), listed[0].size));
+        let stored = chunks_stored(&inner).await;
+        assert!(stored > 10);
+
+        // A small edit uploads a chunk or two.
+        let mut edited = original.clone();
+        edited[150_000..150_010].copy_from_slice(b"0123456789");
+        backend.write(path, &edited).await.unwrap();
+        assert_eq!(edited, backend.read(path).await.unwrap());
+        assert!((stored + 1..=stored + 2).contains(&chunks_stored(&inner).await));
+
+        // Chunks are shared between files.
+        backend.write(Path::new("copy.html"), &edited).await.unwrap();
+        assert!(chunks_stored(&inner).await <= stored + 2);
+        assert!(backend.write_if_absent(Path::new("copy.html"), b"x").await.is_err());
+        assert!(backend.read(Path::new(".chunks/00/00")).await.is_err());
+        backend.rename(Path::new("copy.html"), Path::new("moved.html")).await.unwrap();
+        assert_eq!(edited, backend.read(Path::new("moved.html")).await.unwrap());
+
+        // Corrupt chunks are noticed.
+        let first = &parse_manifest(path, &inner.read(path).await.unwrap()).unwrap().1[0];
+        inner.write(&chunk_path(&first.hash), b"garbage").await.unwrap();
+        assert!(backend.read(path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chunked_stale_index() {
+        let inner: BackendHandle = Arc::new(MockBackend::default());
+        let config = ChunkConfig::new(1024, 4096, 16 * 1024).unwrap();
+        let backend =
+            ChunkedBackend::new(inner.clone()).with_config(config).with_index(Arc::new(MemoryIndex::default()));
+        let original = data(64 * 1024);
+        backend.write(Path::new("a.html"), &original).await.unwrap();
+
+        // A chunk deleted behind the index's back is uploaded again.
+        let first = &parse_manifest(Path::new("a.html"), &inner.read(Path::new("a.html")).await.unwrap()).unwrap().1[0];
+        inner.delete(&chunk_path(&first.hash)).await.unwrap();
+        backend.write(Path::new("b.html"), &original).await.unwrap();
+        assert_eq!(original, backend.read(Path::new("b.html")).await.unwrap());
+        assert_eq!(original, backend.read(Path::new("a.html")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_chunked_writer() {
+        use futures::io::AsyncWriteExt;
+
+        let inner: BackendHandle = Arc::new(MockBackend::default());
+        let backend = ChunkedBackend::new(inner.clone());
+        let original = data(300 * 1024);
+        let mut writer = backend.writer(Path::new("work.html")).await.unwrap();
+        for part in original.chunks(10_000) {
+            writer.write_all(part).await.unwrap();
+        }
+        // Nothing is stored until the writer is closed.
+        assert!(!backend.exists(Path::new("work.html")).await.unwrap());
+        writer.close().await.unwrap();
+        assert_eq!(original, backend.read(Path::new("work.html")).await.unwrap());
+        assert!(writer.write_all(b"more").await.is_err());
+
+        assert!(backend.writer_if_absent(Path::new("work.html")).await.is_err());
+        assert!(backend.writer(Path::new(".chunks/00/00")).await.is_err());
+    }
+}
@@ -0,0 +1,143 @@
+//! Dry-run storage backend.
+//!
+//! This module provides a storage backend implementation that wraps other
+//! implementations and prevents write operations from executing, but
+//! indicating success on return.
+
+use async_trait::async_trait;
+use opendal::Operator;
+use std::path::Path;
+
+use crate::{
+    BackendHandle, Contents, ObjectTags, StorageBackend,
+    backend::{BoxedReader, BoxedWriter, FileInfoStream, OperatorAware, attribute_list_errors},
+    error::{ErrorKind, Result},
+    file::FileInfo,
+};
+
+/// Dry-run storage backend.
+///
+/// Wraps another backend and silently drops all write operations, logging an
+/// [`info event`](tracing::Event). Usually created by [`Mode::backend()`](crate::Mode::backend).
+#[derive(Clone)]
+pub struct DryRunBackend {
+    inner: BackendHandle,
+}
+impl DryRunBackend {
+    pub fn new(inner: BackendHandle) -> Self {
+        Self { inner }
+    }
+}
+impl OperatorAware for DryRunBackend {
+    fn operator(&self) -> &Operator {
+        self.inner.operator()
+    }
+}
+#[async_trait]
+impl StorageBackend for DryRunBackend {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn list_stream<'a>(&'a self, prefix: Option<&'a Path>) -> Result<FileInfoStream<'a>> {
+        Ok(attribute_list_errors(self.name(), self.inner.list_stream(prefix)?))
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.inner.read(path).await
+    }
+
+    async fn read_contents(&self, path: &Path) -> Result<Contents> {
+        self.inner.read_contents(path).await
+    }
+
+    async fn read_head(&self, path: &Path, bytes: usize) -> Result<Vec<u8>> {
+        self.inner.read_head(path, bytes).await
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        tracing::info!(path = %path.display(), bytes = data.len(), "Skipping write during dry run");
+        Ok(())
+    }
+
+    async fn write_if_absent(&self, path: &Path, data: &[u8]) -> Result<()> {
+        // Still fail as the write would have, so dry runs behave alike.
+        if self.inner.exists(path).await? {
+            exn::bail!(ErrorKind::AlreadyExists(path.to_path_buf()));
+        }
+        tracing::info!(path = %path.display(), bytes = data.len(), "Skipping write during dry run");
+        Ok(())
+    }
+
+    async fn delete(&self, path: &Path) -> Result<()> {
+        tracing::info!(path = %path.display(), "Skipping delete during dry run");
+        Ok(())
+    }
+
+    async fn rename(&self, from: &Path, _to: &Path) -> Result<()> {
+        tracing::info!(path = %from.display(), "Skipping rename/move during dry run");
+        Ok(())
+    }
+
+    async fn tag(&self, path: &Path, tags: &ObjectTags) -> Result<()> {
+        tracing::info!(path = %path.display(), tags = tags.len(), "Skipping object tagging during dry run");
+        Ok(())
+    }
+
+    async fn available_space(&self) -> Result<Option<u64>> {
+        self.inner.available_space().await
+    }
+
+    async fn stat(&self, path: &Path) -> Result<FileInfo> {
+        self.inner.stat(path).await
+    }
+
+    async fn reader(&self, path: &Path) -> Result<BoxedReader> {
+        self.inner.reader(path).await
+    }
+
+    async fn writer(&self, path: &Path) -> Result<BoxedWriter> {
+        tracing::info!(path = %path.display(), "Skipping writer during dry run");
+        Ok(Box::new(futures::io::sink()))
+    }
+
+    async fn writer_if_absent(&self, path: &Path) -> Result<BoxedWriter> {
+        if self.inner.exists(path).await? {
+            exn::bail!(ErrorKind::AlreadyExists(path.to_path_buf()));
+        }
+        self.writer(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use futures::io::AsyncWriteExt;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_changes_nothing() {
+        let inner: BackendHandle = Arc::new(MockBackend::with_data([("a.html", Vec::from(*b"original"))]));
+        let backend = DryRunBackend::new(inner.clone());
+        backend.write(Path::new("a.html"), b"replaced").await.unwrap();
+        backend.write(Path::new("b.html"), b"new").await.unwrap();
+        backend.rename(Path::new("a.html"), Path::new("c.html")).await.unwrap();
+        backend.delete(Path::new("a.html")).await.unwrap();
+        let mut writer = backend.writer(Path::new("d.html")).await.unwrap();
+        writer.write_all(b"streamed").await.unwrap();
+        writer.close().await.unwrap();
+
+        assert_eq!(b"original".to_vec(), backend.read(Path::new("a.html")).await.unwrap());
+        let paths: Vec<_> = inner.list(None).await.unwrap().into_iter().map(|f| f.path.clone()).collect();
+        assert_eq!(vec![Path::new("a.html")], paths);
+        // Conditional writes still fail as they would have.
+        let err = backend.write_if_absent(Path::new("a.html"), b"new").await.unwrap_err();
+        assert!(matches!(&*err, ErrorKind::AlreadyExists(_)));
+        backend.write_if_absent(Path::new("b.html"), b"new").await.unwrap();
+    }
+}
@@ -3,13 +3,13 @@
 //! Wraps another backend and restricts all operations to files with
 //! `.html` base extension (after stripping any compression suffix).
 
-use crate::backend::{BoxedReader, BoxedWriter, FileInfoStream, OperatorAware};
+use crate::backend::{BoxedReader, BoxedWriter, FileInfoStream, OperatorAware, attribute_list_errors};
 use crate::error::ErrorKind;
-use crate::{BackendHandle, StorageBackend, error::Result, file::FileInfo};
+use crate::kind::base_extension;
+use crate::{BackendHandle, Contents, ObjectTags, StorageBackend, error::Result, file::FileInfo};
 use async_trait::async_trait;
 use futures::StreamExt;
 use opendal::Operator;
-use rawr_compress::Compression;
 use std::path::Path;
 
 /// The required base extension (after stripping compression).
@@ -21,19 +21,11 @@ const HTML_EXTENSION: &str = "html";
 /// - `file.html` -> html -> true
 /// - `file.html.bz2` -> strip .bz2 -> html -> true
 /// - `file.txt` -> txt -> false
+///
+/// Stricter than [`FileKind::Html`](crate::FileKind::Html), which also
+/// includes `.htm` files.
 fn is_html_path(path: impl AsRef<Path>) -> bool {
-    let path = path.as_ref();
-    let compression = Compression::from_path(path);
-    let check_path = if compression != Compression::None {
-        // Strip the compression extension to get the inner filename
-        Path::new(path.file_stem().unwrap_or_default())
-    } else {
-        path
-    };
-    check_path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .is_some_and(|ext| ext.eq_ignore_ascii_case(HTML_EXTENSION))
+    base_extension(path.as_ref()).is_some_and(|ext| ext.eq_ignore_ascii_case(HTML_EXTENSION))
 }
 
 /// HTML-filtered storage backend.
@@ -62,7 +54,8 @@ impl StorageBackend for HtmlOnlyBackend {
     }
 
     fn list_stream<'a>(&'a self, prefix: Option<&'a Path>) -> Result<FileInfoStream<'a>> {
-        Ok(Box::pin(self.inner.list_stream(prefix)?.filter(|item| {
+        let stream = attribute_list_errors(self.name(), self.inner.list_stream(prefix)?);
+        Ok(Box::pin(stream.filter(|item| {
             std::future::ready(match item {
                 Ok(info) => is_html_path(&info.path),
                 Err(_) => true, // propagate errors
@@ -84,6 +77,13 @@ impl StorageBackend for HtmlOnlyBackend {
         self.inner.read(path).await
     }
 
+    async fn read_contents(&self, path: &Path) -> Result<Contents> {
+        if !is_html_path(path) {
+            exn::bail!(ErrorKind::FilteredPath(path.to_path_buf()));
+        }
+        self.inner.read_contents(path).await
+    }
+
     async fn read_head(&self, path: &Path, bytes: usize) -> Result<Vec<u8>> {
         if !is_html_path(path) {
             exn::bail!(ErrorKind::FilteredPath(path.to_path_buf()));
@@ -98,6 +98,13 @@ impl StorageBackend for HtmlOnlyBackend {
         self.inner.write(path, data).await
     }
 
+    async fn write_if_absent(&self, path: &Path, data: &[u8]) -> Result<()> {
+        if !is_html_path(path) {
+            exn::bail!(ErrorKind::FilteredPath(path.to_path_buf()));
+        }
+        self.inner.write_if_absent(path, data).await
+    }
+
     async fn delete(&self, path: &Path) -> Result<()> {
         if !is_html_path(path) {
             exn::bail!(ErrorKind::FilteredPath(path.to_path_buf()));
@@ -115,6 +122,17 @@ impl StorageBackend for HtmlOnlyBackend {
         self.inner.rename(from, to).await
     }
 
+    async fn tag(&self, path: &Path, tags: &ObjectTags) -> Result<()> {
+        if !is_html_path(path) {
+            exn::bail!(ErrorKind::FilteredPath(path.to_path_buf()));
+        }
+        self.inner.tag(path, tags).await
+    }
+
+    async fn available_space(&self) -> Result<Option<u64>> {
+        self.inner.available_space().await
+    }
+
     async fn stat(&self, path: &Path) -> Result<FileInfo> {
         if !is_html_path(path) {
             exn::bail!(ErrorKind::FilteredPath(path.to_path_buf()));
@@ -135,15 +153,24 @@ impl StorageBackend for HtmlOnlyBackend {
         }
         self.inner.writer(path).await
     }
+
+    async fn writer_if_absent(&self, path: &Path) -> Result<BoxedWriter> {
+        if !is_html_path(path) {
+            exn::bail!(ErrorKind::FilteredPath(path.to_path_buf()));
+        }
+        self.inner.writer_if_absent(path).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{BackendHandle, StorageBackend, backend::LocalBackend, error::ErrorKind};
+    use rawr_compress::Compression;
     use std::path::Path;
     use std::path::PathBuf;
     use std::sync::Arc;
+    use time::UtcDateTime;
 
     #[test]
     fn test_is_html_path_plain_html() {
@@ -237,4 +264,56 @@ mod tests {
         // html -> html: should succeed
         backend.rename(Path::new("a.html"), Path::new("b.html")).await.unwrap();
     }
+
+    /// A backend whose listings fail partway through.
+    struct FailingBackend {
+        inner: crate::backend::MockBackend,
+    }
+    impl OperatorAware for FailingBackend {
+        fn operator(&self) -> &Operator {
+            self.inner.operator()
+        }
+    }
+    #[async_trait]
+    impl StorageBackend for FailingBackend {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn list_stream<'a>(&'a self, _prefix: Option<&'a Path>) -> Result<FileInfoStream<'a>> {
+            let denied = ErrorKind::PermissionDenied(PathBuf::from("Fandom/secret.html"));
+            let items = vec![
+                Ok(FileInfo::new("failing", "work.html", 4, UtcDateTime::now(), Compression::None)),
+                Err(exn::Exn::new(ErrorKind::Network("connection reset".to_string()))),
+                Err(exn::Exn::new(denied)),
+            ];
+            Ok(Box::pin(futures::stream::iter(items)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_errors_attributed_through_decorators() {
+        use crate::backend::{IgnoreBackend, IgnorePatterns, MockBackend, RetryBackend, RetryPolicy};
+        let failing: BackendHandle = Arc::new(FailingBackend { inner: MockBackend::default() });
+        let html: BackendHandle = Arc::new(HtmlOnlyBackend::new(failing));
+        let retry: BackendHandle = Arc::new(RetryBackend::new(html, RetryPolicy::new()));
+        let backend = IgnoreBackend::new(retry, IgnorePatterns::default());
+
+        let items: Vec<_> = backend.list_stream(None).unwrap().collect().await;
+        assert_eq!(3, items.len());
+        assert!(items[0].is_ok());
+        let [_, Err(network), Err(denied)] = &items[..] else {
+            panic!("expected two errors");
+        };
+        assert!(matches!(&**network, ErrorKind::Listing { backend, path: None } if backend == "failing"));
+        let ErrorKind::Listing { path: Some(path), .. } = &**denied else {
+            panic!("unexpected error: {denied:?}");
+        };
+        assert_eq!(Path::new("Fandom/secret.html"), path);
+        // Attributed once, with the original error as its only child.
+        let children = network.frame().children();
+        assert_eq!(1, children.len());
+        assert!(children[0].error().downcast_ref::<ErrorKind>().is_some_and(|e| matches!(e, ErrorKind::Network(_))));
+        assert_eq!("listing failing failed at: Fandom/secret.html", denied.to_string());
+    }
 }
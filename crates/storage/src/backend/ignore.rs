@@ -0,0 +1,230 @@
+//! Ignore-pattern storage backend decorator.
+//!
+//! Wraps another backend and hides files matching a set of glob patterns,
+//! such as the `.part`/`.crdownload` files download managers leave behind
+//! while a download is in progress. Hiding them here, rather than in scan
+//! logic, means every consumer of the backend benefits.
+
+use crate::backend::{BoxedReader, BoxedWriter, FileInfoStream, OperatorAware, attribute_list_errors};
+use crate::error::ErrorKind;
+use crate::{BackendHandle, Contents, ObjectTags, StorageBackend, error::Result, file::FileInfo};
+use async_trait::async_trait;
+use exn::ResultExt;
+use futures::StreamExt;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use opendal::Operator;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::path::Path;
+
+/// Patterns ignored by [`IgnorePatterns::default()`]: temporary files from
+/// browsers and download managers, editor backups, and dotfiles (including
+/// everything inside dot-directories).
+pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
+    "*.part",
+    "*.partial",
+    "*.crdownload",
+    "*.download",
+    "*.opdownload",
+    "*.tmp",
+    "*.temp",
+    "*~",
+    ".*",
+];
+
+/// A compiled set of glob patterns deciding which paths an
+/// [`IgnoreBackend`] hides.
+///
+/// A path is ignored if any pattern matches the whole path (relative to the
+/// storage root), or any single component of it; `.*` therefore ignores both
+/// `.DS_Store` and `.sync/work.html`. Patterns are case-sensitive.
+#[derive(Clone)]
+pub struct IgnorePatterns {
+    patterns: Vec<String>,
+    set: GlobSet,
+}
+impl IgnorePatterns {
+    /// Compiles a set of glob patterns, returning
+    /// [`InvalidPattern`](ErrorKind::InvalidPattern) for the first invalid one.
+    pub fn new<S: AsRef<str>>(patterns: impl IntoIterator<Item = S>) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        let mut compiled = Vec::new();
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            builder.add(Glob::new(pattern).or_raise(|| ErrorKind::InvalidPattern(pattern.to_string()))?);
+            compiled.push(pattern.to_string());
+        }
+        let set = builder.build().or_raise(|| ErrorKind::InvalidPattern(compiled.join(", ")))?;
+        Ok(Self { patterns: compiled, set })
+    }
+
+    /// Whether `path` matches any of the patterns.
+    pub fn is_ignored(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        self.set.is_match(path) || path.components().any(|c| self.set.is_match(c.as_os_str()))
+    }
+}
+impl Default for IgnorePatterns {
+    fn default() -> Self {
+        // Infallible: the default patterns are covered by tests.
+        Self::new(DEFAULT_IGNORE_PATTERNS).expect("default ignore patterns compile")
+    }
+}
+impl Debug for IgnorePatterns {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_tuple("IgnorePatterns").field(&self.patterns).finish()
+    }
+}
+
+/// Ignore-pattern storage backend.
+///
+/// Wraps another backend, omitting files that match its [`IgnorePatterns`]
+/// from listings. All other operations on ignored paths return
+/// `ErrorKind::FilteredPath`.
+#[derive(Clone)]
+pub struct IgnoreBackend {
+    inner: BackendHandle,
+    patterns: IgnorePatterns,
+}
+impl IgnoreBackend {
+    pub fn new(inner: BackendHandle, patterns: IgnorePatterns) -> Self {
+        Self { inner, patterns }
+    }
+
+    fn check(&self, path: &Path) -> Result<()> {
+        if self.patterns.is_ignored(path) {
+            exn::bail!(ErrorKind::FilteredPath(path.to_path_buf()));
+        }
+        Ok(())
+    }
+}
+impl OperatorAware for IgnoreBackend {
+    fn operator(&self) -> &Operator {
+        self.inner.operator()
+    }
+}
+#[async_trait]
+impl StorageBackend for IgnoreBackend {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn list_stream<'a>(&'a self, prefix: Option<&'a Path>) -> Result<FileInfoStream<'a>> {
+        let stream = attribute_list_errors(self.name(), self.inner.list_stream(prefix)?);
+        Ok(Box::pin(stream.filter(|item| {
+            std::future::ready(match item {
+                Ok(info) => !self.patterns.is_ignored(&info.path),
+                Err(_) => true, // propagate errors
+            })
+        })))
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.check(path)?;
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.check(path)?;
+        self.inner.read(path).await
+    }
+
+    async fn read_contents(&self, path: &Path) -> Result<Contents> {
+        self.check(path)?;
+        self.inner.read_contents(path).await
+    }
+
+    async fn read_head(&self, path: &Path, bytes: usize) -> Result<Vec<u8>> {
+        self.check(path)?;
+        self.inner.read_head(path, bytes).await
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.check(path)?;
+        self.inner.write(path, data).await
+    }
+
+    async fn write_if_absent(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.check(path)?;
+        self.inner.write_if_absent(path, data).await
+    }
+
+    async fn delete(&self, path: &Path) -> Result<()> {
+        self.check(path)?;
+        self.inner.delete(path).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.check(from)?;
+        self.check(to)?;
+        self.inner.rename(from, to).await
+    }
+
+    async fn tag(&self, path: &Path, tags: &ObjectTags) -> Result<()> {
+        self.check(path)?;
+        self.inner.tag(path, tags).await
+    }
+
+    async fn available_space(&self) -> Result<Option<u64>> {
+        self.inner.available_space().await
+    }
+
+    async fn stat(&self, path: &Path) -> Result<FileInfo> {
+        self.check(path)?;
+        self.inner.stat(path).await
+    }
+
+    async fn reader(&self, path: &Path) -> Result<BoxedReader> {
+        self.check(path)?;
+        self.inner.reader(path).await
+    }
+
+    async fn writer(&self, path: &Path) -> Result<BoxedWriter> {
+        self.check(path)?;
+        self.inner.writer(path).await
+    }
+
+    async fn writer_if_absent(&self, path: &Path) -> Result<BoxedWriter> {
+        self.check(path)?;
+        self.inner.writer_if_absent(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_default_patterns() {
+        let patterns = IgnorePatterns::default();
+        assert!(patterns.is_ignored("work.html.part"));
+        assert!(patterns.is_ignored("Fandom/work.html.crdownload"));
+        assert!(patterns.is_ignored(".DS_Store"));
+        assert!(patterns.is_ignored(".sync/work.html"));
+        assert!(patterns.is_ignored("Fandom/work.html~"));
+        assert!(!patterns.is_ignored("Fandom/work.html"));
+        assert!(!patterns.is_ignored("Fandom/work.html.bz2"));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let err = IgnorePatterns::new(["*.part", "[unclosed"]).unwrap_err();
+        assert!(matches!(&*err, ErrorKind::InvalidPattern(p) if p == "[unclosed"));
+    }
+
+    #[tokio::test]
+    async fn test_list_hides_ignored_files() {
+        let inner = MockBackend::with_data([
+            ("a.html", b"data".as_slice()),
+            ("b.html.part", b"data"),
+            (".hidden/c.html", b"data"),
+        ]);
+        let backend = IgnoreBackend::new(Arc::new(inner), IgnorePatterns::default());
+        let paths: Vec<_> = backend.list(None).await.unwrap().into_iter().map(|f| f.path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("a.html")]);
+        let err = backend.read(Path::new("b.html.part")).await.unwrap_err();
+        assert!(matches!(&*err, ErrorKind::FilteredPath(_)));
+    }
+}
@@ -3,20 +3,40 @@
 //! This module provides a storage backend implementation for the local filesystem
 //! using [OpenDAL](https://docs.rs/opendal/) with the `Fs` service for async I/O.
 
-use crate::StorageBackend;
-use crate::backend::OperatorAware;
+#[cfg(all(feature = "mmap", unix))]
+use crate::Contents;
+use crate::backend::{BoxedWriter, OperatorAware};
 use crate::error::{ErrorKind, Result};
+use crate::{StorageBackend, ValidatedPath};
 use async_trait::async_trait;
+use futures::io::AsyncWrite;
 use opendal::services::Fs;
 use opendal::{Operator, layers::RetryLayer};
 use std::fs::create_dir_all as sync_create_dir;
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Files smaller than this are read into memory rather than memory-mapped;
+/// below it, the cost of setting up the mapping outweighs the copy saved.
+#[cfg(all(feature = "mmap", unix))]
+const MMAP_THRESHOLD: u64 = 64 * 1024;
 
 /// Local filesystem storage backend.
 ///
 /// Stores files in a directory on the local filesystem.
 /// All paths are relative to the configured root directory.
 ///
+/// On unix, [`available_space()`](StorageBackend::available_space) reports
+/// the space free on the volume the root directory is on.
+///
+/// With the `mmap` feature enabled (unix only), [`read_contents()`](StorageBackend::read_contents)
+/// memory-maps files of 64 KiB or more instead of copying them into memory,
+/// if they can't change while mapped: files nobody has permission to write
+/// to, and files on a read-only mount. Others are copied, as a mapped file
+/// that's truncated crashes the process (with `SIGBUS`) when read.
+///
 /// # Examples
 ///
 /// ```no_run
@@ -24,12 +44,13 @@ use std::path::Path;
 /// use std::path::PathBuf;
 ///
 /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
-/// let backend = LocalBackend::new("local", "/path/to/library")?;
+/// let backend = LocalBackend::new("local", "/path/to/library", false)?;
 /// # Ok(())
 /// # }
 /// ```
 pub struct LocalBackend {
     name: String,
+    root: PathBuf,
     operator: Operator,
 }
 impl LocalBackend {
@@ -44,7 +65,7 @@ impl LocalBackend {
     /// use rawr_storage::backend::LocalBackend;
     ///
     /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
-    /// let backend = LocalBackend::new("nfs", "/absolute/path/to/library")?;
+    /// let backend = LocalBackend::new("nfs", "/absolute/path/to/library", false)?;
     /// # Ok(())
     /// # }
     /// ```
@@ -72,7 +93,71 @@ impl LocalBackend {
             .layer(RetryLayer::default())
             .finish();
 
-        Ok(Self { name: name.into(), operator })
+        Ok(Self { name: name.into(), root, operator })
+    }
+
+    /// The path on disk of `path`, which must be valid.
+    fn local_path(&self, path: &Path) -> Result<PathBuf> {
+        let validated_path = ValidatedPath::new(path)?;
+        Ok(self.root.join(validated_path.as_str()))
+    }
+}
+
+/// Creates a new file at `local` (the path on disk of `path`) holding `data`,
+/// unless there's a file there already.
+///
+/// The data is written to a temporary file beside it first, which is then
+/// moved into place only if the path is still free (with `renameat2`'s
+/// `RENAME_NOREPLACE`, or a hard link): only one caller can create a file,
+/// and one that crashes part-way leaves a temporary file behind, never an
+/// empty or partial file at the path.
+fn create_new(local: &Path, path: &Path, data: &[u8]) -> Result<()> {
+    let parent = local.parent().ok_or_else(|| ErrorKind::InvalidPath(path.to_path_buf()))?;
+    sync_create_dir(parent).map_err(ErrorKind::Io)?;
+    let mut temp =
+        tempfile::Builder::new().prefix(".rawr-").suffix(".tmp").tempfile_in(parent).map_err(ErrorKind::Io)?;
+    temp.write_all(data).map_err(ErrorKind::Io)?;
+    // The temporary file is deleted (by its destructor) if it can't be moved.
+    temp.persist_noclobber(local).map_err(|e| match e.error.kind() {
+        io::ErrorKind::AlreadyExists => ErrorKind::AlreadyExists(path.to_path_buf()),
+        io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied(path.to_path_buf()),
+        _ => ErrorKind::Io(e.error),
+    })?;
+    Ok(())
+}
+
+/// Streamed writer of a new file: buffers everything written in memory, and
+/// [creates](create_new) the file when closed.
+struct NewFileWriter {
+    local: PathBuf,
+    path: PathBuf,
+    buffer: Vec<u8>,
+    closed: bool,
+}
+impl AsyncWrite for NewFileWriter {
+    fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.closed {
+            return Poll::Ready(Err(io::Error::new(io::ErrorKind::BrokenPipe, "writer is closed")));
+        }
+        this.buffer.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if std::mem::replace(&mut this.closed, true) {
+            return Poll::Ready(Ok(()));
+        }
+        let data = std::mem::take(&mut this.buffer);
+        Poll::Ready(create_new(&this.local, &this.path, &data).map_err(|e| match &*e {
+            ErrorKind::AlreadyExists(_) => io::Error::new(io::ErrorKind::AlreadyExists, e.to_string()),
+            _ => io::Error::other(e.to_string()),
+        }))
     }
 }
 
@@ -86,6 +171,67 @@ impl StorageBackend for LocalBackend {
     fn name(&self) -> &str {
         &self.name
     }
+
+    async fn write_if_absent(&self, path: &Path, data: &[u8]) -> Result<()> {
+        tracing::trace!(backend = self.name(), path = %path.display(), bytes = data.len(), "write new file to storage backend");
+        create_new(&self.local_path(path)?, path, data)
+    }
+
+    async fn writer_if_absent(&self, path: &Path) -> Result<BoxedWriter> {
+        tracing::trace!(backend = self.name(), path = %path.display(), "open writer to new file in storage backend");
+        // Checked now to fail early, but only enforced as the writer closes.
+        let local = self.local_path(path)?;
+        if local.try_exists().map_err(ErrorKind::Io)? {
+            exn::bail!(ErrorKind::AlreadyExists(path.to_path_buf()));
+        }
+        Ok(Box::new(NewFileWriter {
+            local,
+            path: path.to_path_buf(),
+            buffer: Vec::new(),
+            closed: false,
+        }))
+    }
+
+    #[cfg(unix)]
+    async fn available_space(&self) -> Result<Option<u64>> {
+        let stat = rustix::fs::statvfs(&self.root).map_err(|e| ErrorKind::Io(e.into()))?;
+        // Blocks free to unprivileged users, not counting those reserved for root.
+        Ok(Some(stat.f_bavail.saturating_mul(stat.f_frsize)))
+    }
+
+    #[cfg(all(feature = "mmap", unix))]
+    async fn read_contents(&self, path: &Path) -> Result<Contents> {
+        tracing::trace!(backend = self.name(), path = %path.display(), "memory-map file from storage backend");
+        let validated_path = ValidatedPath::new(path)?;
+        let map_error = |e: std::io::Error| match e.kind() {
+            std::io::ErrorKind::NotFound => ErrorKind::NotFound(path.to_path_buf()),
+            std::io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied(path.to_path_buf()),
+            _ => ErrorKind::Io(e),
+        };
+        // Opening and mapping don't read any file data, so there's little
+        // point in moving these onto a blocking thread.
+        let file = std::fs::File::open(self.root.join(validated_path.as_str())).map_err(map_error)?;
+        let metadata = file.metadata().map_err(map_error)?;
+        if metadata.len() < MMAP_THRESHOLD || !is_immutable(&file, &metadata) {
+            return self.read(path).await.map(Contents::from);
+        }
+        // SAFETY: the mapping is read-only and lives only as long as the
+        // returned `Contents`, and the file can't be truncated (nor written
+        // to) in the meantime: nobody has permission to write to it, or it's
+        // on a read-only mount. Only its owner (or root) changing the file's
+        // permissions first, or remounting, could still pull it from under us.
+        let map = unsafe { memmap2::Mmap::map(&file) }.map_err(map_error)?;
+        Ok(Contents::mapped(map))
+    }
+}
+
+/// Whether a file can't change while it's mapped: nobody has permission to
+/// write to it, or it's on a read-only mount.
+#[cfg(all(feature = "mmap", unix))]
+fn is_immutable(file: &std::fs::File, metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o222 == 0
+        || rustix::fs::fstatvfs(file).is_ok_and(|stat| stat.f_flag.contains(rustix::fs::StatVfsMountFlags::RDONLY))
 }
 
 #[cfg(test)]
@@ -136,6 +282,44 @@ mod tests {
         assert!(backend.exists(Path::new("a/b/c/file.txt")).await.unwrap());
     }
 
+    #[tokio::test]
+    async fn test_write_if_absent() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backend = LocalBackend::new("name", temp_dir.path(), false).unwrap();
+        let path = Path::new("a/b/new.txt");
+        backend.write_if_absent(path, b"first").await.unwrap();
+        let err = backend.write_if_absent(path, b"second").await.unwrap_err();
+        assert!(matches!(&*err, ErrorKind::AlreadyExists(p) if p == path));
+        assert_eq!(b"first".to_vec(), backend.read(path).await.unwrap());
+
+        // Of many writers racing for the same path, exactly one wins.
+        let backend = &backend;
+        let writes = (0..8u8).map(|i| async move { backend.write_if_absent(Path::new("race.txt"), &[i]).await });
+        let results = futures::future::join_all(writes).await;
+        assert_eq!(1, results.iter().filter(|r| r.is_ok()).count());
+        let winner = results.iter().position(Result::is_ok).unwrap() as u8;
+        assert_eq!(vec![winner], backend.read(Path::new("race.txt")).await.unwrap());
+        // The losers' temporary files are cleaned up.
+        assert_eq!(2, std::fs::read_dir(temp_dir.path()).unwrap().count());
+    }
+
+    #[tokio::test]
+    async fn test_writer_if_absent() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backend = LocalBackend::new("name", temp_dir.path(), false).unwrap();
+        let mut writer = backend.writer_if_absent(Path::new("new.txt")).await.unwrap();
+        let mut late = backend.writer_if_absent(Path::new("new.txt")).await.unwrap();
+        writer.write_all(b"streamed").await.unwrap();
+        writer.close().await.unwrap();
+        assert!(backend.writer_if_absent(Path::new("new.txt")).await.is_err());
+        // The file was created after the second writer was opened.
+        late.write_all(b"late").await.unwrap();
+        assert_eq!(io::ErrorKind::AlreadyExists, late.close().await.unwrap_err().kind());
+        assert_eq!(b"streamed".to_vec(), backend.read(Path::new("new.txt")).await.unwrap());
+        // Nothing is left behind under a temporary name.
+        assert_eq!(1, std::fs::read_dir(temp_dir.path()).unwrap().count());
+    }
+
     #[tokio::test]
     async fn test_exists() {
         let temp_dir = tempfile::tempdir().unwrap();
@@ -293,4 +477,31 @@ mod tests {
         let data = backend.read(Path::new("file.txt")).await.unwrap();
         assert_eq!(data, b"hello world");
     }
+
+    #[tokio::test]
+    async fn test_read_contents() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backend = LocalBackend::new("name", temp_dir.path(), false).unwrap();
+        let large = vec![b'x'; 128 * 1024];
+        backend.write(Path::new("small.txt"), b"small").await.unwrap();
+        backend.write(Path::new("large.txt"), &large).await.unwrap();
+
+        let small = backend.read_contents(Path::new("small.txt")).await.unwrap();
+        assert_eq!(&*small, b"small");
+        assert!(!small.is_mapped());
+        // Files that could be truncated while mapped are copied instead.
+        let contents = backend.read_contents(Path::new("large.txt")).await.unwrap();
+        assert_eq!(&*contents, &large[..]);
+        assert!(!contents.is_mapped());
+        let file = temp_dir.path().join("large.txt");
+        let mut permissions = std::fs::metadata(&file).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&file, permissions).unwrap();
+        let contents = backend.read_contents(Path::new("large.txt")).await.unwrap();
+        assert_eq!(&*contents, &large[..]);
+        assert_eq!(contents.is_mapped(), cfg!(all(feature = "mmap", unix)));
+
+        let err = backend.read_contents(Path::new("missing.txt")).await.unwrap_err();
+        assert!(matches!(&*err, ErrorKind::NotFound(_)));
+    }
 }
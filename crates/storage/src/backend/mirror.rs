@@ -0,0 +1,424 @@
+//! Mirroring storage backend decorator.
+//!
+//! This module provides a storage backend implementation that wraps a primary
+//! backend and one or more replicas, applying every change made through it to
+//! all of them, so the replicas are kept as live backups of the primary
+//! without a separate sync pass.
+
+use crate::backend::{BoxedReader, BoxedWriter, ChunkedBackend, FileInfoStream, OperatorAware, attribute_list_errors};
+use crate::chunk::ChunkIndex;
+use crate::error::{ErrorKind, Result};
+use crate::{BackendHandle, Contents, ObjectTags, StorageBackend, file::FileInfo};
+use async_trait::async_trait;
+use futures::future::join_all;
+use opendal::Operator;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// What a [`MirrorBackend`] does when a change made to the primary can't be
+/// made to a replica.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReplicaPolicy {
+    /// Return a [`ReplicaFailed`](ErrorKind::ReplicaFailed) error. The change
+    /// has still been made to the primary (and any other replicas).
+    #[default]
+    Fail,
+    /// Log a warning, and queue the affected paths to be copied to the
+    /// replica later by [`MirrorBackend::replicate_pending()`].
+    Queue,
+    /// Log a warning, and leave the replica out of date.
+    Warn,
+}
+
+/// Mirroring storage backend.
+///
+/// Reads go to the primary backend only. Writes, deletes and renames are made
+/// to the primary first and, once they succeed there, to every replica (all
+/// at once). What happens when a replica fails depends on the
+/// [`ReplicaPolicy`].
+///
+/// # Notes
+/// - Replicas are assumed to hold the same files as the primary: a file
+///   deleted from the primary that was never on a replica isn't an error,
+///   and renaming one copies it to the replica from the primary instead.
+/// - Streamed writes through [`writer()`](StorageBackend::writer) go to the
+///   primary only; whatever the policy, the path is queued to be copied to
+///   the replicas by [`replicate_pending()`](Self::replicate_pending) once
+///   the writer is closed.
+/// - The queue is kept in memory: paths still queued when the backend is
+///   dropped are only brought up to date by syncing the replicas some other
+///   way.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rawr_storage::BackendHandle;
+/// use rawr_storage::backend::{MirrorBackend, ReplicaPolicy};
+///
+/// # async fn example(local: BackendHandle, s3: BackendHandle) {
+/// // Back up the local library to S3 as it changes, catching up on
+/// // anything S3 missed while it was unreachable.
+/// let backend = MirrorBackend::new(local, [s3]).with_policy(ReplicaPolicy::Queue);
+/// // ...
+/// let still_pending = backend.replicate_pending().await;
+/// # }
+/// ```
+pub struct MirrorBackend {
+    primary: BackendHandle,
+    replicas: Vec<BackendHandle>,
+    policy: ReplicaPolicy,
+    /// Paths (with the index of the replica) to copy from the primary.
+    pending: Mutex<BTreeSet<(usize, PathBuf)>>,
+}
+impl MirrorBackend {
+    pub fn new(primary: BackendHandle, replicas: impl IntoIterator<Item = BackendHandle>) -> Self {
+        Self {
+            primary,
+            replicas: replicas.into_iter().collect(),
+            policy: ReplicaPolicy::default(),
+            pending: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: ReplicaPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Stores files on every replica as [content-defined chunks](ChunkedBackend),
+    /// so that bringing a replica up to date after a small edit to a large
+    /// file only uploads the chunks that changed. Which chunks a replica
+    /// already has are looked up in `index` (such as the cache), if given.
+    ///
+    /// Replicas must only ever be written chunked (they hold manifests, not
+    /// the files themselves), so this is for replicas that start out empty.
+    pub fn with_chunked_replicas(mut self, index: Option<Arc<dyn ChunkIndex>>) -> Self {
+        self.replicas = std::mem::take(&mut self.replicas)
+            .into_iter()
+            .map(|replica| {
+                let chunked = ChunkedBackend::new(replica);
+                let chunked = match &index {
+                    Some(index) => chunked.with_index(index.clone()),
+                    None => chunked,
+                };
+                Arc::new(chunked) as BackendHandle
+            })
+            .collect();
+        self
+    }
+
+    /// Number of paths waiting to be copied to a replica (counted once per
+    /// replica).
+    pub fn pending(&self) -> usize {
+        self.queue().len()
+    }
+
+    /// Copies each queued path from the primary to the replica it's queued
+    /// for (or deletes it from the replica, if it's no longer on the
+    /// primary). Paths that fail are queued again. Returns the number of
+    /// paths still pending.
+    pub async fn replicate_pending(&self) -> usize {
+        let pending = std::mem::take(&mut *self.queue());
+        let results = join_all(pending.iter().map(|(i, path)| self.sync(&self.replicas[*i], path))).await;
+        let mut failed = Vec::new();
+        for ((i, path), result) in pending.into_iter().zip(results) {
+            if let Err(e) = result {
+                tracing::debug!(
+                    backend = self.primary.name(),
+                    replica = self.replicas[i].name(),
+                    path = %path.display(),
+                    error = %e,
+                    "Replicating queued path failed"
+                );
+                failed.push((i, path));
+            }
+        }
+        let mut queue = self.queue();
+        queue.extend(failed);
+        queue.len()
+    }
+
+    fn queue(&self) -> MutexGuard<'_, BTreeSet<(usize, PathBuf)>> {
+        // The queue is never left in an inconsistent state mid-update.
+        self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Makes a replica's copy of `path` match the primary's.
+    async fn sync(&self, replica: &BackendHandle, path: &Path) -> Result<()> {
+        match self.primary.read(path).await {
+            Ok(data) => replica.write(path, &data).await,
+            Err(e) if matches!(&*e, ErrorKind::NotFound(_)) => Self::delete_from(replica, path).await,
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Deletes a path from a replica, if it's there.
+    async fn delete_from(replica: &BackendHandle, path: &Path) -> Result<()> {
+        match replica.delete(path).await {
+            Err(e) if matches!(&*e, ErrorKind::NotFound(_)) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Makes a change (already made to the primary) to every replica,
+    /// handling failures according to the policy. `paths` are those the
+    /// change affects, queued for replicas it fails on.
+    async fn replicate<'a, F, Fut>(&'a self, operation: &'static str, paths: &[&Path], change: F) -> Result<()>
+    where
+        F: Fn(&'a BackendHandle) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let results = join_all(self.replicas.iter().map(change)).await;
+        let mut failure = None;
+        for (i, result) in results.into_iter().enumerate() {
+            let Err(e) = result else {
+                continue;
+            };
+            let replica = self.replicas[i].name();
+            tracing::warn!(
+                backend = self.primary.name(),
+                replica,
+                operation,
+                path = %paths[0].display(),
+                error = %e,
+                policy = ?self.policy,
+                "Replicating change failed"
+            );
+            match self.policy {
+                ReplicaPolicy::Fail => {
+                    failure.get_or_insert_with(|| e.raise(ErrorKind::ReplicaFailed(replica.to_string())));
+                },
+                ReplicaPolicy::Queue => self.queue().extend(paths.iter().map(|path| (i, path.to_path_buf()))),
+                ReplicaPolicy::Warn => {},
+            }
+        }
+        failure.map_or(Ok(()), Err)
+    }
+}
+impl OperatorAware for MirrorBackend {
+    fn operator(&self) -> &Operator {
+        self.primary.operator()
+    }
+}
+#[async_trait]
+impl StorageBackend for MirrorBackend {
+    fn name(&self) -> &str {
+        self.primary.name()
+    }
+
+    fn list_stream<'a>(&'a self, prefix: Option<&'a Path>) -> Result<FileInfoStream<'a>> {
+        Ok(attribute_list_errors(self.name(), self.primary.list_stream(prefix)?))
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.primary.exists(path).await
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.primary.read(path).await
+    }
+
+    async fn read_contents(&self, path: &Path) -> Result<Contents> {
+        self.primary.read_contents(path).await
+    }
+
+    async fn read_head(&self, path: &Path, bytes: usize) -> Result<Vec<u8>> {
+        self.primary.read_head(path, bytes).await
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.primary.write(path, data).await?;
+        self.replicate("write", &[path], |replica| replica.write(path, data)).await
+    }
+
+    /// Only the primary decides whether the file is new: replicas are
+    /// overwritten to match it.
+    async fn write_if_absent(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.primary.write_if_absent(path, data).await?;
+        self.replicate("write", &[path], |replica| replica.write(path, data)).await
+    }
+
+    async fn delete(&self, path: &Path) -> Result<()> {
+        self.primary.delete(path).await?;
+        self.replicate("delete", &[path], |replica| Self::delete_from(replica, path)).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.primary.rename(from, to).await?;
+        self.replicate("rename", &[from, to], |replica| async move {
+            match replica.rename(from, to).await {
+                Err(e) if matches!(&*e, ErrorKind::NotFound(_)) => self.sync(replica, to).await,
+                result => result,
+            }
+        })
+        .await
+    }
+
+    async fn tag(&self, path: &Path, tags: &ObjectTags) -> Result<()> {
+        self.primary.tag(path, tags).await?;
+        self.replicate("tag", &[path], |replica| replica.tag(path, tags)).await
+    }
+
+    async fn available_space(&self) -> Result<Option<u64>> {
+        // Everything written to the primary is copied to every replica, so
+        // whichever has the least room decides.
+        let mut available = self.primary.available_space().await?;
+        for replica in &self.replicas {
+            if let Some(space) = replica.available_space().await? {
+                available = Some(available.map_or(space, |a| a.min(space)));
+            }
+        }
+        Ok(available)
+    }
+
+    async fn stat(&self, path: &Path) -> Result<FileInfo> {
+        self.primary.stat(path).await
+    }
+
+    async fn reader(&self, path: &Path) -> Result<BoxedReader> {
+        self.primary.reader(path).await
+    }
+
+    async fn writer(&self, path: &Path) -> Result<BoxedWriter> {
+        let writer = self.primary.writer(path).await?;
+        self.queue().extend((0..self.replicas.len()).map(|i| (i, path.to_path_buf())));
+        Ok(writer)
+    }
+
+    async fn writer_if_absent(&self, path: &Path) -> Result<BoxedWriter> {
+        let writer = self.primary.writer_if_absent(path).await?;
+        self.queue().extend((0..self.replicas.len()).map(|i| (i, path.to_path_buf())));
+        Ok(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Fails every change while `down`.
+    struct UnreachableBackend {
+        inner: MockBackend,
+        down: AtomicBool,
+    }
+    impl UnreachableBackend {
+        fn check(&self) -> Result<()> {
+            if self.down.load(Ordering::SeqCst) {
+                exn::bail!(ErrorKind::Network("connection refused".to_string()));
+            }
+            Ok(())
+        }
+    }
+    impl OperatorAware for UnreachableBackend {
+        fn operator(&self) -> &Operator {
+            self.inner.operator()
+        }
+    }
+    #[async_trait]
+    impl StorageBackend for UnreachableBackend {
+        fn name(&self) -> &str {
+            "replica"
+        }
+
+        async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+            self.check()?;
+            self.inner.write(path, data).await
+        }
+
+        async fn delete(&self, path: &Path) -> Result<()> {
+            self.check()?;
+            self.inner.delete(path).await
+        }
+
+        async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+            self.check()?;
+            self.inner.rename(from, to).await
+        }
+    }
+
+    fn setup(policy: ReplicaPolicy) -> (Arc<MockBackend>, Arc<UnreachableBackend>, MirrorBackend) {
+        let primary = Arc::new(MockBackend::with_data([("a.html", "aaaa")]));
+        let replica = Arc::new(UnreachableBackend {
+            inner: MockBackend::with_data([("a.html", "aaaa")]),
+            down: AtomicBool::new(false),
+        });
+        let backend = MirrorBackend::new(primary.clone(), [replica.clone() as BackendHandle]).with_policy(policy);
+        (primary, replica, backend)
+    }
+
+    async fn read(backend: &dyn StorageBackend, path: &str) -> Option<Vec<u8>> {
+        backend.read(Path::new(path)).await.ok()
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_mirrored() {
+        let (primary, replica, backend) = setup(ReplicaPolicy::Fail);
+        backend.write(Path::new("b.html"), b"bbbb").await.unwrap();
+        backend.rename(Path::new("a.html"), Path::new("c.html")).await.unwrap();
+        backend.delete(Path::new("b.html")).await.unwrap();
+        for mirror in [&*primary as &dyn StorageBackend, &*replica] {
+            assert_eq!(None, read(mirror, "a.html").await);
+            assert_eq!(None, read(mirror, "b.html").await);
+            assert_eq!(Some(b"aaaa".to_vec()), read(mirror, "c.html").await);
+        }
+
+        // A file the replica never had is copied to it when renamed.
+        primary.write(Path::new("d.html"), b"dddd").await.unwrap();
+        backend.rename(Path::new("d.html"), Path::new("e.html")).await.unwrap();
+        assert_eq!(Some(b"dddd".to_vec()), read(&*replica, "e.html").await);
+    }
+
+    #[tokio::test]
+    async fn test_replica_failure_policies() {
+        let (primary, replica, backend) = setup(ReplicaPolicy::Fail);
+        replica.down.store(true, Ordering::SeqCst);
+        let err = backend.write(Path::new("b.html"), b"bbbb").await.unwrap_err();
+        assert!(matches!(&*err, ErrorKind::ReplicaFailed(name) if name == "replica"));
+        assert_eq!(Some(b"bbbb".to_vec()), read(&*primary, "b.html").await);
+        assert_eq!(0, backend.pending());
+
+        let backend = backend.with_policy(ReplicaPolicy::Warn);
+        backend.delete(Path::new("b.html")).await.unwrap();
+        assert_eq!(0, backend.pending());
+
+        let backend = backend.with_policy(ReplicaPolicy::Queue);
+        backend.write(Path::new("b.html"), b"new").await.unwrap();
+        backend.rename(Path::new("a.html"), Path::new("c.html")).await.unwrap();
+        assert_eq!(3, backend.pending());
+        assert_eq!(3, backend.replicate_pending().await);
+        replica.down.store(false, Ordering::SeqCst);
+        assert_eq!(0, backend.replicate_pending().await);
+        assert_eq!(Some(b"new".to_vec()), read(&*replica, "b.html").await);
+        assert_eq!(None, read(&*replica, "a.html").await);
+        assert_eq!(Some(b"aaaa".to_vec()), read(&*replica, "c.html").await);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_replicas() {
+        let primary: BackendHandle = Arc::new(MockBackend::default());
+        let replica: BackendHandle = Arc::new(MockBackend::default());
+        let backend = MirrorBackend::new(primary, [replica.clone()]).with_chunked_replicas(None);
+        let chunks = async || replica.list(Some(Path::new(".chunks"))).await.unwrap().len();
+        let mut state = 3226u64;
+        let mut data: Vec<u8> = (0..512 * 1024)
+            .map(|_| {
+                state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+                (state >> 56) as u8
+            })
+            .collect();
+
+        backend.write(Path::new("a.html"), &data).await.unwrap();
+        let stored = chunks().await;
+        assert!(stored > 1);
+        assert_eq!(Some(data.clone()), read(&ChunkedBackend::new(replica.clone()), "a.html").await);
+
+        // Replicating a small edit uploads a chunk or two, not the whole file.
+        data[300_000..300_010].copy_from_slice(b"0123456789");
+        backend.write(Path::new("a.html"), &data).await.unwrap();
+        assert!(chunks().await - stored <= 2);
+        assert_eq!(Some(data), read(&ChunkedBackend::new(replica), "a.html").await);
+    }
+}
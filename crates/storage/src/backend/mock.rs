@@ -25,7 +25,7 @@ use std::{fs::File, io::Read};
 /// use rawr_storage::backend::{MockBackend, StorageBackend};
 /// use std::path::Path;
 ///
-/// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
 /// let backend = MockBackend::with_data([
 ///     ("works/123.html.gz", b"<html>...</html>"),
 /// ]);
@@ -183,6 +183,16 @@ mod tests {
         assert!(!backend.exists(Path::new("c/nope")).await.unwrap());
     }
 
+    #[tokio::test]
+    async fn test_write_if_absent() {
+        let backend = MockBackend::with_data([("taken.txt", Vec::from(*b"original"))]);
+        let err = backend.write_if_absent(Path::new("taken.txt"), b"replaced").await.unwrap_err();
+        assert!(matches!(&*err, ErrorKind::AlreadyExists(_)));
+        assert_eq!(b"original".to_vec(), backend.read(Path::new("taken.txt")).await.unwrap());
+        backend.write_if_absent(Path::new("free.txt"), b"new").await.unwrap();
+        assert_eq!(b"new".to_vec(), backend.read(Path::new("free.txt")).await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_read_not_found() {
         let backend = MockBackend::default();
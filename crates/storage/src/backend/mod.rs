@@ -5,26 +5,41 @@
 //! S3-compatible services, etc.).
 //!
 
+mod cache;
+mod chunked;
+mod dry_run;
 mod html;
+mod ignore;
 mod local;
+mod mirror;
 #[cfg(feature = "mock")]
 mod mock;
 mod opendal_util;
-mod ro;
+mod quota;
+mod retry;
 #[cfg(feature = "s3")]
 mod s3;
+mod transaction;
 
+pub use self::cache::CachingBackend;
+pub use self::chunked::ChunkedBackend;
+pub use self::dry_run::DryRunBackend;
 pub use self::html::HtmlOnlyBackend;
+pub use self::ignore::{DEFAULT_IGNORE_PATTERNS, IgnoreBackend, IgnorePatterns};
 pub use self::local::LocalBackend;
+pub use self::mirror::{MirrorBackend, ReplicaPolicy};
 #[cfg(feature = "mock")]
 pub use self::mock::MockBackend;
-use self::opendal_util::{map_opendal_error, metadata_to_file_info};
-pub use self::ro::ReadOnlyBackend;
+use self::opendal_util::{map_conditional_error, map_opendal_error, metadata_to_file_info};
+pub use self::quota::QuotaBackend;
+pub use self::retry::{RetryBackend, RetryPolicy};
 #[cfg(feature = "s3")]
 pub use self::s3::S3Backend;
-use crate::error::{ErrorKind, Result};
+pub use self::transaction::BackendTransaction;
+use crate::error::{Error, ErrorKind, Result};
 use crate::file::FileInfo;
 use crate::path::ValidatedPath;
+use crate::{Contents, ObjectTags};
 use async_stream::stream;
 use async_trait::async_trait;
 use futures::io::{AsyncRead, AsyncWrite};
@@ -33,8 +48,30 @@ use opendal::Operator;
 use std::path::Path;
 use std::pin::Pin;
 
+/// The [`DryRunBackend`]'s old name.
+#[deprecated(note = "renamed to `DryRunBackend`")]
+pub type ReadOnlyBackend = DryRunBackend;
+
 type FileInfoStream<'a> = Pin<Box<dyn Stream<Item = Result<FileInfo>> + Send + 'a>>;
 
+/// Attributes errors in a listing of the backend named `backend` to it, as
+/// [`ErrorKind::Listing`] (with the path each happened at, where known), so
+/// that they're still traceable to where they came from once decorators are
+/// stacked on top. Errors already attributed, by a backend further down the
+/// stack, are passed on untouched.
+pub(crate) fn attribute_list_errors<'a>(backend: &'a str, stream: FileInfoStream<'a>) -> FileInfoStream<'a> {
+    Box::pin(stream.map(move |item| item.map_err(|e| attribute_list_error(backend, e))))
+}
+
+#[track_caller]
+fn attribute_list_error(backend: &str, e: Error) -> Error {
+    if matches!(&*e, ErrorKind::Listing { .. }) {
+        return e;
+    }
+    let path = e.path().map(Path::to_path_buf);
+    e.raise(ErrorKind::Listing { backend: backend.to_string(), path })
+}
+
 /// Boxed async reader returned by [`StorageBackend::reader()`].
 pub type BoxedReader = Box<dyn AsyncRead + Unpin + Send + 'static>;
 /// Boxed async writer returned by [`StorageBackend::writer()`].
@@ -101,6 +138,9 @@ pub trait StorageBackend: OperatorAware + Send + Sync {
     /// # Notes
     /// - the `prefix` argument may have varying behaviour depending
     ///   on the storage backend implementation used.
+    /// - errors in the stream are [`ErrorKind::Listing`], naming the backend
+    ///   they came from, with the underlying error as their child;
+    ///   decorators pass them on as they are.
     /// - [`list()`](Self::list) is a convenience wrapper that collects this
     ///   stream into a [`Vec`] via [`TryStreamExt`](futures::TryStreamExt::try_collect)
     ///   before returning all at once.
@@ -134,7 +174,7 @@ pub trait StorageBackend: OperatorAware + Send + Sync {
     ///     .await?;
     ///
     /// // Prefixes shouldn't attempt to break storage
-    /// assert!(backend.list_stream(Some("../../etc/passwd")).is_err());
+    /// assert!(backend.list_stream(Some(Path::new("../../etc/passwd"))).is_err());
     /// # Ok(())
     /// # }
     /// ```
@@ -151,34 +191,37 @@ pub trait StorageBackend: OperatorAware + Send + Sync {
             .map(|p| format!("{}/", p.as_str().trim_end_matches('/')))
             .unwrap_or_else(|| "/".to_string());
 
-        Ok(Box::pin(stream! {
-            let mut lister = match self.operator().lister_with(&opendal_prefix).recursive(true).await {
-                Ok(l) => l,
-                Err(e) if matches!(e.kind(), opendal::ErrorKind::NotFound) => return,
-                Err(e) => {
-                    yield Err(exn::Exn::from(map_opendal_error(e, Path::new(&opendal_prefix))));
-                    return;
-                },
-            };
-            while let Some(entry_result) = lister.next().await {
-                match entry_result {
-                    Ok(entry) => {
-                        let path_str = entry.path();
-                        if path_str.ends_with('/') { continue; }
-                        let relative = match ValidatedPath::new(path_str) {
-                            Ok(p) => p,
-                            Err(e) => { yield Err(e); continue; }
-                        };
-                        if let Some(pfx) = &validated_prefix && !relative.as_str().starts_with(pfx.as_str()) { continue; }
-                        yield Ok(metadata_to_file_info(self.name(), relative.into(), entry.metadata()));
-                    },
-                    Err(e) if !matches!(e.kind(), opendal::ErrorKind::NotFound) => {
+        Ok(attribute_list_errors(
+            self.name(),
+            Box::pin(stream! {
+                let mut lister = match self.operator().lister_with(&opendal_prefix).recursive(true).await {
+                    Ok(l) => l,
+                    Err(e) if matches!(e.kind(), opendal::ErrorKind::NotFound) => return,
+                    Err(e) => {
                         yield Err(exn::Exn::from(map_opendal_error(e, Path::new(&opendal_prefix))));
+                        return;
                     },
-                    Err(_) => continue,
+                };
+                while let Some(entry_result) = lister.next().await {
+                    match entry_result {
+                        Ok(entry) => {
+                            let path_str = entry.path();
+                            if path_str.ends_with('/') { continue; }
+                            let relative = match ValidatedPath::new(path_str) {
+                                Ok(p) => p,
+                                Err(e) => { yield Err(e); continue; }
+                            };
+                            if let Some(pfx) = &validated_prefix && !relative.as_str().starts_with(pfx.as_str()) { continue; }
+                            yield Ok(metadata_to_file_info(self.name(), relative.into(), entry.metadata()));
+                        },
+                        Err(e) if !matches!(e.kind(), opendal::ErrorKind::NotFound) => {
+                            yield Err(exn::Exn::from(map_opendal_error(e, Path::new(&opendal_prefix))));
+                        },
+                        Err(_) => continue,
+                    }
                 }
-            }
-        }))
+            }),
+        ))
     }
 
     /// Check if a file exists.
@@ -225,6 +268,31 @@ pub trait StorageBackend: OperatorAware + Send + Sync {
         Ok(data.to_vec())
     }
 
+    /// Read file contents, avoiding a copy where the backend supports it.
+    ///
+    /// Like [`read()`](Self::read), but returns [`Contents`] which may be a
+    /// memory map of the file instead of an owned buffer. Prefer this for
+    /// reading whole files that are only needed briefly (hashing, extraction).
+    ///
+    /// The default implementation wraps [`read()`](Self::read); only the
+    /// [`LocalBackend`] memory-maps files, and only with the `mmap` feature
+    /// enabled on unix platforms.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// # use rawr_storage::{backend::StorageBackend, error::Result};
+    /// # async fn example(backend: &dyn StorageBackend) -> Result<()> {
+    /// let contents = backend.read_contents(Path::new("work.html.bz2")).await?;
+    /// println!("Read {} bytes (memory-mapped: {})", contents.len(), contents.is_mapped());
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn read_contents(&self, path: &Path) -> Result<Contents> {
+        self.read(path).await.map(Contents::from)
+    }
+
     /// Read only the first N bytes (for magic byte detection).
     ///
     /// This is useful for detecting file formats without reading the entire
@@ -288,6 +356,47 @@ pub trait StorageBackend: OperatorAware + Send + Sync {
         Ok(())
     }
 
+    /// Write file contents, but only if no file exists at the path yet.
+    ///
+    /// Returns [`AlreadyExists`](crate::error::ErrorKind::AlreadyExists),
+    /// leaving the existing file as it was, if one does. Unlike checking
+    /// [`exists()`](Self::exists) before [`write()`](Self::write), two
+    /// writers can't both succeed: where the backend supports conditional
+    /// writes (`If-None-Match` on S3, a no-replace rename on the local filesystem), the
+    /// check and the write are one operation.
+    ///
+    /// # Notes
+    /// - Backends without conditional writes (e.g. in memory) fall back to
+    ///   checking first, which can race.
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// # use rawr_storage::{backend::StorageBackend, error::{ErrorKind, Result}};
+    /// # async fn example(backend: &dyn StorageBackend) -> Result<()> {
+    /// match backend.write_if_absent(Path::new("work.html"), b"<html>...</html>").await {
+    ///     Err(e) if matches!(&*e, ErrorKind::AlreadyExists(_)) => println!("Someone got there first"),
+    ///     result => result?,
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn write_if_absent(&self, path: &Path, data: &[u8]) -> Result<()> {
+        tracing::trace!(backend = self.name(), path = %path.display(), bytes = data.len(), "write new file to storage backend");
+        let validated_path = ValidatedPath::new(path)?;
+        if !self.operator().info().full_capability().write_with_if_not_exists {
+            if self.exists(path).await? {
+                exn::bail!(ErrorKind::AlreadyExists(path.to_path_buf()));
+            }
+            return self.write(path, data).await;
+        }
+        self.operator()
+            .write_with(validated_path.as_str(), data.to_vec())
+            .if_not_exists(true)
+            .await
+            .map_err(|e| map_conditional_error(e, path))?;
+        Ok(())
+    }
+
     /// Delete a file.
     ///
     /// Returns [`NotFound`](crate::error::ErrorKind::NotFound) if the file
@@ -347,6 +456,54 @@ pub trait StorageBackend: OperatorAware + Send + Sync {
         Ok(())
     }
 
+    /// Attach object tags to a file, replacing any it had.
+    ///
+    /// Only object storage has tags: the default implementation ignores
+    /// them. Backends that support them return
+    /// [`NotFound`](crate::error::ErrorKind::NotFound) if the file does not
+    /// exist.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use rawr_storage::ObjectTags;
+    /// # use rawr_storage::{backend::StorageBackend, error::Result};
+    /// # async fn example(backend: &dyn StorageBackend) -> Result<()> {
+    /// let mut tags = ObjectTags::new();
+    /// tags.insert("work_id", "12345")?;
+    /// backend.tag(Path::new("work.html.bz2"), &tags).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn tag(&self, path: &Path, tags: &ObjectTags) -> Result<()> {
+        tracing::trace!(backend = self.name(), path = %path.display(), tags = tags.len(), "ignore object tags in storage backend without them");
+        ValidatedPath::new(path)?;
+        Ok(())
+    }
+
+    /// Space left for new files, in bytes, if the backend knows.
+    ///
+    /// The default implementation doesn't: object storage has no limit to
+    /// speak of. The local filesystem reports the space free on its volume,
+    /// and a [`QuotaBackend`] whatever is left of its quota.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use rawr_storage::{backend::StorageBackend, error::Result};
+    /// # async fn example(backend: &dyn StorageBackend) -> Result<()> {
+    /// if let Some(available) = backend.available_space().await? {
+    ///     println!("{available} bytes available");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn available_space(&self) -> Result<Option<u64>> {
+        tracing::trace!(backend = self.name(), "no space limit known for storage backend");
+        Ok(None)
+    }
+
     /// Get file metadata without reading contents.
     ///
     /// Returns [`NotFound`](crate::error::ErrorKind::NotFound) if the file
@@ -396,4 +553,32 @@ pub trait StorageBackend: OperatorAware + Send + Sync {
         let writer = self.operator().writer(validated_path.as_str()).await.map_err(|e| map_opendal_error(e, path))?;
         Ok(Box::new(writer.into_futures_async_write()))
     }
+
+    /// Open a streaming writer for a new file, like [`writer()`](Self::writer)
+    /// but with the guarantees of [`write_if_absent()`](Self::write_if_absent).
+    ///
+    /// # Notes
+    /// - On S3 the condition is only checked as the upload completes, so a
+    ///   file created in the meantime fails the writer's `close()` rather
+    ///   than this call.
+    /// - On the local filesystem, the file is buffered in memory and only
+    ///   created as the writer closes, which is when a file created in the
+    ///   meantime is found too.
+    async fn writer_if_absent(&self, path: &Path) -> Result<BoxedWriter> {
+        tracing::trace!(backend = self.name(), path = %path.display(), "open writer to new file in storage backend");
+        let validated_path = ValidatedPath::new(path)?;
+        if !self.operator().info().full_capability().write_with_if_not_exists {
+            if self.exists(path).await? {
+                exn::bail!(ErrorKind::AlreadyExists(path.to_path_buf()));
+            }
+            return self.writer(path).await;
+        }
+        let writer = self
+            .operator()
+            .writer_with(validated_path.as_str())
+            .if_not_exists(true)
+            .await
+            .map_err(|e| map_conditional_error(e, path))?;
+        Ok(Box::new(writer.into_futures_async_write()))
+    }
 }
@@ -20,6 +20,16 @@ pub fn map_opendal_error(e: opendal::Error, path: &Path) -> ErrorKind {
     }
 }
 
+/// Convert an OpenDAL error from a write conditional on the file not existing
+/// (which fails with [`ConditionNotMatch`](opendal::ErrorKind::ConditionNotMatch)
+/// when it does).
+pub fn map_conditional_error(e: opendal::Error, path: &Path) -> ErrorKind {
+    match e.kind() {
+        opendal::ErrorKind::ConditionNotMatch => ErrorKind::AlreadyExists(path.to_path_buf()),
+        _ => map_opendal_error(e, path),
+    }
+}
+
 /// Convert OpenDAL [`opendal::Metadata`] into a [`FileInfo`] for a given path.
 pub fn metadata_to_file_info(backend_name: &str, path: PathBuf, meta: &opendal::Metadata) -> FileInfo {
     let size = meta.content_length();
@@ -0,0 +1,159 @@
+//! Quota storage backend decorator.
+//!
+//! Object storage reports no space limit, so nothing can tell ahead of time
+//! whether a large operation (such as re-compressing a whole target) would
+//! fit in what the bucket is allowed to hold. Wrapping the backend in a
+//! [`QuotaBackend`] gives it a limit to check against.
+
+use crate::backend::{BoxedReader, BoxedWriter, FileInfoStream, OperatorAware, attribute_list_errors};
+use crate::{BackendHandle, ByteSize, Contents, ObjectTags, StorageBackend, error::Result, file::FileInfo};
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use opendal::Operator;
+use std::path::Path;
+
+/// Quota storage backend.
+///
+/// Wraps another backend, reporting the space its files may grow by before
+/// they take up the quota as [available](StorageBackend::available_space)
+/// (or the space the backend itself reports, if that's less). Finding out
+/// lists every file on the backend. The quota is only reported, not
+/// enforced: writes beyond it still succeed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rawr_storage::backend::{QuotaBackend, S3Backend};
+/// use rawr_storage::{BackendHandle, ByteSize};
+/// use std::sync::Arc;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let s3 = S3Backend::new("s3", "my-bucket", None, "us-east-1", None::<String>, "key_id", "key_secret").await?;
+/// let backend = QuotaBackend::new(Arc::new(s3) as BackendHandle, ByteSize::gib(50));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct QuotaBackend {
+    inner: BackendHandle,
+    quota: ByteSize,
+}
+impl QuotaBackend {
+    pub fn new(inner: BackendHandle, quota: ByteSize) -> Self {
+        Self { inner, quota }
+    }
+
+    /// The quota the backend's files are limited to.
+    pub fn quota(&self) -> ByteSize {
+        self.quota
+    }
+}
+impl OperatorAware for QuotaBackend {
+    fn operator(&self) -> &Operator {
+        self.inner.operator()
+    }
+}
+#[async_trait]
+impl StorageBackend for QuotaBackend {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn list_stream<'a>(&'a self, prefix: Option<&'a Path>) -> Result<FileInfoStream<'a>> {
+        Ok(attribute_list_errors(self.name(), self.inner.list_stream(prefix)?))
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.inner.exists(path).await
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.inner.read(path).await
+    }
+
+    async fn read_contents(&self, path: &Path) -> Result<Contents> {
+        self.inner.read_contents(path).await
+    }
+
+    async fn read_head(&self, path: &Path, bytes: usize) -> Result<Vec<u8>> {
+        self.inner.read_head(path, bytes).await
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.inner.write(path, data).await
+    }
+
+    async fn write_if_absent(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.inner.write_if_absent(path, data).await
+    }
+
+    async fn delete(&self, path: &Path) -> Result<()> {
+        self.inner.delete(path).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.inner.rename(from, to).await
+    }
+
+    async fn tag(&self, path: &Path, tags: &ObjectTags) -> Result<()> {
+        self.inner.tag(path, tags).await
+    }
+
+    async fn available_space(&self) -> Result<Option<u64>> {
+        let mut files = self.inner.list_stream(None)?;
+        let mut used = 0;
+        while let Some(file) = files.try_next().await? {
+            // Some listings leave sizes out; ask for them instead.
+            used += match file.size {
+                0 => self.inner.stat(&file.path).await?.size,
+                size => size,
+            };
+        }
+        let remaining = self.quota.as_u64().saturating_sub(used);
+        tracing::debug!(backend = self.name(), used = %ByteSize(used), quota = %self.quota, "Checked storage quota");
+        Ok(Some(match self.inner.available_space().await? {
+            Some(available) => available.min(remaining),
+            None => remaining,
+        }))
+    }
+
+    async fn stat(&self, path: &Path) -> Result<FileInfo> {
+        self.inner.stat(path).await
+    }
+
+    async fn reader(&self, path: &Path) -> Result<BoxedReader> {
+        self.inner.reader(path).await
+    }
+
+    async fn writer(&self, path: &Path) -> Result<BoxedWriter> {
+        self.inner.writer(path).await
+    }
+
+    async fn writer_if_absent(&self, path: &Path) -> Result<BoxedWriter> {
+        self.inner.writer_if_absent(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{LocalBackend, MockBackend};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_available_space() {
+        let inner = MockBackend::with_data([("a.html", [0u8; 600].as_slice()), ("b/c.html", &[0; 300])]);
+        let backend = QuotaBackend::new(Arc::new(inner), ByteSize::kib(1));
+        assert_eq!(Some(1024 - 900), backend.available_space().await.unwrap());
+        backend.write(Path::new("d.html"), &[0; 200]).await.unwrap();
+        assert_eq!(Some(0), backend.available_space().await.unwrap());
+
+        // A quota larger than the volume doesn't make more room.
+        let temp_dir = tempfile::tempdir().unwrap();
+        let local = LocalBackend::new("local", temp_dir.path(), false).unwrap();
+        let free = local.available_space().await.unwrap().unwrap();
+        let backend = QuotaBackend::new(Arc::new(local), ByteSize(u64::MAX));
+        let available = backend.available_space().await.unwrap().unwrap();
+        assert!(available <= free && available > 0);
+    }
+}
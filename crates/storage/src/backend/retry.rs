@@ -0,0 +1,346 @@
+//! Retrying storage backend decorator.
+//!
+//! This module provides a storage backend implementation that wraps other
+//! implementations and transparently retries operations that fail with a
+//! transient error (network blips, throttling, etc.), backing off between
+//! attempts according to a [`RetryPolicy`].
+
+use crate::backend::{BoxedReader, BoxedWriter, FileInfoStream, OperatorAware, attribute_list_errors};
+use crate::error::{Error, ErrorKind, Result};
+use crate::{BackendHandle, Contents, ObjectTags, StorageBackend, file::FileInfo};
+use async_trait::async_trait;
+use opendal::Operator;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+type Classifier = Arc<dyn Fn(&ErrorKind) -> bool + Send + Sync>;
+
+/// Decides how many times, how often, and for which errors a
+/// [`RetryBackend`] retries a failed operation.
+///
+/// The default policy makes up to 3 attempts with exponential backoff
+/// starting at 200ms (capped at 5s), retrying any error for which
+/// [`ErrorKind::is_retryable`] returns `true`.
+///
+/// # Example
+///
+/// ```
+/// use rawr_storage::backend::RetryPolicy;
+/// use rawr_storage::error::ErrorKind;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new()
+///     .with_max_attempts(5)
+///     .with_backoff(Duration::from_millis(500), Duration::from_secs(30))
+///     .with_classifier(|e| matches!(e, ErrorKind::Network(_)));
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    classifier: Classifier,
+}
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            classifier: Arc::new(ErrorKind::is_retryable),
+        }
+    }
+}
+impl Debug for RetryPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .finish_non_exhaustive()
+    }
+}
+impl RetryPolicy {
+    /// Creates the default retry policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the total number of attempts, including the first. A value of `1`
+    /// (or `0`) disables retrying.
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Sets the delay before the first retry, and the ceiling that the
+    /// doubling delay of subsequent retries is capped at.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Replaces the default [`ErrorKind::is_retryable`] classification with a
+    /// custom one. Return `true` for errors worth retrying.
+    pub fn with_classifier(mut self, classifier: impl Fn(&ErrorKind) -> bool + Send + Sync + 'static) -> Self {
+        self.classifier = Arc::new(classifier);
+        self
+    }
+
+    fn is_retryable(&self, error: &ErrorKind) -> bool {
+        (self.classifier)(error)
+    }
+
+    /// Delay before retry number `retry` (starting from 1).
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Retrying storage backend.
+///
+/// Wraps another backend and retries operations that fail with an error the
+/// [`RetryPolicy`] considers transient. Non-retryable errors (and the final
+/// error once attempts are exhausted) are returned unchanged.
+///
+/// # Notes
+/// - Listing is not retried once a stream has started: restarting it would
+///   yield duplicate entries. Errors mid-stream are passed through as usual.
+/// - [`reader()`](StorageBackend::reader) and [`writer()`](StorageBackend::writer)
+///   only retry *opening* the stream, not individual reads/writes.
+/// - A retried [`delete()`](StorageBackend::delete) or
+///   [`rename()`](StorageBackend::rename) whose earlier attempt succeeded
+///   despite reporting failure is treated as successful.
+#[derive(Clone)]
+pub struct RetryBackend {
+    inner: BackendHandle,
+    policy: RetryPolicy,
+}
+impl RetryBackend {
+    pub fn new(inner: BackendHandle, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Runs `operation` (passed the current attempt number, starting from 1)
+    /// until it succeeds, fails with a non-retryable error, or the policy's
+    /// attempts are exhausted.
+    async fn retry<'a, T, F, Fut>(&'a self, name: &'static str, path: &'a Path, mut operation: F) -> Result<T>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation(attempt).await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.policy.max_attempts && self.policy.is_retryable(&e) => {
+                    let delay = self.policy.backoff(attempt);
+                    tracing::warn!(
+                        backend = self.inner.name(),
+                        operation = name,
+                        path = %path.display(),
+                        attempt,
+                        delay_ms = delay.as_millis(),
+                        error = %e,
+                        "Transient storage failure; retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Whether `error`, returned by a retry (not the first attempt), is the
+    /// expected consequence of an earlier attempt having actually succeeded.
+    fn already_done(attempt: u32, error: &Error) -> bool {
+        attempt > 1 && matches!(&**error, ErrorKind::NotFound(_))
+    }
+}
+impl OperatorAware for RetryBackend {
+    fn operator(&self) -> &Operator {
+        self.inner.operator()
+    }
+}
+#[async_trait]
+impl StorageBackend for RetryBackend {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn list_stream<'a>(&'a self, prefix: Option<&'a Path>) -> Result<FileInfoStream<'a>> {
+        Ok(attribute_list_errors(self.name(), self.inner.list_stream(prefix)?))
+    }
+
+    async fn exists(&self, path: &Path) -> Result<bool> {
+        self.retry("exists", path, |_| self.inner.exists(path)).await
+    }
+
+    async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.retry("read", path, |_| self.inner.read(path)).await
+    }
+
+    async fn read_contents(&self, path: &Path) -> Result<Contents> {
+        self.retry("read_contents", path, |_| self.inner.read_contents(path)).await
+    }
+
+    async fn read_head(&self, path: &Path, bytes: usize) -> Result<Vec<u8>> {
+        self.retry("read_head", path, |_| self.inner.read_head(path, bytes)).await
+    }
+
+    async fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.retry("write", path, |_| self.inner.write(path, data)).await
+    }
+
+    async fn write_if_absent(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.retry("write", path, |attempt| async move {
+            match self.inner.write_if_absent(path, data).await {
+                // An attempt that seemed to fail may have written the file.
+                Err(e)
+                    if attempt > 1
+                        && matches!(&*e, ErrorKind::AlreadyExists(_))
+                        && self.inner.read(path).await.is_ok_and(|written| written == data) =>
+                {
+                    Ok(())
+                },
+                result => result,
+            }
+        })
+        .await
+    }
+
+    async fn delete(&self, path: &Path) -> Result<()> {
+        self.retry("delete", path, |attempt| async move {
+            match self.inner.delete(path).await {
+                Err(e) if Self::already_done(attempt, &e) => Ok(()),
+                result => result,
+            }
+        })
+        .await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        self.retry("rename", from, |attempt| async move {
+            match self.inner.rename(from, to).await {
+                Err(e) if Self::already_done(attempt, &e) && self.inner.exists(to).await.unwrap_or(false) => Ok(()),
+                result => result,
+            }
+        })
+        .await
+    }
+
+    async fn tag(&self, path: &Path, tags: &ObjectTags) -> Result<()> {
+        self.retry("tag", path, |_| self.inner.tag(path, tags)).await
+    }
+
+    async fn available_space(&self) -> Result<Option<u64>> {
+        self.retry("available_space", Path::new(""), |_| self.inner.available_space()).await
+    }
+
+    async fn stat(&self, path: &Path) -> Result<FileInfo> {
+        self.retry("stat", path, |_| self.inner.stat(path)).await
+    }
+
+    async fn reader(&self, path: &Path) -> Result<BoxedReader> {
+        self.retry("reader", path, |_| self.inner.reader(path)).await
+    }
+
+    async fn writer(&self, path: &Path) -> Result<BoxedWriter> {
+        self.retry("writer", path, |_| self.inner.writer(path)).await
+    }
+
+    async fn writer_if_absent(&self, path: &Path) -> Result<BoxedWriter> {
+        self.retry("writer", path, |_| self.inner.writer_if_absent(path)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails the first `failures` reads with a network error.
+    struct FlakyBackend {
+        inner: MockBackend,
+        failures: AtomicU32,
+        attempts: AtomicU32,
+    }
+    impl OperatorAware for FlakyBackend {
+        fn operator(&self) -> &Operator {
+            self.inner.operator()
+        }
+    }
+    #[async_trait]
+    impl StorageBackend for FlakyBackend {
+        fn name(&self) -> &str {
+            self.inner.name()
+        }
+
+        async fn read(&self, path: &Path) -> Result<Vec<u8>> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            if self.failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok() {
+                exn::bail!(ErrorKind::Network("connection reset".to_string()));
+            }
+            self.inner.read(path).await
+        }
+    }
+
+    fn setup(failures: u32) -> (Arc<FlakyBackend>, RetryPolicy) {
+        let flaky = Arc::new(FlakyBackend {
+            inner: MockBackend::with_data([("work.html", b"data")]),
+            failures: AtomicU32::new(failures),
+            attempts: AtomicU32::new(0),
+        });
+        let policy = RetryPolicy::new().with_backoff(Duration::from_millis(1), Duration::from_millis(2));
+        (flaky, policy)
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = RetryPolicy::new().with_backoff(Duration::from_millis(100), Duration::from_millis(350));
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+        assert_eq!(policy.backoff(100), Duration::from_millis(350));
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let (flaky, policy) = setup(2);
+        let backend = RetryBackend::new(flaky.clone(), policy);
+        assert_eq!(backend.read(Path::new("work.html")).await.unwrap(), b"data");
+        assert_eq!(flaky.attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let (flaky, policy) = setup(5);
+        let backend = RetryBackend::new(flaky.clone(), policy.with_max_attempts(2));
+        let err = backend.read(Path::new("work.html")).await.unwrap_err();
+        assert!(matches!(&*err, ErrorKind::Network(_)));
+        assert_eq!(flaky.attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_custom_classifier() {
+        let (flaky, policy) = setup(1);
+        let backend = RetryBackend::new(flaky.clone(), policy.with_classifier(|_| false));
+        assert!(backend.read(Path::new("work.html")).await.is_err());
+        assert_eq!(flaky.attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_not_found() {
+        let (flaky, policy) = setup(0);
+        let backend = RetryBackend::new(flaky.clone(), policy);
+        let err = backend.read(Path::new("missing.html")).await.unwrap_err();
+        assert!(matches!(&*err, ErrorKind::NotFound(_)));
+        assert_eq!(flaky.attempts.load(Ordering::SeqCst), 1);
+    }
+}
@@ -8,17 +8,33 @@
 //!
 //! Credentials are provided explicitly via the configuration file. Each
 //! target specifies its own `key_id` and `key_secret`.
+//!
+//! # Object Tags
+//!
+//! OpenDAL has no operation for [object tags](crate::ObjectTags), so
+//! [`tag()`](StorageBackend::tag) sends its own (signed) `PutObjectTagging`
+//! requests, to the same endpoint and with the same credentials.
 
 use super::opendal_util::map_opendal_error;
 use crate::backend::OperatorAware;
 use crate::error::{ErrorKind, Result};
-use crate::{StorageBackend, ValidatedPath};
+use crate::{ObjectTags, StorageBackend, ValidatedPath};
 use async_trait::async_trait;
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
 use futures::{AsyncWriteExt, io::copy as async_copy};
-use opendal::Operator;
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE, HOST};
+use http::{Request, StatusCode};
+use md5::{Digest, Md5};
 use opendal::layers::{ConcurrentLimitLayer, RetryLayer};
+use opendal::raw::{HttpClient, percent_encode_path};
 use opendal::services::S3;
+use opendal::{Buffer, Operator};
+use reqsign::{AwsCredential, AwsV4Signer};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// S3-compatible storage backend.
 ///
@@ -47,6 +63,7 @@ use std::path::Path;
 pub struct S3Backend {
     name: String,
     operator: Operator,
+    tagging: Tagging,
 }
 impl S3Backend {
     /// Create a new S3 storage backend.
@@ -68,18 +85,33 @@ impl S3Backend {
         key_id: impl Into<String>,
         key_secret: impl Into<String>,
     ) -> Result<Self> {
+        let (bucket, region) = (bucket.into(), region.into());
+        let credential = AwsCredential {
+            access_key_id: key_id.into(),
+            secret_access_key: key_secret.into(),
+            ..AwsCredential::default()
+        };
         let mut builder = S3::default()
-            .bucket(&bucket.into())
-            .region(&region.into())
-            .access_key_id(&key_id.into())
-            .secret_access_key(&key_secret.into());
+            .bucket(&bucket)
+            .region(&region)
+            .access_key_id(&credential.access_key_id)
+            .secret_access_key(&credential.secret_access_key);
 
-        if let Some(ep) = endpoint {
-            builder = builder.endpoint(&ep.into());
-        }
+        // Tagging requests go to the custom endpoint if there is one, or to
+        // the region's AWS endpoint (which OpenDAL picks itself).
+        let mut base = match endpoint {
+            Some(ep) => {
+                let ep = ep.into();
+                builder = builder.endpoint(&ep);
+                format!("{}/{bucket}/", ep.trim_end_matches('/'))
+            },
+            None => format!("https://s3.{region}.amazonaws.com/{bucket}/"),
+        };
         if let Some(pfx) = prefix {
             let root = ValidatedPath::new(&pfx)?;
             builder = builder.root(root.as_str());
+            base.push_str(&percent_encode_path(root.as_str().trim_matches('/')));
+            base.push('/');
         }
 
         let operator = Operator::new(builder)
@@ -88,7 +120,80 @@ impl S3Backend {
             .layer(ConcurrentLimitLayer::new(100))
             .finish();
 
-        Ok(Self { name: name.into(), operator })
+        let tagging = Tagging {
+            base,
+            signer: Arc::new(AwsV4Signer::new("s3", &region)),
+            credential,
+            client: HttpClient::new().map_err(|e| ErrorKind::BackendError(e.to_string()))?,
+        };
+        Ok(Self { name: name.into(), operator, tagging })
+    }
+
+    /// Generates a URL that anyone can download the file at `path` from,
+    /// without credentials, until it expires (after `expires_in`).
+    ///
+    /// The file must exist; the URL is signed with this backend's
+    /// credentials, so stops working early if they're revoked. Presigned
+    /// URLs can't outlive seven days on AWS (most S3-compatible services
+    /// have the same limit).
+    pub async fn presign_read(&self, path: &Path, expires_in: Duration) -> Result<String> {
+        let validated = ValidatedPath::new(path)?;
+        if !self.exists(path).await? {
+            exn::bail!(ErrorKind::NotFound(path.to_path_buf()));
+        }
+        let request =
+            self.operator.presign_read(validated.as_str(), expires_in).await.map_err(|e| map_opendal_error(e, path))?;
+        Ok(request.uri().to_string())
+    }
+
+    /// Generates a URL that anyone can upload a file to (at `path`, with an
+    /// HTTP `PUT`), without credentials, until it expires (after
+    /// `expires_in`); see [`presign_read`](Self::presign_read).
+    ///
+    /// Uploading replaces any file already at `path`.
+    pub async fn presign_write(&self, path: &Path, expires_in: Duration) -> Result<String> {
+        let validated = ValidatedPath::new(path)?;
+        let request = self
+            .operator
+            .presign_write(validated.as_str(), expires_in)
+            .await
+            .map_err(|e| map_opendal_error(e, path))?;
+        Ok(request.uri().to_string())
+    }
+}
+
+/// Sends the `PutObjectTagging` requests OpenDAL has no operation for.
+#[derive(Clone)]
+struct Tagging {
+    /// URL of the bucket (path-style), and of the key prefix if any, ending
+    /// with a `/`.
+    base: String,
+    signer: Arc<AwsV4Signer>,
+    credential: AwsCredential,
+    client: HttpClient,
+}
+impl Tagging {
+    /// A signed request replacing the tags of the object at `key` (relative
+    /// to the key prefix) with `tags`.
+    fn request(&self, key: &str, tags: &ObjectTags) -> Result<Request<Buffer>> {
+        let body = tags.to_xml();
+        // S3 requires an integrity check of tagging requests.
+        let md5 = BASE64_STANDARD.encode(Md5::digest(body.as_bytes()));
+        let mut request = Request::put(format!("{}{}?tagging", self.base, percent_encode_path(key)))
+            .header("content-md5", md5)
+            .header(CONTENT_TYPE, "application/xml")
+            .header(CONTENT_LENGTH, body.len())
+            .body(Buffer::from(body))
+            .map_err(|e| ErrorKind::BackendError(e.to_string()))?;
+        self.signer.sign(&mut request, &self.credential).map_err(|e| ErrorKind::BackendError(e.to_string()))?;
+        // The HTTP client sets the host itself (as OpenDAL does).
+        request.headers_mut().remove(HOST);
+        Ok(request)
+    }
+}
+impl Debug for Tagging {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Tagging").field("base", &self.base).finish_non_exhaustive()
     }
 }
 
@@ -103,6 +208,26 @@ impl StorageBackend for S3Backend {
         &self.name
     }
 
+    async fn tag(&self, path: &Path, tags: &ObjectTags) -> Result<()> {
+        tracing::trace!(backend = self.name(), path = %path.display(), tags = tags.len(), "tag file in storage backend");
+        let validated = ValidatedPath::new(path)?;
+        let request = self.tagging.request(validated.as_str(), tags)?;
+        let response = self.tagging.client.send(request).await.map_err(|e| map_opendal_error(e, path))?;
+        let status = response.status();
+        match status {
+            _ if status.is_success() => Ok(()),
+            StatusCode::NOT_FOUND => exn::bail!(ErrorKind::NotFound(path.to_path_buf())),
+            StatusCode::FORBIDDEN => exn::bail!(ErrorKind::PermissionDenied(path.to_path_buf())),
+            _ if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS => {
+                exn::bail!(ErrorKind::Network(format!("tagging failed with {status}")))
+            },
+            _ => {
+                let body = String::from_utf8_lossy(&response.into_body().to_vec()).into_owned();
+                exn::bail!(ErrorKind::BackendError(format!("tagging failed with {status}: {body}")))
+            },
+        }
+    }
+
     async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
         let validated_from = ValidatedPath::new(from)?;
         let validated_to = ValidatedPath::new(to)?;
@@ -131,3 +256,54 @@ impl StorageBackend for S3Backend {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_presign_write() {
+        // Signing happens locally, so needs no bucket to talk to.
+        let backend = S3Backend::new(
+            "s3",
+            "bucket",
+            Some("library/".to_string()),
+            "us-west-004",
+            Some("https://s3.us-west-004.backblazeb2.com"),
+            "key",
+            "secret",
+        )
+        .await
+        .unwrap();
+        let url = backend.presign_write(Path::new("works/1.html.gz"), Duration::from_secs(3600)).await.unwrap();
+        assert!(url.starts_with("https://s3.us-west-004.backblazeb2.com/bucket/library/works/1.html.gz?"));
+        assert!(url.contains("X-Amz-Expires=3600") && url.contains("X-Amz-Signature="));
+        assert!(backend.presign_write(Path::new("../escape.html"), Duration::from_secs(60)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tagging_request() {
+        let backend =
+            S3Backend::new("s3", "bucket", Some("library/".to_string()), "eu-west-1", None::<String>, "key", "secret")
+                .await
+                .unwrap();
+        let mut tags = ObjectTags::new();
+        tags.insert("work_id", "12345").unwrap();
+        tags.insert("fandom", "harry-potter").unwrap();
+        let request = backend.tagging.request("works/My Story.html.gz", &tags).unwrap();
+        assert_eq!(
+            "https://s3.eu-west-1.amazonaws.com/bucket/library/works/My%20Story.html.gz?tagging",
+            request.uri().to_string()
+        );
+        let header = |name| request.headers().get(name).unwrap().to_str().unwrap();
+        assert!(header("authorization").starts_with("AWS4-HMAC-SHA256 Credential=key/"));
+        assert!(header("authorization").contains("/eu-west-1/s3/aws4_request"));
+        let body = String::from_utf8(request.body().to_vec()).unwrap();
+        assert_eq!(BASE64_STANDARD.encode(Md5::digest(body.as_bytes())), header("content-md5"));
+        assert_eq!(
+            "<Tagging><TagSet><Tag><Key>work_id</Key><Value>12345</Value></Tag>\
+             <Tag><Key>fandom</Key><Value>harry-potter</Value></Tag></TagSet></Tagging>",
+            body
+        );
+    }
+}
@@ -0,0 +1,276 @@
+//! Best-effort multi-step storage transactions.
+//!
+//! Storage backends have no notion of transactions, so this module emulates
+//! one: each step records enough information to be undone, and on failure all
+//! completed steps are undone in reverse order.
+
+use crate::StorageBackend;
+use crate::error::{ErrorKind, Result};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// An operation staged in a [`BackendTransaction`].
+enum Step {
+    Write { path: PathBuf, data: Vec<u8> },
+    Rename { from: PathBuf, to: PathBuf },
+    Delete { path: PathBuf },
+}
+
+/// What's needed to revert a completed [`Step`].
+enum Undo {
+    /// Restore the previous contents, or delete the file if there were none.
+    Write { path: PathBuf, previous: Option<Vec<u8>> },
+    /// Move the file back, then restore whatever it overwrote (if anything).
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        displaced: Option<Vec<u8>>,
+    },
+    /// Write the deleted contents back.
+    Delete { path: PathBuf, contents: Vec<u8> },
+}
+
+/// Stages writes, renames and deletes against a backend and applies them as
+/// a unit: either every step succeeds, or the completed steps are rolled back.
+///
+/// # Guarantees
+/// This is **best-effort**, not a database transaction:
+/// - Steps are applied one at a time; other readers can observe intermediate
+///   states, and a crash mid-commit leaves the backend partially modified.
+/// - Anything a step overwrites or deletes is held in memory until the
+///   commit finishes, so that it can be restored.
+/// - Individual steps are only as atomic as the backend: a rename is atomic
+///   on a [`LocalBackend`](crate::backend::LocalBackend) (same filesystem), but
+///   a copy-then-delete on S3 and the mock backend.
+/// - If a rollback step itself fails, rolling back continues with the
+///   remaining steps and [`ErrorKind::RollbackFailed`] is returned with the
+///   original error and every rollback error attached.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rawr_storage::backend::{BackendTransaction, StorageBackend};
+/// # use rawr_storage::error::Result;
+/// # async fn example(backend: &dyn StorageBackend) -> Result<()> {
+/// // Swap two files, or leave both where they are.
+/// BackendTransaction::new(backend)
+///     .rename("a.html", "tmp.html")
+///     .rename("b.html", "a.html")
+///     .rename("tmp.html", "b.html")
+///     .commit()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[must_use = "transactions do nothing until committed"]
+pub struct BackendTransaction<'a> {
+    backend: &'a dyn StorageBackend,
+    steps: Vec<Step>,
+}
+impl<'a> BackendTransaction<'a> {
+    pub fn new(backend: &'a dyn StorageBackend) -> Self {
+        Self { backend, steps: Vec::new() }
+    }
+
+    /// Stages writing `data` to `path`, creating or overwriting the file.
+    pub fn write(mut self, path: impl Into<PathBuf>, data: impl Into<Vec<u8>>) -> Self {
+        self.steps.push(Step::Write { path: path.into(), data: data.into() });
+        self
+    }
+
+    /// Stages moving the file at `from` to `to`, overwriting `to` if it exists.
+    pub fn rename(mut self, from: impl Into<PathBuf>, to: impl Into<PathBuf>) -> Self {
+        self.steps.push(Step::Rename { from: from.into(), to: to.into() });
+        self
+    }
+
+    /// Stages deleting the file at `path`.
+    pub fn delete(mut self, path: impl Into<PathBuf>) -> Self {
+        self.steps.push(Step::Delete { path: path.into() });
+        self
+    }
+
+    /// Applies every staged step in order, rolling back on the first failure.
+    ///
+    /// Returns the failing step's error unchanged if rollback succeeded, or
+    /// [`ErrorKind::RollbackFailed`] if the backend could not be restored.
+    pub async fn commit(self) -> Result<()> {
+        let mut completed = Vec::with_capacity(self.steps.len());
+        for step in self.steps {
+            let failed_path = match &step {
+                Step::Write { path, .. } | Step::Delete { path } => path.clone(),
+                Step::Rename { from, .. } => from.clone(),
+            };
+            let (undo, result) = apply(self.backend, step).await;
+            let error = match result {
+                Ok(()) => {
+                    // Infallible: an undo record is always returned on success.
+                    completed.extend(undo);
+                    continue;
+                },
+                Err(e) => e,
+            };
+            tracing::warn!(
+                backend = self.backend.name(),
+                path = %failed_path.display(),
+                error = %error,
+                steps = completed.len(),
+                "Storage transaction step failed; rolling back"
+            );
+            let mut rollback_errors = Vec::new();
+            // The failed step may have been partially applied.
+            if let Some(undo) = undo
+                && let Err(e) = cleanup(self.backend, undo).await
+            {
+                rollback_errors.push(e);
+            }
+            for undo in completed.into_iter().rev() {
+                if let Err(e) = revert(self.backend, undo).await {
+                    rollback_errors.push(e);
+                }
+            }
+            if rollback_errors.is_empty() {
+                return Err(error);
+            }
+            tracing::error!(
+                backend = self.backend.name(),
+                failures = rollback_errors.len(),
+                "Storage transaction rollback incomplete"
+            );
+            let children = std::iter::once(error).chain(rollback_errors);
+            return Err(exn::Exn::raise_all(ErrorKind::RollbackFailed(failed_path), children));
+        }
+        Ok(())
+    }
+}
+
+/// Reads a file's contents, or `None` if it doesn't exist.
+async fn read_optional(backend: &dyn StorageBackend, path: &Path) -> Result<Option<Vec<u8>>> {
+    match backend.read(path).await {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if matches!(e.deref(), ErrorKind::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Deletes a file, treating one that's already gone as success.
+async fn delete_optional(backend: &dyn StorageBackend, path: &Path) -> Result<()> {
+    match backend.delete(path).await {
+        Err(e) if matches!(e.deref(), ErrorKind::NotFound(_)) => Ok(()),
+        result => result,
+    }
+}
+
+/// Applies a single step. The undo record is returned whenever the step got
+/// far enough to modify the backend, even if it then failed.
+async fn apply(backend: &dyn StorageBackend, step: Step) -> (Option<Undo>, Result<()>) {
+    match step {
+        Step::Write { path, data } => {
+            let previous = match read_optional(backend, &path).await {
+                Ok(previous) => previous,
+                Err(e) => return (None, Err(e)),
+            };
+            let result = backend.write(&path, &data).await;
+            (Some(Undo::Write { path, previous }), result)
+        },
+        Step::Rename { from, to } => {
+            let displaced = match read_optional(backend, &to).await {
+                Ok(displaced) => displaced,
+                Err(e) => return (None, Err(e)),
+            };
+            let result = backend.rename(&from, &to).await;
+            (Some(Undo::Rename { from, to, displaced }), result)
+        },
+        Step::Delete { path } => {
+            let contents = match backend.read(&path).await {
+                Ok(contents) => contents,
+                Err(e) => return (None, Err(e)),
+            };
+            let result = backend.delete(&path).await;
+            (Some(Undo::Delete { path, contents }), result)
+        },
+    }
+}
+
+/// Reverts a step that completed successfully.
+async fn revert(backend: &dyn StorageBackend, undo: Undo) -> Result<()> {
+    match undo {
+        Undo::Write { path, previous: Some(previous) } => backend.write(&path, &previous).await,
+        Undo::Write { path, previous: None } => delete_optional(backend, &path).await,
+        Undo::Rename { from, to, displaced } => {
+            backend.rename(&to, &from).await?;
+            match displaced {
+                Some(displaced) => backend.write(&to, &displaced).await,
+                None => Ok(()),
+            }
+        },
+        Undo::Delete { path, contents } => backend.write(&path, &contents).await,
+    }
+}
+
+/// Reverts whatever part of a failed step was applied.
+async fn cleanup(backend: &dyn StorageBackend, undo: Undo) -> Result<()> {
+    match undo {
+        // A partial write is indistinguishable from a complete one.
+        undo @ Undo::Write { .. } => revert(backend, undo).await,
+        Undo::Rename { from, to, displaced } => match (backend.exists(&from).await?, backend.exists(&to).await?) {
+            // The rename actually happened, despite reporting an error.
+            (false, true) => revert(backend, Undo::Rename { from, to, displaced }).await,
+            // A copy-then-delete rename that failed part way.
+            (true, true) => match displaced {
+                Some(displaced) => backend.write(&to, &displaced).await,
+                None => delete_optional(backend, &to).await,
+            },
+            _ => Ok(()),
+        },
+        Undo::Delete { ref path, .. } => match backend.exists(path).await? {
+            true => Ok(()),
+            false => revert(backend, undo).await,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+
+    #[tokio::test]
+    async fn test_commit_applies_all_steps() {
+        let backend = MockBackend::with_data([("a.html", b"a"), ("b.html", b"b")]);
+        BackendTransaction::new(&backend)
+            .rename("a.html", "tmp.html")
+            .rename("b.html", "a.html")
+            .rename("tmp.html", "b.html")
+            .write("c.html", b"c".to_vec())
+            .delete("c.html")
+            .commit()
+            .await
+            .unwrap();
+        assert_eq!(backend.read(Path::new("a.html")).await.unwrap(), b"b");
+        assert_eq!(backend.read(Path::new("b.html")).await.unwrap(), b"a");
+        assert!(!backend.exists(Path::new("tmp.html")).await.unwrap());
+        assert!(!backend.exists(Path::new("c.html")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_failure_rolls_back_completed_steps() {
+        let backend = MockBackend::with_data([("a.html", b"a"), ("b.html", b"b"), ("c.html", b"c")]);
+        let err = BackendTransaction::new(&backend)
+            .write("new.html", b"new".to_vec())
+            .write("a.html", b"overwritten".to_vec())
+            .rename("b.html", "c.html")
+            .delete("c.html")
+            .rename("missing.html", "d.html")
+            .commit()
+            .await
+            .unwrap_err();
+        // It's the last step that fails, so every other one is rolled back.
+        assert!(matches!(&*err, ErrorKind::NotFound(path) if path == Path::new("missing.html")));
+        assert!(!backend.exists(Path::new("new.html")).await.unwrap());
+        assert_eq!(backend.read(Path::new("a.html")).await.unwrap(), b"a");
+        assert_eq!(backend.read(Path::new("b.html")).await.unwrap(), b"b");
+        assert_eq!(backend.read(Path::new("c.html")).await.unwrap(), b"c");
+        assert!(!backend.exists(Path::new("d.html")).await.unwrap());
+    }
+}
@@ -0,0 +1,209 @@
+//! Content-defined chunking, for transferring only what changed.
+//!
+//! Copying a large file to a target again after a small edit re-uploads all
+//! of it. Splitting files into chunks at fixed offsets doesn't help much: an
+//! insertion near the start shifts every chunk after it. Instead, chunk
+//! boundaries are chosen by the content itself (where a rolling hash of the
+//! last few bytes matches a pattern), so an edit only changes the chunks it
+//! touches, and the rest line up with those already stored.
+//!
+//! [`chunk()`] splits data FastCDC-style (a gear hash, with boundaries
+//! normalized towards the average size); [`ChunkedBackend`](crate::backend::ChunkedBackend)
+//! stores files on a target as the chunks they're made of, uploading only
+//! those the target doesn't have, and a [`ChunkIndex`] (such as the cache)
+//! remembers which those are.
+
+use crate::error::{ErrorKind, Result};
+use async_trait::async_trait;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Random-looking values for each byte, mixed into the rolling hash.
+static GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    // SplitMix64, seeded arbitrarily; the table only has to be fixed.
+    let mut table = [0u64; 256];
+    let mut state = 0x7261_7772_6368_756e_u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// How large chunks should be.
+///
+/// Smaller chunks find more of a changed file already stored, but there are
+/// more of them to upload, index and fetch. The defaults suit HTML works:
+/// most fit in a handful of chunks, and a changed chapter is a chunk or two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkConfig {
+    min: usize,
+    avg: usize,
+    max: usize,
+}
+impl ChunkConfig {
+    /// Chunks of at least `min` bytes (except the last), at most `max`, and
+    /// `avg` on average (rounded down to a power of two).
+    ///
+    /// Returns [`ErrorKind::InvalidSize`] unless `0 < min <= avg <= max`.
+    pub fn new(min: usize, avg: usize, max: usize) -> Result<Self> {
+        if min == 0 || min > avg || avg > max {
+            exn::bail!(ErrorKind::InvalidSize(format!(
+                "chunk sizes must be 0 < min ({min}) <= average ({avg}) <= max ({max})"
+            )));
+        }
+        let avg = 1 << avg.ilog2();
+        Ok(Self { min: min.min(avg), avg, max })
+    }
+
+    pub fn min(&self) -> usize {
+        self.min
+    }
+
+    pub fn avg(&self) -> usize {
+        self.avg
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+impl Default for ChunkConfig {
+    fn default() -> Self {
+        Self {
+            min: 8 * 1024,
+            avg: 32 * 1024,
+            max: 128 * 1024,
+        }
+    }
+}
+
+/// A piece of a file, identified by its content.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Chunk {
+    /// Where the chunk starts in the file.
+    pub offset: u64,
+    pub length: u64,
+    /// BLAKE3 hash of the chunk (hex).
+    pub hash: String,
+}
+
+/// Splits `data` into content-defined chunks, in order, covering all of it.
+///
+/// The same data is always split the same way (with the same `config`), so
+/// chunks of unchanged parts of a file keep their hashes.
+pub fn chunk(data: &[u8], config: &ChunkConfig) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let length = cut_point(&data[offset..], config);
+        let bytes = &data[offset..offset + length];
+        chunks.push(Chunk {
+            offset: offset as u64,
+            length: length as u64,
+            hash: blake3::hash(bytes).to_hex().to_string(),
+        });
+        offset += length;
+    }
+    chunks
+}
+
+/// Length of the chunk at the start of `data`.
+fn cut_point(data: &[u8], config: &ChunkConfig) -> usize {
+    if data.len() <= config.min {
+        return data.len();
+    }
+    let end = data.len().min(config.max);
+    let normal = data.len().min(config.avg).max(config.min);
+    // Harder to match before the average size, and easier after it, so
+    // chunk sizes bunch up around the average.
+    let bits = config.avg.ilog2();
+    let strict = mask(bits + 1);
+    let loose = mask(bits.saturating_sub(1));
+    let mut hash = 0u64;
+    for (i, byte) in data.iter().enumerate().take(end).skip(config.min) {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        let mask = if i < normal { strict } else { loose };
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+/// A mask of `bits` bits, spread over the top of the hash (where the gear
+/// hash has mixed in the most bytes).
+fn mask(bits: u32) -> u64 {
+    match bits {
+        0 => 0,
+        bits => u64::MAX << (64 - bits.min(64)),
+    }
+}
+
+/// Remembers which chunks are stored on which targets, so that storing a
+/// file doesn't have to ask the target about every one of its chunks.
+///
+/// An index is only a hint: [`ChunkedBackend`](crate::backend::ChunkedBackend)
+/// still checks that the chunks it lists are stored before skipping them, but
+/// uploads those it doesn't list without checking, so it should be told about
+/// every chunk stored.
+#[async_trait]
+pub trait ChunkIndex: Send + Sync {
+    /// Which of `hashes` are known to be stored on the target named `target`.
+    async fn stored_chunks(&self, target: &str, hashes: &[&str]) -> Result<HashSet<String>>;
+
+    /// Records that the file at `path` on `target` is made of `chunks`, all
+    /// of which are now stored there.
+    async fn record_chunks(&self, target: &str, path: &Path, chunks: &[Chunk]) -> Result<()>;
+
+    /// Forgets the chunks of the file at `path` on `target` (which is gone).
+    async fn forget_chunks(&self, target: &str, path: &Path) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic, incompressible-looking test data.
+    fn data(len: usize) -> Vec<u8> {
+        let mut state = 1u64;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk() {
+        let config = ChunkConfig::new(1024, 4096, 16 * 1024).unwrap();
+        let original = data(256 * 1024);
+        let chunks = chunk(&original, &config);
+        assert!(chunks.len() > 10);
+        assert_eq!(original.len() as u64, chunks.iter().map(|c| c.length).sum::<u64>());
+        assert!(chunks.iter().all(|c| (1024..=16 * 1024).contains(&c.length)));
+        assert!(chunks.windows(2).all(|w| w[0].offset + w[0].length == w[1].offset));
+        assert_eq!(chunks, chunk(&original, &config));
+
+        // An insertion only changes the chunks around it.
+        let mut edited = original.clone();
+        edited.splice(100_000..100_000, *b"a few more words");
+        let hashes: HashSet<_> = chunks.iter().map(|c| &c.hash).collect();
+        let changed = chunk(&edited, &config).into_iter().filter(|c| !hashes.contains(&c.hash)).count();
+        assert!((1..=2).contains(&changed), "{changed} chunks changed");
+
+        assert!(chunk(&[], &config).is_empty());
+        assert_eq!(1, chunk(b"tiny", &config).len());
+        assert!(ChunkConfig::new(0, 4096, 8192).is_err());
+        assert!(ChunkConfig::new(8192, 4096, 8192).is_err());
+        assert_eq!(4096, ChunkConfig::new(1024, 5000, 8192).unwrap().avg());
+    }
+}
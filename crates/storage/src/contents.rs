@@ -0,0 +1,74 @@
+//! File contents returned by [`StorageBackend::read_contents()`](crate::backend::StorageBackend::read_contents).
+//!
+//! Depending on the backend (and the `mmap` feature), the bytes are either an
+//! owned buffer or a read-only memory map of the file; both dereference to a
+//! plain `[u8]` so consumers don't need to care which.
+
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::ops::Deref;
+
+/// Complete contents of a file, either owned or memory-mapped.
+///
+/// # Memory-Mapped Contents
+/// Mapped contents reflect the file as it is on disk *while the value is
+/// alive*. Only files that can't be changed in the meantime are mapped (see
+/// [`LocalBackend`](crate::backend::LocalBackend)): one truncated while
+/// mapped would crash the process with `SIGBUS`. Even so, hold onto it only
+/// for as long as it takes to process.
+pub struct Contents(Repr);
+
+enum Repr {
+    Owned(Vec<u8>),
+    #[cfg(all(feature = "mmap", unix))]
+    Mapped(memmap2::Mmap),
+}
+
+impl Contents {
+    #[cfg(all(feature = "mmap", unix))]
+    pub(crate) fn mapped(map: memmap2::Mmap) -> Self {
+        Self(Repr::Mapped(map))
+    }
+
+    /// Whether the contents are a memory map rather than an owned buffer.
+    pub fn is_mapped(&self) -> bool {
+        match &self.0 {
+            Repr::Owned(_) => false,
+            #[cfg(all(feature = "mmap", unix))]
+            Repr::Mapped(_) => true,
+        }
+    }
+
+    /// Converts into an owned buffer, copying the contents if they're mapped.
+    pub fn into_vec(self) -> Vec<u8> {
+        match self.0 {
+            Repr::Owned(data) => data,
+            #[cfg(all(feature = "mmap", unix))]
+            Repr::Mapped(map) => map.to_vec(),
+        }
+    }
+}
+impl Deref for Contents {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        match &self.0 {
+            Repr::Owned(data) => data,
+            #[cfg(all(feature = "mmap", unix))]
+            Repr::Mapped(map) => map,
+        }
+    }
+}
+impl AsRef<[u8]> for Contents {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+impl From<Vec<u8>> for Contents {
+    fn from(data: Vec<u8>) -> Self {
+        Self(Repr::Owned(data))
+    }
+}
+impl Debug for Contents {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Contents").field("len", &self.len()).field("mapped", &self.is_mapped()).finish()
+    }
+}
@@ -9,8 +9,9 @@
 
 use derive_more::{Display, Error};
 use rawr_compress::error::{Error as CompressionError, ErrorKind as CompressionErrorKind};
+pub use rawr_error::{Code, Domain, ErrorCode};
 use std::io::Error as IoError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// A storage error with automatic location tracking.
 pub type Error = exn::Exn<ErrorKind>;
@@ -49,6 +50,28 @@ pub enum ErrorKind {
     /// Path rejected by extension filter (e.g. HtmlBackend)
     #[display("filtered path: {}", _0.display())]
     FilteredPath(#[error(not(source))] PathBuf),
+    /// An ignore (glob) pattern could not be compiled
+    #[display("invalid pattern: {_0}")]
+    InvalidPattern(#[error(not(source))] String),
+    /// A size (e.g. a quota) could not be parsed
+    #[display("invalid size: {_0}")]
+    InvalidSize(#[error(not(source))] String),
+    /// An object tag (holding its key) that S3 wouldn't accept
+    #[display("invalid object tag: {_0}")]
+    InvalidTag(#[error(not(source))] String),
+    /// A multi-step operation failed and could not be fully undone; the
+    /// backend may be left partially modified. Holds the failing step's path.
+    #[display("rollback incomplete after failure at: {}", _0.display())]
+    RollbackFailed(#[error(not(source))] PathBuf),
+    /// A change was made to a mirrored backend's primary, but not to the
+    /// replica named.
+    #[display("replica failed: {_0}")]
+    ReplicaFailed(#[error(not(source))] String),
+    /// Listing the files of the backend named failed, at the path given
+    /// (where known). What went wrong is the error's child; see
+    /// [`StorageBackend::list_stream()`](crate::StorageBackend::list_stream).
+    #[display("listing {backend} failed{}", path.as_ref().map(|p| format!(" at: {}", p.display())).unwrap_or_default())]
+    Listing { backend: String, path: Option<PathBuf> },
 }
 impl From<IoError> for ErrorKind {
     fn from(err: IoError) -> Self {
@@ -67,8 +90,45 @@ impl ErrorKind {
 }
 
 impl ErrorKind {
+    /// The path the error happened at, for the kinds that hold one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::NotFound(path)
+            | Self::PermissionDenied(path)
+            | Self::AlreadyExists(path)
+            | Self::InvalidPath(path)
+            | Self::FilteredPath(path)
+            | Self::RollbackFailed(path) => Some(path),
+            Self::Listing { path, .. } => path.as_deref(),
+            _ => None,
+        }
+    }
+
     /// Returns `true` if retrying might succeed.
     pub fn is_retryable(&self) -> bool {
         matches!(self, Self::Io(_) | Self::Network(_) | Self::BackendError(_))
     }
 }
+
+impl ErrorCode for ErrorKind {
+    fn code(&self) -> Code {
+        let number = match self {
+            Self::InvalidPath(_) => 400,
+            Self::PermissionDenied(_) => 403,
+            Self::NotFound(_) => 404,
+            Self::FilteredPath(_) => 406,
+            Self::AlreadyExists(_) => 409,
+            Self::InvalidPattern(_) => 422,
+            Self::InvalidSize(_) => 423,
+            Self::InvalidTag(_) => 424,
+            Self::Io(_) => 500,
+            Self::Network(_) => 502,
+            Self::BackendError(_) => 503,
+            Self::ReplicaFailed(_) => 507,
+            Self::RollbackFailed(_) => 510,
+            Self::Listing { .. } => 520,
+            Self::Compression(_) => 522,
+        };
+        Code::new(Domain::Storage, number)
+    }
+}
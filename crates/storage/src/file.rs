@@ -10,6 +10,7 @@
 //! # Lifecycle
 //!
 //! ```no_run
+//! # use rawr_storage::ByteSize;
 //! # use rawr_storage::file::{FileInfo, Read};
 //! # use rawr_compress::Compression;
 //! # use time::UtcDateTime;
@@ -22,7 +23,7 @@
 //!     Compression::Gzip,
 //! );
 //! // Access metadata fields directly via Deref
-//! println!("{}: {} bytes", file.path.display(), file.size);
+//! println!("{}: {}", file.path.display(), ByteSize(file.size));
 //!
 //! // Attach a hash to transition to Read state
 //! let file = file.with_file_hash("af1349b9f5f9a1a6...");
@@ -39,6 +40,7 @@
 //! | [`&FileInfo<Read>`](FileInfo)      | File hash is required at compile time                     |
 //! | [`&FileInfo<Processed>`](FileInfo) | Content hash is required at compile time                  |
 
+use crate::FileKind;
 use rawr_compress::Compression;
 use std::{ops::Deref, path::PathBuf};
 use time::UtcDateTime;
@@ -80,6 +82,12 @@ impl FileMeta {
         }
     }
 
+    /// The kind of document the file holds, going by its extension (see
+    /// [`FileKind::detect`] to go by its contents too).
+    pub fn kind(&self) -> FileKind {
+        FileKind::from_path(&self.path)
+    }
+
     /// Consumes itself to attach a hash, transitioning to [`FileInfo<Read>`].
     pub fn with_file_hash(self, hash: impl Into<String>) -> FileInfo<Read> {
         FileInfo {
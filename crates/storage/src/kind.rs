@@ -0,0 +1,144 @@
+//! What kind of document a file is.
+//!
+//! Libraries mostly hold AO3's HTML downloads, but targets collect other
+//! things too: the PDF and EPUB downloads of the same works, cover art and
+//! fan art, and archives of bulk downloads. [`FileKind`] tells them apart by extension (ignoring any
+//! compression suffix) and, when the contents are at hand, by magic bytes.
+
+use rawr_compress::Compression;
+use std::path::Path;
+
+/// Enough of an EPUB's first (uncompressed, by the specification) ZIP entry
+/// to identify it: the entry's name and contents, `mimetype`.
+const EPUB_MIMETYPE: &[u8] = b"mimetypeapplication/epub+zip";
+/// Offset of the name of the first entry in a ZIP archive.
+const ZIP_NAME_OFFSET: usize = 30;
+
+/// The kind of document a file holds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FileKind {
+    Html,
+    Pdf,
+    Epub,
+    Image,
+    /// A ZIP or (possibly compressed) tar archive of other files.
+    Archive,
+    #[default]
+    Unknown,
+}
+impl FileKind {
+    /// Detects the kind of file from its extension, after stripping any
+    /// compression suffix (`work.html.gz` is HTML).
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match base_extension(path.as_ref()).map(|ext| ext.to_ascii_lowercase()).as_deref() {
+            Some("html" | "htm") => Self::Html,
+            Some("pdf") => Self::Pdf,
+            Some("epub") => Self::Epub,
+            Some("png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "svg") => Self::Image,
+            Some("zip" | "tar" | "tgz") => Self::Archive,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Detects the kind of file from the start of its (decompressed)
+    /// contents; [`Unknown`](Self::Unknown) if they aren't recognised.
+    pub fn from_magic_bytes(bytes: &[u8]) -> Self {
+        if bytes.starts_with(b"%PDF-") {
+            return Self::Pdf;
+        }
+        if bytes.starts_with(b"PK\x03\x04")
+            && bytes.get(ZIP_NAME_OFFSET..).is_some_and(|b| b.starts_with(EPUB_MIMETYPE))
+        {
+            return Self::Epub;
+        }
+        let image = bytes.starts_with(b"\x89PNG\r\n\x1a\n")
+            || bytes.starts_with(b"\xFF\xD8\xFF")
+            || bytes.starts_with(b"GIF87a")
+            || bytes.starts_with(b"GIF89a")
+            || (bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP"))
+            || bytes.get(4..12) == Some(b"ftypavif");
+        if image {
+            return Self::Image;
+        }
+        let text = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+        let start = text.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(text.len());
+        let text = &text[start..];
+        let html = ["<!doctype html", "<html", "<head", "<body"]
+            .iter()
+            .any(|tag| text.get(..tag.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(tag.as_bytes())));
+        if html {
+            return Self::Html;
+        }
+        Self::Unknown
+    }
+
+    /// Detects the kind of file from its contents, falling back to its path
+    /// when they aren't recognised (such as when they're still compressed).
+    pub fn detect(path: impl AsRef<Path>, bytes: &[u8]) -> Self {
+        match Self::from_magic_bytes(bytes) {
+            Self::Unknown => Self::from_path(path),
+            kind => kind,
+        }
+    }
+
+    /// Returns the name of the kind, in lowercase.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Pdf => "pdf",
+            Self::Epub => "epub",
+            Self::Image => "image",
+            Self::Archive => "archive",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// The extension of a path, once any compression suffix is stripped
+/// (`work.html.bz2` has `html`).
+pub(crate) fn base_extension(path: &Path) -> Option<&str> {
+    let path = match Compression::from_path(path) {
+        Compression::None => path,
+        // Strip the compression extension to get the inner filename
+        _ => Path::new(path.file_stem()?),
+    };
+    path.extension()?.to_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(FileKind::Html, FileKind::from_path("fandom/work.html.bz2"));
+        assert_eq!(FileKind::Html, FileKind::from_path("saved.HTM"));
+        assert_eq!(FileKind::Pdf, FileKind::from_path("work.pdf"));
+        assert_eq!(FileKind::Epub, FileKind::from_path("work.epub.gz"));
+        assert_eq!(FileKind::Image, FileKind::from_path("art/cover.jpeg"));
+        assert_eq!(FileKind::Archive, FileKind::from_path("exports/2024.tar.gz"));
+        assert_eq!(FileKind::Archive, FileKind::from_path("exports/2024.ZIP"));
+        assert_eq!(FileKind::Unknown, FileKind::from_path("work.gz"));
+        assert_eq!(FileKind::Unknown, FileKind::from_path("README"));
+    }
+
+    #[test]
+    fn test_from_magic_bytes() {
+        assert_eq!(FileKind::Html, FileKind::from_magic_bytes(b"\xEF\xBB\xBF\n  <!DOCTYPE html>\n<html>"));
+        assert_eq!(FileKind::Html, FileKind::from_magic_bytes(b"<HTML><head>"));
+        assert_eq!(FileKind::Pdf, FileKind::from_magic_bytes(b"%PDF-1.7\n"));
+        let mut epub = b"PK\x03\x04".to_vec();
+        epub.resize(ZIP_NAME_OFFSET, 0);
+        epub.extend_from_slice(EPUB_MIMETYPE);
+        assert_eq!(FileKind::Epub, FileKind::from_magic_bytes(&epub));
+        assert_eq!(FileKind::Unknown, FileKind::from_magic_bytes(b"PK\x03\x04 just a zip"));
+        assert_eq!(FileKind::Image, FileKind::from_magic_bytes(b"\x89PNG\r\n\x1a\n...."));
+        assert_eq!(FileKind::Image, FileKind::from_magic_bytes(b"RIFF\0\0\0\0WEBPVP8 "));
+        assert_eq!(FileKind::Unknown, FileKind::from_magic_bytes(b"<p>A fragment</p>"));
+        assert_eq!(FileKind::Unknown, FileKind::from_magic_bytes(b""));
+
+        // Contents win over the extension, unless they're not recognised.
+        assert_eq!(FileKind::Pdf, FileKind::detect("mislabelled.html", b"%PDF-1.4"));
+        assert_eq!(FileKind::Html, FileKind::detect("work.html.gz", b"\x1f\x8b\x08"));
+    }
+}
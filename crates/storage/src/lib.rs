@@ -1,10 +1,21 @@
 pub mod backend;
+pub mod chunk;
+mod contents;
 pub mod error;
 pub mod file;
+mod kind;
+mod mode;
 mod path;
+mod size;
+mod tags;
 
 use crate::backend::StorageBackend;
+pub use crate::contents::Contents;
+pub use crate::kind::FileKind;
+pub use crate::mode::Mode;
 pub use crate::path::ValidatedPath;
+pub use crate::size::ByteSize;
+pub use crate::tags::{MAX_TAG_KEY_LEN, MAX_TAG_VALUE_LEN, MAX_TAGS, ObjectTags};
 use std::sync::Arc;
 
 pub type BackendHandle = Arc<dyn StorageBackend + Send + Sync>;
@@ -0,0 +1,55 @@
+//! Whether operations change anything, or only report what they would.
+
+use crate::BackendHandle;
+use crate::backend::DryRunBackend;
+use std::sync::Arc;
+
+/// Whether to make changes, or go through the motions of making them.
+///
+/// One switch for every layer that mutates something: storage backends
+/// (wrapped by [`backend()`](Self::backend)), the cache, and the library
+/// operations built on them. A dry run still reads, validates and logs each
+/// change it would make, and reports it as made, so running one against a
+/// real library shows what a real run would do without doing it.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_storage::Mode;
+///
+/// let mode = Mode::from(true);
+/// assert_eq!(Mode::DryRun, mode);
+/// assert!(mode.is_dry_run());
+/// assert!(!Mode::default().is_dry_run());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Changes are made.
+    #[default]
+    Real,
+    /// Changes are logged, not made.
+    DryRun,
+}
+impl Mode {
+    pub fn is_dry_run(self) -> bool {
+        self == Self::DryRun
+    }
+
+    /// The backend to make changes through: `backend` itself, or in a dry run
+    /// a [`DryRunBackend`] wrapping it.
+    pub fn backend(self, backend: BackendHandle) -> BackendHandle {
+        match self {
+            Self::Real => backend,
+            Self::DryRun => Arc::new(DryRunBackend::new(backend)),
+        }
+    }
+}
+impl From<bool> for Mode {
+    /// `true` for a dry run.
+    fn from(dry_run: bool) -> Self {
+        match dry_run {
+            true => Self::DryRun,
+            false => Self::Real,
+        }
+    }
+}
@@ -14,11 +14,6 @@ use std::ops::Deref;
 use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 
-// Internal boundary-conversation helper trait. Probably shouldn't
-// use this, and just be specific with my conversions.
-pub(crate) trait TryValidatePath: TryInto<ValidatedPath, Error = Error> {}
-impl<T: TryInto<ValidatedPath, Error = Error>> TryValidatePath for T {}
-
 /// Validates a storage path for security and correctness.
 /// Ensures that paths don't escape the storage root (no `..` traversal).
 ///
@@ -44,7 +39,7 @@ impl<T: TryInto<ValidatedPath, Error = Error>> TryValidatePath for T {}
 /// assert!(ValidatedPath::new("a\0b").is_err());
 /// // Paths get resolved
 /// assert_eq!(
-///     ValidatedPath::new("wrong/../still-wrong/.././correct//./path.html/").unwrap(),
+///     ValidatedPath::new("wrong/../still-wrong/.././correct//./path.html/").unwrap().as_str(),
 ///     "correct/path.html"
 /// );
 /// ```
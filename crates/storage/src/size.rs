@@ -0,0 +1,224 @@
+//! Sizes in bytes, formatted and parsed the way people write them.
+//!
+//! [`ByteSize`] displays with binary units (`1.5 MiB`), and parses what users
+//! put in config and on the command line for quotas and thresholds:
+//!
+//! | Input             | Bytes                  |
+//! |-------------------|------------------------|
+//! | `4096`, `4096B`   | 4096                   |
+//! | `2GiB`, `2 gib`   | 2 × 1024³              |
+//! | `2G`, `2g`        | 2 × 1024³              |
+//! | `2GB`, `2 gb`     | 2 × 1000³              |
+//! | `1.5KiB`          | 1536                   |
+//!
+//! Bare unit letters (`K`, `M`, `G`, `T`) are binary, as with `du` and
+//! `ulimit`; only the SI spellings (`kB`, `MB`, …) are powers of 1000.
+
+use crate::error::{Error, ErrorKind};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+use std::str::FromStr;
+
+const KIB: u64 = 1 << 10;
+const MIB: u64 = 1 << 20;
+const GIB: u64 = 1 << 30;
+const TIB: u64 = 1 << 40;
+
+/// Binary units to display sizes with, largest first.
+const UNITS: [(u64, &str); 4] = [(TIB, "TiB"), (GIB, "GiB"), (MIB, "MiB"), (KIB, "KiB")];
+
+/// A size in bytes.
+///
+/// Displays with the largest binary unit it's at least one of, to one
+/// decimal place (or the formatter's precision): `512 B`, `1.5 KiB`,
+/// `2.0 GiB`.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_storage::ByteSize;
+///
+/// let quota: ByteSize = "2GiB".parse().unwrap();
+/// assert_eq!(ByteSize::gib(2), quota);
+/// assert_eq!("2.0 GiB", quota.to_string());
+/// assert_eq!("1.46 KiB", format!("{:.2}", ByteSize(1500)));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+impl ByteSize {
+    pub const fn kib(n: u64) -> Self {
+        Self(n * KIB)
+    }
+
+    pub const fn mib(n: u64) -> Self {
+        Self(n * MIB)
+    }
+
+    pub const fn gib(n: u64) -> Self {
+        Self(n * GIB)
+    }
+
+    pub const fn tib(n: u64) -> Self {
+        Self(n * TIB)
+    }
+
+    /// The size in bytes.
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+impl From<u64> for ByteSize {
+    fn from(bytes: u64) -> Self {
+        Self(bytes)
+    }
+}
+impl From<ByteSize> for u64 {
+    fn from(size: ByteSize) -> Self {
+        size.0
+    }
+}
+impl Add for ByteSize {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self(self.0.saturating_add(other.0))
+    }
+}
+impl AddAssign for ByteSize {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+impl Sum for ByteSize {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), Add::add)
+    }
+}
+impl Display for ByteSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match UNITS.iter().find(|(unit, _)| self.0 >= *unit) {
+            Some((unit, name)) => {
+                let precision = f.precision().unwrap_or(1);
+                write!(f, "{:.precision$} {name}", self.0 as f64 / *unit as f64)
+            },
+            None => write!(f, "{} B", self.0),
+        }
+    }
+}
+impl FromStr for ByteSize {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ErrorKind::InvalidSize(s.to_string());
+        let trimmed = s.trim();
+        let split = trimmed.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(split);
+        let multiplier = match unit.trim_start().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kib" => KIB,
+            "m" | "mib" => MIB,
+            "g" | "gib" => GIB,
+            "t" | "tib" => TIB,
+            "kb" => 1_000,
+            "mb" => 1_000_000,
+            "gb" => 1_000_000_000,
+            "tb" => 1_000_000_000_000,
+            _ => exn::bail!(invalid()),
+        };
+        // Whole numbers are parsed exactly; only fractions go through floats.
+        if let Ok(whole) = number.parse::<u64>() {
+            return whole.checked_mul(multiplier).map(Self).ok_or_else(|| exn::Exn::new(invalid()));
+        }
+        let fraction: f64 = number.parse().map_err(|_| exn::Exn::new(invalid()))?;
+        let bytes = (fraction * multiplier as f64).round();
+        if !bytes.is_finite() || bytes >= u64::MAX as f64 {
+            exn::bail!(invalid());
+        }
+        Ok(Self(bytes as u64))
+    }
+}
+#[cfg(feature = "serde")]
+impl serde::Serialize for ByteSize {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ByteSize {
+    /// Accepts a number of bytes, or a string such as `"2GiB"`.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = ByteSize;
+            fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+                f.write_str("a size in bytes, such as 4096 or \"2GiB\"")
+            }
+            fn visit_u64<E: serde::de::Error>(self, bytes: u64) -> Result<ByteSize, E> {
+                Ok(ByteSize(bytes))
+            }
+            fn visit_i64<E: serde::de::Error>(self, bytes: i64) -> Result<ByteSize, E> {
+                u64::try_from(bytes)
+                    .map(ByteSize)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Signed(bytes), &self))
+            }
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<ByteSize, E> {
+                s.parse().map_err(|_| E::invalid_value(serde::de::Unexpected::Str(s), &self))
+            }
+        }
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("4096", 4096)]
+    #[case("4096B", 4096)]
+    #[case(" 4096 b ", 4096)]
+    #[case("2GiB", 2 * GIB)]
+    #[case("2 gib", 2 * GIB)]
+    #[case("2G", 2 * GIB)]
+    #[case("2GB", 2_000_000_000)]
+    #[case("512k", 512 * KIB)]
+    #[case("1.5KiB", 1536)]
+    #[case("0.5 MB", 500_000)]
+    #[case("16MiB", 16 * MIB)]
+    #[case("1TiB", TIB)]
+    fn test_parse(#[case] input: &str, #[case] bytes: u64) {
+        assert_eq!(ByteSize(bytes), input.parse::<ByteSize>().unwrap());
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("GiB")]
+    #[case("-1KiB")]
+    #[case("2 gigabytes")]
+    #[case("1.2.3MB")]
+    #[case("99999999999TiB")]
+    fn test_parse_invalid(#[case] input: &str) {
+        let err = input.parse::<ByteSize>().unwrap_err();
+        assert!(matches!(&*err, ErrorKind::InvalidSize(s) if s == input));
+    }
+
+    #[rstest]
+    #[case(0, "0 B")]
+    #[case(1023, "1023 B")]
+    #[case(1024, "1.0 KiB")]
+    #[case(1536, "1.5 KiB")]
+    #[case(16 * MIB, "16.0 MiB")]
+    #[case(2 * GIB + GIB / 4, "2.2 GiB")]
+    #[case(3 * TIB, "3.0 TiB")]
+    fn test_display(#[case] bytes: u64, #[case] expected: &str) {
+        assert_eq!(expected, ByteSize(bytes).to_string());
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for size in [ByteSize::kib(3), ByteSize::mib(700), ByteSize::gib(2)] {
+            assert_eq!(size, size.to_string().parse().unwrap());
+        }
+        assert_eq!(ByteSize(6144), [ByteSize::kib(2), ByteSize(4096)].into_iter().sum());
+    }
+}
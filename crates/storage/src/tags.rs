@@ -0,0 +1,143 @@
+//! Object tags: key-value labels attached to files in object storage.
+//!
+//! S3 tags can be matched by bucket lifecycle rules, access policies and
+//! external tools, unlike object metadata. S3 only accepts a few tags per
+//! object, of limited length and made of a limited set of characters, so
+//! [`ObjectTags`] keeps within those constraints.
+
+use crate::error::{ErrorKind, Result};
+
+/// Most tags S3 accepts on an object.
+pub const MAX_TAGS: usize = 10;
+/// Longest tag key (in characters) S3 accepts.
+pub const MAX_TAG_KEY_LEN: usize = 128;
+/// Longest tag value (in characters) S3 accepts.
+pub const MAX_TAG_VALUE_LEN: usize = 256;
+
+/// A set of object tags, within S3's constraints.
+///
+/// Tags are kept in the order they were inserted.
+///
+/// # Examples
+///
+/// ```
+/// use rawr_storage::ObjectTags;
+///
+/// let mut tags = ObjectTags::new();
+/// tags.insert("work_id", "12345").unwrap();
+/// tags.insert("fandom", "Harry Potter - J. K. Rowling").unwrap();
+/// assert_eq!(Some("Harry Potter - J. K. Rowling"), tags.get("fandom"));
+/// assert!(tags.insert("aws:reserved", "no").is_err());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectTags(Vec<(String, String)>);
+impl ObjectTags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the tag `key` to `value`, replacing any value it had.
+    ///
+    /// Characters S3 doesn't allow in values are replaced with `_`, and
+    /// values longer than [`MAX_TAG_VALUE_LEN`] are truncated. Returns
+    /// [`ErrorKind::InvalidTag`] for keys that S3 wouldn't accept (empty,
+    /// too long, reserved by AWS or with characters it doesn't allow), or a
+    /// new key beyond [`MAX_TAGS`].
+    pub fn insert(&mut self, key: impl Into<String>, value: impl AsRef<str>) -> Result<()> {
+        let key = key.into();
+        if key.is_empty()
+            || key.chars().count() > MAX_TAG_KEY_LEN
+            || key.to_ascii_lowercase().starts_with("aws:")
+            || !key.chars().all(is_allowed)
+        {
+            exn::bail!(ErrorKind::InvalidTag(key));
+        }
+        let value: String =
+            value.as_ref().chars().map(|c| if is_allowed(c) { c } else { '_' }).take(MAX_TAG_VALUE_LEN).collect();
+        match self.0.iter().position(|(k, _)| *k == key) {
+            Some(i) => self.0[i].1 = value,
+            None if self.0.len() >= MAX_TAGS => exn::bail!(ErrorKind::InvalidTag(key)),
+            None => self.0.push((key, value)),
+        }
+        Ok(())
+    }
+
+    /// The value of the tag `key`, if set.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The tags as the body of an S3 `PutObjectTagging` request.
+    #[cfg(feature = "s3")]
+    pub(crate) fn to_xml(&self) -> String {
+        let mut xml = String::from("<Tagging><TagSet>");
+        for (key, value) in self.iter() {
+            xml.push_str(&format!("<Tag><Key>{}</Key><Value>{}</Value></Tag>", escape(key), escape(value)));
+        }
+        xml.push_str("</TagSet></Tagging>");
+        xml
+    }
+}
+
+/// Whether S3 allows `c` in tag keys and values: letters, numbers, spaces,
+/// and `+ - = . _ : / @`.
+fn is_allowed(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, ' ' | '+' | '-' | '=' | '.' | '_' | ':' | '/' | '@')
+}
+
+/// Escapes the characters XML doesn't allow in text (though [`is_allowed`]
+/// already leaves none of them).
+#[cfg(feature = "s3")]
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::empty("")]
+    #[case::reserved("AWS:created")]
+    #[case::too_long(&"k".repeat(MAX_TAG_KEY_LEN + 1))]
+    #[case::disallowed_character("fandom#1")]
+    fn test_invalid_key(#[case] key: &str) {
+        let error = ObjectTags::new().insert(key, "value").unwrap_err();
+        assert!(matches!(&*error, ErrorKind::InvalidTag(_)));
+    }
+
+    #[test]
+    fn test_values() {
+        let mut tags = ObjectTags::new();
+        tags.insert("fandom", "Sherlock (TV) & Friends!").unwrap();
+        assert_eq!(Some("Sherlock _TV_ _ Friends_"), tags.get("fandom"));
+        tags.insert("fandom", "é".repeat(300)).unwrap();
+        assert_eq!(MAX_TAG_VALUE_LEN, tags.get("fandom").unwrap().chars().count());
+        assert_eq!(1, tags.len());
+    }
+
+    #[test]
+    fn test_too_many() {
+        let mut tags = ObjectTags::new();
+        for i in 0..MAX_TAGS {
+            tags.insert(format!("tag{i}"), "value").unwrap();
+        }
+        assert!(tags.insert("one_more", "value").is_err());
+        // Replacing a tag doesn't add one.
+        tags.insert("tag0", "replaced").unwrap();
+        assert_eq!(MAX_TAGS, tags.len());
+    }
+}